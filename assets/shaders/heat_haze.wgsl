@@ -0,0 +1,75 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct Haze {
+    time: f32,
+    // texels of the screen per pixel of the world
+    pixel_size: f32,
+    // middle of the screen in pixels of the world
+    center: vec2<f32>,
+}
+
+@group(0) @binding(0) var screen: texture_2d<f32>;
+@group(0) @binding(1) var heat_map: texture_2d<f32>;
+@group(0) @binding(2) var ts: sampler;
+@group(0) @binding(3) var<uniform> haze: Haze;
+
+// furthest the view is shifted in pixels of the world over the hottest air
+const max_shift = 1.5;
+const smoke_color = vec3<f32>(0.32, 0.3, 0.29);
+const smoke_opacity = 0.6;
+// pixels of the world per second smoke drifts up with
+const smoke_speed = 12.0;
+
+// heat map is stored as srgb, channels are encoded back to the bytes that were written
+fn decode(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        return value * 12.92;
+    }
+
+    return 1.055 * pow(value, 1.0 / 2.4) - 0.055;
+}
+
+fn hash(point: vec2<f32>) -> f32 {
+    return fract(sin(dot(point, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+fn noise(point: vec2<f32>) -> f32 {
+    let cell = floor(point);
+    let local = smoothstep(vec2<f32>(0.0), vec2<f32>(1.0), fract(point));
+
+    return mix(
+        mix(hash(cell), hash(cell + vec2<f32>(1.0, 0.0)), local.x),
+        mix(hash(cell + vec2<f32>(0.0, 1.0)), hash(cell + vec2<f32>(1.0, 1.0)), local.x),
+        local.y
+    );
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let dimensions = vec2<f32>(textureDimensions(screen));
+    let heat_sample = textureSampleLevel(heat_map, ts, in.uv, 0.0);
+
+    var output = textureSampleLevel(screen, ts, in.uv, 0.0);
+
+    if heat_sample.a <= 0.0 {
+        return output;
+    }
+
+    let heat = decode(heat_sample.r);
+    let smoke = decode(heat_sample.g);
+    let world = haze.center + (in.uv - 0.5) * vec2<f32>(1.0, -1.0) * dimensions / haze.pixel_size;
+
+    // rising air bends the view sideways, snapped to texels so pixels stay sharp
+    let shift = sin(world.y * 0.35 - haze.time * 6.0 + sin(world.x * 0.1)) * heat * max_shift;
+    let texel = floor(in.uv * dimensions + vec2<f32>(shift * haze.pixel_size, 0.0)) + 0.5;
+    let shifted = textureSampleLevel(screen, ts, texel / dimensions, 0.0);
+
+    output = vec4<f32>(shifted.rgb, output.a);
+
+    // plumes are broken up by noise drifting up with the world
+    let drift = world / 8.0 - vec2<f32>(0.0, haze.time * smoke_speed / 8.0);
+    let density = noise(drift) * 0.6 + noise(drift * 2.3) * 0.4;
+    let amount = clamp(smoke * density * smoke_opacity, 0.0, 1.0);
+
+    return vec4<f32>(mix(output.rgb, smoke_color, amount), output.a);
+}
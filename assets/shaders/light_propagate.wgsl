@@ -4,6 +4,7 @@
 @group(0) @binding(1) var ts: sampler;
 @group(0) @binding(2) var<uniform> step_offset: f32;
 @group(0) @binding(3) var<uniform> shadow: vec3<f32>;
+@group(0) @binding(4) var<uniform> falloff: f32;
 
 const damp_factor = 0.7;
 
@@ -18,6 +19,7 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
     color += textureSample(t, ts, in.uv + vec2<f32>(-step_offset, -step_offset) / dimensions).rgb;
 
     color /= 4.0;
+    color = pow(color, vec3<f32>(falloff));
 
     color = max(color, shadow);
 
@@ -0,0 +1,35 @@
+#import bevy_sprite::mesh2d_view_bindings::view
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) index: u32,
+    // center in xy and size in zw
+    @location(0) quad: vec4<f32>,
+    @location(1) color: vec4<f32>,
+) -> VertexOutput {
+    // two triangles covering the quad of the particle
+    var corners = array<vec2<f32>, 6>(
+        vec2(-0.5, -0.5),
+        vec2(0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, 0.5),
+    );
+
+    var out: VertexOutput;
+    out.position = view.view_proj * vec4(quad.xy + corners[index] * quad.zw, 0.0, 1.0);
+    out.color = color;
+
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
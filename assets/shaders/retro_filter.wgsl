@@ -0,0 +1,91 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct Filter {
+    // strength of the crt look, share of the palette color and colors in the palette
+    crt: f32,
+    palette_intensity: f32,
+    color_count: f32,
+    _padding: f32,
+    // srgb colors, only the first color_count are used
+    palette: array<vec4<f32>, 16>,
+}
+
+@group(0) @binding(0) var screen: texture_2d<f32>;
+@group(0) @binding(1) var ts: sampler;
+@group(0) @binding(2) var<uniform> retro: Filter;
+
+// texels of the window every scanline takes
+const scanline_size = 3.0;
+const max_curvature = 0.12;
+const max_scanline_darkening = 0.4;
+// distance between palette colors dithering may cross
+const dither_spread = 0.12;
+
+const bayer = array<f32, 16>(
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0,
+);
+
+fn to_srgb(color: vec3<f32>) -> vec3<f32> {
+    return pow(max(color, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2));
+}
+
+fn to_linear(color: vec3<f32>) -> vec3<f32> {
+    return pow(max(color, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+fn closest_color(color: vec3<f32>) -> vec3<f32> {
+    var closest = retro.palette[0].rgb;
+    var closest_distance = distance(color, closest);
+
+    for (var index = 1u; index < u32(retro.color_count); index++) {
+        let candidate = retro.palette[index].rgb;
+        let candidate_distance = distance(color, candidate);
+
+        if candidate_distance < closest_distance {
+            closest = candidate;
+            closest_distance = candidate_distance;
+        }
+    }
+
+    return closest;
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let dimensions = vec2<f32>(textureDimensions(screen));
+
+    // screen bulges out towards the middle, corners past the edge of the tube are black
+    let centered = in.uv - 0.5;
+    let uv = in.uv + centered * dot(centered, centered) * max_curvature * retro.crt;
+
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let output = textureSampleLevel(screen, ts, uv, 0.0);
+    var color = output.rgb;
+
+    if retro.color_count > 0.0 && retro.palette_intensity > 0.0 {
+        // constant arrays can only be indexed dynamically through a variable
+        var dither = bayer;
+        let texel = vec2<u32>(in.position.xy) % 4u;
+        let threshold = dither[texel.y * 4u + texel.x] / 16.0 - 0.5;
+
+        let srgb = to_srgb(color);
+        let quantized = closest_color(srgb + threshold * dither_spread);
+
+        color = to_linear(mix(srgb, quantized, retro.palette_intensity));
+    }
+
+    if retro.crt > 0.0 {
+        let scanline = 0.5 + 0.5 * cos(uv.y * dimensions.y * 6.2831853 / scanline_size);
+        let vignette = 1.0 - dot(centered, centered) * retro.crt;
+
+        color *= (1.0 - scanline * max_scanline_darkening * retro.crt) * vignette;
+    }
+
+    return vec4<f32>(color, output.a);
+}
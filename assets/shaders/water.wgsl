@@ -0,0 +1,74 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct WaterStyle {
+    deep_color: vec4<f32>,
+    // amplitude in pixels, frequency and shimmer
+    wave: vec4<f32>,
+}
+
+struct Water {
+    time: f32,
+    // texels of the screen per pixel of the world
+    pixel_size: f32,
+    // middle of the screen in pixels of the world
+    center: vec2<f32>,
+    styles: array<WaterStyle, 16>,
+}
+
+@group(0) @binding(0) var screen: texture_2d<f32>;
+@group(0) @binding(1) var mask: texture_2d<f32>;
+@group(0) @binding(2) var ts: sampler;
+@group(0) @binding(3) var<uniform> water: Water;
+
+// same as the depth the liquid layer of chunks is encoded with
+const max_depth = 32.0;
+
+// mask is stored as srgb, channels are encoded back to the bytes that were written
+fn decode(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        return value * 12.92;
+    }
+
+    return 1.055 * pow(value, 1.0 / 2.4) - 0.055;
+}
+
+fn load_mask(uv: vec2<f32>, dimensions: vec2<f32>) -> vec4<f32> {
+    let texel = clamp(vec2<i32>(uv * dimensions), vec2<i32>(0), vec2<i32>(dimensions) - 1);
+
+    return textureLoad(mask, texel, 0);
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let dimensions = vec2<f32>(textureDimensions(screen));
+    let liquid = load_mask(in.uv, dimensions);
+
+    var output = textureSample(screen, ts, in.uv);
+
+    if liquid.a < 0.5 {
+        return output;
+    }
+
+    let depth = decode(liquid.r) * max_depth;
+    let style = water.styles[u32(round(decode(liquid.g) * 255.0 / 16.0))];
+    let world = floor(water.center + (in.uv - 0.5) * vec2<f32>(1.0, -1.0) * dimensions / water.pixel_size);
+
+    // pixels under the surface sway sideways, the surface itself stays in place
+    let shift = sin(world.y * style.wave.y + water.time * 2.0) * style.wave.x * min(depth / 4.0, 1.0);
+    let shifted_uv = in.uv + vec2<f32>(round(shift) * water.pixel_size / dimensions.x, 0.0);
+
+    if load_mask(shifted_uv, dimensions).a > 0.5 {
+        output = textureSampleLevel(screen, ts, shifted_uv, 0.0);
+    }
+
+    let deep = min(depth / max_depth, 1.0) * style.deep_color.a;
+    var color = mix(output.rgb, style.deep_color.rgb, deep);
+
+    // glints running along the top rows
+    if depth < 2.0 {
+        let glint = max(sin(world.x * 0.7 + water.time * 3.0) * sin(world.x * 0.23 - water.time * 1.7), 0.0);
+        color += pow(glint, 8.0) * style.wave.z;
+    }
+
+    return vec4<f32>(color, output.a);
+}
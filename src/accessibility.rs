@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy_persistent::Persistent;
+
+use crate::{
+    actors::{ actor::Actor, effects::Death, enemy::Enemy },
+    constants::CHUNK_SIZE,
+    registries::{ update_material_palette, Registries },
+    settings::Config,
+    simulation::object::{ Object, Projectile },
+    state::GameState,
+};
+
+const ENEMY_OUTLINE: Color = Color::rgb(0.94, 0.89, 0.26);
+const PROJECTILE_OUTLINE: Color = Color::rgb(1.0, 1.0, 1.0);
+// gap in pixels between the outline and the outlined sprite
+const OUTLINE_PADDING: f32 = 2.0;
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_material_palette.run_if(resource_exists::<Registries>))
+            .add_systems(
+                PostUpdate,
+                draw_high_contrast_outlines.run_if(
+                    in_state(GameState::Game).and_then(|config: Res<Persistent<Config>>| {
+                        config.high_contrast
+                    })
+                )
+            );
+    }
+}
+
+fn draw_high_contrast_outlines(
+    mut gizmos: Gizmos,
+    enemy_q: Query<&Actor, (With<Enemy>, Without<Death>)>,
+    projectile_q: Query<(&Transform, &Object), With<Projectile>>
+) {
+    let padding = Vec2::splat(OUTLINE_PADDING * 2.0);
+
+    for actor in enemy_q.iter() {
+        gizmos.rect_2d(
+            (actor.position + actor.size / 2.0) / (CHUNK_SIZE as f32),
+            0.0,
+            (actor.size + padding) / (CHUNK_SIZE as f32),
+            ENEMY_OUTLINE
+        );
+    }
+
+    for (transform, object) in projectile_q.iter() {
+        let (axis, angle) = transform.rotation.to_axis_angle();
+
+        gizmos.rect_2d(
+            transform.translation.xy(),
+            angle * axis.z,
+            (object.size.as_vec2() + padding) / (CHUNK_SIZE as f32),
+            PROJECTILE_OUTLINE
+        );
+    }
+}
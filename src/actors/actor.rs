@@ -13,6 +13,7 @@ use bevy_rapier2d::{
         Velocity,
     },
     geometry::{ Collider, ColliderMassProperties, CollisionGroups, Group, Sensor },
+    plugin::{ RapierConfiguration, RapierContext },
 };
 use itertools::Itertools;
 
@@ -25,14 +26,49 @@ use crate::{
         colliders::{ ACTOR_MASK, HITBOX_MASK, OBJECT_MASK },
         dirty_rect::DirtyRects,
         materials::{ ContactEffect, PhysicsType },
-        particle::{ Particle, ParticleBundle },
+        object::{ Object, Projectile },
+        particle::{ Particle, ParticleBundle, Particles },
         pixel::Pixel,
     },
+    vehicle::Piloting,
 };
 
 use bitflags::bitflags;
 
-use super::health::{ DamageEvent, Health };
+use super::{
+    effects::{
+        Breath,
+        Burning,
+        Wetness,
+        WETNESS_DECAY,
+        WETNESS_DRIP_CHANCE,
+        WETNESS_DRIP_COST,
+        WETNESS_GAIN,
+        WETNESS_HEAT_DECAY,
+    },
+    health::{ DamageCause, DamageEvent, DamageType, Health, Resistances },
+};
+
+// distance in pixels at which heat sources dry actors off
+const HEAT_RADIUS: i32 = 4;
+// share of the body that has to be in liquid for the actor to swim
+const SWIM_THRESHOLD: f32 = 0.4;
+// rows of pixels at the top of the body that count as the head
+const HEAD_ROWS: i32 = 4;
+// density of liquid that keeps a fully submerged actor from sinking or floating up
+const ACTOR_DENSITY: f32 = 20.0;
+const MAX_LIFT: f32 = 1.5;
+// flow rate of water, liquids that flow slower are more viscous
+const WATER_FLOW_RATE: f32 = 4.0;
+// share of velocity lost every 60th of a second, viscous liquids add up to the other one
+const WATER_DRAG: f32 = 0.04;
+const VISCOUS_DRAG: f32 = 0.25;
+// actors keep at least this share of their speed however much powder they push through
+const MIN_DISPLACEMENT_SPEED: f32 = 0.5;
+// contacts with a normal pointing down steeper than this are objects the actor stands on
+const PLATFORM_NORMAL: f32 = 0.7;
+// actors and objects closing in slower than this in pixels per frame only block each other
+const MIN_PUSH_SPEED: f32 = 0.5;
 
 #[derive(Bundle, Clone)]
 pub struct ActorBundle {
@@ -51,6 +87,8 @@ pub struct ActorBundle {
     pub damping: Damping,
     pub impulse: ExternalImpulse,
     pub gravity: GravityScale,
+    pub wetness: Wetness,
+    pub breath: Breath,
 }
 
 #[derive(Bundle, Clone)]
@@ -91,6 +129,8 @@ impl Default for ActorBundle {
             damping: Damping::default(),
             impulse: ExternalImpulse::default(),
             gravity: GravityScale(3.0),
+            wetness: Wetness::default(),
+            breath: Breath::default(),
         }
     }
 }
@@ -115,6 +155,10 @@ bitflags! {
         const GROUNDED = 1 << 0;
         const SUBMERGED = 1 << 1;
         const INFLUENCED = 1 << 2;
+        /// Enough of the body is in liquid to swim
+        const SWIMMING = 1 << 3;
+        /// Head is in liquid, breath is being held
+        const UNDERWATER = 1 << 4;
     }
 }
 
@@ -125,6 +169,8 @@ pub struct Actor {
     pub movement_type: MovementType,
     #[reflect(ignore)]
     pub flags: ActorFlags,
+    /// Velocity of the object the actor stands on, it is moved along on top of its own velocity
+    pub carried: Vec2,
 }
 
 // since rapier automatically manages transforms it is required to manually store it
@@ -149,16 +195,32 @@ pub fn update_actor_translation(mut actor_q: Query<(&mut Transform, &Actor)>) {
 }
 
 /// based on this [article](http://higherorderfun.com/blog/2012/05/20/the-guide-to-implementing-2d-platformers/)
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn update_actors(
     mut commands: Commands,
-    mut actor_q: Query<(Entity, &mut Actor, &mut Velocity, &mut Health, &mut ExternalImpulse)>,
+    mut particles: Particles,
+    mut actor_q: Query<
+        (
+            Entity,
+            &mut Actor,
+            &mut Velocity,
+            &mut Health,
+            &mut ExternalImpulse,
+            &mut Wetness,
+            &GravityScale,
+            Option<&mut Burning>,
+        ),
+        // pilots are moved along with their vehicles instead
+        Without<Piloting>
+    >,
     mut dirty_rects: ResMut<DirtyRects>,
     mut chunk_manager: ResMut<ChunkManager>,
     mut damage_ev: EventWriter<DamageEvent>,
+    rapier_config: Res<RapierConfiguration>,
     time: Res<Time>
 ) {
     let mut spawn_particle = |pixel: Pixel, position: Vec2, transferred_velocity: Vec2| {
-        commands.spawn(ParticleBundle {
+        particles.spawn(ParticleBundle {
             sprite: SpriteBundle {
                 sprite: Sprite {
                     color: Color::rgba_u8(
@@ -186,7 +248,18 @@ pub fn update_actors(
         dirty_rects.request_render(position.as_ivec2());
     };
 
-    for (entity, mut actor, mut velocity, mut health, mut impulse) in actor_q.iter_mut() {
+    let mut ignited = vec![];
+
+    for (
+        entity,
+        mut actor,
+        mut velocity,
+        mut health,
+        mut impulse,
+        mut wetness,
+        gravity_scale,
+        burning,
+    ) in actor_q.iter_mut() {
         let chunk_position = actor.position
             .round()
             .as_ivec2()
@@ -202,6 +275,18 @@ pub fn update_actors(
         let delta = time.delta_seconds() * 60.0;
 
         let mut in_liquid = false;
+        let mut liquid_pixels = 0;
+        let mut head_liquid_pixels = 0;
+        let mut liquid_density = 0.0f32;
+        let mut liquid_flow_rate = WATER_FLOW_RATE;
+        let mut soaked_in = None;
+        let mut touching_heat = false;
+        // rows are relative to the chunk group, same as the positions below
+        let head_start =
+            actor.position.round().as_ivec2().y - chunk_position.y * CHUNK_SIZE +
+            (height as i32) -
+            HEAD_ROWS;
+
         if
             (0..width as i32)
                 .cartesian_product(0..height as i32)
@@ -231,13 +316,30 @@ pub fn update_actors(
                                 knockback: Vec2::ZERO,
                                 ignore_iframes: true,
                                 play_sound: false,
+                                cause: DamageCause::Material(pixel.material.ui_name.clone()),
+                                damage_type: DamageType::of_material(&pixel.material),
                             });
                             *pixel = Pixel::default();
                         }
                     }
 
-                    if matches!(pixel.physics_type, PhysicsType::Liquid(..)) {
+                    if let PhysicsType::Liquid(liquid) = pixel.physics_type {
                         in_liquid = true;
+                        liquid_pixels += 1;
+                        liquid_density = liquid_density.max(liquid.density as f32);
+                        liquid_flow_rate = liquid_flow_rate.min(liquid.flow_rate as f32);
+
+                        if position.y >= head_start {
+                            head_liquid_pixels += 1;
+                        }
+                    }
+
+                    if soaked_in.is_none() && pixel.material.tags.contains("wet") {
+                        soaked_in = Some(pixel.material.clone());
+                    }
+
+                    if pixel.on_fire || pixel.material.tags.contains("hot") {
+                        touching_heat = true;
                     }
 
                     matches!(pixel.physics_type, PhysicsType::Powder | PhysicsType::Static)
@@ -257,6 +359,8 @@ pub fn update_actors(
                 knockback: Vec2::ZERO,
                 ignore_iframes: false,
                 play_sound: true,
+                cause: DamageCause::Hazard("being buried"),
+                damage_type: DamageType::Physical,
             });
         }
 
@@ -267,15 +371,81 @@ pub fn update_actors(
             impulse.impulse.y -= change.y;
         }
 
+        let liquid_share = (liquid_pixels as f32) / ((width * height) as f32);
+        let head_share = (head_liquid_pixels as f32) / ((width as i32) * HEAD_ROWS.min(height as i32)) as f32;
+
+        actor.flags.set(ActorFlags::SWIMMING, liquid_share >= SWIM_THRESHOLD);
+        actor.flags.set(ActorFlags::UNDERWATER, head_share > 0.5);
+
+        if actor.flags.contains(ActorFlags::SWIMMING) {
+            // buoyancy cancels a part of gravity, dense liquids push actors up to the surface
+            let lift = (liquid_share * liquid_density / ACTOR_DENSITY).min(MAX_LIFT);
+            velocity.linvel.y -=
+                rapier_config.gravity.y * gravity_scale.0 * time.delta_seconds() * lift;
+
+            let viscosity = 1.0 - (liquid_flow_rate / WATER_FLOW_RATE).clamp(0.0, 1.0);
+            let drag = (WATER_DRAG + viscosity * VISCOUS_DRAG) * liquid_share;
+            velocity.linvel *= (1.0 - drag).powf(time.delta_seconds() * 60.0);
+        }
+
+        let near_heat =
+            touching_heat ||
+            (-HEAT_RADIUS..(width as i32) + HEAT_RADIUS)
+                .cartesian_product(-HEAT_RADIUS..(height as i32) + HEAT_RADIUS)
+                .any(|(x, y)| {
+                    chunk_group
+                        .get(
+                            actor.position.round().as_ivec2() +
+                                ivec2(x, y) -
+                                chunk_position * CHUNK_SIZE
+                        )
+                        .is_some_and(|pixel| pixel.on_fire || pixel.material.tags.contains("hot"))
+                });
+
+        if let Some(material) = soaked_in {
+            wetness.value = (wetness.value + WETNESS_GAIN * time.delta_seconds()).min(1.0);
+            wetness.material = Some(material);
+        } else {
+            let decay = if near_heat { WETNESS_HEAT_DECAY } else { WETNESS_DECAY };
+            wetness.value = (wetness.value - decay * time.delta_seconds()).max(0.0);
+
+            if
+                wetness.value > 0.2 &&
+                !in_liquid &&
+                fastrand::f32() < wetness.value * WETNESS_DRIP_CHANCE
+            {
+                if let Some(material) = &wetness.material {
+                    spawn_particle(
+                        Pixel::from(material),
+                        actor.position + vec2(fastrand::f32() * actor.size.x, -1.0),
+                        vec2(0.0, -1.0)
+                    );
+                }
+
+                wetness.value = (wetness.value - WETNESS_DRIP_COST).max(0.0);
+            }
+        }
+
+        if touching_heat && !wetness.is_wet() {
+            match burning {
+                Some(mut burning) => burning.rekindle(),
+                None => ignited.push(entity),
+            }
+        }
+
         {
-            let direction = velocity.linvel.x.signum() as i32;
+            let movement = velocity.linvel.x + actor.carried.x;
+            let direction = movement.signum() as i32;
             let initial_position = actor.position.round().as_ivec2();
             let velocity_offset = ivec2(if direction.is_positive() { width as i32 } else { -1 }, 0);
+            // powder is thrown up and behind the actor walking through it
+            let displaced_velocity = vec2(-velocity.linvel.x * 0.25, velocity.linvel.x.abs() * 0.5);
 
             let mut last_elevation = 0;
+            let mut displacement = 0.0;
 
             if
-                let Some(x) = (0..(velocity.linvel.x * delta).abs().ceil() as i32).find(|x| {
+                let Some(x) = (0..(movement * delta).abs().ceil() as i32).find(|x| {
                     // check whether there is pixels in the way by goind in two directions simotenously from center
                     let collision = (0..=(height / 2) as i32).rev().find_map(|y| {
                         let offset_bottom = ivec2(x * direction, y + last_elevation);
@@ -303,6 +473,7 @@ pub fn update_actors(
                                             return true;
                                         }
 
+                                        displacement += pixel.material.displacement_cost;
                                         spawn_particle(
                                             mem::take(pixel),
                                             (
@@ -310,7 +481,7 @@ pub fn update_actors(
                                                 velocity_offset +
                                                 offset_bottom
                                             ).as_vec2(),
-                                            velocity.linvel * 0.75
+                                            displaced_velocity
                                         );
 
                                         false
@@ -338,17 +509,17 @@ pub fn update_actors(
                                             return true;
                                         }
 
+                                        displacement += pixel.material.displacement_cost;
                                         spawn_particle(
                                             mem::take(pixel),
                                             (
                                                 initial_position +
                                                 velocity_offset +
-                                                offset_bottom
+                                                offset_top
                                             ).as_vec2(),
-                                            velocity.linvel * 0.75
+                                            displaced_velocity
                                         );
 
-                                        // velocity.linvel.x *= 0.97;
                                         false
                                     }
                                     _ => false,
@@ -411,22 +582,29 @@ pub fn update_actors(
                 actor.position.y += last_elevation as f32;
                 velocity.linvel.x *= 0.1;
             } else {
-                actor.position.x += velocity.linvel.x * delta;
+                actor.position.x += movement * delta;
                 actor.position.y += last_elevation as f32;
             }
+
+            velocity.linvel.x *= (1.0 - displacement).max(MIN_DISPLACEMENT_SPEED);
         }
 
         {
-            let direction = velocity.linvel.y.signum() as i32;
+            // objects going down leave the actor to gravity, their pixels are in the way until they are moved
+            let movement = velocity.linvel.y + actor.carried.y.max(0.0);
+            let direction = movement.signum() as i32;
             let initial_position = actor.position.round().as_ivec2();
             let velocity_offset = ivec2(0, if direction.is_positive() {
                 height as i32
             } else {
                 -1
             });
+            let center = (width as i32) / 2;
+
+            let mut displacement = 0.0;
 
             if
-                let Some(y) = (0..(velocity.linvel.y * delta).abs().ceil() as i32).find(|y| {
+                let Some(y) = (0..(movement * delta).abs().ceil() as i32).find(|y| {
                     (0..width as i32).any(|x| {
                         chunk_group
                             .get_mut(
@@ -451,6 +629,10 @@ pub fn update_actors(
                                             return true;
                                         }
 
+                                        // powder is pushed aside, away from the middle of the body
+                                        let side = if x < center { -1.0 } else { 1.0 };
+
+                                        displacement += pixel.material.displacement_cost;
                                         spawn_particle(
                                             mem::take(pixel),
                                             (
@@ -458,10 +640,12 @@ pub fn update_actors(
                                                 velocity_offset +
                                                 ivec2(x, y * direction)
                                             ).as_vec2(),
-                                            velocity.linvel * 0.75
+                                            vec2(
+                                                side * velocity.linvel.y.abs() * 0.5,
+                                                velocity.linvel.y.abs() * 0.25
+                                            )
                                         );
 
-                                        velocity.linvel.y *= 0.95;
                                         false
                                     }
                                     _ => false,
@@ -474,8 +658,10 @@ pub fn update_actors(
                 // actor.position = (start_position + ivec2(0, )).as_vec2();
                 velocity.linvel.y *= 0.25;
             } else {
-                actor.position.y += velocity.linvel.y * delta;
+                actor.position.y += movement * delta;
             }
+
+            velocity.linvel.y *= (1.0 - displacement).max(MIN_DISPLACEMENT_SPEED);
         }
 
         let position = actor.position.round().as_ivec2();
@@ -512,10 +698,118 @@ pub fn update_actors(
             }
         }
 
+        // soaked actors are slightly slower and heavier
+        if wetness.is_wet() {
+            velocity.linvel.x *= 1.0 - wetness.value * 0.05;
+
+            if !actor.flags.contains(ActorFlags::GROUNDED) {
+                velocity.linvel.y -= wetness.value * 0.01 * delta;
+            }
+        }
+
         if actor.flags.contains(ActorFlags::GROUNDED) {
             actor.flags.remove(ActorFlags::INFLUENCED);
         }
     }
+
+    for entity in ignited {
+        commands.entity(entity).insert(Burning::default());
+    }
+}
+
+/// Couples actors with the rigid bodies they touched on the last physics step,
+/// momentum along the contact is shared by mass, so heavy falling objects shove actors aside
+/// and actors running into light objects push them away, objects under an actor carry it along
+#[allow(clippy::type_complexity)]
+pub fn object_contacts(
+    rapier_context: Res<RapierContext>,
+    mut actor_q: Query<
+        (Entity, &mut Actor, &mut Velocity, &ReadMassProperties, Option<&Resistances>),
+        Without<Object>
+    >,
+    mut object_q: Query<
+        (&Transform, &Velocity, &ReadMassProperties, &mut ExternalImpulse),
+        (With<Object>, Without<Projectile>, Without<Actor>)
+    >
+) {
+    // rapier velocities are in world units per second, actors move in pixels per frame
+    let to_pixels = (CHUNK_SIZE as f32) / 60.0;
+
+    for (entity, mut actor, mut velocity, actor_mass, resistances) in actor_q.iter_mut() {
+        let center = (actor.position + actor.size / 2.0) / (CHUNK_SIZE as f32);
+        let mut carried = None;
+
+        for pair in rapier_context.contact_pairs_with(entity) {
+            if !pair.has_any_active_contacts() {
+                continue;
+            }
+
+            // normals point from the first collider of the pair to the second one
+            let (other, sign) = if pair.collider1() == entity {
+                (pair.collider2(), 1.0)
+            } else {
+                (pair.collider1(), -1.0)
+            };
+
+            let Ok((transform, object_velocity, object_mass, mut impulse)) = object_q.get_mut(other) else {
+                continue;
+            };
+
+            let Some(normal) = pair
+                .manifolds()
+                .map(|manifold| manifold.normal() * sign)
+                .find(|normal| *normal != Vec2::ZERO) else {
+                continue;
+            };
+
+            let object_velocity =
+                object_velocity.linear_velocity_at_point(center, transform.translation.xy()) * to_pixels;
+
+            if normal.y < -PLATFORM_NORMAL {
+                carried.get_or_insert(object_velocity);
+                continue;
+            }
+
+            // normal points from the actor to the object, so this is positive while they close in
+            let closing = (velocity.linvel - object_velocity).dot(normal);
+            let total_mass = actor_mass.get().mass + object_mass.get().mass;
+
+            if closing < MIN_PUSH_SPEED || total_mass <= 0.0 {
+                continue;
+            }
+
+            // both end up moving at the same speed along the normal, as in an inelastic collision
+            let mut knockback = -normal * closing * (object_mass.get().mass / total_mass);
+
+            // actors can not be pushed into the ground, they are squeezed out from under the object instead
+            if knockback.y < 0.0 && actor.flags.contains(ActorFlags::GROUNDED) {
+                let side = (center.x - transform.translation.x).signum();
+                knockback = vec2(side * knockback.length(), 0.0);
+            }
+
+            velocity.linvel += resistances.map_or(knockback, |resistances| {
+                resistances.knockback_taken(knockback)
+            });
+            impulse.impulse +=
+                normal * closing * (actor_mass.get().mass / total_mass) * object_mass.get().mass / to_pixels;
+
+            actor.flags.insert(ActorFlags::INFLUENCED);
+        }
+
+        match carried {
+            Some(carried) => {
+                actor.carried = carried;
+            }
+            None => {
+                // leaving a moving object in the air keeps its momentum
+                if !actor.flags.contains(ActorFlags::GROUNDED) {
+                    velocity.linvel += actor.carried;
+                }
+
+                actor.carried = Vec2::ZERO;
+            }
+        }
+    }
 }
 
 #[derive(Resource, Default, PartialEq)]
@@ -525,7 +819,7 @@ pub fn toggle_actors(
     mut ctx: ResMut<ActorDebugRender>,
     keys: Res<ButtonInput<KeyCode>>
 ) {
-    if keys.just_pressed(KeyCode::F3) {
+    if keys.just_pressed(KeyCode::F5) {
         ctx.0 = !ctx.0;
     }
 }
@@ -0,0 +1,320 @@
+use bevy::prelude::*;
+
+use crate::{
+    constants::CHUNK_SIZE,
+    raycast::raycast,
+    simulation::{ chunk_manager::ChunkManager, materials::PhysicsType, object::Projectile },
+};
+
+use super::{ actor::Actor, effects::Death, enemy::Enemy, spatial::SpatialIndex };
+
+// enemies notice players this close, paths are searched within the same distance
+pub const NOTICE_RANGE: i32 = CHUNK_SIZE * 2;
+// seconds an enemy keeps hunting after it last noticed a player or was called for help,
+// without sight of the player it searches around the last known position for the rest of it
+const ALERT_DURATION: f64 = 5.0;
+// searching enemies wander within this many pixels once they reach the last known position
+pub const SEARCH_RANGE: f32 = 24.0;
+
+// indicator is drawn above the health bar, offset is in pixels from the top of the enemy
+const INDICATOR_OFFSET: f32 = 10.0;
+const INDICATOR_FONT_SIZE: f32 = 16.0;
+const INDICATOR_SCALE: f32 = 0.375 / (CHUNK_SIZE as f32);
+const ALERTED_COLOR: Color = Color::rgb(1.0, 0.3, 0.2);
+const SEARCHING_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+// share of the usual speed enemies patrol with
+pub const PATROL_SPEED: f32 = 0.25;
+
+/// Part of the behavior of an enemy, every enemy type composes its own list in the registry
+#[derive(Clone)]
+pub enum Behavior {
+    /// Wanders around the spawn point, range is in pixels
+    Patrol {
+        range: f32,
+    },
+    /// Follows the path to the last known position of the target while alerted
+    Chase,
+    /// Runs away from players once health drops below the share of the total
+    Flee {
+        health: f32,
+    },
+    /// Backs away from players closer than the distance in pixels, pairs with `Shoot`
+    Kite {
+        distance: f32,
+    },
+    /// Alerts every enemy within the radius in pixels as soon as a player is noticed
+    CallForHelp {
+        radius: f32,
+    },
+    /// Throws balls of the material at players within the range in pixels
+    Shoot {
+        base_material: String,
+        cooldown: Timer,
+        projectile: Projectile,
+        speed: f32,
+        range: f32,
+    },
+    /// Digs through pixels up to the hardness once there is no open path to the target,
+    /// rate is in pixels per second
+    Dig {
+        hardness: f32,
+        rate: f32,
+        /// Pixels that can be removed so far
+        progress: f32,
+    },
+}
+
+/// What an enemy knows while picking how to move, distance is in pixels
+pub struct BehaviorContext {
+    pub alerted: bool,
+    pub distance: f32,
+    /// Share of the total health left
+    pub health: f32,
+}
+
+impl Behavior {
+    pub fn dig(hardness: f32, rate: f32) -> Self {
+        Behavior::Dig {
+            hardness,
+            rate,
+            progress: 0.0,
+        }
+    }
+
+    /// Only movement behaviors can be picked, the rest run alongside whichever is
+    fn is_active(&self, context: &BehaviorContext) -> bool {
+        match self {
+            Behavior::Patrol { .. } => true,
+            Behavior::Chase => context.alerted,
+            Behavior::Flee { health } => context.alerted && context.health < *health,
+            Behavior::Kite { distance } => context.alerted && context.distance < *distance,
+            Behavior::CallForHelp { .. } | Behavior::Shoot { .. } | Behavior::Dig { .. } => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Awareness {
+    Unaware,
+    /// Lost sight of the target and heads for the last known position
+    Searching,
+    Alerted,
+}
+
+/// Behaviors of an enemy ordered by priority, the first active movement behavior steers the enemy
+#[derive(Component, Clone)]
+pub struct Behaviors {
+    pub list: Vec<Behavior>,
+    /// Position in pixels patrols are centered on, set on the first update
+    pub home: Option<Vec2>,
+    pub alerted_at: Option<f64>,
+    /// Last known position of the target in pixels, heard noises and calls for help move it as well
+    pub target: Option<IVec2>,
+    pub sees_target: bool,
+    /// Direction walking enemies patrol in
+    pub patrol_direction: f32,
+}
+
+impl Behaviors {
+    pub fn new(list: Vec<Behavior>) -> Self {
+        Self {
+            list,
+            home: None,
+            alerted_at: None,
+            target: None,
+            sees_target: false,
+            patrol_direction: 1.0,
+        }
+    }
+
+    /// `now` is the elapsed time in seconds
+    pub fn is_alerted(&self, now: f64) -> bool {
+        self.alerted_at.is_some_and(|alerted_at| now - alerted_at < ALERT_DURATION)
+    }
+
+    pub fn alert(&mut self, now: f64) {
+        self.alerted_at = Some(now);
+    }
+
+    /// Target is in sight at the position
+    pub fn notice(&mut self, position: IVec2, now: f64) {
+        self.target = Some(position);
+        self.sees_target = true;
+        self.alert(now);
+    }
+
+    /// Something happened at the position, the enemy goes to look without seeing the target
+    pub fn hear(&mut self, position: IVec2, now: f64) {
+        self.target = Some(position);
+        self.alert(now);
+    }
+
+    pub fn awareness(&self, now: f64) -> Awareness {
+        if !self.is_alerted(now) {
+            Awareness::Unaware
+        } else if self.sees_target {
+            Awareness::Alerted
+        } else {
+            Awareness::Searching
+        }
+    }
+
+    /// Radius of the call made once the enemy is alerted
+    pub fn call_radius(&self) -> Option<f32> {
+        self.list.iter().find_map(|behavior| {
+            match behavior {
+                Behavior::CallForHelp { radius } => Some(*radius),
+                _ => None,
+            }
+        })
+    }
+
+    /// Hardest material the enemy can dig through, `None` if it does not dig
+    pub fn dig_hardness(&self) -> Option<f32> {
+        self.list.iter().find_map(|behavior| {
+            match behavior {
+                Behavior::Dig { hardness, .. } => Some(*hardness),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn movement(&self, context: &BehaviorContext) -> Option<&Behavior> {
+        self.list.iter().find(|behavior| behavior.is_active(context))
+    }
+}
+
+impl Default for Behaviors {
+    fn default() -> Self {
+        Self::new(vec![Behavior::Chase, Behavior::Patrol { range: 32.0 }])
+    }
+}
+
+/// Terrain blocks the sight of enemies, liquids, gases and objects do not, positions are in pixels
+pub fn has_line_of_sight(chunk_manager: &ChunkManager, from: IVec2, to: IVec2) -> bool {
+    raycast(from, to, chunk_manager, |pixel| {
+        !matches!(pixel.physics_type, PhysicsType::Static | PhysicsType::Powder)
+    }).is_none()
+}
+
+/// Loud sound enemies within the radius come to check, position and radius are in pixels
+#[derive(Event)]
+pub struct NoiseEvent {
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// Enemies that do not see a target already turn to the noise
+pub fn hear_noises(
+    mut noise_ev: EventReader<NoiseEvent>,
+    mut behaviors_q: Query<&mut Behaviors>,
+    index: Res<SpatialIndex>,
+    time: Res<Time>
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for ev in noise_ev.read() {
+        for (entity, _) in index.in_radius(ev.position, ev.radius) {
+            let Ok(mut behaviors) = behaviors_q.get_mut(entity) else {
+                continue;
+            };
+
+            if behaviors.awareness(now) == Awareness::Alerted {
+                continue;
+            }
+
+            behaviors.hear(ev.position.round().as_ivec2(), now);
+        }
+    }
+}
+
+/// World-space mark above an aware enemy, `!` while it sees the target and `?` while searching
+#[derive(Component)]
+pub struct AlertIndicator {
+    enemy: Entity,
+}
+
+/// Links an enemy to its alert indicator
+#[derive(Component)]
+pub struct EnemyAlertIndicator(Entity);
+
+fn indicator_translation(actor: &Actor) -> Vec3 {
+    let position = actor.position + Vec2::new(actor.size.x / 2.0, actor.size.y + INDICATOR_OFFSET);
+    (position / (CHUNK_SIZE as f32)).extend(10.0)
+}
+
+fn indicator_text(awareness: Awareness) -> Text {
+    let (value, color) = match awareness {
+        Awareness::Searching => ("?", SEARCHING_COLOR),
+        _ => ("!", ALERTED_COLOR),
+    };
+
+    Text::from_section(value, TextStyle {
+        font_size: INDICATOR_FONT_SIZE,
+        color,
+        ..default()
+    })
+}
+
+/// Spawns indicators for enemies that became aware and keeps them following the enemy,
+/// indicators are removed once the enemy dies or calms down
+#[allow(clippy::type_complexity)]
+pub fn update_alert_indicators(
+    mut commands: Commands,
+    enemy_q: Query<
+        (Entity, &Actor, &Behaviors, Option<&EnemyAlertIndicator>),
+        (With<Enemy>, Without<Death>)
+    >,
+    mut indicator_q: Query<(Entity, &AlertIndicator, &mut Text, &mut Transform)>,
+    time: Res<Time>
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (entity, actor, behaviors, indicator) in enemy_q.iter() {
+        let awareness = behaviors.awareness(now);
+
+        if awareness == Awareness::Unaware {
+            continue;
+        }
+
+        if let Some(indicator) = indicator {
+            if let Ok((.., mut text, mut transform)) = indicator_q.get_mut(indicator.0) {
+                *text = indicator_text(awareness);
+                transform.translation = indicator_translation(actor);
+            }
+            continue;
+        }
+
+        let indicator = commands
+            .spawn((
+                Name::new("Alert indicator"),
+                AlertIndicator { enemy: entity },
+                Text2dBundle {
+                    text: indicator_text(awareness),
+                    transform: Transform::from_translation(indicator_translation(actor)).with_scale(
+                        Vec3::splat(INDICATOR_SCALE)
+                    ),
+                    ..default()
+                },
+            ))
+            .id();
+
+        commands.entity(entity).insert(EnemyAlertIndicator(indicator));
+    }
+
+    for (entity, indicator, ..) in indicator_q.iter() {
+        let aware = enemy_q
+            .get(indicator.enemy)
+            .is_ok_and(|(_, _, behaviors, _)| behaviors.awareness(now) != Awareness::Unaware);
+
+        if aware {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+
+        if let Some(mut enemy) = commands.get_entity(indicator.enemy) {
+            enemy.remove::<EnemyAlertIndicator>();
+        }
+    }
+}
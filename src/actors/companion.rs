@@ -0,0 +1,356 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::{ dynamics::Velocity, geometry::{ ColliderMassProperties, Sensor } };
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    bucket::Carrying,
+    constants::CHUNK_SIZE,
+    notifications::{ Notification, NotificationKind },
+    raycast::raycast,
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        materials::{ Material, PhysicsType },
+        object::{ Object, ObjectBundle, Projectile, ProjectileOwner },
+        pixel::Pixel,
+    },
+};
+
+use super::{
+    actor::Actor,
+    effects::Death,
+    enemy::Enemy,
+    health::{ DamageCause, DamageEvent, DamageType, Health },
+    player::{ Player, PlayerActions, PlayerMaterials, PlayerSelectedMaterial },
+    spatial::SpatialIndex,
+};
+
+// amounts of the selected material spent on a summon
+const TURRET_COST: f32 = 64.0;
+const COMPANION_COST: f32 = 96.0;
+
+const TURRET_SIZE: i32 = 8;
+const TURRET_LIFETIME: Duration = Duration::from_secs(20);
+const TURRET_COOLDOWN: Duration = Duration::from_millis(1500);
+// distances are in pixels
+const TURRET_RANGE: f32 = 96.0;
+const TURRET_PROJECTILE_SIZE: i32 = 7;
+const TURRET_PROJECTILE_SPEED: f32 = 1.0;
+const TURRET_DAMAGE: f32 = 3.0;
+// pixels of terrain a turret shot drills through before it stops
+const TURRET_PIERCE: u32 = 6;
+const SUMMON_OFFSET: f32 = 16.0;
+
+// companion reuses the body of an enemy, tinted to tell it apart
+const COMPANION_BASE: &str = "bat";
+pub const COMPANION_TINT: Color = Color::rgb(0.6, 0.9, 1.0);
+const COMPANION_LIFETIME: Duration = Duration::from_secs(30);
+const COMPANION_HEALTH: f32 = 24.0;
+const COMPANION_RANGE: f32 = 128.0;
+const COMPANION_ATTACK_DISTANCE: f32 = 4.0;
+const COMPANION_ATTACK_COOLDOWN: Duration = Duration::from_millis(800);
+const COMPANION_DAMAGE: f32 = 4.0;
+// companions without a target hover this far above their owner
+const FOLLOW_OFFSET: Vec2 = Vec2::new(0.0, 24.0);
+
+/// Turret or companion placed by a player, removed once its time runs out
+#[derive(Component)]
+pub struct Summon {
+    pub owner: Entity,
+    pub lifetime: Timer,
+}
+
+/// Object shooting balls of the material it was built from, it falls apart once it loses too many pixels
+#[derive(Component)]
+pub struct Turret {
+    pub material: String,
+    pub cooldown: Timer,
+}
+
+/// Floating actor that chases enemies near its owner
+#[derive(Component)]
+pub struct Companion {
+    pub cooldown: Timer,
+}
+
+fn material_ball(material: &Material, size: i32) -> Vec<Option<Pixel>> {
+    (0..size)
+        .cartesian_product(0..size)
+        .map(|(y, x)| {
+            let offset = IVec2::new(x, y).as_vec2() + 0.5 - (size as f32) / 2.0;
+            (offset.length_squared() <= ((size as f32) / 2.0).powi(2)).then(|| Pixel::from(material))
+        })
+        .collect_vec()
+}
+
+/// Summoning builds a turret from the selected material, or a companion while crouching
+#[allow(clippy::type_complexity)]
+pub fn player_summon(
+    mut commands: Commands,
+    player_q: Query<(Entity, &Transform, &ActionState<PlayerActions>), (With<Player>, Without<Carrying>)>,
+    mut player_materials: ResMut<PlayerMaterials>,
+    selected_material: Res<PlayerSelectedMaterial>,
+    registries: Res<Registries>,
+    mut notifications: EventWriter<Notification>
+) {
+    for (entity, transform, action_state) in player_q.iter() {
+        if !action_state.just_pressed(&PlayerActions::Summon) {
+            continue;
+        }
+
+        let is_companion = action_state.pressed(&PlayerActions::Crouch);
+        let cost = if is_companion { COMPANION_COST } else { TURRET_COST };
+
+        let Some(material) = registries.materials.get(&selected_material.0) else {
+            continue;
+        };
+
+        let stored = player_materials.get(&selected_material.0).copied().unwrap_or(0.0);
+
+        if stored < cost {
+            notifications.send(
+                Notification::new(format!("Not enough {} to summon", material.ui_name)).with_kind(
+                    NotificationKind::Warning
+                )
+            );
+            continue;
+        }
+
+        *player_materials.entry(selected_material.0.clone()).or_insert(0.0) -= cost;
+
+        let facing = (transform.rotation.y + 0.5) * 2.0;
+        let position =
+            transform.translation.xy() + Vec2::new(facing * SUMMON_OFFSET, 0.0) / (CHUNK_SIZE as f32);
+
+        let summon = Summon {
+            owner: entity,
+            lifetime: Timer::new(
+                if is_companion { COMPANION_LIFETIME } else { TURRET_LIFETIME },
+                TimerMode::Once
+            ),
+        };
+
+        if is_companion {
+            let Some(enemy_fn) = registries.enemies.get(COMPANION_BASE) else {
+                continue;
+            };
+
+            let (base, _) = enemy_fn(position);
+            let mut actor = base.actor;
+
+            actor.health = Health {
+                current: COMPANION_HEALTH,
+                total: COMPANION_HEALTH,
+            };
+            actor.sprite.sprite.color = COMPANION_TINT;
+
+            commands.spawn((
+                Name::new("Companion"),
+                summon,
+                Companion {
+                    cooldown: Timer::new(COMPANION_ATTACK_COOLDOWN, TimerMode::Once),
+                },
+                actor,
+                base.animation_state,
+                base.state_machine,
+                base.idle,
+            ));
+            continue;
+        }
+
+        let Ok(object) = Object::from_pixels(
+            material_ball(material, TURRET_SIZE),
+            IVec2::splat(TURRET_SIZE)
+        ) else {
+            continue;
+        };
+        let Ok(collider) = object.create_collider() else {
+            continue;
+        };
+
+        commands.spawn((
+            Name::new("Turret"),
+            summon,
+            Turret {
+                material: material.id.clone(),
+                cooldown: Timer::new(TURRET_COOLDOWN, TimerMode::Once),
+            },
+            ObjectBundle {
+                object,
+                collider,
+                transform: TransformBundle {
+                    local: Transform::from_translation(position.extend(0.0)),
+                    ..Default::default()
+                },
+                mass_properties: ColliderMassProperties::Density(4.0),
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+/// Turrets shoot at the closest enemy they can see
+#[allow(clippy::type_complexity)]
+pub fn turret_update(
+    mut commands: Commands,
+    mut turret_q: Query<(Entity, &Transform, &mut Turret, &mut Summon)>,
+    chunk_manager: Res<ChunkManager>,
+    index: Res<SpatialIndex>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    for (entity, transform, mut turret, mut summon) in turret_q.iter_mut() {
+        if summon.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        if !turret.cooldown.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let position = transform.translation.xy() * (CHUNK_SIZE as f32);
+
+        // pixels of the turret and other objects do not block its sight
+        let visible = |target: Vec2| {
+            raycast(
+                position.round().as_ivec2(),
+                target.round().as_ivec2(),
+                &chunk_manager,
+                |pixel| pixel.is_empty() || matches!(pixel.physics_type, PhysicsType::Rigidbody(_))
+            ).is_none()
+        };
+
+        let Some((_, target)) = index.nearest(position, TURRET_RANGE, |_, target| {
+            visible(target)
+        }) else {
+            continue;
+        };
+
+        let Some(material) = registries.materials.get(&turret.material) else {
+            continue;
+        };
+
+        let Ok(object) = Object::from_pixels(
+            material_ball(material, TURRET_PROJECTILE_SIZE),
+            IVec2::splat(TURRET_PROJECTILE_SIZE)
+        ) else {
+            continue;
+        };
+        let Ok(collider) = object.create_collider() else {
+            continue;
+        };
+
+        turret.cooldown.reset();
+
+        let direction = (target - position).normalize_or_zero();
+
+        commands.spawn((
+            Projectile::new(0.1, TURRET_DAMAGE)
+                .with_pierce(TURRET_PIERCE)
+                .with_source(entity)
+                .with_owner(ProjectileOwner::Player),
+            Sensor,
+            ObjectBundle {
+                object,
+                collider,
+                transform: TransformBundle {
+                    local: Transform::from_translation(transform.translation.xy().extend(0.0)),
+                    ..Default::default()
+                },
+                velocity: Velocity::linear(direction * TURRET_PROJECTILE_SPEED),
+                mass_properties: ColliderMassProperties::Density(16.0),
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+/// Companions fly to the closest enemy and bite it, or stay next to their owner if there is none
+#[allow(clippy::type_complexity)]
+pub fn companion_update(
+    mut commands: Commands,
+    mut companion_q: Query<(Entity, &Actor, &mut Velocity, &mut Companion, &mut Summon)>,
+    enemy_q: Query<&Actor, (With<Enemy>, Without<Death>)>,
+    player_q: Query<&Actor, With<Player>>,
+    index: Res<SpatialIndex>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    time: Res<Time>
+) {
+    for (entity, actor, mut velocity, mut companion, mut summon) in companion_q.iter_mut() {
+        if summon.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        companion.cooldown.tick(time.delta());
+
+        let position = actor.position + actor.size / 2.0;
+
+        let closest = index
+            .nearest(position, COMPANION_RANGE, |enemy, _| enemy_q.contains(enemy))
+            .and_then(|(enemy, _)| enemy_q.get(enemy).ok().map(|target| (enemy, target)));
+
+        let destination = match closest {
+            Some((enemy, target)) => {
+                let closest = position.clamp(target.position, target.position + target.size);
+
+                if
+                    closest.distance(position) <= actor.size.max_element() / 2.0 + COMPANION_ATTACK_DISTANCE &&
+                    companion.cooldown.finished()
+                {
+                    companion.cooldown.reset();
+                    damage_ev.send(DamageEvent {
+                        target: enemy,
+                        value: COMPANION_DAMAGE,
+                        knockback: (target.position + target.size / 2.0 - position).normalize_or_zero(),
+                        ignore_iframes: false,
+                        play_sound: true,
+                        cause: DamageCause::Player,
+                        damage_type: DamageType::Physical,
+                    });
+                }
+
+                target.position + target.size / 2.0
+            }
+            None => {
+                let Ok(owner) = player_q.get(summon.owner) else {
+                    continue;
+                };
+
+                owner.position + owner.size / 2.0 + FOLLOW_OFFSET
+            }
+        };
+
+        velocity.linvel +=
+            (destination - position).normalize_or_zero() / 16.0 + (fastrand::f32() - 0.5) / 8.0;
+    }
+}
+
+/// Companions are hurt by hazards of the level like any other actor
+pub fn companion_damage(
+    mut commands: Commands,
+    mut damage_ev: EventReader<DamageEvent>,
+    mut companion_q: Query<(&mut Health, &mut Velocity), With<Companion>>
+) {
+    for ev in damage_ev.read() {
+        let Ok((mut health, mut velocity)) = companion_q.get_mut(ev.target) else {
+            continue;
+        };
+
+        // already despawned by an earlier hit
+        if health.current <= 0.0 {
+            continue;
+        }
+
+        health.current -= ev.value;
+        velocity.linvel += ev.knockback;
+
+        if health.current <= 0.0 {
+            commands.entity(ev.target).despawn_recursive();
+        }
+    }
+}
@@ -1,20 +1,64 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
+use bevy_math::{ ivec2, vec2 };
+use bevy_persistent::Persistent;
+use bevy_rapier2d::dynamics::Velocity;
 
 use crate::{
-    constants::CHUNK_SIZE,
+    constants::{ CHUNK_SIZE, PARTICLE_Z },
+    curses::FragileTerrain,
+    difficulty::Difficulty,
+    generation::level::InteractableOnLevel,
     gui::Score,
+    interactables::spawn_interactable,
     registries:: Registries ,
+    score_export::RunStats,
+    settings::{ Config, Profile },
     simulation::{
         chunk_groups::build_chunk_group,
         chunk_manager:: ChunkManager ,
         dirty_rect:: DirtyRects ,
+        materials::Material,
+        particle::{ Particle, ParticleBundle, ParticleMovement, Particles },
         pixel::Pixel,
     },
+    stains::{ StainEvent, StainKind },
+    traps::find_surface,
 };
 
-use super::{ actor::Actor, enemy::ScopePoints };
+use super::{
+    actor::{ Actor, ActorFlags },
+    companion::{ Companion, COMPANION_TINT },
+    elite::{ EliteEffect, EliteModifier },
+    enemy::{ DropTable, Gore, ScopePoints },
+    health::{ DamageCause, DamageEvent, DamageType },
+    player::{ Player, PlayerTrackingParticles },
+};
+
+pub const WETNESS_GAIN: f32 = 1.0;
+pub const WETNESS_DECAY: f32 = 0.05;
+pub const WETNESS_HEAT_DECAY: f32 = 0.5;
+pub const WETNESS_DRIP_CHANCE: f32 = 0.05;
+pub const WETNESS_DRIP_COST: f32 = 0.01;
+
+// seconds an actor can hold its breath underwater
+pub const AIR_CAPACITY: f32 = 8.0;
+const AIR_RECOVERY: f32 = 4.0;
+const DROWNING_DAMAGE: f32 = 2.0;
+const DROWNING_INTERVAL: Duration = Duration::from_secs(1);
+
+const WET_COLOR: Vec3 = Vec3::new(0.6, 0.75, 1.0);
+const BURNING_COLOR: Color = Color::rgb(1.0, 0.6, 0.4);
+// radius of the blood stain around a dead enemy relative to its size
+const BLOOD_SPLATTER_SCALE: f32 = 1.5;
+// replaces the bright white and red blinking of damage flashes when flashes are reduced
+const REDUCED_FLASH_COLOR: Color = Color::rgb(1.0, 0.7, 0.7);
+const ELITE_EXPLOSION_FORCE: f32 = 4.0;
+// players closer than this in pixels pull dropped materials in, otherwise they fall to the ground
+const LOOT_ATTRACT_DISTANCE: f32 = 24.0;
+// speed in pixels per frame dropped materials burst out of the body with
+const LOOT_BURST_SPEED: f32 = 1.5;
 
 #[derive(Component)]
 pub struct DamageFlash {
@@ -34,14 +78,19 @@ impl Default for DamageFlash {
 pub fn damage_flash(
     mut commands: Commands,
     mut flashing_query: Query<(&mut DamageFlash, Entity, &mut Sprite)>,
+    config: Res<Persistent<Config>>,
     time: Res<Time>
 ) {
     for (mut effect, entity, mut sprite) in flashing_query.iter_mut() {
         if !effect.start_timer.finished() {
-            sprite.color = Color::rgba(255.0, 255.0, 255.0, 1.0);
+            sprite.color = if config.reduced_flashes {
+                REDUCED_FLASH_COLOR
+            } else {
+                Color::rgba(255.0, 255.0, 255.0, 1.0)
+            };
             effect.start_timer.tick(time.delta());
         } else if !effect.exit_timer.finished() {
-            sprite.color = Color::RED;
+            sprite.color = if config.reduced_flashes { REDUCED_FLASH_COLOR } else { Color::RED };
             effect.exit_timer.tick(time.delta());
         } else {
             sprite.color = Color::default();
@@ -50,6 +99,149 @@ pub fn damage_flash(
     }
 }
 
+/// Accumulated while standing in materials tagged as `wet`, slowly dries off and much faster near heat
+#[derive(Component, Default, Clone)]
+pub struct Wetness {
+    pub value: f32,
+    /// last material actor was soaked in, used for dripping
+    pub material: Option<Material>,
+}
+
+impl Wetness {
+    pub fn is_wet(&self) -> bool {
+        self.value > 0.0
+    }
+}
+
+/// Air left while the head of the actor is in liquid, once it runs out the actor starts drowning
+#[derive(Component, Clone)]
+pub struct Breath {
+    pub air: f32,
+    pub capacity: f32,
+    drowning_timer: Timer,
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Self {
+            air: AIR_CAPACITY,
+            capacity: AIR_CAPACITY,
+            drowning_timer: Timer::new(DROWNING_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn drowning(
+    mut actor_q: Query<(Entity, &Actor, &mut Breath)>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    time: Res<Time>
+) {
+    for (entity, actor, mut breath) in actor_q.iter_mut() {
+        if !actor.flags.contains(ActorFlags::UNDERWATER) {
+            breath.air = (breath.air + AIR_RECOVERY * time.delta_seconds()).min(breath.capacity);
+            breath.drowning_timer.reset();
+            continue;
+        }
+
+        breath.air = (breath.air - time.delta_seconds()).max(0.0);
+
+        if breath.air > 0.0 {
+            continue;
+        }
+
+        if breath.drowning_timer.tick(time.delta()).just_finished() {
+            damage_ev.send(DamageEvent {
+                value: DROWNING_DAMAGE,
+                target: entity,
+                knockback: Vec2::ZERO,
+                ignore_iframes: true,
+                play_sound: true,
+                cause: DamageCause::Hazard("drowning"),
+                damage_type: DamageType::Physical,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Burning {
+    timer: Timer,
+    tick_timer: Timer,
+}
+
+impl Default for Burning {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(Duration::from_secs(3), TimerMode::Once),
+            tick_timer: Timer::new(Duration::from_millis(500), TimerMode::Repeating),
+        }
+    }
+}
+
+impl Burning {
+    pub fn rekindle(&mut self) {
+        self.timer.reset();
+    }
+}
+
+pub fn burning(
+    mut commands: Commands,
+    mut burning_q: Query<(Entity, &mut Burning, &Wetness)>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    time: Res<Time>
+) {
+    for (entity, mut effect, wetness) in burning_q.iter_mut() {
+        effect.timer.tick(time.delta());
+        effect.tick_timer.tick(time.delta());
+
+        if effect.tick_timer.just_finished() {
+            damage_ev.send(DamageEvent {
+                value: 1.0,
+                target: entity,
+                knockback: Vec2::ZERO,
+                ignore_iframes: true,
+                play_sound: false,
+                cause: DamageCause::Hazard("fire"),
+                damage_type: DamageType::Fire,
+            });
+        }
+
+        if effect.timer.finished() || wetness.is_wet() {
+            commands.entity(entity).remove::<Burning>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn status_tint(
+    mut actor_q: Query<
+        (&mut Sprite, &Wetness, Has<Burning>, Option<&EliteModifier>, Has<Companion>),
+        (Without<DamageFlash>, Without<Death>)
+    >
+) {
+    for (mut sprite, wetness, is_burning, elite, is_companion) in actor_q.iter_mut() {
+        let base = if is_companion {
+            COMPANION_TINT
+        } else {
+            elite.map_or(Color::WHITE, |elite| elite.tint)
+        };
+
+        let color = if is_burning {
+            BURNING_COLOR
+        } else if wetness.is_wet() {
+            Color::rgb_from_array(
+                Vec3::from_slice(&base.as_rgba_f32()).lerp(WET_COLOR, wetness.value.min(1.0))
+            )
+        } else {
+            base
+        };
+
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Death {
     timer: Timer,
@@ -63,16 +255,128 @@ impl Default for Death {
     }
 }
 
+/// Spills the drop table of enemies that are removed by `death` this frame, materials pulled in
+/// by a player are tracked like collected pixels
+#[allow(clippy::type_complexity)]
+pub fn drop_loot(
+    mut commands: Commands,
+    mut particles: Particles,
+    enemy_q: Query<(&Death, &Transform, &DropTable)>,
+    player_q: Query<(Entity, &Transform), (With<Player>, Without<Death>)>,
+    mut tracked_particles: ResMut<PlayerTrackingParticles>,
+    chunk_manager: Res<ChunkManager>,
+    registries: Res<Registries>
+) {
+    for (death, transform, drops) in enemy_q.iter() {
+        if !death.timer.finished() {
+            continue;
+        }
+
+        let center = transform.translation.xy();
+        let collector = player_q
+            .iter()
+            .map(|(entity, player_transform)| {
+                (entity, player_transform.translation.xy().distance(center) * (CHUNK_SIZE as f32))
+            })
+            .filter(|(_, distance)| *distance < LOOT_ATTRACT_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity);
+
+        for (material_id, amount) in drops.materials.iter() {
+            let Some(material) = registries.materials.get(material_id) else {
+                continue;
+            };
+
+            for _ in 0..*amount {
+                let pixel = Pixel::from(material);
+                let velocity = vec2(fastrand::f32() - 0.5, fastrand::f32() * 0.5 + 0.5) * LOOT_BURST_SPEED;
+
+                let particle = particles
+                    .spawn(ParticleBundle {
+                        sprite: SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::rgba_u8(
+                                    pixel.color[0],
+                                    pixel.color[1],
+                                    pixel.color[2],
+                                    pixel.color[3]
+                                ),
+                                custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                                ..Default::default()
+                            },
+                            transform: Transform::from_translation(center.extend(PARTICLE_Z)),
+                            ..Default::default()
+                        },
+                        velocity: Velocity::linear(velocity / (CHUNK_SIZE as f32)),
+                        movement: collector.map_or(ParticleMovement::Fall, ParticleMovement::Follow),
+                        particle: Particle::new(pixel),
+                        ..Default::default()
+                    })
+                    .id();
+
+                if collector.is_some() {
+                    tracked_particles.push((material_id.clone(), particle));
+                }
+            }
+        }
+
+        let position = (center * (CHUNK_SIZE as f32)).as_ivec2();
+        // items rest on the floor below the body, bodies of flying enemies may have none in reach
+        let position = find_surface(&chunk_manager, position, -1).map_or(position, |floor| {
+            ivec2(position.x, floor + 1)
+        });
+
+        for (kind, chance) in drops.items.iter() {
+            if fastrand::f32() >= *chance {
+                continue;
+            }
+
+            spawn_interactable(
+                &mut commands,
+                &(InteractableOnLevel {
+                    offset: (0, 0),
+                    kind: kind.clone(),
+                }),
+                position,
+                &registries
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn death(
     mut commands: Commands,
-    mut effect_q: Query<(&Actor, &mut Death, Entity, &mut Sprite, &ScopePoints, &Transform)>,
+    mut effect_q: Query<
+        (
+            &Actor,
+            &mut Death,
+            Entity,
+            &mut Sprite,
+            &ScopePoints,
+            &Transform,
+            &Name,
+            Option<&EliteModifier>,
+            Option<&Gore>,
+        )
+    >,
+    player_q: Query<(Entity, &Transform), (With<Player>, Without<Death>)>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    mut stain_ev: EventWriter<StainEvent>,
     mut total_score: ResMut<Score>,
+    mut run_stats: ResMut<RunStats>,
+    mut profile: ResMut<Persistent<Profile>>,
     mut chunk_manager: ResMut<ChunkManager>,
     mut dirty_rects: ResMut<DirtyRects>,
     time: Res<Time>,
-    registries: Res<Registries>
+    registries: Res<Registries>,
+    difficulty: Res<Difficulty>,
+    fragile_terrain: Option<Res<FragileTerrain>>,
+    config: Res<Persistent<Config>>
 ) {
-    for (actor, mut effect, entity, mut sprite, points, transform) in effect_q.iter_mut() {
+    let terrain_scale = fragile_terrain.map_or(1.0, |fragile| fragile.damage_scale);
+
+    for (actor, mut effect, entity, mut sprite, points, transform, name, elite, gore) in effect_q.iter_mut() {
         if !effect.timer.finished() {
             effect.timer.tick(time.delta());
             let percentage =
@@ -81,7 +385,10 @@ pub fn death(
 
             sprite.color = Color::rgb_from_array([percentage; 3]);
         } else {
-            total_score.value += points.0;
+            total_score.add_kill(((points.0 as f32) * difficulty.score_multiplier()).round() as i32);
+            run_stats.kills += 1;
+            *run_stats.kills_by_type.entry(name.to_string()).or_insert(0) += 1;
+            profile.kills += 1;
             commands.entity(entity).despawn_recursive();
 
             let position = (transform.translation.xy() * (CHUNK_SIZE as f32)).as_ivec2();
@@ -92,6 +399,63 @@ pub fn death(
                 continue;
             };
 
+            if let Some(EliteEffect::Explosive { radius, damage }) = elite.map(|elite| &elite.effect) {
+                for x in -*radius as i32..=*radius as i32 {
+                    for y in -*radius as i32..=*radius as i32 {
+                        if ((x.pow(2) + y.pow(2)) as f32) > radius.powi(2) {
+                            continue;
+                        }
+
+                        let Some(pixel) = chunk_group.get_mut(local_position + IVec2::new(x, y)) else {
+                            continue;
+                        };
+
+                        if let Some(durability) = &mut pixel.durability {
+                            *durability -= damage * terrain_scale;
+                            if *durability <= 0.0 {
+                                *pixel = Pixel::default();
+                                run_stats.pixels_destroyed += 1;
+                            }
+                        }
+
+                        dirty_rects.request_update(position + IVec2::new(x, y));
+                        dirty_rects.request_render(position + IVec2::new(x, y));
+                    }
+                }
+
+                for (player, player_transform) in player_q.iter() {
+                    let offset = (player_transform.translation.xy() - transform.translation.xy()) *
+                    (CHUNK_SIZE as f32);
+
+                    if offset.length() > *radius {
+                        continue;
+                    }
+
+                    damage_ev.send(DamageEvent {
+                        target: player,
+                        value: *damage,
+                        knockback: offset.normalize_or_zero() * ELITE_EXPLOSION_FORCE,
+                        ignore_iframes: false,
+                        play_sound: true,
+                        cause: DamageCause::Enemy(name.to_string()),
+                        damage_type: DamageType::Physical,
+                    });
+                }
+            }
+
+            let mist = registries.materials.get("enemy_death_mist").unwrap();
+            let elite_remains = elite.and_then(|elite| registries.materials.get(&elite.remains));
+            // remains of elites replace the gore, they are part of their effect
+            let gore = gore.filter(|_| config.gore && elite_remains.is_none());
+
+            if gore.is_some_and(|gore| gore.0.iter().any(|(material_id, _)| material_id == "blood")) {
+                stain_ev.send(StainEvent {
+                    position: position.as_vec2(),
+                    radius: actor.size.max_element() * BLOOD_SPLATTER_SCALE,
+                    kind: StainKind::Blood,
+                });
+            }
+
             let IVec2 { x: width, y: height } = actor.size.as_ivec2();
 
             for x in -width / 2..width / 2 {
@@ -105,7 +469,13 @@ pub fn death(
                     };
 
                     if pixel.is_empty() {
-                        *pixel = Pixel::from(registries.materials.get("enemy_death_mist").unwrap());
+                        let remains = gore
+                            .and_then(|gore| gore.roll())
+                            .and_then(|material_id| registries.materials.get(material_id))
+                            .or(elite_remains)
+                            .unwrap_or(mist);
+
+                        *pixel = Pixel::from(remains);
                         dirty_rects.request_update(position + IVec2::new(x, y));
                         dirty_rects.request_render(position + IVec2::new(x, y));
                     }
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use crate::{
+    registries::Registries,
+    simulation::{ chunk_manager::ChunkManager, dirty_rect::DirtyRects, pixel::Pixel },
+};
+
+use super::{ actor::Actor, effects::Death, enemy::{ Enemy, EnemyBundle } };
+
+// chance of an enemy being upgraded on the first level, grows with every level after it
+const ELITE_CHANCE: f32 = 0.05;
+const ELITE_CHANCE_PER_LEVEL: f32 = 0.02;
+const ELITE_MAX_CHANCE: f32 = 0.25;
+// part by which health, score and explosions of modifiers grow every level
+const ELITE_LEVEL_SCALING: f32 = 0.1;
+
+#[derive(Clone, Debug)]
+pub enum EliteEffect {
+    /// Multiplier of damage the enemy takes
+    Armored {
+        damage_taken: f32,
+    },
+    /// Multiplier of the acceleration of the enemy
+    Fast {
+        speed: f32,
+    },
+    /// Explodes on death, damaging players and breaking terrain, radius is in pixels
+    Explosive {
+        radius: f32,
+        damage: f32,
+    },
+    /// Leaves pixels of the material behind while moving
+    Trailing {
+        material: String,
+        chance: f32,
+    },
+}
+
+/// Upgrade rolled for some enemies while they are placed, defined in the enemy registry
+#[derive(Component, Clone, Debug)]
+pub struct EliteModifier {
+    pub name: String,
+    pub tint: Color,
+    /// Multiplier of enemy health
+    pub health: f32,
+    /// Multiplier of points given for the kill
+    pub score: f32,
+    /// Material the body turns into on death instead of the usual mist
+    pub remains: String,
+    pub effect: EliteEffect,
+    /// Cutscene played the first time an enemy with the modifier is met during a run
+    pub intro: Option<String>,
+}
+
+impl EliteModifier {
+    /// Modifiers are stronger on later levels, `level` starts at 0
+    pub fn scaled(&self, level: u32) -> Self {
+        let scale = 1.0 + (level as f32) * ELITE_LEVEL_SCALING;
+        let mut modifier = self.clone();
+
+        modifier.health *= scale;
+        modifier.score *= scale;
+
+        if let EliteEffect::Explosive { radius, damage } = &mut modifier.effect {
+            *radius *= scale.sqrt();
+            *damage *= scale;
+        }
+
+        modifier
+    }
+
+    pub fn apply(&self, enemy: &mut EnemyBundle) {
+        enemy.name = Name::new(format!("{} {}", self.name, enemy.name));
+        enemy.actor.health.total *= self.health;
+        enemy.actor.health.current = enemy.actor.health.total;
+        enemy.score.0 = ((enemy.score.0 as f32) * self.score).round() as i32;
+    }
+
+    pub fn damage_taken(&self, value: f32) -> f32 {
+        match self.effect {
+            EliteEffect::Armored { damage_taken } => value * damage_taken,
+            _ => value,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        match self.effect {
+            EliteEffect::Fast { speed } => speed,
+            _ => 1.0,
+        }
+    }
+}
+
+pub fn elite_chance(level: u32) -> f32 {
+    (ELITE_CHANCE + (level as f32) * ELITE_CHANCE_PER_LEVEL).min(ELITE_MAX_CHANCE)
+}
+
+/// Picks a scaled modifier for an enemy that is being placed, most enemies stay regular
+pub fn roll_elite(modifiers: &[EliteModifier], level: u32) -> Option<EliteModifier> {
+    if modifiers.is_empty() || fastrand::f32() >= elite_chance(level) {
+        return None;
+    }
+
+    Some(modifiers[fastrand::usize(..modifiers.len())].scaled(level))
+}
+
+#[allow(clippy::type_complexity)]
+pub fn elite_trail(
+    enemy_q: Query<(&Actor, &EliteModifier), (With<Enemy>, Without<Death>)>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>
+) {
+    for (actor, elite) in enemy_q.iter() {
+        let EliteEffect::Trailing { material, chance } = &elite.effect else {
+            continue;
+        };
+
+        if fastrand::f32() >= *chance {
+            continue;
+        }
+
+        let Some(material) = registries.materials.get(material) else {
+            continue;
+        };
+
+        let position = (actor.position + Vec2::new(actor.size.x / 2.0, 0.0)).round().as_ivec2();
+
+        if
+            chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) &&
+            chunk_manager.set(position, Pixel::from(material)).is_ok()
+        {
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{
     animation::AnimationState,
     constants::CHUNK_SIZE,
+    generation::level::InteractableKind,
     raycast::raycast,
     registries::Registries,
     simulation::{
         chunk_manager::ChunkManager,
-        object::{ Projectile, Object, ObjectBundle },
+        dirty_rect::DirtyRects,
+        object::{ Object, ObjectBundle, ProjectileOwner },
+        materials::PhysicsType,
         pixel::Pixel,
     },
 };
@@ -19,30 +22,32 @@ use itertools::Itertools;
 use seldom_state::prelude::StateMachine;
 
 use super::{
-    actor::{ Actor, ActorBundle, ActorFlags, StorredRotation },
+    actor::{ Actor, ActorBundle, ActorFlags, MovementType, StorredRotation },
     animation::IdleAnimation,
+    behavior::{
+        has_line_of_sight,
+        Behavior,
+        BehaviorContext,
+        Behaviors,
+        NOTICE_RANGE,
+        PATROL_SPEED,
+        SEARCH_RANGE,
+    },
     effects::Death,
-    health::DamageEvent,
-    pathfinding::Path,
+    elite::EliteModifier,
+    health::{ DamageCause, DamageEvent, DamageType, Health, Resistances },
+    pathfinding::{ blocking_pixels, Path },
     player::Player,
+    spatial::SpatialIndex,
 };
 
+// how high above the surface floating enemies wait for a target that dove under
+const SURFACE_HOVER: i32 = 12;
+const MAX_SURFACE_SEARCH: i32 = 128;
+
 #[derive(Component)]
 pub struct Enemy;
 
-#[derive(Default, Component, Clone)]
-pub enum EnemyAI {
-    #[default]
-    Follow,
-    Projectiles {
-        base_material: String,
-        cooldown: Timer,
-        projectile: Projectile,
-        speed: f32,
-        range: f32,
-    },
-}
-
 #[derive(Component)]
 pub struct ScopePoints(pub i32);
 
@@ -54,10 +59,72 @@ pub struct EnemyBundle {
     pub actor: ActorBundle,
     pub animation_state: AnimationState,
     pub state_machine: StateMachine,
-    pub ai: EnemyAI,
+    pub behaviors: Behaviors,
+    pub resistances: Resistances,
+    pub gore: Gore,
+    pub drops: DropTable,
     pub idle: IdleAnimation,
 }
 
+/// Materials left in place of the body of a dead enemy along with the share of the body
+/// each of them takes up, the rest turns into mist
+#[derive(Component, Clone)]
+pub struct Gore(pub Vec<(String, f32)>);
+
+impl Gore {
+    pub fn new(materials: &[(&str, f32)]) -> Self {
+        Self(
+            materials
+                .iter()
+                .map(|(material_id, share)| (material_id.to_string(), *share))
+                .collect()
+        )
+    }
+
+    /// Material of a single pixel of the body, none if it should be mist
+    pub fn roll(&self) -> Option<&str> {
+        let mut roll = fastrand::f32();
+
+        self.0.iter().find_map(|(material_id, share)| {
+            roll -= share;
+            (roll < 0.0).then_some(material_id.as_str())
+        })
+    }
+}
+
+impl Default for Gore {
+    fn default() -> Self {
+        Self::new(&[("blood", 0.4), ("bone", 0.1)])
+    }
+}
+
+/// Loot of a dead enemy on top of its remains, materials burst out as particles that are
+/// collected by nearby players and items are left on the ground
+#[derive(Component, Clone, Default)]
+pub struct DropTable {
+    /// Materials along with the amount of pixels dropped
+    pub materials: Vec<(String, u32)>,
+    /// Items along with the chance of dropping them
+    pub items: Vec<(InteractableKind, f32)>,
+}
+
+impl DropTable {
+    pub fn new(materials: &[(&str, u32)]) -> Self {
+        Self {
+            materials: materials
+                .iter()
+                .map(|(material_id, amount)| (material_id.to_string(), *amount))
+                .collect(),
+            items: vec![],
+        }
+    }
+
+    pub fn with_item(mut self, kind: InteractableKind, chance: f32) -> Self {
+        self.items.push((kind, chance));
+        self
+    }
+}
+
 impl Default for EnemyBundle {
     fn default() -> Self {
         Self {
@@ -66,18 +133,78 @@ impl Default for EnemyBundle {
             actor: ActorBundle::default(),
             animation_state: AnimationState::default(),
             state_machine: StateMachine::default(),
-            ai: EnemyAI::Follow,
+            behaviors: Behaviors::default(),
+            resistances: Resistances::default(),
+            gore: Gore::default(),
+            drops: DropTable::default(),
             idle: IdleAnimation,
             score: ScopePoints(15),
         }
     }
 }
 
+fn is_liquid(chunk_manager: &ChunkManager, position: IVec2) -> bool {
+    chunk_manager
+        .get(position)
+        .is_ok_and(|pixel| matches!(pixel.physics_type, PhysicsType::Liquid(..)))
+}
+
+/// First position above the liquid the target is submerged in
+fn surface_above(chunk_manager: &ChunkManager, position: IVec2) -> IVec2 {
+    let mut surface = position;
+
+    while is_liquid(chunk_manager, surface) && surface.y - position.y < MAX_SURFACE_SEARCH {
+        surface.y += 1;
+    }
+
+    surface
+}
+
+/// Pushes the enemy in the direction, walking enemies only move horizontally
+fn steer(velocity: &mut Velocity, movement_type: &MovementType, direction: Vec2, speed_scale: f32) {
+    match movement_type {
+        MovementType::Floating => {
+            velocity.linvel +=
+                (direction.normalize_or_zero() / 16.0) * speed_scale + (fastrand::f32() - 0.5) / 8.0;
+        }
+        MovementType::Walking { speed, .. } => {
+            velocity.linvel.x +=
+                (direction.normalize_or_zero().x / 16.0) * speed * speed_scale +
+                (fastrand::f32() - 0.5) / 8.0;
+        }
+    }
+}
+
+/// Walking enemies turn around in front of walls and liquids
+fn is_blocked(chunk_manager: &ChunkManager, actor: &Actor, direction: f32) -> bool {
+    let position = actor.position.round().as_ivec2();
+    let front = if direction > 0.0 { position.x + (actor.size.x as i32) } else { position.x - 1 };
+
+    let wall = chunk_manager
+        .get(IVec2::new(front, position.y + (actor.size.y as i32) / 2))
+        .is_ok_and(|pixel| matches!(pixel.physics_type, PhysicsType::Static | PhysicsType::Powder));
+
+    wall || is_liquid(chunk_manager, IVec2::new(front, position.y - 1))
+}
+
+/// Runs the behaviors of every enemy, see [`Behaviors`]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn enemy_update(
     mut commands: Commands,
-    player_q: Query<(Entity, &Transform), With<Player>>,
+    player_q: Query<(&Transform, &Actor), With<Player>>,
     mut enemy_q: Query<
-        (Entity, &Actor, &Children, &mut Velocity, &Transform, &mut EnemyAI, Option<&mut Path>),
+        (
+            Entity,
+            &Actor,
+            &Health,
+            &Children,
+            &mut Velocity,
+            &Transform,
+            &mut Behaviors,
+            Option<&mut Path>,
+            Option<&EliteModifier>,
+            &Name,
+        ),
         (With<Enemy>, Without<Death>)
     >,
     hitbox_q: Query<&Collider, With<Sensor>>,
@@ -85,14 +212,34 @@ pub fn enemy_update(
     rapier_context: Res<RapierContext>,
     registries: Res<Registries>,
     chunk_manager: Res<ChunkManager>,
+    index: Res<SpatialIndex>,
     mut damage_ev: EventWriter<DamageEvent>,
 ) {
-    let (player_entity, player_transform) = player_q.single();
-    let player_position = (player_transform.translation.xy() * (CHUNK_SIZE as f32))
-        .round()
-        .as_ivec2();
+    let player_positions = player_q
+        .iter()
+        .map(|(transform, actor)| (
+            (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2(),
+            actor.flags.contains(ActorFlags::UNDERWATER),
+        ))
+        .collect::<Vec<_>>();
+
+    let now = time.elapsed_seconds_f64();
+    let mut calls = vec![];
+
+    for (
+        entity,
+        actor,
+        health,
+        children,
+        mut velocity,
+        transform,
+        mut behaviors,
+        path,
+        elite,
+        name,
+    ) in enemy_q.iter_mut() {
+        let speed_scale = elite.map_or(1.0, |elite| elite.speed());
 
-    for (entity, actor, children, mut velocity, transform, mut ai, path) in enemy_q.iter_mut() {
         if
             let Some(hitbox_entity) = children
                 .iter()
@@ -106,7 +253,7 @@ pub fn enemy_update(
                     continue;
                 };
 
-                if hitbox_q.contains(other) && parent == player_entity {
+                if hitbox_q.contains(other) && player_q.contains(parent) {
                     damage_ev.send(DamageEvent {
                         target: rapier_context.collider_parent(other).unwrap(),
                         value: 4.0,
@@ -114,141 +261,313 @@ pub fn enemy_update(
                         velocity.linvel / 2.0,
                         ignore_iframes: false,
                         play_sound: true,
+                        cause: DamageCause::Enemy(name.to_string()),
+                        damage_type: DamageType::Physical,
                     });
                 }
             }
         }
 
         let enemy_position = (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2();
+        let home = *behaviors.home.get_or_insert(enemy_position.as_vec2());
 
-        match ai.as_mut() {
-            EnemyAI::Follow => {
-                if let Some(mut path) = path {
-                    if time.elapsed_seconds_f64() - path.created_at > 5.0 {
-                        commands.entity(entity).remove::<Path>();
-                    }
+        // enemies chase whichever player is the closest
+        let Some((player_position, underwater)) = player_positions
+            .iter()
+            .min_by_key(|(position, _)| (*position - enemy_position).length_squared())
+            .copied() else {
+            continue;
+        };
 
-                    let mut closest_position = path.nodes[0];
-                    while
-                        path.nodes.len() > 1 &&
-                        (enemy_position - path.nodes[0]).length_squared() < 8
-                    {
-                        closest_position = path.nodes[1];
-                        path.nodes.remove(0);
-                    }
+        let distance = player_position - enemy_position;
 
-                    match actor.movement_type {
-                        super::actor::MovementType::Floating => {
-                            velocity.linvel +=
-                                (closest_position - enemy_position).as_vec2().normalize_or_zero() /
-                                    16.0 +
-                                (fastrand::f32() - 0.5) / 8.0;
-                        }
-                        super::actor::MovementType::Walking { speed, jump_height } => {
-                            if
-                                (
-                                    path.nodes[0..(4).min(path.nodes.len() - 1)]
-                                        .iter()
-                                        .map(|node| *node - closest_position)
-                                        .sum::<IVec2>().y as f32
-                                ) > actor.size.y / 2.0 &&
-                                actor.flags.contains(ActorFlags::GROUNDED) &&
-                                velocity.linvel.y.is_sign_negative()
-                            {
-                                velocity.linvel.y += jump_height;
-                            }
-
-                            velocity.linvel.x +=
-                                ((closest_position - enemy_position)
-                                    .as_vec2()
-                                    .normalize_or_zero().x /
-                                    16.0) *
-                                    speed +
-                                (fastrand::f32() - 0.5) / 8.0;
-                        }
-                    }
-                } else {
-                    velocity.linvel += Vec2::new(
-                        (fastrand::f32() - 0.5) / 4.0,
-                        (fastrand::f32() - 0.5) / 8.0
-                    );
+        behaviors.sees_target =
+            distance.abs().max_element() <= NOTICE_RANGE &&
+            has_line_of_sight(&chunk_manager, enemy_position, player_position);
+
+        if behaviors.sees_target {
+            if !behaviors.is_alerted(now) {
+                if let Some(radius) = behaviors.call_radius() {
+                    calls.push((entity, enemy_position.as_vec2(), radius, player_position));
                 }
             }
-            EnemyAI::Projectiles { base_material, cooldown, projectile, speed, range } => {
-                cooldown.tick(time.delta());
-
-                if cooldown.finished() {
-                    if
-                        (enemy_position - player_position).length_squared() >
-                            (range.powi(2) as i32) ||
-                        raycast(enemy_position, player_position, &chunk_manager, |pixel|
-                            pixel.is_empty()
-                        ).is_some()
-                    {
-                        continue;
+
+            behaviors.notice(player_position, now);
+        }
+
+        let sees_target = behaviors.sees_target;
+
+        for behavior in behaviors.list.iter_mut() {
+            let Behavior::Shoot { base_material, cooldown, projectile, speed, range } = behavior else {
+                continue;
+            };
+
+            cooldown.tick(time.delta());
+
+            if
+                !cooldown.finished() ||
+                !sees_target ||
+                underwater ||
+                distance.length_squared() > (range.powi(2) as i32) ||
+                raycast(enemy_position, player_position, &chunk_manager, |pixel|
+                    pixel.is_empty()
+                ).is_some()
+            {
+                continue;
+            }
+
+            cooldown.reset();
+
+            let direction = distance.as_vec2().normalize_or_zero();
+
+            let Some(material) = registries.materials.get(base_material) else {
+                continue;
+            };
+
+            let size: i32 = 17;
+            let mut pixels = vec![None; size.pow(2) as usize];
+
+            for (x, y) in (0..size).cartesian_product(0..size) {
+                if
+                    (IVec2::new(x, y).as_vec2() - (size as f32) / 2.0).length_squared() >
+                    ((size as f32) / 2.0).powi(2)
+                {
+                    continue;
+                }
+
+                pixels[(y * size + x) as usize] = Some(Pixel::from(material));
+            }
+
+            let Ok(object) = Object::from_pixels(pixels, IVec2::splat(size)) else {
+                continue;
+            };
+
+            let Ok(collider) = object.create_collider() else {
+                continue;
+            };
+
+            commands.spawn((
+                projectile.clone().with_source(entity).with_owner(ProjectileOwner::Enemy),
+                Sensor,
+                ObjectBundle {
+                    object,
+                    collider,
+                    transform: TransformBundle {
+                        local: Transform::from_translation(transform.translation.xy().extend(0.0)),
+                        ..Default::default()
+                    },
+                    velocity: Velocity::linear(
+                        direction * 1.25 * *speed +
+                            velocity.linvel / 16.0 +
+                            distance.as_vec2() / (CHUNK_SIZE as f32) / 2.0 +
+                            (Vec2::Y * (distance.x.abs() as f32)) / (CHUNK_SIZE as f32) / 2.0
+                    ),
+                    mass_properties: ColliderMassProperties::Density(16.0),
+                    ..Default::default()
+                },
+            ));
+        }
+
+        // alerted enemies go after the last known position, which is the player while in sight
+        let target = behaviors.target.unwrap_or(player_position);
+        let distance = target - enemy_position;
+
+        let context = BehaviorContext {
+            alerted: behaviors.is_alerted(now),
+            distance: distance.as_vec2().length(),
+            health: health.current / health.total,
+        };
+
+        let Some(mut movement) = behaviors.movement(&context).cloned() else {
+            continue;
+        };
+
+        // once the last known position is reached the enemy looks around it until the alert runs out
+        let mut center = home;
+
+        if
+            matches!(movement, Behavior::Chase) &&
+            !sees_target &&
+            distance.as_vec2().length() < SEARCH_RANGE
+        {
+            movement = Behavior::Patrol { range: SEARCH_RANGE };
+            center = target.as_vec2();
+        }
+
+        match movement {
+            Behavior::Chase if underwater && sees_target => {
+                match actor.movement_type {
+                    // wait right above the surface for the target to come up for air
+                    MovementType::Floating => {
+                        let surface = surface_above(&chunk_manager, target) +
+                        IVec2::Y * SURFACE_HOVER;
+
+                        steer(
+                            &mut velocity,
+                            &actor.movement_type,
+                            (surface - enemy_position).as_vec2(),
+                            speed_scale
+                        );
                     }
+                    // walking enemies do not follow into the liquid and pace along the shore
+                    MovementType::Walking { .. } => {
+                        velocity.linvel.x += (fastrand::f32() - 0.5) / 8.0;
+                    }
+                }
+            }
+            Behavior::Chase => {
+                let Some(mut path) = path else {
+                    steer(&mut velocity, &actor.movement_type, distance.as_vec2(), speed_scale);
+                    continue;
+                };
 
-                    cooldown.reset();
+                if now - path.created_at > 5.0 {
+                    commands.entity(entity).remove::<Path>();
+                }
 
-                    let distance = player_position - enemy_position;
-                    let direction = (player_position - enemy_position)
-                        .as_vec2()
-                        .normalize_or_zero();
+                let mut closest_position = path.nodes[0];
+                while path.nodes.len() > 1 && (enemy_position - path.nodes[0]).length_squared() < 8 {
+                    closest_position = path.nodes[1];
+                    path.nodes.remove(0);
+                }
 
-                    let Some(material) = registries.materials.get(base_material) else {
+                match actor.movement_type {
+                    MovementType::Walking { .. } if
+                        is_liquid(&chunk_manager, closest_position) &&
+                        !actor.flags.contains(ActorFlags::SWIMMING)
+                    => {
+                        velocity.linvel.x *= 0.5;
                         continue;
-                    };
+                    }
+                    MovementType::Walking { jump_height, .. } => {
+                        if
+                            (
+                                path.nodes[0..(4).min(path.nodes.len() - 1)]
+                                    .iter()
+                                    .map(|node| *node - closest_position)
+                                    .sum::<IVec2>().y as f32
+                            ) > actor.size.y / 2.0 &&
+                            actor.flags.contains(ActorFlags::GROUNDED) &&
+                            velocity.linvel.y.is_sign_negative()
+                        {
+                            velocity.linvel.y += jump_height;
+                        }
+                    }
+                    MovementType::Floating => {}
+                }
+
+                steer(
+                    &mut velocity,
+                    &actor.movement_type,
+                    (closest_position - enemy_position).as_vec2(),
+                    speed_scale
+                );
+            }
+            Behavior::Flee { .. } | Behavior::Kite { .. } => {
+                steer(&mut velocity, &actor.movement_type, -distance.as_vec2(), speed_scale);
+            }
+            Behavior::Patrol { range } => {
+                let offset = enemy_position.as_vec2() - center;
 
-                    let size: i32 = 17;
-                    let mut pixels = vec![None; size.pow(2) as usize];
+                match actor.movement_type {
+                    MovementType::Floating => {
+                        velocity.linvel += Vec2::new(
+                            (fastrand::f32() - 0.5) / 4.0,
+                            (fastrand::f32() - 0.5) / 8.0
+                        );
 
-                    for (x, y) in (0..size).cartesian_product(0..size) {
+                        if offset.length() > range {
+                            steer(&mut velocity, &actor.movement_type, -offset, speed_scale);
+                        }
+                    }
+                    MovementType::Walking { .. } => {
                         if
-                            (IVec2::new(x, y).as_vec2() - (size as f32) / 2.0).length_squared() >
-                            ((size as f32) / 2.0).powi(2)
+                            (offset.x.abs() > range &&
+                                offset.x.signum() == behaviors.patrol_direction) ||
+                            is_blocked(&chunk_manager, actor, behaviors.patrol_direction)
                         {
-                            continue;
+                            behaviors.patrol_direction = -behaviors.patrol_direction;
                         }
 
-                        pixels[(y * size + x) as usize] = Some(Pixel::from(material));
+                        steer(
+                            &mut velocity,
+                            &actor.movement_type,
+                            Vec2::X * behaviors.patrol_direction,
+                            speed_scale * PATROL_SPEED
+                        );
                     }
+                }
+            }
+            Behavior::CallForHelp { .. } | Behavior::Shoot { .. } | Behavior::Dig { .. } => {}
+        }
+    }
 
-                    let Ok(object) = Object::from_pixels(pixels, IVec2::splat(size)) else {
-                        continue;
-                    };
+    for (caller, position, radius, target) in calls {
+        for (entity, _) in index.in_radius(position, radius) {
+            if entity == caller {
+                continue;
+            }
 
-                    let Ok(collider) = object.create_collider() else {
-                        continue;
-                    };
-
-                    commands.spawn((
-                        projectile.clone().with_source(entity),
-                        Sensor,
-                        ObjectBundle {
-                            object,
-                            collider,
-                            transform: TransformBundle {
-                                local: Transform::from_translation(
-                                    transform.translation.xy().extend(0.0)
-                                ),
-                                ..Default::default()
-                            },
-                            velocity: Velocity::linear(
-                                direction * 1.25 * *speed +
-                                    velocity.linvel / 16.0 +
-                                    distance.as_vec2() / (CHUNK_SIZE as f32) / 2.0 +
-                                    (Vec2::Y * (distance.x.abs() as f32)) / (CHUNK_SIZE as f32) / 2.0
-                            ),
-                            mass_properties: ColliderMassProperties::Density(16.0),
-                            ..Default::default()
-                        },
-                    ));
-                }
+            if let Ok((.., mut behaviors, _, _, _)) = enemy_q.get_mut(entity) {
+                behaviors.hear(target, now);
             }
         }
     }
 }
 
+/// Diggers clear the pixels in the way to the next node of their path, closest to them first
+#[allow(clippy::type_complexity)]
+pub fn enemy_dig(
+    mut enemy_q: Query<(&Actor, &mut Behaviors, &Path), (With<Enemy>, Without<Death>)>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    time: Res<Time>
+) {
+    for (actor, mut behaviors, path) in enemy_q.iter_mut() {
+        let Some((hardness, rate, progress)) = behaviors.list
+            .iter_mut()
+            .find_map(|behavior| {
+                match behavior {
+                    Behavior::Dig { hardness, rate, progress } => Some((*hardness, *rate, progress)),
+                    _ => None,
+                }
+            }) else {
+            continue;
+        };
+
+        let center = actor.position + actor.size / 2.0;
+
+        let mut pixels = blocking_pixels(&chunk_manager, path.nodes[0], actor.size, hardness);
+
+        // progress is not saved up while there is nothing to dig
+        if pixels.is_empty() {
+            *progress = 0.0;
+            continue;
+        }
+
+        *progress += rate * time.delta_seconds();
+
+        pixels.sort_by(|a, b| {
+            a.as_vec2().distance_squared(center).total_cmp(&b.as_vec2().distance_squared(center))
+        });
+
+        let clock = chunk_manager.clock();
+
+        for position in pixels.into_iter().take(progress.floor() as usize) {
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            *pixel = Pixel::default().with_clock(clock);
+            *progress -= 1.0;
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+    }
+}
+
 pub fn update_enemy_rotation(
     mut enemy_q: Query<(&mut Transform, &Velocity, &mut StorredRotation), With<Enemy>>
 ) {
@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_persistent::Persistent;
+
+use crate::{
+    constants::CHUNK_SIZE,
+    difficulty::{ AdaptiveDifficulty, Difficulty },
+    settings::Config,
+    vehicle::Piloting,
+};
+
+use super::{
+    actor::Actor,
+    effects::Death,
+    elite::EliteModifier,
+    enemy::Enemy,
+    health::{ player_damage_scale, DamageEvent, Health, IFrames, Resistances },
+    player::Player,
+};
+
+const FLOATER_DURATION: Duration = Duration::from_millis(800);
+// floaters are hidden and reused instead of despawned, the oldest one is taken once all are shown
+const MAX_FLOATERS: usize = 48;
+// drift in pixels per second
+const FLOATER_RISE: f32 = 24.0;
+const FLOATER_SPREAD: f32 = 8.0;
+const FLOATER_FONT_SIZE: f32 = 16.0;
+// text is drawn in screen pixels at default zoom
+const FLOATER_SCALE: f32 = 0.375 / (CHUNK_SIZE as f32);
+// hits taking at least this part of total health are shown as critical
+const CRITICAL_FRACTION: f32 = 0.5;
+
+const DEALT_COLOR: Color = Color::WHITE;
+const CRITICAL_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+const TAKEN_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+
+/// Text entities of damage numbers, hidden floaters are at the front and the newest at the back
+#[derive(Resource, Default)]
+pub struct FloaterPool(Vec<Entity>);
+
+#[derive(Component)]
+pub struct Floater {
+    timer: Timer,
+    velocity: Vec2,
+    color: Color,
+}
+
+/// Shows the damage of every hit the same way it is applied in `process_damage_events`,
+/// hits that are ignored by iframes do not get a floater
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn spawn_damage_floaters(
+    mut commands: Commands,
+    mut pool: ResMut<FloaterPool>,
+    mut damage_ev: EventReader<DamageEvent>,
+    mut floater_q: Query<(&mut Floater, &mut Text, &mut Transform, &mut Visibility)>,
+    actor_q: Query<
+        (
+            &Actor,
+            &Health,
+            &Resistances,
+            Has<Player>,
+            Has<Piloting>,
+            Option<&IFrames>,
+            Option<&EliteModifier>,
+        ),
+        Or<(With<Player>, (With<Enemy>, Without<Death>))>
+    >,
+    config: Res<Persistent<Config>>,
+    difficulty: Res<Difficulty>,
+    adaptive: Option<Res<AdaptiveDifficulty>>
+) {
+    if !config.damage_numbers {
+        damage_ev.clear();
+        return;
+    }
+
+    let mut hit = vec![];
+    let damage_scale = player_damage_scale(&difficulty, adaptive.as_deref());
+
+    for ev in damage_ev.read() {
+        let Ok((actor, health, resistances, is_player, piloting, iframes, elite)) = actor_q.get(
+            ev.target
+        ) else {
+            continue;
+        };
+
+        if (iframes.is_some() || hit.contains(&ev.target)) && !ev.ignore_iframes {
+            continue;
+        }
+
+        if is_player && piloting && ev.cause.is_contact() {
+            continue;
+        }
+
+        if !ev.ignore_iframes {
+            hit.push(ev.target);
+        }
+
+        let damage = if is_player {
+            ev.player_damage(resistances, damage_scale)
+        } else {
+            ev.enemy_damage(resistances, elite)
+        };
+
+        if damage <= 0.0 {
+            continue;
+        }
+
+        let (color, font_size) = if is_player {
+            (TAKEN_COLOR, FLOATER_FONT_SIZE)
+        } else if damage >= health.total * CRITICAL_FRACTION {
+            (CRITICAL_COLOR, FLOATER_FONT_SIZE * 1.5)
+        } else {
+            (DEALT_COLOR, FLOATER_FONT_SIZE)
+        };
+
+        let position =
+            (actor.position + Vec2::new(actor.size.x / 2.0, actor.size.y)) / (CHUNK_SIZE as f32);
+        let velocity = Vec2::new(
+            (fastrand::f32() - 0.5) * FLOATER_SPREAD,
+            FLOATER_RISE
+        ) / (CHUNK_SIZE as f32);
+        let value = format!("{}", damage.round().max(1.0));
+
+        let floater = Floater {
+            timer: Timer::new(FLOATER_DURATION, TimerMode::Once),
+            velocity,
+            color,
+        };
+
+        let reusable = pool.0
+            .first()
+            .and_then(|entity| floater_q.get(*entity).ok())
+            .is_some_and(|(.., visibility)| *visibility == Visibility::Hidden);
+
+        let entity = if !reusable && pool.0.len() < MAX_FLOATERS {
+            commands
+                .spawn((
+                    Name::new("Damage floater"),
+                    Text2dBundle {
+                        text: Text::from_section(String::new(), TextStyle::default()),
+                        ..default()
+                    },
+                ))
+                .id()
+        } else {
+            pool.0.remove(0)
+        };
+
+        pool.0.push(entity);
+
+        let transform = Transform::from_translation(position.extend(10.0)).with_scale(
+            Vec3::splat(FLOATER_SCALE)
+        );
+        let text = Text::from_section(value, TextStyle {
+            font_size,
+            color,
+            ..default()
+        });
+
+        match floater_q.get_mut(entity) {
+            Ok((mut current, mut current_text, mut current_transform, mut visibility)) => {
+                *current = floater;
+                *current_text = text;
+                *current_transform = transform;
+                *visibility = Visibility::Inherited;
+            }
+            Err(_) => {
+                commands.entity(entity).insert((floater, text, transform));
+            }
+        }
+    }
+}
+
+/// Moves shown floaters up while fading them out, finished ones are hidden until reused
+pub fn update_damage_floaters(
+    mut pool: ResMut<FloaterPool>,
+    mut floater_q: Query<(Entity, &mut Floater, &mut Text, &mut Transform, &mut Visibility)>,
+    time: Res<Time>
+) {
+    for (entity, mut floater, mut text, mut transform, mut visibility) in floater_q.iter_mut() {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+
+        floater.timer.tick(time.delta());
+
+        if floater.timer.finished() {
+            *visibility = Visibility::Hidden;
+
+            // hidden floaters are the first to be reused
+            if let Some(index) = pool.0.iter().position(|pooled| *pooled == entity) {
+                pool.0.remove(index);
+                pool.0.insert(0, entity);
+            }
+            continue;
+        }
+
+        transform.translation += (floater.velocity * time.delta_seconds()).extend(0.0);
+
+        let alpha = 1.0 - floater.timer.fraction().powi(2);
+        text.sections[0].style.color = floater.color.with_a(floater.color.a() * alpha);
+    }
+}
+
+/// Hides floaters left over from the previous level
+pub fn hide_damage_floaters(mut floater_q: Query<&mut Visibility, With<Floater>>) {
+    for mut visibility in floater_q.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
@@ -1,9 +1,44 @@
-use bevy::{ audio::{ PlaybackMode, Volume }, prelude::* };
+use std::time::Duration;
+
+use bevy::{ audio::{ PlaybackMode, Volume }, prelude::*, sprite::Anchor };
 use bevy_rapier2d::dynamics::Velocity;
+use bevy_tween::{
+    interpolation::EaseFunction,
+    span_tween::SpanTweenerBundle,
+    tween::ComponentTween,
+};
+
+use crate::{
+    assets::AudioAssetCollection,
+    constants::CHUNK_SIZE,
+    difficulty::{ AdaptiveDifficulty, Difficulty },
+    gui::Score,
+    interpolator::InterpolateSpriteSize,
+    perks::PerkAbilities,
+    score_export::RunStats,
+    settings::AudioBus,
+    simulation::materials::Material,
+    state::GameState,
+    vehicle::Piloting,
+};
 
-use crate::{ assets::AudioAssetCollection, state::GameState };
+use super::{
+    actor::Actor,
+    effects::{ DamageFlash, Death },
+    elite::EliteModifier,
+    enemy::Enemy,
+    player::Player,
+};
 
-use super::{ effects::{ DamageFlash, Death }, enemy::Enemy, player::Player };
+// time after the last hit before the bar is removed
+const HEALTH_BAR_LINGER: Duration = Duration::from_secs(3);
+const HEALTH_BAR_TWEEN: Duration = Duration::from_millis(250);
+// sizes are in pixels, bars are never narrower than the minimum width
+const HEALTH_BAR_MIN_WIDTH: f32 = 12.0;
+const HEALTH_BAR_HEIGHT: f32 = 2.0;
+const HEALTH_BAR_OFFSET: f32 = 4.0;
+const HEALTH_BAR_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const HEALTH_BAR_FILL: Color = Color::rgb(0.85, 0.2, 0.2);
 
 #[derive(Reflect, Component, Clone)]
 pub struct Health {
@@ -11,8 +46,54 @@ pub struct Health {
     pub total: f32,
 }
 
-#[derive(Component)]
-pub struct KnockbackResistance(pub f32);
+/// Share of the damage of every type that is ignored, negative values make the actor
+/// take more damage of that type instead
+#[derive(Component, Clone, Default)]
+pub struct Resistances {
+    pub fire: f32,
+    pub acid: f32,
+    pub projectile: f32,
+    /// Share of the knockback that is ignored, regardless of the damage type
+    pub knockback: f32,
+}
+
+impl Resistances {
+    pub fn damage_taken(&self, value: f32, damage_type: DamageType) -> f32 {
+        let resistance = match damage_type {
+            DamageType::Physical => 0.0,
+            DamageType::Fire => self.fire,
+            DamageType::Acid => self.acid,
+            DamageType::Projectile => self.projectile,
+        };
+
+        value * (1.0 - resistance.min(1.0))
+    }
+
+    pub fn knockback_taken(&self, knockback: Vec2) -> Vec2 {
+        knockback * (1.0 - self.knockback.min(1.0))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageType {
+    Physical,
+    Fire,
+    Acid,
+    Projectile,
+}
+
+impl DamageType {
+    /// Type of the damage dealt by touching the material, based on its tags
+    pub fn of_material(material: &Material) -> Self {
+        if material.tags.contains("hot") {
+            DamageType::Fire
+        } else if material.tags.contains("corrosive") {
+            DamageType::Acid
+        } else {
+            DamageType::Physical
+        }
+    }
+}
 
 #[derive(Event)]
 pub struct DamageEvent {
@@ -21,8 +102,64 @@ pub struct DamageEvent {
     pub knockback: Vec2,
     pub ignore_iframes: bool,
     pub play_sound: bool,
+    pub cause: DamageCause,
+    pub damage_type: DamageType,
+}
+
+/// Damage is worked out here for both applying it and showing it, so damage numbers match the health lost
+impl DamageEvent {
+    pub fn player_damage(&self, resistances: &Resistances, damage_scale: f32) -> f32 {
+        resistances.damage_taken(self.value, self.damage_type) * damage_scale
+    }
+
+    pub fn enemy_damage(&self, resistances: &Resistances, elite: Option<&EliteModifier>) -> f32 {
+        let damage = resistances.damage_taken(self.value, self.damage_type);
+        elite.map_or(damage, |elite| elite.damage_taken(damage))
+    }
+}
+
+/// Scale of the damage players take on the current difficulty
+pub fn player_damage_scale(difficulty: &Difficulty, adaptive: Option<&AdaptiveDifficulty>) -> f32 {
+    difficulty.enemy_damage() * adaptive.map_or(1.0, |adaptive| adaptive.damage)
+}
+
+/// Source of a hit, the one that kills a player is shown on the game over screen
+#[derive(Clone, Debug, PartialEq)]
+pub enum DamageCause {
+    Player,
+    /// Name of the enemy
+    Enemy(String),
+    /// Name of the material touched
+    Material(String),
+    Hazard(&'static str),
+    Object,
+    Explosion,
 }
 
+impl std::fmt::Display for DamageCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DamageCause::Player => write!(f, "a friend"),
+            DamageCause::Enemy(name) => write!(f, "{}", name.to_lowercase()),
+            DamageCause::Material(name) => write!(f, "{}", name),
+            DamageCause::Hazard(name) => write!(f, "{}", name),
+            DamageCause::Object => write!(f, "a flying object"),
+            DamageCause::Explosion => write!(f, "an explosion"),
+        }
+    }
+}
+
+impl DamageCause {
+    /// Hits dealt by touching something, vehicles protect their pilots from these
+    pub fn is_contact(&self) -> bool {
+        matches!(self, DamageCause::Enemy(..) | DamageCause::Material(..) | DamageCause::Object)
+    }
+}
+
+/// What dealt the killing blow of the run
+#[derive(Resource, Default)]
+pub struct DeathCause(pub Option<DamageCause>);
+
 #[derive(Component, Deref, DerefMut, Clone)]
 pub struct IFrames(pub Timer);
 
@@ -40,34 +177,62 @@ pub fn tick_iframes(
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn process_damage_events(
     mut commands: Commands,
     mut damage_ev: EventReader<DamageEvent>,
     mut player_q: Query<
-        (&Transform, &mut Health, &mut Velocity, Option<&IFrames>, &KnockbackResistance),
+        (
+            &Transform,
+            &mut Health,
+            &mut Velocity,
+            Option<&IFrames>,
+            &Resistances,
+            &mut PerkAbilities,
+            Has<Piloting>,
+        ),
         (With<Player>, Without<Enemy>)
     >,
     mut enemy_q: Query<
-        (&Transform, &mut Health, &mut Velocity, Option<&Death>, Option<&IFrames>),
+        (
+            &Transform,
+            &mut Health,
+            &mut Velocity,
+            Option<&Death>,
+            Option<&IFrames>,
+            Option<&EliteModifier>,
+            &Resistances,
+        ),
         (With<Enemy>, Without<Death>)
     >,
     mut state: ResMut<NextState<GameState>>,
-    audio_assets: Res<AudioAssetCollection>
+    audio_assets: Res<AudioAssetCollection>,
+    difficulty: Res<Difficulty>,
+    adaptive: Option<Res<AdaptiveDifficulty>>,
+    mut run_stats: ResMut<RunStats>,
+    mut score: ResMut<Score>,
+    mut death_cause: ResMut<DeathCause>
 ) {
     let mut added_iframes = vec![];
+    let damage_scale = player_damage_scale(&difficulty, adaptive.as_deref());
 
     for ev in damage_ev.read() {
         if
-            let Ok((transform, mut health, mut velocity, iframes, knockback_resistance)) =
+            let Ok((transform, mut health, mut velocity, iframes, resistances, mut abilities, piloting)) =
                 player_q.get_mut(ev.target)
         {
             if (iframes.is_some() || added_iframes.contains(&ev.target)) && !ev.ignore_iframes {
                 continue;
             }
 
+            if piloting && ev.cause.is_contact() {
+                continue;
+            }
+
             if ev.play_sound {
                 commands.spawn((
                     TransformBundle::from_transform(transform.clone()),
+                    AudioBus::Sfx,
                     AudioBundle {
                         source: audio_assets.hit.clone(),
                         settings: PlaybackSettings {
@@ -81,12 +246,29 @@ pub fn process_damage_events(
                 ));
             }
 
-            health.current -= ev.value;
-            velocity.linvel += ev.knockback - ev.knockback * knockback_resistance.0;
+            // overkill is not counted
+            let damage = ev.player_damage(resistances, damage_scale);
+            run_stats.damage_taken += damage.min(health.current.max(0.0));
+
+            if damage > 0.0 {
+                score.break_combo();
+            }
+
+            health.current -= damage;
+            velocity.linvel += resistances.knockback_taken(ev.knockback);
+
+            if health.current <= 0.0 && abilities.second_winds > 0 {
+                abilities.second_winds -= 1;
+                health.current = health.total / 4.0;
+            }
 
             if health.current > 0.0 {
                 commands.entity(ev.target).insert(DamageFlash::default());
             } else {
+                if death_cause.0.is_none() {
+                    death_cause.0 = Some(ev.cause.clone());
+                }
+
                 state.set(GameState::GameOver);
             }
 
@@ -97,9 +279,8 @@ pub fn process_damage_events(
                     .insert(IFrames(Timer::from_seconds(0.5, TimerMode::Once)));
             }
         } else if
-            let Ok((transform, mut health, mut velocity, death, iframes)) = enemy_q.get_mut(
-                ev.target
-            )
+            let Ok((transform, mut health, mut velocity, death, iframes, elite, resistances)) =
+                enemy_q.get_mut(ev.target)
         {
             if (iframes.is_some() || added_iframes.contains(&ev.target)) && !ev.ignore_iframes {
                 continue;
@@ -108,6 +289,7 @@ pub fn process_damage_events(
             if ev.play_sound {
                 commands.spawn((
                     TransformBundle::from_transform(transform.clone()),
+                    AudioBus::Sfx,
                     AudioBundle {
                         source: audio_assets.hit.clone(),
                         settings: PlaybackSettings {
@@ -121,13 +303,22 @@ pub fn process_damage_events(
                 ));
             }
 
-            health.current -= ev.value;
-            velocity.linvel += ev.knockback;
+            let damage = ev.enemy_damage(resistances, elite);
+            run_stats.damage_dealt += damage.min(health.current.max(0.0));
+
+            health.current -= damage;
+            velocity.linvel += resistances.knockback_taken(ev.knockback);
 
             if health.current > 0.0 {
                 commands.entity(ev.target).insert(DamageFlash::default());
             } else if death.is_none() {
                 commands.entity(ev.target).insert(Death::default());
+
+                if ev.cause == DamageCause::Player {
+                    for (_, mut health, _, _, _, abilities, _) in player_q.iter_mut() {
+                        health.current = (health.current + abilities.lifesteal).min(health.total);
+                    }
+                }
             }
 
             if !ev.ignore_iframes {
@@ -139,3 +330,141 @@ pub fn process_damage_events(
         }
     }
 }
+
+/// World-space bar following an enemy, spawned after the enemy first takes damage
+#[derive(Component)]
+pub struct HealthBar {
+    enemy: Entity,
+    timer: Timer,
+}
+
+/// Links an enemy to its health bar and the fill of the bar
+#[derive(Component)]
+pub struct EnemyHealthBar {
+    bar: Entity,
+    fill: Entity,
+}
+
+fn health_bar_size(actor: &Actor) -> Vec2 {
+    Vec2::new(actor.size.x.max(HEALTH_BAR_MIN_WIDTH), HEALTH_BAR_HEIGHT) / (CHUNK_SIZE as f32)
+}
+
+fn health_bar_translation(actor: &Actor) -> Vec3 {
+    let position = actor.position + Vec2::new(actor.size.x / 2.0, actor.size.y + HEALTH_BAR_OFFSET);
+    (position / (CHUNK_SIZE as f32)).extend(10.0)
+}
+
+/// Spawns bars for damaged enemies and shrinks the fill of existing ones,
+/// bars are removed once the enemy dies or is not hit for a while
+#[allow(clippy::type_complexity)]
+pub fn update_health_bars(
+    mut commands: Commands,
+    enemy_q: Query<
+        (Entity, &Actor, &Health, Option<&EnemyHealthBar>),
+        (With<Enemy>, Without<Death>, Changed<Health>)
+    >,
+    alive_q: Query<(), (With<Enemy>, Without<Death>)>,
+    mut bar_q: Query<(Entity, &mut HealthBar)>,
+    fill_q: Query<&Sprite>,
+    time: Res<Time>
+) {
+    for (entity, actor, health, health_bar) in enemy_q.iter() {
+        if health.current >= health.total || health.current <= 0.0 {
+            continue;
+        }
+
+        let size = health_bar_size(actor);
+        let fraction = (health.current / health.total).clamp(0.0, 1.0);
+
+        let Some(health_bar) = health_bar else {
+            let mut fill = Entity::PLACEHOLDER;
+
+            let bar = commands
+                .spawn((
+                    Name::new("Health bar"),
+                    HealthBar {
+                        enemy: entity,
+                        timer: Timer::new(HEALTH_BAR_LINGER, TimerMode::Once),
+                    },
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: HEALTH_BAR_BACKGROUND,
+                            custom_size: Some(size),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(health_bar_translation(actor)),
+                        ..Default::default()
+                    },
+                ))
+                .with_children(|parent| {
+                    fill = parent
+                        .spawn((
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    color: HEALTH_BAR_FILL,
+                                    custom_size: Some(size),
+                                    anchor: Anchor::CenterLeft,
+                                    ..Default::default()
+                                },
+                                transform: Transform::from_xyz(-size.x / 2.0, 0.0, 0.1),
+                                ..Default::default()
+                            },
+                            EaseFunction::ExponentialOut,
+                            SpanTweenerBundle::new(HEALTH_BAR_TWEEN).tween_here(),
+                            ComponentTween::new(InterpolateSpriteSize {
+                                start: size,
+                                end: size * Vec2::new(fraction, 1.0),
+                            }),
+                        ))
+                        .id();
+                })
+                .id();
+
+            commands.entity(entity).insert(EnemyHealthBar { bar, fill });
+            continue;
+        };
+
+        if let Ok((_, mut bar)) = bar_q.get_mut(health_bar.bar) {
+            bar.timer.reset();
+        }
+
+        let Ok(sprite) = fill_q.get(health_bar.fill) else {
+            continue;
+        };
+
+        commands
+            .entity(health_bar.fill)
+            .insert(SpanTweenerBundle::new(HEALTH_BAR_TWEEN).tween_here())
+            .insert(
+                ComponentTween::new(InterpolateSpriteSize {
+                    start: sprite.custom_size.unwrap_or(size),
+                    end: size * Vec2::new(fraction, 1.0),
+                })
+            );
+    }
+
+    for (entity, mut bar) in bar_q.iter_mut() {
+        bar.timer.tick(time.delta());
+
+        if alive_q.contains(bar.enemy) && !bar.timer.finished() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+
+        if let Some(mut enemy) = commands.get_entity(bar.enemy) {
+            enemy.remove::<EnemyHealthBar>();
+        }
+    }
+}
+
+pub fn update_health_bar_translation(
+    mut bar_q: Query<(&HealthBar, &mut Transform)>,
+    actor_q: Query<&Actor, With<Enemy>>
+) {
+    for (bar, mut transform) in bar_q.iter_mut() {
+        if let Ok(actor) = actor_q.get(bar.enemy) {
+            transform.translation = health_bar_translation(actor);
+        }
+    }
+}
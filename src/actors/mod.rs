@@ -1,39 +1,65 @@
 use actor::{ toggle_actors, ActorDebugRender };
 use bevy::prelude::*;
-use leafwing_input_manager::plugin::InputManagerPlugin;
+use bevy_persistent::Persistent;
+use leafwing_input_manager::plugin::{ InputManagerPlugin, InputManagerSystem };
 use pathfinding::pathfind_apply;
 use player::Player;
 
 use crate::{
     assets::AudioAssetCollection,
+    curses::NoHook,
     despawn_component,
+    settings::{ AudioBus, Config },
     simulation::object::unfill_objects,
     state::GameState,
 };
 
 use self::{
-    actor::{ render_actor_gizmos, update_actor_translation, update_actors, Actor, MovementType },
-    effects::{ damage_flash, death },
-    enemy::{ enemy_update, update_enemy_rotation, Enemy },
-    health::{ process_damage_events, tick_iframes, DamageEvent, Health },
+    actor::{ object_contacts, render_actor_gizmos, update_actor_translation, update_actors, Actor, MovementType },
+    behavior::{ hear_noises, update_alert_indicators, AlertIndicator, NoiseEvent },
+    companion::{ companion_damage, companion_update, player_summon, turret_update, Summon },
+    effects::{ burning, damage_flash, death, drop_loot, drowning, status_tint },
+    elite::elite_trail,
+    enemy::{ enemy_dig, enemy_update, update_enemy_rotation, Enemy },
+    floaters::{ hide_damage_floaters, spawn_damage_floaters, update_damage_floaters, FloaterPool },
+    health::{
+        process_damage_events,
+        tick_iframes,
+        update_health_bar_translation,
+        update_health_bars,
+        DamageEvent,
+        DeathCause,
+        Health,
+        HealthBar,
+    },
     pathfinding::{ gizmos_path, pathfind_start },
+    spatial::{ update_spatial_index, SpatialIndex },
     player::{
+        fade_parry_flashes,
         player_attack,
         player_collect_sand,
+        player_crouch,
         player_dash,
+        player_dash_through,
         player_hook,
         player_jump,
         player_jump_extend,
+        player_ledge_grab,
+        player_swim,
+        player_join,
         player_prune_empty_materials,
         player_reset_position,
+        player_roll,
         player_run,
         player_setup,
         player_shoot,
         player_switch_material,
         player_synchronize_attack_rotation,
         store_camera_position,
+        update_player_input_map,
         update_player_rotation,
         update_rope_position,
+        MiningProgress,
         PlayerActions,
         PlayerTrackingParticles,
     },
@@ -46,20 +72,46 @@ pub mod pathfinding;
 pub mod effects;
 pub mod health;
 pub mod animation;
+pub mod floaters;
+pub mod elite;
+pub mod companion;
+pub mod behavior;
+pub mod spatial;
 
 pub struct ActorsPlugin;
 impl Plugin for ActorsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<PlayerTrackingParticles>()
+            .init_resource::<FloaterPool>()
+            .init_resource::<SpatialIndex>()
+            .init_resource::<DeathCause>()
+            .init_resource::<MiningProgress>()
             .add_event::<DamageEvent>()
+            .add_event::<NoiseEvent>()
             .add_plugins(InputManagerPlugin::<PlayerActions>::default())
-            .add_systems(OnEnter(GameState::LevelInitialization), despawn_component::<Enemy>)
+            .add_systems(OnEnter(GameState::LevelInitialization), (
+                despawn_component::<Enemy>,
+                despawn_component::<HealthBar>,
+                despawn_component::<Summon>,
+                despawn_component::<AlertIndicator>,
+            ))
             .add_systems(OnEnter(GameState::LevelInitialization), player_reset_position)
-            .add_systems(OnExit(GameState::GameOver), despawn_component::<Enemy>)
+            .add_systems(OnEnter(GameState::LevelInitialization), hide_damage_floaters)
+            .add_systems(OnEnter(GameState::LevelInitialization), |mut progress: ResMut<MiningProgress>| {
+                progress.clear();
+            })
+            .add_systems(OnExit(GameState::GameOver), (
+                despawn_component::<Enemy>,
+                despawn_component::<HealthBar>,
+                despawn_component::<Summon>,
+                despawn_component::<AlertIndicator>,
+            ))
             .add_systems(OnEnter(GameState::GameOver), (
                 despawn_component::<Player>,
+                hide_damage_floaters,
                 move |mut commands: Commands, audio_assets: Res<AudioAssetCollection>| {
                     commands.spawn((
+                        AudioBus::Sfx,
                         AudioBundle {
                             source: audio_assets.death.clone(),
                             settings: PlaybackSettings::DESPAWN,
@@ -67,28 +119,60 @@ impl Plugin for ActorsPlugin {
                     ));
                 },
             ))
-            .add_systems(OnEnter(GameState::Setup), player_setup)
+            .add_systems(OnEnter(GameState::Setup), (
+                player_setup,
+                |mut death_cause: ResMut<DeathCause>| {
+                    death_cause.0 = None;
+                },
+            ))
             .add_systems(
                 Update,
                 (
                     toggle_actors,
+                    player_join,
                     player_jump,
-                    (player_attack, player_synchronize_attack_rotation).chain(),
-                    player_dash,
-                    player_hook,
+                    (player_attack, player_synchronize_attack_rotation, fade_parry_flashes).chain(),
+                    (player_dash, player_roll, player_dash_through).chain(),
+                    player_hook.run_if(not(resource_exists::<NoHook>)),
                     player_shoot,
                     player_collect_sand,
+                    player_summon,
                     (player_prune_empty_materials, player_switch_material).chain(),
+                    update_damage_floaters,
+                    hear_noises,
                 ).run_if(in_state(GameState::Game))
             )
-            .add_systems(PreUpdate, store_camera_position.run_if(in_state(GameState::Game)))
+            .add_systems(
+                Update,
+                update_player_input_map.run_if(resource_changed::<Persistent<Config>>)
+            )
+            .add_systems(
+                PreUpdate,
+                store_camera_position
+                    .after(InputManagerSystem::Update)
+                    .run_if(in_state(GameState::Game))
+            )
             .add_systems(
                 PreUpdate,
                 (pathfind_start, pathfind_apply).chain().run_if(in_state(GameState::Game))
             )
             .add_systems(
                 FixedUpdate,
-                (player_jump_extend, player_run, update_actors, enemy_update)
+                (
+                    player_jump_extend,
+                    player_crouch,
+                    player_run,
+                    player_swim,
+                    player_ledge_grab,
+                    object_contacts,
+                    update_actors,
+                    update_spatial_index,
+                    enemy_update,
+                    enemy_dig,
+                    elite_trail,
+                    turret_update,
+                    companion_update,
+                )
                     .chain()
                     .run_if(in_state(GameState::Game))
                     .before(unfill_objects)
@@ -99,17 +183,24 @@ impl Plugin for ActorsPlugin {
                     update_player_rotation,
                     update_enemy_rotation,
                     update_actor_translation,
-                    // update_health_bar_translation,
+                    update_health_bar_translation,
                 ).run_if(in_state(GameState::Game))
             )
             .add_systems(
                 PostUpdate,
                 (
                     update_rope_position,
+                    burning,
+                    drowning,
+                    spawn_damage_floaters,
                     process_damage_events,
+                    companion_damage,
                     damage_flash,
+                    status_tint,
+                    drop_loot,
                     death,
-                    // update_health_bars,
+                    update_health_bars,
+                    update_alert_indicators,
                     tick_iframes,
                 )
                     .chain()
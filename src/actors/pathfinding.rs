@@ -8,10 +8,11 @@ use crate::{
         chunk_groups::build_chunk_group,
         chunk_manager::ChunkManager,
         materials::PhysicsType,
+        pixel::Pixel,
     },
 };
 
-use super::{ actor::Actor, enemy::Enemy, player::Player };
+use super::{ actor::Actor, behavior::Behaviors, enemy::Enemy };
 
 #[derive(Component)]
 pub struct Path {
@@ -33,32 +34,74 @@ const DIRECTIONS: [(i32, i32); 8] = [
     (1, 1),
 ];
 
+// cost of digging out a pixel per point of its durability, on top of the cost of the step
+const DIG_COST: f32 = 0.25;
+
+fn is_passable(pixel: &Pixel) -> bool {
+    matches!(pixel.physics_type, PhysicsType::Air | PhysicsType::Gas(..) | PhysicsType::Liquid(..))
+}
+
+/// Durability of a pixel diggers have to get through, powders without one are the softest
+/// and static pixels without one cannot be dug at all
+pub fn material_hardness(pixel: &Pixel) -> Option<f32> {
+    match pixel.physics_type {
+        PhysicsType::Powder => Some(pixel.durability.unwrap_or(0.0)),
+        PhysicsType::Static => pixel.durability,
+        _ => None,
+    }
+}
+
+/// Pixels in the way of an actor of the size at the position that can be dug out
+pub fn blocking_pixels(
+    chunk_manager: &ChunkManager,
+    position: IVec2,
+    size: Vec2,
+    hardness: f32
+) -> Vec<IVec2> {
+    let size = (size / 2.0).round().as_ivec2();
+
+    (-size.x..=size.x)
+        .cartesian_product(-size.y..=size.y)
+        .map(|(x, y)| position + IVec2::new(x, y))
+        .filter(|position| {
+            chunk_manager
+                .get(*position)
+                .is_ok_and(|pixel| {
+                    !is_passable(pixel) &&
+                        material_hardness(pixel).is_some_and(|pixel_hardness| pixel_hardness <= hardness)
+                })
+        })
+        .collect_vec()
+}
+
 pub fn pathfind_start(
     mut commands: Commands,
     mut chunk_manager: ResMut<ChunkManager>,
     mut actors: Query<
-        (Entity, &Actor, &Transform, Option<&Path>),
+        (Entity, &Actor, &Transform, &Behaviors, Option<&Path>),
         (With<Enemy>, Without<PathGenerationTask>)
     >,
-    player_q: Query<&Transform, (With<Player>, Without<Enemy>)>,
     time: Res<Time>
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
-    let player_position = (player_q.single().translation.xy() * (CHUNK_SIZE as f32))
-        .round()
-        .as_ivec2();
 
-    for (entity, actor, transform, path) in actors.iter_mut() {
+    for (entity, actor, transform, behaviors, path) in actors.iter_mut() {
+        let hardness = behaviors.dig_hardness();
         let position = (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2();
 
-        let chunk_position = ((player_position + position) / 2).div_euclid(
-            IVec2::splat(CHUNK_SIZE)
-        );
+        // paths lead to the last known position of the target, only while the enemy is alerted
+        let target = behaviors.target.filter(|_| behaviors.is_alerted(time.elapsed_seconds_f64()));
 
-        if (player_position - position).abs().max_element() > CHUNK_SIZE * 2 {
+        let Some(target) = target.filter(|target| {
+            (*target - position).abs().max_element() <= CHUNK_SIZE * 2
+        }) else {
             commands.entity(entity).remove::<Path>().remove::<PathGenerationTask>();
             continue;
-        }
+        };
+
+        let chunk_position = ((target + position) / 2).div_euclid(
+            IVec2::splat(CHUNK_SIZE)
+        );
 
         if path.map_or(true, |path| time.elapsed_seconds_f64() - path.created_at > 0.1) {
             let size = actor.size;
@@ -72,43 +115,53 @@ pub fn pathfind_start(
                     let node_size = 4;
 
                     let start = (position - chunk_position * CHUNK_SIZE) / node_size;
-                    let end = (player_position - chunk_position * CHUNK_SIZE) / node_size;
-
-                    let path = pathfinding::prelude::astar(
-                        &(start.x, start.y),
-                        |&(x, y)| {
-                            let directions = DIRECTIONS.iter()
-                                .map(|(dx, dy)| (x + dx, y + dy))
-                                .collect_vec();
-
-                            directions
-                                .into_iter()
-                                .filter(|(x, y)| {
-                                    let node_position = IVec2::new(*x, *y);
-                                    let world_position = node_position * node_size + node_size / 2;
-
-                                    let size = (size / 2.0).round().as_ivec2();
-
-                                    (-size.x..=size.x)
-                                        .cartesian_product(-size.y..=size.y)
-                                        .all(|(x, y)|
-                                            chunk_group
-                                                .get(world_position + IVec2::new(x, y))
-                                                .map_or(false, |pixel|
-                                                    matches!(
-                                                        pixel.physics_type,
-                                                        PhysicsType::Air |
-                                                            PhysicsType::Gas(..) |
-                                                            PhysicsType::Liquid(..)
-                                                    )
-                                                )
-                                        )
-                                })
-                                .map(|node| (node, 1))
-                        },
-                        |&(x, y)| (end.x - x).abs() + (end.y - y).abs(),
-                        |&(x, y)| (IVec2::new(x, y) - end).abs().cmple(IVec2::ONE).all()
-                    );
+                    let end = (target - chunk_position * CHUNK_SIZE) / node_size;
+
+                    let size = (size / 2.0).round().as_ivec2();
+
+                    // nodes the actor does not fit in are skipped, unless the pixels in the way
+                    // can be dug out with the hardness, which makes the step more expensive
+                    let node_cost = |x: i32, y: i32, hardness: Option<f32>| -> Option<i32> {
+                        let world_position = IVec2::new(x, y) * node_size + node_size / 2;
+                        let mut cost = 1.0;
+
+                        for (x, y) in (-size.x..=size.x).cartesian_product(-size.y..=size.y) {
+                            let pixel = chunk_group.get(world_position + IVec2::new(x, y))?;
+
+                            if is_passable(pixel) {
+                                continue;
+                            }
+
+                            let pixel_hardness = material_hardness(pixel).filter(|pixel_hardness| {
+                                hardness.is_some_and(|hardness| *pixel_hardness <= hardness)
+                            })?;
+
+                            cost += DIG_COST * (1.0 + pixel_hardness);
+                        }
+
+                        Some(cost.ceil() as i32)
+                    };
+
+                    let search = |hardness: Option<f32>| {
+                        pathfinding::prelude::astar(
+                            &(start.x, start.y),
+                            |&(x, y)| {
+                                DIRECTIONS.iter()
+                                    .map(|(dx, dy)| (x + dx, y + dy))
+                                    .filter_map(|(x, y)| {
+                                        node_cost(x, y, hardness).map(|cost| ((x, y), cost))
+                                    })
+                                    .collect_vec()
+                            },
+                            |&(x, y)| (end.x - x).abs() + (end.y - y).abs(),
+                            |&(x, y)| (IVec2::new(x, y) - end).abs().cmple(IVec2::ONE).all()
+                        )
+                    };
+
+                    // digging is only considered once there is no open way to the target
+                    let path = search(None).or_else(|| {
+                        hardness.and_then(|hardness| search(Some(hardness)))
+                    });
 
 
                     path.map(|(path, _)| {
@@ -9,45 +9,53 @@ use bevy::render::view::RenderLayers;
 use bevy::{
     prelude::*,
     render::{ mesh::{ Indices, PrimitiveTopology }, render_asset::RenderAssetUsages },
-    sprite::{ MaterialMesh2dBundle, Mesh2dHandle },
+    sprite::{ Anchor, MaterialMesh2dBundle, Mesh2dHandle },
+    utils::{ HashMap, HashSet },
     window::PrimaryWindow,
 };
 use bevy_math::{ ivec2, vec2, vec3 };
 use bevy_rapier2d::geometry::Sensor;
 use bevy_rapier2d::{
-    dynamics::{ ImpulseJoint, SpringJointBuilder, Velocity },
+    dynamics::{ ImpulseJoint, JointAxis, SpringJointBuilder, Velocity },
     geometry::{ Collider, ColliderMassProperties, CollisionGroups, Group },
 };
 use bevy_rapier2d::{ pipeline::QueryFilter, plugin::RapierContext };
 use indexmap::IndexMap;
 use itertools::Itertools;
-use leafwing_input_manager::buttonlike::MouseWheelDirection;
+use bevy_persistent::Persistent;
 use leafwing_input_manager::{
     action_state::ActionState,
-    axislike::VirtualAxis,
     input_map::InputMap,
     Actionlike,
     InputManagerBundle,
 };
 use seldom_state::{ prelude::{ AnyState, StateMachine }, trigger::IntoTrigger };
+use serde::{ Deserialize, Serialize };
 
 use crate::{
     animation::{ Animation, AnimationState, DespawnOnFinish },
     assets::{ AudioAssetCollection, SpriteAssetCollection },
-    camera::{ TrackingCamera, ACTOR_RENDER_LAYER, LIGHTING_RENDER_LAYER },
+    bucket::Carrying,
+    camera::{ TrackingCamera, WorldRenderTarget, ACTOR_RENDER_LAYER, LIGHTING_RENDER_LAYER },
     constants::{ CHUNK_SIZE, PARTICLE_Z, PLAYER_Z },
+    gameplay::Swing,
+    perks::PerkAbilities,
     raycast::raycast,
     registries::Registries,
+    score_export::RunStats,
+    settings::{ AudioBus, Config, Profile },
     simulation::{
+        chunk::Chunk,
         chunk_groups::build_chunk_group,
         chunk_manager::ChunkManager,
-        colliders::{ ENEMY_MASK, HITBOX_MASK, PLAYER_MASK },
+        colliders::{ ENEMY_MASK, HITBOX_MASK, OBJECT_MASK, PLAYER_MASK },
         dirty_rect::DirtyRects,
         materials::PhysicsType,
-        object::{ Object, ObjectBundle, Projectile },
-        particle::{ Particle, ParticleBundle, ParticleMovement },
+        object::{ Object, ObjectBundle, Projectile, ProjectileOwner },
+        particle::{ Particle, ParticleBundle, ParticleMovement, Particles },
         pixel::Pixel,
     },
+    warmup::AtlasLayouts,
 };
 
 use super::{
@@ -70,8 +78,9 @@ use super::{
         LandAnimation,
         MoveAnimation,
     },
+    behavior::NoiseEvent,
     enemy::Enemy,
-    health::{ DamageEvent, KnockbackResistance },
+    health::{ DamageCause, DamageEvent, DamageType, IFrames, Resistances },
 };
 
 use bitflags::bitflags;
@@ -79,19 +88,31 @@ use bitflags::bitflags;
 pub const ATLAS_COLUMNS: usize = 9;
 pub const ATLAS_ROWS: usize = 18;
 
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect, Serialize, Deserialize)]
 pub enum PlayerActions {
     Run,
     Crouch,
     Jump,
+    Swim,
     Attack,
     Dash,
+    Roll,
     Hook,
     Shoot,
+    Throw,
+    Summon,
+    Reel,
     Collect,
     Interaction,
+    DropObject,
+    Torch,
+    Storage,
+    SkipTutorial,
     SelectMaterialNext,
     SelectMaterialPrevious,
+    Aim,
+    ZoomIn,
+    ZoomOut,
 }
 
 #[derive(Component, Clone)]
@@ -101,6 +122,26 @@ struct JumpIntroAnimation;
 #[derive(Component, Clone)]
 #[component(storage = "SparseSet")]
 struct DashAnimation;
+
+#[derive(Component, Clone)]
+#[component(storage = "SparseSet")]
+struct RollAnimation;
+
+#[derive(Component, Clone)]
+#[component(storage = "SparseSet")]
+struct CrouchAnimation;
+
+#[derive(Component, Clone)]
+#[component(storage = "SparseSet")]
+struct CrawlAnimation;
+
+#[derive(Component, Clone)]
+#[component(storage = "SparseSet")]
+struct HangAnimation;
+
+#[derive(Component, Clone)]
+#[component(storage = "SparseSet")]
+struct ClimbAnimation;
 bitflags! {
     #[derive(Default, Component, Clone)]
     pub struct PlayerFlags: u32 {
@@ -110,35 +151,78 @@ bitflags! {
         const SHOOT = 1 << 3;
         const HOOKED = 1 << 4;
         const ATTACKING = 1 << 5;
+        const ROLLING = 1 << 6;
+        const CROUCHING = 1 << 7;
+        const HANGING = 1 << 8;
+        const CLIMBING = 1 << 9;
     }
 }
 
 #[derive(Component, Clone)]
 pub struct Player;
 
+// radius of the light every player carries around in pixels
+pub const PLAYER_LIGHT_RADIUS: f32 = 6.0;
+
+/// Light following the player on the lighting layer
+#[derive(Component)]
+pub struct PlayerLight;
+
+/// Player that joined with a gamepad during the run, only that gamepad controls it
+#[derive(Component, Clone, Copy)]
+pub struct CoopPlayer(pub Gamepad);
+
+/// Player of the other client in a networked session, its actions are set from received packets
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+#[derive(Component)]
+pub struct RemotePlayer;
+
+// horizontal distance in pixels between spawned players
+const COOP_SPAWN_OFFSET: f32 = 12.0;
+
+const PLAYER_SIZE: Vec2 = Vec2::new(10.0, 17.0);
+// height in pixels of the crouching player, low enough to crawl through one tile tunnels
+const CROUCH_HEIGHT: f32 = 9.0;
+// size in pixels of a frame of the player atlas
+const PLAYER_FRAME_SIZE: f32 = 48.0;
+
+/// Colliders of the player used for objects and hitboxes, the standing capsule is taller than the body
+fn player_collider(size: Vec2) -> Collider {
+    if size.y < size.x {
+        Collider::capsule_x((size.x - size.y) / 2.0, size.y / 2.0)
+    } else {
+        Collider::capsule_y(size.y / 2.0 - size.x / 2.0 - 2.5, size.x / 2.0 + 4.0)
+    }
+}
+
 pub fn player_reset_position(
-    mut player_q: Query<(&mut Actor, &mut Transform), With<Player>>,
+    mut player_q: Query<(&mut Actor, &mut Transform, Has<CoopPlayer>), With<Player>>,
     mut camera_q: Query<&mut TrackingCamera>
 ) {
-    if let Ok((mut actor, mut transform)) = player_q.get_single_mut() {
-        actor.position = vec2(-actor.size.x / 2.0, 0.0);
-        transform.translation.x = 0.0;
+    let mut camera_position = Vec2::new(0.0, 17.0 / 2.0) / (CHUNK_SIZE as f32);
+
+    for (mut actor, mut transform, is_coop) in player_q.iter_mut() {
+        let offset = if is_coop { COOP_SPAWN_OFFSET } else { 0.0 };
+
+        actor.position = vec2(-actor.size.x / 2.0 + offset, 0.0);
+        transform.translation.x = offset / (CHUNK_SIZE as f32);
         transform.translation.y = actor.size.y / 2.0;
-        camera_q
-            .single_mut()
-            .set_position((actor.position + actor.size / 2.0) / (CHUNK_SIZE as f32));
-        return;
-    } else {
-        camera_q.single_mut().set_position(Vec2::new(0.0, 17.0 / 2.0) / (CHUNK_SIZE as f32));
+
+        if !is_coop {
+            camera_position = (actor.position + actor.size / 2.0) / (CHUNK_SIZE as f32);
+        }
     }
+
+    camera_q.single_mut().set_position(camera_position);
 }
 
 pub fn player_setup(
     mut commands: Commands,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    sprites: Res<SpriteAssetCollection>
+    sprites: Res<SpriteAssetCollection>,
+    layouts: Res<AtlasLayouts>,
+    config: Res<Persistent<Config>>
 ) {
     let mut player_materials = PlayerMaterials::default();
     player_materials.insert("healium".into(), 100.0);
@@ -146,6 +230,74 @@ pub fn player_setup(
     commands.insert_resource(player_materials);
     commands.insert_resource(PlayerSelectedMaterial::default());
 
+    spawn_player(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &sprites,
+        &layouts,
+        config.input_map(),
+        vec2(-5.0, 0.0)
+    );
+}
+
+/// Second player joins once start is pressed on a gamepad, the first player is left with keyboard and mouse
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn player_join(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut player_q: Query<(&Actor, &mut InputMap<PlayerActions>, Has<CoopPlayer>), With<Player>>,
+    sprites: Res<SpriteAssetCollection>,
+    layouts: Res<AtlasLayouts>,
+    config: Res<Persistent<Config>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>
+) {
+    if player_q.iter().any(|(_, _, is_coop)| is_coop) {
+        return;
+    }
+
+    let Some(gamepad) = gamepads
+        .iter()
+        .find(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::Start))
+        }) else {
+        return;
+    };
+
+    let Some(position) = player_q.iter_mut().next().map(|(actor, mut input_map, _)| {
+        *input_map = config.keyboard_input_map();
+        actor.position
+    }) else {
+        return;
+    };
+
+    let mut input_map = Config::gamepad_input_map();
+    input_map.set_gamepad(gamepad);
+
+    let entity = spawn_player(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &sprites,
+        &layouts,
+        input_map,
+        position + Vec2::X * COOP_SPAWN_OFFSET
+    );
+
+    commands.entity(entity).insert((Name::new("Second player"), CoopPlayer(gamepad)));
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    sprites: &SpriteAssetCollection,
+    layouts: &AtlasLayouts,
+    input_map: InputMap<PlayerActions>,
+    position: Vec2
+) -> Entity {
     let mut entity_commands = commands.spawn((
         Name::new("Player"),
         Player,
@@ -153,8 +305,8 @@ pub fn player_setup(
         SpatialListener::new(0.5),
         ActorBundle {
             actor: Actor {
-                position: vec2(-5.0, 0.0),
-                size: Vec2::new(10.0, 17.0),
+                position,
+                size: PLAYER_SIZE,
                 movement_type: MovementType::Walking {
                     speed: 1.5,
                     jump_height: 1.0,
@@ -162,23 +314,15 @@ pub fn player_setup(
                 ..Default::default()
             },
             mass_properties: ColliderMassProperties::Density(0.25),
-            collider: Collider::capsule_y(17.0 / 2.0 - 10.0 / 2.0 - 2.5, 10.0 / 2.0 + 4.0),
+            collider: player_collider(PLAYER_SIZE),
             sprite: SpriteSheetBundle {
                 texture: sprites.player.clone(),
                 atlas: TextureAtlas {
-                    layout: texture_atlas_layouts.add(
-                        TextureAtlasLayout::from_grid(
-                            Vec2::new(48.0, 48.0),
-                            ATLAS_COLUMNS,
-                            ATLAS_ROWS,
-                            None,
-                            None
-                        )
-                    ),
+                    layout: layouts.player.clone(),
                     index: 0,
                 },
                 transform: Transform {
-                    translation: vec3(0.0, 17.0 / 2.0, PLAYER_Z),
+                    translation: vec3((position.x + 5.0) / (CHUNK_SIZE as f32), 17.0 / 2.0, PLAYER_Z),
                     scale: Vec3::splat(1.0 / (CHUNK_SIZE as f32)),
                     ..Default::default()
                 },
@@ -190,7 +334,22 @@ pub fn player_setup(
 
     let run_trigger = create_run_trigger(0.25);
 
+    let stand_trigger = move |In(entity): In<Entity>, player_q: Query<&PlayerFlags, With<Player>>| {
+        match player_q.get(entity).unwrap().contains(PlayerFlags::CROUCHING) {
+            true => Err(()),
+            false => Ok(()),
+        }
+    };
+
+    let release_trigger = move |In(entity): In<Entity>, player_q: Query<&PlayerFlags, With<Player>>| {
+        match player_q.get(entity).unwrap().contains(PlayerFlags::HANGING) {
+            true => Err(()),
+            false => Ok(()),
+        }
+    };
+
     let jump_start_trigger = move |
+        In(entity): In<Entity>,
         player_q: Query<
             (
                 &PlayerFlags,
@@ -201,7 +360,9 @@ pub fn player_setup(
             With<Player>
         >
     | {
-        let (flags, intro_animation, process_animation, exit_animation) = player_q.single();
+        let (flags, intro_animation, process_animation, exit_animation) = player_q
+            .get(entity)
+            .unwrap();
 
         match
             flags.contains(PlayerFlags::JUMPING) &&
@@ -220,8 +381,11 @@ pub fn player_setup(
         IdleAnimation,
         StateMachine::default()
             .trans::<AnyState, _>(
-                move |player_q: Query<(Option<&DashAnimation>, &PlayerFlags), With<Player>>| {
-                    let (animation, flags) = player_q.get_single().unwrap();
+                move |
+                    In(entity): In<Entity>,
+                    player_q: Query<(Option<&DashAnimation>, &PlayerFlags), With<Player>>
+                | {
+                    let (animation, flags) = player_q.get(entity).unwrap();
 
                     if flags.contains(PlayerFlags::DASHING) && animation.is_none() {
                         Ok(())
@@ -232,8 +396,26 @@ pub fn player_setup(
                 DashAnimation
             )
             .trans::<AnyState, _>(
-                move |player_q: Query<(Option<&AttackAnimation>, &PlayerFlags), With<Player>>| {
-                    let (animation, flags) = player_q.get_single().unwrap();
+                move |
+                    In(entity): In<Entity>,
+                    player_q: Query<(Option<&RollAnimation>, &PlayerFlags), With<Player>>
+                | {
+                    let (animation, flags) = player_q.get(entity).unwrap();
+
+                    if flags.contains(PlayerFlags::ROLLING) && animation.is_none() {
+                        Ok(())
+                    } else {
+                        Err(())
+                    }
+                },
+                RollAnimation
+            )
+            .trans::<AnyState, _>(
+                move |
+                    In(entity): In<Entity>,
+                    player_q: Query<(Option<&AttackAnimation>, &PlayerFlags), With<Player>>
+                | {
+                    let (animation, flags) = player_q.get(entity).unwrap();
 
                     if flags.contains(PlayerFlags::ATTACKING) && animation.is_none() {
                         Ok(())
@@ -243,35 +425,108 @@ pub fn player_setup(
                 },
                 AttackAnimation
             )
+            .trans::<AnyState, _>(
+                move |
+                    In(entity): In<Entity>,
+                    player_q: Query<(&PlayerFlags, &Actor), With<Player>>,
+                    crouch_q: Query<(), With<CrouchAnimation>>,
+                    crawl_q: Query<(), With<CrawlAnimation>>
+                | {
+                    let (flags, actor) = player_q.get(entity).unwrap();
+
+                    if
+                        flags.contains(PlayerFlags::CROUCHING) &&
+                        !flags.intersects(
+                            PlayerFlags::DASHING | PlayerFlags::ROLLING | PlayerFlags::ATTACKING
+                        ) &&
+                        actor.flags.contains(ActorFlags::GROUNDED) &&
+                        !crouch_q.contains(entity) &&
+                        !crawl_q.contains(entity)
+                    {
+                        Ok(())
+                    } else {
+                        Err(())
+                    }
+                },
+                CrouchAnimation
+            )
+            .trans::<CrouchAnimation, _>(run_trigger, CrawlAnimation)
+            .trans::<CrawlAnimation, _>(run_trigger.not(), CrouchAnimation)
+            .trans::<CrouchAnimation, _>(stand_trigger, IdleAnimation)
+            .trans::<CrawlAnimation, _>(stand_trigger, IdleAnimation)
+            .trans::<AnyState, _>(
+                move |
+                    In(entity): In<Entity>,
+                    player_q: Query<&PlayerFlags, With<Player>>,
+                    hang_q: Query<(), With<HangAnimation>>,
+                    climb_q: Query<(), With<ClimbAnimation>>
+                | {
+                    let flags = player_q.get(entity).unwrap();
+
+                    if
+                        flags.contains(PlayerFlags::HANGING) &&
+                        !flags.contains(PlayerFlags::ATTACKING) &&
+                        !hang_q.contains(entity) &&
+                        !climb_q.contains(entity)
+                    {
+                        Ok(())
+                    } else {
+                        Err(())
+                    }
+                },
+                HangAnimation
+            )
+            .trans::<HangAnimation, _>(
+                move |In(entity): In<Entity>, player_q: Query<&PlayerFlags, With<Player>>| {
+                    match player_q.get(entity).unwrap().contains(PlayerFlags::CLIMBING) {
+                        true => Ok(()),
+                        false => Err(()),
+                    }
+                },
+                ClimbAnimation
+            )
+            .trans::<HangAnimation, _>(release_trigger, IdleAnimation)
+            .trans::<ClimbAnimation, _>(release_trigger, IdleAnimation)
             .trans::<IdleAnimation, _>(run_trigger, MoveAnimation)
             .trans::<MoveAnimation, _>(run_trigger.not(), IdleAnimation)
             .trans::<AnyState, _>(jump_start_trigger, JumpIntroAnimation)
             .trans::<DashAnimation, _>(create_animation_end_trigger(), IdleAnimation)
+            .trans::<RollAnimation, _>(create_animation_end_trigger(), IdleAnimation)
             .trans::<AttackAnimation, _>(create_animation_end_trigger(), IdleAnimation)
             .trans::<JumpIntroAnimation, _>(create_animation_end_trigger(), JumpAnimation)
-            .trans::<JumpAnimation, _>(move |player_q: Query<&Actor, With<Player>>| {
-                match player_q.single().flags.contains(ActorFlags::GROUNDED) {
+            .trans::<JumpAnimation, _>(move |
+                In(entity): In<Entity>,
+                player_q: Query<&Actor, With<Player>>
+            | {
+                match player_q.get(entity).unwrap().flags.contains(ActorFlags::GROUNDED) {
                     true => Ok(()),
                     false => Err(()),
                 }
             }, LandAnimation)
-            .trans::<JumpAnimation, _>(move |player_q: Query<&Velocity, With<Player>>| {
-                match player_q.single().linvel.y < 0.0 {
+            .trans::<JumpAnimation, _>(move |
+                In(entity): In<Entity>,
+                player_q: Query<&Velocity, With<Player>>
+            | {
+                match player_q.get(entity).unwrap().linvel.y < 0.0 {
                     true => Ok(()),
                     false => Err(()),
                 }
             }, FallAnimation)
             .trans::<AnyState, _>(
                 move |
+                    In(entity): In<Entity>,
                     player_q: Query<
                         (&PlayerFlags, &Actor, &Velocity, Option<&FallAnimation>),
                         With<Player>
                     >
                 | {
-                    let (flags, actor, velocity, falling_animation) = player_q.single();
+                    let (flags, actor, velocity, falling_animation) = player_q
+                        .get(entity)
+                        .unwrap();
 
                     match
                         ((!flags.contains(PlayerFlags::DASHING) &&
+                            !flags.contains(PlayerFlags::ROLLING) &&
                             !flags.contains(PlayerFlags::ATTACKING) &&
                             falling_animation.is_none() &&
                             velocity.linvel.y < -1.0) ||
@@ -285,8 +540,11 @@ pub fn player_setup(
                 },
                 FallAnimation
             )
-            .trans::<FallAnimation, _>(move |player_q: Query<&Actor, With<Player>>| {
-                let actor = player_q.single();
+            .trans::<FallAnimation, _>(move |
+                In(entity): In<Entity>,
+                player_q: Query<&Actor, With<Player>>
+            | {
+                let actor = player_q.get(entity).unwrap();
                 match actor.flags.contains(ActorFlags::GROUNDED) {
                     true => Ok(()),
                     false => Err(()),
@@ -311,6 +569,42 @@ pub fn player_setup(
                 );
                 entity.insert(AnimationState::default());
             })
+            .on_enter::<CrouchAnimation>(|entity| {
+                entity.insert(
+                    Animation(
+                        benimator::Animation::from_indices(18..=18, FrameRate::from_fps(8.0)).repeat()
+                    )
+                );
+                entity.insert(AnimationState::default());
+            })
+            .on_enter::<CrawlAnimation>(|entity| {
+                entity.insert(
+                    Animation(
+                        benimator::Animation
+                            ::from_indices(18..=21, FrameRate::from_fps(8.0))
+                            .repeat()
+                    )
+                );
+                entity.insert(AnimationState::default());
+            })
+            .on_enter::<HangAnimation>(|entity| {
+                entity.insert(
+                    Animation(
+                        benimator::Animation::from_indices(126..=126, FrameRate::from_fps(8.0)).repeat()
+                    )
+                );
+                entity.insert(AnimationState::default());
+            })
+            .on_enter::<ClimbAnimation>(|entity| {
+                entity.insert(
+                    Animation(
+                        benimator::Animation
+                            ::from_indices(117..=119, FrameRate::from_fps(10.0))
+                            .once()
+                    )
+                );
+                entity.insert(AnimationState::default());
+            })
             .on_enter::<JumpIntroAnimation>(|entity| {
                 entity.insert(
                     Animation(
@@ -357,43 +651,55 @@ pub fn player_setup(
                 );
                 entity.insert(AnimationState::default());
             })
-            .on_enter::<AttackAnimation>(|entity| {
+            .on_enter::<RollAnimation>(|entity| {
                 entity.insert(
                     Animation(
-                        benimator::Animation
-                            ::from_indices(153..=155, FrameRate::from_fps(8.0))
-                            .once()
+                        benimator::Animation::from_indices(27..=34, FrameRate::from_fps(16.0)).once()
                     )
                 );
                 entity.insert(AnimationState::default());
             })
-            .command_on_exit::<AttackAnimation>(|world: &mut World| {
-                let mut flags = world.query::<&mut PlayerFlags>().single_mut(world);
-                flags.remove(PlayerFlags::ATTACKING)
+            .on_enter::<AttackAnimation>(|entity| {
+                // every swing of the combo and the heavy attack have their own frames
+                entity.add(|entity: Entity, world: &mut World| {
+                    let (frames, fps) = world
+                        .get::<AttackSwing>(entity)
+                        .map_or(((153, 155), 8.0), |swing| (swing.0.frames, swing.0.fps));
+
+                    world.entity_mut(entity).insert((
+                        Animation(
+                            benimator::Animation
+                                ::from_indices(frames.0..=frames.1, FrameRate::from_fps(fps))
+                                .once()
+                        ),
+                        AnimationState::default(),
+                    ));
+                });
+            })
+            .on_exit::<AttackAnimation>(|entity| {
+                entity.add(|entity: Entity, world: &mut World| {
+                    if let Some(mut flags) = world.get_mut::<PlayerFlags>(entity) {
+                        flags.remove(PlayerFlags::ATTACKING);
+                    }
+                });
+            })
+            .on_exit::<DashAnimation>(|entity| {
+                entity.add(|entity: Entity, world: &mut World| {
+                    if let Some(mut flags) = world.get_mut::<PlayerFlags>(entity) {
+                        flags.remove(PlayerFlags::DASHING);
+                    }
+                });
             })
-            .command_on_exit::<DashAnimation>(|world: &mut World| {
-                let mut flags = world.query::<&mut PlayerFlags>().single_mut(world);
-                flags.remove(PlayerFlags::DASHING)
+            .on_exit::<RollAnimation>(|entity| {
+                entity.add(|entity: Entity, world: &mut World| {
+                    if let Some(mut flags) = world.get_mut::<PlayerFlags>(entity) {
+                        flags.remove(PlayerFlags::ROLLING);
+                    }
+                });
             }),
     ));
 
-    entity_commands.insert((
-        InputManagerBundle::with_map(
-            InputMap::default()
-                .insert(PlayerActions::Run, VirtualAxis::ad())
-                .insert(PlayerActions::Jump, KeyCode::Space)
-                .insert(PlayerActions::Attack, KeyCode::KeyF)
-                .insert(PlayerActions::Crouch, KeyCode::KeyS)
-                .insert(PlayerActions::Dash, KeyCode::KeyQ)
-                .insert(PlayerActions::Hook, MouseButton::Right)
-                .insert(PlayerActions::Interaction, KeyCode::KeyE)
-                .insert(PlayerActions::Shoot, KeyCode::KeyR)
-                .insert(PlayerActions::Collect, KeyCode::KeyG)
-                .insert(PlayerActions::SelectMaterialNext, MouseWheelDirection::Up)
-                .insert(PlayerActions::SelectMaterialPrevious, MouseWheelDirection::Down)
-                .build()
-        ),
-    ));
+    entity_commands.insert((InputManagerBundle::with_map(input_map),));
 
     entity_commands.insert(AttackParameters {
         value: 2.0,
@@ -404,13 +710,17 @@ pub fn player_setup(
         max_storage: 100.0,
     });
 
-    entity_commands.insert(KnockbackResistance(0.0));
+    entity_commands.insert(Resistances::default());
+
+    entity_commands.insert(PerkAbilities::default());
+
+    entity_commands.insert(MiningTool::default());
 
     entity_commands.with_children(|parent| {
         parent.spawn((
             ColliderMassProperties::Mass(0.0),
             ActorHitboxBundle {
-                collider: Collider::capsule_y(17.0 / 2.0 - 10.0 / 2.0 - 2.5, 10.0 / 2.0 + 4.0),
+                collider: player_collider(PLAYER_SIZE),
                 collision_groups: CollisionGroups::new(
                     Group::from_bits_retain(PLAYER_MASK | HITBOX_MASK),
                     Group::from_bits_retain(ENEMY_MASK)
@@ -421,8 +731,9 @@ pub fn player_setup(
 
         parent.spawn((
             Name::new("Player's lighting"),
+            PlayerLight,
             ColorMesh2dBundle {
-                mesh: meshes.add(Mesh::from(Circle::new(6.0))).into(),
+                mesh: meshes.add(Mesh::from(Circle::new(PLAYER_LIGHT_RADIUS))).into(),
                 material: materials.add(Color::WHITE.with_a(0.5)),
                 transform: Transform::from_xyz(0.0, 0.0, -10.0),
                 ..Default::default()
@@ -448,6 +759,8 @@ pub fn player_setup(
         //     ),
         // ));
     });
+
+    entity_commands.id()
 }
 
 pub const RUN_SPEED: f32 = 1.5;
@@ -455,30 +768,60 @@ pub const JUMP_MAG: f32 = 1.0;
 pub const PRESSED_JUMP_MAG: f32 = 0.025;
 pub const JUMP_EXTENSION_MS: u64 = 500;
 pub const JUMP_BUFFER_MS: u64 = 100;
+pub const SWIM_SPEED: f32 = 1.0;
+pub const SWIM_STROKE: f32 = 0.08;
+// share of the running speed kept while crawling
+pub const CRAWL_SPEED: f32 = 0.4;
 
 #[derive(Component, Deref, DerefMut)]
 #[component(storage = "SparseSet")]
 pub struct JumpBuffer(Timer);
 
+pub fn update_player_input_map(
+    mut player_q: Query<(&mut InputMap<PlayerActions>, Option<&CoopPlayer>), With<Player>>,
+    config: Res<Persistent<Config>>
+) {
+    let has_coop = player_q.iter().any(|(_, coop)| coop.is_some());
+
+    for (mut input_map, coop) in player_q.iter_mut() {
+        *input_map = match coop {
+            Some(CoopPlayer(gamepad)) => {
+                let mut input_map = Config::gamepad_input_map();
+                input_map.set_gamepad(*gamepad);
+                input_map
+            }
+            None if has_coop => config.keyboard_input_map(),
+            None => config.input_map(),
+        };
+    }
+}
+
 pub fn player_run(
     mut player: Query<
         (&mut Actor, &mut Velocity, &mut PlayerFlags, &ActionState<PlayerActions>),
         With<Player>
     >
 ) {
-    let (mut actor, mut velocity, flags, action_state) = player.single_mut();
-
-    let delta_velocity = (action_state.value(&PlayerActions::Run) * RUN_SPEED) / 8.0;
+    for (mut actor, mut velocity, flags, action_state) in player.iter_mut() {
+        let delta_velocity = (action_state.value(&PlayerActions::Run) * RUN_SPEED) / 8.0;
 
-    if flags.contains(PlayerFlags::HOOKED) {
-        velocity.linvel.x += delta_velocity / 2.0;
-        actor.flags.insert(ActorFlags::INFLUENCED);
-    } else if velocity.linvel.x.abs() > RUN_SPEED {
-        if velocity.linvel.x.signum() != delta_velocity.signum() {
-            velocity.linvel.x += delta_velocity;
+        if flags.contains(PlayerFlags::HOOKED) {
+            velocity.linvel.x += delta_velocity / 2.0;
+            actor.flags.insert(ActorFlags::INFLUENCED);
+        } else if flags.contains(PlayerFlags::CROUCHING) {
+            let max_speed = RUN_SPEED * CRAWL_SPEED;
+            velocity.linvel.x = f32::clamp(
+                velocity.linvel.x + delta_velocity * CRAWL_SPEED,
+                -max_speed,
+                max_speed
+            );
+        } else if velocity.linvel.x.abs() > RUN_SPEED {
+            if velocity.linvel.x.signum() != delta_velocity.signum() {
+                velocity.linvel.x += delta_velocity;
+            }
+        } else {
+            velocity.linvel.x = f32::clamp(velocity.linvel.x + delta_velocity, -RUN_SPEED, RUN_SPEED);
         }
-    } else {
-        velocity.linvel.x = f32::clamp(velocity.linvel.x + delta_velocity, -RUN_SPEED, RUN_SPEED);
     }
 }
 
@@ -497,321 +840,801 @@ pub fn player_jump(
     >,
     time: Res<Time>
 ) {
-    let (entity, actor, mut velocity, mut flags, action_state, mut jump_buffer) =
-        player.single_mut();
-
-    let can_jump =
-        actor.flags.contains(ActorFlags::GROUNDED) && velocity.linvel.y.is_sign_negative();
+    for (entity, actor, mut velocity, mut flags, action_state, mut jump_buffer) in player.iter_mut() {
+        // head above the surface lets the player jump out of the liquid
+        let can_jump =
+            !flags.contains(PlayerFlags::CROUCHING) &&
+            ((actor.flags.contains(ActorFlags::GROUNDED) && velocity.linvel.y.is_sign_negative()) ||
+                (actor.flags.contains(ActorFlags::SWIMMING) &&
+                    !actor.flags.contains(ActorFlags::UNDERWATER)));
 
-    if
-        flags.contains(PlayerFlags::JUMPING) &&
-        actor.flags.contains(ActorFlags::GROUNDED) &&
-        velocity.linvel.y.is_sign_negative()
-    {
-        flags.remove(PlayerFlags::JUMPING);
-    }
+        if
+            flags.contains(PlayerFlags::JUMPING) &&
+            actor.flags.contains(ActorFlags::GROUNDED) &&
+            velocity.linvel.y.is_sign_negative()
+        {
+            flags.remove(PlayerFlags::JUMPING);
+        }
 
-    if let Some(buffer) = jump_buffer.as_mut() {
-        buffer.tick(time.delta());
+        if let Some(buffer) = jump_buffer.as_mut() {
+            buffer.tick(time.delta());
 
-        if buffer.finished() {
-            commands.entity(entity).remove::<JumpBuffer>();
+            if buffer.finished() {
+                commands.entity(entity).remove::<JumpBuffer>();
+            }
         }
-    }
 
-    if can_jump {
-        if action_state.just_pressed(&PlayerActions::Jump) || jump_buffer.is_some() {
-            velocity.linvel.y = JUMP_MAG;
-            flags.insert(PlayerFlags::JUMPING);
-            commands.entity(entity).remove::<JumpBuffer>();
+        if can_jump {
+            if action_state.just_pressed(&PlayerActions::Jump) || jump_buffer.is_some() {
+                velocity.linvel.y = JUMP_MAG;
+                flags.insert(PlayerFlags::JUMPING);
+                commands.entity(entity).remove::<JumpBuffer>();
+            }
+        } else if action_state.just_pressed(&PlayerActions::Jump) {
+            commands
+                .entity(entity)
+                .insert(JumpBuffer(Timer::new(Duration::from_millis(JUMP_BUFFER_MS), TimerMode::Once)));
         }
-    } else if action_state.just_pressed(&PlayerActions::Jump) {
-        commands
-            .entity(entity)
-            .insert(JumpBuffer(Timer::new(Duration::from_millis(JUMP_BUFFER_MS), TimerMode::Once)));
     }
 }
 
-pub fn player_jump_extend(
-    mut player: Query<(&mut Velocity, &ActionState<PlayerActions>, &mut PlayerFlags)>
+/// Pixels actors can not walk through
+fn is_solid(pixel: &Pixel) -> bool {
+    matches!(pixel.physics_type, PhysicsType::Powder | PhysicsType::Static)
+}
+
+/// Area in pixels has no solid pixels, starting from the bottom left corner
+fn is_clear(chunk_manager: &ChunkManager, min: IVec2, size: IVec2) -> bool {
+    (0..size.x)
+        .cartesian_product(0..size.y)
+        .all(|(x, y)| !chunk_manager.get(min + ivec2(x, y)).is_ok_and(is_solid))
+}
+
+/// Pixels above a crouching player leave enough room to stand up
+fn has_headroom(chunk_manager: &ChunkManager, actor: &Actor) -> bool {
+    is_clear(
+        chunk_manager,
+        actor.position.round().as_ivec2() + ivec2(0, CROUCH_HEIGHT as i32),
+        ivec2(PLAYER_SIZE.x as i32, (PLAYER_SIZE.y - CROUCH_HEIGHT) as i32)
+    )
+}
+
+/// Crouching shrinks the player to crawl through narrow tunnels,
+/// releasing it stands the player back up once nothing is in the way
+#[allow(clippy::type_complexity)]
+pub fn player_crouch(
+    mut player_q: Query<
+        (
+            &mut Actor,
+            &mut PlayerFlags,
+            &mut Collider,
+            &mut Sprite,
+            &Children,
+            &ActionState<PlayerActions>,
+        ),
+        With<Player>
+    >,
+    mut hitbox_q: Query<&mut Collider, (With<Sensor>, Without<Player>)>,
+    chunk_manager: Res<ChunkManager>
 ) {
-    let (mut velocity, action_state, mut flags) = player.single_mut();
+    for (mut actor, mut flags, mut collider, mut sprite, children, action_state) in player_q.iter_mut() {
+        let is_crouching = flags.contains(PlayerFlags::CROUCHING);
+        let pressed = action_state.pressed(&PlayerActions::Crouch);
+
+        let size = if
+            !is_crouching &&
+            pressed &&
+            actor.flags.contains(ActorFlags::GROUNDED) &&
+            !actor.flags.contains(ActorFlags::SWIMMING)
+        {
+            flags.insert(PlayerFlags::CROUCHING);
+            vec2(PLAYER_SIZE.x, CROUCH_HEIGHT)
+        } else if is_crouching && !pressed && has_headroom(&chunk_manager, &actor) {
+            flags.remove(PlayerFlags::CROUCHING);
+            PLAYER_SIZE
+        } else {
+            continue;
+        };
 
-    if flags.contains(PlayerFlags::JUMPING) {
-        if action_state.pressed(&PlayerActions::Jump) {
-            if
-                action_state.current_duration(&PlayerActions::Jump) <
-                Duration::from_millis(JUMP_EXTENSION_MS)
-            {
-                velocity.linvel.y += PRESSED_JUMP_MAG;
+        actor.size = size;
+        *collider = player_collider(size);
+
+        for child in children.iter() {
+            if let Ok(mut hitbox) = hitbox_q.get_mut(*child) {
+                *hitbox = player_collider(size);
             }
-        } else if action_state.released(&PlayerActions::Jump) {
-            flags.remove(PlayerFlags::JUMPING);
         }
+
+        // center of the body moves along with its top, the sprite is shifted back to keep the feet on the ground
+        sprite.anchor = Anchor::Custom(vec2(0.0, (size.y - PLAYER_SIZE.y) / 2.0 / PLAYER_FRAME_SIZE));
     }
 }
 
-#[derive(Component, Deref, DerefMut)]
+// distance in pixels above and below the top of the head a ledge can be grabbed at
+const LEDGE_REACH: i32 = 2;
+const LEDGE_CLIMB_MS: u64 = 300;
+// smallest run input that counts as running towards a wall
+const LEDGE_RUN_DEADZONE: f32 = 0.1;
+// time after letting go during which the ledge can not be grabbed again
+const LEDGE_REGRAB_MS: u64 = 300;
+
+/// Ledge the player hangs on, `ledge` is the top pixel of the terrain and `side` is the direction towards it
+#[derive(Component)]
 #[component(storage = "SparseSet")]
-pub struct AttackBuffer(Timer);
+pub struct LedgeGrab {
+    pub ledge: IVec2,
+    pub side: i32,
+    pub climb: Option<Timer>,
+}
 
 #[derive(Component, Deref, DerefMut)]
 #[component(storage = "SparseSet")]
-pub struct AttackCooldown(Timer);
+pub struct LedgeCooldown(Timer);
 
-#[derive(Component)]
-pub struct AttackSFX;
+/// Probes the column next to the head of a falling player that runs towards a wall,
+/// the ledge needs enough room above it for the player to climb up
+fn find_ledge(chunk_manager: &ChunkManager, actor: &Actor, side: i32) -> Option<LedgeGrab> {
+    let position = actor.position.round().as_ivec2();
+    let size = actor.size.as_ivec2();
 
-pub fn player_attack(
+    let wall_x = if side > 0 { position.x + size.x } else { position.x - 1 };
+    let top = position.y + size.y;
+    let start = ivec2(wall_x, top + LEDGE_REACH);
+
+    let (ledge, _) = raycast(start, ivec2(wall_x, top - LEDGE_REACH), chunk_manager, |pixel| {
+        !is_solid(pixel)
+    })?;
+
+    // wall reaches above the hands
+    if ledge == start {
+        return None;
+    }
+
+    is_clear(chunk_manager, climb_position(ledge, side, size), size).then_some(LedgeGrab {
+        ledge,
+        side,
+        climb: None,
+    })
+}
+
+/// Bottom left corner of the player standing on top of the ledge
+fn climb_position(ledge: IVec2, side: i32, size: IVec2) -> IVec2 {
+    ivec2(if side > 0 { ledge.x } else { ledge.x - size.x + 1 }, ledge.y + 1)
+}
+
+/// Falling players grab ledges they run into and hang on them,
+/// jumping climbs up on top of the ledge and crouching lets go of it
+#[allow(clippy::type_complexity)]
+pub fn player_ledge_grab(
     mut commands: Commands,
     mut player_q: Query<
         (
             Entity,
+            &mut Actor,
+            &mut Velocity,
             &mut PlayerFlags,
-            &Transform,
-            &Velocity,
-            &AttackParameters,
             &ActionState<PlayerActions>,
-            Option<&mut AttackCooldown>,
-            Option<&mut AttackBuffer>,
+            Option<&mut LedgeGrab>,
+            Option<&mut LedgeCooldown>,
         ),
-        (With<Player>, Without<Enemy>)
+        With<Player>
     >,
-    mut enemy_q: Query<&Transform, With<Enemy>>,
-    mut damage_ev: EventWriter<DamageEvent>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    time: Res<Time>,
-    rapier_context: Res<RapierContext>,
-    sprites: Res<SpriteAssetCollection>,
-    audio: Res<AudioAssetCollection>,
-    cursor_position: Option<Res<CursorPosition>>,
-    mut chunk_manager: ResMut<ChunkManager>,
-    mut dirty_rects: ResMut<DirtyRects>
+    chunk_manager: Res<ChunkManager>,
+    time: Res<Time>
 ) {
-    let (
-        entity,
-        mut flags,
-        transform,
-        velocity,
-        attack_parameters,
-        action_state,
-        mut cooldown,
-        mut buffer,
-    ) = player_q.single_mut();
+    for (entity, mut actor, mut velocity, mut flags, action_state, grab, cooldown) in player_q.iter_mut() {
+        if let Some(mut cooldown) = cooldown {
+            if cooldown.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<LedgeCooldown>();
+            }
+            continue;
+        }
 
-    if let Some(buffer) = buffer.as_mut() {
-        buffer.tick(time.delta());
+        let Some(mut grab) = grab else {
+            let run = action_state.value(&PlayerActions::Run);
+            // signum of zero is one, a neutral stick would count as running right
+            let side = if run.abs() < LEDGE_RUN_DEADZONE { 0 } else { run.signum() as i32 };
 
-        if buffer.0.finished() {
-            commands.entity(entity).remove::<AttackBuffer>();
-        }
-    }
+            if
+                side == 0 ||
+                velocity.linvel.y > 0.0 ||
+                actor.flags.intersects(ActorFlags::GROUNDED | ActorFlags::SWIMMING) ||
+                flags.intersects(
+                    PlayerFlags::HOOKED |
+                        PlayerFlags::CROUCHING |
+                        PlayerFlags::DASHING |
+                        PlayerFlags::ROLLING
+                )
+            {
+                continue;
+            }
+
+            if let Some(grab) = find_ledge(&chunk_manager, &actor, side) {
+                actor.position.x = actor.position.x.round();
+                actor.position.y = (grab.ledge.y + 1) as f32 - actor.size.y;
+                velocity.linvel = Vec2::ZERO;
+                flags.insert(PlayerFlags::HANGING);
+                flags.remove(PlayerFlags::JUMPING);
+                commands.entity(entity).insert(grab);
+            }
+            continue;
+        };
+
+        let size = actor.size.as_ivec2();
 
-    let can_attack = cooldown.as_mut().map_or(true, |cooldown| {
-        cooldown.0.tick(time.delta());
+        let let_go =
+            !chunk_manager.get(grab.ledge).is_ok_and(is_solid) ||
+            flags.contains(PlayerFlags::HOOKED) ||
+            (grab.climb.is_none() && action_state.pressed(&PlayerActions::Crouch));
 
-        if cooldown.0.finished() {
-            commands.entity(entity).remove::<AttackCooldown>();
+        if let_go {
+            flags.remove(PlayerFlags::HANGING | PlayerFlags::CLIMBING);
+            commands
+                .entity(entity)
+                .remove::<LedgeGrab>()
+                .insert(LedgeCooldown(Timer::new(Duration::from_millis(LEDGE_REGRAB_MS), TimerMode::Once)));
+            continue;
         }
 
-        cooldown.0.finished()
-    });
+        velocity.linvel = Vec2::ZERO;
 
-    let Some(cursor_position) = cursor_position else {
-        return;
-    };
+        let Some(climb) = grab.climb.as_mut() else {
+            if
+                action_state.pressed(&PlayerActions::Jump) ||
+                action_state.pressed(&PlayerActions::Swim)
+            {
+                grab.climb = Some(Timer::new(Duration::from_millis(LEDGE_CLIMB_MS), TimerMode::Once));
+                flags.insert(PlayerFlags::CLIMBING);
+            }
+            continue;
+        };
 
-    if can_attack {
-        if action_state.just_pressed(&PlayerActions::Attack) || buffer.is_some() {
-            let rotation_modifier = (transform.rotation.y + 0.5) * 2.0;
-
-            let pixel_radius = 18;
-            let hitbox_size = (pixel_radius as f32) / (CHUNK_SIZE as f32);
-            let hitbox_position =
-                transform.translation.xy() +
-                (((pixel_radius as f32) * 0.75) / (CHUNK_SIZE as f32)) * cursor_position.direction;
-
-            rapier_context.intersections_with_shape(
-                hitbox_position,
-                0.0,
-                &Collider::ball(hitbox_size),
-                QueryFilter::new()
-                    .exclude_solids()
-                    .groups(
-                        CollisionGroups::new(
-                            Group::from_bits_retain(PLAYER_MASK),
-                            Group::from_bits_retain(ENEMY_MASK | HITBOX_MASK)
-                        )
-                    ),
-                |enemy_entity| {
-                    let enemy_entity = rapier_context
-                        .collider_parent(enemy_entity)
-                        .unwrap_or(enemy_entity);
-
-                    if let Ok(enemy_transform) = enemy_q.get_mut(enemy_entity) {
-                        damage_ev.send(DamageEvent {
-                            target: enemy_entity,
-                            value: attack_parameters.value + 2.0 * velocity.linvel.length(),
-                            knockback: Vec2::new(
-                                rotation_modifier * 4.0,
-                                (enemy_transform.translation.y - transform.translation.y).clamp(
-                                    -4.0,
-                                    4.0
-                                )
-                            ) +
-                            velocity.linvel / 2.0,
-                            ignore_iframes: false,
-                            play_sound: true,
-                        });
-                    }
-                    true
+        if !climb.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let position = climb_position(grab.ledge, grab.side, size);
+
+        // top of the ledge could have been filled in while climbing
+        if is_clear(&chunk_manager, position, size) {
+            actor.position = position.as_vec2();
+        }
+
+        flags.remove(PlayerFlags::HANGING | PlayerFlags::CLIMBING);
+        commands.entity(entity).remove::<LedgeGrab>();
+    }
+}
+
+pub fn player_swim(
+    mut player: Query<(&Actor, &mut Velocity, &ActionState<PlayerActions>), With<Player>>
+) {
+    for (actor, mut velocity, action_state) in player.iter_mut() {
+        if !actor.flags.contains(ActorFlags::SWIMMING) {
+            continue;
+        }
+
+        if action_state.pressed(&PlayerActions::Swim) {
+            velocity.linvel.y = (velocity.linvel.y + SWIM_STROKE).min(SWIM_SPEED);
+        } else if action_state.pressed(&PlayerActions::Crouch) {
+            velocity.linvel.y = (velocity.linvel.y - SWIM_STROKE).max(-SWIM_SPEED);
+        }
+    }
+}
+
+pub fn player_jump_extend(
+    mut player: Query<(&mut Velocity, &ActionState<PlayerActions>, &mut PlayerFlags)>
+) {
+    for (mut velocity, action_state, mut flags) in player.iter_mut() {
+        if flags.contains(PlayerFlags::JUMPING) {
+            if action_state.pressed(&PlayerActions::Jump) {
+                if
+                    action_state.current_duration(&PlayerActions::Jump) <
+                    Duration::from_millis(JUMP_EXTENSION_MS)
+                {
+                    velocity.linvel.y += PRESSED_JUMP_MAG;
                 }
-            );
+            } else if action_state.released(&PlayerActions::Jump) {
+                flags.remove(PlayerFlags::JUMPING);
+            }
+        }
+    }
+}
+
+#[derive(Component, Deref, DerefMut)]
+#[component(storage = "SparseSet")]
+pub struct AttackBuffer(Timer);
+
+#[derive(Component, Deref, DerefMut)]
+#[component(storage = "SparseSet")]
+pub struct AttackCooldown(Timer);
+
+/// Held attack, released as a heavy one once the timer has finished
+#[derive(Component, Deref, DerefMut)]
+#[component(storage = "SparseSet")]
+pub struct AttackCharge(Timer);
+
+/// Last swing of the combo, the next attack continues the chain until the timer runs out
+#[derive(Component)]
+pub struct AttackCombo {
+    step: usize,
+    timer: Timer,
+}
+
+/// Swing of the latest attack, its frames are played by the attack animation
+#[derive(Component)]
+pub struct AttackSwing(pub Swing);
+
+#[derive(Component)]
+pub struct AttackSFX;
+
+/// Burst of light where a projectile was deflected, fades out on its own
+#[derive(Component, Deref, DerefMut)]
+pub struct ParryFlash(Timer);
+
+// deflected projectiles fly at least this fast along the swing, in world units per second
+const PARRY_MIN_SPEED: f32 = 1.25;
+const PARRY_SPEED_SCALE: f32 = 1.5;
+const PARRY_FLASH_SIZE: f32 = 12.0;
+const PARRY_FLASH_MS: u64 = 150;
+const PARRY_FLASH_COLOR: Color = Color::rgb(1.0, 0.95, 0.7);
+// highest opacity of the flash when flashes are reduced
+const REDUCED_PARRY_FLASH_ALPHA: f32 = 0.35;
+// radius of the regular swing in pixels, scaled by the swing
+const ATTACK_RADIUS: i32 = 18;
+const HEAVY_SLASH_SPEED: f32 = 0.75;
+
+// hard pixels are damaged together in square regions of this size
+const MINING_REGION: i32 = 4;
+
+/// Carving power of the attack, materials harder than the tier are not carved at all
+#[derive(Component, Clone)]
+pub struct MiningTool {
+    pub tier: u32,
+    /// Damage dealt to every region an attack hits, hard pixels break once the damage
+    /// of their region reaches their hardness
+    pub power: f32,
+}
+
+impl Default for MiningTool {
+    fn default() -> Self {
+        Self {
+            tier: 1,
+            power: 1.0,
+        }
+    }
+}
+
+/// Damage dealt to regions of hard pixels on the current level
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct MiningProgress(HashMap<IVec2, f32>);
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn player_attack(
+    mut commands: Commands,
+    mut particles: Particles,
+    mut player_q: Query<
+        (
+            Entity,
+            &mut PlayerFlags,
+            &Transform,
+            &Velocity,
+            &AttackParameters,
+            &ActionState<PlayerActions>,
+            Option<&CursorPosition>,
+            Option<&mut AttackCooldown>,
+            Option<&mut AttackBuffer>,
+            Option<&mut AttackCharge>,
+            Option<&mut AttackCombo>,
+            Option<&MiningTool>,
+        ),
+        (With<Player>, Without<Enemy>)
+    >,
+    mut enemy_q: Query<&Transform, With<Enemy>>,
+    mut projectile_q: Query<(&Transform, &mut Projectile, &mut Velocity), Without<Player>>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    layouts: Res<AtlasLayouts>,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    sprites: Res<SpriteAssetCollection>,
+    audio: Res<AudioAssetCollection>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut mining_progress: ResMut<MiningProgress>,
+    registries: Res<Registries>
+) {
+    let timing = &registries.gameplay.attack;
+
+    for (
+        entity,
+        mut flags,
+        transform,
+        velocity,
+        attack_parameters,
+        action_state,
+        cursor_position,
+        mut cooldown,
+        mut buffer,
+        mut charge,
+        mut combo,
+        mining_tool,
+    ) in player_q.iter_mut() {
+        if let Some(combo) = combo.as_mut() {
+            if combo.timer.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<AttackCombo>();
+            }
+        }
+
+        if let Some(buffer) = buffer.as_mut() {
+            buffer.tick(time.delta());
+
+            if buffer.0.finished() {
+                commands.entity(entity).remove::<AttackBuffer>();
+            }
+        }
+
+        let can_attack = cooldown.as_mut().map_or(true, |cooldown| {
+            cooldown.0.tick(time.delta());
+
+            if cooldown.0.finished() {
+                commands.entity(entity).remove::<AttackCooldown>();
+            }
 
+            cooldown.0.finished()
+        });
+
+        let Some(cursor_position) = cursor_position else {
+            continue;
+        };
+
+        // attacks are let loose once the button is released, holding it long enough charges a heavy one
+        let charged = if let Some(charge) = charge.as_mut() {
+            charge.tick(time.delta());
+
+            if charge.just_finished() {
+                spawn_flash(&mut commands, transform.translation.xy());
+            }
+
+            charge.finished()
+        } else if can_attack && (action_state.just_pressed(&PlayerActions::Attack) || buffer.is_some()) {
             commands
                 .entity(entity)
                 .remove::<AttackBuffer>()
-                .insert(AttackCooldown(Timer::new(Duration::from_millis(500), TimerMode::Once)))
-                .insert(AudioBundle {
+                .insert(AttackCharge(Timer::new(Duration::from_millis(timing.charge_ms), TimerMode::Once)));
+
+            false
+        } else {
+            if !can_attack && action_state.just_pressed(&PlayerActions::Attack) {
+                commands
+                    .entity(entity)
+                    .insert(AttackBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
+            }
+
+            continue;
+        };
+
+        if action_state.pressed(&PlayerActions::Attack) {
+            continue;
+        }
+
+        // heavy attacks break the combo, regular ones continue it while its window is open
+        let step = combo
+            .as_ref()
+            .filter(|combo| !combo.timer.finished())
+            .map_or(0, |combo| (combo.step + 1) % timing.combo.len());
+        let swing = if charged { &timing.heavy } else { &timing.combo[step] };
+
+        let rotation_modifier = (transform.rotation.y + 0.5) * 2.0;
+
+        let pixel_radius = ((ATTACK_RADIUS as f32) * swing.radius).round() as i32;
+        let hitbox_size = (pixel_radius as f32) / (CHUNK_SIZE as f32);
+        let hitbox_position =
+            transform.translation.xy() +
+            (((pixel_radius as f32) * 0.75) / (CHUNK_SIZE as f32)) * cursor_position.direction;
+
+        rapier_context.intersections_with_shape(
+            hitbox_position,
+            0.0,
+            &Collider::ball(hitbox_size),
+            QueryFilter::new()
+                .exclude_solids()
+                .groups(
+                    CollisionGroups::new(
+                        Group::from_bits_retain(PLAYER_MASK),
+                        Group::from_bits_retain(ENEMY_MASK | HITBOX_MASK)
+                    )
+                ),
+            |enemy_entity| {
+                let enemy_entity = rapier_context
+                    .collider_parent(enemy_entity)
+                    .unwrap_or(enemy_entity);
+
+                if let Ok(enemy_transform) = enemy_q.get_mut(enemy_entity) {
+                    damage_ev.send(DamageEvent {
+                        target: enemy_entity,
+                        value: (attack_parameters.value + 2.0 * velocity.linvel.length()) *
+                        swing.damage,
+                        knockback: Vec2::new(
+                            rotation_modifier * 4.0 * swing.knockback,
+                            (enemy_transform.translation.y - transform.translation.y).clamp(
+                                -4.0,
+                                4.0
+                            )
+                        ) +
+                        velocity.linvel / 2.0,
+                        ignore_iframes: false,
+                        play_sound: true,
+                        cause: DamageCause::Player,
+                        damage_type: DamageType::Physical,
+                    });
+                }
+                true
+            }
+        );
+
+        // projectiles of enemies caught by the swing are sent back along it
+        let mut deflected = vec![];
+
+        rapier_context.intersections_with_shape(
+            hitbox_position,
+            0.0,
+            &Collider::ball(hitbox_size),
+            QueryFilter::new()
+                .exclude_solids()
+                .groups(
+                    CollisionGroups::new(
+                        Group::from_bits_retain(PLAYER_MASK),
+                        Group::from_bits_retain(OBJECT_MASK)
+                    )
+                ),
+            |collider_entity| {
+                let projectile_entity = rapier_context
+                    .collider_parent(collider_entity)
+                    .unwrap_or(collider_entity);
+
+                if
+                    let Ok((projectile_transform, mut projectile, mut projectile_velocity)) =
+                        projectile_q.get_mut(projectile_entity)
+                {
+                    if projectile.deflect(entity) {
+                        let speed = projectile_velocity.linvel.length().max(PARRY_MIN_SPEED);
+
+                        projectile_velocity.linvel =
+                            cursor_position.direction * speed * PARRY_SPEED_SCALE;
+                        deflected.push(projectile_transform.translation.xy());
+                    }
+                }
+                true
+            }
+        );
+
+        for position in deflected {
+            spawn_flash(&mut commands, position);
+
+            commands.spawn((
+                AudioBus::Sfx,
+                AudioBundle {
+                    source: audio.hit.clone(),
+                    settings: PlaybackSettings::DESPAWN.with_speed(1.5),
+                },
+            ));
+        }
+
+        if charged {
+            commands.entity(entity).remove::<AttackCombo>();
+        } else {
+            commands.entity(entity).insert(AttackCombo {
+                step,
+                timer: Timer::new(
+                    Duration::from_millis(timing.cooldown_ms + timing.combo_window_ms),
+                    TimerMode::Once
+                ),
+            });
+        }
+
+        commands
+            .entity(entity)
+            .remove::<(AttackBuffer, AttackCharge)>()
+            .insert((
+                AttackCooldown(Timer::new(Duration::from_millis(timing.cooldown_ms), TimerMode::Once)),
+                AttackSwing(swing.clone()),
+            ))
+            .insert((
+                AudioBus::Sfx,
+                AudioBundle {
                     source: audio.slash.clone().into(),
                     settings: PlaybackSettings {
                         volume: Volume::new(0.5),
                         mode: bevy::audio::PlaybackMode::Remove,
+                        speed: if charged { HEAVY_SLASH_SPEED } else { 1.0 },
                         ..Default::default()
                     },
                     ..Default::default()
-                })
-                .with_children(|parent| {
-                    parent.spawn((
-                        AttackSFX,
-                        SpriteSheetBundle {
-                            texture: sprites.attack.clone(),
-                            atlas: TextureAtlas {
-                                layout: texture_atlas_layouts.add(
-                                    TextureAtlasLayout::from_grid(
-                                        Vec2::new(48.0, 48.0),
-                                        5,
-                                        1,
-                                        None,
-                                        None
-                                    )
-                                ),
-                                index: 0,
-                            },
-                            transform: Transform {
-                                // translation: vec3(16.0, 0.0, PLAYER_Z ),
-                                // scale: Vec3::splat(0.0),
-                                ..Default::default()
-                            },
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    AttackSFX,
+                    SpriteSheetBundle {
+                        texture: sprites.attack.clone(),
+                        atlas: TextureAtlas {
+                            layout: layouts.attack.clone(),
+                            index: 0,
+                        },
+                        transform: Transform {
+                            // translation: vec3(16.0, 0.0, PLAYER_Z ),
+                            scale: Vec3::splat(swing.radius),
                             ..Default::default()
                         },
-                        AnimationState::default(),
-                        Animation(
-                            benimator::Animation
-                                ::from_indices(0..=2, FrameRate::from_fps(12.0))
-                                .once()
-                        ),
-                        DespawnOnFinish,
-                    ));
-                });
+                        ..Default::default()
+                    },
+                    AnimationState::default(),
+                    Animation(
+                        benimator::Animation
+                            ::from_indices(0..=2, FrameRate::from_fps(12.0))
+                            .once()
+                    ),
+                    DespawnOnFinish,
+                ));
+            });
 
-            flags.insert(PlayerFlags::ATTACKING);
+        flags.insert(PlayerFlags::ATTACKING);
 
-            let center = (
-                transform.translation.xy() * (CHUNK_SIZE as f32) +
-                (pixel_radius as f32) * 0.5 * cursor_position.direction
-            ).as_ivec2();
-            let chunk_position = center.div_euclid(IVec2::splat(CHUNK_SIZE));
-            let pixel_radius = ((pixel_radius as f32) * 0.75) as i32;
+        let center = (
+            transform.translation.xy() * (CHUNK_SIZE as f32) +
+            (pixel_radius as f32) * 0.5 * cursor_position.direction
+        ).as_ivec2();
+        let chunk_position = center.div_euclid(IVec2::splat(CHUNK_SIZE));
+        let pixel_radius = ((pixel_radius as f32) * 0.75 * swing.carve) as i32;
 
-            if let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) {
-                for x in -pixel_radius..=pixel_radius {
-                    for y in -pixel_radius..=pixel_radius {
-                        let offset = IVec2::new(x, y);
+        let mining_tool = mining_tool.cloned().unwrap_or_default();
+        let mut damaged_regions = HashSet::new();
 
-                        if offset.length_squared() > pixel_radius.pow(2) {
+        if let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) {
+            for x in -pixel_radius..=pixel_radius {
+                for y in -pixel_radius..=pixel_radius {
+                    let offset = IVec2::new(x, y);
+
+                    if offset.length_squared() > pixel_radius.pow(2) {
+                        continue;
+                    }
+
+                    let Some(pixel) = chunk_group.get_mut(
+                        center - chunk_position * CHUNK_SIZE + offset
+                    ) else {
+                        continue;
+                    };
+
+                    let hardness = pixel.material.hardness;
+
+                    if hardness > 0 {
+                        if hardness > mining_tool.tier {
                             continue;
                         }
 
-                        let Some(pixel) = chunk_group
-                            .get_mut(center - chunk_position * CHUNK_SIZE + offset)
-                            .map(|pixel| mem::take(pixel)) else {
+                        let region = (center + offset).div_euclid(IVec2::splat(MINING_REGION));
+                        let damage = mining_progress.entry(region).or_insert(0.0);
+
+                        // every region takes the damage of an attack only once
+                        if damaged_regions.insert(region) {
+                            *damage += mining_tool.power;
+                        }
+
+                        if *damage < (hardness as f32) {
                             continue;
-                        };
-
-                        if
-                            let Some(particle) = match pixel.physics_type {
-                                | PhysicsType::Powder
-                                | PhysicsType::Liquid(_)
-                                | PhysicsType::Gas(_) => {
-                                    Some(Particle::new(pixel.clone()))
-                                }
-                                PhysicsType::Static => { Some(Particle::visual(pixel.clone())) }
-                                _ => { None }
+                        }
+                    }
+
+                    let pixel = mem::take(pixel);
+
+                    if
+                        let Some(particle) = match pixel.physics_type {
+                            | PhysicsType::Powder
+                            | PhysicsType::Liquid(_)
+                            | PhysicsType::Gas(_) => {
+                                Some(Particle::new(pixel.clone()))
                             }
-                        {
-                            commands.spawn(ParticleBundle {
-                                sprite: SpriteBundle {
-                                    sprite: Sprite {
-                                        color: Color::rgba_u8(
-                                            pixel.color[0],
-                                            pixel.color[1],
-                                            pixel.color[2],
-                                            pixel.color[3]
-                                        ),
-                                        custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
-                                        ..Default::default()
-                                    },
-                                    transform: Transform::from_translation(
-                                        ((center + offset).as_vec2() / (CHUNK_SIZE as f32)).extend(
-                                            PARTICLE_Z
-                                        )
+                            PhysicsType::Static => { Some(Particle::visual(pixel.clone())) }
+                            _ => { None }
+                        }
+                    {
+                        particles.spawn(ParticleBundle {
+                            sprite: SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::rgba_u8(
+                                        pixel.color[0],
+                                        pixel.color[1],
+                                        pixel.color[2],
+                                        pixel.color[3]
                                     ),
+                                    custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
                                     ..Default::default()
                                 },
-                                velocity: Velocity::linear(
-                                    vec2(fastrand::f32() - 0.5, fastrand::f32() * 0.5 + 1.0) /
-                                        (CHUNK_SIZE as f32)
+                                transform: Transform::from_translation(
+                                    ((center + offset).as_vec2() / (CHUNK_SIZE as f32)).extend(
+                                        PARTICLE_Z
+                                    )
                                 ),
-                                particle,
                                 ..Default::default()
-                            });
-                        }
-
-                        dirty_rects.request_update_3x3(center + offset);
-                        dirty_rects.request_render(center + offset);
-                        dirty_rects.collider.insert(
-                            (center + offset).div_euclid(IVec2::splat(CHUNK_SIZE))
-                        );
+                            },
+                            velocity: Velocity::linear(
+                                vec2(fastrand::f32() - 0.5, fastrand::f32() * 0.5 + 1.0) /
+                                    (CHUNK_SIZE as f32)
+                            ),
+                            particle,
+                            ..Default::default()
+                        });
                     }
+
+                    dirty_rects.request_update_3x3(center + offset);
+                    dirty_rects.request_render(center + offset);
+                    dirty_rects.collider.insert(
+                        (center + offset).div_euclid(IVec2::splat(CHUNK_SIZE))
+                    );
                 }
             }
         }
-    } else if action_state.just_pressed(&PlayerActions::Attack) {
-        commands
-            .entity(entity)
-            .insert(AttackBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
     }
 }
 
+/// Burst of light where a projectile was deflected or a held attack got fully charged
+fn spawn_flash(commands: &mut Commands, position: Vec2) {
+    commands.spawn((
+        Name::new("Parry flash"),
+        ParryFlash(Timer::new(Duration::from_millis(PARRY_FLASH_MS), TimerMode::Once)),
+        SpriteBundle {
+            sprite: Sprite {
+                color: PARRY_FLASH_COLOR,
+                custom_size: Some(Vec2::splat(PARRY_FLASH_SIZE / (CHUNK_SIZE as f32))),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.extend(PARTICLE_Z)),
+            ..Default::default()
+        },
+        RenderLayers::layer(ACTOR_RENDER_LAYER),
+    ));
+}
+
+/// Flashes grow while fading out, they stay dim when flashes are reduced
+pub fn fade_parry_flashes(
+    mut commands: Commands,
+    mut flash_q: Query<(Entity, &mut ParryFlash, &mut Sprite, &mut Transform)>,
+    config: Res<Persistent<Config>>,
+    time: Res<Time>
+) {
+    let max_alpha = if config.reduced_flashes { REDUCED_PARRY_FLASH_ALPHA } else { 1.0 };
+
+    for (entity, mut flash, mut sprite, mut transform) in flash_q.iter_mut() {
+        flash.tick(time.delta());
+
+        if flash.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        sprite.color.set_a(max_alpha * (1.0 - flash.fraction()));
+        transform.scale = Vec3::splat(1.0 + flash.fraction());
+    }
+}
+
+#[allow(clippy::type_complexity)]
 pub fn player_synchronize_attack_rotation(
-    player_q: Query<(&Transform, &Children), With<Player>>,
-    mut sfx_q: Query<&mut Transform, (With<Parent>, With<AttackSFX>, Without<Player>)>,
-    cursor_position: Option<Res<CursorPosition>>
+    player_q: Query<(&Transform, &Children, Option<&CursorPosition>), With<Player>>,
+    mut sfx_q: Query<&mut Transform, (With<Parent>, With<AttackSFX>, Without<Player>)>
 ) {
-    let (player_transform, children) = player_q.single();
-    let Some(cursor_position) = cursor_position else {
-        return;
-    };
+    for (player_transform, children, cursor_position) in player_q.iter() {
+        let Some(cursor_position) = cursor_position else {
+            continue;
+        };
 
-    for entity in children.iter() {
-        if let Ok(mut sfx_transform) = sfx_q.get_mut(*entity) {
-            if player_transform.rotation.y == 0.0 {
-                sfx_transform.rotation = Quat::from_rotation_z(cursor_position.angle);
-            } else {
-                sfx_transform.rotation = Quat::from_rotation_z(
-                    PI * cursor_position.angle.signum() - cursor_position.angle
-                );
+        for entity in children.iter() {
+            if let Ok(mut sfx_transform) = sfx_q.get_mut(*entity) {
+                if player_transform.rotation.y == 0.0 {
+                    sfx_transform.rotation = Quat::from_rotation_z(cursor_position.angle);
+                } else {
+                    sfx_transform.rotation = Quat::from_rotation_z(
+                        PI * cursor_position.angle.signum() - cursor_position.angle
+                    );
+                }
             }
         }
     }
@@ -825,6 +1648,8 @@ pub struct DashBuffer(Timer);
 #[component(storage = "SparseSet")]
 pub struct DashCooldown(Timer);
 
+const DASH_IFRAMES_MS: u64 = 200;
+
 pub fn player_dash(
     mut commands: Commands,
     mut player_q: Query<
@@ -841,7 +1666,7 @@ pub fn player_dash(
     >,
     time: Res<Time>
 ) {
-    let (
+    for (
         entity,
         mut velocity,
         mut flags,
@@ -849,49 +1674,186 @@ pub fn player_dash(
         action_state,
         mut dash_cooldown,
         mut dash_buffer,
-    ) = player_q.single_mut();
+    ) in player_q.iter_mut() {
+        if let Some(timer) = dash_buffer.as_mut() {
+            timer.tick(time.delta());
+
+            if timer.finished() {
+                commands.entity(entity).remove::<DashBuffer>();
+            }
+        }
+
+        let can_dash = dash_cooldown.as_mut().map_or(true, |cooldown| {
+            cooldown.0.tick(time.delta());
+
+            if cooldown.0.finished() {
+                commands.entity(entity).remove::<DashCooldown>();
+            }
+
+            cooldown.0.finished()
+        });
 
-    if let Some(timer) = dash_buffer.as_mut() {
-        timer.tick(time.delta());
+        if can_dash && !flags.intersects(PlayerFlags::DASHING | PlayerFlags::ROLLING) {
+            if action_state.just_pressed(&PlayerActions::Dash) || dash_buffer.is_some() {
+                velocity.linvel.x += (transform.rotation.y + 0.5) * 2.0 * 6.0;
+                velocity.linvel.y = 1.0;
 
-        if timer.finished() {
-            commands.entity(entity).remove::<DashBuffer>();
+                flags.remove(PlayerFlags::JUMPING);
+                flags.insert(PlayerFlags::DASHING);
+
+                commands
+                    .entity(entity)
+                    .remove::<DashBuffer>()
+                    .insert((
+                        IFrames(Timer::new(Duration::from_millis(DASH_IFRAMES_MS), TimerMode::Once)),
+                        DashCooldown(Timer::new(Duration::from_millis(500), TimerMode::Once)),
+                    ));
+            }
+        } else if action_state.just_pressed(&PlayerActions::Dash) {
+            commands
+                .entity(entity)
+                .insert(DashBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
         }
     }
+}
 
-    let can_dash = dash_cooldown.as_mut().map_or(true, |cooldown| {
-        cooldown.0.tick(time.delta());
+/// Lets players with the perk pass through enemies while dashing, their hitbox stops
+/// overlapping enemy ones for the length of the dash and is restored afterwards
+pub fn player_dash_through(
+    player_q: Query<(&PlayerFlags, &PerkAbilities, &Children), With<Player>>,
+    mut hitbox_q: Query<&mut CollisionGroups, (With<Sensor>, Without<Player>)>
+) {
+    for (flags, abilities, children) in player_q.iter() {
+        let filters = if abilities.dash_through && flags.contains(PlayerFlags::DASHING) {
+            Group::NONE
+        } else {
+            Group::from_bits_retain(ENEMY_MASK)
+        };
 
-        if cooldown.0.finished() {
-            commands.entity(entity).remove::<DashCooldown>();
+        for child in children.iter() {
+            if let Ok(mut groups) = hitbox_q.get_mut(*child) {
+                if groups.filters != filters {
+                    groups.filters = filters;
+                }
+            }
         }
+    }
+}
 
-        cooldown.0.finished()
-    });
+// speed of the roll on clear ground
+const ROLL_SPEED: f32 = 8.0;
+// share of the roll speed lost when the player is fully buried in powder
+const ROLL_POWDER_SLOWDOWN: f32 = 0.6;
+const ROLL_IFRAMES_MS: u64 = 350;
+const DOUBLE_TAP_MS: u64 = 250;
 
-    if can_dash && !flags.contains(PlayerFlags::DASHING) {
-        if action_state.just_pressed(&PlayerActions::Dash) || dash_buffer.is_some() {
-            velocity.linvel.x += (transform.rotation.y + 0.5) * 2.0 * 6.0;
-            velocity.linvel.y = 1.0;
+/// Last tap of a run direction, tapping the same direction again in time starts a roll
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct RunTap {
+    direction: f32,
+    timer: Timer,
+}
 
-            flags.remove(PlayerFlags::JUMPING);
-            flags.insert(PlayerFlags::DASHING);
+#[allow(clippy::type_complexity)]
+pub fn player_roll(
+    mut commands: Commands,
+    mut player_q: Query<
+        (
+            Entity,
+            &Actor,
+            &mut Velocity,
+            &mut PlayerFlags,
+            &Transform,
+            &ActionState<PlayerActions>,
+            Option<&mut RunTap>,
+            Has<DashCooldown>,
+        ),
+        With<Player>
+    >,
+    chunk_manager: Res<ChunkManager>,
+    time: Res<Time>
+) {
+    for (
+        entity,
+        actor,
+        mut velocity,
+        mut flags,
+        transform,
+        action_state,
+        mut run_tap,
+        on_cooldown,
+    ) in player_q.iter_mut() {
+        if let Some(tap) = run_tap.as_mut() {
+            if tap.timer.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<RunTap>();
+            }
+        }
 
-            commands
-                .entity(entity)
-                .remove::<DashBuffer>()
-                .insert(DashCooldown(Timer::new(Duration::from_millis(500), TimerMode::Once)));
+        let run = action_state.value(&PlayerActions::Run);
+        let double_tap =
+            action_state.just_pressed(&PlayerActions::Run) &&
+            run_tap
+                .as_ref()
+                .is_some_and(|tap| !tap.timer.finished() && tap.direction == run.signum());
+
+        if action_state.just_pressed(&PlayerActions::Run) && !double_tap {
+            commands.entity(entity).insert(RunTap {
+                direction: run.signum(),
+                timer: Timer::new(Duration::from_millis(DOUBLE_TAP_MS), TimerMode::Once),
+            });
+        }
+
+        // cooldown is shared with the dash, so the two can not be chained
+        if
+            !(action_state.just_pressed(&PlayerActions::Roll) || double_tap) ||
+            on_cooldown ||
+            flags.intersects(PlayerFlags::DASHING | PlayerFlags::ROLLING) ||
+            !actor.flags.contains(ActorFlags::GROUNDED)
+        {
+            continue;
         }
-    } else if action_state.just_pressed(&PlayerActions::Dash) {
+
+        let direction = if run != 0.0 { run.signum() } else { (transform.rotation.y + 0.5) * 2.0 };
+
+        let position = actor.position.round().as_ivec2();
+        let powder_share =
+            (0..actor.size.x as i32)
+                .cartesian_product(0..actor.size.y as i32)
+                .filter(|(x, y)| {
+                    chunk_manager
+                        .get(position + ivec2(*x, *y))
+                        .is_ok_and(|pixel| pixel.physics_type == PhysicsType::Powder)
+                })
+                .count() as f32 / (actor.size.x * actor.size.y);
+
+        velocity.linvel.x = direction * ROLL_SPEED * (1.0 - ROLL_POWDER_SLOWDOWN * powder_share);
+
+        flags.remove(PlayerFlags::JUMPING);
+        flags.insert(PlayerFlags::ROLLING);
+
         commands
             .entity(entity)
-            .insert(DashBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
+            .remove::<RunTap>()
+            .insert((
+                IFrames(Timer::new(Duration::from_millis(ROLL_IFRAMES_MS), TimerMode::Once)),
+                DashCooldown(Timer::new(Duration::from_millis(500), TimerMode::Once)),
+            ));
     }
 }
 
+// distances are in chunks
+const HOOK_RANGE: f32 = 2.0;
+const REEL_SPEED: f32 = 0.75;
+const MIN_ROPE_LENGTH: f32 = 0.1;
+
 #[derive(Component)]
 pub struct Rope {
     pub source: Entity,
+    /// Rigidbody the rope is attached to, either a chunk of the terrain or an object
+    pub anchor: Entity,
+    /// Attachment point in the space of the anchor
+    pub local_anchor: Vec2,
     pub position: Vec2,
     pub initial_angle: f32,
 }
@@ -899,6 +1861,22 @@ pub struct Rope {
 #[derive(Component)]
 pub struct RopeAnchor;
 
+fn rope_mesh_positions(length: f32) -> Vec<[f32; 3]> {
+    vec![
+        [1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
+        [-1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
+        [-1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
+        [1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0]
+    ]
+}
+
+fn rope_mesh_uvs(length: f32) -> Vec<[f32; 2]> {
+    vec![[length * 8.0, 0.0], [0.0, 0.0], [0.0, length * 8.0], [1.0, length * 8.0]]
+}
+
+/// Hook sticks to the terrain or to objects, which are dragged along by the rope.
+/// While hooked the rope can be reeled in and out
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn player_hook(
     mut commands: Commands,
     rapier_context: Res<RapierContext>,
@@ -912,136 +1890,151 @@ pub fn player_hook(
             &Transform,
             &mut PlayerFlags,
             &ActionState<PlayerActions>,
+            Option<&CursorPosition>,
             Option<&mut ImpulseJoint>,
         ),
         With<Player>
     >,
-    object_q: Query<&GlobalTransform, (With<Collider>, Without<Player>)>,
-    cursor_position: Option<Res<CursorPosition>>
+    anchor_q: Query<&GlobalTransform, Or<(With<Chunk>, With<Object>)>>,
+    config: Res<Persistent<Config>>,
+    time: Res<Time>
 ) {
-    let (entity, mut actor, transform, mut flags, action_state, joint) = player_q.single_mut();
-
-    if let Some(joint) = &joint {
-        if commands.get_entity(joint.parent).is_none() {
-            flags.remove(PlayerFlags::HOOKED);
-            commands.entity(entity).remove::<ImpulseJoint>();
+    for (entity, mut actor, transform, mut flags, action_state, cursor_position, mut joint) in player_q.iter_mut() {
+        if let Some(joint) = &mut joint {
+            if commands.get_entity(joint.parent).is_none() {
+                flags.remove(PlayerFlags::HOOKED);
+                commands.entity(entity).remove::<ImpulseJoint>();
+            } else if action_state.value(&PlayerActions::Reel) != 0.0 {
+                let rest_length = joint.data
+                    .motor(JointAxis::X)
+                    .map_or(0.0, |motor| motor.target_pos);
+
+                joint.data.set_motor_position(
+                    JointAxis::X,
+                    (
+                        rest_length +
+                        action_state.value(&PlayerActions::Reel) * REEL_SPEED * time.delta_seconds()
+                    ).clamp(MIN_ROPE_LENGTH, HOOK_RANGE),
+                    config.hook_stiffness,
+                    config.hook_damping
+                );
+            }
         }
-    }
 
-    let Some(cursor_position) = cursor_position else {
-        return;
-    };
+        let Some(cursor_position) = cursor_position else {
+            continue;
+        };
 
-    if action_state.just_pressed(&PlayerActions::Hook) {
-        if
-            let Some((object_entity, toi)) = rapier_context.cast_ray(
-                transform.translation.xy(),
-                cursor_position.direction,
-                2.0,
-                true,
-                QueryFilter::only_fixed()
-            )
-        {
-            let Ok(object_transform) = object_q.get(object_entity) else {
-                return;
+        if action_state.just_pressed(&PlayerActions::Hook) {
+            let predicate = |collider| {
+                anchor_q.contains(rapier_context.collider_parent(collider).unwrap_or(collider))
             };
 
-            let point = transform.translation.xy() + cursor_position.direction * toi;
-
-            actor.flags.insert(ActorFlags::INFLUENCED);
-            let joint = SpringJointBuilder::new(
-                (cursor_position.direction * toi).length() * 0.5,
-                0.25,
-                0.05
-            )
-                .local_anchor1(point - object_transform.translation().xy())
-                .local_anchor2(Vec2::ZERO);
-
-            let length = (point - transform.translation.xy()).length();
-
-            let mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
-                .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]))
-                .with_inserted_attribute(
-                    Mesh::ATTRIBUTE_POSITION,
-                    vec![
-                        [1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                        [-1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                        [-1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                        [1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0]
-                    ]
+            if
+                let Some((object_entity, toi)) = rapier_context.cast_ray(
+                    transform.translation.xy(),
+                    cursor_position.direction,
+                    HOOK_RANGE,
+                    true,
+                    QueryFilter::default().exclude_sensors().predicate(&predicate)
                 )
-                .with_inserted_attribute(
-                    Mesh::ATTRIBUTE_UV_0,
-                    vec![[length * 8.0, 0.0], [0.0, 0.0], [0.0, length * 8.0], [1.0, length * 8.0]]
-                );
+            {
+                let anchor = rapier_context.collider_parent(object_entity).unwrap_or(object_entity);
 
-            flags.insert(PlayerFlags::HOOKED);
+                let Ok(anchor_transform) = anchor_q.get(anchor) else {
+                    continue;
+                };
 
-            commands
-                .entity(entity)
-                .insert(
-                    ImpulseJoint::new(
-                        rapier_context.collider_parent(object_entity).unwrap_or(object_entity),
-                        joint
-                    )
-                );
-            commands
-                .spawn((
-                    Rope {
-                        source: entity,
-                        position: point,
-                        initial_angle: cursor_position.angle,
-                    },
-                    MaterialMesh2dBundle {
-                        mesh: meshes.add(mesh).into(),
-                        material: materials.add(ColorMaterial {
-                            texture: Some(sprites.rope.clone()),
-                            ..Default::default()
-                        }),
-                        transform: Transform {
-                            translation: ((transform.translation.xy() + point) / 2.0).extend(
-                                PLAYER_Z - 1.0
-                            ),
-                            scale: Vec3::splat(1.0 / (CHUNK_SIZE as f32)),
-                            rotation: Quat::from_rotation_z(cursor_position.angle - PI / 2.0),
-                            ..Default::default()
+                let point = transform.translation.xy() + cursor_position.direction * toi;
+                let local_anchor = anchor_transform
+                    .affine()
+                    .inverse()
+                    .transform_point3(point.extend(0.0))
+                    .xy();
+
+                actor.flags.insert(ActorFlags::INFLUENCED);
+                let joint = SpringJointBuilder::new(
+                    (cursor_position.direction * toi).length() * 0.5,
+                    config.hook_stiffness,
+                    config.hook_damping
+                )
+                    .local_anchor1(local_anchor)
+                    .local_anchor2(Vec2::ZERO);
+
+                let length = (point - transform.translation.xy()).length();
+
+                let mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+                    .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]))
+                    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, rope_mesh_positions(length))
+                    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, rope_mesh_uvs(length));
+
+                flags.insert(PlayerFlags::HOOKED);
+
+                commands.entity(entity).insert(ImpulseJoint::new(anchor, joint));
+                commands
+                    .spawn((
+                        Rope {
+                            source: entity,
+                            anchor,
+                            local_anchor,
+                            position: point,
+                            initial_angle: cursor_position.angle,
                         },
-                        ..Default::default()
-                    },
-                    RenderLayers::layer(ACTOR_RENDER_LAYER),
-                ))
-                .with_children(|parent| {
-                    parent.spawn((
-                        RopeAnchor,
-                        SpriteBundle {
-                            transform: Transform::from_translation(Vec3::new(0.0, length, 0.0)),
-                            texture: sprites.rope_end.clone(),
+                        MaterialMesh2dBundle {
+                            mesh: meshes.add(mesh).into(),
+                            material: materials.add(ColorMaterial {
+                                texture: Some(sprites.rope.clone()),
+                                ..Default::default()
+                            }),
+                            transform: Transform {
+                                translation: ((transform.translation.xy() + point) / 2.0).extend(
+                                    PLAYER_Z - 1.0
+                                ),
+                                scale: Vec3::splat(1.0 / (CHUNK_SIZE as f32)),
+                                rotation: Quat::from_rotation_z(cursor_position.angle - PI / 2.0),
+                                ..Default::default()
+                            },
                             ..Default::default()
                         },
-                    ));
-                });
-        }
-    } else if action_state.released(&PlayerActions::Hook) {
-        if joint.is_some() {
-            flags.remove(PlayerFlags::HOOKED);
-            commands.entity(entity).remove::<ImpulseJoint>();
+                        RenderLayers::layer(ACTOR_RENDER_LAYER),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            RopeAnchor,
+                            SpriteBundle {
+                                transform: Transform::from_translation(Vec3::new(0.0, length, 0.0)),
+                                texture: sprites.rope_end.clone(),
+                                ..Default::default()
+                            },
+                        ));
+                    });
+            }
+        } else if action_state.released(&PlayerActions::Hook) {
+            if joint.is_some() {
+                flags.remove(PlayerFlags::HOOKED);
+                commands.entity(entity).remove::<ImpulseJoint>();
+            }
         }
     }
 }
 
+/// Keeps the rope between the player and the anchor, the rope breaks once
+/// something gets in between or the pixels it was attached to are gone
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn update_rope_position(
     mut commands: Commands,
     mut rope_q: Query<
-        (Entity, &Rope, &mut Transform, &Mesh2dHandle, &Children),
+        (Entity, &mut Rope, &mut Transform, &Mesh2dHandle, &Children),
         (With<Rope>, Without<Actor>)
     >,
     mut rope_end_q: Query<&mut Transform, (With<RopeAnchor>, Without<Actor>, Without<Rope>)>,
     mut actor_q: Query<(&Transform, &mut PlayerFlags, Option<&ImpulseJoint>), With<Actor>>,
+    anchor_q: Query<&GlobalTransform, Without<Rope>>,
     mut meshes: ResMut<Assets<Mesh>>,
     chunk_manager: Res<ChunkManager>,
     rapier_context: Res<RapierContext>
 ) {
-    for (entity, rope, mut transform, mesh_handle, children) in rope_q.iter_mut() {
+    for (entity, mut rope, mut transform, mesh_handle, children) in rope_q.iter_mut() {
         let Ok((actor_transform, mut actor_flags, actor_joint)) = actor_q.get_mut(
             rope.source
         ) else {
@@ -1054,6 +2047,15 @@ pub fn update_rope_position(
             continue;
         }
 
+        let Ok(anchor_transform) = anchor_q.get(rope.anchor) else {
+            commands.entity(entity).despawn_recursive();
+            actor_flags.remove(PlayerFlags::HOOKED);
+            commands.entity(rope.source).remove::<ImpulseJoint>();
+            continue;
+        };
+
+        rope.position = anchor_transform.transform_point(rope.local_anchor.extend(0.0)).xy();
+
         transform.translation.x = (actor_transform.translation.x + rope.position.x) / 2.0;
         transform.translation.y = (actor_transform.translation.y + rope.position.y) / 2.0;
 
@@ -1062,10 +2064,15 @@ pub fn update_rope_position(
 
         let mut intersecting = false;
 
-        rapier_context.intersections_with_point(rope.position, QueryFilter::only_fixed(), |_| {
-            intersecting = true;
-            return false;
-        });
+        rapier_context.intersections_with_point(
+            rope.position,
+            QueryFilter::default().exclude_sensors(),
+            |collider| {
+                intersecting =
+                    rapier_context.collider_parent(collider).unwrap_or(collider) == rope.anchor;
+                !intersecting
+            }
+        );
 
         if
             raycast(
@@ -1088,20 +2095,8 @@ pub fn update_rope_position(
 
         let mesh = meshes.get_mut(mesh_handle.0.clone()).unwrap();
 
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            vec![
-                [1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                [-1.0, (length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                [-1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0],
-                [1.0, (-length * (CHUNK_SIZE as f32)) / 2.0, 0.0]
-            ]
-        );
-
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            vec![[length * 8.0, 0.0], [0.0, 0.0], [0.0, length * 8.0], [1.0, length * 8.0]]
-        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, rope_mesh_positions(length));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, rope_mesh_uvs(length));
 
         transform.rotation = Quat::from_rotation_z(direction.to_angle() - PI / 2.0);
 
@@ -1122,6 +2117,9 @@ pub struct ShootBuffer(Timer);
 #[component(storage = "SparseSet")]
 pub struct ShootCooldown(Timer);
 
+// enemies within this many pixels hear the shot
+const SHOT_NOISE_RADIUS: f32 = 96.0;
+
 pub fn player_shoot(
     mut commands: Commands,
     mut player_q: Query<
@@ -1130,100 +2128,125 @@ pub fn player_shoot(
             &Transform,
             &Velocity,
             &ActionState<PlayerActions>,
+            Option<&CursorPosition>,
             Option<&mut ShootCooldown>,
             Option<&mut ShootBuffer>,
         ),
-        With<Player>
+        // shooting throws the bucket instead while one is held
+        (With<Player>, Without<Carrying>)
     >,
     time: Res<Time>,
     registries: Res<Registries>,
     selected_material: Res<PlayerSelectedMaterial>,
     mut player_materials: ResMut<PlayerMaterials>,
-    cursor_position: Option<ResMut<CursorPosition>>
+    mut noise_ev: EventWriter<NoiseEvent>
 ) {
-    let (entity, transform, velocity, action_state, mut shoot_cooldown, mut shoot_buffer) =
-        player_q.single_mut();
-
-    if let Some(timer) = shoot_buffer.as_mut() {
-        timer.tick(time.delta());
-
-        if timer.finished() {
-            commands.entity(entity).remove::<ShootBuffer>();
+    for (
+        entity,
+        transform,
+        velocity,
+        action_state,
+        cursor_position,
+        mut shoot_cooldown,
+        mut shoot_buffer,
+    ) in player_q.iter_mut() {
+        if let Some(timer) = shoot_buffer.as_mut() {
+            timer.tick(time.delta());
+
+            if timer.finished() {
+                commands.entity(entity).remove::<ShootBuffer>();
+            }
         }
-    }
 
-    let can_shoot = shoot_cooldown.as_mut().map_or(true, |shoot_cooldown| {
-        shoot_cooldown.0.tick(time.delta());
+        let can_shoot = shoot_cooldown.as_mut().map_or(true, |shoot_cooldown| {
+            shoot_cooldown.0.tick(time.delta());
 
-        if shoot_cooldown.0.finished() {
-            commands.entity(entity).remove::<ShootCooldown>();
-        }
+            if shoot_cooldown.0.finished() {
+                commands.entity(entity).remove::<ShootCooldown>();
+            }
 
-        shoot_cooldown.0.finished()
-    });
+            shoot_cooldown.0.finished()
+        });
 
-    let Some(cursor_position) = cursor_position else {
-        return;
-    };
+        let Some(cursor_position) = cursor_position else {
+            continue;
+        };
+
+        if can_shoot {
+            if action_state.just_pressed(&PlayerActions::Shoot) || shoot_buffer.is_some() {
+                if let Some(material) = player_materials.get(selected_material.0.as_str()) {
+                    if *material < 16.0 {
+                        continue;
+                    }
 
-    if can_shoot {
-        if action_state.just_pressed(&PlayerActions::Shoot) || shoot_buffer.is_some() {
-            if let Some(material) = player_materials.get(selected_material.0.as_str()) {
-                if *material < 16.0 {
-                    return;
+                    *player_materials.entry(selected_material.0.clone()).or_insert(0.0) -= 16.0;
                 }
 
-                *player_materials.entry(selected_material.0.clone()).or_insert(0.0) -= 16.0;
-            }
+                let size: i32 = 17;
+                let sand = registries.materials.get(&selected_material.0).unwrap();
+                let mut pixels = vec![None; size.pow(2) as usize];
 
-            let size: i32 = 17;
-            let sand = registries.materials.get(&selected_material.0).unwrap();
-            let mut pixels = vec![None; size.pow(2) as usize];
+                for (x, y) in (0..size).cartesian_product(0..size) {
+                    if
+                        (ivec2(x, y).as_vec2() - (size as f32) / 2.0).length_squared() >
+                        ((size as f32) / 2.0).powi(2)
+                    {
+                        continue;
+                    }
 
-            for (x, y) in (0..size).cartesian_product(0..size) {
-                if
-                    (ivec2(x, y).as_vec2() - (size as f32) / 2.0).length_squared() >
-                    ((size as f32) / 2.0).powi(2)
-                {
-                    continue;
+                    pixels[(y * size + x) as usize] = Some(Pixel::from(sand));
                 }
 
-                pixels[(y * size + x) as usize] = Some(Pixel::from(sand));
-            }
+                // liquids splash against walls instead of forming a ball of liquid inside them,
+                // solid balls lodge in the wall as an object, powders burrow in before settling
+                let projectile = match sand.physics_type {
+                    PhysicsType::Liquid(_) => Projectile::new(0.1, 4.0).with_splash(1.5),
+                    PhysicsType::Static => Projectile::new(0.1, 4.0).stick_on_contact(),
+                    PhysicsType::Powder => Projectile::new(0.1, 4.0).with_pierce(4),
+                    _ => Projectile::new(0.1, 4.0).insert_on_contact(),
+                }
+                    .with_impact(sand.impact.clone())
+                    .with_owner(ProjectileOwner::Player);
 
-            if let Ok(object) = Object::from_pixels(pixels, IVec2::splat(size)) {
-                if let Ok(collider) = object.create_collider() {
-                    commands.spawn((
-                        Sensor,
-                        Projectile::new(0.1, 4.0).insert_on_contact().with_source(entity),
-                        ObjectBundle {
-                            object,
-                            collider,
-                            transform: TransformBundle {
-                                local: Transform::from_translation(
-                                    transform.translation.xy().extend(0.0)
+                if let Ok(object) = Object::from_pixels(pixels, IVec2::splat(size)) {
+                    if let Ok(collider) = object.create_collider() {
+                        commands.spawn((
+                            Sensor,
+                            projectile.with_source(entity),
+                            ObjectBundle {
+                                object,
+                                collider,
+                                transform: TransformBundle {
+                                    local: Transform::from_translation(
+                                        transform.translation.xy().extend(0.0)
+                                    ),
+                                    ..Default::default()
+                                },
+                                velocity: Velocity::linear(
+                                    cursor_position.direction * 1.25 + velocity.linvel / 16.0
                                 ),
+                                mass_properties: ColliderMassProperties::Density(16.0),
                                 ..Default::default()
                             },
-                            velocity: Velocity::linear(
-                                cursor_position.direction * 1.25 + velocity.linvel / 16.0
-                            ),
-                            mass_properties: ColliderMassProperties::Density(16.0),
-                            ..Default::default()
-                        },
-                    ));
+                        ));
+                    }
                 }
-            }
 
-            commands.entity(entity).remove::<ShootBuffer>();
+                noise_ev.send(NoiseEvent {
+                    position: transform.translation.xy() * (CHUNK_SIZE as f32),
+                    radius: SHOT_NOISE_RADIUS,
+                });
+
+                commands.entity(entity).remove::<ShootBuffer>();
+                commands
+                    .entity(entity)
+                    .insert(ShootCooldown(Timer::new(Duration::from_millis(250), TimerMode::Once)));
+            }
+        } else if action_state.just_pressed(&PlayerActions::Shoot) {
             commands
                 .entity(entity)
-                .insert(ShootCooldown(Timer::new(Duration::from_millis(250), TimerMode::Once)));
+                .insert(ShootBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
         }
-    } else if action_state.just_pressed(&PlayerActions::Shoot) {
-        commands
-            .entity(entity)
-            .insert(ShootBuffer(Timer::new(Duration::from_millis(100), TimerMode::Once)));
     }
 }
 
@@ -1245,6 +2268,14 @@ pub struct PlayerTrackingParticles(Vec<(String, Entity)>);
 #[derive(Component)]
 pub struct CollectSFX;
 
+// radius of the regular collection in pixels
+const COLLECT_RADIUS: i32 = 16;
+// range in pixels and half of the angle of the cone of the vacuum
+const VACUUM_RANGE: f32 = 56.0;
+const VACUUM_SPREAD: f32 = 0.5;
+// pixels of the cone tried every frame, the cone is emptied over time
+const VACUUM_SAMPLES: u32 = 64;
+
 #[derive(Component)]
 pub struct InventoryParameters {
     pub max_storage: f32,
@@ -1252,8 +2283,16 @@ pub struct InventoryParameters {
 
 pub fn player_collect_sand(
     mut commands: Commands,
+    mut particles: Particles,
     player_q: Query<
-        (Entity, &Transform, &ActionState<PlayerActions>, &InventoryParameters),
+        (
+            Entity,
+            &Transform,
+            &ActionState<PlayerActions>,
+            &InventoryParameters,
+            &PerkAbilities,
+            Option<&CursorPosition>,
+        ),
         With<Player>
     >,
     mut chunk_manager: ResMut<ChunkManager>,
@@ -1263,21 +2302,26 @@ pub fn player_collect_sand(
     registries: Res<Registries>,
     particle_q: Query<&Particle>,
     audio_assets: Res<AudioAssetCollection>,
-    collect_q: Query<(), With<CollectSFX>>
+    collect_q: Query<(), With<CollectSFX>>,
+    mut profile: ResMut<Persistent<Profile>>,
+    mut run_stats: ResMut<RunStats>
 ) {
-    let (entity, transform, action_state, inventory) = player_q.single();
-
     tracked_particles.retain_mut(|(id, entity)| {
-        if !particle_q.contains(*entity) {
+        // finished particles are kept inactive in the pool
+        if !particle_q.get(*entity).is_ok_and(|particle| particle.active) {
             let entry = player_materials.entry(id.clone()).or_insert(0.0);
 
-            *entry = (*entry + 1.0 / 16.0).clamp(0.0, inventory.max_storage);
+            // anything over the capacity is spilled back out by the storage
+            *entry += 1.0 / 16.0;
+            profile.materials_collected += 1.0 / 16.0;
+            run_stats.materials_collected += 1.0 / 16.0;
 
             if collect_q.iter().len() < 8 {
                 match registries.materials.get(id).unwrap().physics_type {
                     PhysicsType::Powder => {
                         commands.spawn((
                             CollectSFX,
+                            AudioBus::Sfx,
                             AudioBundle {
                                 source: fastrand
                                     ::choice(audio_assets.powder.iter())
@@ -1292,6 +2336,7 @@ pub fn player_collect_sand(
                     PhysicsType::Liquid(_) => {
                         commands.spawn((
                             CollectSFX,
+                            AudioBus::Sfx,
                             AudioBundle {
                                 source: fastrand
                                     ::choice(audio_assets.liquid.iter())
@@ -1313,101 +2358,128 @@ pub fn player_collect_sand(
         true
     });
 
-    if action_state.pressed(&PlayerActions::Collect) {
+    for (entity, transform, action_state, inventory, abilities, cursor_position) in player_q.iter() {
+        if !action_state.pressed(&PlayerActions::Collect) {
+            continue;
+        }
+
         let chunk_position = transform.translation.xy().floor().as_ivec2();
         let player_position = (transform.translation.xy().fract() * (CHUNK_SIZE as f32)).as_ivec2();
 
         let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) else {
-            return;
+            continue;
         };
 
-        let radius = 16;
-        for x in -radius..=radius {
-            for y in -radius..=radius {
-                let position = ivec2(x, y);
-
-                if position.length_squared() > radius.pow(2) {
-                    continue;
-                }
+        // the vacuum pulls in random pixels of a cone towards the cursor instead of everything close by
+        let (offsets, movement) = match cursor_position.filter(|_| abilities.vacuum) {
+            Some(cursor_position) => {
+                (vacuum_offsets(cursor_position.direction), ParticleMovement::Pull(entity))
+            }
+            None => (collect_offsets(), ParticleMovement::Follow(entity)),
+        };
 
-                let Some(pixel) = chunk_group.get_mut(player_position + position) else {
-                    continue;
-                };
+        for position in offsets {
+            let Some(pixel) = chunk_group.get_mut(player_position + position) else {
+                continue;
+            };
 
-                if
-                    matches!(
-                        pixel.physics_type,
-                        PhysicsType::Powder | PhysicsType::Liquid(..) | PhysicsType::Gas(..)
-                    )
-                {
-                    if let Some(material) = player_materials.get_mut(&pixel.material.id) {
-                        if *material >= inventory.max_storage {
-                            continue;
-                        }
+            if
+                matches!(
+                    pixel.physics_type,
+                    PhysicsType::Powder | PhysicsType::Liquid(..) | PhysicsType::Gas(..)
+                )
+            {
+                if let Some(material) = player_materials.get_mut(&pixel.material.id) {
+                    if *material >= pixel.material.capacity(inventory.max_storage) {
+                        continue;
                     }
+                }
 
-                    let pixel = mem::take(pixel);
-
-                    tracked_particles.push((
-                        pixel.material.id.clone(),
-                        commands
-                            .spawn(ParticleBundle {
-                                sprite: SpriteBundle {
-                                    sprite: Sprite {
-                                        color: Color::rgba_u8(
-                                            pixel.color[0],
-                                            pixel.color[1],
-                                            pixel.color[2],
-                                            pixel.color[3]
-                                        ),
-                                        custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
-                                        ..Default::default()
-                                    },
-                                    transform: Transform::from_translation(
-                                        (
-                                            transform.translation.xy() +
-                                            vec2(x as f32, y as f32) / (CHUNK_SIZE as f32)
-                                        ).extend(PARTICLE_Z)
+                let pixel = mem::take(pixel);
+
+                tracked_particles.push((
+                    pixel.material.id.clone(),
+                    particles
+                        .spawn(ParticleBundle {
+                            sprite: SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::rgba_u8(
+                                        pixel.color[0],
+                                        pixel.color[1],
+                                        pixel.color[2],
+                                        pixel.color[3]
                                     ),
+                                    custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
                                     ..Default::default()
                                 },
-                                movement: ParticleMovement::Follow(entity),
-                                particle: Particle::new(pixel),
+                                transform: Transform::from_translation(
+                                    (
+                                        transform.translation.xy() +
+                                        position.as_vec2() / (CHUNK_SIZE as f32)
+                                    ).extend(PARTICLE_Z)
+                                ),
                                 ..Default::default()
-                            })
-                            .id(),
-                    ));
+                            },
+                            movement: movement.clone(),
+                            particle: Particle::new(pixel),
+                            ..Default::default()
+                        })
+                        .id(),
+                ));
 
-                    dirty_rects.request_update(
-                        player_position + position + chunk_position * CHUNK_SIZE
-                    );
-                    dirty_rects.request_render(
-                        player_position + position + chunk_position * CHUNK_SIZE
-                    );
-                }
+                dirty_rects.request_update(
+                    player_position + position + chunk_position * CHUNK_SIZE
+                );
+                dirty_rects.request_render(
+                    player_position + position + chunk_position * CHUNK_SIZE
+                );
             }
         }
     }
 }
 
+/// Every offset in pixels within the radius of the regular collection
+fn collect_offsets() -> Vec<IVec2> {
+    (-COLLECT_RADIUS..=COLLECT_RADIUS)
+        .cartesian_product(-COLLECT_RADIUS..=COLLECT_RADIUS)
+        .map(|(x, y)| ivec2(x, y))
+        .filter(|position| position.length_squared() <= COLLECT_RADIUS.pow(2))
+        .collect()
+}
+
+/// Random offsets in pixels within the cone of the vacuum, pointing in the direction
+fn vacuum_offsets(direction: Vec2) -> Vec<IVec2> {
+    let angle = direction.to_angle();
+
+    (0..VACUUM_SAMPLES)
+        .map(|_| {
+            let spread = (fastrand::f32() * 2.0 - 1.0) * VACUUM_SPREAD;
+            let distance = fastrand::f32() * VACUUM_RANGE;
+
+            (Vec2::from_angle(angle + spread) * distance).round().as_ivec2()
+        })
+        .collect()
+}
+
 pub fn player_switch_material(
     player_q: Query<&ActionState<PlayerActions>, With<Player>>,
     mut selected_material: ResMut<PlayerSelectedMaterial>,
     player_materials: Res<PlayerMaterials>
 ) {
-    let action_state = player_q.single();
-    let index = player_materials.get_index_of(&selected_material.0).unwrap_or(0);
-
-    if action_state.just_pressed(&PlayerActions::SelectMaterialNext) {
-        selected_material.0 = player_materials
-            .get_index(((index as i32) - 1).rem_euclid(player_materials.len() as i32) as usize)
-            .unwrap()
-            .0.clone();
-    } else if action_state.just_pressed(&PlayerActions::SelectMaterialPrevious) {
-        selected_material.0 = player_materials
-            .get_index(((index as i32) + 1).rem_euclid(player_materials.len() as i32) as usize)
-            .unwrap()
-            .0.clone();
+    for action_state in player_q.iter() {
+        let index = player_materials.get_index_of(&selected_material.0).unwrap_or(0);
+
+        if action_state.just_pressed(&PlayerActions::SelectMaterialNext) {
+            selected_material.0 = player_materials
+                .get_index(((index as i32) - 1).rem_euclid(player_materials.len() as i32) as usize)
+                .unwrap()
+                .0.clone();
+        } else if action_state.just_pressed(&PlayerActions::SelectMaterialPrevious) {
+            selected_material.0 = player_materials
+                .get_index(((index as i32) + 1).rem_euclid(player_materials.len() as i32) as usize)
+                .unwrap()
+                .0.clone();
+        }
     }
 }
 
@@ -1424,85 +2496,122 @@ pub fn player_prune_empty_materials(
     });
 }
 
+#[allow(clippy::type_complexity)]
 pub fn update_player_rotation(
     mut player_q: Query<
-        (&PlayerFlags, &mut Transform, &Velocity, &mut StorredRotation),
+        (&PlayerFlags, &mut Transform, &Velocity, &mut StorredRotation, Option<&CursorPosition>),
         With<Player>
-    >,
-    window_q: Query<&Window, With<PrimaryWindow>>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>
+    >
 ) {
-    let (flags, mut transform, velocity, mut rotation) = player_q.single_mut();
-    let (camera, camera_global_transform) = camera_q.single();
-
-    if !flags.contains(PlayerFlags::ATTACKING) && !flags.contains(PlayerFlags::DASHING) {
-        if
-            window_q
-                .get_single()
-                .ok()
-                .filter(|window| window.cursor_position().is_some())
-                .map_or(velocity.linvel.x.is_sign_negative(), |window| {
-                    let point = camera
-                        .viewport_to_world(
-                            camera_global_transform,
-                            window.cursor_position().unwrap()
-                        )
-                        .map(|ray| ray.origin.truncate())
-                        .unwrap();
-
-                    (point.yx() - transform.translation.yx()).to_angle().is_sign_negative()
+    for (flags, mut transform, velocity, mut rotation, cursor_position) in player_q.iter_mut() {
+        if !flags.contains(PlayerFlags::ATTACKING) && !flags.contains(PlayerFlags::DASHING) {
+            if
+                cursor_position.map_or(velocity.linvel.x.is_sign_negative(), |cursor_position| {
+                    cursor_position.direction.x.is_sign_negative()
                 })
-        {
-            rotation.0 = Quat::from_rotation_y(-(180f32).to_radians());
-        } else {
-            rotation.0 = Quat::IDENTITY;
+            {
+                rotation.0 = Quat::from_rotation_y(-(180f32).to_radians());
+            } else {
+                rotation.0 = Quat::IDENTITY;
+            }
         }
-    }
 
-    transform.rotation = rotation.0;
+        transform.rotation = rotation.0;
+    }
 }
 
-#[derive(Resource)]
+/// Aim of the player, either towards the cursor or along the right stick
+#[derive(Component)]
 pub struct CursorPosition {
-    direction: Vec2,
+    pub direction: Vec2,
     world_position: Vec2,
     angle: f32,
+    gamepad_aiming: bool,
 }
 
+// distance from the player at which the aim point is placed when aiming with a stick
+const STICK_AIM_DISTANCE: f32 = 1.0;
+
+#[allow(clippy::type_complexity)]
 pub fn store_camera_position(
     mut commands: Commands,
-    player_q: Query<&Transform, With<Player>>,
+    mut cursor_evr: EventReader<CursorMoved>,
+    mut player_q: Query<
+        (
+            Entity,
+            &Transform,
+            &ActionState<PlayerActions>,
+            Option<&mut CursorPosition>,
+            Has<CoopPlayer>,
+        ),
+        With<Player>
+    >,
     window_q: Query<&Window, With<PrimaryWindow>>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>
 ) {
-    let player_transform = player_q.single();
     let (camera, camera_transform) = camera_q.single();
+    let cursor_moved = cursor_evr.read().count() > 0;
+
+    let cursor_world_position = window_q
+        .get_single()
+        .ok()
+        .and_then(|window| {
+            window.cursor_position().map(|position| world_target.viewport_position(window, position))
+        })
+        .and_then(|cursor_position| camera.viewport_to_world(camera_transform, cursor_position))
+        .map(|ray| ray.origin.truncate());
+
+    for (entity, player_transform, action_state, cursor_position, is_coop) in player_q.iter_mut() {
+        let stick = action_state
+            .clamped_axis_pair(&PlayerActions::Aim)
+            .map_or(Vec2::ZERO, |axis| axis.xy());
+
+        // the last used device keeps control over aiming, so releasing the stick does not snap aim to the cursor,
+        // second player has no mouse and always aims with the stick
+        let previous_gamepad_aiming = cursor_position
+            .as_ref()
+            .is_some_and(|cursor_position| cursor_position.gamepad_aiming);
+        let gamepad_aiming =
+            is_coop || stick != Vec2::ZERO || (previous_gamepad_aiming && !cursor_moved);
+
+        let aim = if gamepad_aiming {
+            let direction = if stick != Vec2::ZERO {
+                stick.normalize()
+            } else {
+                cursor_position
+                    .as_ref()
+                    .map_or(Vec2::X, |cursor_position| cursor_position.direction)
+            };
 
-    match
-        window_q
-            .get_single()
-            .ok()
-            .map(|window| window.cursor_position())
-            .filter(|position| position.is_some())
-            .map(|cursor_position| {
-                let world_position = camera
-                    .viewport_to_world(camera_transform, cursor_position.unwrap())
-                    .map(|ray| ray.origin.truncate())
-                    .unwrap();
-
-                let direction = (
-                    world_position - player_transform.translation.xy()
-                ).normalize_or_zero();
-                let angle = direction.to_angle();
-
-                CursorPosition {
+            Some((direction, player_transform.translation.xy() + direction * STICK_AIM_DISTANCE))
+        } else {
+            cursor_world_position.map(|world_position| {
+                ((world_position - player_transform.translation.xy()).normalize_or_zero(), world_position)
+            })
+        };
+
+        match (aim, cursor_position) {
+            (Some((direction, world_position)), Some(mut cursor_position)) => {
+                *cursor_position = CursorPosition {
                     direction,
                     world_position,
-                    angle,
-                }
-            })
-    {
-        Some(result) => commands.insert_resource(result),
-        None => commands.remove_resource::<CursorPosition>(),
-    };
+                    angle: direction.to_angle(),
+                    gamepad_aiming,
+                };
+            }
+            (Some((direction, world_position)), None) => {
+                commands.entity(entity).insert(CursorPosition {
+                    direction,
+                    world_position,
+                    angle: direction.to_angle(),
+                    gamepad_aiming,
+                });
+            }
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<CursorPosition>();
+            }
+            (None, None) => {}
+        }
+    }
 }
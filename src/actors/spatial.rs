@@ -0,0 +1,75 @@
+use bevy::{ prelude::*, utils::HashMap };
+
+use super::{ actor::Actor, effects::Death, enemy::Enemy };
+
+// side of a cell in pixels, close to the size of the largest enemies
+const CELL_SIZE: f32 = 32.0;
+
+/// Uniform grid of living enemies, rebuilt every fixed update once actors have moved,
+/// positions are the centers of the enemies in pixels
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+}
+
+fn cell(position: Vec2) -> IVec2 {
+    (position / CELL_SIZE).floor().as_ivec2()
+}
+
+impl SpatialIndex {
+    pub fn clear(&mut self) {
+        // occupied cells are kept so their allocations are reused on the next rebuild
+        self.cells.retain(|_, entities| !entities.is_empty());
+
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        self.cells.entry(cell(position)).or_default().push((entity, position));
+    }
+
+    /// Enemies with their center within the radius, in no particular order
+    pub fn in_radius(
+        &self,
+        position: Vec2,
+        radius: f32
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let min = cell(position - radius);
+        let max = cell(position + radius);
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(_, other)| other.distance_squared(position) <= radius.powi(2))
+            .copied()
+    }
+
+    /// Closest enemy within the radius the predicate accepts
+    pub fn nearest<F: Fn(Entity, Vec2) -> bool>(
+        &self,
+        position: Vec2,
+        radius: f32,
+        predicate: F
+    ) -> Option<(Entity, Vec2)> {
+        self.in_radius(position, radius)
+            .filter(|(entity, other)| predicate(*entity, *other))
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position).total_cmp(&b.distance_squared(position))
+            })
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    enemy_q: Query<(Entity, &Actor), (With<Enemy>, Without<Death>)>
+) {
+    index.clear();
+
+    for (entity, actor) in enemy_q.iter() {
+        index.insert(entity, actor.position + actor.size / 2.0);
+    }
+}
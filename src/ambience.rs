@@ -0,0 +1,273 @@
+use std::{ f32::consts::TAU, time::Duration };
+
+use bevy::{
+    audio::{ AddAudioSource, Decodable, PlaybackMode, Source, Volume },
+    prelude::*,
+    time::common_conditions::on_timer,
+    transform::TransformSystem,
+};
+use bevy_persistent::Persistent;
+
+use crate::{
+    actors::actor::Actor,
+    assets::AudioAssetCollection,
+    generation::Ambient,
+    settings::{ AudioBus, Config },
+    simulation::{ chunk_manager::ChunkManager, materials::PhysicsType },
+    state::GameState,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+// rays cast around the listener, a ray that hits terrain within the length counts as a wall
+const ENCLOSURE_RAYS: usize = 16;
+const ENCLOSURE_RAY_LENGTH: i32 = 96;
+const RAY_STEP: i32 = 2;
+// liquid pixels are counted on a grid around the listener
+const LIQUID_RADIUS: i32 = 48;
+const LIQUID_STEP: i32 = 4;
+const LIQUID_FULL_SAMPLES: f32 = 60.0;
+// effects are muffled once most of the rays hit a wall
+const CAVE_ENCLOSURE: f32 = 0.6;
+const OPEN_LOW_PASS: f32 = 8000.0;
+const CAVE_LOW_PASS: f32 = 1500.0;
+const REVERB_DELAY: Duration = Duration::from_millis(90);
+const REVERB_AMPLITUDE: f32 = 0.35;
+// share of the difference between current and target loop volume that is applied per second
+const VOLUME_RESPONSE: f32 = 1.5;
+// drips per second next to a large body of liquid
+const DRIP_RATE: f32 = 1.5;
+
+pub struct AmbiencePlugin;
+
+impl Plugin for AmbiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<MuffledAudio>()
+            .init_resource::<AudioEnvironment>()
+            .add_systems(OnEnter(GameState::Game), spawn_ambience_loops)
+            .add_systems(
+                Update,
+                (
+                    sample_environment.run_if(on_timer(SAMPLE_INTERVAL)),
+                    fade_ambience_loops,
+                    play_drips,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                PostUpdate,
+                muffle_enclosed_effects
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Surroundings of the listener, every value goes from 0 to 1
+#[derive(Resource, Default)]
+pub struct AudioEnvironment {
+    /// Share of directions that are blocked by terrain nearby
+    pub enclosure: f32,
+    /// Amount of liquid around the listener
+    pub liquid: f32,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum AmbienceLoop {
+    /// Open areas
+    Wind,
+    /// Enclosed caves
+    Cave,
+}
+
+impl AmbienceLoop {
+    fn volume(&self, environment: &AudioEnvironment) -> f32 {
+        match self {
+            AmbienceLoop::Wind => (1.0 - environment.enclosure).powi(2) * 0.5,
+            AmbienceLoop::Cave => environment.enclosure.powi(2) * 0.6,
+        }
+    }
+}
+
+/// Effect played with a low-pass filter and an echo, used for effects inside of caves
+#[derive(Asset, TypePath, Clone)]
+pub struct MuffledAudio {
+    pub source: AudioSource,
+    pub low_pass: u32,
+    pub reverb: f32,
+}
+
+impl Decodable for MuffledAudio {
+    type DecoderItem = f32;
+    type Decoder = Box<dyn Source<Item = f32> + Send>;
+
+    fn decoder(&self) -> Self::Decoder {
+        Box::new(
+            self.source
+                .decoder()
+                .convert_samples::<f32>()
+                .low_pass(self.low_pass)
+                .buffered()
+                .reverb(REVERB_DELAY, self.reverb)
+        )
+    }
+}
+
+fn spawn_ambience_loops(mut commands: Commands, asset_server: Res<AssetServer>) {
+    for (kind, path) in [
+        (AmbienceLoop::Wind, "audio/snow.wav"),
+        (AmbienceLoop::Cave, "audio/cave_ambient.wav"),
+    ] {
+        commands.spawn((
+            Ambient,
+            kind,
+            AudioBus::Ambience,
+            AudioBundle {
+                source: asset_server.load(path),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(0.0),
+                    ..Default::default()
+                },
+            },
+        ));
+    }
+}
+
+fn sample_environment(
+    mut environment: ResMut<AudioEnvironment>,
+    listener_q: Query<&Actor, With<SpatialListener>>,
+    chunk_manager: Res<ChunkManager>
+) {
+    let Some(actor) = listener_q.iter().next() else {
+        return;
+    };
+
+    let center = (actor.position + actor.size / 2.0).round().as_ivec2();
+    let is_wall = |position: IVec2| {
+        chunk_manager
+            .get(position)
+            .is_ok_and(|pixel| {
+                matches!(
+                    pixel.physics_type,
+                    PhysicsType::Static | PhysicsType::Powder | PhysicsType::Rigidbody(..)
+                )
+            })
+    };
+
+    let blocked = (0..ENCLOSURE_RAYS)
+        .filter(|index| {
+            let direction = Vec2::from_angle(((*index as f32) / (ENCLOSURE_RAYS as f32)) * TAU);
+
+            (1..=ENCLOSURE_RAY_LENGTH / RAY_STEP).any(|step| {
+                is_wall(center + (direction * ((step * RAY_STEP) as f32)).round().as_ivec2())
+            })
+        })
+        .count();
+
+    let mut liquid = 0;
+    for x in (-LIQUID_RADIUS..=LIQUID_RADIUS).step_by(LIQUID_STEP as usize) {
+        for y in (-LIQUID_RADIUS..=LIQUID_RADIUS).step_by(LIQUID_STEP as usize) {
+            if
+                chunk_manager
+                    .get(center + IVec2::new(x, y))
+                    .is_ok_and(|pixel| matches!(pixel.physics_type, PhysicsType::Liquid(_)))
+            {
+                liquid += 1;
+            }
+        }
+    }
+
+    environment.enclosure = (blocked as f32) / (ENCLOSURE_RAYS as f32);
+    environment.liquid = ((liquid as f32) / LIQUID_FULL_SAMPLES).min(1.0);
+}
+
+fn fade_ambience_loops(
+    loop_q: Query<(&AmbienceLoop, &AudioSink)>,
+    environment: Res<AudioEnvironment>,
+    config: Res<Persistent<Config>>,
+    time: Res<Time>
+) {
+    let response = (VOLUME_RESPONSE * time.delta_seconds()).min(1.0);
+
+    for (kind, sink) in loop_q.iter() {
+        let target = config.volume_of(AudioBus::Ambience, kind.volume(&environment));
+
+        sink.set_volume(sink.volume() + (target - sink.volume()) * response);
+    }
+}
+
+/// Large bodies of liquid nearby are heard as quiet drips
+fn play_drips(
+    mut commands: Commands,
+    listener_q: Query<&Transform, With<SpatialListener>>,
+    environment: Res<AudioEnvironment>,
+    audio_assets: Res<AudioAssetCollection>,
+    time: Res<Time>
+) {
+    let Some(transform) = listener_q.iter().next() else {
+        return;
+    };
+
+    if fastrand::f32() >= environment.liquid * DRIP_RATE * time.delta_seconds() {
+        return;
+    }
+
+    let Some(source) = fastrand::choice(audio_assets.liquid.values()).cloned() else {
+        return;
+    };
+
+    // drips come from a random side of the listener
+    let offset = Vec2::from_angle(fastrand::f32() * TAU) * 0.5;
+
+    commands.spawn((
+        TransformBundle::from_transform(
+            Transform::from_translation(transform.translation + offset.extend(0.0))
+        ),
+        AudioBus::Ambience,
+        AudioBundle {
+            source,
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                spatial: true,
+                volume: Volume::new(0.15),
+                speed: fastrand::f32() * 0.5 + 1.25,
+                ..Default::default()
+            },
+        },
+    ));
+}
+
+/// Spatial effects that start inside of a cave are replaced with their muffled version
+#[allow(clippy::type_complexity)]
+fn muffle_enclosed_effects(
+    mut commands: Commands,
+    effect_q: Query<(Entity, &Handle<AudioSource>, &PlaybackSettings), Added<Handle<AudioSource>>>,
+    mut muffled: ResMut<Assets<MuffledAudio>>,
+    sources: Res<Assets<AudioSource>>,
+    environment: Res<AudioEnvironment>
+) {
+    if environment.enclosure < CAVE_ENCLOSURE {
+        return;
+    }
+
+    let strength = (environment.enclosure - CAVE_ENCLOSURE) / (1.0 - CAVE_ENCLOSURE);
+
+    for (entity, handle, settings) in effect_q.iter() {
+        if !settings.spatial || !matches!(settings.mode, PlaybackMode::Despawn) {
+            continue;
+        }
+
+        let Some(source) = sources.get(handle) else {
+            continue;
+        };
+
+        let handle = muffled.add(MuffledAudio {
+            source: source.clone(),
+            low_pass: OPEN_LOW_PASS.lerp(CAVE_LOW_PASS, strength) as u32,
+            reverb: REVERB_AMPLITUDE * strength,
+        });
+
+        commands.entity(entity).remove::<Handle<AudioSource>>().insert(handle);
+    }
+}
@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use bevy_math::ivec2;
+use itertools::Itertools;
+
+use crate::{
+    actors::{ actor::Actor, enemy::Enemy, player::Player },
+    constants::CHUNK_SIZE,
+    curses::DoubleEnemies,
+    despawn_component,
+    difficulty::{ AdaptiveDifficulty, Difficulty },
+    escalation::spawn_warning,
+    generation::level::{ ArenaEnemy, ArenaOnLevel, ChestLoot },
+    interactables::spawn_chest,
+    registries::Registries,
+    simulation::{ chunk_manager::ChunkManager, dirty_rect::DirtyRects, pixel::Pixel },
+    state::GameState,
+};
+
+// players have to be this far inside the border in pixels for the arena to be sealed
+const ENTRY_MARGIN: i32 = 8;
+const SPAWN_ATTEMPTS: u32 = 32;
+// enemies do not spawn closer than this to a player, in pixels
+const SPAWN_DISTANCE: f32 = 24.0;
+const SEALED_MESSAGE: &str = "The arena is sealed!";
+const CLEARED_MESSAGE: &str = "The arena is cleared";
+
+pub struct ArenasPlugin;
+
+impl Plugin for ArenasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::LevelInitialization), despawn_component::<Arena>)
+            .add_systems(OnExit(GameState::GameOver), despawn_component::<Arena>)
+            .add_systems(
+                Update,
+                (seal_arenas, clear_arenas).chain().run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArenaState {
+    Waiting,
+    Sealed,
+    Cleared,
+}
+
+/// Room found by the generation, bounds are in pixels
+#[derive(Component)]
+pub struct Arena {
+    pub bounds: IRect,
+    pub state: ArenaState,
+    pub seal_material: String,
+    pub encounter: Vec<ArenaEnemy>,
+    pub loot: Vec<ChestLoot>,
+    /// Enemies spawned when the arena was sealed
+    pub enemies: Vec<Entity>,
+    /// Pixels filled with the seal material, only those are cleared again
+    pub sealed: Vec<IVec2>,
+}
+
+pub fn spawn_arena(commands: &mut Commands, arena: &ArenaOnLevel, bounds: IRect) {
+    commands.spawn((
+        Name::new("Arena"),
+        Arena {
+            bounds,
+            state: ArenaState::Waiting,
+            seal_material: arena.seal_material_id.clone(),
+            encounter: arena.encounter.clone(),
+            loot: arena.loot.clone(),
+            enemies: vec![],
+            sealed: vec![],
+        },
+    ));
+}
+
+/// Pixels along the inner edge of the bounds
+pub fn arena_border(bounds: IRect) -> impl Iterator<Item = IVec2> {
+    let horizontal = (bounds.min.x..bounds.max.x)
+        .flat_map(move |x| [ivec2(x, bounds.min.y), ivec2(x, bounds.max.y - 1)]);
+    let vertical = (bounds.min.y + 1..bounds.max.y - 1)
+        .flat_map(move |y| [ivec2(bounds.min.x, y), ivec2(bounds.max.x - 1, y)]);
+
+    horizontal.chain(vertical)
+}
+
+/// Fills the openings of the border and spawns the encounter once a player is inside
+#[allow(clippy::too_many_arguments)]
+fn seal_arenas(
+    mut commands: Commands,
+    mut arena_q: Query<&mut Arena>,
+    player_q: Query<&Actor, With<Player>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    difficulty: Res<Difficulty>,
+    double_enemies: Option<Res<DoubleEnemies>>,
+    adaptive: Option<Res<AdaptiveDifficulty>>
+) {
+    let density =
+        difficulty.enemy_density() *
+        double_enemies.map_or(1.0, |double| double.density) *
+        adaptive.map_or(1.0, |adaptive| adaptive.density);
+
+    for mut arena in arena_q.iter_mut() {
+        if arena.state != ArenaState::Waiting {
+            continue;
+        }
+
+        let inner = arena.bounds.inset(-ENTRY_MARGIN);
+        let entered = player_q.iter().any(|actor| {
+            inner.contains(actor.position.as_ivec2()) &&
+                inner.contains((actor.position + actor.size).as_ivec2())
+        });
+
+        if !entered {
+            continue;
+        }
+
+        let Some(material) = registries.materials.get(&arena.seal_material) else {
+            warn!("unknown seal material: {}", arena.seal_material);
+            continue;
+        };
+
+        arena.sealed = arena_border(arena.bounds)
+            .filter(|position| {
+                chunk_manager.get(*position).is_ok_and(|pixel| pixel.is_empty()) &&
+                    chunk_manager.set(*position, Pixel::from(material)).is_ok()
+            })
+            .collect_vec();
+
+        for position in arena.sealed.iter() {
+            dirty_rects.request_update(*position);
+            dirty_rects.request_render(*position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+
+        let players = player_q
+            .iter()
+            .map(|actor| actor.position + actor.size / 2.0)
+            .collect_vec();
+
+        let mut enemies = vec![];
+
+        for enemy in arena.encounter.iter() {
+            let Some(enemy_fn) = registries.enemies.get(&enemy.enemy_id) else {
+                warn!("unknown arena enemy: {}", enemy.enemy_id);
+                continue;
+            };
+
+            let enemy_size = enemy_fn(Vec2::ZERO).0.actor.actor.size.as_ivec2();
+            let count = ((enemy.count as f32) * density).round().max(1.0) as u32;
+
+            for _ in 0..count {
+                let position = (0..SPAWN_ATTEMPTS)
+                    .map(|_| {
+                        ivec2(
+                            fastrand::i32(arena.bounds.min.x + 1..arena.bounds.max.x - enemy_size.x),
+                            fastrand::i32(arena.bounds.min.y + 1..arena.bounds.max.y - enemy_size.y)
+                        )
+                    })
+                    .find(|position| {
+                        players
+                            .iter()
+                            .all(|player| player.distance(position.as_vec2()) > SPAWN_DISTANCE) &&
+                            (0..enemy_size.x)
+                                .cartesian_product(0..enemy_size.y)
+                                .all(|(x, y)| {
+                                    chunk_manager
+                                        .get(*position + ivec2(x, y))
+                                        .is_ok_and(|pixel| pixel.is_empty())
+                                })
+                    });
+
+                if let Some(position) = position {
+                    let (enemy, enemy_hitbox) = enemy_fn(position.as_vec2() / (CHUNK_SIZE as f32));
+
+                    enemies.push(
+                        commands
+                            .spawn(enemy)
+                            .with_children(|parent| {
+                                parent.spawn(enemy_hitbox);
+                            })
+                            .id()
+                    );
+                }
+            }
+        }
+
+        arena.enemies = enemies;
+        arena.state = ArenaState::Sealed;
+
+        spawn_warning(&mut commands, SEALED_MESSAGE);
+    }
+}
+
+/// Opens the arena once every enemy of its encounter is gone and leaves the loot on its floor
+fn clear_arenas(
+    mut commands: Commands,
+    mut arena_q: Query<&mut Arena>,
+    enemy_q: Query<(), With<Enemy>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>
+) {
+    for mut arena in arena_q.iter_mut() {
+        if arena.state != ArenaState::Sealed || arena.enemies.iter().any(|enemy| enemy_q.contains(*enemy)) {
+            continue;
+        }
+
+        for position in std::mem::take(&mut arena.sealed) {
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            if pixel.material.id != arena.seal_material {
+                continue;
+            }
+
+            *pixel = Pixel::default();
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+
+        if !arena.loot.is_empty() {
+            if let Some(floor) = find_floor(&chunk_manager, arena.bounds) {
+                spawn_chest(&mut commands, floor, &arena.loot, &registries);
+            }
+        }
+
+        arena.enemies.clear();
+        arena.state = ArenaState::Cleared;
+
+        spawn_warning(&mut commands, CLEARED_MESSAGE);
+    }
+}
+
+/// First empty pixel above the ground, searched down from the center of the arena
+fn find_floor(chunk_manager: &ChunkManager, bounds: IRect) -> Option<IVec2> {
+    let center = bounds.center();
+
+    (bounds.min.y + 1..=center.y)
+        .rev()
+        .map(|y| ivec2(center.x, y))
+        .find(|position| {
+            chunk_manager.get(*position).is_ok_and(|pixel| pixel.is_empty()) &&
+                chunk_manager
+                    .get(*position - IVec2::Y)
+                    .is_ok_and(|pixel| !pixel.is_empty())
+        })
+}
@@ -62,6 +62,12 @@ pub struct LayoutAssetCollection {
     pub folder: HashMap<String, Handle<Image>>,
 }
 
+#[derive(AssetCollection, Resource)]
+pub struct StampAssetCollection {
+    #[asset(path = "stamps", collection(typed, mapped))]
+    pub folder: HashMap<String, Handle<Image>>,
+}
+
 #[derive(AssetCollection, Resource, Clone)]
 pub struct SpriteAssetCollection {
     #[asset(path = "player/alchemist.png")]
@@ -136,6 +142,9 @@ pub struct SpriteAssetCollection {
     #[asset(image(sampler = linear))]
     pub help_divider_horizontal: Handle<Image>,
 
+    #[asset(path = "ui/loading.png")]
+    pub loading: Handle<Image>,
+
     #[asset(path = "ui/health_border.png")]
     pub in_game_border: Handle<Image>,
 }
@@ -168,6 +177,9 @@ pub struct AudioAssetCollection {
 
     #[asset(path = "audio/liquid", collection(typed, mapped))]
     pub liquid: HashMap<String, Handle<AudioSource>>,
+
+    #[asset(path = "audio/destroy", collection(typed, mapped))]
+    pub surface: HashMap<String, Handle<AudioSource>>,
 }
 
 pub fn process_assets(
@@ -0,0 +1,413 @@
+use bevy::prelude::*;
+use bevy_math::{ ivec2, vec2 };
+use bevy_rapier2d::prelude::*;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::player::{ CursorPosition, Player, PlayerActions },
+    constants::CHUNK_SIZE,
+    despawn_component,
+    interactables::unlock_doors,
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        colliders::OBJECT_MASK,
+        dirty_rect::DirtyRects,
+        materials::PhysicsType,
+        object::{ fill_objects, unfill_objects, Object, ObjectBundle },
+        pixel::Pixel,
+    },
+    state::GameState,
+    vehicle::Piloting,
+};
+
+const BUCKET_MATERIAL: &str = "wood";
+const BUCKET_SIZE: IVec2 = IVec2::new(12, 12);
+const WALL_THICKNESS: i32 = 2;
+/// Amount of liquid pixels a bucket holds, equals to the amount of its inner pixels
+const BUCKET_CAPACITY: u32 = ((BUCKET_SIZE.x - WALL_THICKNESS * 2) *
+    (BUCKET_SIZE.y - WALL_THICKNESS)) as u32;
+
+const FLOW_INTERVAL: f32 = 0.02;
+const SCOOP_PER_TICK: u32 = 2;
+const POUR_PER_TICK: u32 = 1;
+// bucket is carried in front of the player with a small gap, so it does not block movement
+const CARRY_OFFSET: Vec2 = Vec2::new(15.0, 2.0);
+const POUR_ANGLE: f32 = 110.0;
+const PICKUP_DISTANCE: f32 = 24.0;
+const THROW_SPEED: f32 = 1.5;
+const SPAWN_PLACEMENT_ATTEMPTS: u32 = 64;
+
+pub struct BucketPlugin;
+
+impl Plugin for BucketPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, spawn_bucket)
+            .add_systems(OnEnter(GameState::LevelInitialization), (
+                despawn_component::<Bucket>,
+                |mut commands: Commands, player_q: Query<Entity, With<Carrying>>| {
+                    for entity in player_q.iter() {
+                        commands.entity(entity).remove::<Carrying>();
+                    }
+                },
+            ))
+            .add_systems(
+                Update,
+                (interact_with_buckets.after(unlock_doors), transfer_liquid).run_if(
+                    in_state(GameState::Game)
+                )
+            )
+            .add_systems(
+                FixedUpdate,
+                (carry_buckets, update_bucket_pixels)
+                    .chain()
+                    .after(unfill_objects)
+                    .before(PhysicsSet::SyncBackend)
+                    .before(fill_objects)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Container that scoops liquid while upright and pours it out once tipped over
+#[derive(Component)]
+pub struct Bucket {
+    pub material: Option<String>,
+    pub amount: u32,
+    pub timer: Timer,
+    /// Contents changed and pixels of the object have to be redrawn
+    pub dirty: bool,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            material: None,
+            amount: 0,
+            timer: Timer::from_seconds(FLOW_INTERVAL, TimerMode::Repeating),
+            dirty: false,
+        }
+    }
+}
+
+/// Bucket held by the player
+#[derive(Component)]
+pub struct Carrying(pub Entity);
+
+/// Player holding the bucket
+#[derive(Component)]
+pub struct CarriedBy(pub Entity);
+
+fn is_wall(position: IVec2) -> bool {
+    position.y < WALL_THICKNESS ||
+        position.x < WALL_THICKNESS ||
+        position.x >= BUCKET_SIZE.x - WALL_THICKNESS
+}
+
+/// Inner positions of the bucket from the bottom row to the top one
+fn inner_positions() -> impl Iterator<Item = IVec2> {
+    (WALL_THICKNESS..BUCKET_SIZE.y)
+        .cartesian_product(WALL_THICKNESS..BUCKET_SIZE.x - WALL_THICKNESS)
+        .map(|(y, x)| ivec2(x, y))
+}
+
+/// Converts position inside of the bucket into world pixel position
+fn to_world(transform: &Transform, position: IVec2) -> IVec2 {
+    let local = position.as_vec2() - BUCKET_SIZE.as_vec2() / 2.0 + 0.5;
+
+    (transform.translation.xy() * (CHUNK_SIZE as f32) + transform.rotation.mul_vec3(local.extend(0.0)).xy())
+        .round()
+        .as_ivec2()
+}
+
+fn spawn_bucket(
+    mut commands: Commands,
+    chunk_manager: Res<ChunkManager>,
+    registries: Res<Registries>
+) {
+    let wood = &registries.materials[BUCKET_MATERIAL];
+
+    let pixels = (0..BUCKET_SIZE.y)
+        .cartesian_product(0..BUCKET_SIZE.x)
+        .map(|(y, x)| is_wall(ivec2(x, y)).then(|| Pixel::from(wood)))
+        .collect_vec();
+
+    let Ok(object) = Object::from_pixels(pixels, BUCKET_SIZE) else {
+        return;
+    };
+    let Ok(collider) = object.create_collider() else {
+        return;
+    };
+
+    // bucket is placed near the spawn, where the terrain is cleared for the player
+    let Some(position) = (0..SPAWN_PLACEMENT_ATTEMPTS)
+        .map(|attempt| ivec2(16 + (attempt as i32) % 8 * 4, 4 + (attempt as i32) / 8 * 4))
+        .find(|position| {
+            (0..BUCKET_SIZE.x)
+                .cartesian_product(0..BUCKET_SIZE.y)
+                .all(|(x, y)| {
+                    chunk_manager
+                        .get(*position + ivec2(x, y))
+                        .is_ok_and(|pixel| pixel.is_empty())
+                })
+        }) else {
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Bucket"),
+        Bucket::default(),
+        ObjectBundle {
+            object,
+            collider,
+            transform: TransformBundle {
+                local: Transform::from_translation(
+                    ((position.as_vec2() + BUCKET_SIZE.as_vec2() / 2.0) / (CHUNK_SIZE as f32)).extend(0.0)
+                ),
+                ..Default::default()
+            },
+            mass_properties: ColliderMassProperties::Density(2.0),
+            ..Default::default()
+        },
+    ));
+}
+
+fn release_bucket(commands: &mut Commands, bucket: Entity, velocity: Vec2) {
+    // bucket might be gone already, e.g. taken into the inventory
+    let Some(mut bucket) = commands.get_entity(bucket) else {
+        return;
+    };
+
+    bucket
+        .remove::<CarriedBy>()
+        .insert((
+            RigidBody::Dynamic,
+            Velocity::linear(velocity),
+            CollisionGroups::new(Group::from_bits_truncate(OBJECT_MASK), Group::all()),
+        ));
+}
+
+/// Interaction picks up the closest bucket or drops the held one, shooting throws it towards the aim,
+/// chests and locks get the press first
+#[allow(clippy::type_complexity)]
+pub fn interact_with_buckets(
+    mut commands: Commands,
+    mut player_q: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &mut ActionState<PlayerActions>,
+            Option<&CursorPosition>,
+            Option<&Carrying>,
+        ),
+        (With<Player>, Without<Piloting>)
+    >,
+    bucket_q: Query<(Entity, &Transform), (With<Bucket>, Without<CarriedBy>)>
+) {
+    for (entity, transform, velocity, mut action_state, cursor_position, carrying) in player_q.iter_mut() {
+        if let Some(carrying) = carrying {
+            if action_state.just_pressed(&PlayerActions::Interaction) {
+                action_state.consume(&PlayerActions::Interaction);
+                commands.entity(entity).remove::<Carrying>();
+                release_bucket(&mut commands, carrying.0, velocity.linvel / 16.0);
+            } else if action_state.just_pressed(&PlayerActions::Shoot) {
+                let direction = cursor_position.map_or(Vec2::Y, |cursor_position| {
+                    cursor_position.direction
+                });
+
+                commands.entity(entity).remove::<Carrying>();
+                release_bucket(
+                    &mut commands,
+                    carrying.0,
+                    direction * THROW_SPEED + velocity.linvel / 16.0
+                );
+            }
+
+            continue;
+        }
+
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        let Some((bucket, _)) = bucket_q
+            .iter()
+            .map(|(bucket, bucket_transform)| {
+                (bucket, bucket_transform.translation.xy().distance(transform.translation.xy()))
+            })
+            .filter(|(_, distance)| *distance < PICKUP_DISTANCE / (CHUNK_SIZE as f32))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b)) else {
+            continue;
+        };
+
+        action_state.consume(&PlayerActions::Interaction);
+
+        commands.entity(entity).insert(Carrying(bucket));
+        commands
+            .entity(bucket)
+            .insert((
+                CarriedBy(entity),
+                RigidBody::KinematicPositionBased,
+                CollisionGroups::new(Group::from_bits_truncate(OBJECT_MASK), Group::NONE),
+            ));
+    }
+}
+
+/// Moves held buckets along with players, crouching tips the bucket forward to pour out the contents
+#[allow(clippy::type_complexity)]
+fn carry_buckets(
+    mut commands: Commands,
+    mut bucket_q: Query<(Entity, &mut Transform, &mut Sleeping, &Object, &CarriedBy), With<Bucket>>,
+    player_q: Query<(&Transform, &ActionState<PlayerActions>), (With<Player>, Without<Bucket>)>
+) {
+    for (entity, mut transform, mut sleeping, object, carried_by) in bucket_q.iter_mut() {
+        let Ok((player_transform, action_state)) = player_q.get(carried_by.0) else {
+            release_bucket(&mut commands, entity, Vec2::ZERO);
+            continue;
+        };
+
+        sleeping.sleeping = false;
+
+        // pixels of the bucket are still in the world, it can not be moved until they are taken out
+        if object.placed {
+            continue;
+        }
+
+        let facing = (player_transform.rotation.y + 0.5) * 2.0;
+        let offset = vec2(CARRY_OFFSET.x * facing, CARRY_OFFSET.y) / (CHUNK_SIZE as f32);
+
+        transform.translation = (player_transform.translation.xy() + offset).extend(
+            transform.translation.z
+        );
+        transform.rotation = if action_state.pressed(&PlayerActions::Crouch) {
+            Quat::from_rotation_z(-facing * POUR_ANGLE.to_radians())
+        } else {
+            Quat::IDENTITY
+        };
+    }
+}
+
+/// Redraws inner pixels of the bucket to show how much liquid it holds
+fn update_bucket_pixels(
+    mut bucket_q: Query<(&mut Bucket, &mut Object)>,
+    registries: Res<Registries>
+) {
+    for (mut bucket, mut object) in bucket_q.iter_mut() {
+        if !bucket.dirty || object.placed {
+            continue;
+        }
+
+        let material = bucket.material
+            .as_ref()
+            .and_then(|material| registries.materials.get(material));
+
+        for (index, position) in inner_positions().enumerate() {
+            let pixel = &mut object.pixels[(position.y * BUCKET_SIZE.x + position.x) as usize];
+
+            *pixel = match material {
+                Some(material) if (index as u32) < bucket.amount => Some(Pixel::from(material)),
+                _ => None,
+            };
+        }
+
+        object.pixel_count = object.pixels
+            .iter()
+            .filter(|pixel| pixel.is_some())
+            .count();
+        bucket.dirty = false;
+    }
+}
+
+/// Upright buckets scoop liquid around their opening, tipped over ones pour it out
+fn transfer_liquid(
+    mut bucket_q: Query<(&mut Bucket, &Transform, &mut Sleeping)>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    for (mut bucket, transform, mut sleeping) in bucket_q.iter_mut() {
+        if !bucket.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let up = transform.rotation.mul_vec3(Vec3::Y).y;
+        let amount = bucket.amount;
+
+        if up > 0.5 && bucket.amount < BUCKET_CAPACITY {
+            let mut scooped = 0;
+
+            // opening of the bucket and two rows above it
+            for position in inner_positions().chain(
+                (BUCKET_SIZE.y..BUCKET_SIZE.y + 2)
+                    .cartesian_product(WALL_THICKNESS..BUCKET_SIZE.x - WALL_THICKNESS)
+                    .map(|(y, x)| ivec2(x, y))
+            ) {
+                if scooped == SCOOP_PER_TICK || bucket.amount == BUCKET_CAPACITY {
+                    break;
+                }
+
+                let world_position = to_world(transform, position);
+
+                let Ok(pixel) = chunk_manager.get(world_position) else {
+                    continue;
+                };
+
+                if
+                    !matches!(pixel.physics_type, PhysicsType::Liquid(..)) ||
+                    bucket.material.as_ref().is_some_and(|material| *material != pixel.material.id)
+                {
+                    continue;
+                }
+
+                bucket.material = Some(pixel.material.id.clone());
+
+                if chunk_manager.set(world_position, Pixel::default()).is_ok() {
+                    bucket.amount += 1;
+                    scooped += 1;
+
+                    dirty_rects.request_update(world_position);
+                    dirty_rects.request_render(world_position);
+                }
+            }
+        } else if up < 0.0 && bucket.amount > 0 {
+            let Some(material) = bucket.material
+                .as_ref()
+                .and_then(|material| registries.materials.get(material)) else {
+                continue;
+            };
+
+            let mut poured = 0;
+
+            for x in WALL_THICKNESS..BUCKET_SIZE.x - WALL_THICKNESS {
+                if poured == POUR_PER_TICK || bucket.amount == 0 {
+                    break;
+                }
+
+                let world_position = to_world(transform, ivec2(x, BUCKET_SIZE.y + 1));
+
+                if
+                    chunk_manager.get(world_position).is_ok_and(|pixel| pixel.is_empty()) &&
+                    chunk_manager.set(world_position, Pixel::from(material)).is_ok()
+                {
+                    bucket.amount -= 1;
+                    poured += 1;
+
+                    dirty_rects.request_update(world_position);
+                    dirty_rects.request_render(world_position);
+                }
+            }
+
+            if bucket.amount == 0 {
+                bucket.material = None;
+            }
+        }
+
+        if bucket.amount != amount {
+            bucket.dirty = true;
+            sleeping.sleeping = false;
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use bevy::{ prelude::*, time::common_conditions::on_timer, window::PrimaryWindow };
+use bevy_egui::{ egui, EguiContext };
+use bevy_rapier2d::{ geometry::Collider, prelude::ColliderView };
+
+use crate::{
+    gui::egui_has_primary_context,
+    has_window,
+    painter::BrushRes,
+    profiler::ProfilerOverlay,
+    simulation::{ dirty_rect::DirtyRects, particle::Particle },
+    state::GameState,
+};
+
+const COUNTERS_INTERVAL: Duration = Duration::from_millis(500);
+// share of a budget after which the meter turns into a warning
+const WARNING_SHARE: f32 = 0.8;
+// the fps counter grows into the profiler overlay while it is open
+const PROFILER_OFFSET: f32 = 200.0;
+
+pub const PARTICLE_BUDGET: usize = 4000;
+pub const COLLIDER_VERTEX_BUDGET: usize = 20000;
+pub const AWAKE_CHUNK_BUDGET: usize = 64;
+
+pub struct BudgetPlugin;
+
+impl Plugin for BudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PerformanceCounters>().add_systems(
+            Update,
+            (
+                update_counters.run_if(on_timer(COUNTERS_INTERVAL)),
+                ui_budget_system.run_if(has_window).run_if(egui_has_primary_context),
+            )
+                .chain()
+                .run_if(in_state(GameState::Game))
+        );
+    }
+}
+
+/// Amount of work the engine is doing, sampled a couple of times per second
+#[derive(Resource, Default)]
+pub struct PerformanceCounters {
+    pub particles: usize,
+    pub collider_vertices: usize,
+    /// Chunks that are simulated on the next pass
+    pub awake_chunks: usize,
+}
+
+struct Meter {
+    name: &'static str,
+    value: usize,
+    budget: usize,
+    suggestion: &'static str,
+}
+
+impl PerformanceCounters {
+    fn meters(&self) -> [Meter; 3] {
+        [
+            Meter {
+                name: "Particles",
+                value: self.particles,
+                budget: PARTICLE_BUDGET,
+                suggestion: "let particles settle before spawning more",
+            },
+            Meter {
+                name: "Collider vertices",
+                value: self.collider_vertices,
+                budget: COLLIDER_VERTEX_BUDGET,
+                suggestion: "paint solid shapes instead of thin or noisy ones",
+            },
+            Meter {
+                name: "Awake chunks",
+                value: self.awake_chunks,
+                budget: AWAKE_CHUNK_BUDGET,
+                suggestion: "let liquids and powders come to rest to freeze the region",
+            },
+        ]
+    }
+}
+
+pub fn collider_vertices(collider: ColliderView) -> usize {
+    match collider {
+        ColliderView::Polyline(polyline) => polyline.vertices().len(),
+        ColliderView::ConvexPolygon(polygon) => polygon.points().len(),
+        ColliderView::TriMesh(mesh) => mesh.vertices().len(),
+        ColliderView::Compound(compound) =>
+            compound
+                .shapes()
+                .map(|(_, _, shape)| collider_vertices(shape))
+                .sum(),
+        _ => 1,
+    }
+}
+
+fn update_counters(
+    mut counters: ResMut<PerformanceCounters>,
+    particle_q: Query<&Particle>,
+    collider_q: Query<&Collider>,
+    dirty_rects: Res<DirtyRects>
+) {
+    counters.particles = particle_q.iter().filter(|particle| particle.active).count();
+    counters.collider_vertices = collider_q
+        .iter()
+        .map(|collider| collider_vertices(collider.as_typed_shape()))
+        .sum();
+    counters.awake_chunks = dirty_rects.current.len();
+}
+
+fn ui_budget_system(
+    counters: Res<PerformanceCounters>,
+    brush: Res<BrushRes>,
+    overlay: Res<ProfilerOverlay>,
+    mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
+) {
+    let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
+        return;
+    };
+
+    let meters = counters.meters();
+    let warning = meters
+        .iter()
+        .any(|meter| (meter.value as f32) >= (meter.budget as f32) * WARNING_SHARE);
+
+    // meters are only interesting while painting or when something is close to the limit
+    if brush.material.is_none() && !warning {
+        return;
+    }
+
+    let ctx = egui_ctx.get_mut();
+
+    egui::Window
+        ::new("Budget")
+        .auto_sized()
+        .title_bar(false)
+        // right above the fps counter, bottom left corner belongs to the minimap
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2 {
+            x: -ctx.pixels_per_point() * 8.0,
+            y: -ctx.pixels_per_point() * (if overlay.0 { PROFILER_OFFSET } else { 40.0 }),
+        })
+        .show(ctx, |ui| {
+            ui.set_max_width(ctx.pixels_per_point() * 120.0);
+
+            for meter in meters.iter() {
+                let share = (meter.value as f32) / (meter.budget as f32);
+
+                ui.add(
+                    egui::ProgressBar
+                        ::new(share.min(1.0))
+                        .text(format!("{}: {}/{}", meter.name, meter.value, meter.budget))
+                );
+
+                if share >= WARNING_SHARE {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(0xff, 0x6a, 0x3d),
+                        format!("Tip: {}", meter.suggestion)
+                    );
+                }
+            }
+        });
+}
@@ -11,21 +11,27 @@ use bevy::{
             TextureFormat,
             TextureUsages,
         },
-        texture::BevyDefault,
+        texture::{ BevyDefault, ImageSampler },
         view::RenderLayers,
     },
     window::{ PrimaryWindow, WindowResized },
 };
 use bevy_math::vec2;
+use bevy_persistent::Persistent;
+use leafwing_input_manager::action_state::ActionState;
 use log::info;
 
 use crate::{
-    actors::player::Player,
-    constants::CHUNK_SIZE,
+    actors::player::{ Player, PlayerActions, RemotePlayer },
+    constants::{ CAMERA_Z, CHUNK_SIZE },
     postprocessing::{
         light_apply::LightApply,
+        heat_haze::HeatHaze,
         light_propagate::LightPropagationSettings,
+        retro_filter::RetroFilter,
+        water::WaterSurfaces,
     },
+    settings::Config,
     state::GameState,
 };
 
@@ -39,8 +45,37 @@ pub struct TrackingCamera {
     pub speed: f64,
     pub recenter_timeout: f32,
     pub last_track: f32,
+    /// Multiplier of the projection scale, follows preferred zoom and grows to keep every player on screen
+    pub zoom: f32,
 }
 
+/// Point in world units the camera glides to instead of following the players, set by cutscenes
+#[derive(Resource)]
+pub struct CameraFocus(pub Vec2);
+
+/// Furthest the camera is pushed away from its position in pixels, every frame in a random direction
+#[derive(Resource, Default)]
+pub struct CameraShake(pub f32);
+
+/// Projection scale of a camera at default zoom
+#[derive(Component)]
+pub struct BaseScale(pub f32);
+
+// how far the camera may zoom out to fit all players
+const MAX_PLAYERS_ZOOM: f32 = 2.0;
+// share of the screen players may spread over before zooming out
+const PLAYERS_SCREEN_SHARE: f32 = 0.7;
+// multiplier of the preferred zoom applied by a single zoom keypress
+const ZOOM_STEP: f32 = 1.25;
+// distance between light samples in texels of the lighting texture at default zoom
+const LIGHT_OFFSET: f32 = 4.0;
+// projection scale of world cameras rendering at the full window resolution
+const DEFAULT_SCALE: f32 = 0.375 / (CHUNK_SIZE as f32);
+// the lighting texture covers a bit more than the view so light from outside of it is visible
+const LIGHTING_SCALE: f32 = 1.25;
+// the heat map is blurry anyway, a quarter of the world resolution is enough
+const HEAT_MAP_SCALE: f32 = 0.25;
+
 impl Default for TrackingCamera {
     fn default() -> Self {
         Self {
@@ -52,6 +87,7 @@ impl Default for TrackingCamera {
             speed: 0.98,
             recenter_timeout: 3.0,
             last_track: 0.0,
+            zoom: 1.0,
         }
     }
 }
@@ -116,17 +152,93 @@ impl TrackingCamera {
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn update_camera(
-    player_q: Query<&Transform, With<Player>>,
-    mut camera_q: Query<(&mut Transform, &mut TrackingCamera), (With<Camera>, Without<Player>)>,
+    player_q: Query<&Transform, (With<Player>, Without<RemotePlayer>)>,
+    mut camera_q: Query<
+        (&mut Transform, &mut TrackingCamera, &mut OrthographicProjection, &BaseScale),
+        (With<Camera>, Without<Player>)
+    >,
+    mut projection_q: Query<(&mut OrthographicProjection, &BaseScale), Without<TrackingCamera>>,
+    mut light_q: Query<&mut LightPropagationSettings>,
+    world_target: Res<WorldRenderTarget>,
+    config: Res<Persistent<Config>>,
+    focus: Option<Res<CameraFocus>>,
+    shake: Res<CameraShake>,
     time: Res<Time>
 ) {
-    let transform = player_q.single();
-    let (mut camera_transform, mut camera_tracking) = camera_q.single_mut();
+    let Some(bounds) = player_q
+        .iter()
+        .map(|transform| Rect::from_center_size(transform.translation.xy(), Vec2::ZERO))
+        .reduce(|bounds, rect| bounds.union(rect)) else {
+        return;
+    };
+
+    let (mut camera_transform, mut camera_tracking, mut projection, base_scale) =
+        camera_q.single_mut();
     let dt = time.delta_seconds_f64();
 
-    camera_tracking.update(transform.translation.xy(), dt);
-    camera_transform.translation = camera_tracking.position.extend(4.0);
+    // camera follows the middle point between players and zooms out when they spread apart
+    let base_area = projection.area.size() / camera_tracking.zoom;
+    let players_zoom = (bounds.size() / (base_area * PLAYERS_SCREEN_SHARE))
+        .max_element()
+        .min(MAX_PLAYERS_ZOOM);
+    let target_zoom = config.zoom
+        .max(config.zoom_range[0])
+        .min(config.zoom_range[1])
+        .max(players_zoom);
+
+    let lerp = 1.0 - ((1.0 - camera_tracking.speed).powf(dt) as f32);
+    camera_tracking.zoom += (target_zoom - camera_tracking.zoom) * lerp;
+
+    if let Some(focus) = focus {
+        camera_tracking.target = focus.0;
+        camera_tracking.position = camera_tracking.position.lerp(focus.0, lerp);
+    } else {
+        camera_tracking.update(bounds.center(), dt);
+    }
+    camera_transform.translation = camera_tracking.position.extend(CAMERA_Z);
+
+    if shake.0 > 0.0 {
+        let offset = vec2(fastrand::f32() * 2.0 - 1.0, fastrand::f32() * 2.0 - 1.0) * shake.0;
+        camera_transform.translation += (offset / (CHUNK_SIZE as f32)).extend(0.0);
+    }
+
+    projection.scale = base_scale.0 * camera_tracking.zoom;
+    for (mut projection, base_scale) in projection_q.iter_mut() {
+        projection.scale = base_scale.0 * camera_tracking.zoom;
+    }
+
+    // light spreads by texels, so the offset shrinks with zoom and render scale to cover
+    // the same distance in world
+    let preset = config.lighting_quality.preset();
+    for mut light_settings in light_q.iter_mut() {
+        light_settings.offset = (LIGHT_OFFSET * preset.radius * world_target.scale) / camera_tracking.zoom;
+        light_settings.passes = preset.passes;
+        light_settings.falloff = preset.falloff;
+    }
+}
+
+pub fn zoom_camera(
+    player_q: Query<&ActionState<PlayerActions>, Without<RemotePlayer>>,
+    mut config: ResMut<Persistent<Config>>
+) {
+    let mut zoom = config.zoom;
+
+    for action_state in player_q.iter() {
+        if action_state.just_pressed(&PlayerActions::ZoomIn) {
+            zoom /= ZOOM_STEP;
+        }
+        if action_state.just_pressed(&PlayerActions::ZoomOut) {
+            zoom *= ZOOM_STEP;
+        }
+    }
+
+    let zoom = zoom.max(config.zoom_range[0]).min(config.zoom_range[1]);
+    if zoom != config.zoom {
+        config.zoom = zoom;
+        config.persist().expect("failed to update config");
+    }
 }
 
 pub const BACKGROUND_RENDER_LAYER: u8 = 1;
@@ -134,6 +246,9 @@ pub const TERRAIN_RENDER_LAYER: u8 = 2;
 pub const ACTOR_RENDER_LAYER: u8 = 3;
 pub const PARTICLE_RENDER_LAYER: u8 = 4;
 pub const LIGHTING_RENDER_LAYER: u8 = 5;
+pub const UPSCALE_RENDER_LAYER: u8 = 6;
+pub const LIQUID_RENDER_LAYER: u8 = 7;
+pub const HEAT_RENDER_LAYER: u8 = 8;
 
 #[derive(Default, Resource, ExtractResource, Clone)]
 pub struct LightingTexture {
@@ -141,51 +256,218 @@ pub struct LightingTexture {
     pub texture: Handle<Image>,
 }
 
-fn on_resize_system(
+/// Depth and water styles of the liquids on screen, same size as the [`WorldRenderTarget`]
+/// so every texel matches the one of the world
+#[derive(Default, Resource, ExtractResource, Clone)]
+pub struct LiquidMaskTexture {
+    pub texture: Handle<Image>,
+}
+
+/// Heat and smoke over the screen at a quarter of the [`WorldRenderTarget`]
+/// resolution, sampled smoothly by the heat haze shader
+#[derive(Default, Resource, ExtractResource, Clone)]
+pub struct HeatMapTexture {
+    pub texture: Handle<Image>,
+}
+
+/// Image the world cameras render into at a share of the window resolution,
+/// it is drawn over the whole window with nearest-neighbor sampling
+#[derive(Default, Resource, Clone)]
+pub struct WorldRenderTarget {
+    pub scale: f32,
+    pub size: UVec2,
+    pub texture: Handle<Image>,
+}
+
+impl WorldRenderTarget {
+    /// Cursor position on the window converted to the viewport of the world cameras
+    pub fn viewport_position(&self, window: &Window, position: Vec2) -> Vec2 {
+        (position * self.size.as_vec2()) / vec2(window.width(), window.height())
+    }
+}
+
+/// Camera rendering into a scaled target, its base scale follows the render scale
+#[derive(Component)]
+pub struct RenderScaled;
+
+/// Sprite showing the world render target on the window
+#[derive(Component)]
+pub struct UpscaleSprite;
+
+#[derive(Component)]
+pub struct LightingCamera;
+
+fn render_texture(size: Extent3d) -> Image {
+    let mut texture = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING |
+            TextureUsages::COPY_DST |
+            TextureUsages::COPY_SRC |
+            TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+
+    texture.resize(size);
+    texture
+}
+
+fn world_target_size(window: &Window, render_scale: f32) -> UVec2 {
+    (vec2(window.physical_width() as f32, window.physical_height() as f32) * render_scale)
+        .round()
+        .as_uvec2()
+        .max(UVec2::ONE)
+}
+
+fn heat_map_size(world_target_size: UVec2) -> Extent3d {
+    let size = (world_target_size.as_vec2() * HEAT_MAP_SCALE).ceil().as_uvec2().max(UVec2::ONE);
+
+    Extent3d {
+        width: size.x,
+        height: size.y,
+        ..Default::default()
+    }
+}
+
+fn resize_texture(images: &mut Assets<Image>, texture: &Handle<Image>, size: Extent3d) {
+    let image = images.get_mut(texture.clone()).unwrap();
+
+    image.texture_descriptor = TextureDescriptor {
+        size,
+        ..image.texture_descriptor.clone()
+    };
+
+    image.resize(size);
+}
+
+/// Resizes the render targets when the window or the render scale change,
+/// the world cameras are rescaled to keep covering the same area
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn resize_render_targets(
     mut resize_reader: EventReader<WindowResized>,
-    lighting: Res<LightingTexture>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut BaseScale, &mut OrthographicProjection), With<RenderScaled>>,
+    mut sprite_q: Query<&mut Sprite, With<UpscaleSprite>>,
+    mut lighting: ResMut<LightingTexture>,
+    mut world_target: ResMut<WorldRenderTarget>,
+    liquid_mask: Res<LiquidMaskTexture>,
+    heat_map: Res<HeatMapTexture>,
     mut images: ResMut<Assets<Image>>,
+    config: Res<Persistent<Config>>
 ) {
-    for e in resize_reader.read() {
-        let size = Extent3d {
-            width: (e.width.ceil() * lighting.scale) as u32,
-            height: (e.height.ceil() * lighting.scale) as u32,
-            ..Default::default()
-        };
+    let resized = resize_reader.read().last().is_some();
+
+    if !resized && world_target.scale == config.render_scale {
+        return;
+    }
 
-        let image = images.get_mut(lighting.texture.clone()).unwrap();
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
 
-        image.texture_descriptor = TextureDescriptor {
-            size,
-            ..image.texture_descriptor.clone()
-        };
+    let render_scale = config.render_scale;
+    let previous_scale = world_target.scale;
 
-        info!("window was resized: {}:{}", e.width, e.height);
+    lighting.scale = LIGHTING_SCALE * render_scale;
+    resize_texture(&mut images, &lighting.texture, Extent3d {
+        width: (window.width() * lighting.scale).round() as u32,
+        height: (window.height() * lighting.scale).round() as u32,
+        ..Default::default()
+    });
 
-        image.resize(size);
+    world_target.scale = render_scale;
+    world_target.size = world_target_size(window, render_scale);
+    let extent = Extent3d {
+        width: world_target.size.x,
+        height: world_target.size.y,
+        ..Default::default()
+    };
+    resize_texture(&mut images, &world_target.texture, extent);
+    resize_texture(&mut images, &liquid_mask.texture, extent);
+    resize_texture(&mut images, &heat_map.texture, heat_map_size(world_target.size));
+
+    for (mut base_scale, mut projection) in camera_q.iter_mut() {
+        let zoom = projection.scale / base_scale.0;
+
+        // targets keep their share of the render scale, so cameras keep theirs of the base scale
+        base_scale.0 *= previous_scale / render_scale;
+        projection.scale = base_scale.0 * zoom;
     }
-}
 
-#[derive(Component)]
-pub struct LightingCamera;
+    for mut sprite in sprite_q.iter_mut() {
+        sprite.custom_size = Some(vec2(window.width(), window.height()));
+    }
+
+    info!(
+        "render targets resized: {}:{} at {}",
+        window.width(),
+        window.height(),
+        render_scale
+    );
+}
 
 // TODO: optimize by moving processing of terrain into separate pipeline and downscalling
+#[allow(clippy::too_many_arguments)]
 fn setup_camera(
     mut commands: Commands,
     mut time: ResMut<Time<Fixed>>,
     lighting: Res<LightingTexture>,
+    world_target: Res<WorldRenderTarget>,
+    liquid_mask: Res<LiquidMaskTexture>,
+    heat_map: Res<HeatMapTexture>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    config: Res<Persistent<Config>>
 ) {
     time.set_timestep_hz(58.0);
 
+    let preset = config.lighting_quality.preset();
+
+    let window = window_q.single();
+
+    commands.spawn((
+        Name::new("Upscale"),
+        Camera2dBundle {
+            camera: Camera {
+                order: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RetroFilter::default(),
+        RenderLayers::layer(UPSCALE_RENDER_LAYER),
+    ));
+
+    commands.spawn((
+        Name::new("World render"),
+        UpscaleSprite,
+        SpriteBundle {
+            texture: world_target.texture.clone(),
+            sprite: Sprite {
+                custom_size: Some(vec2(window.width(), window.height())),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RenderLayers::layer(UPSCALE_RENDER_LAYER),
+    ));
+
     commands
         .spawn((
             Camera2dBundle {
                 camera: Camera {
                     order: 0,
+                    target: RenderTarget::Image(world_target.texture.clone()),
                     ..Default::default()
                 },
                 projection: OrthographicProjection {
-                    scale: 0.375 / (CHUNK_SIZE as f32),
+                    scale: DEFAULT_SCALE / world_target.scale,
                     ..Default::default()
                 },
                 ..Default::default()
@@ -193,7 +475,11 @@ fn setup_camera(
             InheritedVisibility::VISIBLE,
             Visibility::Visible,
             TrackingCamera::default(),
+            BaseScale(DEFAULT_SCALE / world_target.scale),
+            RenderScaled,
             LightApply,
+            WaterSurfaces::default(),
+            HeatHaze::default(),
             RenderLayers::from_layers(
                 &[
                     BACKGROUND_RENDER_LAYER,
@@ -218,15 +504,61 @@ fn setup_camera(
                         ..Default::default()
                     },
                     projection: OrthographicProjection {
-                        scale: (0.375 / (CHUNK_SIZE as f32) / lighting.scale) * 1.25,
+                        scale: (DEFAULT_SCALE / lighting.scale) * LIGHTING_SCALE,
                         ..Default::default()
                     },
                     ..Default::default()
                 },
-                LightPropagationSettings { offset: 4.0, passes: 8 },
+                BaseScale((DEFAULT_SCALE / lighting.scale) * LIGHTING_SCALE),
+                RenderScaled,
+                LightPropagationSettings {
+                    offset: LIGHT_OFFSET * preset.radius,
+                    passes: preset.passes,
+                    falloff: preset.falloff,
+                },
                 RenderLayers::layer(LIGHTING_RENDER_LAYER),
             ));
 
+            parent.spawn((
+                Name::new("Liquids"),
+                Camera2dBundle {
+                    camera: Camera {
+                        order: -2,
+                        clear_color: ClearColorConfig::Custom(Color::rgba_from_array([0.0; 4])),
+                        target: RenderTarget::Image(liquid_mask.texture.clone()),
+                        ..Default::default()
+                    },
+                    projection: OrthographicProjection {
+                        scale: DEFAULT_SCALE / world_target.scale,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                BaseScale(DEFAULT_SCALE / world_target.scale),
+                RenderScaled,
+                RenderLayers::layer(LIQUID_RENDER_LAYER),
+            ));
+
+            parent.spawn((
+                Name::new("Heat"),
+                Camera2dBundle {
+                    camera: Camera {
+                        order: -3,
+                        clear_color: ClearColorConfig::Custom(Color::rgba_from_array([0.0; 4])),
+                        target: RenderTarget::Image(heat_map.texture.clone()),
+                        ..Default::default()
+                    },
+                    projection: OrthographicProjection {
+                        scale: DEFAULT_SCALE / (world_target.scale * HEAT_MAP_SCALE),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                BaseScale(DEFAULT_SCALE / (world_target.scale * HEAT_MAP_SCALE)),
+                RenderScaled,
+                RenderLayers::layer(HEAT_RENDER_LAYER),
+            ));
+
             parent.spawn((
                 Name::new("Other"),
                 Camera2dBundle {
@@ -236,11 +568,12 @@ fn setup_camera(
                         ..Default::default()
                     },
                     projection: OrthographicProjection {
-                        scale: 0.375 / (CHUNK_SIZE as f32),
+                        scale: DEFAULT_SCALE,
                         ..Default::default()
                     },
                     ..Default::default()
                 },
+                BaseScale(DEFAULT_SCALE),
                 RenderLayers::layer(0),
             ));
         });
@@ -249,49 +582,68 @@ fn setup_camera(
 pub fn setup_lighting(
     mut commands: Commands,
     window_q: Query<&Window, With<PrimaryWindow>>,
-    mut images: ResMut<Assets<Image>>
+    mut images: ResMut<Assets<Image>>,
+    config: Res<Persistent<Config>>
 ) {
     let window = window_q.single();
 
-    let scale = 1.25;
+    let scale = LIGHTING_SCALE * config.render_scale;
     let size = Extent3d {
         width: (window.width() * scale).round() as u32,
         height: (window.height() * scale).round() as u32,
         ..Default::default()
     };
 
-    let mut texture = Image {
-        texture_descriptor: TextureDescriptor {
-            label: None,
-            size,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::bevy_default(),
-            mip_level_count: 1,
-            sample_count: 1,
-            usage: TextureUsages::TEXTURE_BINDING |
-            TextureUsages::COPY_DST |
-            TextureUsages::COPY_SRC |
-            TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        },
-        ..default()
+    commands.insert_resource(LightingTexture {
+        scale,
+        texture: images.add(render_texture(size)),
+    })
+}
+
+pub fn setup_world_target(
+    mut commands: Commands,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut images: ResMut<Assets<Image>>,
+    config: Res<Persistent<Config>>
+) {
+    let window = window_q.single();
+
+    let size = world_target_size(window, config.render_scale);
+    let extent = Extent3d {
+        width: size.x,
+        height: size.y,
+        ..Default::default()
     };
 
-    texture.resize(size);
+    let mut texture = render_texture(extent);
+    texture.sampler = ImageSampler::nearest();
 
-    commands.insert_resource(LightingTexture {
-        scale,
-        texture: images.add(texture.clone()),
+    commands.insert_resource(LiquidMaskTexture {
+        texture: images.add(render_texture(extent)),
+    });
+
+    commands.insert_resource(HeatMapTexture {
+        texture: images.add(render_texture(heat_map_size(size))),
+    });
+
+    commands.insert_resource(WorldRenderTarget {
+        scale: config.render_scale,
+        size,
+        texture: images.add(texture),
     })
 }
 
 pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractResourcePlugin::<LightingTexture>::default())
-
-            .add_systems(Startup, (setup_lighting, setup_camera).chain())
-            .add_systems(Update, update_camera.run_if(in_state(GameState::Game)))
-            .add_systems(Update, on_resize_system);
+        app.add_plugins((
+            ExtractResourcePlugin::<LightingTexture>::default(),
+            ExtractResourcePlugin::<LiquidMaskTexture>::default(),
+            ExtractResourcePlugin::<HeatMapTexture>::default(),
+        ))
+            .init_resource::<CameraShake>()
+            .add_systems(Startup, (setup_lighting, setup_world_target, setup_camera).chain())
+            .add_systems(Update, (zoom_camera, update_camera).chain().run_if(in_state(GameState::Game)))
+            .add_systems(Update, resize_render_targets.before(update_camera));
     }
 }
@@ -0,0 +1,594 @@
+use std::{ fs::{ self, File }, io::BufWriter, path::PathBuf, time::{ SystemTime, UNIX_EPOCH } };
+
+use bevy::{
+    prelude::*,
+    tasks::{ block_on, futures_lite::future, AsyncComputeTaskPool, Task },
+    time::common_conditions::on_timer,
+    window::PrimaryWindow,
+};
+use bevy_egui::{ egui, EguiContext };
+
+use crate::{
+    camera::{ TrackingCamera, WorldRenderTarget },
+    constants::CHUNK_SIZE,
+    generation::ShadowColor,
+    gui::egui_has_primary_context,
+    has_window,
+    notifications::{ Notification, NotificationKind },
+    settings::config_dir,
+    simulation::{ chunk_manager::ChunkManager, render_dirty_rect_updates, SIMULATION_STEP },
+    state::GameState,
+};
+
+// simulation ticks between frames, a tick is 10ms
+const DEFAULT_INTERVAL: u32 = 4;
+const MAX_INTERVAL: u32 = 100;
+// limits memory used by frames that are waiting to be encoded, large regions get fewer frames
+const MAX_CAPTURE_BYTES: usize = 256 * 1024 * 1024;
+const MAX_FRAMES: usize = 600;
+const MAX_REGION_SIZE: i32 = 384;
+// baked lighting spreads the same way as the propagation shader, offsets are in pixels
+const LIGHT_PASSES: i32 = 8;
+const LIGHT_OFFSET: i32 = 1;
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Capture>()
+            .init_resource::<CaptureTasks>()
+            .add_systems(
+                Update,
+                (
+                    toggle_capture,
+                    select_capture_region.run_if(has_window).run_if(capture_selecting),
+                    draw_capture_region,
+                    ui_capture_system.run_if(has_window).run_if(egui_has_primary_context),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                PostUpdate,
+                record_capture_frame
+                    .after(render_dirty_rect_updates)
+                    .run_if(on_timer(SIMULATION_STEP))
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(Update, poll_capture_tasks)
+            .add_systems(OnExit(GameState::Game), finish_capture);
+    }
+}
+
+#[derive(Default)]
+pub enum CaptureState {
+    #[default]
+    Idle,
+    /// Waiting for the region to be dragged out with the mouse
+    Selecting {
+        start: Option<IVec2>,
+    },
+    Recording {
+        region: IRect,
+        ticks: u32,
+        frames: Vec<CaptureFrame>,
+    },
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    #[default]
+    TimeLapse,
+    /// Single frame exported as a still png right after the region is selected
+    Image,
+}
+
+/// Time-lapse of a world region saved as an animated png or a still export of it,
+/// toggled with F4 and Shift+F4
+#[derive(Resource)]
+pub struct Capture {
+    pub state: CaptureState,
+    pub mode: CaptureMode,
+    pub interval: u32,
+    /// Draw the background behind the terrain, otherwise empty pixels stay transparent
+    pub background: bool,
+    /// Bake an approximation of the lighting into the colors
+    pub lighting: bool,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            state: CaptureState::Idle,
+            mode: CaptureMode::TimeLapse,
+            interval: DEFAULT_INTERVAL,
+            background: true,
+            lighting: false,
+        }
+    }
+}
+
+/// Raw rows of the chunk textures, composited only when the capture is encoded
+pub struct CaptureFrame {
+    terrain: Vec<u8>,
+    background: Vec<u8>,
+    /// Left empty unless lighting is baked in
+    lighting: Vec<u8>,
+}
+
+/// Options that are fixed once the region is selected
+#[derive(Clone, Copy)]
+struct CaptureOptions {
+    background: bool,
+    /// Darkest color of the level, lit pixels are never darker than it
+    shadow: Option<[f32; 3]>,
+}
+
+#[derive(Resource, Default)]
+pub struct CaptureTasks(Vec<Task<Result<PathBuf, String>>>);
+
+pub fn capture_selecting(capture: Res<Capture>) -> bool {
+    matches!(capture.state, CaptureState::Selecting { .. })
+}
+
+fn toggle_capture(
+    mut commands: Commands,
+    mut capture: ResMut<Capture>,
+    mut tasks: ResMut<CaptureTasks>,
+    shadow: Option<Res<ShadowColor>>,
+    keys: Res<ButtonInput<KeyCode>>
+) {
+    if !keys.just_pressed(KeyCode::F4) {
+        return;
+    }
+
+    match capture.state {
+        CaptureState::Idle => {
+            capture.mode = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+                CaptureMode::Image
+            } else {
+                CaptureMode::TimeLapse
+            };
+            capture.state = CaptureState::Selecting { start: None };
+        }
+        CaptureState::Selecting { .. } => {
+            capture.state = CaptureState::Idle;
+        }
+        CaptureState::Recording { .. } => {
+            encode_capture(&mut commands, &mut capture, &mut tasks, shadow.as_deref());
+        }
+    }
+}
+
+fn select_capture_region(
+    mut capture: ResMut<Capture>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
+    buttons: Res<ButtonInput<MouseButton>>
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (window_q.get_single(), camera_q.get_single()) else {
+        return;
+    };
+
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|position| {
+            camera.viewport_to_world_2d(camera_transform, world_target.viewport_position(window, position))
+        })
+        .map(|position| (position * (CHUNK_SIZE as f32)).round().as_ivec2()) else {
+        return;
+    };
+
+    let CaptureState::Selecting { start } = &mut capture.state else {
+        return;
+    };
+
+    if buttons.just_pressed(MouseButton::Left) {
+        *start = Some(position);
+    }
+
+    let Some(start) = *start else {
+        return;
+    };
+
+    if buttons.just_released(MouseButton::Left) {
+        let region = IRect::from_corners(start, position);
+        let size = region.size().min(IVec2::splat(MAX_REGION_SIZE));
+
+        if size.min_element() == 0 {
+            capture.state = CaptureState::Selecting { start: None };
+            return;
+        }
+
+        capture.state = CaptureState::Recording {
+            region: IRect::from_corners(region.min, region.min + size),
+            ticks: 0,
+            frames: vec![],
+        };
+    }
+}
+
+fn draw_capture_region(
+    capture: Res<Capture>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
+    mut gizmos: Gizmos
+) {
+    let (region, color) = match &capture.state {
+        CaptureState::Recording { region, .. } => (*region, Color::RED),
+        CaptureState::Selecting { start: Some(start) } => {
+            let (Ok(window), Ok((camera, camera_transform))) = (
+                window_q.get_single(),
+                camera_q.get_single(),
+            ) else {
+                return;
+            };
+
+            let Some(position) = window
+                .cursor_position()
+                .and_then(|position| {
+                    camera.viewport_to_world_2d(
+                        camera_transform,
+                        world_target.viewport_position(window, position)
+                    )
+                }) else {
+                return;
+            };
+
+            (
+                IRect::from_corners(*start, (position * (CHUNK_SIZE as f32)).round().as_ivec2()),
+                Color::WHITE,
+            )
+        }
+        _ => {
+            return;
+        }
+    };
+
+    gizmos.rect_2d(
+        region.center().as_vec2() / (CHUNK_SIZE as f32),
+        0.0,
+        region.size().as_vec2() / (CHUNK_SIZE as f32),
+        color
+    );
+}
+
+/// Only copies the rows out of the textures, everything else is left for the encoding task
+fn record_capture_frame(
+    mut commands: Commands,
+    mut capture: ResMut<Capture>,
+    mut tasks: ResMut<CaptureTasks>,
+    chunk_manager: Res<ChunkManager>,
+    shadow: Option<Res<ShadowColor>>,
+    images: Res<Assets<Image>>
+) {
+    let (interval, mode, lighting) = (capture.interval, capture.mode, capture.lighting);
+    let CaptureState::Recording { region, ticks, frames } = &mut capture.state else {
+        return;
+    };
+
+    *ticks += 1;
+    if (*ticks - 1) % interval != 0 {
+        return;
+    }
+
+    frames.push(copy_frame(*region, lighting, &chunk_manager, &images));
+
+    if mode == CaptureMode::Image || frames.len() >= max_frames(*region, lighting) {
+        encode_capture(&mut commands, &mut capture, &mut tasks, shadow.as_deref());
+    }
+}
+
+/// Frames recorded before the capture is saved, so the copied rows stay within the memory budget
+fn max_frames(region: IRect, lighting: bool) -> usize {
+    let layers = if lighting { 3 } else { 2 };
+    let frame_bytes = ((region.size().x * region.size().y * 4) as usize) * layers;
+
+    (MAX_CAPTURE_BYTES / frame_bytes.max(1)).clamp(1, MAX_FRAMES)
+}
+
+fn copy_frame(
+    region: IRect,
+    lighting: bool,
+    chunk_manager: &ChunkManager,
+    images: &Assets<Image>
+) -> CaptureFrame {
+    let size = region.size();
+    let mut frame = CaptureFrame {
+        terrain: vec![0; (size.x * size.y * 4) as usize],
+        background: vec![0; (size.x * size.y * 4) as usize],
+        lighting: if lighting { vec![0; (size.x * size.y * 4) as usize] } else { vec![] },
+    };
+
+    // image rows go from top to bottom while world goes up
+    for row in 0..size.y {
+        let y = region.max.y - 1 - row;
+        let mut x = region.min.x;
+
+        while x < region.max.x {
+            let chunk_position = IVec2::new(x, y).div_euclid(IVec2::splat(CHUNK_SIZE));
+            let local = IVec2::new(x, y).rem_euclid(IVec2::splat(CHUNK_SIZE));
+            let length = (CHUNK_SIZE - local.x).min(region.max.x - x);
+
+            if let Some(chunk) = chunk_manager.get_chunk_data(&chunk_position) {
+                let source = ((local.y * CHUNK_SIZE + local.x) * 4) as usize..(
+                    (local.y * CHUNK_SIZE + local.x + length) * 4
+                ) as usize;
+                let target = ((row * size.x + x - region.min.x) * 4) as usize..(
+                    (row * size.x + x - region.min.x + length) * 4
+                ) as usize;
+
+                frame.terrain[target.clone()].copy_from_slice(&chunk.terrain[source.clone()]);
+
+                if let Some(background) = images.get(&chunk.background) {
+                    frame.background[target.clone()].copy_from_slice(&background.data[source.clone()]);
+                }
+
+                if lighting {
+                    frame.lighting[target].copy_from_slice(&chunk.lighting[source]);
+                }
+            }
+
+            x += length;
+        }
+    }
+
+    frame
+}
+
+fn finish_capture(
+    mut commands: Commands,
+    mut capture: ResMut<Capture>,
+    mut tasks: ResMut<CaptureTasks>,
+    shadow: Option<Res<ShadowColor>>
+) {
+    encode_capture(&mut commands, &mut capture, &mut tasks, shadow.as_deref());
+}
+
+fn encode_capture(
+    commands: &mut Commands,
+    capture: &mut Capture,
+    tasks: &mut CaptureTasks,
+    shadow: Option<&ShadowColor>
+) {
+    let CaptureState::Recording { region, frames, .. } = std::mem::take(&mut capture.state) else {
+        capture.state = CaptureState::Idle;
+        return;
+    };
+
+    if frames.is_empty() {
+        return;
+    }
+
+    let options = CaptureOptions {
+        background: capture.background,
+        shadow: capture.lighting.then(|| {
+            shadow.map_or([0.0; 3], |shadow| [shadow.0.r(), shadow.0.g(), shadow.0.b()])
+        }),
+    };
+    let mode = capture.mode;
+    let delay = (capture.interval as u128) * SIMULATION_STEP.as_millis();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let path = dirs::picture_dir()
+        .map(|directory| directory.join("sandforge"))
+        .unwrap_or_else(|| config_dir().join("captures"))
+        .join(match mode {
+            CaptureMode::TimeLapse => format!("capture-{}.png", timestamp),
+            CaptureMode::Image => format!("export-{}.png", timestamp),
+        });
+
+    if mode == CaptureMode::TimeLapse {
+        let notification = if frames.len() >= max_frames(region, capture.lighting) {
+            Notification::new(
+                format!("Frame limit reached, saving {} frames...", frames.len())
+            ).with_kind(NotificationKind::Warning)
+        } else {
+            Notification::new(format!("Saving {} frames...", frames.len()))
+        };
+
+        commands.add(move |world: &mut World| {
+            world.send_event(notification);
+        });
+    }
+
+    tasks.0.push(
+        AsyncComputeTaskPool::get().spawn(async move {
+            write_apng(&path, region.size().as_uvec2(), frames, delay as u16, mode, options)
+                .map(|_| path.clone())
+                .map_err(|error| format!("{}: {}", path.display(), error))
+        })
+    );
+}
+
+/// Still exports are written as a regular png with the first frame
+fn write_apng(
+    path: &PathBuf,
+    size: UVec2,
+    frames: Vec<CaptureFrame>,
+    delay: u16,
+    mode: CaptureMode,
+    options: CaptureOptions
+) -> Result<(), String> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+    }
+
+    let file = File::create(path).map_err(|error| error.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size.x, size.y);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    if mode == CaptureMode::TimeLapse {
+        encoder.set_animated(frames.len() as u32, 0).map_err(|error| error.to_string())?;
+        encoder.set_frame_delay(delay, 1000).map_err(|error| error.to_string())?;
+    }
+
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+
+    for frame in frames {
+        writer
+            .write_image_data(&compose_frame(&frame, size, options))
+            .map_err(|error| error.to_string())?;
+
+        if mode == CaptureMode::Image {
+            break;
+        }
+    }
+
+    writer.finish().map_err(|error| error.to_string())
+}
+
+/// Terrain is drawn on top of the background, lighting multiplies the result
+fn compose_frame(frame: &CaptureFrame, size: UVec2, options: CaptureOptions) -> Vec<u8> {
+    let light = options.shadow.map(|shadow| propagate_light(&frame.lighting, size, shadow));
+
+    frame.terrain
+        .chunks_exact(4)
+        .zip(frame.background.chunks_exact(4))
+        .enumerate()
+        .flat_map(|(index, (terrain, background))| {
+            let background = if options.background { background } else { &[0; 4] };
+            let alpha = (terrain[3] as f32) / 255.0;
+            let mut color = [0; 4];
+
+            for channel in 0..3 {
+                let value =
+                    (terrain[channel] as f32) * alpha + (background[channel] as f32) * (1.0 - alpha);
+
+                color[channel] = match &light {
+                    Some(light) => (value * light[index][channel]) as u8,
+                    None => value as u8,
+                };
+            }
+            color[3] = terrain[3].max(background[3]);
+
+            color
+        })
+        .collect::<Vec<u8>>()
+}
+
+/// Same averaging as the propagation shader, done on the light sources of the region
+fn propagate_light(lighting: &[u8], size: UVec2, shadow: [f32; 3]) -> Vec<[f32; 3]> {
+    let size = size.as_ivec2();
+    let mut light = lighting
+        .chunks_exact(4)
+        .map(|color| [color[0], color[1], color[2]].map(|value| (value as f32) / 255.0))
+        .collect::<Vec<[f32; 3]>>();
+
+    let sample = |light: &[[f32; 3]], x: i32, y: i32| {
+        light[(y.clamp(0, size.y - 1) * size.x + x.clamp(0, size.x - 1)) as usize]
+    };
+
+    for step in 0..LIGHT_PASSES {
+        let offset = (step + 1) * LIGHT_OFFSET;
+        let mut next = Vec::with_capacity(light.len());
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let mut color = sample(&light, x, y);
+
+                for (dx, dy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                    let neighbour = sample(&light, x + dx * offset, y + dy * offset);
+
+                    for channel in 0..3 {
+                        color[channel] += neighbour[channel];
+                    }
+                }
+
+                next.push([0, 1, 2].map(|channel| (color[channel] / 4.0).max(shadow[channel])));
+            }
+        }
+
+        light = next;
+    }
+
+    light
+}
+
+fn poll_capture_tasks(mut tasks: ResMut<CaptureTasks>, mut notifications: EventWriter<Notification>) {
+    tasks.0.retain_mut(|task| {
+        let Some(result) = block_on(future::poll_once(task)) else {
+            return true;
+        };
+
+        match result {
+            Ok(path) => {
+                info!("capture saved to {}", path.display());
+
+                notifications.send(
+                    Notification::new(format!("Capture saved to {}", path.display())).with_kind(
+                        NotificationKind::Success
+                    )
+                );
+            }
+            Err(error) => {
+                error!("failed to save capture: {}", error);
+
+                notifications.send(
+                    Notification::new(format!("Failed to save capture: {}", error)).with_kind(
+                        NotificationKind::Error
+                    )
+                );
+            }
+        }
+
+        false
+    });
+}
+
+fn ui_capture_system(
+    mut capture: ResMut<Capture>,
+    mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
+) {
+    let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
+        return;
+    };
+
+    let status = match &capture.state {
+        CaptureState::Idle => {
+            return;
+        }
+        CaptureState::Selecting { .. } => "Drag to select the region, F4 to cancel".to_string(),
+        CaptureState::Recording { .. } if capture.mode == CaptureMode::Image => "Exporting...".to_string(),
+        CaptureState::Recording { region, frames, .. } =>
+            format!(
+                "Recording: {}/{} frames, F4 to save",
+                frames.len(),
+                max_frames(*region, capture.lighting)
+            ),
+    };
+
+    let ctx = egui_ctx.get_mut();
+
+    egui::Window
+        ::new("Capture")
+        .auto_sized()
+        .title_bar(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2 {
+            x: 0.0,
+            y: ctx.pixels_per_point() * 8.0,
+        })
+        .show(ctx, |ui| {
+            ui.label(status);
+
+            // options are fixed once the recording has started
+            ui.add_enabled_ui(matches!(capture.state, CaptureState::Selecting { .. }), |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut capture.mode, CaptureMode::TimeLapse, "Time-lapse");
+                    ui.radio_value(&mut capture.mode, CaptureMode::Image, "Image");
+                });
+
+                ui.checkbox(&mut capture.background, "Background");
+                ui.checkbox(&mut capture.lighting, "Lighting");
+
+                if capture.mode == CaptureMode::TimeLapse {
+                    ui.add(
+                        egui::Slider::new(&mut capture.interval, 1..=MAX_INTERVAL).text("ticks per frame")
+                    );
+                }
+            });
+        });
+}
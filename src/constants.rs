@@ -1,9 +1,13 @@
 pub const CHUNK_SIZE: i32 = 64;
 pub const CHUNK_CELLS: i32 = CHUNK_SIZE.pow(2);
 
+pub const PARALLAX_BACKGROUND_Z: f32 = -20.;
 pub const BACKGROUND_Z: f32 = -10.;
 pub const DECORATION_Z: f32 = -1.;
 pub const ENEMY_Z: f32 = 1.;
 pub const PLAYER_Z: f32 = 2.;
 pub const PARTICLE_Z: f32 = 3.;
-pub const TERRAIN_Z: f32 = 4.;
\ No newline at end of file
+pub const TERRAIN_Z: f32 = 4.;
+pub const PARALLAX_FOREGROUND_Z: f32 = 5.;
+// nothing above the camera is drawn
+pub const CAMERA_Z: f32 = 6.;
\ No newline at end of file
@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use bevy_rapier2d::plugin::RapierConfiguration;
+
+use crate::{
+    daily::RunMode,
+    generation::{ next_level, LevelCounter },
+    gui::Score,
+    score_export::RunStats,
+    state::GameState,
+};
+
+// rapier_config gravity is derived from the same scale as in main
+const PIXELS_PER_METER: f32 = 0.1;
+const LOW_GRAVITY_SCALE: f32 = 0.5;
+
+pub struct CursesPlugin;
+
+impl Plugin for CursesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedCurses>()
+            .init_resource::<ActiveCurses>()
+            .add_systems(OnEnter(GameState::Setup), remove_curses)
+            .add_systems(OnEnter(GameState::LevelInitialization), apply_curses.before(next_level))
+            .add_systems(OnEnter(GameState::Game), apply_low_gravity)
+            .add_systems(OnEnter(GameState::GameOver), apply_curse_bonus);
+    }
+}
+
+/// Optional handicap of a run, every curse is a resource of its own while it is active
+/// so the plugins it affects only have to check whether the resource exists
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Curse {
+    LowGravity,
+    FragileTerrain,
+    DoubleEnemies,
+    NoHook,
+    Darkness,
+}
+
+impl Curse {
+    pub const ALL: [Curse; 5] = [
+        Curse::LowGravity,
+        Curse::FragileTerrain,
+        Curse::DoubleEnemies,
+        Curse::NoHook,
+        Curse::Darkness,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Curse::LowGravity => "Low gravity",
+            Curse::FragileTerrain => "Fragile terrain",
+            Curse::DoubleEnemies => "Double enemies",
+            Curse::NoHook => "No hook",
+            Curse::Darkness => "Darkness",
+        }
+    }
+
+    /// Name recorded in the run history
+    pub fn id(self) -> &'static str {
+        match self {
+            Curse::LowGravity => "low_gravity",
+            Curse::FragileTerrain => "fragile_terrain",
+            Curse::DoubleEnemies => "double_enemies",
+            Curse::NoHook => "no_hook",
+            Curse::Darkness => "darkness",
+        }
+    }
+
+    /// Share of the final score added while the curse is active
+    pub fn score_bonus(self) -> f32 {
+        match self {
+            Curse::LowGravity => 0.1,
+            Curse::FragileTerrain => 0.15,
+            Curse::DoubleEnemies => 0.5,
+            Curse::NoHook => 0.3,
+            Curse::Darkness => 0.25,
+        }
+    }
+
+    /// Level from which the curse is active even if it was not chosen
+    pub fn forced_from(self) -> Option<u32> {
+        match self {
+            Curse::Darkness => Some(6),
+            Curse::FragileTerrain => Some(9),
+            _ => None,
+        }
+    }
+
+    fn insert(self, commands: &mut Commands) {
+        match self {
+            Curse::LowGravity => commands.insert_resource(LowGravity { scale: LOW_GRAVITY_SCALE }),
+            Curse::FragileTerrain => commands.insert_resource(FragileTerrain { damage_scale: 2.0 }),
+            Curse::DoubleEnemies => commands.insert_resource(DoubleEnemies { density: 2.0 }),
+            Curse::NoHook => commands.insert_resource(NoHook),
+            Curse::Darkness => commands.insert_resource(Darkness { light: 0.35 }),
+        }
+    }
+}
+
+/// Curses toggled on the main menu, daily runs ignore them
+#[derive(Resource, Default)]
+pub struct SelectedCurses(pub Vec<Curse>);
+
+impl SelectedCurses {
+    pub fn toggle(&mut self, curse: Curse) {
+        if let Some(index) = self.0.iter().position(|selected| *selected == curse) {
+            self.0.remove(index);
+        } else {
+            self.0.push(curse);
+        }
+    }
+
+    pub fn score_multiplier(&self) -> f32 {
+        score_multiplier(&self.0)
+    }
+}
+
+pub fn score_multiplier(curses: &[Curse]) -> f32 {
+    1.0 + curses.iter().map(|curse| curse.score_bonus()).sum::<f32>()
+}
+
+/// Curses of the current run, both chosen and forced
+#[derive(Resource, Default)]
+pub struct ActiveCurses(pub Vec<Curse>);
+
+/// Scale of the gravity of physics bodies and actors
+#[derive(Resource)]
+pub struct LowGravity {
+    pub scale: f32,
+}
+
+/// Scale of the damage explosions deal to terrain pixels
+#[derive(Resource)]
+pub struct FragileTerrain {
+    pub damage_scale: f32,
+}
+
+/// Multiplier of the number of enemies placed on a level, on top of the difficulty
+#[derive(Resource)]
+pub struct DoubleEnemies {
+    pub density: f32,
+}
+
+/// Players can not use the hook
+#[derive(Resource)]
+pub struct NoHook;
+
+/// Scale of the lighting and shadow colors of levels
+#[derive(Resource)]
+pub struct Darkness {
+    pub light: f32,
+}
+
+fn remove_curses(mut commands: Commands, mut active: ResMut<ActiveCurses>) {
+    active.0.clear();
+
+    commands.remove_resource::<LowGravity>();
+    commands.remove_resource::<FragileTerrain>();
+    commands.remove_resource::<DoubleEnemies>();
+    commands.remove_resource::<NoHook>();
+    commands.remove_resource::<Darkness>();
+}
+
+/// Activates the chosen curses on the first level and the forced ones once the level they
+/// start from is reached, the level counter is only advanced after this
+pub fn apply_curses(
+    mut commands: Commands,
+    mut active: ResMut<ActiveCurses>,
+    mut stats: ResMut<RunStats>,
+    selected: Res<SelectedCurses>,
+    counter: Res<LevelCounter>,
+    run_mode: Res<RunMode>
+) {
+    let level = counter.0 + 1;
+
+    for curse in Curse::ALL {
+        let chosen = level == 1 && *run_mode == RunMode::Normal && selected.0.contains(&curse);
+        let forced = curse.forced_from().is_some_and(|from| level >= from);
+
+        if active.0.contains(&curse) || !(chosen || forced) {
+            continue;
+        }
+
+        active.0.push(curse);
+        stats.mutators.push(curse.id().to_string());
+        curse.insert(&mut commands);
+    }
+}
+
+fn apply_low_gravity(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    low_gravity: Option<Res<LowGravity>>
+) {
+    let scale = low_gravity.map_or(1.0, |low_gravity| low_gravity.scale);
+    rapier_config.gravity = RapierConfiguration::new(PIXELS_PER_METER).gravity * scale;
+}
+
+pub fn apply_curse_bonus(mut score: ResMut<Score>, active: Res<ActiveCurses>) {
+    let multiplier = score_multiplier(&active.0);
+    let bonus = ((score.value.max(0) as f32) * (multiplier - 1.0)).round() as i32;
+
+    score.value += bonus;
+    score.breakdown.curses = bonus;
+}
@@ -1,7 +1,7 @@
 use bevy::{ prelude::*, window::PrimaryWindow };
 
 use crate::{
-    assets::SpriteAssetCollection, camera::TrackingCamera, constants::CHUNK_SIZE, simulation::{ chunk_manager::ChunkManager, materials::PhysicsType }, state::GameState
+    assets::SpriteAssetCollection, camera::{ TrackingCamera, WorldRenderTarget }, constants::CHUNK_SIZE, simulation::{ chunk_manager::ChunkManager, materials::PhysicsType }, state::GameState
 };
 
 #[derive(Component)]
@@ -44,11 +44,13 @@ pub fn setup_cursor(mut commands: Commands, sprites: Res<SpriteAssetCollection>)
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn move_cursor(
     mut cursor_q: Query<&mut Style, With<GameCursor>>,
     mut material_q: Query<(&mut Style, &mut Text), (With<MaterialName>, Without<GameCursor>)>,
     window_q: Query<&Window, With<PrimaryWindow>>,
     camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
     chunk_manager: Res<ChunkManager>,
     ui_scale: Res<UiScale>,
     game_state: Res<State<GameState>>
@@ -86,7 +88,10 @@ pub fn move_cursor(
             .filter(|position| position.is_some())
             .map(|cursor_position| {
                 camera
-                    .viewport_to_world(camera_transform, cursor_position.unwrap())
+                    .viewport_to_world(
+                        camera_transform,
+                        world_target.viewport_position(window, cursor_position.unwrap())
+                    )
                     .map(|ray| ray.origin.truncate() * CHUNK_SIZE as f32)
                     .unwrap()
                     .as_ivec2()
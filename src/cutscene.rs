@@ -0,0 +1,428 @@
+use std::collections::VecDeque;
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_math::ivec2;
+use itertools::Itertools;
+use leafwing_input_manager::{ action_state::ActionState, plugin::InputManagerSystem };
+use serde::Deserialize;
+
+use crate::{
+    actors::{
+        actor::Actor,
+        effects::Death,
+        elite::EliteModifier,
+        enemy::Enemy,
+        player::{ Player, PlayerActions },
+    },
+    camera::CameraFocus,
+    constants::CHUNK_SIZE,
+    despawn_component,
+    generation::{ LevelCounter, LevelData },
+    importer::Stamps,
+    localization::Locale,
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        materials::PhysicsType,
+        pixel::Pixel,
+    },
+    state::GameState,
+    tutorial::is_tutorial_run,
+};
+
+// played on the first level of every run
+const RUN_INTRO: &str = "intro";
+// elites closer than this to a player in pixels are introduced
+const ELITE_INTRO_DISTANCE: f32 = 96.0;
+
+pub struct CutscenePlugin;
+
+impl Plugin for CutscenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayCutscene>()
+            .init_resource::<CutsceneQueue>()
+            .init_resource::<IntroducedElites>()
+            .add_systems(OnEnter(GameState::Setup), |mut introduced: ResMut<IntroducedElites>| {
+                introduced.clear();
+            })
+            .add_systems(
+                OnTransition { from: GameState::Splash, to: GameState::Game },
+                // the run intro waits for the first real level once the tutorial is left
+                play_level_intros.run_if(not(is_tutorial_run))
+            )
+            .add_systems(OnExit(GameState::Game), (
+                end_cutscene,
+                despawn_component::<UiCutscene>,
+                despawn_component::<UiCutsceneText>,
+                |mut queue: ResMut<CutsceneQueue>| queue.clear(),
+            ))
+            .add_systems(
+                Update,
+                (introduce_elites, queue_cutscenes, start_cutscene, skip_cutscene, run_cutscene)
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                PreUpdate,
+                block_player_input
+                    .after(InputManagerSystem::Update)
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<ActiveCutscene>))
+            );
+    }
+}
+
+/// Step of a cutscene, positions are in pixels relative to the origin the cutscene is played at
+#[derive(Deserialize, Clone, Debug)]
+pub enum CutsceneStep {
+    /// Holds the next step back for the time in seconds
+    Wait(f32),
+    /// Camera glides to the position, or back to the players without one
+    Camera(Option<[i32; 2]>),
+    /// Text box shown for the time in seconds, the text is a key of the language files
+    Text {
+        text: String,
+        duration: f32,
+    },
+    Spawn {
+        enemy_id: String,
+        position: [i32; 2],
+    },
+    Stamp {
+        stamp_id: String,
+        position: [i32; 2],
+    },
+    /// Static pixels of the area turn into the material, or are removed without one
+    Collapse {
+        min: [i32; 2],
+        max: [i32; 2],
+        #[serde(default)]
+        material_id: Option<String>,
+    },
+}
+
+impl CutsceneStep {
+    /// Steps that only hold the cutscene and leave nothing behind, passed over when it is skipped
+    fn is_presentation(&self) -> bool {
+        matches!(self, CutsceneStep::Wait(..) | CutsceneStep::Camera(..) | CutsceneStep::Text { .. })
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Cutscene {
+    pub steps: Vec<CutsceneStep>,
+}
+
+pub fn load_cutscenes() -> HashMap<String, Cutscene> {
+    ron::de
+        ::from_str::<HashMap<String, Cutscene>>(&std::fs::read_to_string("cutscenes.ron").unwrap())
+        .unwrap()
+}
+
+/// Queues the cutscene, origin is in pixels
+#[derive(Event, Clone)]
+pub struct PlayCutscene {
+    pub id: String,
+    pub origin: IVec2,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct CutsceneQueue(VecDeque<PlayCutscene>);
+
+/// Present while a cutscene plays, players can not act until it ends
+#[derive(Resource)]
+pub struct ActiveCutscene {
+    pub steps: Vec<CutsceneStep>,
+    pub origin: IVec2,
+    /// Index of the step that is run once the timer finishes
+    pub next: usize,
+    pub timer: Timer,
+}
+
+/// Names of elite modifiers whose intro was played during the run
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct IntroducedElites(Vec<String>);
+
+#[derive(Component)]
+struct UiCutscene;
+
+#[derive(Component)]
+struct UiCutsceneText;
+
+fn play_level_intros(
+    mut cutscene_ev: EventWriter<PlayCutscene>,
+    counter: Res<LevelCounter>,
+    level_data: Res<LevelData>,
+    registries: Res<Registries>
+) {
+    if counter.0 == 1 && registries.cutscenes.contains_key(RUN_INTRO) {
+        cutscene_ev.send(PlayCutscene { id: RUN_INTRO.to_string(), origin: IVec2::ZERO });
+    }
+
+    if let Some(intro) = &level_data.0.intro {
+        cutscene_ev.send(PlayCutscene { id: intro.clone(), origin: IVec2::ZERO });
+    }
+}
+
+/// Plays the intro of an elite modifier the first time an enemy with it gets close to a player
+#[allow(clippy::type_complexity)]
+fn introduce_elites(
+    mut cutscene_ev: EventWriter<PlayCutscene>,
+    elite_q: Query<(&Actor, &EliteModifier), (With<Enemy>, Without<Death>)>,
+    player_q: Query<&Actor, With<Player>>,
+    mut introduced: ResMut<IntroducedElites>
+) {
+    for (actor, elite) in elite_q.iter() {
+        let Some(intro) = &elite.intro else {
+            continue;
+        };
+
+        if introduced.contains(&elite.name) {
+            continue;
+        }
+
+        let center = actor.position + actor.size / 2.0;
+        let close = player_q
+            .iter()
+            .any(|player| (player.position + player.size / 2.0).distance(center) < ELITE_INTRO_DISTANCE);
+
+        if close {
+            introduced.push(elite.name.clone());
+            cutscene_ev.send(PlayCutscene { id: intro.clone(), origin: center.as_ivec2() });
+        }
+    }
+}
+
+fn queue_cutscenes(mut events: EventReader<PlayCutscene>, mut queue: ResMut<CutsceneQueue>) {
+    queue.extend(events.read().cloned());
+}
+
+fn start_cutscene(
+    mut commands: Commands,
+    mut queue: ResMut<CutsceneQueue>,
+    active: Option<Res<ActiveCutscene>>,
+    registries: Res<Registries>,
+    locale: Res<Locale>
+) {
+    if active.is_some() {
+        return;
+    }
+
+    let Some(play) = queue.pop_front() else {
+        return;
+    };
+
+    let Some(cutscene) = registries.cutscenes.get(&play.id) else {
+        warn!("unknown cutscene: {}", play.id);
+        return;
+    };
+
+    commands.insert_resource(ActiveCutscene {
+        steps: cutscene.steps.clone(),
+        origin: play.origin,
+        next: 0,
+        timer: Timer::default(),
+    });
+
+    commands.spawn((
+        Name::new("Cutscene"),
+        UiCutscene,
+        TextBundle::from_section(locale.get("cutscene.skip"), TextStyle {
+            font_size: 16.0,
+            color: Color::GRAY,
+            ..Default::default()
+        }).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(16.0),
+            right: Val::Px(16.0),
+            ..default()
+        }),
+    ));
+}
+
+/// Leaves out the steps that only present the cutscene, the rest still runs so the level ends up the same
+fn skip_cutscene(
+    cutscene: Option<ResMut<ActiveCutscene>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>
+) {
+    let Some(mut cutscene) = cutscene else {
+        return;
+    };
+
+    let skipped =
+        keys.just_pressed(KeyCode::Enter) ||
+        gamepads
+            .iter()
+            .any(|gamepad| {
+                gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Select))
+            });
+
+    if skipped {
+        let next = cutscene.next;
+        cutscene.steps.drain(..next);
+        cutscene.steps.retain(|step| !step.is_presentation());
+        cutscene.next = 0;
+
+        let duration = cutscene.timer.duration();
+        cutscene.timer.set_elapsed(duration);
+    }
+}
+
+/// Runs the steps one after another, waiting for the timer of the last one before the next
+#[allow(clippy::too_many_arguments)]
+fn run_cutscene(
+    mut commands: Commands,
+    cutscene: Option<ResMut<ActiveCutscene>>,
+    ui_q: Query<Entity, With<UiCutscene>>,
+    text_q: Query<Entity, With<UiCutsceneText>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    stamps: Res<Stamps>,
+    locale: Res<Locale>,
+    time: Res<Time>
+) {
+    let Some(mut cutscene) = cutscene else {
+        return;
+    };
+
+    if !cutscene.timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    // text boxes are shown until the timer of their step runs out
+    for entity in text_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let origin = cutscene.origin;
+    let at = |position: [i32; 2]| origin + ivec2(position[0], position[1]);
+
+    while cutscene.timer.finished() {
+        let Some(step) = cutscene.steps.get(cutscene.next).cloned() else {
+            commands.remove_resource::<ActiveCutscene>();
+            commands.remove_resource::<CameraFocus>();
+
+            for entity in ui_q.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+
+            return;
+        };
+
+        cutscene.next += 1;
+
+        match step {
+            CutsceneStep::Wait(duration) => {
+                cutscene.timer = Timer::from_seconds(duration, TimerMode::Once);
+            }
+            CutsceneStep::Camera(Some(target)) => {
+                commands.insert_resource(
+                    CameraFocus(at(target).as_vec2() / (CHUNK_SIZE as f32))
+                );
+            }
+            CutsceneStep::Camera(None) => {
+                commands.remove_resource::<CameraFocus>();
+            }
+            CutsceneStep::Text { text, duration } => {
+                cutscene.timer = Timer::from_seconds(duration, TimerMode::Once);
+
+                commands
+                    .spawn((
+                        UiCutsceneText,
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                bottom: Val::Px(64.0),
+                                width: Val::Percent(100.0),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(locale.get(&text), TextStyle {
+                                font_size: 28.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            })
+                                .with_text_justify(JustifyText::Center)
+                                .with_style(Style {
+                                    padding: UiRect::all(Val::Px(12.0)),
+                                    max_width: Val::Percent(60.0),
+                                    ..default()
+                                })
+                                .with_background_color(Color::rgba(0.1, 0.1, 0.1, 0.85))
+                        );
+                    });
+            }
+            CutsceneStep::Spawn { enemy_id, position } => {
+                let Some(enemy_fn) = registries.enemies.get(&enemy_id) else {
+                    warn!("unknown cutscene enemy: {}", enemy_id);
+                    continue;
+                };
+
+                let (enemy, enemy_hitbox) = enemy_fn(at(position).as_vec2() / (CHUNK_SIZE as f32));
+
+                commands.spawn(enemy).with_children(|parent| {
+                    parent.spawn(enemy_hitbox);
+                });
+            }
+            CutsceneStep::Stamp { stamp_id, position } => {
+                match stamps.get(&stamp_id) {
+                    Some(stamp) => stamp.place(at(position), &mut chunk_manager, &mut dirty_rects),
+                    None => warn!("unknown cutscene stamp: {}", stamp_id),
+                }
+            }
+            CutsceneStep::Collapse { min, max, material_id } => {
+                let material = match &material_id {
+                    Some(material_id) => {
+                        let Some(material) = registries.materials.get(material_id) else {
+                            warn!("unknown cutscene material: {}", material_id);
+                            continue;
+                        };
+
+                        Some(material)
+                    }
+                    None => None,
+                };
+
+                let (min, max) = (at(min), at(max));
+
+                for (x, y) in (min.x..max.x).cartesian_product(min.y..max.y) {
+                    let position = ivec2(x, y);
+
+                    let Ok(pixel) = chunk_manager.get_mut(position) else {
+                        continue;
+                    };
+
+                    if pixel.physics_type != PhysicsType::Static {
+                        continue;
+                    }
+
+                    *pixel = material.map_or_else(Pixel::default, Pixel::from);
+
+                    dirty_rects.request_update(position);
+                    dirty_rects.request_render(position);
+                    dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+                }
+            }
+        }
+    }
+}
+
+/// Keeps every held action consumed, so nothing carries over once the cutscene ends
+fn block_player_input(mut player_q: Query<&mut ActionState<PlayerActions>, With<Player>>) {
+    for mut action_state in player_q.iter_mut() {
+        action_state.consume_all();
+    }
+}
+
+fn end_cutscene(mut commands: Commands) {
+    commands.remove_resource::<ActiveCutscene>();
+    commands.remove_resource::<CameraFocus>();
+}
@@ -0,0 +1,120 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::{ generation::{ noise::Seed, reset_generation }, state::GameState };
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunMode>()
+            .init_resource::<RetrySeed>()
+            .add_systems(
+                OnEnter(GameState::Setup),
+                (
+                    apply_daily_seed.run_if(is_daily_run),
+                    apply_retry_seed,
+                ).after(reset_generation)
+            );
+    }
+}
+
+/// Chosen on the main menu, daily runs share the level layouts between every player on the same day
+#[derive(Resource, Default, Clone, PartialEq, Eq, Debug)]
+pub enum RunMode {
+    #[default]
+    Normal,
+    Daily(DailyDate),
+    /// Starts on the tutorial level and turns into a normal run once it is left
+    Tutorial,
+}
+
+/// Seed of the run restarted from the game over screen, used instead of a new one on the next setup
+#[derive(Resource, Default)]
+pub struct RetrySeed(pub Option<u32>);
+
+/// Day in UTC, so players in every timezone get the same seed
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct DailyDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DailyRecord {
+    pub date: DailyDate,
+    pub seed: u32,
+    pub level: u32,
+    pub score: i32,
+}
+
+impl DailyDate {
+    pub fn today() -> Self {
+        let days = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() / SECONDS_PER_DAY;
+
+        Self::from_days(days as i64)
+    }
+
+    /// Day of a time in seconds since unix epoch
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        Self::from_days((timestamp / SECONDS_PER_DAY) as i64)
+    }
+
+    /// Converts days since unix epoch into a calendar date
+    fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// Same for every player on the same day, levels add their index on top so the value is kept small
+    pub fn seed(&self) -> u32 {
+        let mut hash: u32 = 0x811c9dc5;
+
+        for byte in self.to_string().bytes() {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+
+        hash >> 8
+    }
+}
+
+impl std::fmt::Display for DailyDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+pub fn is_daily_run(run_mode: Res<RunMode>) -> bool {
+    matches!(*run_mode, RunMode::Daily(..))
+}
+
+fn apply_daily_seed(mut commands: Commands, run_mode: Res<RunMode>) {
+    if let RunMode::Daily(date) = *run_mode {
+        commands.insert_resource(Seed(date.seed()));
+    }
+}
+
+fn apply_retry_seed(mut commands: Commands, mut retry_seed: ResMut<RetrySeed>) {
+    if let Some(seed) = retry_seed.0.take() {
+        commands.insert_resource(Seed(seed));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_persistent::Persistent;
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    generation::{ level::AdaptiveBounds, LevelData },
+    score_export::RunStats,
+    settings::Config,
+    state::GameState,
+    tutorial::is_tutorial_run,
+};
+
+// levels the adaptive difficulty judges the players by
+const ADAPTIVE_HISTORY: usize = 5;
+// damage taken on a level that counts as an average performance, half of the starting health
+const PAR_DAMAGE_TAKEN: f32 = 8.0;
+// share of the distance to the rating of recent levels the skill moves by after every level
+const ADAPTIVE_RATE: f32 = 0.5;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Difficulty>()
+            .add_systems(
+                Update,
+                toggle_adaptive_difficulty.run_if(resource_changed::<Persistent<Config>>)
+            )
+            .add_systems(
+                OnTransition { from: GameState::Splash, to: GameState::Game },
+                start_adaptive_level.run_if(resource_exists::<AdaptiveDifficulty>)
+            )
+            // the tutorial says nothing about the skill of the players, its run mode
+            // is only reset on entering the next level, after these transitions
+            .add_systems(
+                OnTransition { from: GameState::Game, to: GameState::LevelInitialization },
+                record_cleared_level.run_if(
+                    resource_exists::<AdaptiveDifficulty>.and_then(not(is_tutorial_run))
+                )
+            )
+            .add_systems(
+                OnTransition { from: GameState::Game, to: GameState::GameOver },
+                record_death.run_if(resource_exists::<AdaptiveDifficulty>.and_then(not(is_tutorial_run)))
+            );
+    }
+}
+
+/// Chosen on the main menu before a run starts, daily runs are always played on normal
+#[derive(Resource, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// Multiplier of the number of enemies placed on a level
+    pub fn enemy_density(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    pub fn enemy_health(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    /// Multiplier of the damage players take
+    pub fn enemy_damage(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    pub fn score_multiplier(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 2.0,
+        }
+    }
+}
+
+/// Judges how well the players did on recent levels and scales enemies towards it,
+/// present only while enabled in the settings and kept between runs
+#[derive(Resource)]
+pub struct AdaptiveDifficulty {
+    /// Ratings of recent levels from -1 for a death to 1 for a quick clear without a scratch
+    pub history: VecDeque<f32>,
+    /// Follows the ratings of recent levels, 0 keeps the level as it is configured
+    pub skill: f32,
+    /// Multipliers of the current level, on top of the chosen difficulty
+    pub density: f32,
+    pub damage: f32,
+    /// Damage taken in the run before the current level started
+    pub damage_at_start: f32,
+}
+
+impl Default for AdaptiveDifficulty {
+    fn default() -> Self {
+        Self {
+            history: VecDeque::new(),
+            skill: 0.0,
+            density: 1.0,
+            damage: 1.0,
+            damage_at_start: 0.0,
+        }
+    }
+}
+
+impl AdaptiveDifficulty {
+    /// Multiplier of the number of enemies placed on a level with the bounds
+    pub fn density(&self, bounds: &AdaptiveBounds) -> f32 {
+        scale_within(self.skill, bounds.density)
+    }
+
+    /// Multiplier of the damage players take on a level with the bounds
+    pub fn damage(&self, bounds: &AdaptiveBounds) -> f32 {
+        scale_within(self.skill, bounds.damage)
+    }
+
+    fn record(&mut self, rating: f32) {
+        self.history.push_back(rating.clamp(-1.0, 1.0));
+
+        if self.history.len() > ADAPTIVE_HISTORY {
+            self.history.pop_front();
+        }
+
+        let average = self.history.iter().sum::<f32>() / (self.history.len() as f32);
+        self.skill += (average - self.skill) * ADAPTIVE_RATE;
+    }
+}
+
+/// Goes from the lower bound at the lowest skill through no change to the upper bound at the highest
+fn scale_within(skill: f32, (min, max): (f32, f32)) -> f32 {
+    let scale = if skill < 0.0 { 1.0 + skill * (1.0 - min) } else { 1.0 + skill * (max - 1.0) };
+
+    scale.clamp(min.min(max), max.max(min))
+}
+
+fn toggle_adaptive_difficulty(
+    mut commands: Commands,
+    adaptive: Option<Res<AdaptiveDifficulty>>,
+    config: Res<Persistent<Config>>
+) {
+    if config.adaptive_difficulty && adaptive.is_none() {
+        commands.init_resource::<AdaptiveDifficulty>();
+    } else if !config.adaptive_difficulty && adaptive.is_some() {
+        commands.remove_resource::<AdaptiveDifficulty>();
+    }
+}
+
+fn start_adaptive_level(
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    level_data: Res<LevelData>,
+    stats: Res<RunStats>
+) {
+    adaptive.density = adaptive.density(&level_data.0.adaptive);
+    adaptive.damage = adaptive.damage(&level_data.0.adaptive);
+    adaptive.damage_at_start = stats.damage_taken;
+}
+
+/// Quick clears and little damage taken make the next levels harder
+fn record_cleared_level(
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    level_data: Res<LevelData>,
+    stats: Res<RunStats>
+) {
+    let time = stats.level_times.last().copied().unwrap_or_default();
+    let damage_taken = stats.damage_taken - adaptive.damage_at_start;
+
+    let time_rating = 1.0 - time / level_data.0.adaptive.par_time.max(1.0);
+    let damage_rating = 1.0 - damage_taken / PAR_DAMAGE_TAKEN;
+
+    adaptive.record((time_rating.clamp(-1.0, 1.0) + damage_rating.clamp(-1.0, 1.0)) / 2.0);
+}
+
+fn record_death(mut adaptive: ResMut<AdaptiveDifficulty>) {
+    adaptive.record(-1.0);
+}
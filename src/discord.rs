@@ -0,0 +1,178 @@
+use std::io::{ self, Read, Write };
+#[cfg(unix)]
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde_json::{ json, Value };
+
+use crate::{ localization::Locale, presence::PresenceChanged };
+
+// id of the Discord application the presence is shown for, set when the game is built
+const CLIENT_ID: Option<&str> = option_env!("SANDFORGE_DISCORD_CLIENT_ID");
+// Discord listens on the first free one of these sockets
+const IPC_SOCKETS: u32 = 10;
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+#[cfg(unix)]
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Stream = std::fs::File;
+
+/// Shows the presence on the Discord profile of the player through the IPC socket of the local client
+///
+/// Messages are written on a thread of their own, so a missing or slow client never holds up a frame.
+/// Connection is retried on every change, Discord started after the game picks up the next one.
+pub struct DiscordPlugin;
+
+impl Plugin for DiscordPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(client_id) = CLIENT_ID else {
+            warn!("discord presence is disabled, the game was built without SANDFORGE_DISCORD_CLIENT_ID");
+            return;
+        };
+
+        let (sender, receiver) = async_channel::unbounded::<Value>();
+
+        std::thread::spawn(move || {
+            let mut client = DiscordClient::new(client_id);
+
+            while let Ok(activity) = receiver.recv_blocking() {
+                if let Err(error) = client.set_activity(activity) {
+                    debug!("failed to update discord presence: {}", error);
+                }
+            }
+        });
+
+        app.insert_resource(DiscordSender(sender)).add_systems(Update, send_activity);
+    }
+}
+
+#[derive(Resource)]
+struct DiscordSender(async_channel::Sender<Value>);
+
+fn send_activity(
+    mut presence_ev: EventReader<PresenceChanged>,
+    sender: Res<DiscordSender>,
+    locale: Res<Locale>
+) {
+    // only the latest presence matters when several changed in a frame
+    let Some(event) = presence_ev.read().last() else {
+        return;
+    };
+
+    let mut activity = json!({
+        "details": event.presence.details(&locale),
+        "timestamps": { "start": event.since },
+    });
+
+    if let Some(state) = event.presence.state(&locale) {
+        activity["state"] = json!(state);
+    }
+
+    sender.0.try_send(activity).ok();
+}
+
+struct DiscordClient {
+    client_id: &'static str,
+    stream: Option<Stream>,
+    nonce: u64,
+}
+
+impl DiscordClient {
+    fn new(client_id: &'static str) -> Self {
+        Self {
+            client_id,
+            stream: None,
+            nonce: 0,
+        }
+    }
+
+    fn set_activity(&mut self, activity: Value) -> io::Result<()> {
+        self.nonce += 1;
+
+        let command = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": std::process::id(), "activity": activity },
+            "nonce": self.nonce.to_string(),
+        });
+
+        // the client may have been restarted since the last message, so a failed write connects again once
+        if self.send(OP_FRAME, &command).is_err() {
+            self.stream = None;
+            self.send(OP_FRAME, &command)?;
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, op: u32, payload: &Value) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+        }
+
+        let stream = self.stream.as_mut().unwrap();
+
+        write_frame(stream, op, payload)?;
+        // every message is answered, reading the answer keeps the socket from filling up
+        read_frame(stream)?;
+
+        Ok(())
+    }
+
+    fn connect(&self) -> io::Result<Stream> {
+        let mut stream = (0..IPC_SOCKETS)
+            .find_map(|index| open_socket(index).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "discord is not running"))?;
+
+        write_frame(&mut stream, OP_HANDSHAKE, &json!({ "v": 1, "client_id": self.client_id }))?;
+        read_frame(&mut stream)?;
+
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+fn open_socket(index: u32) -> io::Result<Stream> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .unwrap_or_else(|| "/tmp".to_string());
+
+    let stream = Stream::connect(std::path::Path::new(&dir).join(format!("discord-ipc-{}", index)))?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn open_socket(index: u32) -> io::Result<Stream> {
+    std::fs::OpenOptions::new().read(true).write(true).open(format!(r"\\.\pipe\discord-ipc-{}", index))
+}
+
+/// Frame is the opcode and the length of the payload as little endian integers followed by json
+fn write_frame(stream: &mut Stream, op: u32, payload: &Value) -> io::Result<()> {
+    let payload = serde_json::to_vec(payload)?;
+    let mut frame = Vec::with_capacity(8 + payload.len());
+
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&payload);
+
+    stream.write_all(&frame)
+}
+
+fn read_frame(stream: &mut Stream) -> io::Result<(u32, Value)> {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header)?;
+
+    let op = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let length = u32::from_le_bytes(header[4..8].try_into().unwrap());
+
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+
+    Ok((op, serde_json::from_slice(&payload)?))
+}
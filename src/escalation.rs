@@ -0,0 +1,290 @@
+use bevy::prelude::*;
+use bevy_math::ivec2;
+use itertools::Itertools;
+
+use crate::{
+    actors::{ enemy::Enemy, player::Player },
+    constants::CHUNK_SIZE,
+    despawn_component,
+    generation::{ level::Escalation, LevelData },
+    registries::Registries,
+    simulation::{ chunk_manager::ChunkManager, dirty_rect::DirtyRects, pixel::Pixel },
+    state::GameState,
+};
+
+const LAVA_MATERIAL: &str = "lava";
+const VENT_MATERIAL: &str = "toxic_gas";
+const VENT_INTERVAL: f32 = 0.1;
+const VENT_PLACEMENT_ATTEMPTS: u32 = 64;
+const WAVE_PLACEMENT_ATTEMPTS: u32 = 32;
+const WARNING_DURATION: f32 = 4.0;
+
+pub struct EscalationPlugin;
+
+impl Plugin for EscalationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, setup_escalation)
+            .add_systems(
+                Update,
+                (advance_escalation, raise_lava, emit_gas, spawn_waves)
+                    .chain()
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<EscalationState>))
+            )
+            .add_systems(Update, fade_warnings.run_if(in_state(GameState::Game)))
+            .add_systems(OnExit(GameState::Game), despawn_component::<UiEscalationWarning>);
+    }
+}
+
+/// Progress of hazards on the current level, absent on levels without escalation
+#[derive(Resource)]
+pub struct EscalationState {
+    pub escalation: Escalation,
+    pub elapsed: f32,
+    pub stage: Option<usize>,
+    /// Pixel bounds of the level
+    pub bounds: IRect,
+    /// Height of the lava floor in pixels above the bottom of the level
+    pub lava_height: f32,
+    pub vents: Vec<IVec2>,
+    pub vent_timer: Timer,
+    pub wave_timer: Timer,
+}
+
+#[derive(Component)]
+pub struct UiEscalationWarning(Timer);
+
+fn setup_escalation(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>
+) {
+    let Some(escalation) = level_data.0.escalation.clone() else {
+        commands.remove_resource::<EscalationState>();
+        return;
+    };
+
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2() / CHUNK_SIZE;
+    let bounds = IRect::from_center_size(IVec2::ZERO, size * CHUNK_SIZE);
+
+    commands.insert_resource(EscalationState {
+        escalation,
+        elapsed: 0.0,
+        stage: None,
+        bounds,
+        lava_height: 0.0,
+        vents: vec![],
+        vent_timer: Timer::from_seconds(VENT_INTERVAL, TimerMode::Repeating),
+        wave_timer: Timer::default(),
+    });
+}
+
+fn advance_escalation(
+    mut commands: Commands,
+    mut state: ResMut<EscalationState>,
+    time: Res<Time>,
+    chunk_manager: Res<ChunkManager>
+) {
+    state.elapsed += time.delta_seconds();
+
+    // last stage acts as a hard cap, escalation does not go beyond it
+    let stage = state.escalation.stages
+        .iter()
+        .rposition(|stage| stage.after <= state.elapsed);
+
+    if stage == state.stage {
+        return;
+    }
+
+    state.stage = stage;
+
+    let Some(stage) = stage.map(|index| state.escalation.stages[index].clone()) else {
+        return;
+    };
+
+    let bounds = state.bounds;
+    let vent_count = stage.gas_vents as usize;
+
+    state.vents.truncate(vent_count);
+    for _ in state.vents.len()..vent_count {
+        if let Some(vent) = find_vent(&chunk_manager, bounds) {
+            state.vents.push(vent);
+        }
+    }
+
+    if let Some(wave) = &stage.wave {
+        state.wave_timer = Timer::from_seconds(wave.interval, TimerMode::Repeating);
+    }
+
+    spawn_warning(&mut commands, stage.message);
+}
+
+/// Shows a message at the top of the screen that fades out after a few seconds
+pub fn spawn_warning(commands: &mut Commands, message: impl Into<String>) {
+    commands.spawn((
+        Name::new("Escalation warning"),
+        UiEscalationWarning(Timer::from_seconds(WARNING_DURATION, TimerMode::Once)),
+        TextBundle::from_section(message, TextStyle {
+            font_size: 28.0,
+            color: Color::rgb_u8(0xff, 0x6a, 0x3d),
+            ..Default::default()
+        })
+            .with_text_justify(JustifyText::Center)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(80.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            }),
+    ));
+}
+
+/// Picks empty pixel right above the ground, so the gas seeps out of the terrain
+fn find_vent(chunk_manager: &ChunkManager, bounds: IRect) -> Option<IVec2> {
+    (0..VENT_PLACEMENT_ATTEMPTS)
+        .map(|_| {
+            ivec2(
+                fastrand::i32(bounds.min.x..bounds.max.x),
+                fastrand::i32(bounds.min.y + 1..bounds.max.y)
+            )
+        })
+        .find(|position| {
+            chunk_manager.get(*position).is_ok_and(|pixel| pixel.is_empty()) &&
+                chunk_manager
+                    .get(*position - IVec2::Y)
+                    .is_ok_and(|pixel| !pixel.is_empty())
+        })
+}
+
+fn raise_lava(
+    mut state: ResMut<EscalationState>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    let Some(stage) = state.stage.map(|index| &state.escalation.stages[index]) else {
+        return;
+    };
+
+    let max_height = state.escalation.max_lava_height * (state.bounds.height() as f32);
+    let height = (state.lava_height + stage.lava_rise * time.delta_seconds()).min(max_height);
+
+    let lava = &registries.materials[LAVA_MATERIAL];
+
+    let bounds = state.bounds;
+    for row in state.lava_height.ceil() as i32..height.ceil() as i32 {
+        for x in bounds.min.x..bounds.max.x {
+            let position = ivec2(x, bounds.min.y + row);
+
+            if
+                chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) &&
+                chunk_manager.set(position, Pixel::from(lava)).is_ok()
+            {
+                dirty_rects.request_update(position);
+                dirty_rects.request_render(position);
+            }
+        }
+    }
+
+    state.lava_height = height;
+}
+
+fn emit_gas(
+    mut state: ResMut<EscalationState>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    if !state.vent_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let gas = &registries.materials[VENT_MATERIAL];
+
+    for vent in state.vents.iter() {
+        if
+            chunk_manager.get(*vent).is_ok_and(|pixel| pixel.is_empty()) &&
+            chunk_manager.set(*vent, Pixel::from(gas)).is_ok()
+        {
+            dirty_rects.request_update(*vent);
+            dirty_rects.request_render(*vent);
+        }
+    }
+}
+
+fn spawn_waves(
+    mut commands: Commands,
+    mut state: ResMut<EscalationState>,
+    chunk_manager: Res<ChunkManager>,
+    registries: Res<Registries>,
+    player_q: Query<&Transform, With<Player>>,
+    enemy_q: Query<(), With<Enemy>>,
+    time: Res<Time>
+) {
+    let Some(wave) = state.stage.and_then(|index| state.escalation.stages[index].wave.clone()) else {
+        return;
+    };
+
+    if !state.wave_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let (Some(player_transform), Some(enemy_fn)) = (
+        player_q.iter().next(),
+        registries.enemies.get(&wave.enemy_id),
+    ) else {
+        return;
+    };
+
+    let free_slots = state.escalation.max_enemies.saturating_sub(enemy_q.iter().count() as u32);
+    let enemy_size = enemy_fn(Vec2::ZERO).0.actor.actor.size.as_ivec2();
+    let player_position = player_transform.translation.xy() * (CHUNK_SIZE as f32);
+
+    for _ in 0..wave.count.min(free_slots) {
+        // enemies appear around the player, but not right on top of them
+        let position = (0..WAVE_PLACEMENT_ATTEMPTS)
+            .map(|_| {
+                let angle = fastrand::f32() * std::f32::consts::TAU;
+                let distance = CHUNK_SIZE as f32 * (1.0 + fastrand::f32());
+
+                (player_position + Vec2::from_angle(angle) * distance).as_ivec2()
+            })
+            .find(|position| {
+                (0..enemy_size.x)
+                    .cartesian_product(0..enemy_size.y)
+                    .all(|(x, y)| {
+                        let pixel_position = *position + ivec2(x, y);
+
+                        state.bounds.contains(pixel_position) &&
+                            chunk_manager.get(pixel_position).is_ok_and(|pixel| pixel.is_empty())
+                    })
+            });
+
+        if let Some(position) = position {
+            let (enemy, enemy_hitbox) = enemy_fn(position.as_vec2() / (CHUNK_SIZE as f32));
+
+            commands.spawn(enemy).with_children(|parent| {
+                parent.spawn(enemy_hitbox);
+            });
+        }
+    }
+}
+
+fn fade_warnings(
+    mut commands: Commands,
+    mut warning_q: Query<(Entity, &mut UiEscalationWarning, &mut Text)>,
+    time: Res<Time>
+) {
+    for (entity, mut warning, mut text) in warning_q.iter_mut() {
+        if warning.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(warning.0.fraction_remaining().min(0.5) * 2.0);
+        }
+    }
+}
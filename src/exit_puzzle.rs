@@ -0,0 +1,111 @@
+use bevy::{ prelude::*, utils::HashMap };
+
+use crate::{
+    constants::CHUNK_SIZE,
+    escalation::spawn_warning,
+    generation::{ level::{ ExitCondition, ExitPuzzle }, Exit, LevelData },
+    simulation::chunk_manager::ChunkManager,
+    state::GameState,
+};
+
+const CHECK_INTERVAL: f32 = 1.0;
+const SOLVED_MESSAGE: &str = "The exit is free!";
+
+pub struct ExitPuzzlePlugin;
+
+impl Plugin for ExitPuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, setup_exit_puzzle)
+            .add_systems(
+                Update,
+                check_exit_puzzle.run_if(
+                    in_state(GameState::Game).and_then(resource_exists::<ExitPuzzleState>)
+                )
+            );
+    }
+}
+
+/// Progress of the exit puzzle on the current level, absent on levels without one
+#[derive(Resource)]
+pub struct ExitPuzzleState {
+    pub puzzle: ExitPuzzle,
+    pub solved: bool,
+    pub timer: Timer,
+}
+
+impl ExitCondition {
+    fn is_met(&self, composition: &HashMap<String, u32>, total: u32) -> bool {
+        let share = |material_id: &String| {
+            (composition.get(material_id).copied().unwrap_or(0) as f32) / (total.max(1) as f32)
+        };
+
+        match self {
+            ExitCondition::Cleared { material_id, max } => share(material_id) <= *max,
+            ExitCondition::Flooded { material_id, min } => share(material_id) >= *min,
+        }
+    }
+}
+
+fn setup_exit_puzzle(mut commands: Commands, level_data: Res<LevelData>) {
+    let Some(puzzle) = level_data.0.exit_puzzle.clone() else {
+        commands.remove_resource::<ExitPuzzleState>();
+        return;
+    };
+
+    spawn_warning(&mut commands, puzzle.message.clone());
+
+    commands.insert_resource(ExitPuzzleState {
+        puzzle,
+        solved: false,
+        timer: Timer::from_seconds(CHECK_INTERVAL, TimerMode::Repeating),
+    });
+}
+
+fn check_exit_puzzle(
+    mut commands: Commands,
+    mut state: ResMut<ExitPuzzleState>,
+    exit_q: Query<&Transform, With<Exit>>,
+    chunk_manager: Res<ChunkManager>,
+    time: Res<Time>
+) {
+    if state.solved || !state.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let Ok(exit_transform) = exit_q.get_single() else {
+        return;
+    };
+
+    let center = (exit_transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2();
+    let radius = state.puzzle.radius;
+
+    let mut composition = HashMap::new();
+    let mut total = 0;
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let offset = IVec2::new(x, y);
+
+            if offset.length_squared() > radius.pow(2) {
+                continue;
+            }
+
+            // exit is evaluated only while all of its surroundings are loaded
+            let Ok(pixel) = chunk_manager.get(center + offset) else {
+                return;
+            };
+
+            *composition.entry(pixel.material.id.clone()).or_insert(0) += 1;
+            total += 1;
+        }
+    }
+
+    if
+        state.puzzle.conditions
+            .iter()
+            .all(|condition| condition.is_met(&composition, total))
+    {
+        state.solved = true;
+        spawn_warning(&mut commands, SOLVED_MESSAGE);
+    }
+}
@@ -0,0 +1,322 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{ Extent3d, TextureDimension, TextureFormat },
+        texture::BevyDefault,
+        view::RenderLayers,
+    },
+    sprite::Anchor,
+    time::common_conditions::on_timer,
+    utils::HashMap,
+};
+use bevy_math::ivec2;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::player::{ CursorPosition, Player, PlayerActions, PlayerLight, PLAYER_LIGHT_RADIUS },
+    camera::LIGHTING_RENDER_LAYER,
+    constants::CHUNK_SIZE,
+    generation::LevelData,
+    localization::Locale,
+    notifications::{ Notification, NotificationKind },
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        emission::ChunkLight,
+        materials::PhysicsType,
+        pixel::Pixel,
+    },
+    state::GameState,
+    vehicle::Piloting,
+};
+
+// shadow of levels in the fog, replaces the one of the level
+pub const FOG_SHADOW: [f32; 3] = [0.01, 0.01, 0.015];
+// explored light is kept for squares of this many pixels
+const FOG_CELL_SIZE: i32 = 4;
+const FOG_CELLS: i32 = CHUNK_SIZE / FOG_CELL_SIZE;
+const EXPLORE_INTERVAL: Duration = Duration::from_millis(100);
+const TORCH_MATERIAL: &str = "torch";
+// distance in pixels from the middle of the player to the wall a torch is put in
+const TORCH_REACH: i32 = 24;
+
+/// Optional darkness of levels with [`FogOfWar`](crate::generation::level::FogOfWar),
+/// players light their surroundings and carry torches that are embedded into walls,
+/// areas that were lit once stay dimly visible
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExploredLight>()
+            .init_resource::<Torches>()
+            .add_systems(OnEnter(GameState::LevelInitialization), |mut explored: ResMut<ExploredLight>| {
+                explored.chunks.clear();
+            })
+            .add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, give_torches)
+            .add_systems(
+                Update,
+                (
+                    scale_player_lights,
+                    place_torches,
+                    explore_lit_cells.run_if(on_timer(EXPLORE_INTERVAL)),
+                ).run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Brightest light every part of the level was lit with, kept for chunks that were lit at least once
+/// until the level is left, even while they are unloaded
+#[derive(Resource, Default)]
+pub struct ExploredLight {
+    pub chunks: HashMap<IVec2, ExploredChunk>,
+}
+
+pub struct ExploredChunk {
+    /// Value of every cell of the chunk, rows go from the bottom up
+    pub cells: Vec<u8>,
+    /// Sprite drawing the cells, only present while the chunk is loaded
+    sprite: Option<ExploredSprite>,
+    dirty: bool,
+}
+
+struct ExploredSprite {
+    /// Chunk the sprite belongs to
+    chunk: Entity,
+    texture: Handle<Image>,
+}
+
+/// Torches left to place on the current level
+#[derive(Resource, Default)]
+pub struct Torches(pub u32);
+
+fn give_torches(
+    mut torches: ResMut<Torches>,
+    mut notification_ev: EventWriter<Notification>,
+    level_data: Res<LevelData>,
+    locale: Res<Locale>
+) {
+    let Some(fog) = &level_data.0.fog else {
+        torches.0 = 0;
+        return;
+    };
+
+    torches.0 = fog.torches;
+    notification_ev.send(Notification::new(locale.format("fog.intro", &[&fog.torches])));
+}
+
+/// Grows the light of the players to the radius of the fog, it is back to normal on other levels
+fn scale_player_lights(mut light_q: Query<&mut Transform, With<PlayerLight>>, level_data: Res<LevelData>) {
+    let scale = level_data.0.fog.as_ref().map_or(1.0, |fog| fog.light_radius / PLAYER_LIGHT_RADIUS);
+
+    for mut transform in light_q.iter_mut() {
+        if transform.scale.x != scale {
+            transform.scale = Vec3::new(scale, scale, 1.0);
+        }
+    }
+}
+
+/// Replaces the wall the player aims at with a few pixels of the torch material,
+/// the chunk emission makes them glow like any other emissive material
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn place_torches(
+    player_q: Query<
+        (&Transform, &ActionState<PlayerActions>, Option<&CursorPosition>),
+        (With<Player>, Without<Piloting>)
+    >,
+    mut torches: ResMut<Torches>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut notification_ev: EventWriter<Notification>,
+    level_data: Res<LevelData>,
+    registries: Res<Registries>,
+    locale: Res<Locale>
+) {
+    if level_data.0.fog.is_none() {
+        return;
+    }
+
+    let Some(material) = registries.materials.get(TORCH_MATERIAL) else {
+        return;
+    };
+
+    for (transform, action_state, cursor_position) in player_q.iter() {
+        if !action_state.just_pressed(&PlayerActions::Torch) {
+            continue;
+        }
+
+        if torches.0 == 0 {
+            notification_ev.send(
+                Notification::new(locale.get("fog.no_torches")).with_kind(NotificationKind::Warning)
+            );
+            continue;
+        }
+
+        let origin = transform.translation.xy() * (CHUNK_SIZE as f32);
+        let direction = cursor_position.map_or(Vec2::X, |cursor_position| cursor_position.direction);
+
+        let Some(wall) = (0..TORCH_REACH)
+            .map(|distance| (origin + direction * (distance as f32)).round().as_ivec2())
+            .find(|position| {
+                chunk_manager.get(*position).is_ok_and(|pixel| pixel.physics_type == PhysicsType::Static)
+            }) else {
+            notification_ev.send(
+                Notification::new(locale.get("fog.no_wall")).with_kind(NotificationKind::Warning)
+            );
+            continue;
+        };
+
+        let clock = chunk_manager.clock();
+
+        for (x, y) in (-1..=1).cartesian_product(-1..=1) {
+            let position = wall + ivec2(x, y);
+
+            if !chunk_manager.get(position).is_ok_and(|pixel| pixel.physics_type == PhysicsType::Static) {
+                continue;
+            }
+
+            if chunk_manager.set(position, Pixel::from(material).with_clock(clock)).is_ok() {
+                dirty_rects.request_update(position);
+                dirty_rects.request_render(position);
+            }
+        }
+
+        torches.0 -= 1;
+
+        notification_ev.send(Notification::new(locale.format("fog.torches_left", &[&torches.0])));
+    }
+}
+
+/// Records the light of the players and the chunk lights in the explored light
+/// and redraws the cells that got brighter
+#[allow(clippy::type_complexity)]
+fn explore_lit_cells(
+    mut commands: Commands,
+    mut explored: ResMut<ExploredLight>,
+    mut images: ResMut<Assets<Image>>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    light_q: Query<(&Sprite, &GlobalTransform, &InheritedVisibility), With<ChunkLight>>,
+    chunk_manager: Res<ChunkManager>,
+    level_data: Res<LevelData>
+) {
+    let Some(fog) = &level_data.0.fog else {
+        return;
+    };
+
+    // sprites of unloaded chunks are gone along with them, they are drawn again once the chunk is back
+    for (position, chunk) in explored.chunks.iter_mut() {
+        let loaded = chunk_manager.chunks.get(position).map(|(entity, _)| *entity);
+
+        if chunk.sprite.as_ref().map(|sprite| sprite.chunk) != loaded {
+            chunk.sprite = loaded.map(|entity| spawn_explored_sprite(&mut commands, &mut images, entity));
+            chunk.dirty = true;
+        }
+    }
+
+    let lights = player_q
+        .iter()
+        .map(|transform| (transform.translation().xy() * (CHUNK_SIZE as f32), fog.light_radius, 1.0))
+        .chain(
+            light_q
+                .iter()
+                .filter(|(.., visibility)| visibility.get())
+                .filter_map(|(sprite, transform, _)| {
+                    Some((
+                        transform.translation().xy() * (CHUNK_SIZE as f32),
+                        (sprite.custom_size?.x * (CHUNK_SIZE as f32)) / 2.0,
+                        sprite.color.a(),
+                    ))
+                })
+        )
+        .collect_vec();
+
+    for (center, radius, brightness) in lights {
+        let min = ((center - radius) / (FOG_CELL_SIZE as f32)).floor().as_ivec2();
+        let max = ((center + radius) / (FOG_CELL_SIZE as f32)).ceil().as_ivec2();
+
+        for (x, y) in (min.x..max.x).cartesian_product(min.y..max.y) {
+            let cell = ivec2(x, y);
+            let distance = ((cell.as_vec2() + 0.5) * (FOG_CELL_SIZE as f32)).distance(center);
+            let value = ((1.0 - distance / radius).clamp(0.0, 1.0) * brightness * 255.0) as u8;
+
+            if value == 0 {
+                continue;
+            }
+
+            let chunk_position = cell.div_euclid(IVec2::splat(FOG_CELLS));
+
+            let Some((chunk_entity, _)) = chunk_manager.chunks.get(&chunk_position) else {
+                continue;
+            };
+
+            let chunk = explored.chunks.entry(chunk_position).or_insert_with(|| ExploredChunk {
+                cells: vec![0; FOG_CELLS.pow(2) as usize],
+                sprite: Some(spawn_explored_sprite(&mut commands, &mut images, *chunk_entity)),
+                dirty: false,
+            });
+
+            let local = cell.rem_euclid(IVec2::splat(FOG_CELLS));
+            let stored = &mut chunk.cells[(local.y * FOG_CELLS + local.x) as usize];
+
+            if value > *stored {
+                *stored = value;
+                chunk.dirty = true;
+            }
+        }
+    }
+
+    for chunk in explored.chunks.values_mut().filter(|chunk| chunk.dirty) {
+        chunk.dirty = false;
+
+        let Some(image) = chunk.sprite.as_ref().and_then(|sprite| images.get_mut(&sprite.texture)) else {
+            continue;
+        };
+
+        for (pixel, value) in image.data.chunks_exact_mut(4).zip(chunk.cells.iter()) {
+            pixel[3] = ((*value as f32) * fog.memory) as u8;
+        }
+    }
+}
+
+fn spawn_explored_sprite(commands: &mut Commands, images: &mut Assets<Image>, chunk: Entity) -> ExploredSprite {
+    let texture = images.add(
+        Image::new(
+            Extent3d {
+                width: FOG_CELLS as u32,
+                height: FOG_CELLS as u32,
+                ..Default::default()
+            },
+            TextureDimension::D2,
+            [255, 255, 255, 0].repeat(FOG_CELLS.pow(2) as usize),
+            TextureFormat::bevy_default(),
+            RenderAssetUsages::default()
+        )
+    );
+
+    let sprite = commands
+        .spawn((
+            Name::new("Explored light"),
+            SpriteBundle {
+                texture: texture.clone(),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::ONE),
+                    anchor: Anchor::BottomLeft,
+                    flip_y: true,
+                    ..Default::default()
+                },
+                // between the chunk's own lighting texture and its emission light
+                transform: Transform::from_translation(Vec2::ZERO.extend(0.25)),
+                ..Default::default()
+            },
+            RenderLayers::layer(LIGHTING_RENDER_LAYER),
+        ))
+        .id();
+
+    commands.entity(chunk).add_child(sprite);
+
+    ExploredSprite { chunk, texture }
+}
@@ -0,0 +1,410 @@
+use std::panic::{ self, AssertUnwindSafe };
+
+use bevy::{
+    ecs::system::RunSystemOnce,
+    prelude::*,
+    tasks::{ ComputeTaskPool, TaskPoolBuilder },
+    utils::HashMap,
+};
+use bevy_math::ivec2;
+
+use crate::{
+    constants::CHUNK_SIZE,
+    inspector::ChunkInspector,
+    profiler::StageTimings,
+    registries::{ load_materials, Registries },
+    simulation::{
+        chunk::{ ChunkData, ChunkState },
+        chunk_groups::build_chunk_group,
+        chunk_manager::{ chunks_update, ChunkManager },
+        colliders::ChunkColliderEvent,
+        dirty_rect::DirtyRects,
+        materials::Material,
+        pixel::Pixel,
+    },
+};
+
+// simulated area spans WORLD_CHUNKS x WORLD_CHUNKS chunks centered around origin
+const WORLD_CHUNKS: i32 = 4;
+const MAX_ACTIONS: usize = 64;
+
+// materials that do not disappear on their own, so pixel count must be preserved by simulation
+const MATERIALS: [&str; 6] = ["stone", "dirt", "sand", "water", "mud", "lava"];
+
+#[derive(Debug, Clone)]
+enum Action {
+    Paint {
+        position: IVec2,
+        radius: i32,
+        material: &'static str,
+    },
+    Erase {
+        position: IVec2,
+        radius: i32,
+    },
+    PlaceObject {
+        position: IVec2,
+        size: IVec2,
+        material: &'static str,
+    },
+    Explode {
+        position: IVec2,
+        radius: i32,
+        damage: f32,
+    },
+    Step(u32),
+}
+
+/// Runs random sequences of world edits on a headless world and reports minimal failing sequence,
+/// usage: `sandforge --fuzz [runs] [seed]`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let runs = args
+        .next()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(100);
+    let seed = args
+        .next()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or_else(|| fastrand::u64(..));
+
+    ComputeTaskPool::get_or_init(|| TaskPoolBuilder::new().num_threads(1).build());
+
+    let materials = load_materials();
+
+    // failures are reported after shrinking, so default panic output is only noise
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut failure = None;
+    for run in 0..runs {
+        let mut rng = fastrand::Rng::with_seed(seed.wrapping_add(run));
+        let actions = (0..rng.usize(1..=MAX_ACTIONS))
+            .map(|_| random_action(&mut rng))
+            .collect::<Vec<_>>();
+
+        if let Err(error) = execute(&actions, seed.wrapping_add(run), &materials) {
+            failure = Some((run, shrink(actions, seed.wrapping_add(run), &materials, error)));
+            break;
+        }
+    }
+
+    panic::set_hook(default_hook);
+
+    match failure {
+        Some((run, (actions, error))) => {
+            println!("fuzz: run {} with seed {} failed: {}", run, seed.wrapping_add(run), error);
+            println!("minimal reproduction ({} actions):", actions.len());
+            for action in actions {
+                println!("    {:?}", action);
+            }
+            std::process::exit(1);
+        }
+        None => {
+            println!("fuzz: {} runs with seed {} passed", runs, seed);
+        }
+    }
+}
+
+fn random_position(rng: &mut fastrand::Rng) -> IVec2 {
+    let half_size = (WORLD_CHUNKS / 2) * CHUNK_SIZE;
+
+    // most of the edits are placed near chunk borders, since that is where chunk groups stitch chunks
+    let mut coordinate = || {
+        if rng.f32() < 0.75 {
+            rng.i32(-WORLD_CHUNKS / 2..=WORLD_CHUNKS / 2) * CHUNK_SIZE + rng.i32(-4..4)
+        } else {
+            rng.i32(-half_size - 8..half_size + 8)
+        }
+    };
+
+    ivec2(coordinate(), coordinate())
+}
+
+fn random_action(rng: &mut fastrand::Rng) -> Action {
+    match rng.u8(0..5) {
+        0 =>
+            Action::Paint {
+                position: random_position(rng),
+                radius: rng.i32(0..12),
+                material: MATERIALS[rng.usize(..MATERIALS.len())],
+            },
+        1 =>
+            Action::Erase {
+                position: random_position(rng),
+                radius: rng.i32(0..12),
+            },
+        2 =>
+            Action::PlaceObject {
+                position: random_position(rng),
+                size: ivec2(rng.i32(1..80), rng.i32(1..80)),
+                material: MATERIALS[rng.usize(..MATERIALS.len())],
+            },
+        3 =>
+            Action::Explode {
+                position: random_position(rng),
+                radius: rng.i32(1..24),
+                damage: rng.f32() * 16.0,
+            },
+        _ => Action::Step(rng.u32(1..16)),
+    }
+}
+
+fn setup_world(materials: &HashMap<String, Material>) -> World {
+    let mut world = World::new();
+    let mut chunk_manager = ChunkManager::from_world(&mut world);
+
+    let active = -WORLD_CHUNKS / 2..WORLD_CHUNKS / 2;
+
+    // like in game, active chunks are surrounded by sleeping ones
+    for x in -WORLD_CHUNKS / 2 - 1..WORLD_CHUNKS / 2 + 1 {
+        for y in -WORLD_CHUNKS / 2 - 1..WORLD_CHUNKS / 2 + 1 {
+            let is_border = !active.contains(&x) || !active.contains(&y);
+
+            chunk_manager.chunks.insert(ivec2(x, y), (
+                Entity::PLACEHOLDER,
+                ChunkData {
+                    state: if is_border { ChunkState::Sleeping } else { ChunkState::Active },
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+
+    world.insert_resource(chunk_manager);
+    world.insert_resource(DirtyRects::default());
+    world.insert_resource(Registries {
+        materials: materials.clone(),
+        enemies: HashMap::default(),
+        elites: vec![],
+        levels: vec![],
+        unlocks: vec![],
+        perks: vec![],
+        tutorial: crate::tutorial::load_tutorial(),
+        cutscenes: crate::cutscene::load_cutscenes(),
+        gameplay: crate::gameplay::load_gameplay(),
+    });
+    world.init_resource::<Events<ChunkColliderEvent>>();
+    world.init_resource::<StageTimings>();
+    world.init_resource::<ChunkInspector>();
+
+    world
+}
+
+fn execute(
+    actions: &[Action],
+    seed: u64,
+    materials: &HashMap<String, Material>
+) -> Result<(), String> {
+    panic
+        ::catch_unwind(
+            AssertUnwindSafe(|| {
+                fastrand::seed(seed);
+
+                let mut world = setup_world(materials);
+
+                for (index, action) in actions.iter().enumerate() {
+                    let before = count_pixels(&world);
+
+                    apply(&mut world, action, materials);
+
+                    check_dirty_rects(&world).map_err(|error| format!("action {}: {}", index, error))?;
+
+                    if let Action::Step(_) = action {
+                        let after = count_pixels(&world);
+                        if before != after {
+                            return Err(
+                                format!(
+                                    "action {}: pixel count drifted from {} to {}",
+                                    index,
+                                    before,
+                                    after
+                                )
+                            );
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+        )
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+
+            Err(format!("panicked: {}", message))
+        })
+}
+
+/// Removes actions one by one while the failure still reproduces
+fn shrink(
+    mut actions: Vec<Action>,
+    seed: u64,
+    materials: &HashMap<String, Material>,
+    mut error: String
+) -> (Vec<Action>, String) {
+    loop {
+        let mut progress = false;
+        let mut index = 0;
+
+        while index < actions.len() {
+            let mut candidate = actions.clone();
+            candidate.remove(index);
+
+            match execute(&candidate, seed, materials) {
+                Err(candidate_error) => {
+                    actions = candidate;
+                    error = candidate_error;
+                    progress = true;
+                }
+                Ok(()) => {
+                    index += 1;
+                }
+            }
+        }
+
+        if !progress {
+            return (actions, error);
+        }
+    }
+}
+
+fn apply(world: &mut World, action: &Action, materials: &HashMap<String, Material>) {
+    if let Action::Step(steps) = action {
+        for _ in 0..*steps {
+            world.run_system_once(chunks_update);
+        }
+        return;
+    }
+
+    world.resource_scope(|world, mut chunk_manager: Mut<ChunkManager>| {
+        let mut dirty_rects = world.resource_mut::<DirtyRects>();
+
+        match action {
+            Action::Paint { position, radius, material } => {
+                for_each_in_circle(*position, *radius, |position| {
+                    if chunk_manager.set(position, Pixel::from(&materials[*material])).is_ok() {
+                        dirty_rects.request_update(position);
+                        dirty_rects.request_render(position);
+                    }
+                });
+            }
+            Action::Erase { position, radius } => {
+                for_each_in_circle(*position, *radius, |position| {
+                    if chunk_manager.set(position, Pixel::default()).is_ok() {
+                        dirty_rects.request_update(position);
+                        dirty_rects.request_render(position);
+                    }
+                });
+            }
+            Action::PlaceObject { position, size, material } => {
+                let chunk_position = position.div_euclid(IVec2::splat(CHUNK_SIZE));
+                let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) else {
+                    return;
+                };
+
+                for x in 0..size.x {
+                    for y in 0..size.y {
+                        let global_position = *position + ivec2(x, y);
+                        let local_position = global_position - chunk_position * CHUNK_SIZE;
+
+                        if
+                            chunk_group
+                                .get(local_position)
+                                .is_some_and(|pixel| pixel.is_empty()) &&
+                            chunk_group
+                                .set(local_position, Pixel::from(&materials[*material]))
+                                .is_ok()
+                        {
+                            dirty_rects.request_update(global_position);
+                            dirty_rects.request_render(global_position);
+                        }
+                    }
+                }
+            }
+            Action::Explode { position, radius, damage } => {
+                let chunk_position = position.div_euclid(IVec2::splat(CHUNK_SIZE));
+                let clock = chunk_manager.clock();
+                let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) else {
+                    return;
+                };
+
+                for_each_in_circle(*position, *radius, |global_position| {
+                    let Some(pixel) = chunk_group.get_mut(
+                        global_position - chunk_position * CHUNK_SIZE
+                    ) else {
+                        return;
+                    };
+
+                    if let Some(durability) = &mut pixel.durability {
+                        *durability -= damage;
+                        if *durability <= 0.0 {
+                            *pixel = Pixel::default().with_clock(clock);
+                        }
+                    }
+
+                    dirty_rects.request_update(global_position);
+                    dirty_rects.request_render(global_position);
+                });
+            }
+            Action::Step(_) => unreachable!(),
+        }
+    });
+}
+
+fn for_each_in_circle<F: FnMut(IVec2)>(center: IVec2, radius: i32, mut operation: F) {
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            if x.pow(2) + y.pow(2) <= radius.pow(2) {
+                operation(center + ivec2(x, y));
+            }
+        }
+    }
+}
+
+fn count_pixels(world: &World) -> usize {
+    world
+        .resource::<ChunkManager>()
+        .chunks.values()
+        .map(|(_, chunk)| {
+            chunk.pixels
+                .iter()
+                .filter(|pixel| !pixel.is_empty())
+                .count()
+        })
+        .sum()
+}
+
+fn check_dirty_rects(world: &World) -> Result<(), String> {
+    let chunk_manager = world.resource::<ChunkManager>();
+    let dirty_rects = world.resource::<DirtyRects>();
+
+    for (name, rects) in [
+        ("current", &dirty_rects.current),
+        ("new", &dirty_rects.new),
+        ("render", &dirty_rects.render),
+    ] {
+        for (position, rect) in rects.iter() {
+            if rect.min.x > rect.max.x || rect.min.y > rect.max.y {
+                return Err(format!("{} dirty rect of chunk {} is inverted: {:?}", name, position, rect));
+            }
+
+            if rect.max.x > (CHUNK_SIZE as u32) || rect.max.y > (CHUNK_SIZE as u32) {
+                return Err(format!("{} dirty rect of chunk {} is out of bounds: {:?}", name, position, rect));
+            }
+
+            // neighbours of loaded chunks may be marked, anything further means leaked coordinates
+            let is_near_loaded = (-1..=1).any(|x| {
+                (-1..=1).any(|y| chunk_manager.chunks.contains_key(&(*position + ivec2(x, y))))
+            });
+
+            if !is_near_loaded {
+                return Err(format!("{} dirty rect is placed far from loaded chunks: {}", name, position));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+
+/// Tuning values of the moves of the players, read from gameplay.ron
+#[derive(Deserialize, Clone)]
+pub struct Gameplay {
+    pub attack: AttackTiming,
+}
+
+/// Timing windows of the attack, durations are in milliseconds
+#[derive(Deserialize, Clone)]
+pub struct AttackTiming {
+    pub cooldown_ms: u64,
+    /// Time after the cooldown during which the next attack continues the combo
+    pub combo_window_ms: u64,
+    /// Time the attack has to be held for to release a heavy attack
+    pub charge_ms: u64,
+    /// Swings of the combo in order, the chain starts over after the last one
+    pub combo: Vec<Swing>,
+    pub heavy: Swing,
+}
+
+/// Multipliers of a single swing, relative to the regular attack
+#[derive(Deserialize, Clone)]
+pub struct Swing {
+    #[serde(default = "one")]
+    pub damage: f32,
+    #[serde(default = "one")]
+    pub knockback: f32,
+    /// Scale of the hitbox around the cursor direction
+    #[serde(default = "one")]
+    pub radius: f32,
+    /// Scale of the area of terrain carved by the swing
+    #[serde(default = "one")]
+    pub carve: f32,
+    /// First and last index of the animation in the player sprite sheet
+    pub frames: (usize, usize),
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn default_fps() -> f64 {
+    8.0
+}
+
+/// Panics on a combo without swings, attacks always need one to continue with
+pub fn load_gameplay() -> Gameplay {
+    let gameplay = ron::de
+        ::from_str::<Gameplay>(&std::fs::read_to_string("gameplay.ron").unwrap())
+        .unwrap();
+
+    assert!(!gameplay.attack.combo.is_empty(), "gameplay.ron: the attack combo has no swings");
+
+    gameplay
+}
@@ -17,6 +17,7 @@ use bevy::{
     utils::petgraph::matrix_graph::Zero,
 };
 use bevy_math::{ ivec2, IVec2 };
+use bevy_persistent::Persistent;
 use bevy_rapier2d::{
     dynamics:: RigidBody ,
     geometry::{ CollisionGroups, Group },
@@ -25,10 +26,20 @@ use indexmap::IndexSet;
 use itertools::Itertools;
 
 use crate::{
-    camera::{ BACKGROUND_RENDER_LAYER, LIGHTING_RENDER_LAYER, TERRAIN_RENDER_LAYER },
+    actors::elite::roll_elite,
+    camera::{
+        BACKGROUND_RENDER_LAYER,
+        HEAT_RENDER_LAYER,
+        LIGHTING_RENDER_LAYER,
+        LIQUID_RENDER_LAYER,
+        TERRAIN_RENDER_LAYER,
+    },
     constants::{ BACKGROUND_Z, CHUNK_SIZE, TERRAIN_Z },
+    difficulty::Difficulty,
     registries::Registries,
+    settings::Config,
     simulation::{
+        atlas::ChunkAtlas,
         chunk::{ Chunk, ChunkData, ChunkState },
         chunk_groups::build_chunk_group,
         chunk_manager::ChunkManager,
@@ -37,7 +48,7 @@ use crate::{
     },
 };
 
-use super::{ LevelData, Noise, EnemyPositions };
+use super::{ LevelData, LevelCounter, Noise, EnemyPositions };
 
 #[derive(Event, Deref)]
 pub struct GenerationEvent(pub IVec2);
@@ -57,10 +68,12 @@ pub fn push_events_to_queue(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn process_chunk_generation_events(
     mut commands: Commands,
     mut queue: ResMut<GenerationQueue>,
     mut chunk_manager: ResMut<ChunkManager>,
+    mut atlas: ResMut<ChunkAtlas>,
     mut images: ResMut<Assets<Image>>,
     registries: Res<Registries>,
     noise: Res<Noise>,
@@ -96,6 +109,8 @@ pub fn process_chunk_generation_events(
         })
         .collect_vec();
 
+    let mirror = level_data.0.mirror;
+
     let tasks_to_launch = queue.len().min(8 - current_tasks);
     for position in queue.drain(0..tasks_to_launch) {
         if chunk_manager.chunks.contains_key(&position) {
@@ -111,11 +126,13 @@ pub fn process_chunk_generation_events(
         let terrain_layers = terrain_layers.clone();
         let background_layers = background_layers.clone();
 
+        let slot = atlas.allocate(&mut images);
+        let page = atlas.page(slot.page);
+
         let chunk = ChunkData {
             pixels: vec![],
-            texture: images.add(ChunkData::new_image()),
             background: images.add(ChunkData::new_image()),
-            lighting: images.add(ChunkData::new_image()),
+            slot: Some(slot),
             state: ChunkState::Generating,
             ..Default::default()
         };
@@ -126,9 +143,10 @@ pub fn process_chunk_generation_events(
                 Chunk,
                 RigidBody::Fixed,
                 SpriteBundle {
-                    texture: chunk.texture.clone(),
+                    texture: page.terrain.clone(),
                     sprite: Sprite {
                         custom_size: Some(Vec2::new(1.0, 1.0)),
+                        rect: Some(slot.rect()),
                         anchor: Anchor::BottomLeft,
                         flip_y: true,
                         ..Default::default()
@@ -156,9 +174,10 @@ pub fn process_chunk_generation_events(
 
                 parent.spawn((
                     SpriteBundle {
-                        texture: chunk.lighting.clone(),
+                        texture: page.lighting.clone(),
                         sprite: Sprite {
                             custom_size: Some(Vec2::new(1.0, 1.0)),
+                            rect: Some(slot.rect()),
                             anchor: Anchor::BottomLeft,
                             flip_y: true,
                             ..Default::default()
@@ -168,6 +187,38 @@ pub fn process_chunk_generation_events(
                     },
                     RenderLayers::layer(LIGHTING_RENDER_LAYER),
                 ));
+
+                parent.spawn((
+                    SpriteBundle {
+                        texture: page.liquid.clone(),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(1.0, 1.0)),
+                            rect: Some(slot.rect()),
+                            anchor: Anchor::BottomLeft,
+                            flip_y: true,
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(Vec2::ZERO.extend(0.0)),
+                        ..Default::default()
+                    },
+                    RenderLayers::layer(LIQUID_RENDER_LAYER),
+                ));
+
+                parent.spawn((
+                    SpriteBundle {
+                        texture: page.heat.clone(),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(1.0, 1.0)),
+                            rect: Some(slot.rect()),
+                            anchor: Anchor::BottomLeft,
+                            flip_y: true,
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(Vec2::ZERO.extend(0.0)),
+                        ..Default::default()
+                    },
+                    RenderLayers::layer(HEAT_RENDER_LAYER),
+                ));
             })
             .id();
 
@@ -176,17 +227,17 @@ pub fn process_chunk_generation_events(
         commands.entity(entity).insert(
             GenerationTask(
                 thread_pool.spawn(async move {
-                    let texture_position = position * CHUNK_SIZE + image.size().as_ivec2() / 2;
+                    let _span = info_span!("generate_chunk").entered();
 
                     let pixels = (0..CHUNK_SIZE.pow(2))
                         .map(|index| {
-                            let point =
-                                position.as_vec2() +
-                                ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE).as_vec2() /
-                                    (CHUNK_SIZE as f32);
+                            let pixel_position = mirror.source_pixel(
+                                position * CHUNK_SIZE + ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE)
+                            );
+                            let point = pixel_position.as_vec2() / (CHUNK_SIZE as f32);
 
                             let texture_position = (
-                                texture_position + ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE)
+                                pixel_position + image.size().as_ivec2() / 2
                             ).clamp(IVec2::ZERO, image.size().as_ivec2() - 1);
 
                             let texture_modifier =
@@ -234,13 +285,13 @@ pub fn process_chunk_generation_events(
 
                     let bg_texture = (0..CHUNK_SIZE.pow(2))
                         .map(|index| {
-                            let point =
-                                position.as_vec2() +
-                                ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE).as_vec2() /
-                                    (CHUNK_SIZE as f32);
+                            let pixel_position = mirror.source_pixel(
+                                position * CHUNK_SIZE + ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE)
+                            );
+                            let point = pixel_position.as_vec2() / (CHUNK_SIZE as f32);
 
                             let texture_position = (
-                                texture_position + ivec2(index % CHUNK_SIZE, index / CHUNK_SIZE)
+                                pixel_position + image.size().as_ivec2() / 2
                             ).clamp(IVec2::ZERO, image.size().as_ivec2() - 1);
 
                             let texture_modifier =
@@ -266,22 +317,7 @@ pub fn process_chunk_generation_events(
 
                             return Pixel::default();
                         })
-                        .map(|pixel| {
-                            let mut color = pixel.get_color();
-
-                            let f = 0.6;
-
-                            let (r, g, b) = (color[0] as f32, color[1] as f32, color[2] as f32);
-
-                            let l = 0.3 * r + 0.6 * g + 0.1 * b;
-
-                            color[0] = (((r + f * (l - r)) * 0.8) as u8).saturating_sub(25);
-                            color[1] = (((g + f * (l - g)) * 0.8) as u8).saturating_sub(25);
-                            color[2] = (((b + f * (l - b)) * 0.8) as u8).saturating_sub(25);
-
-                            color
-                        })
-                        .flatten()
+                        .flat_map(|pixel| background_color(pixel.get_color()))
                         .collect_vec();
 
                     (pixels, bg_texture)
@@ -291,14 +327,33 @@ pub fn process_chunk_generation_events(
     }
 }
 
+/// Desaturated and darkened color of a material when it is part of the background
+pub fn background_color(mut color: [u8; 4]) -> [u8; 4] {
+    let f = 0.6;
+
+    let (r, g, b) = (color[0] as f32, color[1] as f32, color[2] as f32);
+
+    let l = 0.3 * r + 0.6 * g + 0.1 * b;
+
+    color[0] = (((r + f * (l - r)) * 0.8) as u8).saturating_sub(25);
+    color[1] = (((g + f * (l - g)) * 0.8) as u8).saturating_sub(25);
+    color[2] = (((b + f * (l - b)) * 0.8) as u8).saturating_sub(25);
+
+    color
+}
+
 pub fn process_chunk_generation_tasks(
     mut commands: Commands,
     mut chunk_manager: ResMut<ChunkManager>,
+    mut atlas: ResMut<ChunkAtlas>,
     mut images: ResMut<Assets<Image>>,
     mut chunk_q: Query<(Entity, &Transform, &mut GenerationTask), With<Chunk>>,
     mut awaiting: ResMut<AwaitingNearbyChunks>,
-    level: Res<LevelData>
+    level: Res<LevelData>,
+    config: Res<Persistent<Config>>
 ) {
+    let _span = info_span!("generation_tasks").entered();
+
     for (entity, transform, mut task) in chunk_q.iter_mut() {
         let result = block_on(future::poll_once(&mut task.0));
 
@@ -313,25 +368,23 @@ pub fn process_chunk_generation_tasks(
             commands
                 .entity(entity)
                 .with_children(|parent| {
-                    if let Ok(colliders) = chunk.build_colliders() {
-                        for collider in colliders {
-                            parent.spawn((
-                                collider,
-                                TransformBundle {
-                                    local: Transform::IDENTITY,
-                                    ..Default::default()
-                                },
-                                CollisionGroups::new(
-                                    Group::from_bits_truncate(TERRAIN_MASK),
-                                    Group::from_bits_truncate(OBJECT_MASK)
-                                ),
-                            ));
-                        }
+                    if let Ok(collider) = chunk.build_colliders(config.collider_tolerance) {
+                        parent.spawn((
+                            collider,
+                            TransformBundle {
+                                local: Transform::IDENTITY,
+                                ..Default::default()
+                            },
+                            CollisionGroups::new(
+                                Group::from_bits_truncate(TERRAIN_MASK),
+                                Group::from_bits_truncate(OBJECT_MASK)
+                            ),
+                        ));
                     }
                 })
                 .remove::<GenerationTask>();
 
-            chunk.update_textures(&mut images, level.0.lighting);
+            chunk.update_textures(&images, &mut atlas, level.0.lighting);
             chunk.state = ChunkState::Populating;
             awaiting.push(position);
         }
@@ -346,7 +399,9 @@ pub fn populate_chunk(
     mut chunk_manager: ResMut<ChunkManager>,
     mut awaiting: ResMut<AwaitingNearbyChunks>,
     mut enemies_queue: ResMut<EnemyPositions>,
-    registries: Res<Registries>
+    registries: Res<Registries>,
+    difficulty: Res<Difficulty>,
+    counter: Res<LevelCounter>
 ) {
     awaiting.retain(|position| {
         if
@@ -442,11 +497,25 @@ pub fn populate_chunk(
                     })
                     .for_each(|position| {
                         let enemy_fn = registries.enemies.get(&id).unwrap();
-                        let (enemy, enemy_hitbox) = enemy_fn(position);
+                        let (mut enemy, enemy_hitbox) = enemy_fn(position);
+                        let elite = roll_elite(&registries.elites, counter.0.saturating_sub(1));
+
+                        if let Some(elite) = &elite {
+                            elite.apply(&mut enemy);
+                        }
 
-                        commands.spawn(enemy).with_children(|parent| {
+                        let health = &mut enemy.actor.health;
+                        health.total *= difficulty.enemy_health();
+                        health.current = health.total;
+
+                        let mut entity = commands.spawn(enemy);
+                        entity.with_children(|parent| {
                             parent.spawn(enemy_hitbox);
                         });
+
+                        if let Some(elite) = elite {
+                            entity.insert(elite);
+                        }
                     });
             });
 
@@ -1,3 +1,4 @@
+use bevy::math::{ vec2, IVec2, Vec2 };
 use serde::Deserialize;
 
 use super::noise::NoiseType;
@@ -15,11 +16,365 @@ pub struct EnemyOnLevel {
     pub spawn_chance: f32,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct DecorationOnLevel {
+    pub stamp_id: String,
+    pub count: u32,
+    /// Spawned along with every placed stamp
+    #[serde(default)]
+    pub interactables: Vec<InteractableOnLevel>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChestLoot {
+    pub material_id: String,
+    /// Amount of pixels spilled out of the chest
+    pub amount: u32,
+}
+
+/// Rectangle of terrain pixels opened and closed by a trigger
+#[derive(Deserialize, Clone)]
+pub struct DoorOnLevel {
+    pub material_id: String,
+    /// Bottom left corner relative to the center of the stamp
+    pub offset: (i32, i32),
+    pub size: (i32, i32),
+}
+
+#[derive(Deserialize, Clone)]
+pub enum InteractableKind {
+    Chest {
+        loot: Vec<ChestLoot>,
+    },
+    Lever {
+        door: DoorOnLevel,
+    },
+    /// Holds the door open while an actor or a heavy object is standing on it
+    PressurePlate {
+        door: DoorOnLevel,
+    },
+    /// Picked up by walking over it, keys are kept until the end of the level
+    Key,
+    /// Picked up by walking over it, raises the mining tier of every player for the rest of the run
+    ToolUpgrade,
+    /// Lock of a door that is opened with a key, the key is used up
+    LockedDoor {
+        door: DoorOnLevel,
+    },
+    /// Opens once every enemy within the radius in pixels is dead
+    RoomGate {
+        door: DoorOnLevel,
+        radius: f32,
+    },
+}
+
+/// Offset of the bottom center is relative to the center of the stamp, in pixels
+#[derive(Deserialize, Clone)]
+pub struct InteractableOnLevel {
+    pub offset: (i32, i32),
+    pub kind: InteractableKind,
+}
+
+/// Sizes are in pixels
+#[derive(Deserialize, Clone)]
+pub enum TrapKind {
+    /// Strip on the floor that damages every actor touching it
+    Spikes {
+        width: i32,
+        damage: f32,
+    },
+    /// Part of a ceiling that turns into the material once a player walks underneath
+    UnstableCeiling {
+        width: i32,
+        height: i32,
+        material_id: String,
+    },
+    /// Pit dug into the floor and filled with the material
+    AcidPool {
+        width: i32,
+        depth: i32,
+        material_id: String,
+    },
+}
+
+/// Emitter or sink material set into the terrain, emitters replace a pixel of a ceiling
+/// and sinks a pixel of a floor
+#[derive(Deserialize, Clone)]
+pub struct EmitterOnLevel {
+    pub material_id: String,
+    pub count: u32,
+}
+
+/// Pairs of linked teleporters, twins are placed at least the distance in pixels apart
+#[derive(Deserialize, Clone)]
+pub struct TeleporterOnLevel {
+    pub count: u32,
+    pub min_distance: f32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TrapOnLevel {
+    pub kind: TrapKind,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EnemyWave {
+    pub enemy_id: String,
+    pub count: u32,
+    pub interval: f32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ArenaEnemy {
+    pub enemy_id: String,
+    pub count: u32,
+}
+
+/// Open part of a cave that is sealed once a player walks in and opened again after
+/// the encounter spawned in it is dead, clearing it leaves a chest with the loot
+#[derive(Deserialize, Clone)]
+pub struct ArenaOnLevel {
+    /// Size of the room in pixels
+    pub size: (i32, i32),
+    pub count: u32,
+    /// Fills the openings in the border of the room while it is sealed
+    pub seal_material_id: String,
+    pub encounter: Vec<ArenaEnemy>,
+    #[serde(default)]
+    pub loot: Vec<ChestLoot>,
+}
+
+/// Hazards that are activated after the player spends `after` seconds on the level,
+/// every stage replaces the previous one
+#[derive(Deserialize, Clone)]
+pub struct EscalationStage {
+    pub after: f32,
+    pub message: String,
+
+    /// Speed of the lava floor in pixels per second
+    #[serde(default)]
+    pub lava_rise: f32,
+    #[serde(default)]
+    pub gas_vents: u32,
+    #[serde(default)]
+    pub wave: Option<EnemyWave>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Escalation {
+    pub stages: Vec<EscalationStage>,
+
+    /// Highest point of the lava floor relative to the level height
+    pub max_lava_height: f32,
+    pub max_enemies: u32,
+}
+
+/// Requirement on the share of pixels around the exit taken by a material
+#[derive(Deserialize, Clone)]
+pub enum ExitCondition {
+    /// At most `max` of the pixels are the material, barrier has to be melted or dissolved
+    Cleared {
+        material_id: String,
+        max: f32,
+    },
+    /// At least `min` of the pixels are the material, exit has to be flooded
+    Flooded {
+        material_id: String,
+        min: f32,
+    },
+}
+
+/// Exit stays closed until every condition is met, conditions are checked once per second
+#[derive(Deserialize, Clone)]
+pub struct ExitPuzzle {
+    /// Hint shown when the level starts
+    pub message: String,
+    /// Material the exit is encased in
+    #[serde(default)]
+    pub encasing: Option<String>,
+    /// Radius in pixels of the encasing and of the area the conditions are checked in
+    pub radius: i32,
+    pub conditions: Vec<ExitCondition>,
+}
+
+/// Pixels of a material falling from the top of the loaded chunks,
+/// patterns of a level follow each other in a loop
+#[derive(Deserialize, Clone)]
+pub struct WeatherPattern {
+    pub material_id: String,
+    /// Pixels spawned per second above every column of chunks at full intensity
+    pub rate: f32,
+    /// Horizontal velocity of the falling pixels in pixels per frame, negative values blow to the left
+    #[serde(default)]
+    pub wind: f32,
+    /// Seconds it takes to reach full intensity and to calm down afterwards
+    pub ramp: f32,
+    /// Seconds at full intensity
+    pub duration: f32,
+    /// Seconds of clear sky before the next pattern
+    pub pause: f32,
+}
+
+/// Tiled sprite behind the background or in front of the terrain that scrolls
+/// at its own rate as the camera moves
+#[derive(Deserialize, Clone)]
+pub struct ParallaxLayer {
+    pub texture_path: String,
+    /// Share of the camera movement the layer follows, 0 scrolls along with the terrain and 1
+    /// stays fixed on screen, foreground layers go below 0 to pass by faster than the terrain
+    pub factor: f32,
+    /// Drawn over the terrain instead of behind the background
+    #[serde(default)]
+    pub foreground: bool,
+    /// Terrain pixels covered by a pixel of the texture
+    #[serde(default = "default_parallax_scale")]
+    pub scale: f32,
+    #[serde(default = "default_parallax_opacity")]
+    pub opacity: f32,
+}
+
+fn default_parallax_scale() -> f32 {
+    1.0
+}
+
+fn default_parallax_opacity() -> f32 {
+    1.0
+}
+
+/// Darkness where the sky does not light anything, only light sources and the players themselves
+#[derive(Deserialize, Clone)]
+pub struct FogOfWar {
+    /// Radius of the light around every player in pixels
+    pub light_radius: f32,
+    /// Torches every run gets at the start of the level
+    pub torches: u32,
+    /// Share of the brightest light an area was lit with that it keeps once the light is gone
+    #[serde(default = "default_fog_memory")]
+    pub memory: f32,
+}
+
+fn default_fog_memory() -> f32 {
+    0.15
+}
+
+/// Rare events striking the level at random times, only one of them is active at once
+#[derive(Deserialize, Clone)]
+pub struct WorldEvents {
+    /// Seconds between the end of an event and the start of the next one, picked from the range
+    pub interval: (f32, f32),
+    /// Next event is picked at random from the list
+    pub kinds: Vec<WorldEventKind>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum WorldEventKind {
+    /// Shakes the camera, crumbles patches of terrain around the players into powder
+    /// and opens crevices in the floor
+    Earthquake {
+        duration: f32,
+        /// Furthest the camera is shaken in pixels
+        #[serde(default = "default_earthquake_strength")]
+        strength: f32,
+        /// Patches of terrain crumbled per second
+        #[serde(default = "default_earthquake_patches")]
+        patches: f32,
+        #[serde(default)]
+        crevices: u32,
+    },
+    /// Ceiling above a random player comes down, radius is in pixels
+    CaveIn {
+        radius: i32,
+    },
+}
+
+fn default_earthquake_strength() -> f32 {
+    3.0
+}
+
+fn default_earthquake_patches() -> f32 {
+    4.0
+}
+
+/// Limits of the multipliers the adaptive difficulty applies on the level, on top of the chosen difficulty
+#[derive(Deserialize, Clone)]
+pub struct AdaptiveBounds {
+    /// Lowest and highest multiplier of the number of enemies
+    pub density: (f32, f32),
+    /// Lowest and highest multiplier of the damage players take
+    pub damage: (f32, f32),
+    /// Seconds clearing the level is expected to take, faster clears raise the difficulty
+    pub par_time: f32,
+}
+
+impl Default for AdaptiveBounds {
+    fn default() -> Self {
+        Self {
+            density: (0.7, 1.3),
+            damage: (0.7, 1.3),
+            par_time: 300.0,
+        }
+    }
+}
+
+/// Axes the level is mirrored across, only the negative half is generated and the rest is reflected
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct Mirror {
+    /// Reflects left half of the level to the right
+    #[serde(default)]
+    pub x: bool,
+    /// Reflects bottom half of the level to the top
+    #[serde(default)]
+    pub y: bool,
+}
+
+impl Mirror {
+    pub fn is_enabled(&self) -> bool {
+        self.x || self.y
+    }
+
+    /// Pixel of the generated half that the given pixel is copied from
+    pub fn source_pixel(&self, position: IVec2) -> IVec2 {
+        let reflect = |enabled: bool, value: i32| {
+            if enabled && value >= 0 { -1 - value } else { value }
+        };
+
+        IVec2::new(reflect(self.x, position.x), reflect(self.y, position.y))
+    }
+
+    pub fn is_source(&self, point: Vec2) -> bool {
+        (!self.x || point.x < 0.0) && (!self.y || point.y < 0.0)
+    }
+
+    /// Returns the point along with all of its reflections
+    pub fn reflect(&self, point: Vec2) -> Vec<Vec2> {
+        let mut points = vec![point];
+
+        if self.x {
+            points.push(vec2(-point.x, point.y));
+        }
+
+        if self.y {
+            points.extend(
+                points
+                    .clone()
+                    .into_iter()
+                    .map(|point| vec2(point.x, -point.y))
+            );
+        }
+
+        points
+    }
+}
+
 #[derive(Deserialize, Clone)]
 pub struct Level {
     pub terrain_layers: Vec<NoiseLayer>,
     pub background_layers: Vec<NoiseLayer>,
     pub texture_path: String,
+    /// Stamp placed over the center of the level before anything else, for handcrafted levels
+    #[serde(default)]
+    pub layout: Option<String>,
     pub noise_type: NoiseType,
     pub powder_id: String,
     pub liquid_id: String,
@@ -28,4 +383,38 @@ pub struct Level {
     pub background: [f32; 3],
     pub shadow: [f32; 3],
     pub ambient: String,
+    /// Layered over the ambient track when enemies are nearby
+    #[serde(default)]
+    pub combat_music: Option<String>,
+
+    #[serde(default)]
+    pub decorations: Vec<DecorationOnLevel>,
+    #[serde(default)]
+    pub traps: Vec<TrapOnLevel>,
+    #[serde(default)]
+    pub arenas: Vec<ArenaOnLevel>,
+    #[serde(default)]
+    pub emitters: Vec<EmitterOnLevel>,
+    #[serde(default)]
+    pub teleporters: Vec<TeleporterOnLevel>,
+    #[serde(default)]
+    pub escalation: Option<Escalation>,
+    #[serde(default)]
+    pub mirror: Mirror,
+    #[serde(default)]
+    pub exit_puzzle: Option<ExitPuzzle>,
+    #[serde(default)]
+    pub weather: Vec<WeatherPattern>,
+    /// Decorative layers, later ones are drawn over earlier ones
+    #[serde(default)]
+    pub parallax: Vec<ParallaxLayer>,
+    /// Cutscene played once the level starts
+    #[serde(default)]
+    pub intro: Option<String>,
+    #[serde(default)]
+    pub fog: Option<FogOfWar>,
+    #[serde(default)]
+    pub events: Option<WorldEvents>,
+    #[serde(default)]
+    pub adaptive: AdaptiveBounds,
 }
@@ -2,10 +2,11 @@ use std::time::Duration;
 
 use benimator::FrameRate;
 use bevy::{
-    audio::PlaybackMode,
     prelude::*,
     render::{ extract_resource::{ ExtractResource, ExtractResourcePlugin }, view::RenderLayers },
+    utils::HashSet,
 };
+use bevy_math::ivec2;
 use bevy_rapier2d::{ dynamics::Velocity, geometry::{ Collider, Sensor }, plugin::RapierContext };
 use bevy_tween::{
     interpolation::EaseFunction,
@@ -15,29 +16,42 @@ use bevy_tween::{
 use itertools::Itertools;
 
 use crate::{
-    actors::{
-        actor::AttackParameters,
-        enemy::Enemy,
-        health::{ Health, KnockbackResistance },
-        player::{ InventoryParameters, Player },
-    },
+    actors::{ enemy::Enemy, player::{ Player, PlayerMaterials } },
     animation::{ Animation, AnimationState },
+    arenas::{ arena_border, spawn_arena },
     assets::{ AudioAssetCollection, LayoutAssetCollection, SpriteAssetCollection },
     camera::BACKGROUND_RENDER_LAYER,
     constants::{ CHUNK_SIZE, DECORATION_Z },
+    curses::{ Darkness, DoubleEnemies },
+    daily::RunMode,
     despawn_component,
+    difficulty::{ AdaptiveDifficulty, Difficulty },
+    exit_puzzle::ExitPuzzleState,
+    fog::FOG_SHADOW,
+    importer::Stamps,
     interpolator::{ InterpolateBackgroundColor, InterpolateSize },
+    interactables::spawn_interactable,
+    localization::Locale,
+    minimap::{ PointOfInterest, PointOfInterestKind, PointsOfInterest },
+    perks::{ perk_names, roll_perk_offers, Perk, PerkTarget },
     registries::Registries,
     remove_respurce,
+    score_export::RunStats,
+    settings::AudioBus,
     simulation::{
+        atlas::ChunkAtlas,
         chunk_groups::build_chunk_group_with_texture_access,
         chunk_manager::{ update_loaded_chunks, ChunkManager },
         dirty_rect::DirtyRects,
-        materials::PhysicsType,
+        materials::{ Material, PhysicsType },
         pixel::Pixel,
         reset_world,
     },
     state::GameState,
+    teleporters::{ find_teleporter_floor, spawn_teleporters, TeleporterRegistry },
+    traps::{ find_surface, place_trap },
+    tutorial::TutorialProgress,
+    warmup::{ warmup_finished, AtlasLayouts },
 };
 
 use self::{
@@ -66,13 +80,15 @@ pub struct GenerationPlugin;
 #[derive(Component)]
 pub struct Exit;
 
+#[allow(clippy::too_many_arguments)]
 pub fn add_exit(
     mut commands: Commands,
     mut chunk_manager: ResMut<ChunkManager>,
+    mut atlas: ResMut<ChunkAtlas>,
     mut images: ResMut<Assets<Image>>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     mut dirty_rects: ResMut<DirtyRects>,
     sprites: Res<SpriteAssetCollection>,
+    layouts: Res<AtlasLayouts>,
     level_data: Res<LevelData>,
     registries: Res<Registries>
 ) {
@@ -114,6 +130,37 @@ pub fn add_exit(
         }
     }
 
+    // puzzle exits are sealed inside a block of material that the player has to get rid of
+    if let Some(puzzle) = &level_data.0.exit_puzzle {
+        let encasing = puzzle.encasing.as_ref().and_then(|material_id| {
+            let material = registries.materials.get(material_id);
+            if material.is_none() {
+                warn!("unknown exit encasing material: {}", material_id);
+            }
+            material
+        });
+
+        if let Some(material) = encasing {
+            let encasing_radius = puzzle.radius.min(radius - 4);
+
+            for x in -encasing_radius..=encasing_radius {
+                for y in -encasing_radius..=encasing_radius {
+                    let position = IVec2::new(x, y);
+
+                    if position.length_squared() > encasing_radius.pow(2) {
+                        continue;
+                    }
+
+                    if chunk_group.set(position, Pixel::from(material)).is_ok() {
+                        dirty_rects.request_update(position);
+                        dirty_rects.request_render(position);
+                        dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+                    }
+                }
+            }
+        }
+    }
+
     for x in -radius / 2..=radius / 2 {
         for y in -radius / 2..=radius / 2 {
             let position = IVec2::new(x, y);
@@ -132,9 +179,7 @@ pub fn add_exit(
         SpriteSheetBundle {
             texture: sprites.portal.clone(),
             atlas: TextureAtlas {
-                layout: texture_atlas_layouts.add(
-                    TextureAtlasLayout::from_grid(Vec2::new(48.0, 48.0), 8, 3, None, None)
-                ),
+                layout: layouts.portal.clone(),
                 index: 0,
             },
             transform: Transform {
@@ -161,10 +206,394 @@ pub fn add_exit(
     // }
 
     (-1..=1).cartesian_product(-1..=1).for_each(|(x, y)| {
-        if let Some(chunk) = chunk_manager.get_chunk_data(&IVec2::new(x, y)) {
-            chunk.update_textures(&mut images, level_data.0.lighting);
+        if let Some(chunk) = chunk_manager.get_chunk_data_mut(&IVec2::new(x, y)) {
+            chunk.update_textures(&images, &mut atlas, level_data.0.lighting);
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn place_decorations(
+    mut commands: Commands,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut points_of_interest: ResMut<PointsOfInterest>,
+    images: Res<Assets<Image>>,
+    stamps: Res<Stamps>,
+    level_data: Res<LevelData>,
+    seed: Res<Seed>,
+    counter: Res<LevelCounter>,
+    seed_offset: Res<SeedOffset>,
+    registries: Res<Registries>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+    let mut rng = fastrand::Rng::with_seed((seed.0 + counter.0 + seed_offset.0) as u64);
+    let mirror = level_data.0.mirror;
+
+    points_of_interest.clear();
+
+    if let Some(layout) = &level_data.0.layout {
+        match stamps.get(layout) {
+            Some(stamp) => stamp.place(IVec2::ZERO, &mut chunk_manager, &mut dirty_rects),
+            None => warn!("unknown layout stamp: {}", layout),
+        }
+    }
+
+    for decoration in level_data.0.decorations.iter() {
+        let Some(stamp) = stamps.get(&decoration.stamp_id) else {
+            warn!("unknown stamp: {}", decoration.stamp_id);
+            continue;
+        };
+
+        let mut placed = 0;
+        for _ in 0..decoration.count * 32 {
+            if placed >= decoration.count {
+                break;
+            }
+
+            let position = IVec2::new(
+                rng.i32(-size.x / 2..size.x / 2),
+                rng.i32(-size.y / 2..size.y / 2)
+            );
+
+            // keep spawn area clear
+            if
+                position.length_squared() < (CHUNK_SIZE * 2).pow(2) ||
+                !mirror.is_source(position.as_vec2())
+            {
+                continue;
+            }
+
+            let ground = position - IVec2::Y * (stamp.size.y / 2 + 1);
+            if
+                chunk_manager.get(ground).map_or(true, |pixel| pixel.is_empty()) ||
+                !stamp.fits(position, &chunk_manager)
+            {
+                continue;
+            }
+
+            for position in mirror.reflect(position.as_vec2()) {
+                let position = position.as_ivec2();
+
+                if !stamp.fits(position, &chunk_manager) {
+                    continue;
+                }
+
+                stamp.place(position, &mut chunk_manager, &mut dirty_rects);
+
+                for interactable in decoration.interactables.iter() {
+                    spawn_interactable(&mut commands, interactable, position, &registries);
+                }
+
+                points_of_interest.push(PointOfInterest {
+                    position: position.as_vec2() / (CHUNK_SIZE as f32),
+                    kind: PointOfInterestKind::Shrine,
+                });
+            }
+            placed += 1;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn place_traps(
+    mut commands: Commands,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>,
+    seed: Res<Seed>,
+    counter: Res<LevelCounter>,
+    seed_offset: Res<SeedOffset>,
+    registries: Res<Registries>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+    // offset keeps traps from being rolled on the same spots as decorations
+    let mut rng = fastrand::Rng::with_seed((seed.0 + counter.0 + seed_offset.0) as u64 + 1);
+    let mirror = level_data.0.mirror;
+
+    for trap in level_data.0.traps.iter() {
+        let mut placed = 0;
+        for _ in 0..trap.count * 32 {
+            if placed >= trap.count {
+                break;
+            }
+
+            let position = IVec2::new(
+                rng.i32(-size.x / 2..size.x / 2),
+                rng.i32(-size.y / 2..size.y / 2)
+            );
+
+            // keep spawn area clear
+            if
+                position.length_squared() < (CHUNK_SIZE * 2).pow(2) ||
+                !mirror.is_source(position.as_vec2())
+            {
+                continue;
+            }
+
+            if
+                !place_trap(
+                    &mut commands,
+                    &trap.kind,
+                    position,
+                    &mut chunk_manager,
+                    &mut dirty_rects,
+                    &registries
+                )
+            {
+                continue;
+            }
+
+            for position in mirror.reflect(position.as_vec2()).into_iter().skip(1) {
+                place_trap(
+                    &mut commands,
+                    &trap.kind,
+                    position.as_ivec2(),
+                    &mut chunk_manager,
+                    &mut dirty_rects,
+                    &registries
+                );
+            }
+            placed += 1;
         }
+    }
+}
+
+/// Pairs are not mirrored, a shortcut links two spots anywhere in the level
+#[allow(clippy::too_many_arguments)]
+pub fn place_teleporters(
+    mut commands: Commands,
+    mut registry: ResMut<TeleporterRegistry>,
+    mut points_of_interest: ResMut<PointsOfInterest>,
+    chunk_manager: Res<ChunkManager>,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>,
+    seed: Res<Seed>,
+    counter: Res<LevelCounter>,
+    seed_offset: Res<SeedOffset>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+    let mut rng = fastrand::Rng::with_seed((seed.0 + counter.0 + seed_offset.0) as u64 + 4);
+
+    for teleporter in level_data.0.teleporters.iter() {
+        let mut roll_floor = || {
+            let position = IVec2::new(
+                rng.i32(-size.x / 2..size.x / 2),
+                rng.i32(-size.y / 2..size.y / 2)
+            );
+
+            // keep spawn area clear
+            if position.length_squared() < (CHUNK_SIZE * 2).pow(2) {
+                return None;
+            }
+
+            find_teleporter_floor(&chunk_manager, position)
+        };
+
+        let mut placed = 0;
+        for _ in 0..teleporter.count * 32 {
+            if placed >= teleporter.count {
+                break;
+            }
+
+            let (Some(first), Some(second)) = (roll_floor(), roll_floor()) else {
+                continue;
+            };
+
+            if
+                first.as_vec2().distance(second.as_vec2()) < teleporter.min_distance ||
+                !registry.is_clear(first) ||
+                !registry.is_clear(second)
+            {
+                continue;
+            }
+
+            spawn_teleporters(&mut commands, &mut registry, [first, second]);
+
+            for position in [first, second] {
+                points_of_interest.push(PointOfInterest {
+                    position: position.as_vec2() / (CHUNK_SIZE as f32),
+                    kind: PointOfInterestKind::Teleporter,
+                });
+            }
+            placed += 1;
+        }
+    }
+}
+
+// share of empty pixels inside an arena and in its border
+const ARENA_MIN_OPEN_SHARE: f32 = 0.6;
+const ARENA_MAX_OPENING_SHARE: f32 = 0.2;
+
+fn open_share(chunk_manager: &ChunkManager, positions: impl Iterator<Item = IVec2>) -> f32 {
+    let (open, total) = positions.fold((0, 0), |(open, total), position| {
+        let empty = chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty());
+        (open + (empty as u32), total + 1)
     });
+
+    (open as f32) / (total.max(1) as f32)
+}
+
+/// Arenas are placed over open parts of the caves that are mostly enclosed, so that sealing
+/// the openings in their border locks the room
+#[allow(clippy::too_many_arguments)]
+pub fn place_arenas(
+    mut commands: Commands,
+    mut points_of_interest: ResMut<PointsOfInterest>,
+    chunk_manager: Res<ChunkManager>,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>,
+    seed: Res<Seed>,
+    counter: Res<LevelCounter>,
+    seed_offset: Res<SeedOffset>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+    let level_bounds = IRect::from_center_size(IVec2::ZERO, size);
+    let mut rng = fastrand::Rng::with_seed((seed.0 + counter.0 + seed_offset.0) as u64 + 3);
+    let mirror = level_data.0.mirror;
+
+    let mut placed_bounds: Vec<IRect> = vec![];
+
+    for arena in level_data.0.arenas.iter() {
+        let arena_size = IVec2::from(arena.size);
+
+        let mut placed = 0;
+        for _ in 0..arena.count * 32 {
+            if placed >= arena.count {
+                break;
+            }
+
+            let position = IVec2::new(
+                rng.i32(-size.x / 2..size.x / 2),
+                rng.i32(-size.y / 2..size.y / 2)
+            );
+            let bounds = IRect::from_center_size(position, arena_size);
+
+            // keep spawn area clear
+            if
+                bounds.inset(CHUNK_SIZE * 2).contains(IVec2::ZERO) ||
+                !mirror.is_source(position.as_vec2()) ||
+                level_bounds.intersect(bounds) != bounds ||
+                placed_bounds.iter().any(|placed| !placed.intersect(bounds).is_empty())
+            {
+                continue;
+            }
+
+            let interior = bounds.inset(-1);
+            let inside = open_share(
+                &chunk_manager,
+                (interior.min.x..interior.max.x)
+                    .cartesian_product(interior.min.y..interior.max.y)
+                    .map(|(x, y)| IVec2::new(x, y))
+            );
+            let openings = open_share(&chunk_manager, arena_border(bounds));
+
+            if inside < ARENA_MIN_OPEN_SHARE || openings == 0.0 || openings > ARENA_MAX_OPENING_SHARE {
+                continue;
+            }
+
+            for position in mirror.reflect(position.as_vec2()) {
+                let bounds = IRect::from_center_size(position.as_ivec2(), arena_size);
+
+                spawn_arena(&mut commands, arena, bounds);
+                placed_bounds.push(bounds);
+
+                points_of_interest.push(PointOfInterest {
+                    position: position / (CHUNK_SIZE as f32),
+                    kind: PointOfInterestKind::Arena,
+                });
+            }
+            placed += 1;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn place_emitters(
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>,
+    seed: Res<Seed>,
+    counter: Res<LevelCounter>,
+    seed_offset: Res<SeedOffset>,
+    registries: Res<Registries>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+    let mut rng = fastrand::Rng::with_seed((seed.0 + counter.0 + seed_offset.0) as u64 + 2);
+    let mirror = level_data.0.mirror;
+
+    for emitter in level_data.0.emitters.iter() {
+        let Some(material) = registries.materials.get(&emitter.material_id) else {
+            warn!("unknown material: {}", emitter.material_id);
+            continue;
+        };
+
+        let mut placed = 0;
+        for _ in 0..emitter.count * 32 {
+            if placed >= emitter.count {
+                break;
+            }
+
+            let position = IVec2::new(
+                rng.i32(-size.x / 2..size.x / 2),
+                rng.i32(-size.y / 2..size.y / 2)
+            );
+
+            if
+                position.length_squared() < (CHUNK_SIZE * 2).pow(2) ||
+                !mirror.is_source(position.as_vec2())
+            {
+                continue;
+            }
+
+            if !place_emitter(&mut chunk_manager, &mut dirty_rects, material, position) {
+                continue;
+            }
+
+            for position in mirror.reflect(position.as_vec2()).into_iter().skip(1) {
+                place_emitter(&mut chunk_manager, &mut dirty_rects, material, position.as_ivec2());
+            }
+            placed += 1;
+        }
+    }
+}
+
+/// Replaces the terrain above an empty position for emitters and below it for sinks
+fn place_emitter(
+    chunk_manager: &mut ChunkManager,
+    dirty_rects: &mut DirtyRects,
+    material: &Material,
+    position: IVec2
+) -> bool {
+    if !chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) {
+        return false;
+    }
+
+    let direction = if material.sink.is_some() { -1 } else { 1 };
+    let Some(y) = find_surface(chunk_manager, position, direction) else {
+        return false;
+    };
+    let surface = ivec2(position.x, y);
+
+    if
+        !chunk_manager
+            .get(surface)
+            .is_ok_and(|pixel| pixel.physics_type == PhysicsType::Static)
+    {
+        return false;
+    }
+
+    if chunk_manager.set(surface, Pixel::from(material)).is_err() {
+        return false;
+    }
+
+    dirty_rects.request_update(surface);
+    dirty_rects.request_render(surface);
+    dirty_rects.collider.insert(surface.div_euclid(IVec2::splat(CHUNK_SIZE)));
+
+    true
 }
 
 pub fn remove_exit(mut commands: Commands, exit_q: Query<Entity, With<Exit>>) {
@@ -187,35 +616,44 @@ pub fn update_portal_sprite(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn move_actors_to_exit(
     mut commands: Commands,
     enemy_q: Query<Entity, With<Enemy>>,
     mut player_q: Query<(Entity, &Transform, &mut Velocity), With<Player>>,
     exit_q: Query<(Entity, &Transform, Option<&Open>), With<Exit>>,
     mut game_state: ResMut<NextState<GameState>>,
-    rapier_context: Res<RapierContext>
+    rapier_context: Res<RapierContext>,
+    puzzle: Option<Res<ExitPuzzleState>>,
+    tutorial: Option<Res<TutorialProgress>>
 ) {
     let Ok((entity, transform, open)) = exit_q.get_single() else {
         return;
     };
 
-    if !enemy_q.is_empty() {
+    if
+        !enemy_q.is_empty() ||
+        puzzle.is_some_and(|puzzle| !puzzle.solved) ||
+        tutorial.is_some_and(|tutorial| !tutorial.finished)
+    {
         return;
     } else if open.is_none() {
         commands.entity(entity).insert(Open);
     }
 
-    let (player_entity, player_transform, mut player_velocity) = player_q.single_mut();
-    if player_transform.translation.xy().distance(transform.translation.xy()) < 2.0 {
-        let delta = transform.translation.xy() - player_transform.translation.xy();
-        if delta.length() > 8.0 / (CHUNK_SIZE as f32) {
-            player_velocity.linvel +=
-                ((delta.signum() * delta.length_recip()) / (CHUNK_SIZE as f32)) * 4.0;
+    // any player reaching the exit takes everyone to the next level
+    for (player_entity, player_transform, mut player_velocity) in player_q.iter_mut() {
+        if player_transform.translation.xy().distance(transform.translation.xy()) < 2.0 {
+            let delta = transform.translation.xy() - player_transform.translation.xy();
+            if delta.length() > 8.0 / (CHUNK_SIZE as f32) {
+                player_velocity.linvel +=
+                    ((delta.signum() * delta.length_recip()) / (CHUNK_SIZE as f32)) * 4.0;
+            }
         }
-    }
 
-    if rapier_context.intersection_pair(entity, player_entity).is_some() {
-        game_state.set(GameState::LevelInitialization);
+        if rapier_context.intersection_pair(entity, player_entity).is_some() {
+            game_state.set(GameState::LevelInitialization);
+        }
     }
 }
 
@@ -228,13 +666,16 @@ struct UiSplashScreen;
 #[derive(Component)]
 pub struct LevelUpMenu;
 
+/// Offer of the level up menu, holds the id of the perk
 #[derive(Component)]
-pub enum LevelUpButton {
-    Health,
-    Damage,
-    Inventory,
-    KnockbackResistance,
-}
+pub struct LevelUpButton(pub String);
+
+/// Replaces the offers of the level up menu, removed once used
+#[derive(Component)]
+pub struct LevelUpReroll;
+
+#[derive(Component)]
+pub struct UiPerkOffers;
 
 #[derive(Component)]
 pub struct LoadingIcon;
@@ -242,11 +683,17 @@ pub struct LoadingIcon;
 #[derive(Component)]
 pub struct LoadingText;
 
+#[allow(clippy::too_many_arguments)]
 fn splash_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    counter: Res<LevelCounter>
+    sprites: Res<SpriteAssetCollection>,
+    layouts: Res<AtlasLayouts>,
+    counter: Res<LevelCounter>,
+    registries: Res<Registries>,
+    stats: Res<RunStats>,
+    locale: Res<Locale>,
+    run_mode: Res<RunMode>
 ) {
     commands
         .spawn((
@@ -264,94 +711,68 @@ fn splash_setup(
             },
         ))
         .with_children(|parent| {
-            if counter.0 != 0 {
+            // nothing is gained in the tutorial, so leaving it is not a level up
+            if counter.0 != 0 && *run_mode != RunMode::Tutorial {
                 parent
                     .spawn((
                         LevelUpMenu,
                         NodeBundle {
                             style: Style {
-                                column_gap: Val::Px(50.0),
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                row_gap: Val::Px(30.0),
                                 ..Default::default()
                             },
                             ..Default::default()
                         },
                     ))
                     .with_children(|parent| {
-                        let image = asset_server.load("ui/level_up.png");
-                        let slicer = TextureSlicer {
-                            border: BorderRect::square(17.0),
-                            center_scale_mode: SliceScaleMode::Stretch,
-                            sides_scale_mode: SliceScaleMode::Stretch,
-                            max_corner_scale: 1.0,
-                        };
-
-                        let buttons = [
-                            (LevelUpButton::Health, "+10% HP", "ui/health_up.png"),
-                            (LevelUpButton::Damage, "+1 DMG", "ui/attack_up.png"),
-                            (LevelUpButton::Inventory, "+5 INV", "ui/inventory_up.png"),
-                            (LevelUpButton::KnockbackResistance, "x1.5 KBR", "ui/defense_up.png"),
-                        ];
-                        for (button_type, text, path) in buttons {
-                            parent
-                                .spawn((
-                                    NodeBundle {
-                                        style: Style {
-                                            max_width: Val::Px(140.0),
-                                            flex_direction: FlexDirection::Column,
-                                            align_items: AlignItems::Center,
-                                            row_gap: Val::Px(20.0),
-                                            ..Default::default()
-                                        },
+                        parent
+                            .spawn((
+                                UiPerkOffers,
+                                NodeBundle {
+                                    style: Style {
+                                        column_gap: Val::Px(50.0),
                                         ..Default::default()
                                     },
-                                ))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn((
-                                            button_type,
-                                            EaseFunction::QuadraticInOut,
-                                            SpanTweenerBundle::new(Duration::from_millis(259)),
-                                            SpanTweenBundle::new(..Duration::from_millis(250)),
-                                            ButtonBundle {
-                                                style: Style {
-                                                    width: Val::Px(120.0),
-                                                    height: Val::Px(120.0),
-                                                    max_width: Val::Px(140.0),
-                                                    justify_content: JustifyContent::Center,
-                                                    align_items: AlignItems::Center,
-                                                    ..default()
-                                                },
-                                                background_color: Color::GRAY.into(),
-                                                image: image.clone().into(),
-                                                ..default()
-                                            },
-                                            ImageScaleMode::Sliced(slicer.clone()),
-                                        ))
-                                        .with_children(|parent| {
-                                            parent.spawn((
-                                                EaseFunction::QuadraticInOut,
-                                                SpanTweenerBundle::new(Duration::from_millis(259)),
-                                                SpanTweenBundle::new(..Duration::from_millis(250)),
-                                                ImageBundle {
-                                                    style: Style {
-                                                        width: Val::Percent(100.0),
-                                                        height: Val::Percent(100.0),
-                                                        ..default()
-                                                    },
-                                                    background_color: Color::GRAY.into(),
-                                                    image: asset_server.load(path).into(),
-                                                    ..default()
-                                                },
-                                            ));
-                                        });
-
-                                    parent.spawn(
-                                        TextBundle::from_section(text, TextStyle {
-                                            font_size: 28.0,
-                                            ..Default::default()
-                                        }).with_text_justify(JustifyText::Center)
-                                    );
-                                });
+                                    ..Default::default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                for perk in roll_perk_offers(&registries.perks, &stats.perks, &[]) {
+                                    spawn_perk_offer(parent, perk, &asset_server, &locale);
+                                }
+                            });
+
+                        parent
+                            .spawn((
+                                LevelUpReroll,
+                                ButtonBundle {
+                                    background_color: Color::NONE.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(
+                                    TextBundle::from_section(locale.get("perks.reroll"), TextStyle {
+                                        font_size: 24.0,
+                                        color: Color::GRAY,
+                                        ..Default::default()
+                                    })
+                                );
+                            });
+
+                        if !stats.perks.is_empty() {
+                            parent.spawn(
+                                TextBundle::from_section(
+                                    locale.format("perks.taken", &[&perk_names(&registries.perks, &stats.perks, &locale)]),
+                                    TextStyle {
+                                        font_size: 16.0,
+                                        color: Color::GRAY,
+                                        ..Default::default()
+                                    }
+                                ).with_text_justify(JustifyText::Center)
+                            );
                         }
                     });
             }
@@ -380,22 +801,14 @@ fn splash_setup(
                                 .repeat()
                         ),
                         AtlasImageBundle {
-                            image: asset_server.load("ui/loading.png").into(),
+                            image: sprites.loading.clone().into(),
                             style: Style {
                                 width: Val::Percent(100.0),
                                 margin: UiRect::bottom(Val::Px(-50.0)),
                                 ..Default::default()
                             },
                             texture_atlas: TextureAtlas {
-                                layout: texture_atlas_layouts.add(
-                                    TextureAtlasLayout::from_grid(
-                                        Vec2::new(48.0, 48.0),
-                                        6,
-                                        1,
-                                        None,
-                                        None
-                                    )
-                                ),
+                                layout: layouts.loading.clone(),
                                 index: 0,
                             },
                             ..default()
@@ -404,7 +817,7 @@ fn splash_setup(
 
                     parent.spawn((
                         LoadingText,
-                        TextBundle::from_section("Loading...", TextStyle {
+                        TextBundle::from_section(locale.get("menu.loading"), TextStyle {
                             font_size: 16.0,
                             ..Default::default()
                         }),
@@ -413,27 +826,99 @@ fn splash_setup(
         });
 }
 
+fn spawn_perk_offer(parent: &mut ChildBuilder, perk: &Perk, asset_server: &AssetServer, locale: &Locale) {
+    let slicer = TextureSlicer {
+        border: BorderRect::square(17.0),
+        center_scale_mode: SliceScaleMode::Stretch,
+        sides_scale_mode: SliceScaleMode::Stretch,
+        max_corner_scale: 1.0,
+    };
+
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    max_width: Val::Px(140.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    LevelUpButton(perk.id.clone()),
+                    EaseFunction::QuadraticInOut,
+                    SpanTweenerBundle::new(Duration::from_millis(259)),
+                    SpanTweenBundle::new(..Duration::from_millis(250)),
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(120.0),
+                            height: Val::Px(120.0),
+                            max_width: Val::Px(140.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::GRAY.into(),
+                        image: asset_server.load("ui/level_up.png").into(),
+                        ..default()
+                    },
+                    ImageScaleMode::Sliced(slicer),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        EaseFunction::QuadraticInOut,
+                        SpanTweenerBundle::new(Duration::from_millis(259)),
+                        SpanTweenBundle::new(..Duration::from_millis(250)),
+                        ImageBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::GRAY.into(),
+                            image: asset_server.load(perk.icon.clone()).into(),
+                            ..default()
+                        },
+                    ));
+                });
+
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::new(format!("{}\n", perk.localized_name(locale)), TextStyle {
+                        font_size: 28.0,
+                        color: perk.rarity.color(),
+                        ..Default::default()
+                    }),
+                    TextSection::new(perk.localized_description(locale), TextStyle {
+                        font_size: 16.0,
+                        color: Color::GRAY,
+                        ..Default::default()
+                    }),
+                ]).with_text_justify(JustifyText::Center)
+            );
+        });
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn level_up_button(
     mut commands: Commands,
-    mut player_q: Query<
-        (
-            &mut Health,
-            &mut AttackParameters,
-            &mut InventoryParameters,
-            &mut KnockbackResistance,
-        ),
-        With<Player>
-    >,
+    mut player_q: Query<PerkTarget, With<Player>>,
     button_q: Query<
         (Entity, &Style, &BackgroundColor, &LevelUpButton, &Interaction, &Children),
         (With<Button>, Changed<Interaction>)
     >,
     image_q: Query<(Entity, &BackgroundColor), Without<Button>>,
     menu_q: Query<Entity, With<LevelUpMenu>>,
-    audio_assets: Res<AudioAssetCollection>
+    audio_assets: Res<AudioAssetCollection>,
+    registries: Res<Registries>,
+    mut player_materials: ResMut<PlayerMaterials>,
+    mut stats: ResMut<RunStats>
 ) {
-    let (mut health, mut attack, mut inventory, mut knockback) =
-        player_q.single_mut();
     let Ok(menu_entity) = menu_q.get_single() else {
         return;
     };
@@ -447,26 +932,22 @@ fn level_up_button(
 
         match *interaction {
             Interaction::Pressed => {
-                commands.spawn(AudioBundle {
-                    source: audio_assets.perk.clone(),
-                    settings: PlaybackSettings::DESPAWN,
-                });
+                commands.spawn((
+                    AudioBus::Ui,
+                    AudioBundle {
+                        source: audio_assets.perk.clone(),
+                        settings: PlaybackSettings::DESPAWN,
+                    },
+                ));
 
-                match button {
-                    LevelUpButton::Health => {
-                        let change = health.total * 0.1;
-                        health.current += change;
-                        health.total += change;
-                    }
-                    LevelUpButton::Damage => {
-                        attack.value += 1.0;
-                    }
-                    LevelUpButton::Inventory => {
-                        inventory.max_storage += 5.0;
-                    }
-                    LevelUpButton::KnockbackResistance => {
-                        knockback.0 = knockback.0 * 1.5;
+                // perks are shared between players
+                if let Some(perk) = registries.perks.iter().find(|perk| perk.id == button.0) {
+                    for mut player in player_q.iter_mut() {
+                        perk.apply(&mut player);
                     }
+
+                    perk.grant_materials(&mut player_materials);
+                    stats.perks.push(perk.id.clone());
                 }
 
                 commands.entity(menu_entity).despawn_recursive();
@@ -486,10 +967,13 @@ fn level_up_button(
                 commands
                     .entity(entity)
                     .insert(SpanTweenerBundle::new(Duration::from_millis(250)))
-                    .insert(AudioBundle {
-                        source: audio_assets.button_select.clone(),
-                        settings: PlaybackSettings::REMOVE,
-                    })
+                    .insert((
+                        AudioBus::Ui,
+                        AudioBundle {
+                            source: audio_assets.button_select.clone(),
+                            settings: PlaybackSettings::REMOVE,
+                        },
+                    ))
                     .insert(
                         ComponentTween::new(InterpolateBackgroundColor {
                             start: color.0,
@@ -540,6 +1024,7 @@ pub struct SeedOffset(u32);
 #[derive(Resource)]
 pub struct LevelData(pub Level, pub Handle<Image>);
 
+#[allow(clippy::too_many_arguments)]
 pub fn next_level(
     mut commands: Commands,
     mut counter: ResMut<LevelCounter>,
@@ -547,23 +1032,53 @@ pub fn next_level(
     registries: Res<Registries>,
     layouts: ResMut<LayoutAssetCollection>,
     seed: Res<Seed>,
-    seed_offset: Res<SeedOffset>
+    seed_offset: Res<SeedOffset>,
+    difficulty: Res<Difficulty>,
+    double_enemies: Option<Res<DoubleEnemies>>,
+    adaptive: Option<Res<AdaptiveDifficulty>>,
+    darkness: Option<Res<Darkness>>,
+    run_mode: Res<RunMode>
 ) {
     counter.0 += 1;
 
-    let level = registries.levels
-        .get((counter.0 - 1).rem_euclid(registries.levels.len() as u32) as usize)
-        .unwrap();
+    let mut level = if *run_mode == RunMode::Tutorial {
+        registries.tutorial.level.clone()
+    } else {
+        registries.levels
+            .get((counter.0 - 1).rem_euclid(registries.levels.len() as u32) as usize)
+            .unwrap()
+            .clone()
+    };
+
+    if let Some(darkness) = darkness {
+        for channel in level.lighting.iter_mut().chain(level.shadow.iter_mut()) {
+            *channel *= darkness.light;
+        }
+    }
+
+    // the sky does not light anything in the fog, only the shadow keeps it from being pitch black
+    if level.fog.is_some() {
+        level.lighting = [0.0; 3];
+        level.shadow = FOG_SHADOW;
+    }
 
     let level_texture = layouts.folder.get(&level.texture_path).unwrap();
     let size = images.get(level_texture).unwrap().size().as_ivec2() / CHUNK_SIZE;
     let seed = seed.0 + counter.0 + seed_offset.0;
 
     let noise = Noise::from_seed(seed, level.noise_type);
-    let enemies = EnemyPositions::new(seed, size, level.enemies.clone());
+    let enemies = EnemyPositions::new(
+        seed,
+        size,
+        level.enemies.clone(),
+        level.mirror,
+        difficulty.enemy_density() *
+            double_enemies.map_or(1.0, |double| double.density) *
+            adaptive.map_or(1.0, |adaptive| adaptive.density(&level.adaptive))
+    );
 
     commands.insert_resource(AwaitingNearbyChunks::default());
-    commands.insert_resource(LevelData(level.clone(), level_texture.clone()));
+    commands.insert_resource(LevelData(level, level_texture.clone()));
     commands.insert_resource(noise);
     commands.insert_resource(enemies);
     commands.insert_resource(GenerationQueue::default());
@@ -600,10 +1115,20 @@ fn check_generation_tasks(
     enemy_q: Query<&Enemy>,
     icon_q: Query<Entity, With<LoadingIcon>>,
     mut seed_offset: ResMut<SeedOffset>,
-    mut text_q: Query<&mut Text, With<LoadingText>>
+    mut text_q: Query<&mut Text, With<LoadingText>>,
+    chunk_manager: Res<ChunkManager>,
+    images: Res<Assets<Image>>,
+    level_data: Res<LevelData>,
+    locale: Res<Locale>
 ) {
     if tasks_q.is_empty() && queue.is_empty() {
-        if enemy_q.is_empty() {
+        let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2();
+
+        // mirroring can wall off parts of the arena, such levels are generated again with another seed
+        if
+            (enemy_q.is_empty() && !level_data.0.enemies.is_empty()) ||
+            (level_data.0.mirror.is_enabled() && reachable_share(&chunk_manager, size) < MIN_REACHABLE_SHARE)
+        {
             counter.0 -= 1;
             seed_offset.0 += 1;
             state.set(GameState::LevelInitialization);
@@ -611,7 +1136,7 @@ fn check_generation_tasks(
         }
 
         commands.insert_resource(FinishedGeneration);
-        text_q.single_mut().sections[0].value = "Level is ready...".to_string();
+        text_q.single_mut().sections[0].value = locale.get("menu.level_ready").to_string();
 
         if let Ok(entity) = icon_q.get_single() {
             commands.entity(entity).despawn_recursive();
@@ -619,6 +1144,116 @@ fn check_generation_tasks(
     }
 }
 
+const MIN_REACHABLE_SHARE: f32 = 0.4;
+
+/// Share of open pixels of the level that can be reached from the spawn area
+fn reachable_share(chunk_manager: &ChunkManager, size: IVec2) -> f32 {
+    let bounds = IRect::from_center_size(IVec2::ZERO, size);
+
+    let is_open = |position: IVec2| {
+        if !bounds.contains(position) {
+            return false;
+        }
+
+        let chunk_position = position.div_euclid(IVec2::splat(CHUNK_SIZE));
+        let local = position.rem_euclid(IVec2::splat(CHUNK_SIZE));
+
+        chunk_manager
+            .get_chunk_data(&chunk_position)
+            .and_then(|chunk| chunk.pixels.get((local.y * CHUNK_SIZE + local.x) as usize))
+            .is_some_and(|pixel| {
+                matches!(
+                    pixel.physics_type,
+                    PhysicsType::Air | PhysicsType::Liquid(_) | PhysicsType::Gas(_)
+                )
+            })
+    };
+
+    // spawn area is cleared once the level starts, so all of it counts as reachable
+    let mut visited = HashSet::new();
+    let mut stack = vec![];
+    let radius = 28;
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let position = ivec2(x, y);
+
+            if position.length_squared() <= radius.pow(2) && visited.insert(position) {
+                stack.push(position);
+            }
+        }
+    }
+
+    while let Some(position) = stack.pop() {
+        for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let neighbour = position + offset;
+
+            if !visited.contains(&neighbour) && is_open(neighbour) {
+                visited.insert(neighbour);
+                stack.push(neighbour);
+            }
+        }
+    }
+
+    let open = (bounds.min.x..bounds.max.x)
+        .cartesian_product(bounds.min.y..bounds.max.y)
+        .filter(|(x, y)| is_open(ivec2(*x, *y)))
+        .count();
+
+    let reachable = visited
+        .iter()
+        .filter(|position| is_open(**position))
+        .count();
+
+    (reachable as f32) / (open.max(1) as f32)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn level_up_reroll(
+    mut commands: Commands,
+    button_q: Query<(Entity, &Interaction), (With<LevelUpReroll>, Changed<Interaction>)>,
+    offers_q: Query<Entity, With<UiPerkOffers>>,
+    offered_q: Query<&LevelUpButton>,
+    registries: Res<Registries>,
+    stats: Res<RunStats>,
+    asset_server: Res<AssetServer>,
+    audio_assets: Res<AudioAssetCollection>,
+    locale: Res<Locale>
+) {
+    let Ok(offers_entity) = offers_q.get_single() else {
+        return;
+    };
+
+    for (entity, interaction) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let offered = offered_q
+            .iter()
+            .map(|button| button.0.clone())
+            .collect_vec();
+
+        commands
+            .entity(offers_entity)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for perk in roll_perk_offers(&registries.perks, &stats.perks, &offered) {
+                    spawn_perk_offer(parent, perk, &asset_server, &locale);
+                }
+            });
+
+        commands.entity(entity).despawn_recursive();
+        commands.spawn((
+            AudioBus::Ui,
+            AudioBundle {
+                source: audio_assets.button_select.clone(),
+                settings: PlaybackSettings::DESPAWN,
+            },
+        ));
+    }
+}
+
 fn switch_to_game(
     mut state: ResMut<NextState<GameState>>,
     menu_q: Query<Entity, With<LevelUpMenu>>
@@ -628,7 +1263,7 @@ fn switch_to_game(
     }
 }
 
-fn reset_generation(mut commands: Commands) {
+pub fn reset_generation(mut commands: Commands) {
     commands.insert_resource(Seed::new());
     commands.insert_resource(LevelCounter::default());
     commands.insert_resource(SeedOffset::default());
@@ -660,11 +1295,7 @@ impl Plugin for GenerationPlugin {
             )
             .add_systems(
                 OnEnter(GameState::Game),
-                move |
-                    mut commands: Commands,
-                    level: Res<LevelData>,
-                    asset_server: Res<AssetServer>
-                | {
+                move |mut commands: Commands, level: Res<LevelData>| {
                     commands.insert_resource(
                         ShadowColor(
                             Color::rgb_from_array([
@@ -675,18 +1306,6 @@ impl Plugin for GenerationPlugin {
                         )
                     );
 
-                    commands.spawn((
-                        Ambient,
-                        AudioBundle {
-                            source: asset_server.load(level.0.ambient.clone()),
-                            settings: PlaybackSettings {
-                                mode: PlaybackMode::Loop,
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        },
-                    ));
-
                     commands.insert_resource(
                         ClearColor(
                             Color::rgb(
@@ -716,7 +1335,7 @@ impl Plugin for GenerationPlugin {
             ))
             .add_systems(
                 Update,
-                level_up_button.run_if(
+                (level_up_button, level_up_reroll).run_if(
                     in_state(GameState::Splash).and_then(not(resource_exists::<ChoseLevelUp>))
                 )
             )
@@ -730,7 +1349,9 @@ impl Plugin for GenerationPlugin {
                 PostUpdate,
                 (
                     check_generation_tasks.run_if(not(resource_exists::<FinishedGeneration>)),
-                    switch_to_game.run_if(resource_exists::<FinishedGeneration>),
+                    switch_to_game.run_if(
+                        resource_exists::<FinishedGeneration>.and_then(warmup_finished)
+                    ),
                 )
                     .chain()
                     .run_if(in_state(GameState::Splash))
@@ -747,7 +1368,7 @@ impl Plugin for GenerationPlugin {
                     .after(update_loaded_chunks)
                     .run_if(in_state(GameState::Game))
             )
-            .add_systems(OnExit(GameState::Splash), add_exit)
+            .add_systems(OnExit(GameState::Splash), (place_decorations, place_traps, place_emitters, place_arenas, place_teleporters, add_exit).chain())
             .add_systems(
                 PreUpdate,
                 (move_actors_to_exit, update_portal_sprite)
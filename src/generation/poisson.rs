@@ -2,13 +2,14 @@ use bevy::{prelude::*, utils::HashMap};
 use fast_poisson::Poisson2D;
 use rand::{Rng, SeedableRng};
 
-use super::level::EnemyOnLevel;
+use super::level::{ EnemyOnLevel, Mirror };
 
 #[derive(Resource, Deref, DerefMut)]
 pub struct EnemyPositions(pub HashMap<IVec2, Vec<(String, Vec2)>>);
 
 impl EnemyPositions {
-    pub fn new(seed: u32, size: IVec2, enemies: Vec<EnemyOnLevel>) -> Self {
+    /// Density multiplies the number of placed enemies, so the distance between them shrinks with its square root
+    pub fn new(seed: u32, size: IVec2, enemies: Vec<EnemyOnLevel>, mirror: Mirror, density: f32) -> Self {
         let mut map = HashMap::new();
         let mut seed = seed;
 
@@ -16,7 +17,7 @@ impl EnemyPositions {
             seed += 1;
             let poisson = Poisson2D::new()
                 .with_seed(seed as u64)
-                .with_dimensions([size.x as f64, size.y as f64], (1.0 / enemy_type.frequency) as f64
+                .with_dimensions([size.x as f64, size.y as f64], (1.0 / (enemy_type.frequency * density.sqrt())) as f64
             );
             
             let mut probability_rng = rand::rngs::SmallRng::seed_from_u64(seed as u64);
@@ -27,7 +28,15 @@ impl EnemyPositions {
                 }
 
                 let point = Vec2::new(point[0] as f32, point[1] as f32) - size.as_vec2() / 2.0;
-                map.entry(point.floor().as_ivec2()).or_insert(Vec::new()).push((enemy_type.enemy_id.clone(), point));
+
+                // on mirrored levels enemies of the generated half are reflected, so placements stay symmetric
+                if !mirror.is_source(point) {
+                    continue;
+                }
+
+                for point in mirror.reflect(point) {
+                    map.entry(point.floor().as_ivec2()).or_insert(Vec::new()).push((enemy_type.enemy_id.clone(), point));
+                }
             }
         }
 
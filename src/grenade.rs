@@ -0,0 +1,317 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_math::ivec2;
+use bevy_rapier2d::prelude::*;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::{
+        actor::Actor,
+        effects::Death,
+        enemy::Enemy,
+        health::{ DamageCause, DamageEvent, DamageType },
+        player::{ CursorPosition, Player, PlayerActions },
+    },
+    bucket::Carrying,
+    constants::CHUNK_SIZE,
+    despawn_component,
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        object::{
+            process_explosions,
+            ExplosionEvent,
+            ExplosionParameters,
+            Object,
+            ObjectBundle,
+        },
+        pixel::Pixel,
+    },
+    state::GameState,
+};
+
+const GRENADE_MATERIAL: &str = "granite";
+// objects with fewer than 32 pixels are removed once they lose any, so the grenade is kept above that
+const GRENADE_SIZE: i32 = 8;
+// fuse starts burning once the throw key is pressed, holding it for too long blows the grenade up in hand
+const GRENADE_FUSE: Duration = Duration::from_secs(3);
+const GRENADE_COOLDOWN: Duration = Duration::from_secs(2);
+// time it takes to charge a throw to full strength
+const CHARGE_DURATION: f32 = 1.0;
+const MIN_THROW_SPEED: f32 = 0.75;
+const MAX_THROW_SPEED: f32 = 2.5;
+// distance in pixels from the center of the player the grenade is released at
+const THROW_OFFSET: f32 = 12.0;
+const GRENADE_RESTITUTION: f32 = 0.4;
+const GRENADE_EXPLOSION: ExplosionParameters = ExplosionParameters {
+    radius: 16.0,
+    damage: 20.0,
+    force: 4.0,
+};
+
+// preview simulates the throw with fixed steps until it hits the terrain
+const PREVIEW_STEP: f32 = 1.0 / 58.0;
+const PREVIEW_STEPS: usize = 120;
+// every few steps a dot of the arc is drawn
+const PREVIEW_DOT_INTERVAL: usize = 4;
+const PREVIEW_DOT_RADIUS: f32 = 0.75;
+const PREVIEW_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+
+pub struct GrenadePlugin;
+
+impl Plugin for GrenadePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::LevelInitialization), (
+            despawn_component::<Grenade>,
+            reset_throws,
+        ))
+            .add_systems(
+                FixedUpdate,
+                (cook_grenades, update_grenades)
+                    .after(PhysicsSet::Writeback)
+                    .before(process_explosions)
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(PostUpdate, draw_throw_preview.run_if(in_state(GameState::Game)));
+    }
+}
+
+fn reset_throws(mut commands: Commands, player_q: Query<Entity, With<Player>>) {
+    for entity in player_q.iter() {
+        commands.entity(entity).remove::<(Cooking, GrenadeCooldown)>();
+    }
+}
+
+/// Grenade held by the player while the throw is charged, the fuse is already burning
+#[derive(Component)]
+pub struct Cooking {
+    pub fuse: Timer,
+    /// Part of the full throw strength, from 0 to 1
+    pub charge: f32,
+}
+
+#[derive(Component)]
+pub struct GrenadeCooldown(pub Timer);
+
+/// Thrown grenade, explodes once the fuse burns out or as soon as it touches an enemy
+#[derive(Component)]
+pub struct Grenade {
+    pub fuse: Timer,
+}
+
+fn grenade_pixels(registries: &Registries) -> Vec<Option<Pixel>> {
+    let material = &registries.materials[GRENADE_MATERIAL];
+    let radius = (GRENADE_SIZE as f32) / 2.0;
+
+    (0..GRENADE_SIZE)
+        .cartesian_product(0..GRENADE_SIZE)
+        .map(|(y, x)| {
+            let offset = ivec2(x, y).as_vec2() + 0.5 - radius;
+            (offset.length_squared() <= radius.powi(2)).then(|| Pixel::from(material))
+        })
+        .collect_vec()
+}
+
+/// Velocity of a throw in the aimed direction, inherits a part of the velocity of the player
+fn throw_velocity(charge: f32, direction: Vec2, player_velocity: Vec2) -> Vec2 {
+    direction * (MIN_THROW_SPEED + (MAX_THROW_SPEED - MIN_THROW_SPEED) * charge) +
+        player_velocity / 16.0
+}
+
+/// Pressing the throw key starts the fuse, releasing it throws the grenade with the charged strength
+#[allow(clippy::type_complexity)]
+fn cook_grenades(
+    mut commands: Commands,
+    mut player_q: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &ActionState<PlayerActions>,
+            Option<&CursorPosition>,
+            Option<&mut Cooking>,
+            Option<&mut GrenadeCooldown>,
+        ),
+        (With<Player>, Without<Carrying>)
+    >,
+    mut explosion_ev: EventWriter<ExplosionEvent>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    for (
+        entity,
+        transform,
+        velocity,
+        action_state,
+        cursor_position,
+        cooking,
+        cooldown,
+    ) in player_q.iter_mut() {
+        if let Some(mut cooldown) = cooldown {
+            if cooldown.0.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<GrenadeCooldown>();
+            }
+            continue;
+        }
+
+        let pressed = action_state.pressed(&PlayerActions::Throw);
+
+        let Some(mut cooking) = cooking else {
+            if pressed {
+                commands.entity(entity).insert(Cooking {
+                    fuse: Timer::new(GRENADE_FUSE, TimerMode::Once),
+                    charge: 0.0,
+                });
+            }
+            continue;
+        };
+
+        cooking.fuse.tick(time.delta());
+        cooking.charge = (cooking.charge + time.delta_seconds() / CHARGE_DURATION).min(1.0);
+
+        if cooking.fuse.finished() {
+            explosion_ev.send(ExplosionEvent {
+                position: transform.translation.xy(),
+                parameters: GRENADE_EXPLOSION,
+            });
+            // explosions only push enemies away, the player holding the grenade is hurt separately
+            damage_ev.send(DamageEvent {
+                target: entity,
+                value: GRENADE_EXPLOSION.damage,
+                knockback: Vec2::ZERO,
+                ignore_iframes: true,
+                play_sound: true,
+                cause: DamageCause::Explosion,
+                damage_type: DamageType::Physical,
+            });
+        } else if !pressed {
+            let direction = cursor_position.map_or(Vec2::Y, |cursor_position| {
+                cursor_position.direction
+            });
+
+            spawn_grenade(
+                &mut commands,
+                transform.translation.xy() + (direction * THROW_OFFSET) / (CHUNK_SIZE as f32),
+                throw_velocity(cooking.charge, direction, velocity.linvel),
+                cooking.fuse.remaining(),
+                &registries
+            );
+        } else {
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .remove::<Cooking>()
+            .insert(GrenadeCooldown(Timer::new(GRENADE_COOLDOWN, TimerMode::Once)));
+    }
+}
+
+fn spawn_grenade(
+    commands: &mut Commands,
+    position: Vec2,
+    velocity: Vec2,
+    fuse: Duration,
+    registries: &Registries
+) {
+    let Ok(object) = Object::from_pixels(grenade_pixels(registries), IVec2::splat(GRENADE_SIZE)) else {
+        return;
+    };
+    let Ok(collider) = object.create_collider() else {
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Grenade"),
+        Grenade {
+            fuse: Timer::new(fuse, TimerMode::Once),
+        },
+        ObjectBundle {
+            object,
+            collider,
+            transform: TransformBundle {
+                local: Transform::from_translation(position.extend(0.0)),
+                ..Default::default()
+            },
+            velocity: Velocity::linear(velocity),
+            mass_properties: ColliderMassProperties::Density(4.0),
+            ..Default::default()
+        },
+        Restitution::coefficient(GRENADE_RESTITUTION),
+    ));
+}
+
+fn update_grenades(
+    mut commands: Commands,
+    mut grenade_q: Query<(Entity, &Transform, &mut Grenade, &Object, &mut Sleeping)>,
+    enemy_q: Query<&Actor, (With<Enemy>, Without<Death>)>,
+    mut explosion_ev: EventWriter<ExplosionEvent>,
+    time: Res<Time>
+) {
+    for (entity, transform, mut grenade, object, mut sleeping) in grenade_q.iter_mut() {
+        let position = transform.translation.xy() * (CHUNK_SIZE as f32);
+        let radius = (GRENADE_SIZE as f32) / 2.0;
+
+        let touched_enemy = enemy_q.iter().any(|actor| {
+            let closest = position.clamp(actor.position, actor.position + actor.size);
+            closest.distance(position) <= radius
+        });
+
+        if !grenade.fuse.tick(time.delta()).finished() && !touched_enemy {
+            continue;
+        }
+
+        // pixels of a resting grenade are left in the world, it explodes once they are taken out
+        if object.placed {
+            sleeping.sleeping = false;
+            continue;
+        }
+
+        explosion_ev.send(ExplosionEvent {
+            position: transform.translation.xy(),
+            parameters: GRENADE_EXPLOSION,
+        });
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Dotted arc of the throw that would be made if the key was released now
+#[allow(clippy::type_complexity)]
+fn draw_throw_preview(
+    mut gizmos: Gizmos,
+    player_q: Query<(&Transform, &Velocity, &Cooking, Option<&CursorPosition>), With<Player>>,
+    chunk_manager: Res<ChunkManager>,
+    rapier_config: Res<RapierConfiguration>
+) {
+    for (transform, velocity, cooking, cursor_position) in player_q.iter() {
+        let direction = cursor_position.map_or(Vec2::Y, |cursor_position| cursor_position.direction);
+
+        let mut position =
+            transform.translation.xy() + (direction * THROW_OFFSET) / (CHUNK_SIZE as f32);
+        let mut velocity = throw_velocity(cooking.charge, direction, velocity.linvel);
+
+        for step in 0..PREVIEW_STEPS {
+            velocity += rapier_config.gravity * PREVIEW_STEP;
+            position += velocity * PREVIEW_STEP;
+
+            let hit = !chunk_manager
+                .get((position * (CHUNK_SIZE as f32)).round().as_ivec2())
+                .is_ok_and(|pixel| pixel.is_empty());
+
+            if hit {
+                break;
+            }
+
+            if step % PREVIEW_DOT_INTERVAL == 0 {
+                gizmos.circle_2d(
+                    position,
+                    PREVIEW_DOT_RADIUS / (CHUNK_SIZE as f32),
+                    PREVIEW_COLOR
+                );
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
 
 
 use bevy_egui::{
@@ -10,41 +10,77 @@ use bevy_egui::{
 use bevy::{
     a11y::{ accesskit::{ NodeBuilder, Role }, AccessibilityNode },
     app::AppExit,
-    audio::{ PlaybackMode, Volume },
+    audio::PlaybackMode,
     diagnostic::{ DiagnosticsStore, FrameTimeDiagnosticsPlugin },
     input::mouse::{ MouseScrollUnit, MouseWheel },
     prelude::*,
+    ui::UiSystem,
+    utils::HashMap,
     window::{ PresentMode, PrimaryWindow, WindowMode },
 };
 use bevy_math::{ ivec2, vec2 };
 use bevy_persistent::Persistent;
-use bevy_rapier2d::geometry::ColliderMassProperties;
 use bevy_tween::{
     interpolation::EaseFunction,
     span_tween::{ SpanTweenBundle, SpanTweenerBundle },
     tween::{ ComponentTween, TargetComponent },
 };
 use itertools::Itertools;
+use leafwing_input_manager::buttonlike::MouseWheelDirection;
 
 use crate::{
-    actors::{ health::Health, player::{ InventoryParameters, Player, PlayerMaterials, PlayerSelectedMaterial } }, assets::{
+    actors::{ effects::Breath, health::{ DeathCause, Health }, player::{ InventoryParameters, Player, PlayerActions, PlayerMaterials, PlayerSelectedMaterial } }, assets::{
         process_assets,
         AudioAssetCollection,
-        FontAssetCollection,
-        FontBytes,
         SpriteAssetCollection,
-    }, camera::TrackingCamera, constants::CHUNK_SIZE, despawn_component, fade_out_audio, generation::LevelCounter, has_window, interpolator::{InterpolateBackgroundColor, InterpolatePadding, InterpolateTextColor, InterpolateTopOffset}, painter::{ BrushRes, BrushShape, BrushType, PainterObjectBuffer }, registries::Registries, settings::{ Config, Scoreboard }, simulation::{
+    }, budget::PerformanceCounters, camera::{ TrackingCamera, WorldRenderTarget }, constants::CHUNK_SIZE, curses::{ apply_curse_bonus, Curse, SelectedCurses }, daily::{ DailyDate, DailyRecord, RetrySeed, RunMode }, despawn_component, difficulty::Difficulty, fade_out_audio, generation::{ noise::Seed, LevelCounter }, has_window, importer::Stamps, localization::{ Languages, Locale }, object_inventory::ReleaseObject, interpolator::{InterpolateBackgroundColor, InterpolatePadding, InterpolateTextColor, InterpolateTopOffset}, painter::{ BrushLayer, BrushRes, BrushShape, BrushType, PainterObjectBuffer }, perks::{ perk_names, Perk }, profiler::{ ProfilerOverlay, Stage, StageTimings }, progression::Requirement, registries::Registries, score_export::{ record_run, RunRecord, RunStats }, settings::{ apply_window_config, default_controls, process_config, switch_profile, update_audio_volumes, AudioBus, BaseVolume, Binding, ColorPalette, Config, LightingQuality, Profile, ProfileIndex, RetroPalette, Scoreboard, AUDIO_BUSES }, simulation::{
         chunk_manager::ChunkManager,
         materials::Material,
-        object::{ get_object_by_click, Object, ObjectBundle },
-    }, state::GameState
+        object::{ get_object_by_click, Object },
+    }, state::GameState, thumbnails::Thumbnails
 };
 
+pub mod widgets;
+
+use widgets::{
+    drag_sliders,
+    spawn_cycle_button,
+    spawn_slider,
+    spawn_toggle,
+    step_sliders,
+    toggle_text,
+    update_slider_fills,
+    Slider,
+};
+
+// time after a kill for the next one to extend the combo
+const COMBO_WINDOW: Duration = Duration::from_secs(4);
+// multiplier gained by every kill of a combo after the first
+const COMBO_STEP: f32 = 0.25;
+const MAX_COMBO_MULTIPLIER: f32 = 4.0;
+// levels cleared faster than this grant a point per second left, scaled by the difficulty
+const SPEED_BONUS_PAR: f32 = 180.0;
+
 pub struct GuiPlugin;
 #[derive(Resource)]
 pub struct Score {
     pub value: i32,
     pub timer: Timer,
+    /// Kills in a row, each within the combo window of the previous one
+    pub combo: u32,
+    pub combo_timer: Timer,
+    pub breakdown: ScoreBreakdown,
+}
+
+/// Sources of the score of the run, shown on the game over screen
+#[derive(Default, Clone, Copy)]
+pub struct ScoreBreakdown {
+    pub kills: i32,
+    pub combo: i32,
+    pub speed: i32,
+    pub decay: i32,
+    /// Added by the curses of the run once it is over
+    pub curses: i32,
 }
 
 impl Default for Score {
@@ -52,41 +88,100 @@ impl Default for Score {
         Self {
             value: 0,
             timer: Timer::new(Duration::from_secs(2), TimerMode::Repeating),
+            combo: 0,
+            combo_timer: Timer::new(COMBO_WINDOW, TimerMode::Once),
+            breakdown: ScoreBreakdown::default(),
         }
     }
 }
 
-fn write_score(score: Res<Score>, level: Res<LevelCounter>, mut scoreboard: ResMut<Persistent<Scoreboard>>) {
-    scoreboard.scores.push((level.0 as i32, score.value));
+impl Score {
+    pub fn multiplier(&self) -> f32 {
+        (1.0 + (self.combo.saturating_sub(1) as f32) * COMBO_STEP).min(MAX_COMBO_MULTIPLIER)
+    }
+
+    /// Extends the combo, points of the kill are multiplied along with it
+    pub fn add_kill(&mut self, points: i32) {
+        self.combo += 1;
+        self.combo_timer.reset();
+
+        let total = ((points as f32) * self.multiplier()).round() as i32;
+
+        self.breakdown.kills += points;
+        self.breakdown.combo += total - points;
+        self.value += total;
+    }
+
+    pub fn break_combo(&mut self) {
+        self.combo = 0;
+    }
+}
+
+fn write_score(
+    score: Res<Score>,
+    level: Res<LevelCounter>,
+    run_mode: Res<RunMode>,
+    mut scoreboard: ResMut<Persistent<Scoreboard>>,
+    mut profile: ResMut<Persistent<Profile>>
+) {
+    // daily runs are only compared between each other
+    match *run_mode {
+        RunMode::Normal => scoreboard.scores.push((level.0 as i32, score.value)),
+        RunMode::Daily(date) =>
+            scoreboard.daily.push(DailyRecord {
+                date,
+                seed: date.seed(),
+                level: level.0,
+                score: score.value,
+            }),
+        RunMode::Tutorial => {}
+    }
+    scoreboard.prune();
     scoreboard.persist().expect("failed to update scoreboard");
+
+    // kills and collected materials of the run are saved along with the score
+    profile.best_score = profile.best_score.max(score.value);
+    profile.persist().expect("failed to update profile");
 }
 
 impl Plugin for GuiPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<MenuState>()
             .init_resource::<Inventory>()
+            .init_resource::<MenuFocus>()
             .add_systems(OnExit(GameState::LoadingAssets), setup_egui.after(process_assets))
             .add_systems(OnEnter(GameState::Game), setup_in_game_interface)
             .add_systems(OnExit(GameState::Game), despawn_component::<UiBars>)
             .add_systems(OnEnter(GameState::GameOver), (
                 despawn_component::<UiHealthBar>,
                 despawn_component::<UiMaterials>,
-                game_over_splash,
-                write_score
+                game_over_splash.after(record_run),
+                write_score.after(apply_curse_bonus),
+                // keys held when the run ended are not typed into the name
+                |mut character_events: ResMut<Events<ReceivedCharacter>>| character_events.clear(),
+            ))
+            .add_systems(OnExit(GameState::GameOver), (
+                despawn_component::<UiGameOver>,
+                |scoreboard: Res<Persistent<Scoreboard>>| {
+                    scoreboard.persist().expect("failed to update scoreboard");
+                },
             ))
-            .add_systems(OnExit(GameState::GameOver), despawn_component::<UiGameOver>)
             .add_systems(OnEnter(GameState::Setup), move |mut commands: Commands|
                 commands.insert_resource(Score::default())
             )
             .add_systems(Update, tick_score.run_if(in_state(GameState::Game)))
-            .add_systems(Update, game_over_button.run_if(in_state(GameState::GameOver)))
+            .add_systems(
+                OnTransition { from: GameState::Game, to: GameState::LevelInitialization },
+                award_speed_bonus
+            )
+            .add_systems(Update, (game_over_button, edit_run_name).run_if(in_state(GameState::GameOver)))
             .add_systems(
                 Update,
                 (
                     ui_info_system,
                     // ui_selected_cell_system,
                     ui_painter_system,
-                    // ui_inventory_system,
+                    ui_inventory_system,
                     get_object_by_click,
                 )
                     .run_if(has_window)
@@ -95,33 +190,108 @@ impl Plugin for GuiPlugin {
             )
             .add_systems(
                 Update,
-                (synchonize_health_value, synchonize_materials).run_if(in_state(GameState::Game))
+                (
+                    synchonize_health_value,
+                    synchonize_air_value,
+                    synchonize_materials,
+                    synchonize_combo_meter,
+                ).run_if(in_state(GameState::Game))
             )
             .add_systems(
                 Update,
                 (
                     button_style_system,
                     menu_action,
+                    update_difficulty_text.after(menu_action),
+                    update_curse_text.after(menu_action),
                     mouse_scroll,
                     button_next_option_scroll,
+                    capture_binding.before(button_next_option),
                     button_next_option,
+                    (drag_sliders, step_sliders, update_slider_fills)
+                        .chain()
+                        .after(button_next_option)
+                        .after(button_next_option_scroll),
                 ).run_if(in_state(GameState::Menu))
             )
+            .add_systems(
+                PreUpdate,
+                gamepad_menu_navigation
+                    .after(UiSystem::Focus)
+                    .run_if(not(in_state(GameState::Game)))
+            )
             .add_systems(OnEnter(GameState::Menu), setup_menu)
             .add_systems(OnExit(GameState::Menu), fade_out_audio::<UiTrack>)
             .add_systems(OnEnter(MenuState::Main), setup_main_menu)
             .add_systems(OnExit(MenuState::Main), despawn_component::<UiMainMenu>)
             .add_systems(OnEnter(MenuState::Settings), setup_settings)
-            .add_systems(OnExit(MenuState::Settings), despawn_component::<UiSettings>);
+            .add_systems(OnExit(MenuState::Settings), despawn_component::<UiSettings>)
+            .add_systems(OnEnter(MenuState::Progress), setup_progress)
+            .add_systems(OnExit(MenuState::Progress), despawn_component::<UiProgress>)
+            .add_systems(OnEnter(MenuState::Curses), setup_curses)
+            .add_systems(OnExit(MenuState::Curses), despawn_component::<UiCurses>)
+            .add_systems(OnEnter(MenuState::Statistics), setup_statistics)
+            .add_systems(OnExit(MenuState::Statistics), despawn_component::<UiStatistics>)
+            .init_resource::<ScoreboardScreen>()
+            .add_systems(OnEnter(MenuState::Scoreboard), setup_scoreboard)
+            .add_systems(OnExit(MenuState::Scoreboard), (
+                despawn_component::<UiScoreboard>,
+                |mut thumbnails: ResMut<Thumbnails>| thumbnails.clear(),
+            ))
+            .add_systems(
+                Update,
+                (
+                    scoreboard_action,
+                    setup_scoreboard.run_if(resource_changed::<ScoreboardScreen>),
+                    show_scoreboard_thumbnail,
+                )
+                    .chain()
+                    .run_if(in_state(MenuState::Scoreboard))
+            )
+            .init_resource::<ProfileScreen>()
+            .add_systems(OnEnter(MenuState::Profiles), setup_profiles)
+            .add_systems(OnExit(MenuState::Profiles), (
+                despawn_component::<UiProfiles>,
+                |mut screen: ResMut<ProfileScreen>| *screen = ProfileScreen::default(),
+                process_config,
+            ))
+            .add_systems(
+                Update,
+                (
+                    profile_action,
+                    edit_profile_name,
+                    setup_profiles.run_if(resource_changed::<ProfileScreen>),
+                )
+                    .chain()
+                    .run_if(in_state(MenuState::Profiles))
+            );
     }
 }
 
 pub fn tick_score(mut score: ResMut<Score>, time: Res<Time>) {
     score.timer.tick(time.delta());
     if score.timer.finished() {
-        score.value = i32::max(score.value - 1, 0);
+        if score.value > 0 {
+            score.value -= 1;
+            score.breakdown.decay += 1;
+        }
         score.timer.reset();
     }
+
+    if score.combo > 0 && score.combo_timer.tick(time.delta()).finished() {
+        score.break_combo();
+    }
+}
+
+fn award_speed_bonus(mut score: ResMut<Score>, stats: Res<RunStats>, difficulty: Res<Difficulty>) {
+    let Some(time) = stats.level_times.last() else {
+        return;
+    };
+
+    let bonus = ((SPEED_BONUS_PAR - time).max(0.0) * difficulty.score_multiplier()).round() as i32;
+
+    score.breakdown.speed += bonus;
+    score.value += bonus;
 }
 
 fn button_style_system(
@@ -142,10 +312,13 @@ fn button_style_system(
 
         match *interaction {
             Interaction::Pressed => {
-                commands.spawn(AudioBundle {
-                    source: audio_assets.button_click.clone(),
-                    settings: PlaybackSettings::DESPAWN,
-                });
+                commands.spawn((
+                    AudioBus::Ui,
+                    AudioBundle {
+                        source: audio_assets.button_click.clone(),
+                        settings: PlaybackSettings::DESPAWN,
+                    },
+                ));
             }
             Interaction::Hovered => {
                 commands
@@ -168,10 +341,13 @@ fn button_style_system(
                 commands
                     .entity(entity)
                     .insert(SpanTweenerBundle::new(Duration::from_millis(250)))
-                    .insert(AudioBundle {
-                        source: audio_assets.button_select.clone(),
-                        settings: PlaybackSettings::REMOVE,
-                    })
+                    .insert((
+                        AudioBus::Ui,
+                        AudioBundle {
+                            source: audio_assets.button_select.clone(),
+                            settings: PlaybackSettings::REMOVE,
+                        },
+                    ))
                     .insert(
                         ComponentTween::new(InterpolatePadding {
                             start: [offset, 0.0, 0.0, 0.0],
@@ -261,6 +437,23 @@ pub struct UiBars;
 #[derive(Component)]
 pub struct UiHealthBar;
 
+/// Breath meter under the health bar, only shown while a player is short of air
+#[derive(Component)]
+pub struct UiAirBar;
+
+#[derive(Component)]
+pub struct UiAirValue;
+
+/// Multiplier of the current combo with the time left to extend it, hidden without one
+#[derive(Component)]
+pub struct UiComboMeter;
+
+#[derive(Component)]
+pub struct UiComboText;
+
+#[derive(Component)]
+pub struct UiComboValue;
+
 #[derive(Component)]
 pub struct UiMaterials;
 
@@ -272,16 +465,23 @@ fn synchonize_materials(
     mut style_q: Query<(&mut Style, &mut BackgroundColor), With<UiMaterials>>
 ) {
     let (mut style, mut color) = style_q.single_mut();
-    let Ok(inventory) = inventory_q.get_single() else {
+    // inventory is shared, so the roomiest storage among players is shown
+    let Some(max_storage) = inventory_q
+        .iter()
+        .map(|inventory| inventory.max_storage)
+        .reduce(f32::max) else {
         return;
     };
 
     if selected_material.is_changed() || stored_materials.is_changed() {
-        let id = selected_material.0.clone();
-        let value = *stored_materials.entry(selected_material.0.clone()).or_insert(0.0) / inventory.max_storage * 100.0;
+        let material = registries.materials.get(&selected_material.0).unwrap();
+        let value =
+            (*stored_materials.entry(selected_material.0.clone()).or_insert(0.0) /
+                material.capacity(max_storage)) *
+            100.0;
 
         style.height = Val::Percent(value.clamp(0.0, 100.0));
-        let material_color = registries.materials.get(&id).unwrap().color;
+        let material_color = material.color;
         color.0 = Color::rgba_u8(
             material_color[0],
             material_color[1],
@@ -295,10 +495,60 @@ fn synchonize_health_value(
     player_q: Query<&Health, With<Player>>,
     mut health_bar: Query<&mut Style, With<UiHealthBar>>
 ) {
-    let health = player_q.single();
+    // in co-op the bar follows the most wounded player, since losing either ends the run
+    let Some(fraction) = player_q
+        .iter()
+        .map(|health| health.current.max(0.0) / health.total)
+        .reduce(f32::min) else {
+        return;
+    };
     let mut style = health_bar.single_mut();
 
-    style.width = Val::Percent((health.current.max(0.0) / health.total) * 100.0);
+    style.width = Val::Percent(fraction * 100.0);
+}
+
+fn synchonize_air_value(
+    player_q: Query<&Breath, With<Player>>,
+    mut air_bar_q: Query<&mut Visibility, With<UiAirBar>>,
+    mut air_value_q: Query<&mut Style, With<UiAirValue>>
+) {
+    let Some(fraction) = player_q
+        .iter()
+        .map(|breath| breath.air / breath.capacity)
+        .reduce(f32::min) else {
+        return;
+    };
+
+    let (Ok(mut visibility), Ok(mut style)) = (air_bar_q.get_single_mut(), air_value_q.get_single_mut()) else {
+        return;
+    };
+
+    *visibility = if fraction < 1.0 { Visibility::Inherited } else { Visibility::Hidden };
+    style.width = Val::Percent(fraction.max(0.0) * 100.0);
+}
+
+fn synchonize_combo_meter(
+    score: Res<Score>,
+    mut meter_q: Query<&mut Visibility, With<UiComboMeter>>,
+    mut text_q: Query<&mut Text, With<UiComboText>>,
+    mut value_q: Query<&mut Style, With<UiComboValue>>
+) {
+    let (Ok(mut visibility), Ok(mut text), Ok(mut style)) = (
+        meter_q.get_single_mut(),
+        text_q.get_single_mut(),
+        value_q.get_single_mut(),
+    ) else {
+        return;
+    };
+
+    if score.combo < 2 {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    }
+
+    visibility.set_if_neq(Visibility::Inherited);
+    text.sections[0].value = format!("combo {}  x{:.2}", score.combo, score.multiplier());
+    style.width = Val::Percent(score.combo_timer.fraction_remaining() * 100.0);
 }
 
 fn setup_in_game_interface(mut commands: Commands, sprites: Res<SpriteAssetCollection>) {
@@ -325,7 +575,14 @@ fn setup_in_game_interface(mut commands: Commands, sprites: Res<SpriteAssetColle
             },
         ))
         .with_children(|parent| {
-            parent.spawn(NodeBundle::default()).with_children(|parent| {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
                 parent
                     .spawn((
                         ImageBundle {
@@ -357,6 +614,87 @@ fn setup_in_game_interface(mut commands: Commands, sprites: Res<SpriteAssetColle
                             },
                         ));
                     });
+
+                parent
+                    .spawn((
+                        UiAirBar,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(136.0),
+                                height: Val::Px(4.0),
+                                margin: UiRect::horizontal(Val::Px(12.0)),
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            UiAirValue,
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgb_u8(0x8a, 0xd4, 0xf0).into(),
+                                ..default()
+                            },
+                        ));
+                    });
+
+                parent
+                    .spawn((
+                        UiComboMeter,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(136.0),
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(2.0),
+                                margin: UiRect::horizontal(Val::Px(12.0)),
+                                ..default()
+                            },
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            UiComboText,
+                            TextBundle::from_section("", TextStyle {
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            }),
+                        ));
+
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(100.0),
+                                    height: Val::Px(4.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    UiComboValue,
+                                    NodeBundle {
+                                        style: Style {
+                                            width: Val::Percent(100.0),
+                                            height: Val::Percent(100.0),
+                                            ..default()
+                                        },
+                                        background_color: Color::rgb_u8(0xf0, 0xc0, 0x4a).into(),
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    });
             });
 
             parent
@@ -410,8 +748,13 @@ fn setup_in_game_interface(mut commands: Commands, sprites: Res<SpriteAssetColle
 // State used for the current menu screen
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 enum MenuState {
+    Profiles,
     Main,
     Settings,
+    Progress,
+    Statistics,
+    Scoreboard,
+    Curses,
     #[default]
     Disabled,
 }
@@ -419,7 +762,16 @@ enum MenuState {
 #[derive(Component)]
 enum MenuButtonAction {
     Play,
+    Daily,
+    Tutorial,
     Settings,
+    Progress,
+    Statistics,
+    Scoreboard,
+    Curses,
+    Profiles,
+    ToggleCurse(Curse),
+    Difficulty,
     ApplySettings,
     BackToMainMenu,
     Quit,
@@ -436,8 +788,13 @@ fn menu_action(
     mut config: ResMut<Persistent<Config>>,
     display_index_q: Query<&UiOptions>,
     mut window_q: Query<&mut Window, With<PrimaryWindow>>,
-    mut audio_sink_q: Query<&mut AudioSink>,
+    audio_sink_q: Query<(&AudioSink, Option<&AudioBus>, Option<&BaseVolume>)>,
     mut global_volume: ResMut<GlobalVolume>,
+    mut run_mode: ResMut<RunMode>,
+    mut difficulty: ResMut<Difficulty>,
+    mut selected_curses: ResMut<SelectedCurses>,
+    languages: Res<Languages>,
+    profile: Res<Persistent<Profile>>
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
@@ -446,16 +803,42 @@ fn menu_action(
                     app_exit_events.send(AppExit);
                 }
                 MenuButtonAction::Play => {
+                    *run_mode = if profile.skip_tutorial { RunMode::Normal } else { RunMode::Tutorial };
+                    game_state.set(GameState::Setup);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Daily => {
+                    // daily scores are only comparable when everyone plays on the same difficulty
+                    *difficulty = Difficulty::Normal;
+                    selected_curses.0.clear();
+                    *run_mode = RunMode::Daily(DailyDate::today());
+                    game_state.set(GameState::Setup);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Tutorial => {
+                    *run_mode = RunMode::Tutorial;
                     game_state.set(GameState::Setup);
                     menu_state.set(MenuState::Disabled);
                 }
                 MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::Difficulty => {
+                    *difficulty = difficulty.next();
+                }
+                MenuButtonAction::Progress => menu_state.set(MenuState::Progress),
+                MenuButtonAction::Statistics => menu_state.set(MenuState::Statistics),
+                MenuButtonAction::Scoreboard => menu_state.set(MenuState::Scoreboard),
+                MenuButtonAction::Curses => menu_state.set(MenuState::Curses),
+                MenuButtonAction::Profiles => menu_state.set(MenuState::Profiles),
+                MenuButtonAction::ToggleCurse(curse) => selected_curses.toggle(*curse),
                 MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
                 MenuButtonAction::ApplySettings => {
                     let mut window = window_q.single_mut();
 
                     for display_index in display_index_q.iter() {
                         match display_index {
+                            UiOptions::Language(index) => {
+                                config.language = languages.0[*index].0.clone();
+                            }
                             UiOptions::Mode(index) => {
                                 config.mode = ALLOWED_WINDOW_MODES[*index].0;
                             }
@@ -465,30 +848,63 @@ fn menu_action(
                             UiOptions::Resolution(index) => {
                                 config.resolution = ALLOWED_RESOLUTIONS[*index];
                             }
+                            UiOptions::RenderScale(index) => {
+                                config.render_scale = ALLOWED_RENDER_SCALES[*index].0;
+                            }
+                            UiOptions::LightingQuality(index) => {
+                                if let Some(index) = index {
+                                    config.lighting_quality = ALLOWED_LIGHTING_QUALITIES[*index].0;
+                                }
+                            }
+                            UiOptions::CrtFilter(value) => {
+                                config.crt_filter = *value;
+                            }
+                            UiOptions::CrtIntensity(value) => {
+                                config.crt_intensity = *value;
+                            }
+                            UiOptions::RetroPalette(index) => {
+                                config.retro_palette = ALLOWED_RETRO_PALETTES[*index].0;
+                            }
+                            UiOptions::RetroPaletteIntensity(value) => {
+                                config.retro_palette_intensity = *value;
+                            }
                             UiOptions::Volume(value) => {
                                 config.volume = *value;
                             }
+                            UiOptions::BusVolume(bus, value) => {
+                                config.set_bus_volume(*bus, *value);
+                            }
                             UiOptions::Spatial(value) => {
                                 config.spatial = *value;
                             }
+                            UiOptions::ReducedFlashes(value) => {
+                                config.reduced_flashes = *value;
+                            }
+                            UiOptions::HighContrast(value) => {
+                                config.high_contrast = *value;
+                            }
+                            UiOptions::DamageNumbers(value) => {
+                                config.damage_numbers = *value;
+                            }
+                            UiOptions::Gore(value) => {
+                                config.gore = *value;
+                            }
+                            UiOptions::AdaptiveDifficulty(value) => {
+                                config.adaptive_difficulty = *value;
+                            }
+                            UiOptions::Palette(index) => {
+                                config.palette = ALLOWED_PALETTES[*index].0;
+                            }
+                            UiOptions::Control(action, binding) => {
+                                config.set_binding(*action, *binding);
+                            }
                         }
                     }
 
                     config.persist().expect("failed to update config");
 
-                    window.resolution.set(config.resolution[0] as f32, config.resolution[1] as f32);
-                    window.resolution.set_scale_factor_override(
-                        Some((config.resolution[0] as f32) / 1280.0)
-                    );
-                    window.mode = config.mode.clone();
-                    window.present_mode = config.vsync.clone();
-
-                    let volume = ((config.volume as f32) / 100.0).clamp(0.0, 100.0);
-                    global_volume.volume = Volume::new(volume);
-                
-                    for audio_sink in audio_sink_q.iter_mut() {
-                        audio_sink.set_volume(volume);
-                    }
+                    apply_window_config(&config, &mut window);
+                    update_audio_volumes(&config, &mut global_volume, &audio_sink_q);
                 }
             }
         }
@@ -501,16 +917,27 @@ pub struct UiSettings;
 #[derive(Component)]
 pub struct UiMainMenu;
 
+#[derive(Component)]
+pub struct UiProgress;
+
+#[derive(Component)]
+pub struct UiStatistics;
+
+#[derive(Component)]
+pub struct UiCurses;
+
 #[derive(Component)]
 pub struct UiTrack;
 
 fn setup_menu(
     mut commands: Commands,
     mut menu_state: ResMut<NextState<MenuState>>,
+    mut profile_picked: Local<bool>,
     audios: Res<AudioAssetCollection>
 ) {
     commands.spawn((
         UiTrack,
+        AudioBus::Music,
         AudioBundle {
             source: audios.menu.clone(),
             settings: PlaybackSettings {
@@ -520,13 +947,19 @@ fn setup_menu(
         },
     ));
 
-    menu_state.set(MenuState::Main);
+    // the profile is picked once the game starts, later it is changed from the main menu
+    menu_state.set(if *profile_picked { MenuState::Main } else { MenuState::Profiles });
+    *profile_picked = true;
 }
 
 fn setup_main_menu(
     mut commands: Commands,
+    difficulty: Res<Difficulty>,
+    selected_curses: Res<SelectedCurses>,
     sprites: Res<SpriteAssetCollection>,
-    scoreboard: Res<Persistent<Scoreboard>>
+    scoreboard: Res<Persistent<Scoreboard>>,
+    profiles: Res<Persistent<ProfileIndex>>,
+    locale: Res<Locale>
 ) {
     let border_slicer = TextureSlicer {
         border: BorderRect::square(13.0),
@@ -579,9 +1012,17 @@ fn setup_main_menu(
                     ));
 
                     for (action, text) in [
-                        (MenuButtonAction::Play, "Start"),
-                        (MenuButtonAction::Settings, "Settings"),
-                        (MenuButtonAction::Quit, "Exit"),
+                        (MenuButtonAction::Play, locale.get("menu.start").to_string()),
+                        (MenuButtonAction::Difficulty, difficulty_text(*difficulty, &locale)),
+                        (MenuButtonAction::Curses, curses_text(&selected_curses, &locale)),
+                        (MenuButtonAction::Daily, locale.get("menu.daily").to_string()),
+                        (MenuButtonAction::Tutorial, locale.get("menu.tutorial").to_string()),
+                        (MenuButtonAction::Settings, locale.get("menu.settings").to_string()),
+                        (MenuButtonAction::Progress, locale.get("menu.progress").to_string()),
+                        (MenuButtonAction::Statistics, locale.get("menu.statistics").to_string()),
+                        (MenuButtonAction::Scoreboard, locale.get("menu.scores").to_string()),
+                        (MenuButtonAction::Profiles, locale.format("menu.profile", &[&profiles.selected().name])),
+                        (MenuButtonAction::Quit, locale.get("menu.exit").to_string()),
                     ] {
                         parent
                             .spawn((
@@ -723,7 +1164,7 @@ fn setup_main_menu(
                                                     ..Default::default()
                                                 },
                                                 text: Text::from_section(
-                                                    "  Game Controls ",
+                                                    format!("  {} ", locale.get("menu.controls")),
                                                     TextStyle {
                                                         font_size: 24.0,
                                                         color: Color::WHITE,
@@ -739,7 +1180,7 @@ fn setup_main_menu(
                                                     ..Default::default()
                                                 },
                                                 text: Text::from_section(
-                                                    "Movement\n\n- Run: Use the A and D keys to move left and right, respectively.\n- Crouch: Press the S key to crouch.\n\nActions\n\n- Jump: Press the Spacebar to make your character jump.\n- Attack: Press the F key to perform an attack.\n- Dash: Use the Q key to dash forward quickly.\n- Hook: Click the right mouse button to use the hook.\n- Shoot: Use the R key to shoot.\n- Collect: Press the G key to collect materials.\n\nMaterial Selection\n\n- Next Material: Scroll the mouse wheel up to cycle to the next material.\n- Previous Material: Scroll the mouse wheel down to cycle to the previous material.",
+                                                    locale.get("menu.controls_help"),
                                                     TextStyle {
                                                         font_size: 18.0,
                                                         color: Color::WHITE,
@@ -772,7 +1213,7 @@ fn setup_main_menu(
                                     top: Val::Px(16.0),
                                     ..Default::default()
                                 },
-                                text: Text::from_section("  SCOREBOARD ", TextStyle {
+                                text: Text::from_section(format!("  {} ", locale.get("menu.scoreboard")), TextStyle {
                                     font_size: 24.0,
                                     color: Color::WHITE,
                                     ..Default::default()
@@ -863,12 +1304,53 @@ fn setup_main_menu(
                                                             ..Default::default()
                                                         },
                                                         text: Text::from_section(
-                                                            format!(
-                                                                "{}. Level {}: {}",
-                                                                index + 1,
-                                                                level,
-                                                                score
-                                                            ),
+                                                            locale.format("menu.score", &[&(index + 1), &level, &score]),
+                                                            TextStyle {
+                                                                font_size: 18.0,
+                                                                color: Color::WHITE,
+                                                                ..Default::default()
+                                                            }
+                                                        ),
+                                                        ..Default::default()
+                                                    });
+                                                });
+
+                                            let today = DailyDate::today();
+
+                                            parent.spawn(TextBundle {
+                                                style: Style {
+                                                    margin: UiRect::top(Val::Px(16.0)),
+                                                    ..Default::default()
+                                                },
+                                                text: Text::from_section(
+                                                    format!("  {} ", locale.format("menu.daily_scoreboard", &[&today])),
+                                                    TextStyle {
+                                                        font_size: 24.0,
+                                                        color: Color::WHITE,
+                                                        ..Default::default()
+                                                    }
+                                                ).with_justify(JustifyText::Center),
+                                                ..Default::default()
+                                            });
+
+                                            scoreboard.daily
+                                                .iter()
+                                                .filter(|record| record.date == today)
+                                                .sorted_by(|record_1, record_2| record_2.score.cmp(&record_1.score))
+                                                .enumerate()
+                                                .for_each(|(index, record)| {
+                                                    parent.spawn(TextBundle {
+                                                        style: Style {
+                                                            width: Val::Percent(100.0),
+                                                            height: Val::Auto,
+                                                            ..Default::default()
+                                                        },
+                                                        text: Text::from_section(
+                                                            locale.format("menu.score", &[
+                                                                &(index + 1),
+                                                                &record.level,
+                                                                &record.score,
+                                                            ]),
                                                             TextStyle {
                                                                 font_size: 18.0,
                                                                 color: Color::WHITE,
@@ -885,27 +1367,169 @@ fn setup_main_menu(
         });
 }
 
+fn difficulty_name(difficulty: Difficulty, locale: &Locale) -> &str {
+    locale.get_or(&format!("difficulty.{}", difficulty.name().to_lowercase()), difficulty.name())
+}
+
+fn curse_name(curse: Curse, locale: &Locale) -> &str {
+    locale.get_or(&format!("curse.{}", curse.id()), curse.name())
+}
+
+fn difficulty_text(difficulty: Difficulty, locale: &Locale) -> String {
+    locale.format("menu.difficulty", &[&difficulty_name(difficulty, locale)])
+}
+
+fn curses_text(selected_curses: &SelectedCurses, locale: &Locale) -> String {
+    if selected_curses.0.is_empty() {
+        locale.get("menu.curses_none").to_string()
+    } else {
+        locale.format("menu.curses", &[
+            &selected_curses.0.len(),
+            &format!("{:.0}", (selected_curses.score_multiplier() - 1.0) * 100.0),
+        ])
+    }
+}
+
+fn curse_toggle_text(curse: Curse, selected_curses: &SelectedCurses, locale: &Locale) -> String {
+    format!(
+        "[{}] {} +{:.0}%",
+        if selected_curses.0.contains(&curse) { "x" } else { " " },
+        curse_name(curse, locale),
+        curse.score_bonus() * 100.0
+    )
+}
+
+fn update_curse_text(
+    button_q: Query<(&MenuButtonAction, &Children)>,
+    mut text_q: Query<&mut Text>,
+    selected_curses: Res<SelectedCurses>,
+    locale: Res<Locale>
+) {
+    if !selected_curses.is_changed() {
+        return;
+    }
+
+    for (action, children) in button_q.iter() {
+        let MenuButtonAction::ToggleCurse(curse) = action else {
+            continue;
+        };
+
+        if let Ok(mut text) = text_q.get_mut(children[0]) {
+            text.sections[0].value = curse_toggle_text(*curse, &selected_curses, &locale);
+        }
+    }
+}
+
+fn update_difficulty_text(
+    button_q: Query<(&MenuButtonAction, &Children)>,
+    mut text_q: Query<&mut Text>,
+    difficulty: Res<Difficulty>,
+    locale: Res<Locale>
+) {
+    if !difficulty.is_changed() {
+        return;
+    }
+
+    for (action, children) in button_q.iter() {
+        if !matches!(action, MenuButtonAction::Difficulty) {
+            continue;
+        }
+
+        if let Ok(mut text) = text_q.get_mut(children[0]) {
+            text.sections[0].value = difficulty_text(*difficulty, &locale);
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct UiWindowModeValue(WindowMode);
 
 #[derive(Debug, Component)]
 pub enum UiOptions {
+    Language(usize),
     Mode(usize),
     VSync(usize),
     Resolution(usize),
+    RenderScale(usize),
+    /// None while the config has custom values, they are kept until another tier is picked
+    LightingQuality(Option<usize>),
+    CrtFilter(bool),
+    CrtIntensity(i32),
+    RetroPalette(usize),
+    RetroPaletteIntensity(i32),
     Volume(i32),
+    BusVolume(AudioBus, i32),
     Spatial(bool),
+    ReducedFlashes(bool),
+    HighContrast(bool),
+    Palette(usize),
+    DamageNumbers(bool),
+    Gore(bool),
+    AdaptiveDifficulty(bool),
+    Control(PlayerActions, Binding),
+}
+
+impl UiOptions {
+    /// Value of the options shown as a [`Slider`]
+    pub fn slider_value(&self) -> Option<i32> {
+        match self {
+            | UiOptions::Volume(value)
+            | UiOptions::BusVolume(_, value)
+            | UiOptions::CrtIntensity(value)
+            | UiOptions::RetroPaletteIntensity(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn slider_value_mut(&mut self) -> Option<&mut i32> {
+        match self {
+            | UiOptions::Volume(value)
+            | UiOptions::BusVolume(_, value)
+            | UiOptions::CrtIntensity(value)
+            | UiOptions::RetroPaletteIntensity(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Marks control option that waits for the next pressed input, axis bindings are captured in two steps
+#[derive(Component, Default)]
+pub struct UiCapturingBinding {
+    step: usize,
 }
 
+// names of the modes and palettes are keys of the language files
 const ALLOWED_WINDOW_MODES: [(WindowMode, &str); 2] = [
-    (WindowMode::Windowed, "Windowed"),
-    // (WindowMode::BorderlessFullscreen, "Borderless fullscreen"),
-    (WindowMode::SizedFullscreen, "Fullscreen"),
+    (WindowMode::Windowed, "settings.windowed"),
+    // (WindowMode::BorderlessFullscreen, "settings.borderless_fullscreen"),
+    (WindowMode::SizedFullscreen, "settings.fullscreen"),
 ];
 
 const ALLOWED_VSYNC_MODES: [(PresentMode, &str); 2] = [
-    (PresentMode::AutoNoVsync, "Off"),
-    (PresentMode::AutoVsync, "On"),
+    (PresentMode::AutoNoVsync, "common.off"),
+    (PresentMode::AutoVsync, "common.on"),
+];
+
+const ALLOWED_RENDER_SCALES: [(f32, &str); 3] = [(1.0, "100 %"), (0.75, "75 %"), (0.5, "50 %")];
+
+const ALLOWED_LIGHTING_QUALITIES: [(LightingQuality, &str); 3] = [
+    (LightingQuality::Low, "settings.quality_low"),
+    (LightingQuality::Medium, "settings.quality_medium"),
+    (LightingQuality::High, "settings.quality_high"),
+];
+
+const ALLOWED_RETRO_PALETTES: [(RetroPalette, &str); 4] = [
+    (RetroPalette::Off, "common.off"),
+    (RetroPalette::GameBoy, "settings.retro_palette_game_boy"),
+    (RetroPalette::Pico8, "settings.retro_palette_pico8"),
+    (RetroPalette::Cga, "settings.retro_palette_cga"),
+];
+
+const ALLOWED_PALETTES: [(ColorPalette, &str); 4] = [
+    (ColorPalette::Default, "settings.palette_default"),
+    (ColorPalette::Deuteranopia, "settings.palette_deuteranopia"),
+    (ColorPalette::Protanopia, "settings.palette_protanopia"),
+    (ColorPalette::Tritanopia, "settings.palette_tritanopia"),
 ];
 
 const ALLOWED_RESOLUTIONS: [[u32; 2]; 5] = [
@@ -918,11 +1542,1187 @@ const ALLOWED_RESOLUTIONS: [[u32; 2]; 5] = [
     // [3840, 2160],
 ];
 
-fn setup_settings(
-    mut commands: Commands,
-    config: ResMut<Persistent<Config>>,
-    sprites: Res<SpriteAssetCollection>
-) {
+/// Optional handicaps of the next run, each one adds a share to its final score
+fn setup_curses(mut commands: Commands, selected_curses: Res<SelectedCurses>, locale: Res<Locale>) {
+    let text_style = |color: Color| TextStyle {
+        font_size: 18.0,
+        color,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            UiCurses,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(64.0)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Stretch,
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(locale.get("curses.title"), TextStyle {
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Left)
+                    );
+
+                    let mut actions = Curse::ALL
+                        .iter()
+                        .map(|curse| {
+                            (
+                                MenuButtonAction::ToggleCurse(*curse),
+                                curse_toggle_text(*curse, &selected_curses, &locale),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    actions.push((MenuButtonAction::BackToMainMenu, locale.get("common.return").to_string()));
+
+                    for (action, text) in actions {
+                        parent
+                            .spawn((
+                                action,
+                                ButtonBundle {
+                                    style: Style {
+                                        justify_content: JustifyContent::Start,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: Color::NONE.into(),
+                                    ..default()
+                                },
+                                EaseFunction::ExponentialOut,
+                                SpanTweenBundle::new(..Duration::from_millis(250)),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    TextBundle::from_section(text, TextStyle {
+                                        font_size: 32.0,
+                                        color: Color::WHITE,
+                                        ..Default::default()
+                                    }),
+                                    EaseFunction::ExponentialOut,
+                                    SpanTweenBundle::new(..Duration::from_millis(250)),
+                                ));
+                            });
+                    }
+
+                    let forced = Curse::ALL
+                        .iter()
+                        .filter_map(|curse| {
+                            curse
+                                .forced_from()
+                                .map(|level| locale.format("curses.forced", &[&curse_name(*curse, &locale), &level]))
+                        })
+                        .join(", ");
+
+                    parent.spawn(
+                        TextBundle::from_section(
+                            locale.format("curses.always_active", &[&forced]),
+                            text_style(Color::GRAY)
+                        )
+                    );
+                });
+        });
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProfileEdit {
+    Create,
+    Rename(usize),
+}
+
+/// State of the profile screen, the screen is built again whenever it changes
+#[derive(Resource, Default)]
+struct ProfileScreen {
+    editing: Option<ProfileEdit>,
+    /// Name typed in while editing
+    name: String,
+    /// Profile that is deleted once its delete button is pressed again
+    deleting: Option<usize>,
+}
+
+#[derive(Component)]
+enum ProfileButtonAction {
+    Select(usize),
+    Rename(usize),
+    Delete(usize),
+    Create,
+    Return,
+}
+
+#[derive(Component)]
+struct UiProfiles;
+
+#[derive(Component)]
+struct UiProfileName;
+
+const MAX_PROFILE_NAME: usize = 16;
+
+fn spawn_text_button(parent: &mut ChildBuilder, action: impl Component, text: String, font_size: f32) {
+    parent
+        .spawn((
+            action,
+            ButtonBundle {
+                style: Style {
+                    justify_content: JustifyContent::Start,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            EaseFunction::ExponentialOut,
+            SpanTweenBundle::new(..Duration::from_millis(250)),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(text, TextStyle {
+                    font_size,
+                    color: Color::WHITE,
+                    ..Default::default()
+                }),
+                EaseFunction::ExponentialOut,
+                SpanTweenBundle::new(..Duration::from_millis(250)),
+            ));
+        });
+}
+
+fn spawn_profile_name_input(parent: &mut ChildBuilder, name: &str, locale: &Locale) {
+    parent.spawn((
+        UiProfileName,
+        TextBundle::from_sections([
+            TextSection::new(format!("{}_", name), TextStyle {
+                font_size: 32.0,
+                color: Color::rgb_u8(0xf2, 0xf1, 0xa3),
+                ..Default::default()
+            }),
+            TextSection::new(format!("  {}", locale.get("profiles.name_hint")), TextStyle {
+                font_size: 18.0,
+                color: Color::GRAY,
+                ..Default::default()
+            }),
+        ]),
+    ));
+}
+
+/// Every profile with buttons to pick, rename and delete it, built again whenever the screen changes
+fn setup_profiles(
+    mut commands: Commands,
+    ui_q: Query<Entity, With<UiProfiles>>,
+    screen: Res<ProfileScreen>,
+    profiles: Res<Persistent<ProfileIndex>>,
+    locale: Res<Locale>
+) {
+    for entity in ui_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands
+        .spawn((
+            UiProfiles,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(64.0)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Stretch,
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(locale.get("profiles.title"), TextStyle {
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Left)
+                    );
+
+                    for (index, slot) in profiles.slots.iter().enumerate() {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(24.0),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                if screen.editing == Some(ProfileEdit::Rename(index)) {
+                                    spawn_profile_name_input(parent, &screen.name, &locale);
+                                    return;
+                                }
+
+                                let marker = if index == profiles.selected { "> " } else { "" };
+                                spawn_text_button(
+                                    parent,
+                                    ProfileButtonAction::Select(index),
+                                    format!("{}{}", marker, slot.name),
+                                    32.0
+                                );
+                                spawn_text_button(
+                                    parent,
+                                    ProfileButtonAction::Rename(index),
+                                    locale.get("profiles.rename").to_string(),
+                                    18.0
+                                );
+
+                                // the last profile can not be deleted
+                                if profiles.slots.len() > 1 {
+                                    let text = if screen.deleting == Some(index) {
+                                        locale.get("profiles.confirm_delete")
+                                    } else {
+                                        locale.get("profiles.delete")
+                                    };
+
+                                    spawn_text_button(
+                                        parent,
+                                        ProfileButtonAction::Delete(index),
+                                        text.to_string(),
+                                        18.0
+                                    );
+                                }
+                            });
+                    }
+
+                    if screen.editing == Some(ProfileEdit::Create) {
+                        spawn_profile_name_input(parent, &screen.name, &locale);
+                    } else {
+                        spawn_text_button(
+                            parent,
+                            ProfileButtonAction::Create,
+                            locale.get("profiles.new").to_string(),
+                            32.0
+                        );
+                    }
+
+                    spawn_text_button(
+                        parent,
+                        ProfileButtonAction::Return,
+                        locale.get("common.return").to_string(),
+                        32.0
+                    );
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn profile_action(
+    mut commands: Commands,
+    interaction_query: Query<
+        (&Interaction, &ProfileButtonAction),
+        (Changed<Interaction>, With<Button>)
+    >,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut screen: ResMut<ProfileScreen>,
+    mut profiles: ResMut<Persistent<ProfileIndex>>
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            ProfileButtonAction::Select(index) => {
+                if *index != profiles.selected {
+                    profiles.selected = *index;
+                    profiles.persist().expect("failed to update profiles");
+                    switch_profile(&mut commands, &profiles);
+                }
+
+                menu_state.set(MenuState::Main);
+            }
+            ProfileButtonAction::Rename(index) => {
+                screen.editing = Some(ProfileEdit::Rename(*index));
+                screen.name = profiles.slots[*index].name.clone();
+                screen.deleting = None;
+            }
+            ProfileButtonAction::Delete(index) => {
+                if screen.deleting != Some(*index) {
+                    screen.deleting = Some(*index);
+                    continue;
+                }
+
+                match profiles.delete(*index) {
+                    Ok(changed) => {
+                        profiles.persist().expect("failed to update profiles");
+
+                        if changed {
+                            switch_profile(&mut commands, &profiles);
+                        }
+                    }
+                    Err(error) => warn!("failed to delete profile files: {}", error),
+                }
+
+                screen.deleting = None;
+            }
+            ProfileButtonAction::Create => {
+                screen.editing = Some(ProfileEdit::Create);
+                screen.name.clear();
+                screen.deleting = None;
+            }
+            ProfileButtonAction::Return => menu_state.set(MenuState::Main),
+        }
+    }
+}
+
+/// Typing goes into the edited name, enter saves it and escape leaves it as it was
+fn edit_profile_name(
+    mut name_q: Query<&mut Text, With<UiProfileName>>,
+    mut character_events: EventReader<ReceivedCharacter>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut screen: ResMut<ProfileScreen>,
+    mut profiles: ResMut<Persistent<ProfileIndex>>
+) {
+    let Some(edit) = screen.editing else {
+        character_events.clear();
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        screen.editing = None;
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Enter) {
+        let name = screen.name.trim().to_string();
+
+        if name.is_empty() {
+            return;
+        }
+
+        match edit {
+            ProfileEdit::Create => {
+                profiles.create(name);
+            }
+            ProfileEdit::Rename(index) => {
+                profiles.slots[index].name = name;
+            }
+        }
+        profiles.persist().expect("failed to update profiles");
+
+        screen.editing = None;
+        return;
+    }
+
+    // typed characters change only the text, so the screen is not built again on every key
+    let screen = screen.bypass_change_detection();
+    let mut changed = false;
+
+    for event in character_events.read() {
+        for character in event.char.chars().filter(|character| !character.is_control()) {
+            if screen.name.chars().count() < MAX_PROFILE_NAME {
+                screen.name.push(character);
+                changed = true;
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        changed |= screen.name.pop().is_some();
+    }
+
+    if changed {
+        for mut text in name_q.iter_mut() {
+            text.sections[0].value = format!("{}_", screen.name);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum ScoreColumn {
+    Name,
+    #[default]
+    Score,
+    Level,
+    Difficulty,
+    Seed,
+    Date,
+    Duration,
+}
+
+impl ScoreColumn {
+    const ALL: [ScoreColumn; 7] = [
+        ScoreColumn::Name,
+        ScoreColumn::Score,
+        ScoreColumn::Level,
+        ScoreColumn::Difficulty,
+        ScoreColumn::Seed,
+        ScoreColumn::Date,
+        ScoreColumn::Duration,
+    ];
+
+    fn label(&self, locale: &Locale) -> String {
+        let key = match self {
+            ScoreColumn::Name => "scoreboard.name",
+            ScoreColumn::Score => "scoreboard.score",
+            ScoreColumn::Level => "scoreboard.level",
+            ScoreColumn::Difficulty => "scoreboard.difficulty",
+            ScoreColumn::Seed => "scoreboard.seed",
+            ScoreColumn::Date => "scoreboard.date",
+            ScoreColumn::Duration => "scoreboard.duration",
+        };
+
+        locale.get(key).to_string()
+    }
+
+    fn value(&self, record: &RunRecord, locale: &Locale) -> String {
+        match self {
+            ScoreColumn::Name => record.name.clone(),
+            ScoreColumn::Score => record.score.to_string(),
+            ScoreColumn::Level => record.level.to_string(),
+            ScoreColumn::Difficulty => difficulty_name(record.difficulty, locale).to_string(),
+            ScoreColumn::Seed => record.seed.to_string(),
+            ScoreColumn::Date => DailyDate::from_timestamp(record.timestamp).to_string(),
+            ScoreColumn::Duration => format_duration(record.duration),
+        }
+    }
+
+    fn compare(&self, a: &RunRecord, b: &RunRecord) -> std::cmp::Ordering {
+        match self {
+            ScoreColumn::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            ScoreColumn::Score => a.score.cmp(&b.score),
+            ScoreColumn::Level => a.level.cmp(&b.level),
+            ScoreColumn::Difficulty => (a.difficulty as u8).cmp(&(b.difficulty as u8)),
+            ScoreColumn::Seed => a.seed.cmp(&b.seed),
+            ScoreColumn::Date => a.timestamp.cmp(&b.timestamp),
+            ScoreColumn::Duration => a.duration.total_cmp(&b.duration),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum RunFilter {
+    #[default]
+    All,
+    Normal,
+    Daily,
+}
+
+impl RunFilter {
+    fn next(&self) -> Self {
+        match self {
+            RunFilter::All => RunFilter::Normal,
+            RunFilter::Normal => RunFilter::Daily,
+            RunFilter::Daily => RunFilter::All,
+        }
+    }
+
+    fn matches(&self, record: &RunRecord) -> bool {
+        match self {
+            RunFilter::All => true,
+            RunFilter::Normal => !record.daily,
+            RunFilter::Daily => record.daily,
+        }
+    }
+
+    fn name(&self, locale: &Locale) -> String {
+        let key = match self {
+            RunFilter::All => "scoreboard.all",
+            RunFilter::Normal => "scoreboard.normal",
+            RunFilter::Daily => "scoreboard.daily",
+        };
+
+        locale.get(key).to_string()
+    }
+}
+
+/// Sorting and filters of the scoreboard table, kept between visits and built again whenever they change
+#[derive(Resource, Default)]
+struct ScoreboardScreen {
+    sort: ScoreColumn,
+    ascending: bool,
+    difficulty: Option<Difficulty>,
+    runs: RunFilter,
+}
+
+#[derive(Component)]
+enum ScoreboardButtonAction {
+    Sort(ScoreColumn),
+    Difficulty,
+    Runs,
+    Return,
+}
+
+#[derive(Component)]
+struct UiScoreboard;
+
+/// Cell of a scoreboard row, hovering it shows the thumbnail of the run with the timestamp
+#[derive(Component)]
+struct ScoreboardEntry(u64);
+
+#[derive(Component)]
+struct UiScoreboardThumbnail;
+
+// distance in pixels between the cursor and the thumbnail next to it
+const THUMBNAIL_OFFSET: f32 = 16.0;
+
+/// Every recorded run of the profile in a table, columns are sorted by pressing their header
+fn setup_scoreboard(
+    mut commands: Commands,
+    ui_q: Query<Entity, With<UiScoreboard>>,
+    screen: Res<ScoreboardScreen>,
+    scoreboard: Res<Persistent<Scoreboard>>,
+    locale: Res<Locale>
+) {
+    for entity in ui_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let records = scoreboard.runs
+        .iter()
+        .filter(|record| screen.runs.matches(record))
+        .filter(|record| screen.difficulty.is_none_or(|difficulty| record.difficulty == difficulty))
+        .sorted_by(|a, b| {
+            let ordering = screen.sort.compare(a, b).then(a.score.cmp(&b.score));
+            if screen.ascending { ordering } else { ordering.reverse() }
+        })
+        .collect_vec();
+
+    let cell_style = |color: Color| TextStyle {
+        font_size: 18.0,
+        color,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            UiScoreboard,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(64.0)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(locale.get("scoreboard.title"), TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                })
+            );
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(32.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let difficulty = match screen.difficulty {
+                        Some(difficulty) => difficulty_name(difficulty, &locale).to_string(),
+                        None => locale.get("scoreboard.all").to_string(),
+                    };
+
+                    spawn_text_button(
+                        parent,
+                        ScoreboardButtonAction::Difficulty,
+                        locale.format("scoreboard.difficulty_filter", &[&difficulty]),
+                        24.0
+                    );
+                    spawn_text_button(
+                        parent,
+                        ScoreboardButtonAction::Runs,
+                        locale.format("scoreboard.runs_filter", &[&screen.runs.name(&locale)]),
+                        24.0
+                    );
+                    spawn_text_button(
+                        parent,
+                        ScoreboardButtonAction::Return,
+                        locale.get("common.return").to_string(),
+                        24.0
+                    );
+                });
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        overflow: Overflow::clip_y(),
+                        width: Val::Percent(100.0),
+                        height: Val::ZERO,
+                        flex_grow: 1.0,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    display: Display::Grid,
+                                    // rank followed by every column
+                                    grid_template_columns: RepeatedGridTrack::auto(
+                                        (ScoreColumn::ALL.len() + 1) as u16
+                                    ),
+                                    column_gap: Val::Px(32.0),
+                                    row_gap: Val::Px(8.0),
+                                    top: Val::Px(0.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            Interaction::default(),
+                            ScrollingList::default(),
+                            AccessibilityNode(NodeBuilder::new(Role::Table)),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("#", cell_style(Color::GRAY)));
+
+                            for column in ScoreColumn::ALL {
+                                let arrow = match (column == screen.sort, screen.ascending) {
+                                    (false, _) => "",
+                                    (true, true) => " ^",
+                                    (true, false) => " v",
+                                };
+
+                                spawn_text_button(
+                                    parent,
+                                    ScoreboardButtonAction::Sort(column),
+                                    format!("{}{}", column.label(&locale), arrow),
+                                    18.0
+                                );
+                            }
+
+                            for (index, record) in records.iter().enumerate() {
+                                parent.spawn((
+                                    TextBundle::from_section((index + 1).to_string(), cell_style(Color::GRAY)),
+                                    ScoreboardEntry(record.timestamp),
+                                    Interaction::default(),
+                                ));
+
+                                for column in ScoreColumn::ALL {
+                                    parent.spawn((
+                                        TextBundle::from_section(
+                                            column.value(record, &locale),
+                                            cell_style(Color::WHITE)
+                                        ),
+                                        ScoreboardEntry(record.timestamp),
+                                        Interaction::default(),
+                                    ));
+                                }
+                            }
+                        });
+
+                    if records.is_empty() {
+                        parent.spawn(
+                            TextBundle::from_section(locale.get("scoreboard.empty"), cell_style(Color::GRAY))
+                        );
+                    }
+                });
+
+            parent.spawn((
+                UiScoreboardThumbnail,
+                ImageBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                BorderColor(Color::WHITE),
+            ));
+        });
+}
+
+/// Thumbnail of the hovered run follows the cursor, runs recorded without one show nothing
+fn show_scoreboard_thumbnail(
+    entry_q: Query<(&ScoreboardEntry, &Interaction)>,
+    mut thumbnail_q: Query<(&mut UiImage, &mut Style, &mut Visibility), With<UiScoreboardThumbnail>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut thumbnails: ResMut<Thumbnails>,
+    mut images: ResMut<Assets<Image>>
+) {
+    let Ok((mut image, mut style, mut visibility)) = thumbnail_q.get_single_mut() else {
+        return;
+    };
+
+    let thumbnail = entry_q
+        .iter()
+        .find(|(_, interaction)| **interaction != Interaction::None)
+        .and_then(|(entry, _)| thumbnails.get(&mut images, entry.0));
+
+    let (Some(thumbnail), Some(cursor)) = (
+        thumbnail,
+        window_q.get_single().ok().and_then(|window| window.cursor_position()),
+    ) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    if image.texture != thumbnail {
+        image.texture = thumbnail;
+    }
+
+    style.left = Val::Px(cursor.x + THUMBNAIL_OFFSET);
+    style.top = Val::Px(cursor.y + THUMBNAIL_OFFSET);
+    *visibility = Visibility::Visible;
+}
+
+#[allow(clippy::type_complexity)]
+fn scoreboard_action(
+    interaction_query: Query<
+        (&Interaction, &ScoreboardButtonAction),
+        (Changed<Interaction>, With<Button>)
+    >,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut screen: ResMut<ScoreboardScreen>
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            ScoreboardButtonAction::Sort(column) => {
+                if screen.sort == *column {
+                    screen.ascending = !screen.ascending;
+                } else {
+                    // names read best from a to z, every other column from the highest value
+                    screen.sort = *column;
+                    screen.ascending = *column == ScoreColumn::Name;
+                }
+            }
+            ScoreboardButtonAction::Difficulty => {
+                screen.difficulty = match screen.difficulty {
+                    None => Some(Difficulty::Easy),
+                    Some(Difficulty::Hard) => None,
+                    Some(difficulty) => Some(difficulty.next()),
+                };
+            }
+            ScoreboardButtonAction::Runs => {
+                screen.runs = screen.runs.next();
+            }
+            ScoreboardButtonAction::Return => menu_state.set(MenuState::Main),
+        }
+    }
+}
+
+/// Totals of every run so far along with the unlocks they lead to
+fn setup_progress(
+    mut commands: Commands,
+    sprites: Res<SpriteAssetCollection>,
+    profile: Res<Persistent<Profile>>,
+    registries: Res<Registries>
+) {
+    let border_slicer = TextureSlicer {
+        border: BorderRect::square(13.0),
+        center_scale_mode: SliceScaleMode::Stretch,
+        sides_scale_mode: SliceScaleMode::Stretch,
+        max_corner_scale: 1.0,
+    };
+
+    let text_style = |color: Color| TextStyle {
+        font_size: 18.0,
+        color,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            UiProgress,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(64.0)),
+                    column_gap: Val::Px(32.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Stretch,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        max_width: Val::Px(300.0),
+                        min_width: Val::Px(150.0),
+                        height: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Stretch,
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(20.0),
+                        flex_shrink: 0.0,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section("Progress", TextStyle {
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Left)
+                    );
+
+                    for (label, value) in [
+                        ("Kills", profile.kills.to_string()),
+                        ("Levels cleared", profile.levels_cleared.to_string()),
+                        ("Materials collected", format!("{:.0}", profile.materials_collected)),
+                        ("Best score", profile.best_score.to_string()),
+                    ] {
+                        parent.spawn(
+                            TextBundle::from_section(format!("{}: {}", label, value), text_style(Color::WHITE))
+                        );
+                    }
+
+                    parent
+                        .spawn((
+                            MenuButtonAction::BackToMainMenu,
+                            ButtonBundle {
+                                style: Style {
+                                    justify_content: JustifyContent::Start,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            },
+                            EaseFunction::ExponentialOut,
+                            SpanTweenBundle::new(..Duration::from_millis(250)),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("Return", TextStyle {
+                                    font_size: 32.0,
+                                    color: Color::WHITE,
+                                    ..Default::default()
+                                }),
+                                EaseFunction::ExponentialOut,
+                                SpanTweenBundle::new(..Duration::from_millis(250)),
+                            ));
+                        });
+                });
+
+            parent
+                .spawn((
+                    ImageBundle {
+                        style: Style {
+                            width: Val::Auto,
+                            height: Val::Percent(100.0),
+                            flex_direction: FlexDirection::Column,
+                            padding: UiRect::all(Val::Px(28.0)),
+                            row_gap: Val::Px(12.0),
+                            overflow: Overflow::clip_y(),
+                            flex_grow: 1.0,
+                            ..default()
+                        },
+                        image: sprites.border.clone().into(),
+                        ..default()
+                    },
+                    ImageScaleMode::Sliced(border_slicer),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section("  UNLOCKS ", TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Center)
+                    );
+
+                    for unlock in registries.unlocks.iter() {
+                        let (value, goal) = unlock.requirement.progress(&profile);
+                        let unlocked = unlock.is_unlocked(&profile);
+                        let requirement = match unlock.requirement {
+                            Requirement::Kills(_) => "kills",
+                            Requirement::LevelsCleared(_) => "levels cleared",
+                            Requirement::MaterialsCollected(_) => "materials collected",
+                        };
+
+                        parent.spawn(
+                            TextBundle::from_sections([
+                                TextSection::new(
+                                    format!("{} - {}\n", unlock.name, unlock.description),
+                                    text_style(if unlocked { Color::WHITE } else { Color::GRAY })
+                                ),
+                                TextSection::new(
+                                    if unlocked {
+                                        "  unlocked".to_string()
+                                    } else {
+                                        format!("  {:.0}/{:.0} {}", value.min(goal), goal, requirement)
+                                    },
+                                    text_style(Color::GRAY)
+                                ),
+                            ])
+                        );
+                    }
+                });
+        });
+}
+
+fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Enemy names are used as they are set on spawn, some of them are in snake case
+fn format_kills(kills_by_type: &HashMap<String, u32>) -> String {
+    kills_by_type
+        .iter()
+        .sorted_by(|(a_name, a_kills), (b_name, b_kills)| {
+            b_kills.cmp(a_kills).then(a_name.cmp(b_name))
+        })
+        .map(|(name, kills)| format!("{} x{}", name.replace('_', " ").to_lowercase(), kills))
+        .join(", ")
+}
+
+fn score_summary(breakdown: &ScoreBreakdown) -> String {
+    format!(
+        "kills: {}   combo bonus: {}   speed bonus: {}   curses: {}   decay: -{}",
+        breakdown.kills,
+        breakdown.combo,
+        breakdown.speed,
+        breakdown.curses,
+        breakdown.decay
+    )
+}
+
+fn run_summary(stats: &RunStats, perks: &[Perk], locale: &Locale) -> String {
+    let mut lines = vec![
+        format!("damage dealt: {:.0}   damage taken: {:.0}", stats.damage_dealt, stats.damage_taken),
+        format!(
+            "materials collected: {:.0}   pixels destroyed: {}",
+            stats.materials_collected,
+            stats.pixels_destroyed
+        ),
+        format!("enemies killed: {}", stats.kills),
+    ];
+
+    if !stats.kills_by_type.is_empty() {
+        lines.push(format_kills(&stats.kills_by_type));
+    }
+
+    if !stats.perks.is_empty() {
+        lines.push(format!("perks: {}", perk_names(perks, &stats.perks, locale)));
+    }
+
+    lines.push(format!("time: {}", format_duration(stats.duration)));
+
+    if !stats.level_times.is_empty() {
+        lines.push(
+            stats.level_times
+                .iter()
+                .enumerate()
+                .map(|(index, time)| format!("level {}: {}", index + 1, format_duration(*time)))
+                .join("   ")
+        );
+    }
+
+    lines.join("\n")
+}
+
+fn setup_statistics(
+    mut commands: Commands,
+    sprites: Res<SpriteAssetCollection>,
+    profile: Res<Persistent<Profile>>
+) {
+    let border_slicer = TextureSlicer {
+        border: BorderRect::square(13.0),
+        center_scale_mode: SliceScaleMode::Stretch,
+        sides_scale_mode: SliceScaleMode::Stretch,
+        max_corner_scale: 1.0,
+    };
+
+    let text_style = |color: Color| TextStyle {
+        font_size: 18.0,
+        color,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            UiStatistics,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(64.0)),
+                    column_gap: Val::Px(32.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Stretch,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        max_width: Val::Px(300.0),
+                        min_width: Val::Px(150.0),
+                        height: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Stretch,
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(20.0),
+                        flex_shrink: 0.0,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section("Statistics", TextStyle {
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Left)
+                    );
+
+                    parent
+                        .spawn((
+                            MenuButtonAction::BackToMainMenu,
+                            ButtonBundle {
+                                style: Style {
+                                    justify_content: JustifyContent::Start,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            },
+                            EaseFunction::ExponentialOut,
+                            SpanTweenBundle::new(..Duration::from_millis(250)),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("Return", TextStyle {
+                                    font_size: 32.0,
+                                    color: Color::WHITE,
+                                    ..Default::default()
+                                }),
+                                EaseFunction::ExponentialOut,
+                                SpanTweenBundle::new(..Duration::from_millis(250)),
+                            ));
+                        });
+                });
+
+            parent
+                .spawn((
+                    ImageBundle {
+                        style: Style {
+                            width: Val::Auto,
+                            height: Val::Percent(100.0),
+                            flex_direction: FlexDirection::Column,
+                            padding: UiRect::all(Val::Px(28.0)),
+                            row_gap: Val::Px(12.0),
+                            overflow: Overflow::clip_y(),
+                            flex_grow: 1.0,
+                            ..default()
+                        },
+                        image: sprites.border.clone().into(),
+                        ..default()
+                    },
+                    ImageScaleMode::Sliced(border_slicer),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section("  LIFETIME ", TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..Default::default()
+                        }).with_text_justify(JustifyText::Center)
+                    );
+
+                    for (label, value) in [
+                        ("Runs", profile.runs.to_string()),
+                        ("Time played", format_duration(profile.play_time)),
+                        ("Levels cleared", profile.levels_cleared.to_string()),
+                        ("Best score", profile.best_score.to_string()),
+                        ("Damage dealt", format!("{:.0}", profile.damage_dealt)),
+                        ("Damage taken", format!("{:.0}", profile.damage_taken)),
+                        ("Materials collected", format!("{:.0}", profile.materials_collected)),
+                        ("Pixels destroyed", profile.pixels_destroyed.to_string()),
+                        ("Enemies killed", profile.kills.to_string()),
+                    ] {
+                        parent.spawn(
+                            TextBundle::from_section(format!("{}: {}", label, value), text_style(Color::WHITE))
+                        );
+                    }
+
+                    if !profile.kills_by_type.is_empty() {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                format!("  {}", format_kills(&profile.kills_by_type)),
+                                text_style(Color::GRAY)
+                            )
+                        );
+                    }
+                });
+        });
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    config: ResMut<Persistent<Config>>,
+    sprites: Res<SpriteAssetCollection>,
+    languages: Res<Languages>,
+    locale: Res<Locale>
+) {
     let border_slicer = TextureSlicer {
         border: BorderRect::square(13.0),
         center_scale_mode: SliceScaleMode::Stretch,
@@ -967,7 +2767,7 @@ fn setup_settings(
                 })
                 .with_children(|parent| {
                     parent.spawn(
-                        TextBundle::from_section("Settings", TextStyle {
+                        TextBundle::from_section(locale.get("settings.title"), TextStyle {
                             font_size: 40.0,
                             color: Color::WHITE,
                             ..Default::default()
@@ -975,8 +2775,8 @@ fn setup_settings(
                     );
 
                     for (action, text) in [
-                        (MenuButtonAction::ApplySettings, "Apply"),
-                        (MenuButtonAction::BackToMainMenu, "Return"),
+                        (MenuButtonAction::ApplySettings, locale.get("settings.apply")),
+                        (MenuButtonAction::BackToMainMenu, locale.get("common.return")),
                     ] {
                         parent
                             .spawn((
@@ -1075,7 +2875,7 @@ fn setup_settings(
                                                     ..Default::default()
                                                 },
                                                 text: Text::from_section(
-                                                    "Display settings: ",
+                                                    format!("{}: ", locale.get("settings.display")),
                                                     TextStyle {
                                                         font_size: 18.0,
                                                         color: Color::WHITE,
@@ -1124,144 +2924,149 @@ fn setup_settings(
                                                             )
                                                             .unwrap();
 
-                                                    parent
-                                                        .spawn((
-                                                            UiOptions::Mode(mode_index),
-                                                            ButtonBundle {
-                                                                style: Style {
-                                                                    justify_content: JustifyContent::Start,
-                                                                    align_items: AlignItems::Center,
-                                                                    ..default()
-                                                                },
-                                                                background_color: Color::NONE.into(),
-                                                                ..default()
-                                                            },
-                                                            EaseFunction::ExponentialOut,
-                                                            SpanTweenBundle::new(
-                                                                ..Duration::from_millis(250)
-                                                            ),
-                                                        ))
-                                                        .with_children(|parent| {
-                                                            parent.spawn((
-                                                                TextBundle::from_sections([
-                                                                    TextSection {
-                                                                        value: "Window mode: ".into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
+                                                    let language_index = languages.0
+                                                        .iter()
+                                                        .position(|(id, _)| *id == config.language)
+                                                        .unwrap_or(0);
+
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::Language(language_index),
+                                                        format!("{}: ", locale.get("settings.language")),
+                                                        languages.0[language_index].1.clone()
+                                                    );
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::Mode(mode_index),
+                                                        format!("{}: ", locale.get("settings.window_mode")),
+                                                        locale.get(mode_text)
+                                                    );
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::Resolution(resolution_index),
+                                                        format!("{}: ", locale.get("settings.resolution")),
+                                                        format!("{}x{}", resolution[0], resolution[1])
+                                                    );
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::VSync(vsync_index),
+                                                        format!("{}: ", locale.get("settings.vsync")),
+                                                        locale.get(vsync_text)
+                                                    );
+
+                                                    let (render_scale_index, (_, render_scale_text)) =
+                                                        ALLOWED_RENDER_SCALES.into_iter()
+                                                            .enumerate()
+                                                            .find(
+                                                                |(_, (scale, _))|
+                                                                    *scale == config.render_scale
+                                                            )
+                                                            .unwrap_or((0, ALLOWED_RENDER_SCALES[0]));
+
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::RenderScale(render_scale_index),
+                                                        format!("{}: ", locale.get("settings.render_scale")),
+                                                        render_scale_text
+                                                    );
+
+                                                    let lighting_index = ALLOWED_LIGHTING_QUALITIES.iter().position(
+                                                        |(quality, _)| *quality == config.lighting_quality
+                                                    );
+
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::LightingQuality(lighting_index),
+                                                        format!("{}: ", locale.get("settings.lighting_quality")),
+                                                        locale.get(
+                                                            lighting_index.map_or("settings.quality_custom", |index| {
+                                                                ALLOWED_LIGHTING_QUALITIES[index].1
+                                                            })
+                                                        )
+                                                    );
+
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::CrtFilter(config.crt_filter),
+                                                        format!("{}: ", locale.get("settings.crt_filter")),
+                                                        config.crt_filter,
+                                                        &locale
+                                                    );
+                                                    spawn_slider(
+                                                        parent,
+                                                        UiOptions::CrtIntensity(config.crt_intensity),
+                                                        format!("{}: ", locale.get("settings.crt_intensity")),
+                                                        Slider::PERCENT
+                                                    );
+
+                                                    let (retro_palette_index, (_, retro_palette_text)) =
+                                                        ALLOWED_RETRO_PALETTES.into_iter()
+                                                            .enumerate()
+                                                            .find(
+                                                                |(_, (palette, _))|
+                                                                    *palette == config.retro_palette
+                                                            )
+                                                            .unwrap();
 
-                                                                    TextSection {
-                                                                        value: mode_text.into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
-                                                                ]),
-                                                                EaseFunction::ExponentialOut,
-                                                                SpanTweenBundle::new(
-                                                                    ..Duration::from_millis(250)
-                                                                ),
-                                                            ));
-                                                        });
-
-                                                    parent
-                                                        .spawn((
-                                                            UiOptions::Resolution(resolution_index),
-                                                            ButtonBundle {
-                                                                style: Style {
-                                                                    justify_content: JustifyContent::Start,
-                                                                    align_items: AlignItems::Center,
-                                                                    ..default()
-                                                                },
-                                                                background_color: Color::NONE.into(),
-                                                                ..default()
-                                                            },
-                                                            EaseFunction::ExponentialOut,
-                                                            SpanTweenBundle::new(
-                                                                ..Duration::from_millis(250)
-                                                            ),
-                                                        ))
-                                                        .with_children(|parent| {
-                                                            parent.spawn((
-                                                                TextBundle::from_sections([
-                                                                    TextSection {
-                                                                        value: "Resolution: ".into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::RetroPalette(retro_palette_index),
+                                                        format!("{}: ", locale.get("settings.retro_palette")),
+                                                        locale.get(retro_palette_text)
+                                                    );
+                                                    spawn_slider(
+                                                        parent,
+                                                        UiOptions::RetroPaletteIntensity(config.retro_palette_intensity),
+                                                        format!("{}: ", locale.get("settings.retro_palette_intensity")),
+                                                        Slider::PERCENT
+                                                    );
+                                                });
 
-                                                                    TextSection {
-                                                                        value: format!(
-                                                                            "{}x{}",
-                                                                            resolution[0],
-                                                                            resolution[1]
-                                                                        ),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
-                                                                ]),
-                                                                EaseFunction::ExponentialOut,
-                                                                SpanTweenBundle::new(
-                                                                    ..Duration::from_millis(250)
-                                                                ),
-                                                            ));
-                                                        });
-
-                                                    parent
-                                                        .spawn((
-                                                            UiOptions::VSync(vsync_index),
-                                                            ButtonBundle {
-                                                                style: Style {
-                                                                    justify_content: JustifyContent::Start,
-                                                                    align_items: AlignItems::Center,
-                                                                    ..default()
-                                                                },
-                                                                background_color: Color::NONE.into(),
-                                                                ..default()
-                                                            },
-                                                            EaseFunction::ExponentialOut,
-                                                            SpanTweenBundle::new(
-                                                                ..Duration::from_millis(250)
-                                                            ),
-                                                        ))
-                                                        .with_children(|parent| {
-                                                            parent.spawn((
-                                                                TextBundle::from_sections([
-                                                                    TextSection {
-                                                                        value: "VSync: ".into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
+                                            parent.spawn(TextBundle {
+                                                style: Style {
+                                                    width: Val::Percent(100.0),
+                                                    height: Val::Auto,
+                                                    ..Default::default()
+                                                },
+                                                text: Text::from_section(
+                                                    format!("{}: ", locale.get("settings.audio")),
+                                                    TextStyle {
+                                                        font_size: 18.0,
+                                                        color: Color::WHITE,
+                                                        ..Default::default()
+                                                    }
+                                                ),
+                                                ..Default::default()
+                                            });
 
-                                                                    TextSection {
-                                                                        value: vsync_text.into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
-                                                                ]),
-                                                                EaseFunction::ExponentialOut,
-                                                                SpanTweenBundle::new(
-                                                                    ..Duration::from_millis(250)
-                                                                ),
-                                                            ));
-                                                        });
+                                            parent
+                                                .spawn(NodeBundle {
+                                                    style: Style {
+                                                        width: Val::Percent(100.0),
+                                                        margin: UiRect::horizontal(Val::Px(32.0)),
+                                                        row_gap: Val::Px(4.0),
+                                                        flex_direction: FlexDirection::Column,
+                                                        height: Val::Auto,
+                                                        ..Default::default()
+                                                    },
+                                                    ..Default::default()
+                                                })
+                                                .with_children(|parent| {
+                                                    spawn_slider(
+                                                        parent,
+                                                        UiOptions::Volume(config.volume),
+                                                        format!("{}: ", locale.get("settings.volume")),
+                                                        Slider::PERCENT
+                                                    );
+
+                                                    for (bus, name) in AUDIO_BUSES {
+                                                        spawn_slider(
+                                                            parent,
+                                                            UiOptions::BusVolume(bus, config.bus_volume(bus)),
+                                                            format!("{}: ", locale.get(name)),
+                                                            Slider::PERCENT
+                                                        );
+                                                    }
                                                 });
 
                                             parent.spawn(TextBundle {
@@ -1271,7 +3076,7 @@ fn setup_settings(
                                                     ..Default::default()
                                                 },
                                                 text: Text::from_section(
-                                                    "Audio settings: ",
+                                                    format!("{}: ", locale.get("settings.accessibility")),
                                                     TextStyle {
                                                         font_size: 18.0,
                                                         color: Color::WHITE,
@@ -1294,53 +3099,142 @@ fn setup_settings(
                                                     ..Default::default()
                                                 })
                                                 .with_children(|parent| {
-                                                    parent
-                                                        .spawn((
-                                                            UiOptions::Volume(config.volume),
-                                                            ButtonBundle {
-                                                                style: Style {
-                                                                    justify_content: JustifyContent::Start,
-                                                                    align_items: AlignItems::Center,
-                                                                    ..default()
-                                                                },
-                                                                background_color: Color::NONE.into(),
-                                                                ..default()
-                                                            },
-                                                            EaseFunction::ExponentialOut,
-                                                            SpanTweenBundle::new(
-                                                                ..Duration::from_millis(250)
-                                                            ),
-                                                        ))
-                                                        .with_children(|parent| {
-                                                            parent.spawn((
-                                                                TextBundle::from_sections([
-                                                                    TextSection {
-                                                                        value: "Volume: ".into(),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
-                                                                    },
+                                                    let (palette_index, (_, palette_text)) =
+                                                        ALLOWED_PALETTES.into_iter()
+                                                            .enumerate()
+                                                            .find(
+                                                                |(_, (palette, _))|
+                                                                    *palette == config.palette
+                                                            )
+                                                            .unwrap();
 
-                                                                    TextSection {
-                                                                        value: format!(
-                                                                            "{}%",
-                                                                            config.volume
-                                                                        ),
-                                                                        style: TextStyle {
-                                                                            font_size: 18.0,
-                                                                            color: Color::WHITE,
-                                                                            ..Default::default()
-                                                                        },
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::ReducedFlashes(config.reduced_flashes),
+                                                        format!("{}: ", locale.get("settings.reduced_flashes")),
+                                                        config.reduced_flashes,
+                                                        &locale
+                                                    );
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::HighContrast(config.high_contrast),
+                                                        format!("{}: ", locale.get("settings.high_contrast")),
+                                                        config.high_contrast,
+                                                        &locale
+                                                    );
+                                                    spawn_cycle_button(
+                                                        parent,
+                                                        UiOptions::Palette(palette_index),
+                                                        format!("{}: ", locale.get("settings.palette")),
+                                                        locale.get(palette_text)
+                                                    );
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::DamageNumbers(config.damage_numbers),
+                                                        format!("{}: ", locale.get("settings.damage_numbers")),
+                                                        config.damage_numbers,
+                                                        &locale
+                                                    );
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::Gore(config.gore),
+                                                        format!("{}: ", locale.get("settings.gore")),
+                                                        config.gore,
+                                                        &locale
+                                                    );
+                                                    spawn_toggle(
+                                                        parent,
+                                                        UiOptions::AdaptiveDifficulty(config.adaptive_difficulty),
+                                                        format!("{}: ", locale.get("settings.adaptive_difficulty")),
+                                                        config.adaptive_difficulty,
+                                                        &locale
+                                                    );
+                                                });
+
+                                            parent.spawn(TextBundle {
+                                                style: Style {
+                                                    width: Val::Percent(100.0),
+                                                    height: Val::Auto,
+                                                    ..Default::default()
+                                                },
+                                                text: Text::from_section(
+                                                    format!("{}: ", locale.get("settings.controls")),
+                                                    TextStyle {
+                                                        font_size: 18.0,
+                                                        color: Color::WHITE,
+                                                        ..Default::default()
+                                                    }
+                                                ),
+                                                ..Default::default()
+                                            });
+
+                                            parent
+                                                .spawn(NodeBundle {
+                                                    style: Style {
+                                                        width: Val::Percent(100.0),
+                                                        margin: UiRect::horizontal(Val::Px(32.0)),
+                                                        row_gap: Val::Px(4.0),
+                                                        flex_direction: FlexDirection::Column,
+                                                        height: Val::Auto,
+                                                        ..Default::default()
+                                                    },
+                                                    ..Default::default()
+                                                })
+                                                .with_children(|parent| {
+                                                    for control in default_controls() {
+                                                        let binding = config.binding(control.action);
+
+                                                        parent
+                                                            .spawn((
+                                                                UiOptions::Control(
+                                                                    control.action,
+                                                                    binding
+                                                                ),
+                                                                ButtonBundle {
+                                                                    style: Style {
+                                                                        justify_content: JustifyContent::Start,
+                                                                        align_items: AlignItems::Center,
+                                                                        ..default()
                                                                     },
-                                                                ]),
+                                                                    background_color: Color::NONE.into(),
+                                                                    ..default()
+                                                                },
                                                                 EaseFunction::ExponentialOut,
                                                                 SpanTweenBundle::new(
                                                                     ..Duration::from_millis(250)
                                                                 ),
-                                                            ));
-                                                        });
+                                                            ))
+                                                            .with_children(|parent| {
+                                                                parent.spawn((
+                                                                    TextBundle::from_sections([
+                                                                        TextSection {
+                                                                            value: format!(
+                                                                                "{}: ",
+                                                                                action_name(control.action)
+                                                                            ),
+                                                                            style: TextStyle {
+                                                                                font_size: 18.0,
+                                                                                color: Color::WHITE,
+                                                                                ..Default::default()
+                                                                            },
+                                                                        },
+
+                                                                        TextSection {
+                                                                            value: binding.describe(),
+                                                                            style: TextStyle {
+                                                                                font_size: 18.0,
+                                                                                color: Color::WHITE,
+                                                                                ..Default::default()
+                                                                            },
+                                                                        },
+                                                                    ]),
+                                                                    EaseFunction::ExponentialOut,
+                                                                    SpanTweenBundle::new(
+                                                                        ..Duration::from_millis(250)
+                                                                    ),
+                                                                ));
+                                                            });
+                                                    }
                                                 });
                                         });
                                 });
@@ -1355,20 +3249,78 @@ pub struct UiGameOver;
 #[derive(Component)]
 pub struct UiGameOverReturnButton;
 
+/// Starts a new run on the seed of the one that just ended
+#[derive(Component)]
+pub struct UiGameOverRetryButton;
+
+/// Name the run is saved under on the scoreboard, only shown for runs that were recorded
+#[derive(Component)]
+pub struct UiGameOverName;
+
+/// Typing on the game over screen changes the name of the run that just ended,
+/// it is saved once the screen is left
+fn edit_run_name(
+    mut name_q: Query<&mut Text, With<UiGameOverName>>,
+    mut character_events: EventReader<ReceivedCharacter>,
+    keys: Res<ButtonInput<KeyCode>>,
+    stats: Res<RunStats>,
+    mut scoreboard: ResMut<Persistent<Scoreboard>>,
+    locale: Res<Locale>
+) {
+    // the run may have been pruned right away when its score was the lowest
+    let Some(timestamp) = stats.recorded_at else {
+        character_events.clear();
+        return;
+    };
+    let Some(record) = scoreboard.runs.iter_mut().rev().find(|record| record.timestamp == timestamp) else {
+        character_events.clear();
+        return;
+    };
+
+    let mut changed = false;
+
+    for event in character_events.read() {
+        for character in event.char.chars().filter(|character| !character.is_control()) {
+            if record.name.chars().count() < MAX_PROFILE_NAME {
+                record.name.push(character);
+                changed = true;
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        changed |= record.name.pop().is_some();
+    }
+
+    if changed {
+        for mut text in name_q.iter_mut() {
+            text.sections[0].value = locale.format("game_over.name", &[&format!("{}_", record.name)]);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn game_over_button(
     mut commands: Commands,
     button_q: Query<
-        (Entity, &Style, &Interaction, &Children),
-        (Changed<Interaction>, With<UiGameOverReturnButton>)
+        (Entity, &Style, &Interaction, &Children, Has<UiGameOverRetryButton>),
+        (Changed<Interaction>, Or<(With<UiGameOverReturnButton>, With<UiGameOverRetryButton>)>)
     >,
     mut game_state: ResMut<NextState<GameState>>,
+    mut retry_seed: ResMut<RetrySeed>,
+    seed: Res<Seed>,
     mut text_query: Query<(Entity, &Text)>
 ) {
-    for (entity, style, interaction, children) in button_q.iter() {
+    for (entity, style, interaction, children, retry) in button_q.iter() {
         let (text_entity, text) = text_query.get_mut(children[0]).unwrap();
         match *interaction {
             Interaction::Pressed => {
-                game_state.set(GameState::Menu);
+                if retry {
+                    retry_seed.0 = Some(seed.0);
+                    game_state.set(GameState::Setup);
+                } else {
+                    game_state.set(GameState::Menu);
+                }
             }
             Interaction::Hovered => {
                 commands
@@ -1406,7 +3358,26 @@ fn game_over_button(
     }
 }
 
-fn game_over_splash(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<Score>) {
+#[allow(clippy::too_many_arguments)]
+fn game_over_splash(
+    mut commands: Commands,
+    score: Res<Score>,
+    stats: Res<RunStats>,
+    death_cause: Res<DeathCause>,
+    level_counter: Res<LevelCounter>,
+    registries: Res<Registries>,
+    scoreboard: Res<Persistent<Scoreboard>>,
+    locale: Res<Locale>
+) {
+    let recorded_name = stats.recorded_at
+        .and_then(|timestamp| scoreboard.runs.iter().rev().find(|record| record.timestamp == timestamp))
+        .map(|record| record.name.clone());
+
+    let death = match &death_cause.0 {
+        Some(cause) => format!("  killed by {} on level {} ", cause, level_counter.0),
+        None => format!("  died on level {} ", level_counter.0),
+    };
+
     commands
         .spawn((
             UiGameOver,
@@ -1487,6 +3458,75 @@ fn game_over_splash(mut commands: Commands, asset_server: Res<AssetServer>, scor
                         }),
                     ));
 
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section(death, TextStyle {
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            }).with_justify(JustifyText::Center),
+                            ..Default::default()
+                        },
+                        EaseFunction::ExponentialOut,
+                        SpanTweenerBundle::new(Duration::from_millis(1500)).tween_here(),
+                        ComponentTween::new(InterpolateTextColor {
+                            start: Color::NONE,
+                            end: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+                        }),
+                    ));
+
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section(score_summary(&score.breakdown), TextStyle {
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            }).with_justify(JustifyText::Center),
+                            ..Default::default()
+                        },
+                        EaseFunction::ExponentialOut,
+                        SpanTweenerBundle::new(Duration::from_millis(1500)).tween_here(),
+                        ComponentTween::new(InterpolateTextColor {
+                            start: Color::NONE,
+                            end: Color::Rgba { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 },
+                        }),
+                    ));
+
+                    parent.spawn((
+                        TextBundle {
+                            text: Text::from_section(run_summary(&stats, &registries.perks, &locale), TextStyle {
+                                font_size: 18.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            }).with_justify(JustifyText::Center),
+                            ..Default::default()
+                        },
+                        EaseFunction::ExponentialOut,
+                        SpanTweenerBundle::new(Duration::from_millis(1500)).tween_here(),
+                        ComponentTween::new(InterpolateTextColor {
+                            start: Color::NONE,
+                            end: Color::Rgba { red: 0.75, green: 0.75, blue: 0.75, alpha: 1.0 },
+                        }),
+                    ));
+
+                    if let Some(name) = recorded_name {
+                        parent.spawn((
+                            UiGameOverName,
+                            TextBundle::from_sections([
+                                TextSection::new(locale.format("game_over.name", &[&format!("{}_", name)]), TextStyle {
+                                    font_size: 24.0,
+                                    color: Color::rgb_u8(0xf2, 0xf1, 0xa3),
+                                    ..Default::default()
+                                }),
+                                TextSection::new(format!("  {}", locale.get("game_over.name_hint")), TextStyle {
+                                    font_size: 18.0,
+                                    color: Color::GRAY,
+                                    ..Default::default()
+                                }),
+                            ]),
+                        ));
+                    }
+
                     parent
                         .spawn((
                             UiGameOverReturnButton,
@@ -1520,47 +3560,190 @@ fn game_over_splash(mut commands: Commands, asset_server: Res<AssetServer>, scor
                                 }),
                             ));
                         });
+
+                    parent
+                        .spawn((
+                            UiGameOverRetryButton,
+                            ButtonBundle {
+                                style: Style {
+                                    justify_content: JustifyContent::Start,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: Color::NONE.into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("  retry seed ", TextStyle {
+                                    font_size: 36.0,
+                                    color: Color::NONE,
+                                    ..Default::default()
+                                }),
+                                EaseFunction::ExponentialOut,
+                                SpanTweenerBundle::new(Duration::from_millis(1500)).tween_here(),
+                                ComponentTween::new(InterpolateTextColor {
+                                    start: Color::NONE,
+                                    end: Color::Rgba {
+                                        red: 0.75,
+                                        green: 0.75,
+                                        blue: 0.75,
+                                        alpha: 1.0,
+                                    },
+                                }),
+                            ));
+                        });
                 });
         });
 }
 
+/// Button selected with a gamepad, `pressed` is released on the next frame
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    pub entity: Option<Entity>,
+    pub pressed: Option<Entity>,
+}
+
+// focus is shown by placing the cursor over the button, so hover effects work the same way as with a mouse
+fn gamepad_menu_navigation(
+    mut focus: ResMut<MenuFocus>,
+    mut button_q: Query<(Entity, &mut Interaction, &GlobalTransform, &ViewVisibility), With<Button>>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>
+) {
+    if let Some(entity) = focus.pressed.take() {
+        if let Ok((_, mut interaction, _, _)) = button_q.get_mut(entity) {
+            if *interaction == Interaction::Pressed {
+                *interaction = Interaction::Hovered;
+            }
+        }
+    }
+
+    let just_pressed = |button_type: GamepadButtonType| {
+        gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    };
+
+    let offset = if just_pressed(GamepadButtonType::DPadUp) {
+        -1
+    } else if just_pressed(GamepadButtonType::DPadDown) {
+        1
+    } else {
+        0
+    };
+    let confirm = just_pressed(GamepadButtonType::South);
+
+    if offset == 0 && !confirm {
+        return;
+    }
+
+    // buttons are ordered the way they are read, top to bottom and left to right
+    let buttons = button_q
+        .iter()
+        .filter(|(_, _, _, visibility)| visibility.get())
+        .map(|(entity, _, transform, _)| (entity, transform.translation().xy()))
+        .sorted_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)))
+        .collect_vec();
+
+    if buttons.is_empty() {
+        return;
+    }
+
+    let index = match buttons.iter().position(|(entity, _)| Some(*entity) == focus.entity) {
+        Some(index) => (index as i32 + offset).rem_euclid(buttons.len() as i32) as usize,
+        None => 0,
+    };
+    let (entity, position) = buttons[index];
+
+    if let Ok(mut window) = window_q.get_single_mut() {
+        window.set_cursor_position(Some(position));
+    }
+
+    if confirm && focus.entity == Some(entity) {
+        if let Ok((_, mut interaction, _, _)) = button_q.get_mut(entity) {
+            *interaction = Interaction::Pressed;
+            focus.pressed = Some(entity);
+        }
+    }
+
+    focus.entity = Some(entity);
+}
+
 fn button_next_option(
+    mut commands: Commands,
     mut interaction_query: Query<
-        (&mut UiOptions, &Interaction, &Children),
-        (Changed<Interaction>, With<Button>)
+        (Entity, &mut UiOptions, &Interaction, &Children),
+        (Changed<Interaction>, With<Button>, Without<Slider>)
     >,
-    mut text_query: Query<&mut Text>
+    mut text_query: Query<&mut Text>,
+    languages: Res<Languages>,
+    locale: Res<Locale>
 ) {
-    for (mut option, interaction, children) in &mut interaction_query {
+    for (entity, mut option, interaction, children) in &mut interaction_query {
         let mut text = text_query.get_mut(children[0]).unwrap();
         match *interaction {
             Interaction::Pressed => {
                 match option.as_mut() {
+                    UiOptions::Language(index) => {
+                        *index = (*index + 1) % languages.0.len();
+                        text.sections[1].value = languages.0[*index].1.clone();
+                    }
                     UiOptions::Mode(index) => {
                         *index = (*index + 1) % ALLOWED_WINDOW_MODES.len();
                         let (mode, string) = ALLOWED_WINDOW_MODES[*index];
-                        text.sections[1].value = string.to_owned();
+                        text.sections[1].value = locale.get(string).to_owned();
                     }
                     UiOptions::VSync(index) => {
                         *index = (*index + 1) % ALLOWED_VSYNC_MODES.len();
                         let (mode, string) = ALLOWED_VSYNC_MODES[*index];
-                        text.sections[1].value = string.to_owned();
+                        text.sections[1].value = locale.get(string).to_owned();
                     }
                     UiOptions::Resolution(index) => {
                         *index = (*index + 1) % ALLOWED_RESOLUTIONS.len();
                         let resolution = ALLOWED_RESOLUTIONS[*index];
                         text.sections[1].value = format!("{}x{}", resolution[0], resolution[1]);
                     }
-                    UiOptions::Volume(value) => {
-                        *value = (*value + 1).clamp(0, 100);
-                        text.sections[1].value = format!("{} %", *value);
+                    UiOptions::RenderScale(index) => {
+                        *index = (*index + 1) % ALLOWED_RENDER_SCALES.len();
+                        text.sections[1].value = ALLOWED_RENDER_SCALES[*index].1.to_owned();
                     }
-                    UiOptions::Spatial(value) => {
+                    UiOptions::LightingQuality(index) => {
+                        let next = index.map_or(0, |index| (index + 1) % ALLOWED_LIGHTING_QUALITIES.len());
+                        *index = Some(next);
+                        text.sections[1].value = locale.get(ALLOWED_LIGHTING_QUALITIES[next].1).to_owned();
+                    }
+                    // changed by their sliders
+                    | UiOptions::Volume(_)
+                    | UiOptions::BusVolume(..)
+                    | UiOptions::CrtIntensity(_)
+                    | UiOptions::RetroPaletteIntensity(_) => {}
+                    | UiOptions::Spatial(value)
+                    | UiOptions::CrtFilter(value)
+                    | UiOptions::ReducedFlashes(value)
+                    | UiOptions::HighContrast(value)
+                    | UiOptions::DamageNumbers(value)
+                    | UiOptions::Gore(value)
+                    | UiOptions::AdaptiveDifficulty(value) => {
                         *value = !*value;
-                        text.sections[1].value = format!("{}", match *value {
-                            true => "on",
-                            false => "off",
-                        });
+                        text.sections[1].value = toggle_text(*value, &locale).to_owned();
+                    }
+                    UiOptions::RetroPalette(index) => {
+                        *index = (*index + 1) % ALLOWED_RETRO_PALETTES.len();
+                        text.sections[1].value = locale.get(ALLOWED_RETRO_PALETTES[*index].1).to_owned();
+                    }
+                    UiOptions::Palette(index) => {
+                        *index = (*index + 1) % ALLOWED_PALETTES.len();
+                        text.sections[1].value = locale.get(ALLOWED_PALETTES[*index].1).to_owned();
+                    }
+                    UiOptions::Control(_, binding) => {
+                        text.sections[1].value = match binding {
+                            Binding::Axis(..) => "press negative key...".to_string(),
+                            _ => "press any input...".to_string(),
+                        };
+                        commands.entity(entity).insert(UiCapturingBinding::default());
                     }
                 }
             }
@@ -1569,61 +3752,115 @@ fn button_next_option(
     }
 }
 
+fn capture_binding(
+    mut commands: Commands,
+    mut capturing_q: Query<(Entity, &mut UiOptions, &mut UiCapturingBinding, &Children)>,
+    mut text_query: Query<&mut Text>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<MouseButton>>
+) {
+    let wheel = mouse_wheel_events
+        .read()
+        .find(|event| event.y != 0.0)
+        .map(|event| {
+            if event.y > 0.0 { MouseWheelDirection::Up } else { MouseWheelDirection::Down }
+        });
+
+    for (entity, mut option, mut capture, children) in capturing_q.iter_mut() {
+        let UiOptions::Control(_, binding) = option.as_mut() else {
+            continue;
+        };
+
+        let mut text = text_query.get_mut(children[0]).unwrap();
+
+        if keys.just_pressed(KeyCode::Escape) {
+            text.sections[1].value = binding.describe();
+            commands.entity(entity).remove::<UiCapturingBinding>();
+            continue;
+        }
+
+        let key = keys.get_just_pressed().next().copied();
+
+        match binding {
+            Binding::Axis(negative, positive) => {
+                let Some(key) = key else {
+                    continue;
+                };
+
+                if capture.step == 0 {
+                    *negative = key;
+                    capture.step += 1;
+                    text.sections[1].value = "press positive key...".to_string();
+                    continue;
+                }
+
+                *positive = key;
+            }
+            _ => {
+                *binding = if let Some(key) = key {
+                    Binding::Key(key)
+                } else if let Some(button) = buttons.get_just_pressed().next() {
+                    Binding::Mouse(*button)
+                } else if let Some(direction) = wheel {
+                    Binding::Wheel(direction)
+                } else {
+                    continue;
+                };
+            }
+        }
+
+        text.sections[1].value = binding.describe();
+        commands.entity(entity).remove::<UiCapturingBinding>();
+    }
+}
+
+fn action_name(action: PlayerActions) -> String {
+    format!("{:?}", action)
+        .chars()
+        .enumerate()
+        .flat_map(|(index, character)| {
+            if index > 0 && character.is_uppercase() {
+                vec![' ', character.to_ascii_lowercase()]
+            } else {
+                vec![character]
+            }
+        })
+        .collect()
+}
+
 fn button_next_option_scroll(
-    mut interaction_query: Query<(&mut UiOptions, &Interaction, &Children), With<Button>>,
+    mut interaction_query: Query<(&mut UiOptions, &Slider, &Interaction, &Children), With<Button>>,
     mut text_query: Query<&mut Text>,
     mut mouse_wheel_events: EventReader<MouseWheel>
 ) {
     for ev in mouse_wheel_events.read() {
         let direction = ev.y.signum() as i32;
 
-        for (mut option, interaction, children) in &mut interaction_query {
+        for (mut option, slider, interaction, children) in &mut interaction_query {
             if *interaction != Interaction::Hovered {
                 continue;
             }
 
+            let Some(value) = option.slider_value_mut() else {
+                continue;
+            };
+
+            *value = slider.clamp(*value + direction);
+
+            let value = *value;
             let mut text = text_query.get_mut(children[0]).unwrap();
-            match option.as_mut() {
-                UiOptions::Volume(value) => {
-                    *value = (*value + direction).clamp(0, 100);
-                    text.sections[1].value = format!("{} %", *value);
-                }
-                _ => {}
-            }
+            text.sections[1].value = slider.text(value);
         }
     }
 }
 
-fn setup_egui(
-    mut contexts: EguiContexts,
-    fonts: Res<FontAssetCollection>,
-    fonts_assets: Res<Assets<FontBytes>>
-) {
+fn setup_egui(mut contexts: EguiContexts) {
     contexts.ctx_mut().style_mut(|style| {
         style.visuals.override_text_color = Some(egui::Color32::WHITE);
         style.visuals.window_fill = egui::Color32::from_rgba_unmultiplied(27, 27, 27, 200);
         style.interaction.selectable_labels = false;
     });
-
-    let font = fonts_assets.get(fonts.ui.clone()).unwrap();
-    let mut fonts_definitions = egui::FontDefinitions::default();
-
-    fonts_definitions.font_data.insert(
-        "pixel font".to_owned(),
-        egui::FontData::from_owned(font.get_bytes().clone())
-    );
-
-    fonts_definitions.families
-        .entry(egui::FontFamily::Proportional)
-        .or_default()
-        .insert(0, "pixel font".to_owned());
-
-    fonts_definitions.families
-        .entry(egui::FontFamily::Monospace)
-        .or_default()
-        .push("pixel font".to_owned());
-
-    contexts.ctx_mut().set_fonts(fonts_definitions);
 }
 
 pub fn egui_has_primary_context(query: Query<&EguiContext, With<PrimaryWindow>>) -> bool {
@@ -1632,6 +3869,9 @@ pub fn egui_has_primary_context(query: Query<&EguiContext, With<PrimaryWindow>>)
 
 fn ui_info_system(
     diagnostics: Res<DiagnosticsStore>,
+    overlay: Res<ProfilerOverlay>,
+    timings: Res<StageTimings>,
+    counters: Res<PerformanceCounters>,
     mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
 ) {
     let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
@@ -1649,7 +3889,7 @@ fn ui_info_system(
             y: -ctx.pixels_per_point() * 8.0,
         })
         .show(ctx, |ui| {
-            ui.set_max_width(ctx.pixels_per_point() * 80.0);
+            ui.set_max_width(ctx.pixels_per_point() * (if overlay.0 { 140.0 } else { 80.0 }));
 
             ui.colored_label(
                 egui::Color32::WHITE,
@@ -1662,12 +3902,46 @@ fn ui_info_system(
                         .unwrap_or(String::from("NaN"))
                 )
             );
+
+            if !overlay.0 {
+                return;
+            }
+
+            ui.separator();
+
+            for stage in Stage::ALL {
+                ui.colored_label(
+                    egui::Color32::WHITE,
+                    format!(
+                        "{}: {}",
+                        stage.name(),
+                        diagnostics
+                            .get(&stage.path())
+                            .and_then(|time| time.smoothed())
+                            .map(|time| format!("{:.2} ms", time))
+                            .unwrap_or(String::from("NaN"))
+                    )
+                );
+            }
+
+            ui.separator();
+
+            for (name, value) in [
+                ("Active chunks", counters.awake_chunks),
+                ("Particles", counters.particles),
+                ("Update rects", timings.update_rects),
+                ("Render rects", timings.render_rects),
+                ("Collider rects", timings.collider_rects),
+            ] {
+                ui.colored_label(egui::Color32::WHITE, format!("{}: {}", name, value));
+            }
         });
 }
 
 fn ui_selected_cell_system(
     q_window: Query<&Window, With<PrimaryWindow>>,
     mut q_camera: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
     registries: Res<Registries>,
     chunk_manager: Res<ChunkManager>,
     mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
@@ -1694,7 +3968,12 @@ fn ui_selected_cell_system(
 
             let Some(world_position) = window
                 .cursor_position()
-                .and_then(|cursor| camera.viewport_to_world(camera_global_transform, cursor))
+                .and_then(|cursor| {
+                    camera.viewport_to_world(
+                        camera_global_transform,
+                        world_target.viewport_position(window, cursor)
+                    )
+                })
                 .map(|ray| ray.origin.truncate())
                 .map(|point| vec2(point.x, point.y))
                 .map(|point| {
@@ -1791,6 +4070,7 @@ fn ui_painter_system(
     brush: Option<ResMut<BrushRes>>,
     object_buffer: Option<ResMut<PainterObjectBuffer>>,
     registries: Res<Registries>,
+    stamps: Res<Stamps>,
     mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
 ) {
     let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
@@ -1913,6 +4193,13 @@ fn ui_painter_system(
 
             ui.add_space(ctx.pixels_per_point() * 8.0);
 
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut brush.layer, BrushLayer::Terrain, "Terrain");
+                ui.radio_value(&mut brush.layer, BrushLayer::Background, "Background");
+            });
+
+            ui.add_space(ctx.pixels_per_point() * 8.0);
+
             egui::ComboBox
                 ::from_label("Shape")
                 .selected_text(match brush.shape {
@@ -1924,6 +4211,8 @@ fn ui_painter_system(
                     ui.selectable_value(&mut brush.shape, BrushShape::Circle, "Circle");
                 });
 
+            ui.checkbox(&mut brush.filled, "Filled");
+
             ui.add_space(ctx.pixels_per_point() * 8.0);
 
             ui.label("Brush size");
@@ -1943,6 +4232,10 @@ fn ui_painter_system(
                     BrushType::Cell => "Cell",
                     BrushType::Object => "Object",
                     BrushType::Particle(_) => "Particle",
+                    BrushType::Stamp(_) => "Stamp",
+                    BrushType::Line => "Line",
+                    BrushType::Rectangle => "Rectangle",
+                    BrushType::Fill => "Fill",
                 })
                 .show_ui(ui, |ui| {
                     if let Some(mut object_buffer) = object_buffer {
@@ -1952,8 +4245,55 @@ fn ui_painter_system(
                     ui.selectable_value(&mut brush.brush_type, BrushType::Cell, "Cell");
                     ui.selectable_value(&mut brush.brush_type, BrushType::Particle(1), "Particle");
                     ui.selectable_value(&mut brush.brush_type, BrushType::Object, "Object");
+                    ui.selectable_value(&mut brush.brush_type, BrushType::Line, "Line");
+                    ui.selectable_value(&mut brush.brush_type, BrushType::Rectangle, "Rectangle");
+                    ui.selectable_value(&mut brush.brush_type, BrushType::Fill, "Fill");
+
+                    if let Some(id) = stamps.keys().sorted().next() {
+                        if !matches!(brush.brush_type, BrushType::Stamp(_)) {
+                            ui.selectable_value(
+                                &mut brush.brush_type,
+                                BrushType::Stamp(id.clone()),
+                                "Stamp"
+                            );
+                        }
+                    }
+                });
+
+            ui.add_space(ctx.pixels_per_point() * 8.0);
+
+            egui::ComboBox
+                ::from_label("Replace")
+                .selected_text(
+                    brush.replace.as_ref().map_or("Anything".to_string(), |material| material.id.clone())
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut brush.replace, None, "Anything");
+
+                    for material in registries.materials
+                        .values()
+                        .sorted_by_key(|material| material.id.to_lowercase()) {
+                        ui.selectable_value(
+                            &mut brush.replace,
+                            Some(material.clone()),
+                            material.id.clone()
+                        );
+                    }
                 });
 
+            if let BrushType::Stamp(selected) = &mut brush.brush_type {
+                ui.add_space(ctx.pixels_per_point() * 8.0);
+
+                egui::ComboBox
+                    ::from_label("Stamp")
+                    .selected_text(selected.clone())
+                    .show_ui(ui, |ui| {
+                        for id in stamps.keys().sorted() {
+                            ui.selectable_value(selected, id.clone(), id);
+                        }
+                    });
+            }
+
             if let BrushType::Particle(size) = &mut brush.brush_type {
                 ui.add_space(ctx.pixels_per_point() * 8.0);
 
@@ -1976,6 +4316,16 @@ pub struct Cell {
     pub object: Object,
 }
 
+impl Cell {
+    pub fn new(object: Object) -> Self {
+        Self {
+            id: Id::new(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()),
+            texture: None,
+            object,
+        }
+    }
+}
+
 const INVENTORY_ROWS: usize = 2;
 const INVENTORY_COLUMNS: usize = 4;
 const INVENTORY_SLOTS: usize = INVENTORY_ROWS * INVENTORY_COLUMNS;
@@ -1985,6 +4335,34 @@ pub struct Inventory {
     pub cells: [Option<Cell>; INVENTORY_SLOTS],
 }
 
+impl Inventory {
+    pub fn is_full(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_some())
+    }
+
+    /// Puts the object into the first empty cell, returns false when every cell is taken
+    pub fn store(&mut self, object: Object) -> bool {
+        let Some(cell) = self.cells.iter_mut().find(|cell| cell.is_none()) else {
+            return false;
+        };
+
+        *cell = Some(Cell::new(object));
+        true
+    }
+
+    /// Takes the object out of the first taken cell, cells are reordered by dragging them around
+    pub fn take_first(&mut self) -> Option<Object> {
+        self.cells
+            .iter_mut()
+            .find_map(|cell| cell.take())
+            .map(|cell| cell.object)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells = Default::default();
+    }
+}
+
 impl FromWorld for Inventory {
     fn from_world(world: &mut World) -> Self {
         let mut initial_cells = vec![];
@@ -2037,10 +4415,11 @@ fn bilinear_filtering(image: &[[u8; 4]], position: Vec2, width: i32, height: i32
 }
 
 fn ui_inventory_system(
-    mut commands: Commands,
     mut inventory: ResMut<Inventory>,
+    mut release_ev: EventWriter<ReleaseObject>,
     window_q: Query<(Entity, &Window), With<PrimaryWindow>>,
     camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
     mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
 ) {
     let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
@@ -2106,32 +4485,19 @@ fn ui_inventory_system(
 
                                     if let Some(position) = window.cursor_position() {
                                         let point = camera
-                                            .viewport_to_world(camera_global_transform, position)
+                                            .viewport_to_world(
+                                                camera_global_transform,
+                                                world_target.viewport_position(window, position)
+                                            )
                                             .map(|ray| ray.origin.truncate())
                                             .unwrap();
 
-                                        let collider_result = cell_option
-                                            .as_ref()
-                                            .unwrap()
-                                            .object.create_collider();
-
-                                        if let Ok(collider) = collider_result {
-                                            let cell = cell_option.take().unwrap();
-                                            commands.spawn(ObjectBundle {
-                                                object: cell.object,
-                                                collider,
-                                                transform: TransformBundle {
-                                                    local: Transform::from_translation(
-                                                        point.extend(0.0)
-                                                    ),
-                                                    ..Default::default()
-                                                },
-                                                mass_properties: ColliderMassProperties::Density(
-                                                    2.0
-                                                ),
-                                                ..Default::default()
-                                            });
-                                        }
+                                        let cell = cell_option.take().unwrap();
+                                        release_ev.send(ReleaseObject {
+                                            object: cell.object,
+                                            player: None,
+                                            target: Some(point),
+                                        });
                                     }
 
                                     return;
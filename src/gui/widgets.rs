@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use bevy::{ ecs::system::EntityCommands, prelude::*, ui::{ FocusPolicy, RelativeCursorPosition } };
+use bevy_tween::{ interpolation::EaseFunction, span_tween::SpanTweenBundle };
+
+use crate::localization::Locale;
+
+use super::UiOptions;
+
+const FONT_SIZE: f32 = 18.0;
+const TRACK_WIDTH: f32 = 128.0;
+const TRACK_HEIGHT: f32 = 6.0;
+const TRACK_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Range of an option changed with a slider, the value itself is kept in its [`UiOptions`]
+#[derive(Component, Clone, Copy)]
+pub struct Slider {
+    pub min: i32,
+    pub max: i32,
+    /// Change of the value on every step with the arrow keys or the d-pad
+    pub step: i32,
+    pub suffix: &'static str,
+}
+
+impl Slider {
+    pub const PERCENT: Slider = Slider {
+        min: 0,
+        max: 100,
+        step: 5,
+        suffix: " %",
+    };
+
+    pub fn clamp(&self, value: i32) -> i32 {
+        value.clamp(self.min, self.max)
+    }
+
+    pub fn text(&self, value: i32) -> String {
+        format!("{}{}", value, self.suffix)
+    }
+
+    fn share(&self, value: i32) -> f32 {
+        ((value - self.min) as f32) / ((self.max - self.min).max(1) as f32)
+    }
+
+    fn value_at(&self, share: f32) -> i32 {
+        self.min + (share.clamp(0.0, 1.0) * ((self.max - self.min) as f32)).round() as i32
+    }
+}
+
+/// Part of the slider that is dragged, placed after the text of the row
+#[derive(Component)]
+pub struct SliderTrack;
+
+#[derive(Component)]
+pub struct SliderFill;
+
+pub fn toggle_text(value: bool, locale: &Locale) -> &str {
+    match value {
+        true => locale.get("common.on"),
+        false => locale.get("common.off"),
+    }
+}
+
+fn option_text(label: String, value: String) -> TextBundle {
+    let style = TextStyle {
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+        ..Default::default()
+    };
+
+    TextBundle::from_sections([
+        TextSection {
+            value: label,
+            style: style.clone(),
+        },
+        TextSection { value, style },
+    ])
+}
+
+/// Row of an option, its first child is the text with the label and the current value
+fn spawn_option<'a>(parent: &'a mut ChildBuilder, option: UiOptions) -> EntityCommands<'a> {
+    parent.spawn((
+        option,
+        ButtonBundle {
+            style: Style {
+                justify_content: JustifyContent::Start,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(8.0),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        },
+        EaseFunction::ExponentialOut,
+        SpanTweenBundle::new(..Duration::from_millis(250)),
+    ))
+}
+
+/// Button with a label and the current value of the option, pressing it cycles through the values
+pub fn spawn_cycle_button(
+    parent: &mut ChildBuilder,
+    option: UiOptions,
+    label: impl Into<String>,
+    value: impl Into<String>
+) {
+    let text = option_text(label.into(), value.into());
+
+    spawn_option(parent, option).with_children(|parent| {
+        parent.spawn((
+            text,
+            EaseFunction::ExponentialOut,
+            SpanTweenBundle::new(..Duration::from_millis(250)),
+        ));
+    });
+}
+
+pub fn spawn_toggle(
+    parent: &mut ChildBuilder,
+    option: UiOptions,
+    label: impl Into<String>,
+    value: bool,
+    locale: &Locale
+) {
+    spawn_cycle_button(parent, option, label, toggle_text(value, locale));
+}
+
+/// Option with a track that can be dragged with the mouse, scrolled over
+/// or stepped with the arrow keys and the d-pad while it is hovered or focused
+pub fn spawn_slider(parent: &mut ChildBuilder, option: UiOptions, label: impl Into<String>, slider: Slider) {
+    let value = option.slider_value().map_or(slider.min, |value| slider.clamp(value));
+    let text = option_text(label.into(), slider.text(value));
+
+    spawn_option(parent, option)
+        .insert(slider)
+        .with_children(|parent| {
+            parent.spawn((
+                text,
+                EaseFunction::ExponentialOut,
+                SpanTweenBundle::new(..Duration::from_millis(250)),
+            ));
+
+            parent
+                .spawn((
+                    SliderTrack,
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(TRACK_WIDTH),
+                            height: Val::Px(TRACK_HEIGHT),
+                            ..default()
+                        },
+                        background_color: TRACK_COLOR.into(),
+                        // the row stays hovered over the track, so it can be scrolled and stepped there too
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    },
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        SliderFill,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(slider.share(value) * 100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+/// Sets the value of the slider from the cursor for as long as its track is held
+pub fn drag_sliders(
+    track_q: Query<(&Interaction, &RelativeCursorPosition, &Parent), With<SliderTrack>>,
+    mut option_q: Query<(&mut UiOptions, &Slider, &Children)>,
+    mut text_q: Query<&mut Text>
+) {
+    for (interaction, cursor, parent) in track_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let (Some(position), Ok((mut option, slider, children))) = (
+            cursor.normalized,
+            option_q.get_mut(parent.get()),
+        ) else {
+            continue;
+        };
+
+        let value = slider.value_at(position.x);
+
+        if option.slider_value() == Some(value) {
+            continue;
+        }
+
+        if let Some(current) = option.slider_value_mut() {
+            *current = value;
+        }
+
+        if let Ok(mut text) = text_q.get_mut(children[0]) {
+            text.sections[1].value = slider.text(value);
+        }
+    }
+}
+
+pub fn step_sliders(
+    mut option_q: Query<(&mut UiOptions, &Slider, &Interaction, &Children)>,
+    mut text_q: Query<&mut Text>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>
+) {
+    let just_pressed = |button_type: GamepadButtonType| {
+        gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    };
+
+    let direction = if keys.just_pressed(KeyCode::ArrowLeft) || just_pressed(GamepadButtonType::DPadLeft) {
+        -1
+    } else if keys.just_pressed(KeyCode::ArrowRight) || just_pressed(GamepadButtonType::DPadRight) {
+        1
+    } else {
+        return;
+    };
+
+    for (mut option, slider, interaction, children) in option_q.iter_mut() {
+        if *interaction != Interaction::Hovered {
+            continue;
+        }
+
+        let Some(value) = option.slider_value_mut() else {
+            continue;
+        };
+
+        *value = slider.clamp(*value + direction * slider.step);
+
+        let value = *value;
+
+        if let Ok(mut text) = text_q.get_mut(children[0]) {
+            text.sections[1].value = slider.text(value);
+        }
+    }
+}
+
+/// Keeps the fill of every slider in line with its value, however it was changed
+pub fn update_slider_fills(
+    option_q: Query<(&UiOptions, &Slider, &Children), Changed<UiOptions>>,
+    track_q: Query<&Children, With<SliderTrack>>,
+    mut fill_q: Query<&mut Style, With<SliderFill>>
+) {
+    for (option, slider, children) in option_q.iter() {
+        let Some(value) = option.slider_value() else {
+            continue;
+        };
+
+        for fill in children
+            .iter()
+            .filter_map(|child| track_q.get(*child).ok())
+            .flat_map(|track| track.iter()) {
+            if let Ok(mut style) = fill_q.get_mut(*fill) {
+                style.width = Val::Percent(slider.share(value) * 100.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::{ prelude::*, utils::HashMap };
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::{
+    assets::StampAssetCollection,
+    constants::CHUNK_SIZE,
+    registries::Registries,
+    simulation::{ chunk_manager::ChunkManager, dirty_rect::DirtyRects, materials::Material, pixel::Pixel },
+};
+
+#[derive(Deserialize, Clone)]
+pub struct PaletteColor {
+    pub color: [u8; 3],
+    pub material_id: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Palette {
+    #[serde(default = "default_tolerance")]
+    pub tolerance: u32,
+    pub colors: Vec<PaletteColor>,
+}
+
+fn default_tolerance() -> u32 {
+    32
+}
+
+impl Palette {
+    pub fn nearest(&self, color: [u8; 3]) -> Option<&PaletteColor> {
+        self.colors
+            .iter()
+            .map(|entry| {
+                let distance = (0..3)
+                    .map(|channel| (color[channel] as i32 - entry.color[channel] as i32).pow(2) as u32)
+                    .sum::<u32>();
+
+                (distance, entry)
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .filter(|(distance, _)| *distance <= self.tolerance.pow(2))
+            .map(|(_, entry)| entry)
+    }
+}
+
+#[derive(Clone)]
+pub struct Stamp {
+    pub size: IVec2,
+    pub pixels: Vec<Option<Pixel>>,
+    pub unmapped: Vec<[u8; 3]>,
+}
+
+impl Stamp {
+    pub fn from_image(
+        image: &Image,
+        palette: &Palette,
+        materials: &HashMap<String, Material>
+    ) -> Result<Self, String> {
+        let size = image.size().as_ivec2();
+
+        if image.data.len() != (size.x * size.y * 4) as usize {
+            return Err("only 8-bit rgba images are supported".to_string());
+        }
+
+        let mut resolved: HashMap<[u8; 3], Option<Pixel>> = HashMap::new();
+        let mut unmapped = vec![];
+        let mut pixels = Vec::with_capacity((size.x * size.y) as usize);
+
+        // images are stored top to bottom, world is bottom to top
+        for y in (0..size.y).rev() {
+            for x in 0..size.x {
+                let index = ((y * size.x + x) * 4) as usize;
+                let [r, g, b, a] = [
+                    image.data[index],
+                    image.data[index + 1],
+                    image.data[index + 2],
+                    image.data[index + 3],
+                ];
+
+                if a < 128 {
+                    pixels.push(None);
+                    continue;
+                }
+
+                let pixel = resolved
+                    .entry([r, g, b])
+                    .or_insert_with(|| {
+                        let pixel = palette
+                            .nearest([r, g, b])
+                            .and_then(|entry| materials.get(&entry.material_id))
+                            .map(Pixel::from);
+
+                        if pixel.is_none() {
+                            unmapped.push([r, g, b]);
+                        }
+
+                        pixel
+                    })
+                    .clone();
+
+                pixels.push(pixel);
+            }
+        }
+
+        Ok(Self { size, pixels, unmapped })
+    }
+
+    pub fn for_each_pixel<F: FnMut(IVec2, &Pixel)>(&self, center: IVec2, mut operation: F) {
+        let origin = center - self.size / 2;
+
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            if let Some(pixel) = pixel {
+                let offset = IVec2::new(index as i32 % self.size.x, index as i32 / self.size.x);
+                operation(origin + offset, pixel);
+            }
+        }
+    }
+
+    pub fn fits(&self, center: IVec2, chunk_manager: &ChunkManager) -> bool {
+        let mut fits = true;
+
+        self.for_each_pixel(center, |position, _| {
+            fits &= chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty());
+        });
+
+        fits
+    }
+
+    pub fn place(
+        &self,
+        center: IVec2,
+        chunk_manager: &mut ChunkManager,
+        dirty_rects: &mut DirtyRects
+    ) {
+        self.for_each_pixel(center, |position, pixel| {
+            if chunk_manager.set(position, Pixel::from(&pixel.material)).is_ok() {
+                dirty_rects.request_update(position);
+                dirty_rects.request_render(position);
+                dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+            }
+        });
+    }
+}
+
+#[derive(Resource, Deref)]
+pub struct Stamps(pub HashMap<String, Stamp>);
+
+impl FromWorld for Stamps {
+    fn from_world(world: &mut World) -> Self {
+        let palette = ron::de
+            ::from_str::<Palette>(&std::fs::read_to_string("palette.ron").unwrap())
+            .unwrap();
+
+        let registries = world.resource::<Registries>();
+        let images = world.resource::<Assets<Image>>();
+        let mut stamps = HashMap::new();
+
+        for (path, handle) in world.resource::<StampAssetCollection>().folder.iter() {
+            let Some(image) = images.get(handle) else {
+                continue;
+            };
+
+            match Stamp::from_image(image, &palette, &registries.materials) {
+                Ok(stamp) => {
+                    if !stamp.unmapped.is_empty() {
+                        warn!(
+                            "{}: {} colors are not in the palette: {}",
+                            path,
+                            stamp.unmapped.len(),
+                            stamp.unmapped
+                                .iter()
+                                .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+                                .join(", ")
+                        );
+                    }
+
+                    stamps.insert(path.clone(), stamp);
+                }
+                Err(error) => {
+                    warn!("{}: failed to import stamp: {}", path, error);
+                }
+            }
+        }
+
+        Self(stamps)
+    }
+}
@@ -0,0 +1,276 @@
+use std::{ collections::VecDeque, time::Duration };
+
+use bevy::{ prelude::*, utils::HashMap, window::PrimaryWindow };
+use bevy_egui::{ egui, EguiContext };
+use bevy_math::{ URect, UVec2 };
+use bevy_rapier2d::geometry::Collider;
+use itertools::Itertools;
+
+use crate::{
+    budget::collider_vertices,
+    camera::{ TrackingCamera, WorldRenderTarget },
+    constants::CHUNK_SIZE,
+    gui::egui_has_primary_context,
+    has_window,
+    simulation::{
+        chunk::ChunkState,
+        chunk_manager::ChunkManager,
+        colliders::ChunkColliderEvent,
+        dirty_rect::{ update_dirty_rects, DirtyRects },
+    },
+    state::GameState,
+};
+
+// passes over the selected chunk that are kept in its dirty rect history
+const HISTORY_LENGTH: usize = 16;
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkInspector>()
+            .add_systems(
+                Update,
+                (
+                    toggle_inspector,
+                    select_inspected_chunk.run_if(has_window).run_if(inspecting_chunks),
+                    draw_inspected_chunk,
+                    ui_inspector_system.run_if(has_window).run_if(egui_has_primary_context),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(OnExit(GameState::Game), |mut inspector: ResMut<ChunkInspector>| {
+                inspector.select(None);
+            });
+    }
+}
+
+/// Debug mode toggled with F11, clicking a chunk selects it for the inspector panel
+#[derive(Resource, Default)]
+pub struct ChunkInspector {
+    pub enabled: bool,
+    pub selected: Option<IVec2>,
+    /// Update rects the selected chunk was simulated with, along with the number of the pass
+    pub history: VecDeque<(u64, URect)>,
+    /// Time the selected chunk took in the last pass it was awake in
+    pub last_update: Option<Duration>,
+    passes: u64,
+}
+
+impl ChunkInspector {
+    pub fn select(&mut self, position: Option<IVec2>) {
+        self.selected = position;
+        self.history.clear();
+        self.last_update = None;
+    }
+
+    /// Called on every pass over the chunks with the update rect of the selected chunk
+    pub fn record_pass(&mut self, rect: Option<URect>, duration: Option<Duration>) {
+        self.passes += 1;
+
+        if let Some(rect) = rect {
+            if self.history.len() == HISTORY_LENGTH {
+                self.history.pop_front();
+            }
+
+            self.history.push_back((self.passes, rect));
+        }
+
+        if duration.is_some() {
+            self.last_update = duration;
+        }
+    }
+}
+
+pub fn inspecting_chunks(inspector: Res<ChunkInspector>) -> bool {
+    inspector.enabled
+}
+
+fn toggle_inspector(mut inspector: ResMut<ChunkInspector>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::F11) {
+        inspector.enabled = !inspector.enabled;
+
+        if !inspector.enabled {
+            inspector.select(None);
+        }
+    }
+}
+
+fn select_inspected_chunk(
+    mut inspector: ResMut<ChunkInspector>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
+    mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // clicks on the panel itself do not change the selection
+    if egui_ctx_q.get_single_mut().is_ok_and(|mut ctx| ctx.get_mut().is_pointer_over_area()) {
+        return;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) = (window_q.get_single(), camera_q.get_single()) else {
+        return;
+    };
+
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|position| {
+            camera.viewport_to_world_2d(camera_transform, world_target.viewport_position(window, position))
+        }) else {
+        return;
+    };
+
+    inspector.select(Some(position.floor().as_ivec2()));
+}
+
+fn draw_inspected_chunk(inspector: Res<ChunkInspector>, mut gizmos: Gizmos) {
+    let Some(position) = inspector.selected else {
+        return;
+    };
+
+    gizmos.rect_2d(position.as_vec2() + Vec2::ONE / 2.0, 0.0, Vec2::ONE, Color::YELLOW);
+}
+
+fn ui_inspector_system(
+    mut inspector: ResMut<ChunkInspector>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut collider_ev: EventWriter<ChunkColliderEvent>,
+    chunk_manager: Res<ChunkManager>,
+    children_q: Query<&Children>,
+    collider_q: Query<&Collider>,
+    mut egui_ctx_q: Query<&mut EguiContext, With<PrimaryWindow>>
+) {
+    let Ok(mut egui_ctx) = egui_ctx_q.get_single_mut() else {
+        return;
+    };
+
+    let ctx = egui_ctx.get_mut();
+
+    let Some(position) = inspector.selected else {
+        if inspector.enabled {
+            egui::Window
+                ::new("Chunk inspector")
+                .auto_sized()
+                .title_bar(false)
+                .anchor(egui::Align2::CENTER_TOP, egui::Vec2 {
+                    x: 0.0,
+                    y: ctx.pixels_per_point() * 8.0,
+                })
+                .show(ctx, |ui| {
+                    ui.label("Click a chunk to inspect it, F11 to exit");
+                });
+        }
+
+        return;
+    };
+
+    let Some((entity, chunk)) = chunk_manager.chunks.get(&position) else {
+        inspector.select(None);
+        return;
+    };
+
+    let mut counts = HashMap::<&str, usize>::default();
+    for pixel in chunk.pixels.iter() {
+        *counts.entry(pixel.material.ui_name.as_str()).or_default() += 1;
+    }
+
+    let collider_vertices = children_q
+        .get(*entity)
+        .map(|children| {
+            children
+                .iter()
+                .filter_map(|child| collider_q.get(*child).ok())
+                .map(|collider| collider_vertices(collider.as_typed_shape()))
+                .sum::<usize>()
+        })
+        .unwrap_or(0);
+
+    let mut open = true;
+
+    egui::Window
+        ::new(format!("Chunk {}, {}", position.x, position.y))
+        .id(egui::Id::new("Chunk inspector"))
+        .open(&mut open)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_TOP, egui::Vec2 {
+            x: ctx.pixels_per_point() * 8.0,
+            y: ctx.pixels_per_point() * 8.0,
+        })
+        .show(ctx, |ui| {
+            ui.set_max_width(ctx.pixels_per_point() * 160.0);
+
+            let state = match chunk.state {
+                ChunkState::Initialized => "Initialized",
+                ChunkState::Generating => "Generating",
+                ChunkState::Populating => "Populating",
+                ChunkState::Active => "Active",
+                ChunkState::Sleeping => "Sleeping",
+            };
+
+            ui.label(format!("State: {}", state));
+            ui.label(format!("Awake: {}", dirty_rects.current.contains_key(&position)));
+            ui.label(format!("Collider vertices: {}", collider_vertices));
+            ui.label(
+                format!(
+                    "Last update: {}",
+                    inspector.last_update
+                        .map(|duration| format!("{:.3} ms", duration.as_secs_f64() * 1000.0))
+                        .unwrap_or(String::from("none"))
+                )
+            );
+
+            ui.separator();
+            ui.label("Pixels");
+
+            for (name, count) in counts.iter().sorted_by_key(|(_, count)| std::cmp::Reverse(**count)) {
+                ui.label(format!("{}: {}", name, count));
+            }
+
+            ui.separator();
+            ui.label("Dirty rects");
+
+            if inspector.history.is_empty() {
+                ui.label("Not updated since selected");
+            }
+
+            for (pass, rect) in inspector.history.iter().rev() {
+                ui.label(
+                    format!(
+                        "#{}: {}, {} - {}, {}",
+                        pass,
+                        rect.min.x,
+                        rect.min.y,
+                        rect.max.x,
+                        rect.max.y
+                    )
+                );
+            }
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Rebuild collider").clicked() {
+                    collider_ev.send(ChunkColliderEvent(position));
+                }
+
+                if ui.button("Redraw texture").clicked() {
+                    update_dirty_rects(&mut dirty_rects.render, position, UVec2::ZERO);
+                    update_dirty_rects(
+                        &mut dirty_rects.render,
+                        position,
+                        UVec2::splat((CHUNK_SIZE - 1) as u32)
+                    );
+                }
+            });
+        });
+
+    if !open {
+        inspector.select(None);
+    }
+}
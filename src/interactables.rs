@@ -0,0 +1,681 @@
+use std::time::Duration;
+
+use bevy::{ prelude::*, render::view::RenderLayers, sprite::Anchor };
+use bevy_math::ivec2;
+use bevy_rapier2d::prelude::*;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::{ actor::Actor, player::{ MiningTool, Player, PlayerActions }, spatial::SpatialIndex },
+    bucket::Carrying,
+    camera::ACTOR_RENDER_LAYER,
+    constants::{ CHUNK_SIZE, DECORATION_Z },
+    despawn_component,
+    generation::level::{ ChestLoot, DoorOnLevel, InteractableKind, InteractableOnLevel },
+    notifications::{ Notification, NotificationKind },
+    registries::Registries,
+    simulation::{
+        chunk_groups::build_chunk_group,
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        object::{ fill_objects, unfill_objects, Object, ObjectBundle },
+        pixel::Pixel,
+    },
+    state::GameState,
+    vehicle::Piloting,
+};
+
+const CHEST_SIZE: IVec2 = IVec2::new(12, 8);
+const CHEST_MATERIAL: &str = "wood";
+const CHEST_LID_MATERIAL: &str = "granite";
+const CHEST_LID_HEIGHT: i32 = 2;
+// loot is spilled into empty pixels in rows above the chest, rows past the limit are skipped
+const LOOT_MAX_ROWS: i32 = 24;
+const INTERACT_DISTANCE: f32 = 24.0;
+
+// sizes are in pixels
+const LEVER_SIZE: Vec2 = Vec2::new(2.0, 8.0);
+const LEVER_ANGLE: f32 = 30.0;
+const LEVER_COLOR: Color = Color::rgb(0.55, 0.35, 0.17);
+const PLATE_SIZE: Vec2 = Vec2::new(12.0, 2.0);
+const PLATE_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+const PLATE_PRESSED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+// objects with fewer pixels are too light to press a plate down
+const PLATE_MIN_PIXELS: usize = 48;
+const KEY_SIZE: Vec2 = Vec2::new(4.0, 4.0);
+const KEY_COLOR: Color = Color::rgb(1.0, 0.84, 0.25);
+const LOCK_SIZE: Vec2 = Vec2::new(3.0, 4.0);
+const LOCK_COLOR: Color = Color::rgb(0.75, 0.6, 0.2);
+const KEY_PICKUP_DISTANCE: f32 = 8.0;
+const TOOL_UPGRADE_SIZE: Vec2 = Vec2::new(5.0, 5.0);
+const TOOL_UPGRADE_COLOR: Color = Color::rgb(0.45, 0.85, 0.95);
+
+// doors open from the bottom row up and close from the top row down
+const DOOR_ROW_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct InteractablesPlugin;
+
+impl Plugin for InteractablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Keys>()
+            .add_systems(OnEnter(GameState::LevelInitialization), (
+                despawn_component::<Interactable>,
+                |mut keys: ResMut<Keys>| {
+                    keys.0 = 0;
+                },
+            ))
+            .add_systems(OnExit(GameState::GameOver), despawn_component::<Interactable>)
+            .add_systems(
+                Update,
+                (
+                    (
+                        interact.before(unlock_doors),
+                        unlock_doors,
+                        pick_up_keys,
+                        pick_up_tool_upgrades,
+                        press_plates,
+                        clear_rooms,
+                    ),
+                    animate_doors,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                FixedUpdate,
+                update_chest_pixels
+                    .after(unfill_objects)
+                    .before(fill_objects)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Marks every entity spawned by decorations, removed along with the level
+#[derive(Component)]
+pub struct Interactable;
+
+#[derive(Component)]
+pub struct Chest {
+    pub loot: Vec<ChestLoot>,
+    pub opened: bool,
+    /// Lid has to be removed from pixels of the object
+    dirty: bool,
+}
+
+#[derive(Component)]
+pub struct Lever {
+    pub door: Entity,
+    pub pulled: bool,
+}
+
+#[derive(Component)]
+pub struct PressurePlate {
+    pub door: Entity,
+    pub pressed: bool,
+}
+
+/// Rectangle of terrain pixels written into the chunks one row at a time,
+/// opening only clears pixels of the door material
+#[derive(Component)]
+pub struct Door {
+    pub min: IVec2,
+    pub size: IVec2,
+    pub material: String,
+    pub open: bool,
+    /// Rows from the bottom that are currently open
+    opened_rows: i32,
+    timer: Timer,
+}
+
+/// Keys picked up on the current level
+#[derive(Resource, Default)]
+pub struct Keys(pub u32);
+
+#[derive(Component)]
+pub struct Key;
+
+#[derive(Component)]
+pub struct ToolUpgrade;
+
+#[derive(Component)]
+pub struct Lock {
+    pub door: Entity,
+}
+
+#[derive(Component)]
+pub struct RoomGate {
+    pub door: Entity,
+    pub radius: f32,
+    /// Gate is only opened after enemies were seen in the room, they spawn once chunks are loaded
+    pub armed: bool,
+}
+
+/// Spawns the interactable of a decoration placed at the given pixel position,
+/// interactables stand on their offset and doors extend from theirs to the top right
+pub fn spawn_interactable(
+    commands: &mut Commands,
+    interactable: &InteractableOnLevel,
+    center: IVec2,
+    registries: &Registries
+) {
+    let position = center + IVec2::from(interactable.offset);
+
+    match &interactable.kind {
+        InteractableKind::Chest { loot } => spawn_chest(commands, position, loot, registries),
+        InteractableKind::Lever { door } => {
+            let door = spawn_door(commands, center, door);
+
+            commands.spawn((
+                Name::new("Lever"),
+                Interactable,
+                Lever { door, pulled: false },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: LEVER_COLOR,
+                        custom_size: Some(LEVER_SIZE / (CHUNK_SIZE as f32)),
+                        anchor: Anchor::BottomCenter,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(
+                        (position.as_vec2() / (CHUNK_SIZE as f32)).extend(DECORATION_Z)
+                    ).with_rotation(Quat::from_rotation_z(LEVER_ANGLE.to_radians())),
+                    ..Default::default()
+                },
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        InteractableKind::PressurePlate { door } => {
+            let door = spawn_door(commands, center, door);
+
+            commands.spawn((
+                Name::new("Pressure plate"),
+                Interactable,
+                PressurePlate { door, pressed: false },
+                marker_sprite(position, PLATE_SIZE, PLATE_COLOR),
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        InteractableKind::Key => {
+            commands.spawn((
+                Name::new("Key"),
+                Interactable,
+                Key,
+                marker_sprite(position, KEY_SIZE, KEY_COLOR),
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        InteractableKind::ToolUpgrade => {
+            commands.spawn((
+                Name::new("Tool upgrade"),
+                Interactable,
+                ToolUpgrade,
+                marker_sprite(position, TOOL_UPGRADE_SIZE, TOOL_UPGRADE_COLOR),
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        InteractableKind::LockedDoor { door } => {
+            let door = spawn_door(commands, center, door);
+
+            commands.spawn((
+                Name::new("Lock"),
+                Interactable,
+                Lock { door },
+                marker_sprite(position, LOCK_SIZE, LOCK_COLOR),
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        InteractableKind::RoomGate { door, radius } => {
+            let door = spawn_door(commands, center, door);
+
+            commands.spawn((
+                Name::new("Room gate"),
+                Interactable,
+                RoomGate {
+                    door,
+                    radius: *radius,
+                    armed: false,
+                },
+                TransformBundle::from_transform(
+                    Transform::from_translation((position.as_vec2() / (CHUNK_SIZE as f32)).extend(0.0))
+                ),
+            ));
+        }
+    }
+}
+
+fn marker_sprite(position: IVec2, size: Vec2, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size / (CHUNK_SIZE as f32)),
+            anchor: Anchor::BottomCenter,
+            ..Default::default()
+        },
+        transform: Transform::from_translation(
+            (position.as_vec2() / (CHUNK_SIZE as f32)).extend(DECORATION_Z)
+        ),
+        ..Default::default()
+    }
+}
+
+/// Doors are closed by `animate_doors` once the level starts
+fn spawn_door(commands: &mut Commands, center: IVec2, door: &DoorOnLevel) -> Entity {
+    commands
+        .spawn((
+            Name::new("Door"),
+            Interactable,
+            Door {
+                min: center + IVec2::from(door.offset),
+                size: IVec2::from(door.size),
+                material: door.material_id.clone(),
+                open: false,
+                opened_rows: door.size.1,
+                timer: Timer::new(DOOR_ROW_INTERVAL, TimerMode::Repeating),
+            },
+        ))
+        .id()
+}
+
+fn chest_pixels(registries: &Registries, opened: bool) -> Vec<Option<Pixel>> {
+    let body = &registries.materials[CHEST_MATERIAL];
+    let lid = &registries.materials[CHEST_LID_MATERIAL];
+
+    (0..CHEST_SIZE.y)
+        .cartesian_product(0..CHEST_SIZE.x)
+        .map(|(y, x)| {
+            let is_wall = y == 0 || x == 0 || x == CHEST_SIZE.x - 1;
+
+            if y >= CHEST_SIZE.y - CHEST_LID_HEIGHT {
+                (!opened).then(|| Pixel::from(lid))
+            } else {
+                is_wall.then(|| Pixel::from(body))
+            }
+        })
+        .collect_vec()
+}
+
+pub fn spawn_chest(
+    commands: &mut Commands,
+    position: IVec2,
+    loot: &[ChestLoot],
+    registries: &Registries
+) {
+    let Ok(object) = Object::from_pixels(chest_pixels(registries, false), CHEST_SIZE) else {
+        return;
+    };
+    let Ok(collider) = object.create_collider() else {
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Chest"),
+        Interactable,
+        Chest {
+            loot: loot.to_vec(),
+            opened: false,
+            dirty: false,
+        },
+        ObjectBundle {
+            object,
+            collider,
+            transform: TransformBundle {
+                local: Transform::from_translation(
+                    ((position.as_vec2() + Vec2::Y * ((CHEST_SIZE.y as f32) / 2.0)) /
+                        (CHUNK_SIZE as f32)).extend(0.0)
+                ),
+                ..Default::default()
+            },
+            mass_properties: ColliderMassProperties::Density(4.0),
+            ..Default::default()
+        },
+    ));
+}
+
+/// Interaction opens the closest chest or pulls the closest lever, unless the player carries a bucket,
+/// the press is consumed so nothing else reacts to it
+#[allow(clippy::type_complexity)]
+pub fn interact(
+    mut player_q: Query<
+        (&Transform, &mut ActionState<PlayerActions>),
+        (With<Player>, Without<Carrying>, Without<Piloting>)
+    >,
+    mut chest_q: Query<(&Transform, &mut Chest, &mut Sleeping), Without<Player>>,
+    mut lever_q: Query<(&mut Transform, &mut Lever), (Without<Player>, Without<Chest>)>,
+    mut door_q: Query<&mut Door>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>
+) {
+    let max_distance = INTERACT_DISTANCE / (CHUNK_SIZE as f32);
+
+    for (player_transform, mut action_state) in player_q.iter_mut() {
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        let player_position = player_transform.translation.xy();
+
+        let chest = chest_q
+            .iter_mut()
+            .filter(|(_, chest, _)| !chest.opened)
+            .map(|(transform, chest, sleeping)| {
+                (transform.translation.xy().distance(player_position), transform, chest, sleeping)
+            })
+            .filter(|(distance, ..)| *distance < max_distance)
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+        let lever = lever_q
+            .iter_mut()
+            .map(|(transform, lever)| {
+                (transform.translation.xy().distance(player_position), transform, lever)
+            })
+            .filter(|(distance, ..)| *distance < max_distance)
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+        match (chest, lever) {
+            (Some((chest_distance, transform, mut chest, mut sleeping)), lever) if
+                lever.as_ref().is_none_or(|(distance, ..)| chest_distance <= *distance)
+            => {
+                action_state.consume(&PlayerActions::Interaction);
+
+                chest.opened = true;
+                chest.dirty = true;
+                sleeping.sleeping = false;
+
+                spill_loot(
+                    &chest.loot,
+                    (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2() +
+                        ivec2(0, CHEST_SIZE.y / 2 + 1),
+                    &mut chunk_manager,
+                    &mut dirty_rects,
+                    &registries
+                );
+            }
+            (_, Some((_, mut transform, mut lever))) => {
+                action_state.consume(&PlayerActions::Interaction);
+
+                lever.pulled = !lever.pulled;
+
+                let angle = if lever.pulled { -LEVER_ANGLE } else { LEVER_ANGLE };
+                transform.rotation = Quat::from_rotation_z(angle.to_radians());
+
+                if let Ok(mut door) = door_q.get_mut(lever.door) {
+                    door.open = lever.pulled;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fills empty pixels row by row, starting right above the opening
+fn spill_loot(
+    loot: &[ChestLoot],
+    origin: IVec2,
+    chunk_manager: &mut ChunkManager,
+    dirty_rects: &mut DirtyRects,
+    registries: &Registries
+) {
+    let mut positions = (0..LOOT_MAX_ROWS)
+        .cartesian_product(-CHEST_SIZE.x / 2..CHEST_SIZE.x / 2)
+        .map(|(y, x)| origin + ivec2(x, y));
+
+    for entry in loot.iter() {
+        let Some(material) = registries.materials.get(&entry.material_id) else {
+            warn!("unknown loot material: {}", entry.material_id);
+            continue;
+        };
+
+        let mut spilled = 0;
+
+        while spilled < entry.amount {
+            let Some(position) = positions.next() else {
+                return;
+            };
+
+            if
+                chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) &&
+                chunk_manager.set(position, Pixel::from(material)).is_ok()
+            {
+                spilled += 1;
+
+                dirty_rects.request_update(position);
+                dirty_rects.request_render(position);
+            }
+        }
+    }
+}
+
+fn update_chest_pixels(mut chest_q: Query<(&mut Chest, &mut Object)>, registries: Res<Registries>) {
+    for (mut chest, mut object) in chest_q.iter_mut() {
+        if !chest.dirty || object.placed {
+            continue;
+        }
+
+        object.pixels = chest_pixels(&registries, chest.opened);
+        object.pixel_count = object.pixels
+            .iter()
+            .filter(|pixel| pixel.is_some())
+            .count();
+        chest.dirty = false;
+    }
+}
+
+/// Plates are pressed by actors and heavy objects resting right above them
+fn press_plates(
+    mut plate_q: Query<(&Transform, &mut PressurePlate, &mut Sprite)>,
+    mut door_q: Query<&mut Door>,
+    actor_q: Query<&Actor>,
+    object_q: Query<(&Transform, &Object), Without<PressurePlate>>
+) {
+    for (transform, mut plate, mut sprite) in plate_q.iter_mut() {
+        let bottom_left =
+            transform.translation.xy() * (CHUNK_SIZE as f32) - Vec2::X * (PLATE_SIZE.x / 2.0);
+        let area = Rect::from_corners(bottom_left, bottom_left + PLATE_SIZE + Vec2::Y * 2.0);
+
+        let pressed =
+            actor_q.iter().any(|actor| {
+                !area.intersect(Rect::from_corners(actor.position, actor.position + actor.size)).is_empty()
+            }) ||
+            object_q.iter().any(|(object_transform, object)| {
+                let center = object_transform.translation.xy() * (CHUNK_SIZE as f32);
+                let half_size = object.size.as_vec2() / 2.0;
+
+                object.pixel_count >= PLATE_MIN_PIXELS &&
+                    !area.intersect(Rect::from_corners(center - half_size, center + half_size)).is_empty()
+            });
+
+        if pressed == plate.pressed {
+            continue;
+        }
+
+        plate.pressed = pressed;
+        sprite.color = if pressed { PLATE_PRESSED_COLOR } else { PLATE_COLOR };
+
+        if let Ok(mut door) = door_q.get_mut(plate.door) {
+            door.open = pressed;
+        }
+    }
+}
+
+/// Clears or fills the next row of every door that is not in its requested state yet
+fn animate_doors(
+    mut door_q: Query<&mut Door>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    for mut door in door_q.iter_mut() {
+        let target = if door.open { door.size.y } else { 0 };
+
+        if door.opened_rows == target || !door.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let Some(material) = registries.materials.get(&door.material) else {
+            continue;
+        };
+
+        let chunk_position = door.min.div_euclid(IVec2::splat(CHUNK_SIZE));
+
+        // door is not loaded yet, rows are written once it is
+        let Some(mut chunk_group) = build_chunk_group(&mut chunk_manager, chunk_position) else {
+            continue;
+        };
+
+        let row = if door.open { door.opened_rows } else { door.opened_rows - 1 };
+
+        for x in 0..door.size.x {
+            let position = door.min + ivec2(x, row);
+
+            let Some(pixel) = chunk_group.get_mut(position - chunk_position * CHUNK_SIZE) else {
+                continue;
+            };
+
+            if door.open {
+                if pixel.material.id != door.material {
+                    continue;
+                }
+                *pixel = Pixel::default();
+            } else {
+                if !pixel.is_empty() {
+                    continue;
+                }
+                *pixel = Pixel::from(material);
+            }
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+
+        door.opened_rows += if door.open { 1 } else { -1 };
+    }
+}
+
+/// Interaction next to a lock opens its door for good if the player has a key
+#[allow(clippy::type_complexity)]
+pub fn unlock_doors(
+    mut commands: Commands,
+    mut player_q: Query<(&Transform, &mut ActionState<PlayerActions>), (With<Player>, Without<Carrying>)>,
+    lock_q: Query<(Entity, &Transform, &Lock), Without<Player>>,
+    mut door_q: Query<&mut Door>,
+    mut keys: ResMut<Keys>,
+    mut notifications: EventWriter<Notification>
+) {
+    let max_distance = INTERACT_DISTANCE / (CHUNK_SIZE as f32);
+
+    for (player_transform, mut action_state) in player_q.iter_mut() {
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        let Some((entity, _, lock)) = lock_q
+            .iter()
+            .find(|(_, transform, _)| {
+                transform.translation.xy().distance(player_transform.translation.xy()) < max_distance
+            }) else {
+            continue;
+        };
+
+        action_state.consume(&PlayerActions::Interaction);
+
+        if keys.0 == 0 {
+            notifications.send(
+                Notification::new("The door is locked").with_kind(NotificationKind::Warning)
+            );
+            continue;
+        }
+
+        keys.0 -= 1;
+        commands.entity(entity).despawn_recursive();
+
+        if let Ok(mut door) = door_q.get_mut(lock.door) {
+            door.open = true;
+        }
+    }
+}
+
+fn pick_up_keys(
+    mut commands: Commands,
+    player_q: Query<&Actor, With<Player>>,
+    key_q: Query<(Entity, &Transform), With<Key>>,
+    mut keys: ResMut<Keys>,
+    mut notifications: EventWriter<Notification>
+) {
+    for (entity, transform) in key_q.iter() {
+        let position = transform.translation.xy() * (CHUNK_SIZE as f32);
+
+        let picked_up = player_q.iter().any(|actor| {
+            let closest = position.clamp(actor.position, actor.position + actor.size);
+            closest.distance(position) < KEY_PICKUP_DISTANCE
+        });
+
+        if !picked_up {
+            continue;
+        }
+
+        keys.0 += 1;
+        commands.entity(entity).despawn_recursive();
+        notifications.send(Notification::new(format!("Picked up a key ({})", keys.0)));
+    }
+}
+
+/// Upgrades are shared, every player can carve the harder materials once one is picked up
+fn pick_up_tool_upgrades(
+    mut commands: Commands,
+    mut player_q: Query<(&Actor, &mut MiningTool), With<Player>>,
+    upgrade_q: Query<(Entity, &Transform), With<ToolUpgrade>>,
+    mut notifications: EventWriter<Notification>
+) {
+    for (entity, transform) in upgrade_q.iter() {
+        let position = transform.translation.xy() * (CHUNK_SIZE as f32);
+
+        let picked_up = player_q.iter().any(|(actor, _)| {
+            let closest = position.clamp(actor.position, actor.position + actor.size);
+            closest.distance(position) < KEY_PICKUP_DISTANCE
+        });
+
+        if !picked_up {
+            continue;
+        }
+
+        let mut tier = 0;
+        for (_, mut mining_tool) in player_q.iter_mut() {
+            mining_tool.tier += 1;
+            tier = tier.max(mining_tool.tier);
+        }
+
+        commands.entity(entity).despawn_recursive();
+        notifications.send(Notification::new(format!("Mining tool upgraded (tier {})", tier)));
+    }
+}
+
+/// Gates open once the last enemy in their room is dead, they do not close again
+fn clear_rooms(
+    mut gate_q: Query<(&Transform, &mut RoomGate)>,
+    mut door_q: Query<&mut Door>,
+    index: Res<SpatialIndex>
+) {
+    for (transform, mut gate) in gate_q.iter_mut() {
+        let position = transform.translation.xy() * (CHUNK_SIZE as f32);
+
+        let occupied = index.in_radius(position, gate.radius).next().is_some();
+
+        if occupied {
+            gate.armed = true;
+            continue;
+        }
+
+        if !gate.armed {
+            continue;
+        }
+
+        if let Ok(mut door) = door_q.get_mut(gate.door) {
+            door.open = true;
+        }
+    }
+}
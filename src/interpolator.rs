@@ -91,6 +91,19 @@ impl Interpolator for InterpolateSize {
     }
 }
 
+pub struct InterpolateSpriteSize {
+    pub start: Vec2,
+    pub end: Vec2,
+}
+
+impl Interpolator for InterpolateSpriteSize {
+    type Item = Sprite;
+
+    fn interpolate(&self, item: &mut Self::Item, value: f32) {
+        item.custom_size = Some(self.start.lerp(self.end, value));
+    }
+}
+
 pub struct InterpolateBackgroundColor {
     pub start: Color,
     pub end: Color,
@@ -138,6 +151,7 @@ impl Plugin for InterpolatorPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(DefaultTweenPlugins)
             .add_tween_systems(component_tween_system::<InterpolateSize>())
+            .add_tween_systems(component_tween_system::<InterpolateSpriteSize>())
             .add_tween_systems(component_tween_system::<InterpolateBackgroundColor>())
             .add_tween_systems(component_tween_system::<InterpolatePadding>())
             .add_tween_systems(component_tween_system::<InterpolateTopOffset>())
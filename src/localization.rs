@@ -0,0 +1,221 @@
+use std::{ fmt::Display, path::Path };
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_egui::{ egui, EguiContexts };
+use bevy_persistent::Persistent;
+use serde::Deserialize;
+
+use crate::{
+    assets::{ process_assets, FontAssetCollection, FontBytes },
+    settings::Config,
+    state::GameState,
+};
+
+const ASSETS_PATH: &str = "assets";
+const LOCALES_PATH: &str = "assets/locales";
+// strings missing from a language file are taken from this one
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Languages(find_languages()))
+            .add_systems(Startup, load_locale)
+            .add_systems(OnExit(GameState::LoadingAssets), apply_text_font.after(process_assets))
+            .add_systems(
+                Update,
+                (
+                    switch_language.run_if(resource_changed::<Persistent<Config>>),
+                    apply_egui_fonts.run_if(
+                        resource_exists::<FontAssetCollection>.and_then(resource_changed::<Locale>)
+                    ),
+                ).chain()
+            );
+    }
+}
+
+#[derive(Deserialize)]
+struct LanguageFile {
+    name: String,
+    /// Font used instead of the pixel font, relative to the assets folder
+    #[serde(default)]
+    font: Option<String>,
+    strings: HashMap<String, String>,
+}
+
+fn read_language(id: &str) -> Option<LanguageFile> {
+    let path = Path::new(LOCALES_PATH).join(format!("{}.ron", id));
+
+    let contents = std::fs
+        ::read_to_string(&path)
+        .map_err(|error| warn!("failed to read language {}: {}", path.display(), error))
+        .ok()?;
+
+    ron::de
+        ::from_str::<LanguageFile>(&contents)
+        .map_err(|error| warn!("failed to parse language {}: {}", path.display(), error))
+        .ok()
+}
+
+/// Ids and names of the language files found in the locales folder, sorted by id
+#[derive(Resource)]
+pub struct Languages(pub Vec<(String, String)>);
+
+fn find_languages() -> Vec<(String, String)> {
+    let mut languages = std::fs
+        ::read_dir(LOCALES_PATH)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+
+            if path.extension()? != "ron" {
+                return None;
+            }
+
+            let id = path.file_stem()?.to_str()?.to_string();
+            let file = read_language(&id)?;
+
+            Some((id, file.name))
+        })
+        .collect::<Vec<_>>();
+
+    languages.sort_by(|(id_1, _), (id_2, _)| id_1.cmp(id_2));
+
+    if languages.is_empty() {
+        languages.push((DEFAULT_LANGUAGE.to_string(), "English".to_string()));
+    }
+
+    languages
+}
+
+/// Strings of the selected language, keys missing from it fall back to the default language
+/// and then to the key itself
+#[derive(Resource, Default)]
+pub struct Locale {
+    pub id: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+    font: Option<Vec<u8>>,
+}
+
+impl Locale {
+    pub fn load(id: &str) -> Locale {
+        let fallback = read_language(DEFAULT_LANGUAGE).map(|file| file.strings).unwrap_or_default();
+
+        let Some(file) = read_language(id) else {
+            return Locale {
+                id: id.to_string(),
+                fallback,
+                ..Default::default()
+            };
+        };
+
+        let font = file.font.and_then(|font| {
+            let path = Path::new(ASSETS_PATH).join(font);
+
+            std::fs
+                ::read(&path)
+                .map_err(|error| warn!("failed to read font {}: {}", path.display(), error))
+                .ok()
+        });
+
+        Locale {
+            id: id.to_string(),
+            strings: file.strings,
+            fallback,
+            font,
+        }
+    }
+
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.get_or(key, key)
+    }
+
+    /// Used for data files that carry their own English text, like perks
+    pub fn get_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map_or(default, |string| string.as_str())
+    }
+
+    /// Replaces every `{}` of the string with the next argument
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        let mut text = String::new();
+
+        for (index, part) in self.get(key).split("{}").enumerate() {
+            if let Some(arg) = index.checked_sub(1).and_then(|index| args.get(index)) {
+                text.push_str(&arg.to_string());
+            }
+
+            text.push_str(part);
+        }
+
+        text
+    }
+}
+
+fn load_locale(mut commands: Commands, config: Res<Persistent<Config>>) {
+    commands.insert_resource(Locale::load(&config.language));
+}
+
+/// Screens pick up the new strings the next time they are opened
+fn switch_language(mut locale: ResMut<Locale>, config: Res<Persistent<Config>>) {
+    if locale.id != config.language {
+        *locale = Locale::load(&config.language);
+    }
+}
+
+/// Text of the interface is laid out with the font of the language it was started with,
+/// the font of a language picked later is used after a restart
+fn apply_text_font(locale: Res<Locale>, mut fonts: ResMut<Assets<Font>>) {
+    let Some(bytes) = locale.font.clone() else {
+        return;
+    };
+
+    match Font::try_from_bytes(bytes) {
+        Ok(font) => {
+            fonts.insert(TextStyle::default().font, font);
+        }
+        Err(error) => warn!("failed to load font of language {}: {}", locale.id, error),
+    }
+}
+
+/// The pixel font stays first, glyphs missing from it are taken from the font of the language
+fn apply_egui_fonts(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    fonts: Res<FontAssetCollection>,
+    fonts_assets: Res<Assets<FontBytes>>
+) {
+    let Some(font) = fonts_assets.get(fonts.ui.clone()) else {
+        return;
+    };
+
+    let mut fonts_definitions = egui::FontDefinitions::default();
+
+    fonts_definitions.font_data.insert(
+        "pixel font".to_owned(),
+        egui::FontData::from_owned(font.get_bytes().clone())
+    );
+
+    let mut families = vec!["pixel font".to_owned()];
+
+    if let Some(bytes) = locale.font.clone() {
+        fonts_definitions.font_data.insert("locale font".to_owned(), egui::FontData::from_owned(bytes));
+        families.push("locale font".to_owned());
+    }
+
+    for (index, family) in families.into_iter().enumerate() {
+        fonts_definitions.families
+            .entry(egui::FontFamily::Proportional)
+            .or_default()
+            .insert(index, family.clone());
+
+        fonts_definitions.families.entry(egui::FontFamily::Monospace).or_default().push(family);
+    }
+
+    contexts.ctx_mut().set_fonts(fonts_definitions);
+}
@@ -22,17 +22,65 @@ mod postprocessing;
 mod cursor;
 mod settings;
 mod interpolator;
+mod importer;
+mod minimap;
+mod world_map;
+mod escalation;
+mod exit_puzzle;
+mod bucket;
+mod budget;
+mod fuzz;
+mod score_export;
+mod warmup;
+mod weather;
+mod parallax;
+mod perks;
+mod progression;
+mod daily;
+mod capture;
+mod sounds;
+mod ambience;
+mod music;
+mod accessibility;
+mod difficulty;
+mod notifications;
+mod interactables;
+mod traps;
+mod grenade;
+mod profiler;
+mod trace;
+mod inspector;
+mod curses;
+mod arenas;
+mod stains;
+mod localization;
+mod tutorial;
+mod cutscene;
+mod presence;
+mod object_inventory;
+mod vehicle;
+mod teleporters;
+mod fog;
+mod thumbnails;
+mod world_events;
+mod gameplay;
+mod storage;
+#[cfg(feature = "network")]
+mod network;
+#[cfg(feature = "discord")]
+mod discord;
 
 use std::time::Duration;
 
 use actors::ActorsPlugin;
 use animation::AnimationPlugin;
 use assets::{
-    process_assets, AudioAssetCollection, FontAssetCollection, FontAssetLoader, FontBytes, LayoutAssetCollection, SpriteAssetCollection
+    process_assets, AudioAssetCollection, FontAssetCollection, FontAssetLoader, FontBytes, LayoutAssetCollection, SpriteAssetCollection, StampAssetCollection
 };
 use bevy::{
     audio::{AudioPlugin, SpatialScale},
     diagnostic::FrameTimeDiagnosticsPlugin,
+    log::LogPlugin,
     prelude::*,
     render::{ settings::{ PowerPreference, WgpuSettings }, RenderPlugin },
     window::{ Cursor, PresentMode, PrimaryWindow, WindowMode, WindowResolution },
@@ -53,7 +101,14 @@ use cursor::{ move_cursor, setup_cursor };
 use gui::GuiPlugin;
 
 use helpers::{ tick_despawn_timer, DespawnTimer };
+use importer::Stamps;
 use interpolator::{ InterpolateVolume, InterpolatorPlugin };
+use minimap::MinimapPlugin;
+use world_map::WorldMapPlugin;
+use escalation::EscalationPlugin;
+use exit_puzzle::ExitPuzzlePlugin;
+use bucket::BucketPlugin;
+use score_export::ScoreExportPlugin;
 use painter::PainterPlugin;
 
 use postprocessing::PostProcessPlugin;
@@ -62,9 +117,53 @@ use seldom_state::StateMachinePlugin;
 use settings::{ process_config, SettingsPlugin };
 use simulation::SimulationPlugin;
 use state::{ state_auto_transition, GameState };
+use warmup::WarmupPlugin;
+use weather::WeatherPlugin;
+use parallax::ParallaxPlugin;
+use progression::ProgressionPlugin;
+use daily::DailyPlugin;
+use capture::CapturePlugin;
+use sounds::SoundsPlugin;
+use ambience::AmbiencePlugin;
+use music::MusicPlugin;
+use accessibility::AccessibilityPlugin;
+use difficulty::DifficultyPlugin;
+use notifications::NotificationsPlugin;
+use interactables::InteractablesPlugin;
+use traps::TrapsPlugin;
+use grenade::GrenadePlugin;
+use profiler::ProfilerPlugin;
+use trace::{ record_spans, TracePlugin };
+use inspector::InspectorPlugin;
+use curses::CursesPlugin;
+use arenas::ArenasPlugin;
+use stains::StainsPlugin;
+use localization::LocalizationPlugin;
+use tutorial::TutorialPlugin;
+use cutscene::CutscenePlugin;
+use presence::PresencePlugin;
+use object_inventory::ObjectInventoryPlugin;
+use vehicle::VehiclePlugin;
+use teleporters::TeleportersPlugin;
+use fog::FogPlugin;
+use thumbnails::ThumbnailsPlugin;
+use world_events::WorldEventsPlugin;
+use storage::StoragePlugin;
 
 fn main() {
-    App::new()
+    if let Some(index) = std::env::args().position(|arg| arg == "--fuzz") {
+        fuzz::run(std::env::args().skip(index + 1));
+        return;
+    }
+
+    if let Some(index) = std::env::args().position(|arg| arg == "--export-scores") {
+        score_export::run(std::env::args().skip(index + 1));
+        return;
+    }
+
+    let mut app = App::new();
+
+    app
         .add_plugins((
             DefaultPlugins
                 .set(AudioPlugin {
@@ -72,6 +171,10 @@ fn main() {
                     ..Default::default()
                 })
                 .set(ImagePlugin::default_nearest())
+                .set(LogPlugin {
+                    update_subscriber: Some(record_spans),
+                    ..Default::default()
+                })
                 .set(RenderPlugin {
                     render_creation: bevy::render::settings::RenderCreation::Automatic(
                         WgpuSettings {
@@ -119,6 +222,50 @@ fn main() {
             GuiPlugin,
             PostProcessPlugin,
             SettingsPlugin,
+            MinimapPlugin,
+            WorldMapPlugin,
+            EscalationPlugin,
+            ExitPuzzlePlugin,
+            ScoreExportPlugin,
+            BucketPlugin,
+        ))
+        .add_plugins((
+            WarmupPlugin,
+            WeatherPlugin,
+            ParallaxPlugin,
+            ProgressionPlugin,
+            DailyPlugin,
+            CapturePlugin,
+            SoundsPlugin,
+            AmbiencePlugin,
+            MusicPlugin,
+            AccessibilityPlugin,
+            DifficultyPlugin,
+            NotificationsPlugin,
+            InteractablesPlugin,
+            TrapsPlugin,
+            GrenadePlugin,
+        ))
+        .add_plugins((
+            ProfilerPlugin,
+            TracePlugin,
+            InspectorPlugin,
+            CursesPlugin,
+            ArenasPlugin,
+            StainsPlugin,
+            LocalizationPlugin,
+            TutorialPlugin,
+            CutscenePlugin,
+            PresencePlugin,
+            ObjectInventoryPlugin,
+            VehiclePlugin,
+            TeleportersPlugin,
+            FogPlugin,
+            ThumbnailsPlugin,
+        ))
+        .add_plugins((
+            WorldEventsPlugin,
+            StoragePlugin,
         ))
         .insert_resource(RapierConfiguration::new(0.1))
         .insert_resource(ClearColor(Color::BLACK))
@@ -129,6 +276,7 @@ fn main() {
             LoadingState::new(GameState::LoadingAssets)
                 .load_collection::<FontAssetCollection>()
                 .load_collection::<LayoutAssetCollection>()
+                .load_collection::<StampAssetCollection>()
                 .load_collection::<SpriteAssetCollection>()
                 .load_collection::<AudioAssetCollection>()
                 .continue_to_state(GameState::Menu)
@@ -139,10 +287,20 @@ fn main() {
             process_config,
             move |mut commands: Commands| {
                 commands.init_resource::<Registries>();
+                commands.init_resource::<Stamps>();
             },
         ))
-        .add_systems(Update, (state_auto_transition, tick_despawn_timer, move_cursor))
-        .run();
+        .add_systems(Update, (state_auto_transition, tick_despawn_timer, move_cursor));
+
+    #[cfg(feature = "network")]
+    if let Some(plugin) = network::NetworkPlugin::from_args(std::env::args()) {
+        app.add_plugins(plugin);
+    }
+
+    #[cfg(feature = "discord")]
+    app.add_plugins(discord::DiscordPlugin);
+
+    app.run();
 }
 
 pub fn has_window(query: Query<&Window, With<PrimaryWindow>>) -> bool {
@@ -0,0 +1,327 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{ Extent3d, TextureDimension, TextureFormat },
+    },
+    utils::HashSet,
+};
+use bevy_math::URect;
+
+use crate::{
+    actors::{ enemy::Enemy, player::Player },
+    assets::SpriteAssetCollection,
+    constants::CHUNK_SIZE,
+    despawn_component,
+    generation::{ Exit, LevelData },
+    simulation::{
+        chunk::{ ChunkData, ChunkState },
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        render_dirty_rect_updates,
+    },
+    state::GameState,
+};
+
+// amount of world pixels per minimap pixel
+pub const MINIMAP_SCALE: i32 = 4;
+pub const MINIMAP_CHUNK_SIZE: i32 = CHUNK_SIZE / MINIMAP_SCALE;
+
+const UNEXPLORED_COLOR: [u8; 4] = [16, 16, 16, 200];
+const EXPLORED_AIR_COLOR: [u8; 4] = [0, 0, 0, 120];
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExploredChunks>()
+            .init_resource::<PointsOfInterest>()
+            .add_systems(OnEnter(GameState::Game), setup_minimap)
+            .add_systems(OnExit(GameState::Game), despawn_component::<UiMinimap>)
+            .add_systems(
+                Update,
+                (explore_chunks, update_minimap_markers).run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                PostUpdate,
+                update_minimap
+                    .before(render_dirty_rect_updates)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Chunks that were visible to the player during the current level
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ExploredChunks(pub HashSet<IVec2>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PointOfInterestKind {
+    Shrine,
+    Arena,
+    Teleporter,
+}
+
+pub struct PointOfInterest {
+    pub position: Vec2,
+    pub kind: PointOfInterestKind,
+}
+
+/// Notable places of the current level, shown on the world map once their chunk is explored
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct PointsOfInterest(pub Vec<PointOfInterest>);
+
+#[derive(Resource)]
+pub struct Minimap {
+    pub texture: Handle<Image>,
+    pub bounds: IRect,
+}
+
+impl Minimap {
+    pub fn draw_chunk(&self, images: &mut Assets<Image>, position: IVec2, chunk: &ChunkData) {
+        self.draw_chunk_part(
+            images,
+            position,
+            chunk,
+            URect::from_corners(UVec2::ZERO, UVec2::splat(CHUNK_SIZE as u32))
+        );
+    }
+
+    pub fn draw_chunk_part(
+        &self,
+        images: &mut Assets<Image>,
+        position: IVec2,
+        chunk: &ChunkData,
+        rect: URect
+    ) {
+        if !self.bounds.contains(position) || chunk.pixels.is_empty() {
+            return;
+        }
+
+        let Some(image) = images.get_mut(self.texture.clone()) else {
+            return;
+        };
+
+        let width = self.bounds.width() * MINIMAP_CHUNK_SIZE;
+        let offset = IVec2::new(
+            (position.x - self.bounds.min.x) * MINIMAP_CHUNK_SIZE,
+            (self.bounds.max.y - 1 - position.y) * MINIMAP_CHUNK_SIZE
+        );
+
+        let min = rect.min.as_ivec2() / MINIMAP_SCALE;
+        let max = (rect.max.as_ivec2() + MINIMAP_SCALE - 1) / MINIMAP_SCALE;
+
+        for x in min.x..max.x {
+            for y in min.y..max.y {
+                let pixel = &chunk.pixels
+                    [(y * MINIMAP_SCALE * CHUNK_SIZE + x * MINIMAP_SCALE) as usize];
+
+                let color = if pixel.is_empty() { EXPLORED_AIR_COLOR } else { pixel.color };
+                let index = (
+                    (offset.y + MINIMAP_CHUNK_SIZE - 1 - y) * width + offset.x + x
+                ) as usize;
+
+                image.data[index * 4..(index + 1) * 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Converts world position into normalized minimap coordinates with origin at top left corner
+    pub fn normalize(&self, position: Vec2) -> Vec2 {
+        let relative = (position - self.bounds.min.as_vec2()) / self.bounds.size().as_vec2();
+        Vec2::new(relative.x, 1.0 - relative.y)
+    }
+}
+
+#[derive(Component)]
+pub struct UiMinimap;
+
+#[derive(Component)]
+pub struct UiMinimapTexture;
+
+#[derive(Component)]
+pub struct UiMinimapMarker(pub Entity);
+
+fn setup_minimap(
+    mut commands: Commands,
+    mut explored: ResMut<ExploredChunks>,
+    mut images: ResMut<Assets<Image>>,
+    level_data: Res<LevelData>,
+    sprites: Res<SpriteAssetCollection>
+) {
+    let size = images.get(level_data.1.clone()).unwrap().size().as_ivec2() / CHUNK_SIZE;
+    let bounds = IRect::from_center_size(IVec2::ZERO, size);
+    let texture_size = (bounds.size() * MINIMAP_CHUNK_SIZE).as_uvec2();
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: texture_size.x,
+            height: texture_size.y,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        &UNEXPLORED_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all()
+    );
+    image.sampler = bevy::render::texture::ImageSampler::nearest();
+
+    let texture = images.add(image);
+
+    explored.clear();
+    commands.insert_resource(Minimap {
+        texture: texture.clone(),
+        bounds,
+    });
+
+    commands
+        .spawn((
+            Name::new("Minimap"),
+            UiMinimap,
+            ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    width: Val::Px(160.0),
+                    height: Val::Px(160.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                image: sprites.in_game_border.clone().into(),
+                ..default()
+            },
+            ImageScaleMode::Sliced(TextureSlicer {
+                border: BorderRect::square(10.0),
+                center_scale_mode: SliceScaleMode::Stretch,
+                sides_scale_mode: SliceScaleMode::Stretch,
+                max_corner_scale: 1.0,
+            }),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                UiMinimapTexture,
+                ImageBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    image: texture.into(),
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn explore_chunks(
+    mut explored: ResMut<ExploredChunks>,
+    mut images: ResMut<Assets<Image>>,
+    chunk_manager: Res<ChunkManager>,
+    minimap: Res<Minimap>
+) {
+    for (position, (_, chunk)) in chunk_manager.chunks.iter() {
+        if chunk.state != ChunkState::Active || explored.contains(position) {
+            continue;
+        }
+
+        explored.insert(*position);
+        minimap.draw_chunk(&mut images, *position, chunk);
+    }
+}
+
+fn update_minimap(
+    mut images: ResMut<Assets<Image>>,
+    dirty_rects: Res<DirtyRects>,
+    explored: Res<ExploredChunks>,
+    chunk_manager: Res<ChunkManager>,
+    minimap: Res<Minimap>
+) {
+    for (position, rect) in dirty_rects.render.iter() {
+        if !explored.contains(position) {
+            continue;
+        }
+
+        if let Some(chunk) = chunk_manager.get_chunk_data(position) {
+            minimap.draw_chunk_part(&mut images, *position, chunk, *rect);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_minimap_markers(
+    mut commands: Commands,
+    target_q: Query<
+        (Entity, &GlobalTransform, Has<Player>, Has<Exit>),
+        Or<(With<Player>, With<Exit>, With<Enemy>)>
+    >,
+    mut marker_q: Query<(Entity, &UiMinimapMarker, &mut Style, &mut Visibility)>,
+    minimap_q: Query<Entity, With<UiMinimapTexture>>,
+    explored: Res<ExploredChunks>,
+    minimap: Res<Minimap>
+) {
+    let Ok(minimap_entity) = minimap_q.get_single() else {
+        return;
+    };
+
+    let mut tracked = HashSet::new();
+    for (entity, marker, mut style, mut visibility) in marker_q.iter_mut() {
+        let Ok((_, transform, is_player, is_exit)) = target_q.get(marker.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        tracked.insert(marker.0);
+
+        let position = transform.translation().xy();
+        let normalized = minimap.normalize(position);
+
+        style.left = Val::Percent(normalized.x * 100.0);
+        style.top = Val::Percent(normalized.y * 100.0);
+
+        *visibility = if
+            is_player ||
+            is_exit ||
+            explored.contains(&position.floor().as_ivec2())
+        {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    for (entity, _, is_player, is_exit) in target_q.iter() {
+        if tracked.contains(&entity) {
+            continue;
+        }
+
+        let (color, size) = if is_player {
+            (Color::WHITE, 6.0)
+        } else if is_exit {
+            (Color::rgb_u8(0xb0, 0x6c, 0xff), 6.0)
+        } else {
+            (Color::rgb_u8(0xe0, 0x30, 0x30), 4.0)
+        };
+
+        let marker = commands
+            .spawn((
+                UiMinimapMarker(entity),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(size),
+                        height: Val::Px(size),
+                        margin: UiRect::all(Val::Px(-size / 2.0)),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    visibility: Visibility::Hidden,
+                    z_index: ZIndex::Local(if is_player { 2 } else { 1 }),
+                    ..default()
+                },
+            ))
+            .id();
+
+        commands.entity(minimap_entity).add_child(marker);
+    }
+}
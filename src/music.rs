@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use bevy::{ audio::{ PlaybackMode, Volume }, prelude::* };
+use bevy_persistent::Persistent;
+use bevy_tween::{ interpolation::EaseFunction, span_tween::SpanTweenerBundle, tween::ComponentTween };
+
+use crate::{
+    actors::{ health::Health, player::Player, spatial::SpatialIndex },
+    assets::AudioAssetCollection,
+    constants::CHUNK_SIZE,
+    generation::{ Ambient, LevelData },
+    interpolator::InterpolateVolume,
+    settings::{ AudioBus, Config },
+    state::GameState,
+};
+
+// enemies closer than this in pixels put the music into combat
+const COMBAT_RANGE: f32 = 160.0;
+// seconds without enemies nearby before the music goes back to exploration
+const COMBAT_COOLDOWN: f32 = 4.0;
+const CROSSFADE: Duration = Duration::from_secs(2);
+// exploration keeps playing quietly under the combat layer
+const EXPLORATION_COMBAT_VOLUME: f32 = 0.25;
+// share of the total health under which the sting is played
+const LOW_HEALTH: f32 = 0.25;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicDirector>()
+            .add_systems(OnEnter(GameState::Game), spawn_music_layers)
+            .add_systems(
+                Update,
+                (update_music_state, crossfade_music_layers, play_low_health_sting)
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MusicState {
+    #[default]
+    Exploration,
+    Combat,
+}
+
+/// Picks which of the layered tracks of the level is heard
+#[derive(Resource, Default)]
+pub struct MusicDirector {
+    pub state: MusicState,
+    /// Seconds since an enemy was last nearby
+    pub calm: f32,
+    /// Set once the sting was played, cleared when health goes back up
+    pub low_health: bool,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum MusicLayer {
+    /// Always plays
+    Exploration,
+    /// Fades in when enemies are nearby
+    Combat,
+}
+
+impl MusicLayer {
+    fn volume(&self, state: MusicState) -> f32 {
+        match (self, state) {
+            (MusicLayer::Exploration, MusicState::Exploration) => 1.0,
+            (MusicLayer::Exploration, MusicState::Combat) => EXPLORATION_COMBAT_VOLUME,
+            (MusicLayer::Combat, MusicState::Exploration) => 0.0,
+            (MusicLayer::Combat, MusicState::Combat) => 1.0,
+        }
+    }
+}
+
+fn spawn_music_layers(
+    mut commands: Commands,
+    mut director: ResMut<MusicDirector>,
+    level: Res<LevelData>,
+    asset_server: Res<AssetServer>
+) {
+    *director = MusicDirector::default();
+
+    let layers = std::iter
+        ::once((MusicLayer::Exploration, Some(level.0.ambient.clone())))
+        .chain(std::iter::once((MusicLayer::Combat, level.0.combat_music.clone())));
+
+    for (layer, path) in layers {
+        let Some(path) = path else {
+            continue;
+        };
+
+        commands.spawn((
+            Ambient,
+            layer,
+            AudioBus::Music,
+            AudioBundle {
+                source: asset_server.load(path),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(layer.volume(MusicState::Exploration)),
+                    ..Default::default()
+                },
+            },
+        ));
+    }
+}
+
+fn update_music_state(
+    mut director: ResMut<MusicDirector>,
+    player_q: Query<&Transform, With<Player>>,
+    index: Res<SpatialIndex>,
+    time: Res<Time>
+) {
+    let engaged = player_q.iter().any(|player| {
+        index
+            .in_radius(player.translation.xy() * (CHUNK_SIZE as f32), COMBAT_RANGE)
+            .next()
+            .is_some()
+    });
+
+    if engaged {
+        director.calm = 0.0;
+    } else {
+        director.calm += time.delta_seconds();
+    }
+
+    let state = if engaged {
+        MusicState::Combat
+    } else if director.calm >= COMBAT_COOLDOWN {
+        MusicState::Exploration
+    } else {
+        director.state
+    };
+
+    director.state = state;
+}
+
+/// Layers only get a new tween when the state or the volume settings change, the tween replaces the one in progress,
+/// changes are kept pending until the track of every layer is loaded and has a sink
+fn crossfade_music_layers(
+    mut commands: Commands,
+    mut applied: Local<MusicState>,
+    mut volume_changed: Local<bool>,
+    director: Res<MusicDirector>,
+    layer_q: Query<(Entity, &MusicLayer, Option<&AudioSink>)>,
+    config: Res<Persistent<Config>>
+) {
+    *volume_changed |= config.is_changed();
+
+    if *applied == director.state && !*volume_changed {
+        return;
+    }
+
+    if layer_q.iter().any(|(_, _, sink)| sink.is_none()) {
+        return;
+    }
+
+    *applied = director.state;
+    *volume_changed = false;
+
+    for (entity, layer, sink) in layer_q.iter() {
+        let Some(sink) = sink else {
+            continue;
+        };
+
+        let end = config.volume_of(AudioBus::Music, layer.volume(director.state));
+
+        if (sink.volume() - end).abs() < f32::EPSILON {
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .insert(EaseFunction::QuadraticInOut)
+            .insert(SpanTweenerBundle::new(CROSSFADE).tween_here())
+            .insert(
+                ComponentTween::new(InterpolateVolume {
+                    start: sink.volume(),
+                    end,
+                })
+            );
+    }
+}
+
+fn play_low_health_sting(
+    mut commands: Commands,
+    mut director: ResMut<MusicDirector>,
+    player_q: Query<&Health, With<Player>>,
+    audio_assets: Res<AudioAssetCollection>
+) {
+    let low_health = player_q
+        .iter()
+        .any(|health| health.current > 0.0 && health.current < health.total * LOW_HEALTH);
+
+    if low_health && !director.low_health {
+        commands.spawn((
+            AudioBus::Music,
+            AudioBundle {
+                source: audio_assets.death.clone(),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    volume: Volume::new(0.6),
+                    speed: 1.5,
+                    ..Default::default()
+                },
+            },
+        ));
+    }
+
+    if director.low_health != low_health {
+        director.low_health = low_health;
+    }
+}
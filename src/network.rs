@@ -0,0 +1,453 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{ Hash, Hasher },
+    io::ErrorKind,
+    net::{ SocketAddr, ToSocketAddrs, UdpSocket },
+};
+
+use bevy::{ prelude::*, utils::{ HashMap, HashSet } };
+use bevy_math::{ ivec2, vec2 };
+use leafwing_input_manager::{
+    action_state::ActionState,
+    axislike::DualAxisData,
+    input_map::InputMap,
+    plugin::InputManagerSystem,
+};
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    actors::{
+        actor::Actor,
+        player::{
+            spawn_player,
+            store_camera_position,
+            CoopPlayer,
+            CursorPosition,
+            Player,
+            PlayerActions,
+            RemotePlayer,
+        },
+    },
+    assets::SpriteAssetCollection,
+    daily::is_daily_run,
+    generation::{ noise::Seed, reset_generation, LevelCounter },
+    simulation::{ chunk::ChunkState, chunk_manager::ChunkManager },
+    state::GameState,
+    warmup::AtlasLayouts,
+};
+
+const MAX_PACKET_SIZE: usize = 65507;
+const HELLO_INTERVAL: f32 = 0.5;
+const CHECKSUM_INTERVAL: f32 = 2.0;
+const TIMEOUT: f32 = 5.0;
+// remote player is snapped to the received position once it drifts further than this amount of pixels
+const POSITION_TOLERANCE: f32 = 4.0;
+
+/// Two player session over UDP, one client hosts and the other joins it,
+/// usage: `sandforge --host <port>` or `sandforge --join <address:port>`
+///
+/// Clients share the level seed, exchange inputs of their players and compare checksums of loaded chunks.
+/// Simulation is not lockstepped yet, so remote players are corrected by position
+/// and chunk mismatches are only reported once they persist between two checksum rounds.
+pub struct NetworkPlugin {
+    role: Role,
+}
+
+#[derive(Clone)]
+enum Role {
+    Host(u16),
+    Join(SocketAddr),
+}
+
+impl NetworkPlugin {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        while let Some(arg) = args.next() {
+            let role = match arg.as_str() {
+                "--host" =>
+                    Role::Host(
+                        args
+                            .next()
+                            .and_then(|port| port.parse().ok())
+                            .expect("expected port after --host")
+                    ),
+                "--join" =>
+                    Role::Join(
+                        args
+                            .next()
+                            .and_then(|address| address.to_socket_addrs().ok())
+                            .and_then(|mut addresses| addresses.next())
+                            .expect("expected address after --join")
+                    ),
+                _ => {
+                    continue;
+                }
+            };
+
+            return Some(Self { role });
+        }
+
+        None
+    }
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        let (socket, peer, seed) = match self.role {
+            Role::Host(port) => {
+                (UdpSocket::bind(("0.0.0.0", port)), None, Some(Seed::new().0))
+            }
+            Role::Join(address) => (UdpSocket::bind(("0.0.0.0", 0)), Some(address), None),
+        };
+
+        let socket = socket.expect("failed to bind network socket");
+        socket.set_nonblocking(true).expect("failed to configure network socket");
+
+        app.insert_resource(NetworkSession {
+            socket,
+            is_host: matches!(self.role, Role::Host(_)),
+            peer,
+            connected: false,
+            seed,
+            last_received: 0.0,
+            remote_input: None,
+            mismatched: HashSet::new(),
+            desynced: HashSet::new(),
+            hello_timer: Timer::from_seconds(HELLO_INTERVAL, TimerMode::Repeating),
+            checksum_timer: Timer::from_seconds(CHECKSUM_INTERVAL, TimerMode::Repeating),
+        })
+            .add_systems(
+                PreUpdate,
+                (receive_packets, apply_remote_input)
+                    .chain()
+                    .after(InputManagerSystem::Update)
+                    .before(store_camera_position)
+            )
+            .add_systems(Update, (send_hello, check_timeout))
+            // daily runs take the seed from the date, peers on the same day get the same levels anyway
+            .add_systems(
+                OnEnter(GameState::Setup),
+                apply_session_seed.after(reset_generation).run_if(not(is_daily_run))
+            )
+            .add_systems(
+                Update,
+                (spawn_remote_player, send_input, send_checksums).run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    Hello,
+    Welcome {
+        seed: u32,
+    },
+    Input {
+        level: u32,
+        pressed: Vec<PlayerActions>,
+        run: f32,
+        aim: [f32; 2],
+        position: [f32; 2],
+    },
+    Checksums {
+        level: u32,
+        chunks: Vec<([i32; 2], u64)>,
+    },
+}
+
+struct RemoteInput {
+    level: u32,
+    pressed: Vec<PlayerActions>,
+    run: f32,
+    aim: Vec2,
+    position: Vec2,
+}
+
+#[derive(Resource)]
+pub struct NetworkSession {
+    socket: UdpSocket,
+    is_host: bool,
+    peer: Option<SocketAddr>,
+    connected: bool,
+    /// Seed of the session, chosen by the host
+    seed: Option<u32>,
+    last_received: f32,
+    remote_input: Option<RemoteInput>,
+    /// Chunks that differed in the last checksum round
+    mismatched: HashSet<IVec2>,
+    /// Chunks that differed in two rounds in a row
+    pub desynced: HashSet<IVec2>,
+    hello_timer: Timer,
+    checksum_timer: Timer,
+}
+
+impl NetworkSession {
+    fn send(&self, packet: &Packet) {
+        let Some(peer) = self.peer else {
+            return;
+        };
+
+        match serde_json::to_vec(packet) {
+            Ok(bytes) => {
+                if let Err(error) = self.socket.send_to(&bytes, peer) {
+                    warn!("network: failed to send packet to {}: {}", peer, error);
+                }
+            }
+            Err(error) => warn!("network: failed to serialize packet: {}", error),
+        }
+    }
+}
+
+/// Checksum of the materials of chunk pixels, colors are randomized per pixel and are not included
+fn chunk_checksum(chunk_manager: &ChunkManager) -> HashMap<IVec2, u64> {
+    chunk_manager.chunks
+        .iter()
+        .filter(|(_, (_, chunk))| matches!(chunk.state, ChunkState::Active | ChunkState::Sleeping))
+        .map(|(position, (_, chunk))| {
+            let mut hasher = DefaultHasher::new();
+
+            for pixel in chunk.pixels.iter() {
+                pixel.material.id.hash(&mut hasher);
+            }
+
+            (*position, hasher.finish())
+        })
+        .collect()
+}
+
+fn receive_packets(
+    mut session: ResMut<NetworkSession>,
+    chunk_manager: Res<ChunkManager>,
+    level: Res<LevelCounter>,
+    time: Res<Time>
+) {
+    let mut buffer = [0; MAX_PACKET_SIZE];
+
+    loop {
+        let (size, source) = match session.socket.recv_from(&mut buffer) {
+            Ok(result) => result,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                break;
+            }
+            Err(error) => {
+                // unreachable peer is reported on the next receive on some platforms
+                warn!("network: failed to receive packet: {}", error);
+                break;
+            }
+        };
+
+        if session.is_host && session.peer.is_none() {
+            session.peer = Some(source);
+        }
+
+        if session.peer != Some(source) {
+            continue;
+        }
+
+        let Ok(packet) = serde_json::from_slice::<Packet>(&buffer[..size]) else {
+            warn!("network: malformed packet from {}", source);
+            continue;
+        };
+
+        if !session.connected {
+            info!("network: connected to {}", source);
+        }
+
+        session.connected = true;
+        session.last_received = time.elapsed_seconds();
+
+        match packet {
+            Packet::Hello => {
+                if let Some(seed) = session.seed {
+                    session.send(&Packet::Welcome { seed });
+                }
+            }
+            Packet::Welcome { seed } => {
+                session.seed = Some(seed);
+            }
+            Packet::Input { level, pressed, run, aim, position } => {
+                session.remote_input = Some(RemoteInput {
+                    level,
+                    pressed,
+                    run,
+                    aim: vec2(aim[0], aim[1]),
+                    position: vec2(position[0], position[1]),
+                });
+            }
+            Packet::Checksums { level: remote_level, chunks } => {
+                if remote_level != level.0 {
+                    continue;
+                }
+
+                let local = chunk_checksum(&chunk_manager);
+
+                for (position, checksum) in chunks {
+                    let position = ivec2(position[0], position[1]);
+
+                    let Some(local_checksum) = local.get(&position) else {
+                        continue;
+                    };
+
+                    if *local_checksum == checksum {
+                        session.mismatched.remove(&position);
+                        session.desynced.remove(&position);
+                    } else if !session.mismatched.insert(position) && session.desynced.insert(position) {
+                        warn!("network: chunk {} is out of sync", position);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn send_hello(mut session: ResMut<NetworkSession>, time: Res<Time>) {
+    if session.is_host || session.seed.is_some() {
+        return;
+    }
+
+    if session.hello_timer.tick(time.delta()).just_finished() {
+        session.send(&Packet::Hello);
+    }
+}
+
+fn check_timeout(
+    mut commands: Commands,
+    mut session: ResMut<NetworkSession>,
+    remote_q: Query<Entity, With<RemotePlayer>>,
+    time: Res<Time>
+) {
+    if !session.connected || time.elapsed_seconds() - session.last_received < TIMEOUT {
+        return;
+    }
+
+    warn!("network: connection timed out");
+
+    session.connected = false;
+    session.remote_input = None;
+
+    // host waits for anyone to join again, while joining client keeps the address
+    if session.is_host {
+        session.peer = None;
+    }
+
+    for entity in remote_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn apply_session_seed(mut commands: Commands, session: Res<NetworkSession>) {
+    match session.seed {
+        Some(seed) => commands.insert_resource(Seed(seed)),
+        None => warn!("network: level seed was not received, levels of clients will differ"),
+    }
+}
+
+fn spawn_remote_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    session: Res<NetworkSession>,
+    sprites: Res<SpriteAssetCollection>,
+    layouts: Res<AtlasLayouts>,
+    remote_q: Query<(), With<RemotePlayer>>
+) {
+    if !session.connected || !remote_q.is_empty() {
+        return;
+    }
+
+    let Some(input) = &session.remote_input else {
+        return;
+    };
+
+    let entity = spawn_player(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &sprites,
+        &layouts,
+        InputMap::default(),
+        input.position
+    );
+
+    // without input map actions are left untouched by input manager and are set from packets instead
+    commands
+        .entity(entity)
+        .insert((Name::new("Remote player"), RemotePlayer))
+        .remove::<InputMap<PlayerActions>>();
+}
+
+fn apply_remote_input(
+    session: Res<NetworkSession>,
+    level: Res<LevelCounter>,
+    mut remote_q: Query<(&mut ActionState<PlayerActions>, &mut Actor), With<RemotePlayer>>
+) {
+    let Some(input) = &session.remote_input else {
+        return;
+    };
+
+    for (mut action_state, mut actor) in remote_q.iter_mut() {
+        for action in action_state.get_pressed() {
+            if !input.pressed.contains(&action) {
+                action_state.release(&action);
+            }
+        }
+
+        for action in input.pressed.iter() {
+            action_state.press(action);
+        }
+
+        action_state.action_data_mut_or_default(&PlayerActions::Run).value = input.run;
+        action_state.action_data_mut_or_default(&PlayerActions::Aim).axis_pair = Some(
+            DualAxisData::new(input.aim.x, input.aim.y)
+        );
+
+        if input.level == level.0 && actor.position.distance(input.position) > POSITION_TOLERANCE {
+            actor.position = input.position;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn send_input(
+    session: Res<NetworkSession>,
+    level: Res<LevelCounter>,
+    player_q: Query<
+        (&ActionState<PlayerActions>, &Actor, Option<&CursorPosition>),
+        (With<Player>, Without<RemotePlayer>, Without<CoopPlayer>)
+    >
+) {
+    if !session.connected {
+        return;
+    }
+
+    let Some((action_state, actor, cursor_position)) = player_q.iter().next() else {
+        return;
+    };
+
+    let aim = cursor_position.map_or(Vec2::X, |cursor_position| cursor_position.direction);
+
+    session.send(&Packet::Input {
+        level: level.0,
+        pressed: action_state.get_pressed(),
+        run: action_state.value(&PlayerActions::Run),
+        aim: aim.to_array(),
+        position: actor.position.to_array(),
+    });
+}
+
+fn send_checksums(
+    mut session: ResMut<NetworkSession>,
+    chunk_manager: Res<ChunkManager>,
+    level: Res<LevelCounter>,
+    time: Res<Time>
+) {
+    if !session.connected || !session.checksum_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let chunks = chunk_checksum(&chunk_manager)
+        .into_iter()
+        .map(|(position, checksum)| (position.to_array(), checksum))
+        .collect();
+
+    session.send(&Packet::Checksums { level: level.0, chunks });
+}
@@ -0,0 +1,272 @@
+use std::{ collections::VecDeque, time::Duration };
+
+use bevy::prelude::*;
+use bevy_tween::{ interpolation::EaseFunction, span_tween::SpanTweenerBundle, tween::ComponentTween };
+
+use crate::interpolator::{ InterpolateBackgroundColor, InterpolateTextColor, InterpolateTopOffset };
+
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+const TOAST_FADE: Duration = Duration::from_millis(300);
+// toasts over the limit wait in the queue until one of the shown toasts is gone
+const MAX_TOASTS: usize = 4;
+// distance in pixels toasts slide from when they appear
+const TOAST_SLIDE: f32 = -24.0;
+const TOAST_BACKGROUND: Color = Color::rgba(0.1, 0.1, 0.1, 0.85);
+const ICON_SIZE: f32 = 24.0;
+
+pub struct NotificationsPlugin;
+
+impl Plugin for NotificationsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Notification>()
+            .init_resource::<NotificationQueue>()
+            .add_systems(Update, (queue_notifications, expire_toasts, show_toasts).chain());
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotificationKind {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl NotificationKind {
+    fn color(&self) -> Color {
+        match self {
+            NotificationKind::Info => Color::WHITE,
+            NotificationKind::Success => Color::rgb_u8(0xf2, 0xf1, 0xa3),
+            NotificationKind::Warning => Color::rgb_u8(0xff, 0x6a, 0x3d),
+            NotificationKind::Error => Color::rgb_u8(0xff, 0x3d, 0x3d),
+        }
+    }
+}
+
+/// Transient message shown in the corner of the screen, sent from any state
+#[derive(Event, Clone)]
+pub struct Notification {
+    pub message: String,
+    pub kind: NotificationKind,
+    pub icon: Option<Handle<Image>>,
+}
+
+impl Notification {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind: NotificationKind::default(),
+            icon: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: NotificationKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_icon(mut self, icon: Handle<Image>) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct NotificationQueue(VecDeque<Notification>);
+
+/// Column the toasts are stacked in, newest toast is at the bottom
+#[derive(Component)]
+pub struct UiNotifications;
+
+#[derive(Component)]
+pub struct UiToast {
+    timer: Timer,
+    closing: bool,
+}
+
+fn queue_notifications(mut events: EventReader<Notification>, mut queue: ResMut<NotificationQueue>) {
+    queue.extend(events.read().cloned());
+}
+
+fn show_toasts(
+    mut commands: Commands,
+    mut queue: ResMut<NotificationQueue>,
+    container_q: Query<Entity, With<UiNotifications>>,
+    toast_q: Query<&UiToast>
+) {
+    if queue.is_empty() {
+        return;
+    }
+
+    let container = container_q.get_single().unwrap_or_else(|_| {
+        commands
+            .spawn((
+                Name::new("Notifications"),
+                UiNotifications,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(16.0),
+                        right: Val::Px(16.0),
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::End,
+                        row_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    z_index: ZIndex::Global(100),
+                    ..default()
+                },
+            ))
+            .id()
+    });
+
+    let mut shown = toast_q
+        .iter()
+        .filter(|toast| !toast.closing)
+        .count();
+
+    while shown < MAX_TOASTS {
+        let Some(notification) = queue.pop_front() else {
+            break;
+        };
+
+        let toast = spawn_toast(&mut commands, notification);
+        commands.entity(container).add_child(toast);
+        shown += 1;
+    }
+}
+
+fn spawn_toast(commands: &mut Commands, notification: Notification) -> Entity {
+    commands
+        .spawn((
+            UiToast {
+                timer: Timer::new(TOAST_DURATION, TimerMode::Once),
+                closing: false,
+            },
+            NodeBundle {
+                style: Style {
+                    top: Val::Px(TOAST_SLIDE),
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(8.0)),
+                    column_gap: Val::Px(8.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            EaseFunction::ExponentialOut,
+            SpanTweenerBundle::new(TOAST_FADE).tween_here(),
+            ComponentTween::new(InterpolateTopOffset {
+                start: Val::Px(TOAST_SLIDE),
+                end: Val::Px(0.0),
+            }),
+            ComponentTween::new(InterpolateBackgroundColor {
+                start: Color::NONE,
+                end: TOAST_BACKGROUND,
+            }),
+        ))
+        .with_children(|parent| {
+            if let Some(icon) = notification.icon {
+                parent.spawn((
+                    ImageBundle {
+                        style: Style {
+                            width: Val::Px(ICON_SIZE),
+                            height: Val::Px(ICON_SIZE),
+                            ..default()
+                        },
+                        image: icon.into(),
+                        background_color: Color::NONE.into(),
+                        ..default()
+                    },
+                    EaseFunction::ExponentialOut,
+                    SpanTweenerBundle::new(TOAST_FADE).tween_here(),
+                    ComponentTween::new(InterpolateBackgroundColor {
+                        start: Color::NONE,
+                        end: Color::WHITE,
+                    }),
+                ));
+            }
+
+            parent.spawn((
+                TextBundle::from_section(notification.message, TextStyle {
+                    font_size: 18.0,
+                    color: Color::NONE,
+                    ..default()
+                }),
+                EaseFunction::ExponentialOut,
+                SpanTweenerBundle::new(TOAST_FADE).tween_here(),
+                ComponentTween::new(InterpolateTextColor {
+                    start: Color::NONE,
+                    end: notification.kind.color(),
+                }),
+            ));
+        })
+        .id()
+}
+
+/// Toasts slide back and fade out once their time is up, and are removed when the fade is over
+#[allow(clippy::type_complexity)]
+fn expire_toasts(
+    mut commands: Commands,
+    mut toast_q: Query<(Entity, &mut UiToast, &BackgroundColor, &Children)>,
+    part_q: Query<(Option<&BackgroundColor>, Option<&Text>), Without<UiToast>>,
+    time: Res<Time>
+) {
+    for (entity, mut toast, background, children) in toast_q.iter_mut() {
+        toast.timer.tick(time.delta());
+
+        if !toast.timer.finished() {
+            continue;
+        }
+
+        if toast.closing {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        toast.closing = true;
+        toast.timer = Timer::new(TOAST_FADE, TimerMode::Once);
+
+        commands
+            .entity(entity)
+            .insert(SpanTweenerBundle::new(TOAST_FADE).tween_here())
+            .insert(
+                ComponentTween::new(InterpolateTopOffset {
+                    start: Val::Px(0.0),
+                    end: Val::Px(TOAST_SLIDE),
+                })
+            )
+            .insert(
+                ComponentTween::new(InterpolateBackgroundColor {
+                    start: background.0,
+                    end: Color::NONE,
+                })
+            );
+
+        for child in children.iter() {
+            let Ok((background, text)) = part_q.get(*child) else {
+                continue;
+            };
+
+            let mut child = commands.entity(*child);
+            child.insert(SpanTweenerBundle::new(TOAST_FADE).tween_here());
+
+            if let Some(text) = text {
+                child.insert(
+                    ComponentTween::new(InterpolateTextColor {
+                        start: text.sections[0].style.color,
+                        end: Color::NONE,
+                    })
+                );
+            } else if let Some(background) = background {
+                child.insert(
+                    ComponentTween::new(InterpolateBackgroundColor {
+                        start: background.0,
+                        end: Color::NONE,
+                    })
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::player::{ CoopPlayer, CursorPosition, Player, PlayerActions },
+    bucket::{ interact_with_buckets, CarriedBy, Carrying },
+    constants::CHUNK_SIZE,
+    gui::Inventory,
+    localization::Locale,
+    notifications::{ Notification, NotificationKind },
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        object::{ remove_object, Object, ObjectBundle, Storable },
+    },
+    state::GameState,
+    vehicle::Piloting,
+};
+
+// same reach as buckets and chests, so interaction picks whichever object is the closest
+const GRAB_DISTANCE: f32 = 24.0;
+// objects with a longer side than this in pixels are too large to store
+const MAX_STORED_SIZE: i32 = 24;
+// distance in pixels between the center of the player and the edge of a released object
+const RELEASE_OFFSET: f32 = 8.0;
+const DROP_SPEED: f32 = 0.25;
+const THROW_SPEED: f32 = 1.5;
+
+/// Lets the player store small loose objects in the inventory and take them out again,
+/// stored objects are shown in the inventory window and dragged out of it to be thrown
+pub struct ObjectInventoryPlugin;
+
+impl Plugin for ObjectInventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReleaseObject>()
+            .add_systems(OnEnter(GameState::Setup), |mut inventory: ResMut<Inventory>| {
+                inventory.clear();
+            })
+            .add_systems(
+                Update,
+                (store_objects.after(interact_with_buckets), release_objects)
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Object taken out of the inventory, spawned next to the player
+#[derive(Event)]
+pub struct ReleaseObject {
+    pub object: Object,
+    /// Player the object is released by, dragging it out of the inventory with the mouse
+    /// releases it from the player on the keyboard
+    pub player: Option<Entity>,
+    /// World position the object is thrown towards, it is dropped in the aiming direction without one
+    pub target: Option<Vec2>,
+}
+
+/// Interaction takes the closest loose object into the inventory unless something else took the press,
+/// dropping one takes the first stored object out again
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn store_objects(
+    mut commands: Commands,
+    mut player_q: Query<
+        (Entity, &Transform, &mut ActionState<PlayerActions>),
+        (With<Player>, Without<Carrying>, Without<Piloting>)
+    >,
+    mut object_q: Query<
+        (Entity, &Transform, &mut Object, Has<Storable>),
+        (Without<Player>, Without<CarriedBy>)
+    >,
+    mut inventory: ResMut<Inventory>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut release_ev: EventWriter<ReleaseObject>,
+    mut notification_ev: EventWriter<Notification>,
+    locale: Res<Locale>
+) {
+    for (player, player_transform, mut action_state) in player_q.iter_mut() {
+        if action_state.just_pressed(&PlayerActions::DropObject) {
+            if let Some(object) = inventory.take_first() {
+                release_ev.send(ReleaseObject { object, player: Some(player), target: None });
+            }
+        }
+
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        let player_position = player_transform.translation.xy();
+
+        let closest = object_q
+            .iter_mut()
+            .map(|(entity, transform, object, storable)| {
+                (transform.translation.xy().distance(player_position), entity, transform, object, storable)
+            })
+            .filter(|(distance, ..)| *distance < GRAB_DISTANCE / (CHUNK_SIZE as f32))
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+        // buckets, chests and the like have interactions of their own
+        if let Some((_, entity, transform, mut object, true)) = closest {
+            action_state.consume(&PlayerActions::Interaction);
+
+            if object.size.max_element() > MAX_STORED_SIZE {
+                notification_ev.send(
+                    Notification::new(locale.get("inventory.too_large")).with_kind(
+                        NotificationKind::Warning
+                    )
+                );
+                continue;
+            }
+
+            if inventory.is_full() {
+                notification_ev.send(
+                    Notification::new(locale.get("inventory.full")).with_kind(NotificationKind::Warning)
+                );
+                continue;
+            }
+
+            remove_object(
+                &mut commands,
+                &mut chunk_manager,
+                &mut dirty_rects,
+                entity,
+                transform,
+                &mut object
+            );
+            inventory.store(object.clone());
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn release_objects(
+    mut commands: Commands,
+    mut release_ev: EventReader<ReleaseObject>,
+    player_q: Query<
+        (&Transform, &Velocity, Option<&CursorPosition>, Has<CoopPlayer>),
+        With<Player>
+    >
+) {
+    for event in release_ev.read() {
+        let Ok(collider) = event.object.create_collider() else {
+            continue;
+        };
+
+        let player = match event.player {
+            Some(player) => player_q.get(player).ok(),
+            None => player_q.iter().find(|(.., is_coop)| !is_coop),
+        };
+
+        let (position, velocity) = match player {
+            Some((transform, velocity, cursor_position, _)) => {
+                let origin = transform.translation.xy();
+
+                let (direction, speed) = match event.target {
+                    Some(target) => ((target - origin).normalize_or_zero(), THROW_SPEED),
+                    None =>
+                        (
+                            cursor_position.map_or(Vec2::X, |cursor_position| cursor_position.direction),
+                            DROP_SPEED,
+                        ),
+                };
+
+                // released past the edge of the player, so the object does not start inside of it
+                let distance = RELEASE_OFFSET + (event.object.size.max_element() as f32) / 2.0;
+
+                (
+                    origin + (direction * distance) / (CHUNK_SIZE as f32),
+                    direction * speed + velocity.linvel / 16.0,
+                )
+            }
+            // without a player the object is put right where it was dragged to
+            None =>
+                match event.target {
+                    Some(target) => (target, Vec2::ZERO),
+                    None => {
+                        continue;
+                    }
+                }
+        };
+
+        commands.spawn((
+            Name::new("Object"),
+            Storable,
+            ObjectBundle {
+                object: event.object.clone(),
+                collider,
+                transform: TransformBundle {
+                    local: Transform::from_translation(position.extend(0.0)),
+                    ..Default::default()
+                },
+                velocity: Velocity::linear(velocity),
+                mass_properties: ColliderMassProperties::Density(2.0),
+                ..Default::default()
+            },
+        ));
+    }
+}
@@ -1,5 +1,7 @@
+use std::collections::VecDeque;
+
 use bevy::{
-    input::mouse::MouseMotion,
+    input::{ mouse::MouseMotion, InputSystem },
     prelude::*,
     utils::{ HashMap, HashSet },
     window::PrimaryWindow,
@@ -12,34 +14,62 @@ use bevy_rapier2d::{
 };
 
 use crate::{
-    camera::TrackingCamera,
+    budget::BudgetPlugin,
+    capture::capture_selecting,
+    camera::{ TrackingCamera, WorldRenderTarget },
     constants::{ CHUNK_SIZE, PARTICLE_Z },
+    generation::chunk::background_color,
     has_window,
     helpers::WalkGrid,
+    importer::Stamps,
+    inspector::inspecting_chunks,
     simulation::{
         chunk_manager::ChunkManager,
         colliders:: ChunkColliderEvent ,
         dirty_rect::{ update_dirty_rects, DirtyRects },
         materials::{ Material, PhysicsType },
-        object::{ Object, ObjectBundle },
+        object::{ Object, ObjectBundle, Storable },
         particle::{
-            Particle, ParticleBundle
+            Particle, ParticleBundle, Particles
         }, pixel::Pixel,
     },
     state::GameState,
 };
 
+// flood fill stops after this many pixels, so clicking open sky does not repaint the whole world
+const MAX_FILL_PIXELS: usize = 1 << 16;
+
 pub struct PainterPlugin;
 
 impl Plugin for PainterPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<MouseState>()
+        app.add_plugins(BudgetPlugin)
+            .init_resource::<MouseState>()
             .init_resource::<BrushRes>()
             .init_resource::<PainterObjectBuffer>()
+            .init_resource::<PainterEdits>()
             .add_systems(
                 PreUpdate,
-                mouse_system.run_if(has_window).run_if(in_state(GameState::Game))
-            );
+                (
+                    eyedropper_system.run_if(has_window),
+                    mouse_system
+                        .run_if(has_window)
+                        .run_if(not(capture_selecting))
+                        .run_if(not(inspecting_chunks)),
+                    apply_painter_edits,
+                )
+                    .chain()
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                Update,
+                draw_shape_preview.run_if(has_window).run_if(in_state(GameState::Game))
+            )
+            .add_systems(OnExit(GameState::Game), |mut edits: ResMut<PainterEdits>| {
+                edits.pixels.clear();
+                edits.background.clear();
+            });
     }
 }
 
@@ -48,6 +78,8 @@ enum MouseState {
     #[default]
     Normal,
     Painting,
+    /// Line or rectangle is dragged out from the start position and drawn on release
+    Shaping(IVec2),
 }
 
 #[derive(Resource)]
@@ -56,6 +88,18 @@ pub struct BrushRes {
     pub brush_type: BrushType,
     pub shape: BrushShape,
     pub size: i32,
+    /// Hollow shapes only draw their outline
+    pub filled: bool,
+    /// When set, painting only overwrites pixels of this material
+    pub replace: Option<Material>,
+    pub layer: BrushLayer,
+}
+
+/// Background only takes cells, lines, rectangles and stamps, painting air erases it
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BrushLayer {
+    Terrain,
+    Background,
 }
 
 #[derive(Clone, PartialEq)]
@@ -63,6 +107,10 @@ pub enum BrushType {
     Cell,
     Object,
     Particle(u8),
+    Stamp(String),
+    Line,
+    Rectangle,
+    Fill,
 }
 
 #[derive(Clone, PartialEq)]
@@ -72,12 +120,20 @@ pub enum BrushShape {
 }
 
 impl BrushShape {
-    pub fn draw<F: FnMut(IVec2)>(&self, position: IVec2, size: i32, operation: &mut F) {
+    pub fn draw<F: FnMut(IVec2)>(
+        &self,
+        position: IVec2,
+        size: i32,
+        filled: bool,
+        operation: &mut F
+    ) {
         match self {
             BrushShape::Circle => {
                 for dx in -size..=size {
                     for dy in -size..=size {
-                        if dx.pow(2) + dy.pow(2) > size.pow(2) {
+                        let distance = dx.pow(2) + dy.pow(2);
+
+                        if distance > size.pow(2) || (!filled && distance <= (size - 1).pow(2)) {
                             continue;
                         }
 
@@ -88,6 +144,10 @@ impl BrushShape {
             BrushShape::Square => {
                 for dx in -size..=size {
                     for dy in -size..=size {
+                        if !filled && dx.abs() < size && dy.abs() < size {
+                            continue;
+                        }
+
                         operation(position + ivec2(dx, dy));
                     }
                 }
@@ -96,6 +156,92 @@ impl BrushShape {
     }
 }
 
+impl BrushType {
+    /// Draws the shape between two corners, lines and outlines are stroked with the brush shape
+    pub fn draw_shape<F: FnMut(IVec2)>(
+        &self,
+        start: IVec2,
+        end: IVec2,
+        brush: &BrushRes,
+        operation: &mut F
+    ) {
+        let mut stroke = |from: IVec2, to: IVec2| {
+            for position in WalkGrid::new(from, to) {
+                brush.shape.draw(position, brush.size, true, operation);
+            }
+        };
+
+        match self {
+            BrushType::Line => stroke(start, end),
+            BrushType::Rectangle => {
+                let min = start.min(end);
+                let max = start.max(end);
+
+                if brush.filled {
+                    for x in min.x..=max.x {
+                        for y in min.y..=max.y {
+                            operation(ivec2(x, y));
+                        }
+                    }
+                } else {
+                    stroke(min, ivec2(max.x, min.y));
+                    stroke(ivec2(max.x, min.y), max);
+                    stroke(max, ivec2(min.x, max.y));
+                    stroke(ivec2(min.x, max.y), min);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces the connected area of the same material as the pixel under the cursor
+fn flood_fill<F: FnMut(IVec2)>(
+    chunk_manager: &ChunkManager,
+    position: IVec2,
+    material: &Material,
+    operation: &mut F
+) {
+    let Ok(target) = chunk_manager.get(position) else {
+        return;
+    };
+
+    if
+        target.material.id == material.id ||
+        matches!(target.physics_type, PhysicsType::Rigidbody(..))
+    {
+        return;
+    }
+
+    let target_id = target.material.id.clone();
+    let mut visited = HashSet::from([position]);
+    let mut queue = VecDeque::from([position]);
+
+    while let Some(position) = queue.pop_front() {
+        operation(position);
+
+        for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let neighbour = position + offset;
+
+            if visited.len() >= MAX_FILL_PIXELS || visited.contains(&neighbour) {
+                continue;
+            }
+
+            if
+                chunk_manager
+                    .get(neighbour)
+                    .is_ok_and(|pixel| {
+                        pixel.material.id == target_id &&
+                            !matches!(pixel.physics_type, PhysicsType::Rigidbody(..))
+                    })
+            {
+                visited.insert(neighbour);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
 impl FromWorld for BrushRes {
     fn from_world(_world: &mut World) -> Self {
         Self {
@@ -103,6 +249,9 @@ impl FromWorld for BrushRes {
             brush_type: BrushType::Cell,
             shape: BrushShape::Circle,
             size: 10,
+            filled: true,
+            replace: None,
+            layer: BrushLayer::Terrain,
         }
     }
 }
@@ -112,38 +261,123 @@ pub struct PainterObjectBuffer {
     pub map: HashMap<IVec2, Pixel>,
 }
 
+/// Pixels painted by the brush that are not yet written to the world,
+/// only those of chunks that are still being loaded are kept over a frame
+#[derive(Resource, Default)]
+pub struct PainterEdits {
+    pub pixels: HashMap<IVec2, Pixel>,
+    /// Colors of the background texture
+    pub background: HashMap<IVec2, [u8; 4]>,
+}
+
+/// Middle click picks the material under the cursor into the brush, with shift into the replaced material
+#[allow(clippy::too_many_arguments)]
+fn eyedropper_system(
+    mut brush: ResMut<BrushRes>,
+    window_q: Query<(Entity, &Window), With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
+    mut contexts: EguiContexts,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    chunk_manager: Res<ChunkManager>
+) {
+    if !buttons.just_pressed(MouseButton::Middle) {
+        return;
+    }
+
+    let (window_entity, window) = window_q.single();
+    let (camera, camera_global_transform) = camera_q.single();
+
+    if
+        contexts
+            .try_ctx_for_window_mut(window_entity)
+            .is_some_and(|ctx| ctx.is_pointer_over_area())
+    {
+        return;
+    }
+
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|position| {
+            camera.viewport_to_world_2d(
+                camera_global_transform,
+                world_target.viewport_position(window, position)
+            )
+        }) else {
+        return;
+    };
+
+    let Ok(pixel) = chunk_manager.get((position * (CHUNK_SIZE as f32)).round().as_ivec2()) else {
+        return;
+    };
+
+    if matches!(pixel.physics_type, PhysicsType::Rigidbody(..)) {
+        return;
+    }
+
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        brush.replace = Some(pixel.material.clone());
+    } else {
+        brush.material = Some(pixel.material.clone());
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn mouse_system(
     mut commands: Commands,
+    mut particles: Particles,
     brush: Res<BrushRes>,
     window_q: Query<(Entity, &Window), With<PrimaryWindow>>,
-    mut chunk_manager: ResMut<ChunkManager>,
+    mut edits: ResMut<PainterEdits>,
     mut dirty_rects: ResMut<DirtyRects>,
     mut motion_evr: EventReader<MouseMotion>,
     mut cursor_evr: EventReader<CursorMoved>,
     mut camera: Query<(&Camera, &mut Transform, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
     mut contexts: EguiContexts,
     mut mouse_state: ResMut<MouseState>,
     mut object_buffer: ResMut<PainterObjectBuffer>,
     buttons: Res<ButtonInput<MouseButton>>,
-    mut chunk_collider_ev: EventWriter<ChunkColliderEvent>
+    stamps: Res<Stamps>,
+    chunk_manager: Res<ChunkManager>
 ) {
     let (camera, mut camera_transform, camera_global_transform) = camera.single_mut();
     let (window_entity, window) = window_q.single();
 
     let mut buffer = HashMap::new();
+    let mut stamp_buffer = vec![];
 
     let mut draw_operation = |position: IVec2| {
         if brush.material.is_none() {
             return;
         }
 
+        if brush.layer == BrushLayer::Background {
+            if matches!(brush.brush_type, BrushType::Cell | BrushType::Line | BrushType::Rectangle) {
+                buffer.insert(position, brush.material.as_ref().unwrap().into());
+            }
+
+            return;
+        }
+
+        if let Some(source) = &brush.replace {
+            let replaceable = chunk_manager.get(position).is_ok_and(|pixel| {
+                pixel.material.id == source.id &&
+                    !matches!(pixel.physics_type, PhysicsType::Rigidbody(..))
+            });
+
+            if !replaceable {
+                return;
+            }
+        }
+
         match brush.brush_type {
             BrushType::Particle(rate) => {
                 if fastrand::u8(0..255) <= rate {
                     let pixel = Pixel::from(brush.material.as_ref().unwrap());
 
-                    commands.spawn(ParticleBundle {
+                    particles.spawn(ParticleBundle {
                         sprite: SpriteBundle {
                             sprite: Sprite {
                                 color: Color::rgba_u8(
@@ -179,6 +413,7 @@ fn mouse_system(
                     object_buffer.map.insert(position, brush.material.as_ref().unwrap().clone().into());
                 }
             }
+            BrushType::Stamp(_) => {}
             _ => {
                 buffer.insert(position, brush.material.as_ref().unwrap().into());
             }
@@ -194,15 +429,32 @@ fn mouse_system(
         mouse_state.set_if_neq(MouseState::Painting);
         if let Some(position) = window.cursor_position() {
             let world_position = camera
-                .viewport_to_world(camera_global_transform, position)
+                .viewport_to_world(camera_global_transform, world_target.viewport_position(window, position))
                 .map(|ray| ray.origin.truncate())
                 .unwrap();
 
-            brush.shape.draw(
-                (world_position * (CHUNK_SIZE as f32)).round().as_ivec2(),
-                brush.size,
-                &mut draw_operation
-            );
+            let position = (world_position * (CHUNK_SIZE as f32)).round().as_ivec2();
+
+            match &brush.brush_type {
+                BrushType::Stamp(id) => {
+                    if let Some(stamp) = stamps.get(id) {
+                        stamp.for_each_pixel(position, |position, pixel| {
+                            stamp_buffer.push((position, Pixel::from(&pixel.material)));
+                        });
+                    }
+                }
+                BrushType::Line | BrushType::Rectangle => {
+                    *mouse_state = MouseState::Shaping(position);
+                }
+                BrushType::Fill => {
+                    if let Some(material) = &brush.material {
+                        flood_fill(&chunk_manager, position, material, &mut draw_operation);
+                    }
+                }
+                _ => {
+                    brush.shape.draw(position, brush.size, brush.filled, &mut draw_operation);
+                }
+            }
         }
     }
 
@@ -211,14 +463,20 @@ fn mouse_system(
             MouseState::Painting => {
                 if let Some(cursor_position) = window.cursor_position() {
                     let mut last_position = camera
-                        .viewport_to_world(camera_global_transform, cursor_position)
+                        .viewport_to_world(
+                            camera_global_transform,
+                            world_target.viewport_position(window, cursor_position)
+                        )
                         .map(|ray| ray.origin.truncate())
                         .unwrap();
 
                     let movement_events = cursor_evr.read().collect::<Vec<&CursorMoved>>();
                     for event in movement_events.iter().rev() {
                         let new_position = camera
-                            .viewport_to_world(camera_global_transform, event.position)
+                            .viewport_to_world(
+                                camera_global_transform,
+                                world_target.viewport_position(window, event.position)
+                            )
                             .map(|ray| ray.origin.truncate())
                             .unwrap();
 
@@ -226,7 +484,12 @@ fn mouse_system(
                             (last_position * (CHUNK_SIZE as f32)).round().as_ivec2(),
                             (new_position * (CHUNK_SIZE as f32)).round().as_ivec2()
                         ) {
-                            brush.shape.draw(position, brush.size, &mut draw_operation);
+                            brush.shape.draw(
+                                position,
+                                brush.size,
+                                brush.filled,
+                                &mut draw_operation
+                            );
                         }
 
                         last_position = new_position;
@@ -237,22 +500,41 @@ fn mouse_system(
         };
     }
 
-    let mut affected_chunks = HashSet::new();
-    for (position, pixel) in buffer {
-        if chunk_manager.set(position, pixel).is_ok() {
-            let chunk_position = position.div_euclid(IVec2::ONE * CHUNK_SIZE);
-            let cell_position = position.rem_euclid(IVec2::ONE * CHUNK_SIZE).as_uvec2();
-
-            affected_chunks.insert(chunk_position);
-
-            update_dirty_rects(&mut dirty_rects.current, chunk_position, cell_position);
-            update_dirty_rects(&mut dirty_rects.render, chunk_position, cell_position);
+    if buttons.just_released(MouseButton::Left) {
+        if let MouseState::Shaping(start) = *mouse_state {
+            if
+                let Some(end) = window
+                    .cursor_position()
+                    .and_then(|position| {
+                        camera.viewport_to_world_2d(
+                            camera_global_transform,
+                            world_target.viewport_position(window, position)
+                        )
+                    })
+            {
+                let end = (end * (CHUNK_SIZE as f32)).round().as_ivec2();
+
+                brush.brush_type.draw_shape(start, end, &brush, &mut draw_operation);
+            }
         }
     }
 
-    chunk_collider_ev.send_batch(
-        affected_chunks.into_iter().map(|position| ChunkColliderEvent(position))
-    );
+    buffer.extend(stamp_buffer);
+
+    match brush.layer {
+        BrushLayer::Terrain => edits.pixels.extend(buffer),
+        BrushLayer::Background => {
+            edits.background.extend(
+                buffer.into_iter().map(|(position, pixel)| {
+                    if pixel.physics_type == PhysicsType::Air {
+                        (position, [0; 4])
+                    } else {
+                        (position, background_color(pixel.get_color()))
+                    }
+                })
+            );
+        }
+    }
 
     cursor_evr.clear();
     motion_evr.clear();
@@ -287,6 +569,7 @@ fn mouse_system(
                 if let Ok(object) = Object::from_pixels(pixels, rect.size()) {
                     if let Ok(collider) = object.create_collider() {
                         commands.spawn((
+                            Storable,
                             ObjectBundle {
                                 object,
                                 collider,
@@ -312,3 +595,102 @@ fn mouse_system(
         mouse_state.set_if_neq(MouseState::Normal);
     }
 }
+
+/// Outline of the line or rectangle that is drawn when the button is released
+fn draw_shape_preview(
+    brush: Res<BrushRes>,
+    mouse_state: Res<MouseState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
+    mut gizmos: Gizmos
+) {
+    let MouseState::Shaping(start) = *mouse_state else {
+        return;
+    };
+
+    let (Ok(window), Ok((camera, camera_transform))) = (
+        window_q.get_single(),
+        camera_q.get_single(),
+    ) else {
+        return;
+    };
+
+    let Some(end) = window
+        .cursor_position()
+        .and_then(|position| {
+            camera.viewport_to_world_2d(camera_transform, world_target.viewport_position(window, position))
+        }) else {
+        return;
+    };
+
+    let start = start.as_vec2() / (CHUNK_SIZE as f32);
+    let end = (end * (CHUNK_SIZE as f32)).round() / (CHUNK_SIZE as f32);
+    let color = brush.material
+        .as_ref()
+        .map_or(Color::WHITE, |material| {
+            Color::rgb_u8(material.color[0], material.color[1], material.color[2])
+        });
+
+    match brush.brush_type {
+        BrushType::Line => gizmos.line_2d(start, end, color),
+        BrushType::Rectangle => {
+            gizmos.rect_2d((start + end) / 2.0, 0.0, (end - start).abs(), color);
+        }
+        _ => {}
+    }
+}
+
+/// Writes painted pixels to the world right after input is read, so they land in the same frame
+/// and before the simulation pass, which never sees a partially applied stroke
+fn apply_painter_edits(
+    mut edits: ResMut<PainterEdits>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut images: ResMut<Assets<Image>>,
+    mut chunk_collider_ev: EventWriter<ChunkColliderEvent>
+) {
+    let edits = &mut *edits;
+
+    // lighting of the terrain depends on the background, so the chunk is redrawn with it
+    edits.background.retain(|position, color| {
+        let chunk_position = position.div_euclid(IVec2::ONE * CHUNK_SIZE);
+        let cell_position = position.rem_euclid(IVec2::ONE * CHUNK_SIZE).as_uvec2();
+
+        let Some((_, chunk)) = chunk_manager.chunks.get(&chunk_position) else {
+            return false;
+        };
+
+        // texture of a chunk that was just spawned may not be added yet
+        let Some(background) = images.get_mut(&chunk.background) else {
+            return true;
+        };
+
+        let index = (cell_position.y * (CHUNK_SIZE as u32) + cell_position.x) as usize;
+        background.data[index * 4..(index + 1) * 4].copy_from_slice(color);
+
+        update_dirty_rects(&mut dirty_rects.render, chunk_position, cell_position);
+        false
+    });
+
+    let mut affected_chunks = HashSet::new();
+    edits.pixels.retain(|position, pixel| {
+        let chunk_position = position.div_euclid(IVec2::ONE * CHUNK_SIZE);
+        let cell_position = position.rem_euclid(IVec2::ONE * CHUNK_SIZE).as_uvec2();
+
+        // pixels outside of the loaded chunks are dropped, those of generating chunks wait for them
+        if chunk_manager.set(*position, pixel.clone()).is_err() {
+            return chunk_manager.chunks.contains_key(&chunk_position);
+        }
+
+        affected_chunks.insert(chunk_position);
+
+        update_dirty_rects(&mut dirty_rects.current, chunk_position, cell_position);
+        update_dirty_rects(&mut dirty_rects.render, chunk_position, cell_position);
+        false
+    });
+
+    chunk_collider_ev.send_batch(
+        affected_chunks.into_iter().map(|position| ChunkColliderEvent(position))
+    );
+}
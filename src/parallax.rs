@@ -0,0 +1,110 @@
+use bevy::{ prelude::*, render::view::RenderLayers };
+
+use crate::{
+    camera::{ update_camera, TrackingCamera, BACKGROUND_RENDER_LAYER, TERRAIN_RENDER_LAYER },
+    constants::{ CHUNK_SIZE, PARALLAX_BACKGROUND_Z, PARALLAX_FOREGROUND_Z },
+    despawn_component,
+    generation::LevelData,
+    state::GameState,
+};
+
+pub struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnTransition { from: GameState::Splash, to: GameState::Game },
+            spawn_parallax_layers
+        )
+            .add_systems(OnEnter(GameState::LevelInitialization), despawn_component::<ParallaxSprite>)
+            .add_systems(OnEnter(GameState::Menu), despawn_component::<ParallaxSprite>)
+            .add_systems(
+                Update,
+                scroll_parallax_layers.after(update_camera).run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Decorative layer of the level, see [`crate::generation::level::ParallaxLayer`]
+#[derive(Component)]
+pub struct ParallaxSprite {
+    pub factor: f32,
+    pub scale: f32,
+}
+
+fn spawn_parallax_layers(
+    mut commands: Commands,
+    level_data: Res<LevelData>,
+    asset_server: Res<AssetServer>
+) {
+    for (index, layer) in level_data.0.parallax.iter().enumerate() {
+        let (z, render_layer) = if layer.foreground {
+            (PARALLAX_FOREGROUND_Z, TERRAIN_RENDER_LAYER)
+        } else {
+            (PARALLAX_BACKGROUND_Z, BACKGROUND_RENDER_LAYER)
+        };
+
+        commands.spawn((
+            Name::new("Parallax layer"),
+            ParallaxSprite {
+                factor: layer.factor,
+                scale: layer.scale,
+            },
+            SpriteBundle {
+                texture: asset_server.load(layer.texture_path.clone()),
+                sprite: Sprite {
+                    color: Color::rgba(1.0, 1.0, 1.0, layer.opacity),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, z + (index as f32) * 0.1),
+                // shown once the texture is loaded and the sprite is sized to the view
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            ImageScaleMode::Tiled {
+                tile_x: true,
+                tile_y: true,
+                stretch_value: layer.scale / (CHUNK_SIZE as f32),
+            },
+            RenderLayers::layer(render_layer),
+        ));
+    }
+}
+
+/// Sprites cover the view with a tile to spare on every side and follow the camera in whole
+/// tiles, so the pattern itself only moves by the share of the camera movement of its layer
+#[allow(clippy::type_complexity)]
+fn scroll_parallax_layers(
+    camera_q: Query<(&Transform, &OrthographicProjection), With<TrackingCamera>>,
+    mut layer_q: Query<
+        (&ParallaxSprite, &Handle<Image>, &mut Sprite, &mut Transform, &mut Visibility),
+        Without<TrackingCamera>
+    >,
+    images: Res<Assets<Image>>
+) {
+    let Ok((camera_transform, projection)) = camera_q.get_single() else {
+        return;
+    };
+
+    let camera = camera_transform.translation.xy();
+
+    for (layer, texture, mut sprite, mut transform, mut visibility) in layer_q.iter_mut() {
+        let Some(image) = images.get(texture) else {
+            continue;
+        };
+
+        let tile = (image.size_f32() * layer.scale) / (CHUNK_SIZE as f32);
+        let size = ((projection.area.size() / tile).ceil() + 2.0) * tile;
+
+        // resizing recomputes the tiles, so it is only done once the zoom changes
+        if sprite.custom_size != Some(size) {
+            sprite.custom_size = Some(size);
+        }
+
+        let offset = camera * (1.0 - layer.factor);
+        let position = camera * layer.factor + (offset / tile).round() * tile;
+
+        transform.translation = position.extend(transform.translation.z);
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
@@ -0,0 +1,239 @@
+use bevy::{ ecs::query::QueryData, prelude::* };
+use serde::Deserialize;
+
+use crate::{
+    actors::{
+        actor::AttackParameters,
+        effects::Breath,
+        health::{ Health, Resistances },
+        player::{ InventoryParameters, MiningTool, PlayerMaterials },
+    },
+    localization::Locale,
+};
+
+// perks offered on every level up
+pub const PERK_OFFERS: usize = 3;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+}
+
+impl Rarity {
+    /// Relative chance of a perk of the rarity to be offered
+    pub fn weight(self) -> f32 {
+        match self {
+            Rarity::Common => 6.0,
+            Rarity::Uncommon => 3.0,
+            Rarity::Rare => 1.0,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Rarity::Common => Color::WHITE,
+            Rarity::Uncommon => Color::rgb_u8(0x7c, 0xc8, 0x6e),
+            Rarity::Rare => Color::rgb_u8(0xf2, 0xc1, 0x4e),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum PerkEffect {
+    /// Share of the max health added to it, the player is healed by the same amount
+    MaxHealth(f32),
+    Damage(f32),
+    AttackKnockback(f32),
+    Storage(f32),
+    /// Knockback taken is divided by the value
+    KnockbackResistance(f32),
+    /// Damage of the type taken is divided by the value
+    FireResistance(f32),
+    AcidResistance(f32),
+    ProjectileResistance(f32),
+    /// Air capacity is multiplied by the value
+    Breath(f32),
+    /// Health restored to every player on each kill
+    Lifesteal(f32),
+    /// Survives a lethal hit with a quarter of the health once
+    SecondWind,
+    /// Dashing passes through enemies without touching them
+    DashThrough,
+    /// Collecting pulls loose materials in from a cone towards the cursor
+    Vacuum,
+    /// Raises the hardness of materials attacks can carve
+    MiningTier(u32),
+    MiningPower(f32),
+    /// Added to the shared inventory once, ignoring the storage limit
+    Material {
+        material_id: String,
+        amount: f32,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Perk {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+    pub rarity: Rarity,
+    /// Offered only until it is picked once
+    #[serde(default)]
+    pub unique: bool,
+    pub effects: Vec<PerkEffect>,
+}
+
+/// Abilities granted by perks that are not tied to any other player component
+#[derive(Component, Default, Clone)]
+pub struct PerkAbilities {
+    pub lifesteal: f32,
+    pub second_winds: u32,
+    pub dash_through: bool,
+    pub vacuum: bool,
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub struct PerkTarget {
+    pub health: &'static mut Health,
+    pub attack: &'static mut AttackParameters,
+    pub inventory: &'static mut InventoryParameters,
+    pub resistances: &'static mut Resistances,
+    pub breath: &'static mut Breath,
+    pub abilities: &'static mut PerkAbilities,
+    pub mining: &'static mut MiningTool,
+}
+
+impl Perk {
+    /// Name from the language files, the one of perks.ron is used when the key is missing
+    pub fn localized_name<'a>(&'a self, locale: &'a Locale) -> &'a str {
+        locale.get_or(&format!("perk.{}.name", self.id), &self.name)
+    }
+
+    pub fn localized_description<'a>(&'a self, locale: &'a Locale) -> &'a str {
+        locale.get_or(&format!("perk.{}.description", self.id), &self.description)
+    }
+
+    /// Effects on a single player, perks are shared so this is done for every one of them
+    pub fn apply(&self, player: &mut PerkTargetItem) {
+        for effect in self.effects.iter() {
+            match *effect {
+                PerkEffect::MaxHealth(share) => {
+                    let change = player.health.total * share;
+                    player.health.current += change;
+                    player.health.total += change;
+                }
+                PerkEffect::Damage(value) => {
+                    player.attack.value += value;
+                }
+                PerkEffect::AttackKnockback(value) => {
+                    player.attack.knockback_strength += value;
+                }
+                PerkEffect::Storage(value) => {
+                    player.inventory.max_storage += value;
+                }
+                PerkEffect::KnockbackResistance(divisor) => {
+                    player.resistances.knockback = 1.0 - (1.0 - player.resistances.knockback) / divisor;
+                }
+                PerkEffect::FireResistance(divisor) => {
+                    player.resistances.fire = 1.0 - (1.0 - player.resistances.fire) / divisor;
+                }
+                PerkEffect::AcidResistance(divisor) => {
+                    player.resistances.acid = 1.0 - (1.0 - player.resistances.acid) / divisor;
+                }
+                PerkEffect::ProjectileResistance(divisor) => {
+                    player.resistances.projectile =
+                        1.0 - (1.0 - player.resistances.projectile) / divisor;
+                }
+                PerkEffect::Breath(scale) => {
+                    player.breath.capacity *= scale;
+                }
+                PerkEffect::Lifesteal(value) => {
+                    player.abilities.lifesteal += value;
+                }
+                PerkEffect::SecondWind => {
+                    player.abilities.second_winds += 1;
+                }
+                PerkEffect::DashThrough => {
+                    player.abilities.dash_through = true;
+                }
+                PerkEffect::Vacuum => {
+                    player.abilities.vacuum = true;
+                }
+                PerkEffect::MiningTier(value) => {
+                    player.mining.tier += value;
+                }
+                PerkEffect::MiningPower(value) => {
+                    player.mining.power += value;
+                }
+                PerkEffect::Material { .. } => {}
+            }
+        }
+    }
+
+    /// Materials of the perk, given once no matter how many players there are
+    pub fn grant_materials(&self, player_materials: &mut PlayerMaterials) {
+        for effect in self.effects.iter() {
+            if let PerkEffect::Material { material_id, amount } = effect {
+                *player_materials.entry(material_id.clone()).or_insert(0.0) += amount;
+            }
+        }
+    }
+}
+
+pub fn load_perks() -> Vec<Perk> {
+    ron::de::from_str::<Vec<Perk>>(&std::fs::read_to_string("perks.ron").unwrap()).unwrap()
+}
+
+/// Picks perks weighted by their rarity from ids, unique perks that were already taken are never
+/// offered again and the excluded ones only when there are not enough perks left otherwise
+pub fn roll_perk_offers<'a>(perks: &'a [Perk], taken: &[String], excluded: &[String]) -> Vec<&'a Perk> {
+    let available = perks
+        .iter()
+        .filter(|perk| !(perk.unique && taken.contains(&perk.id)))
+        .collect::<Vec<_>>();
+
+    let mut candidates = available
+        .iter()
+        .copied()
+        .filter(|perk| !excluded.contains(&perk.id))
+        .collect::<Vec<_>>();
+
+    if candidates.len() < PERK_OFFERS {
+        candidates = available;
+    }
+
+    let mut offers = vec![];
+
+    while offers.len() < PERK_OFFERS && !candidates.is_empty() {
+        let total = candidates
+            .iter()
+            .map(|perk| perk.rarity.weight())
+            .sum::<f32>();
+        let mut roll = fastrand::f32() * total;
+
+        let index = candidates
+            .iter()
+            .position(|perk| {
+                roll -= perk.rarity.weight();
+                roll <= 0.0
+            })
+            .unwrap_or(candidates.len() - 1);
+
+        offers.push(candidates.swap_remove(index));
+    }
+
+    offers
+}
+
+/// Names of the perks with the given ids, in the same order
+pub fn perk_names(perks: &[Perk], ids: &[String], locale: &Locale) -> String {
+    ids.iter()
+        .filter_map(|id| perks.iter().find(|perk| perk.id == *id))
+        .map(|perk| perk.localized_name(locale))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,177 @@
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent, render_asset::RenderAssets, render_graph::{ NodeRunError, RenderGraphContext, RenderLabel, ViewNode }, render_resource::{ binding_types::{ sampler, texture_2d, uniform_buffer_sized }, * }, renderer::{ RenderContext, RenderDevice }, texture::BevyDefault, view::ViewTarget
+    },
+};
+
+use crate::{ camera::HeatMapTexture, constants::CHUNK_SIZE };
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct HeatHazeLabel;
+
+#[derive(Default)]
+pub(crate) struct HeatHazeNode;
+
+impl ViewNode for HeatHazeNode {
+    type ViewQuery = (&'static ViewTarget, &'static HeatHaze);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, haze): QueryItem<Self::ViewQuery>,
+        world: &World
+    ) -> Result<(), NodeRunError> {
+        let pipeline = world.resource::<HeatHazePipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let Some(pipeline_id) = pipeline_cache.get_render_pipeline(pipeline.pipeline_id) else {
+            return Ok(());
+        };
+
+        let Some(heat_map) = world.get_resource::<HeatMapTexture>() else {
+            return Ok(());
+        };
+
+        let Some(gpu_images) = world.get_resource::<RenderAssets<Image>>() else {
+            return Ok(());
+        };
+
+        let Some(heat) = gpu_images.get(&heat_map.texture) else {
+            return Ok(());
+        };
+
+        let uniform = [haze.time, haze.pixel_size, haze.center.x, haze.center.y];
+
+        let haze_buffer = render_context.render_device().create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&uniform),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context
+            .render_device()
+            .create_bind_group(
+                "heat_haze_bind_group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((
+                    post_process.source,
+                    &heat.texture_view,
+                    &pipeline.sampler,
+                    haze_buffer.as_entire_binding(),
+                ))
+            );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("heat_haze_process_pass"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_render_pipeline(pipeline_id);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct HeatHazePipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for HeatHazePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "heat_haze_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::FRAGMENT, (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+                uniform_buffer_sized(false, None),
+            ))
+        );
+
+        // heat map is a fraction of the screen resolution and is smoothed out while sampling
+        let sampler = render_device.create_sampler(
+            &(SamplerDescriptor {
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                ..Default::default()
+            })
+        );
+        let shader = world.resource::<AssetServer>().load("shaders/heat_haze.wgsl");
+
+        let pipeline_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some("heat_haze_pipeline".into()),
+                layout: vec![layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader,
+                    shader_defs: vec![],
+                    entry_point: "fragment".into(),
+                    targets: vec![
+                        Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: Some(BlendState {
+                                color: BlendComponent::OVER,
+                                alpha: BlendComponent::OVER,
+                            }),
+                            write_mask: ColorWrites::ALL,
+                        })
+                    ],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+            });
+
+        Self {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
+
+/// Marks the camera whose view shimmers above hot materials and gets smoke plumes over fire,
+/// values are updated every frame
+#[derive(Component, Default, Clone, Copy, ExtractComponent)]
+pub struct HeatHaze {
+    pub time: f32,
+    /// Texels of the view per pixel of the world
+    pub pixel_size: f32,
+    /// Middle of the view in pixels, smoke drifts with the world instead of the camera
+    pub center: Vec2,
+}
+
+pub fn update_heat_haze(
+    mut camera_q: Query<(&mut HeatHaze, &GlobalTransform, &OrthographicProjection)>,
+    time: Res<Time>
+) {
+    for (mut haze, transform, projection) in camera_q.iter_mut() {
+        haze.time = time.elapsed_seconds_wrapped();
+        haze.pixel_size = 1.0 / (projection.scale * (CHUNK_SIZE as f32));
+        haze.center = transform.translation().xy() * (CHUNK_SIZE as f32);
+    }
+}
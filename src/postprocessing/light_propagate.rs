@@ -25,6 +25,8 @@ impl ViewNode for LightPropagationNode {
         (view_target, propagation_settings): QueryItem<Self::ViewQuery>,
         world: &World
     ) -> Result<(), NodeRunError> {
+        let _span = info_span!("light_propagation").entered();
+
         let pipeline = world.resource::<LightPropagationPipeline>();
         let pipeline_cache = world.resource::<PipelineCache>();
 
@@ -46,6 +48,12 @@ impl ViewNode for LightPropagationNode {
             usage: BufferUsages::UNIFORM,
         });
 
+        let falloff_buffer = render_context.render_device().create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&propagation_settings.falloff),
+            usage: BufferUsages::UNIFORM,
+        });
+
         for step in 0..propagation_settings.passes {
             let post_process = view_target.post_process_write();
 
@@ -65,6 +73,7 @@ impl ViewNode for LightPropagationNode {
                         &pipeline.sampler,
                         offset_buffer.as_entire_binding(),
                         shadow_buffer.as_entire_binding(),
+                        falloff_buffer.as_entire_binding(),
                     ))
                 );
     
@@ -109,6 +118,7 @@ impl FromWorld for LightPropagationPipeline {
                 sampler(SamplerBindingType::Filtering),
                 uniform_buffer::<f32>(false),
                 uniform_buffer::<[f32; 3]>(false),
+                uniform_buffer::<f32>(false),
             ))
         );
 
@@ -163,4 +173,6 @@ impl FromWorld for LightPropagationPipeline {
 pub struct LightPropagationSettings {
     pub offset: f32,
     pub passes: u32,
+    /// Exponent the light is raised to after every pass
+    pub falloff: f32,
 }
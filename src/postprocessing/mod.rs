@@ -2,17 +2,24 @@ use bevy::{
     core_pipeline::core_2d::graph::{ Core2d, Node2d },
     prelude::*,
     render::{
-        extract_component::ExtractComponentPlugin, render_graph::{ RenderGraphApp, ViewNodeRunner }, RenderApp
+        extract_component::ExtractComponentPlugin, extract_resource::ExtractResourcePlugin, render_graph::{ RenderGraphApp, ViewNodeRunner }, RenderApp
     },
+    transform::TransformSystem,
 };
 
 use self::{
-    light_apply::{LightApply, ApplyLightingLabel, ApplyLightingNode, ApplyLightingPipeline}, light_propagate::{ LightPropagationLabel, LightPropagationNode, LightPropagationPipeline, LightPropagationSettings }, light_calculate::{ CalculateLightingLabel, CalculateLightingNode, CalculateLightingPipeline, LightMask }
+    heat_haze::{ update_heat_haze, HeatHaze, HeatHazeLabel, HeatHazeNode, HeatHazePipeline }, light_apply::{LightApply, ApplyLightingLabel, ApplyLightingNode, ApplyLightingPipeline}, light_propagate::{ LightPropagationLabel, LightPropagationNode, LightPropagationPipeline, LightPropagationSettings }, light_calculate::{ CalculateLightingLabel, CalculateLightingNode, CalculateLightingPipeline, LightMask }, retro_filter::{ update_retro_filters, RetroFilter, RetroFilterLabel, RetroFilterNode, RetroFilterPipeline }, water::{ update_water_styles, update_water_surfaces, WaterLabel, WaterNode, WaterPipeline, WaterStyles, WaterSurfaces }
 };
+use bevy_persistent::Persistent;
+
+use crate::{ registries::Registries, settings::Config };
 
 pub mod light_propagate;
 pub mod light_calculate;
 pub mod light_apply;
+pub mod water;
+pub mod heat_haze;
+pub mod retro_filter;
 
 pub struct PostProcessPlugin;
 
@@ -22,13 +29,36 @@ impl Plugin for PostProcessPlugin {
             ExtractComponentPlugin::<LightPropagationSettings>::default(),
             ExtractComponentPlugin::<LightMask>::default(),
             ExtractComponentPlugin::<LightApply>::default(),
-        ));
+            ExtractComponentPlugin::<WaterSurfaces>::default(),
+            ExtractResourcePlugin::<WaterStyles>::default(),
+            ExtractComponentPlugin::<HeatHaze>::default(),
+            ExtractComponentPlugin::<RetroFilter>::default(),
+        ))
+            .init_resource::<WaterStyles>()
+            .add_systems(Update, update_water_styles.run_if(resource_exists_and_changed::<Registries>))
+            .add_systems(Update, update_retro_filters.run_if(resource_changed::<Persistent<Config>>))
+            .add_systems(
+                PostUpdate,
+                (update_water_surfaces, update_heat_haze).after(TransformSystem::TransformPropagate)
+            );
 
         let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
 
         render_app
+            .add_render_graph_node::<ViewNodeRunner<WaterNode>>(
+                Core2d,
+                WaterLabel
+            )
+            .add_render_graph_node::<ViewNodeRunner<HeatHazeNode>>(
+                Core2d,
+                HeatHazeLabel
+            )
+            .add_render_graph_node::<ViewNodeRunner<RetroFilterNode>>(
+                Core2d,
+                RetroFilterLabel
+            )
             .add_render_graph_node::<ViewNodeRunner<LightPropagationNode>>(
                 Core2d,
                 LightPropagationLabel
@@ -43,7 +73,7 @@ impl Plugin for PostProcessPlugin {
             )
             .add_render_graph_edges(
                 Core2d,
-                (Node2d::Tonemapping, CalculateLightingLabel, LightPropagationLabel, ApplyLightingLabel, Node2d::EndMainPassPostProcessing)
+                (Node2d::Tonemapping, WaterLabel, HeatHazeLabel, CalculateLightingLabel, LightPropagationLabel, ApplyLightingLabel, RetroFilterLabel, Node2d::EndMainPassPostProcessing)
             );
     }
 
@@ -55,6 +85,9 @@ impl Plugin for PostProcessPlugin {
         render_app
             .init_resource::<CalculateLightingPipeline>()
             .init_resource::<LightPropagationPipeline>()
-            .init_resource::<ApplyLightingPipeline>();
+            .init_resource::<ApplyLightingPipeline>()
+            .init_resource::<WaterPipeline>()
+            .init_resource::<HeatHazePipeline>()
+            .init_resource::<RetroFilterPipeline>();
     }
 }
\ No newline at end of file
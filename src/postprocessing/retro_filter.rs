@@ -0,0 +1,170 @@
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent, render_graph::{ NodeRunError, RenderGraphContext, RenderLabel, ViewNode }, render_resource::{ binding_types::{ sampler, texture_2d, uniform_buffer_sized }, * }, renderer::{ RenderContext, RenderDevice }, texture::BevyDefault, view::ViewTarget
+    },
+};
+use bevy_persistent::Persistent;
+
+use crate::settings::Config;
+
+// colors of the largest palette, the shader reads that many
+const MAX_PALETTE_COLORS: usize = 16;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct RetroFilterLabel;
+
+#[derive(Default)]
+pub(crate) struct RetroFilterNode;
+
+impl ViewNode for RetroFilterNode {
+    type ViewQuery = (&'static ViewTarget, &'static RetroFilter);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, filter): QueryItem<Self::ViewQuery>,
+        world: &World
+    ) -> Result<(), NodeRunError> {
+        // both filters are off, the view is left as it is
+        if filter.crt <= 0.0 && (filter.palette_intensity <= 0.0 || filter.palette.is_empty()) {
+            return Ok(());
+        }
+
+        let pipeline = world.resource::<RetroFilterPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let Some(pipeline_id) = pipeline_cache.get_render_pipeline(pipeline.pipeline_id) else {
+            return Ok(());
+        };
+
+        let mut uniform = vec![filter.crt, filter.palette_intensity, filter.palette.len() as f32, 0.0];
+        uniform.extend(filter.palette.iter().flat_map(|color| [color[0], color[1], color[2], 1.0]));
+        uniform.resize(4 + MAX_PALETTE_COLORS * 4, 0.0);
+
+        let filter_buffer = render_context.render_device().create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&uniform),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context
+            .render_device()
+            .create_bind_group(
+                "retro_filter_bind_group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((
+                    post_process.source,
+                    &pipeline.sampler,
+                    filter_buffer.as_entire_binding(),
+                ))
+            );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("retro_filter_process_pass"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_render_pipeline(pipeline_id);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct RetroFilterPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for RetroFilterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "retro_filter_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::FRAGMENT, (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+                uniform_buffer_sized(false, None),
+            ))
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        let shader = world.resource::<AssetServer>().load("shaders/retro_filter.wgsl");
+
+        let pipeline_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some("retro_filter_pipeline".into()),
+                layout: vec![layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader,
+                    shader_defs: vec![],
+                    entry_point: "fragment".into(),
+                    targets: vec![
+                        Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: Some(BlendState {
+                                color: BlendComponent::OVER,
+                                alpha: BlendComponent::OVER,
+                            }),
+                            write_mask: ColorWrites::ALL,
+                        })
+                    ],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+            });
+
+        Self {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
+
+/// Marks the camera showing the world on the window, the CRT look and the retro palette
+/// are applied to it, the interface is drawn afterwards and stays untouched
+#[derive(Component, Default, Clone, ExtractComponent)]
+pub struct RetroFilter {
+    /// Strength of the scanlines and curvature, zero turns them off
+    pub crt: f32,
+    /// Share of the palette color in the final one
+    pub palette_intensity: f32,
+    /// Colors of the palette in srgb
+    pub palette: Vec<[f32; 3]>,
+}
+
+pub fn update_retro_filters(mut camera_q: Query<&mut RetroFilter>, config: Res<Persistent<Config>>) {
+    for mut filter in camera_q.iter_mut() {
+        filter.crt = if config.crt_filter { (config.crt_intensity as f32) / 100.0 } else { 0.0 };
+        filter.palette_intensity = (config.retro_palette_intensity as f32) / 100.0;
+        filter.palette = config.retro_palette
+            .colors()
+            .iter()
+            .take(MAX_PALETTE_COLORS)
+            .map(|color| color.map(|channel| (channel as f32) / 255.0))
+            .collect();
+    }
+}
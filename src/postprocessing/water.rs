@@ -0,0 +1,224 @@
+use bevy::{
+    core_pipeline::fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent, extract_resource::ExtractResource, render_asset::RenderAssets, render_graph::{ NodeRunError, RenderGraphContext, RenderLabel, ViewNode }, render_resource::{ binding_types::{ sampler, texture_2d, uniform_buffer_sized }, * }, renderer::{ RenderContext, RenderDevice }, texture::BevyDefault, view::ViewTarget
+    },
+};
+
+use crate::{
+    camera::LiquidMaskTexture,
+    constants::CHUNK_SIZE,
+    registries::Registries,
+    simulation::materials::MAX_WATER_STYLES,
+};
+
+// floats of a single style in the uniform, deep color and wave parameters
+const STYLE_SIZE: usize = 8;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct WaterLabel;
+
+#[derive(Default)]
+pub(crate) struct WaterNode;
+
+impl ViewNode for WaterNode {
+    type ViewQuery = (&'static ViewTarget, &'static WaterSurfaces);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, surfaces): QueryItem<Self::ViewQuery>,
+        world: &World
+    ) -> Result<(), NodeRunError> {
+        let pipeline = world.resource::<WaterPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let Some(pipeline_id) = pipeline_cache.get_render_pipeline(pipeline.pipeline_id) else {
+            return Ok(());
+        };
+
+        let (Some(liquid_mask), Some(styles)) = (
+            world.get_resource::<LiquidMaskTexture>(),
+            world.get_resource::<WaterStyles>(),
+        ) else {
+            return Ok(());
+        };
+
+        let Some(gpu_images) = world.get_resource::<RenderAssets<Image>>() else {
+            return Ok(());
+        };
+
+        let Some(mask) = gpu_images.get(&liquid_mask.texture) else {
+            return Ok(());
+        };
+
+        let mut uniform = vec![
+            surfaces.time,
+            surfaces.pixel_size,
+            surfaces.center.x,
+            surfaces.center.y,
+        ];
+        uniform.extend_from_slice(&styles.0);
+
+        let water_buffer = render_context.render_device().create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&uniform),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context
+            .render_device()
+            .create_bind_group(
+                "water_bind_group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((
+                    post_process.source,
+                    &mask.texture_view,
+                    &pipeline.sampler,
+                    water_buffer.as_entire_binding(),
+                ))
+            );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("water_process_pass"),
+            color_attachments: &[
+                Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                }),
+            ],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_render_pipeline(pipeline_id);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct WaterPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for WaterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "water_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::FRAGMENT, (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+                uniform_buffer_sized(false, None),
+            ))
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        let shader = world.resource::<AssetServer>().load("shaders/water.wgsl");
+
+        let pipeline_id = world
+            .resource_mut::<PipelineCache>()
+            .queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some("water_pipeline".into()),
+                layout: vec![layout.clone()],
+                vertex: fullscreen_shader_vertex_state(),
+                fragment: Some(FragmentState {
+                    shader,
+                    shader_defs: vec![],
+                    entry_point: "fragment".into(),
+                    targets: vec![
+                        Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: Some(BlendState {
+                                color: BlendComponent::OVER,
+                                alpha: BlendComponent::OVER,
+                            }),
+                            write_mask: ColorWrites::ALL,
+                        })
+                    ],
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                push_constant_ranges: vec![],
+            });
+
+        Self {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
+
+/// Marks the camera whose liquids get waves, values are updated every frame
+#[derive(Component, Default, Clone, Copy, ExtractComponent)]
+pub struct WaterSurfaces {
+    pub time: f32,
+    /// Texels of the view per pixel of the world
+    pub pixel_size: f32,
+    /// Middle of the view in pixels, waves stay in place while the camera moves
+    pub center: Vec2,
+}
+
+/// Water styles of the materials laid out the way the shader reads them
+#[derive(Resource, ExtractResource, Clone)]
+pub struct WaterStyles(pub Vec<f32>);
+
+impl Default for WaterStyles {
+    fn default() -> Self {
+        Self(vec![0.0; MAX_WATER_STYLES * STYLE_SIZE])
+    }
+}
+
+pub fn update_water_styles(mut styles: ResMut<WaterStyles>, registries: Res<Registries>) {
+    *styles = WaterStyles::default();
+
+    for style in registries.materials.values().filter_map(|material| material.water) {
+        let deep_color = Color::rgba_u8(
+            style.deep_color[0],
+            style.deep_color[1],
+            style.deep_color[2],
+            style.deep_color[3]
+        ).as_linear_rgba_f32();
+
+        let offset = (style.index as usize) * STYLE_SIZE;
+
+        styles.0[offset..offset + STYLE_SIZE].copy_from_slice(
+            &[
+                deep_color[0],
+                deep_color[1],
+                deep_color[2],
+                deep_color[3],
+                style.wave_amplitude,
+                style.wave_frequency,
+                style.shimmer,
+                0.0,
+            ]
+        );
+    }
+}
+
+pub fn update_water_surfaces(
+    mut camera_q: Query<(&mut WaterSurfaces, &GlobalTransform, &OrthographicProjection)>,
+    time: Res<Time>
+) {
+    for (mut surfaces, transform, projection) in camera_q.iter_mut() {
+        surfaces.time = time.elapsed_seconds_wrapped();
+        surfaces.pixel_size = 1.0 / (projection.scale * (CHUNK_SIZE as f32));
+        surfaces.center = transform.translation().xy() * (CHUNK_SIZE as f32);
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use bevy::prelude::*;
+
+use crate::{
+    arenas::{ Arena, ArenaState },
+    daily::RunMode,
+    generation::LevelCounter,
+    gui::Score,
+    localization::Locale,
+    state::GameState,
+};
+
+/// Keeps track of what the players are doing and sends [`PresenceChanged`] once it changes,
+/// backends like the Discord one only read these events
+pub struct PresencePlugin;
+
+impl Plugin for PresencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PresenceChanged>()
+            .init_resource::<CurrentPresence>()
+            .add_systems(Update, (update_presence, log_presence).chain());
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Presence {
+    #[default]
+    Loading,
+    Menu,
+    Tutorial,
+    Level {
+        level: u32,
+        daily: bool,
+    },
+    /// Sealed in an arena until its encounter is cleared
+    ArenaFight {
+        level: u32,
+        daily: bool,
+    },
+    GameOver {
+        level: u32,
+        score: i32,
+    },
+}
+
+impl Presence {
+    /// First line of the presence, what the players are doing
+    pub fn details(&self, locale: &Locale) -> String {
+        match self {
+            Presence::Loading | Presence::Menu => locale.get("presence.menu").to_string(),
+            Presence::Tutorial => locale.get("presence.tutorial").to_string(),
+            Presence::Level { level, .. } => locale.format("presence.level", &[level]),
+            Presence::ArenaFight { level, .. } => locale.format("presence.arena", &[level]),
+            Presence::GameOver { level, .. } => locale.format("presence.game_over", &[level]),
+        }
+    }
+
+    /// Second line of the presence, if there is anything to add
+    pub fn state(&self, locale: &Locale) -> Option<String> {
+        match self {
+            Presence::Level { daily: true, .. } | Presence::ArenaFight { daily: true, .. } =>
+                Some(locale.get("presence.daily").to_string()),
+            Presence::GameOver { score, .. } => Some(locale.format("presence.score", &[score])),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PresenceChanged {
+    pub presence: Presence,
+    /// Unix time in seconds the presence started at
+    pub since: u64,
+}
+
+#[derive(Resource, Default)]
+pub struct CurrentPresence {
+    pub presence: Presence,
+    pub since: u64,
+}
+
+fn update_presence(
+    mut presence_ev: EventWriter<PresenceChanged>,
+    mut current: ResMut<CurrentPresence>,
+    game_state: Res<State<GameState>>,
+    arena_q: Query<&Arena>,
+    run_mode: Res<RunMode>,
+    counter: Option<Res<LevelCounter>>,
+    score: Option<Res<Score>>
+) {
+    let level = counter.map_or(0, |counter| counter.0);
+    let daily = matches!(*run_mode, RunMode::Daily(..));
+
+    let presence = match game_state.get() {
+        GameState::LoadingAssets => Presence::Loading,
+        GameState::Menu => Presence::Menu,
+        _ if *run_mode == RunMode::Tutorial => Presence::Tutorial,
+        GameState::GameOver =>
+            Presence::GameOver {
+                level,
+                score: score.map_or(0, |score| score.value),
+            },
+        GameState::Game if arena_q.iter().any(|arena| arena.state == ArenaState::Sealed) => {
+            Presence::ArenaFight { level, daily }
+        }
+        _ => Presence::Level { level, daily },
+    };
+
+    if presence == current.presence {
+        return;
+    }
+
+    // a fight is a part of the level, the time shown keeps counting from the start of the level
+    let continues_level = matches!(
+        (current.presence, presence),
+        (Presence::Level { .. }, Presence::ArenaFight { .. }) |
+            (Presence::ArenaFight { .. }, Presence::Level { .. })
+    );
+
+    if !continues_level {
+        current.since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+    }
+
+    current.presence = presence;
+
+    presence_ev.send(PresenceChanged {
+        presence,
+        since: current.since,
+    });
+}
+
+/// Simplest backend, writes every change to the log
+fn log_presence(mut presence_ev: EventReader<PresenceChanged>, locale: Res<Locale>) {
+    for event in presence_ev.read() {
+        match event.presence.state(&locale) {
+            Some(state) => debug!("presence since {}: {} ({})", event.since, event.presence.details(&locale), state),
+            None => debug!("presence since {}: {}", event.since, event.presence.details(&locale)),
+        }
+    }
+}
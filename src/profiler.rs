@@ -0,0 +1,119 @@
+use std::time::{ Duration, Instant };
+
+use bevy::{
+    diagnostic::{ Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic },
+    prelude::*,
+};
+use bevy_rapier2d::plugin::PhysicsSet;
+
+pub struct ProfilerPlugin;
+
+impl Plugin for ProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        for stage in Stage::ALL {
+            app.register_diagnostic(Diagnostic::new(stage.path()).with_suffix("ms"));
+        }
+
+        app.init_resource::<ProfilerOverlay>()
+            .init_resource::<StageTimings>()
+            .add_systems(Update, toggle_profiler)
+            .add_systems(
+                FixedUpdate,
+                (
+                    start_physics_timing
+                        .after(PhysicsSet::SyncBackend)
+                        .before(PhysicsSet::StepSimulation),
+                    end_physics_timing
+                        .after(PhysicsSet::StepSimulation)
+                        .before(PhysicsSet::Writeback),
+                )
+            )
+            .add_systems(Last, submit_stage_timings);
+    }
+}
+
+/// Parts of the frame the profiler overlay shows the cost of, lighting only covers the
+/// work done on the CPU since the passes themselves run on the GPU
+#[derive(Clone, Copy)]
+pub enum Stage {
+    ChunkUpdate,
+    Colliders,
+    TextureUpload,
+    Lighting,
+    Physics,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::ChunkUpdate,
+        Stage::Colliders,
+        Stage::TextureUpload,
+        Stage::Lighting,
+        Stage::Physics,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::ChunkUpdate => "Chunk update",
+            Stage::Colliders => "Colliders",
+            Stage::TextureUpload => "Texture upload",
+            Stage::Lighting => "Lighting",
+            Stage::Physics => "Physics",
+        }
+    }
+
+    pub fn path(self) -> DiagnosticPath {
+        DiagnosticPath::const_new(
+            match self {
+                Stage::ChunkUpdate => "profiler/chunk_update",
+                Stage::Colliders => "profiler/colliders",
+                Stage::TextureUpload => "profiler/texture_upload",
+                Stage::Lighting => "profiler/lighting",
+                Stage::Physics => "profiler/physics",
+            }
+        )
+    }
+}
+
+#[derive(Resource, Default, PartialEq)]
+pub struct ProfilerOverlay(pub bool);
+
+/// Time spent in each stage during the current frame, summed over the simulation passes
+/// and fixed updates that ran in it, and the amount of dirty rects the stages handled
+#[derive(Resource, Default)]
+pub struct StageTimings {
+    elapsed: [Duration; Stage::ALL.len()],
+    physics_start: Option<Instant>,
+    pub update_rects: usize,
+    pub render_rects: usize,
+    pub collider_rects: usize,
+}
+
+impl StageTimings {
+    pub fn record(&mut self, stage: Stage, start: Instant) {
+        self.elapsed[stage as usize] += start.elapsed();
+    }
+}
+
+pub fn toggle_profiler(mut overlay: ResMut<ProfilerOverlay>, keys: Res<ButtonInput<KeyCode>>) {
+    if keys.just_pressed(KeyCode::F3) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+fn start_physics_timing(mut timings: ResMut<StageTimings>) {
+    timings.physics_start = Some(Instant::now());
+}
+
+fn end_physics_timing(mut timings: ResMut<StageTimings>) {
+    if let Some(start) = timings.physics_start.take() {
+        timings.record(Stage::Physics, start);
+    }
+}
+
+fn submit_stage_timings(mut timings: ResMut<StageTimings>, mut diagnostics: Diagnostics) {
+    for stage in Stage::ALL {
+        let elapsed = std::mem::take(&mut timings.elapsed[stage as usize]);
+        diagnostics.add_measurement(&stage.path(), || elapsed.as_secs_f64() * 1000.0);
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::{ prelude::*, utils::HashSet };
+use bevy_persistent::Persistent;
+use serde::Deserialize;
+
+use crate::{
+    actors::player::{ player_setup, Player, PlayerMaterials },
+    notifications::{ Notification, NotificationKind },
+    perks::PerkTarget,
+    registries::Registries,
+    settings::Profile,
+    state::GameState,
+};
+
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnnouncedUnlocks>()
+            .add_systems(OnEnter(GameState::Setup), (
+                grant_starting_materials.after(player_setup),
+                reset_announced_unlocks,
+            ))
+            .add_systems(Update, grant_starting_perks.run_if(resource_exists::<Registries>))
+            .add_systems(
+                Update,
+                announce_unlocks.run_if(
+                    in_state(GameState::Game).and_then(resource_changed::<Persistent<Profile>>)
+                )
+            )
+            .add_systems(
+                OnTransition { from: GameState::Game, to: GameState::LevelInitialization },
+                count_cleared_level
+            );
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum Requirement {
+    Kills(u32),
+    LevelsCleared(u32),
+    MaterialsCollected(f32),
+}
+
+impl Requirement {
+    /// Current value along with the goal
+    pub fn progress(&self, profile: &Profile) -> (f32, f32) {
+        match *self {
+            Requirement::Kills(goal) => (profile.kills as f32, goal as f32),
+            Requirement::LevelsCleared(goal) => (profile.levels_cleared as f32, goal as f32),
+            Requirement::MaterialsCollected(goal) => (profile.materials_collected, goal),
+        }
+    }
+
+    pub fn is_met(&self, profile: &Profile) -> bool {
+        let (value, goal) = self.progress(profile);
+
+        value >= goal
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum Reward {
+    /// Id of the perk applied to every player as if it was picked between levels
+    Perk(String),
+    /// Added to the inventory at the start of the run
+    Material {
+        material_id: String,
+        amount: f32,
+    },
+    /// Replaces the starting attack if it is stronger
+    Weapon {
+        damage: f32,
+        knockback_strength: f32,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Unlock {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub requirement: Requirement,
+    pub reward: Reward,
+}
+
+impl Unlock {
+    pub fn is_unlocked(&self, profile: &Profile) -> bool {
+        self.requirement.is_met(profile)
+    }
+}
+
+pub fn load_unlocks() -> Vec<Unlock> {
+    ron::de::from_str::<Vec<Unlock>>(&std::fs::read_to_string("unlocks.ron").unwrap()).unwrap()
+}
+
+/// Unlocks that were already unlocked or announced during the current run
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct AnnouncedUnlocks(HashSet<String>);
+
+fn reset_announced_unlocks(
+    mut announced: ResMut<AnnouncedUnlocks>,
+    profile: Res<Persistent<Profile>>,
+    registries: Res<Registries>
+) {
+    announced.clear();
+    announced.extend(
+        registries.unlocks
+            .iter()
+            .filter(|unlock| unlock.is_unlocked(&profile))
+            .map(|unlock| unlock.id.clone())
+    );
+}
+
+fn grant_starting_materials(
+    mut player_materials: ResMut<PlayerMaterials>,
+    profile: Res<Persistent<Profile>>,
+    registries: Res<Registries>
+) {
+    for unlock in registries.unlocks.iter().filter(|unlock| unlock.is_unlocked(&profile)) {
+        // materials of perks are shared, so they are granted here instead of for every player
+        if let Reward::Perk(perk_id) = &unlock.reward {
+            if let Some(perk) = registries.perks.iter().find(|perk| perk.id == *perk_id) {
+                perk.grant_materials(&mut player_materials);
+            }
+        }
+
+        let Reward::Material { material_id, amount } = &unlock.reward else {
+            continue;
+        };
+
+        if !registries.materials.contains_key(material_id) {
+            warn!("{}: unknown material {}", unlock.id, material_id);
+            continue;
+        }
+
+        *player_materials.entry(material_id.clone()).or_insert(0.0) += amount;
+    }
+}
+
+/// Players joining in the middle of the run get the same perks
+fn grant_starting_perks(
+    mut player_q: Query<PerkTarget, Added<Player>>,
+    profile: Res<Persistent<Profile>>,
+    registries: Res<Registries>
+) {
+    for mut player in player_q.iter_mut() {
+        for unlock in registries.unlocks.iter().filter(|unlock| unlock.is_unlocked(&profile)) {
+            match &unlock.reward {
+                Reward::Perk(perk_id) => {
+                    match registries.perks.iter().find(|perk| perk.id == *perk_id) {
+                        Some(perk) => perk.apply(&mut player),
+                        None => warn!("{}: unknown perk {}", unlock.id, perk_id),
+                    }
+                }
+                Reward::Weapon { damage, knockback_strength } => {
+                    if *damage > player.attack.value {
+                        player.attack.value = *damage;
+                        player.attack.knockback_strength =
+                            player.attack.knockback_strength.max(*knockback_strength);
+                    }
+                }
+                Reward::Material { .. } => {}
+            }
+        }
+    }
+}
+
+fn announce_unlocks(
+    mut notifications: EventWriter<Notification>,
+    mut announced: ResMut<AnnouncedUnlocks>,
+    profile: Res<Persistent<Profile>>,
+    registries: Res<Registries>,
+    asset_server: Res<AssetServer>
+) {
+    for unlock in registries.unlocks.iter() {
+        if announced.contains(&unlock.id) || !unlock.is_unlocked(&profile) {
+            continue;
+        }
+
+        announced.insert(unlock.id.clone());
+        notifications.send(
+            Notification::new(format!("Unlocked {}: {}", unlock.name, unlock.description))
+                .with_kind(NotificationKind::Success)
+                .with_icon(asset_server.load("ui/level_up.png"))
+        );
+    }
+}
+
+fn count_cleared_level(mut profile: ResMut<Persistent<Profile>>) {
+    profile.levels_cleared += 1;
+    profile.persist().expect("failed to update profile");
+}
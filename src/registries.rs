@@ -1,9 +1,11 @@
 use benimator::FrameRate;
 use bevy::{ prelude::*, utils::HashMap };
+use bevy_persistent::Persistent;
 use bevy_rapier2d::{
     dynamics::{ GravityScale, Velocity },
     geometry::{ Collider, CollisionGroups, Group },
 };
+use itertools::Itertools;
 use seldom_state::{ prelude::{ AnyState, StateMachine }, trigger::IntoTrigger };
 
 use crate::{
@@ -18,17 +20,26 @@ use crate::{
             LandAnimation,
             MoveAnimation,
         },
-        enemy::{ EnemyAI, EnemyBundle },
+        elite::{ EliteEffect, EliteModifier },
+        behavior::{ Behavior, Behaviors },
+        enemy::{ DropTable, EnemyBundle, Gore },
+        health::Resistances,
     },
     animation::{ Animation, AnimationState },
     assets::SpriteAssetCollection,
     constants::{ CHUNK_SIZE, ENEMY_Z },
-    generation::level::Level,
+    gameplay::{ load_gameplay, Gameplay },
+    generation::level::{ InteractableKind, Level },
+    perks::{ load_perks, Perk },
+    progression::{ load_unlocks, Unlock },
+    settings::{ ColorPalette, Config },
     simulation::{
         colliders::{ ENEMY_MASK, HITBOX_MASK, PLAYER_MASK },
-        materials::{ Material, Reaction },
+        materials::{ Material, Reaction, MAX_WATER_STYLES },
         object::Projectile,
     },
+    tutorial::{ load_tutorial, Tutorial },
+    cutscene::{ load_cutscenes, Cutscene },
 };
 
 #[derive(Resource)]
@@ -40,34 +51,135 @@ pub struct Registries {
         String,
         Box<dyn (Fn(Vec2) -> (EnemyBundle, ActorHitboxBundle)) + Sync + Send>
     >,
+    pub elites: Vec<EliteModifier>,
     pub levels: Vec<Level>,
+    pub unlocks: Vec<Unlock>,
+    pub perks: Vec<Perk>,
+    pub tutorial: Tutorial,
+    pub cutscenes: HashMap<String, Cutscene>,
+    pub gameplay: Gameplay,
 }
 
-impl FromWorld for Registries {
-    fn from_world(world: &mut World) -> Self {
-        let mut materials = HashMap::new();
+/// Loads materials along with their reactions, does not depend on any assets
+pub fn load_materials() -> HashMap<String, Material> {
+    let mut materials = HashMap::new();
 
-        materials.insert("air".to_string(), Material::default());
+    materials.insert("air".to_string(), Material::default());
 
-        ron::de
-            ::from_str::<Vec<Material>>(&std::fs::read_to_string("materials.ron").unwrap())
-            .unwrap()
-            .into_iter()
-            .for_each(|material| {
-                materials.insert(material.id.clone(), material);
-            });
+    ron::de
+        ::from_str::<Vec<Material>>(&std::fs::read_to_string("materials.ron").unwrap())
+        .unwrap()
+        .into_iter()
+        .for_each(|material| {
+            materials.insert(material.id.clone(), material);
+        });
 
-        ron::de
-            ::from_str::<Vec<Reaction>>(&std::fs::read_to_string("reactions.ron").unwrap())
-            .unwrap()
-            .into_iter()
-            .for_each(|reaction| {
-                materials.entry(reaction.input_material_1.clone()).and_modify(|material| {
-                    material.reactions
-                        .get_or_insert(HashMap::default())
-                        .insert(reaction.input_material_2.clone(), reaction);
-                });
+    ron::de
+        ::from_str::<Vec<Reaction>>(&std::fs::read_to_string("reactions.ron").unwrap())
+        .unwrap()
+        .into_iter()
+        .for_each(|reaction| {
+            materials.entry(reaction.input_material_1.clone()).and_modify(|material| {
+                material.reactions
+                    .get_or_insert(HashMap::default())
+                    .insert(reaction.input_material_2.clone(), reaction);
             });
+        });
+
+    // sorted by id, so every material keeps its style when the file is reloaded
+    for (index, id) in materials
+        .values()
+        .filter(|material| material.water.is_some())
+        .map(|material| material.id.clone())
+        .sorted()
+        .collect_vec()
+        .into_iter()
+        .enumerate()
+    {
+        let material = materials.get_mut(&id).unwrap();
+
+        if index < MAX_WATER_STYLES {
+            material.water.as_mut().unwrap().index = index as u8;
+        } else {
+            warn!("{}: more than {} water styles, the material is drawn flat", id, MAX_WATER_STYLES);
+            material.water = None;
+        }
+    }
+
+    materials
+}
+
+/// Colors of the materials that are swapped by the palette, picked from the Okabe-Ito set
+fn palette_colors(palette: ColorPalette) -> &'static [(&'static str, [u8; 3])] {
+    match palette {
+        ColorPalette::Default => &[],
+        ColorPalette::Deuteranopia =>
+            &[
+                ("acid", [0xcc, 0x79, 0xa7]),
+                ("lava", [0xe6, 0x9f, 0x00]),
+                ("water", [0x00, 0x72, 0xb2]),
+            ],
+        ColorPalette::Protanopia =>
+            &[
+                ("acid", [0xcc, 0x79, 0xa7]),
+                ("lava", [0xf0, 0xe4, 0x42]),
+                ("water", [0x00, 0x72, 0xb2]),
+            ],
+        ColorPalette::Tritanopia =>
+            &[
+                ("acid", [0xcc, 0x79, 0xa7]),
+                ("lava", [0xd5, 0x5e, 0x00]),
+                ("water", [0x00, 0x72, 0xb2]),
+            ],
+    }
+}
+
+/// Recolors materials along with their light, alpha is kept from the original material
+pub fn apply_palette(materials: &mut HashMap<String, Material>, palette: ColorPalette) {
+    for (id, [red, green, blue]) in palette_colors(palette) {
+        let Some(material) = materials.get_mut(*id) else {
+            continue;
+        };
+
+        material.color = [*red, *green, *blue, material.color[3]];
+
+        if let Some(lighting) = &mut material.lighting {
+            *lighting = [*red, *green, *blue, lighting[3]];
+        }
+
+        if let Some(emission) = &mut material.emission {
+            emission.color = [*red, *green, *blue];
+        }
+    }
+}
+
+/// Materials are reloaded when the palette is changed in the settings
+pub fn update_material_palette(
+    mut registries: ResMut<Registries>,
+    mut applied: Local<Option<ColorPalette>>,
+    config: Res<Persistent<Config>>
+) {
+    let Some(palette) = *applied else {
+        *applied = Some(config.palette);
+        return;
+    };
+
+    if palette == config.palette {
+        return;
+    }
+
+    registries.materials = load_materials();
+    apply_palette(&mut registries.materials, config.palette);
+    *applied = Some(config.palette);
+}
+
+impl FromWorld for Registries {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = load_materials();
+
+        if let Some(config) = world.get_resource::<Persistent<Config>>() {
+            apply_palette(&mut materials, config.palette);
+        }
 
         let sprites = world.get_resource::<SpriteAssetCollection>().cloned().unwrap();
         let mut texture_atlas_layouts = world
@@ -88,14 +200,23 @@ impl FromWorld for Registries {
             "plant".into(),
             Box::new(move |position: Vec2| (
                 EnemyBundle {
-                    ai: EnemyAI::Projectiles {
-                        base_material: "sand".to_string(),
-                        cooldown: Timer::from_seconds(2.0, TimerMode::Repeating),
-                        projectile: Projectile::new(0.1, 4.0).insert_on_contact(),
-                        speed: 0.5,
-                        range: 64.0,
-                    },
+                    behaviors: Behaviors::new(
+                        vec![Behavior::Shoot {
+                            base_material: "sand".to_string(),
+                            cooldown: Timer::from_seconds(2.0, TimerMode::Repeating),
+                            projectile: Projectile::new(0.1, 4.0).with_bounce(0.5, 1),
+                            speed: 0.5,
+                            range: 64.0,
+                        }]
+                    ),
                     name: Name::new("Plant"),
+                    // dry leaves catch fire easily
+                    resistances: Resistances {
+                        fire: -0.5,
+                        ..Default::default()
+                    },
+                    gore: Gore::new(&[("sap", 0.3), ("seeds", 0.1)]),
+                    drops: DropTable::new(&[("sap", 16), ("seeds", 8)]),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -165,6 +286,19 @@ impl FromWorld for Registries {
             Box::new(move |position: Vec2| (
                 EnemyBundle {
                     name: Name::new("Bat"),
+                    // small and erratic, shots often only graze it
+                    resistances: Resistances {
+                        projectile: 0.25,
+                        ..Default::default()
+                    },
+                    drops: DropTable::new(&[("sulfur", 12)]),
+                    behaviors: Behaviors::new(
+                        vec![
+                            Behavior::Flee { health: 0.25 },
+                            Behavior::Chase,
+                            Behavior::Patrol { range: 48.0 }
+                        ]
+                    ),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -235,6 +369,19 @@ impl FromWorld for Registries {
             Box::new(move |position: Vec2| (
                 EnemyBundle {
                     name: Name::new("fungus_tiny"),
+                    resistances: Resistances {
+                        fire: -0.5,
+                        ..Default::default()
+                    },
+                    gore: Gore::new(&[("fungus", 0.4)]),
+                    drops: DropTable::new(&[("fungus", 12)]),
+                    behaviors: Behaviors::new(
+                        vec![
+                            Behavior::CallForHelp { radius: 96.0 },
+                            Behavior::Chase,
+                            Behavior::Patrol { range: 24.0 }
+                        ]
+                    ),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -305,6 +452,23 @@ impl FromWorld for Registries {
             Box::new(move |position: Vec2| (
                 EnemyBundle {
                     name: Name::new("fungus_big"),
+                    resistances: Resistances {
+                        fire: -0.5,
+                        knockback: 0.5,
+                        ..Default::default()
+                    },
+                    gore: Gore::new(&[("fungus", 0.5)]),
+                    drops: DropTable::new(&[("fungus", 32), ("healium", 8)]).with_item(
+                        InteractableKind::ToolUpgrade,
+                        0.05
+                    ),
+                    behaviors: Behaviors::new(
+                        vec![
+                            Behavior::CallForHelp { radius: 128.0 },
+                            Behavior::Chase,
+                            Behavior::Patrol { range: 16.0 }
+                        ]
+                    ),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -375,6 +539,17 @@ impl FromWorld for Registries {
             Box::new(move |position: Vec2| (
                 EnemyBundle {
                     name: Name::new("rat"),
+                    // hoards whatever it digs up
+                    drops: DropTable::new(&[("bone", 8), ("coal", 8)]).with_item(InteractableKind::Key, 0.1),
+                    behaviors: Behaviors::new(
+                        vec![
+                            Behavior::Flee { health: 0.3 },
+                            Behavior::Chase,
+                            Behavior::Patrol { range: 64.0 },
+                            // burrows through sand and dirt
+                            Behavior::dig(6.0, 48.0)
+                        ]
+                    ),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -445,6 +620,27 @@ impl FromWorld for Registries {
             Box::new(move |position: Vec2| (
                 EnemyBundle {
                     name: Name::new("frog"),
+                    // lives in the swamps next to acid pools
+                    resistances: Resistances {
+                        acid: 0.75,
+                        ..Default::default()
+                    },
+                    gore: Gore::new(&[("blood", 0.3), ("bone", 0.05), ("acid", 0.1)]),
+                    drops: DropTable::new(&[("acid", 24)]),
+                    behaviors: Behaviors::new(
+                        vec![
+                            Behavior::Kite { distance: 32.0 },
+                            Behavior::Shoot {
+                                base_material: "water".to_string(),
+                                cooldown: Timer::from_seconds(3.0, TimerMode::Repeating),
+                                projectile: Projectile::new(0.1, 2.0).with_splash(1.5),
+                                speed: 0.4,
+                                range: 64.0,
+                            },
+                            Behavior::Chase,
+                            Behavior::Patrol { range: 24.0 }
+                        ]
+                    ),
                     actor: ActorBundle {
                         actor: Actor {
                             position: position * (CHUNK_SIZE as f32),
@@ -603,6 +799,45 @@ impl FromWorld for Registries {
             ))
         );
 
+        let elites = vec![
+            EliteModifier {
+                name: "Armored".into(),
+                tint: Color::rgb(0.65, 0.7, 0.8),
+                health: 1.5,
+                score: 1.5,
+                remains: "sand".into(),
+                effect: EliteEffect::Armored { damage_taken: 0.5 },
+                intro: Some("elite_armored".into()),
+            },
+            EliteModifier {
+                name: "Fast".into(),
+                tint: Color::rgb(0.6, 1.0, 0.6),
+                health: 1.0,
+                score: 1.5,
+                remains: "steam".into(),
+                effect: EliteEffect::Fast { speed: 1.75 },
+                intro: Some("elite_fast".into()),
+            },
+            EliteModifier {
+                name: "Explosive".into(),
+                tint: Color::rgb(1.0, 0.55, 0.35),
+                health: 1.25,
+                score: 2.0,
+                remains: "burning_gas".into(),
+                effect: EliteEffect::Explosive { radius: 16.0, damage: 8.0 },
+                intro: Some("elite_explosive".into()),
+            },
+            EliteModifier {
+                name: "Trailing".into(),
+                tint: Color::rgb(0.55, 0.45, 0.6),
+                health: 1.25,
+                score: 1.5,
+                remains: "oil".into(),
+                effect: EliteEffect::Trailing { material: "oil".into(), chance: 0.2 },
+                intro: Some("elite_trailing".into()),
+            },
+        ];
+
         let levels = ron::de
             ::from_str::<Vec<Level>>(&std::fs::read_to_string("levels.ron").unwrap())
             .unwrap();
@@ -611,6 +846,12 @@ impl FromWorld for Registries {
             materials,
             levels,
             enemies,
+            elites,
+            unlocks: load_unlocks(),
+            perks: load_perks(),
+            tutorial: load_tutorial(),
+            cutscenes: load_cutscenes(),
+            gameplay: load_gameplay(),
         }
     }
 }
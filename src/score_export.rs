@@ -0,0 +1,295 @@
+use std::{
+    fs::{ self, OpenOptions },
+    io::{ self, Write },
+    path::{ Path, PathBuf },
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_persistent::Persistent;
+use serde::{ Deserialize, Serialize };
+
+use crate::{
+    curses::apply_curse_bonus,
+    daily::RunMode,
+    difficulty::Difficulty,
+    generation::{ noise::Seed, LevelCounter, LevelData },
+    gui::Score,
+    settings::{
+        load_profile_config,
+        load_scoreboard,
+        selected_profile_dir,
+        Config,
+        Profile,
+        ProfileIndex,
+        Scoreboard,
+    },
+    state::GameState,
+};
+
+const CSV_HEADER: &str = "timestamp,seed,level,score,duration,kills,mutators,difficulty,name,daily";
+
+pub struct ScoreExportPlugin;
+
+impl Plugin for ScoreExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(OnEnter(GameState::Setup), |mut commands: Commands| {
+                commands.insert_resource(RunStats::default());
+            })
+            .add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, (
+                record_mutators,
+                start_level_timer,
+            ))
+            .add_systems(Update, tick_run_duration.run_if(in_state(GameState::Game)))
+            .add_systems(OnEnter(GameState::GameOver), (
+                record_run.after(apply_curse_bonus),
+                record_lifetime_stats.after(apply_curse_bonus),
+            ));
+    }
+}
+
+/// Statistics of the current run that are not kept anywhere else
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub duration: f32,
+    pub kills: u32,
+    pub mutators: Vec<String>,
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    /// Kills by the name of the enemy
+    pub kills_by_type: HashMap<String, u32>,
+    pub materials_collected: f32,
+    pub pixels_destroyed: u32,
+    /// Seconds spent in every level, in the order they were played
+    pub level_times: Vec<f32>,
+    /// Ids of the perks picked between levels, in the order they were picked
+    pub perks: Vec<String>,
+    /// Timestamp of the scoreboard entry of the run once it ended, none if it was not recorded
+    pub recorded_at: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunRecord {
+    /// Seconds since unix epoch at the end of the run
+    pub timestamp: u64,
+    pub seed: u32,
+    pub level: u32,
+    pub score: i32,
+    /// Seconds spent in levels, splash screens and menus are not counted
+    pub duration: f32,
+    pub kills: u32,
+    pub mutators: Vec<String>,
+    /// Runs recorded before difficulties were added were played on normal
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    /// Entered on the game over screen, starts as the name of the previous run or the profile
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub daily: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ExportFormat::Json,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+impl RunRecord {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{:.1},{},{},{},{},{}",
+            self.timestamp,
+            self.seed,
+            self.level,
+            self.score,
+            self.duration,
+            self.kills,
+            csv_field(&self.mutators.join(";")),
+            csv_field(&self.difficulty.name().to_lowercase()),
+            csv_field(&self.name),
+            self.daily
+        )
+    }
+}
+
+/// Quotes fields holding separators, quotes or line breaks as described by RFC 4180
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn record_mutators(level_data: Res<LevelData>, mut stats: ResMut<RunStats>) {
+    let level = &level_data.0;
+
+    let mutators = [
+        ("mirrored", level.mirror.is_enabled()),
+        ("escalation", level.escalation.is_some()),
+    ];
+
+    for (name, enabled) in mutators {
+        if enabled && !stats.mutators.iter().any(|mutator| mutator == name) {
+            stats.mutators.push(name.to_string());
+        }
+    }
+}
+
+fn start_level_timer(mut stats: ResMut<RunStats>) {
+    stats.level_times.push(0.0);
+}
+
+fn tick_run_duration(mut stats: ResMut<RunStats>, time: Res<Time>) {
+    stats.duration += time.delta_seconds();
+
+    if let Some(level_time) = stats.level_times.last_mut() {
+        *level_time += time.delta_seconds();
+    }
+}
+
+fn record_lifetime_stats(stats: Res<RunStats>, mut profile: ResMut<Persistent<Profile>>) {
+    profile.runs += 1;
+    profile.play_time += stats.duration;
+    profile.damage_dealt += stats.damage_dealt;
+    profile.damage_taken += stats.damage_taken;
+    profile.pixels_destroyed += stats.pixels_destroyed;
+
+    for (name, kills) in stats.kills_by_type.iter() {
+        *profile.kills_by_type.entry(name.clone()).or_insert(0) += kills;
+    }
+
+    profile.persist().expect("failed to update profile");
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn record_run(
+    mut stats: ResMut<RunStats>,
+    score: Res<Score>,
+    level: Res<LevelCounter>,
+    seed: Res<Seed>,
+    config: Res<Persistent<Config>>,
+    difficulty: Res<Difficulty>,
+    run_mode: Res<RunMode>,
+    profiles: Res<Persistent<ProfileIndex>>,
+    mut scoreboard: ResMut<Persistent<Scoreboard>>
+) {
+    // the tutorial is not scored
+    if *run_mode == RunMode::Tutorial {
+        return;
+    }
+
+    let name = scoreboard.runs
+        .last()
+        .map(|record| record.name.clone())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| profiles.selected().name.clone());
+
+    let record = RunRecord {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        seed: seed.0,
+        level: level.0,
+        score: score.value,
+        duration: stats.duration,
+        kills: stats.kills,
+        mutators: stats.mutators.clone(),
+        difficulty: *difficulty,
+        name,
+        daily: matches!(*run_mode, RunMode::Daily(..)),
+    };
+
+    if let Some(path) = &config.score_export {
+        if let Err(error) = append_run(path, &record) {
+            error!("{}: failed to export run: {}", path.display(), error);
+        }
+    }
+
+    stats.recorded_at = Some(record.timestamp);
+
+    scoreboard.runs.push(record);
+    scoreboard.prune();
+    scoreboard.persist().expect("failed to update scoreboard");
+}
+
+/// Adds a single run to the export file, creating it when missing
+pub fn append_run(path: &Path, record: &RunRecord) -> io::Result<()> {
+    match ExportFormat::from_path(path) {
+        ExportFormat::Csv => {
+            let is_new = fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+            if is_new {
+                writeln!(file, "{}", CSV_HEADER)?;
+            }
+
+            writeln!(file, "{}", record.to_csv())
+        }
+        ExportFormat::Json => {
+            let mut records: Vec<RunRecord> = match fs::read_to_string(path) {
+                Ok(contents) if !contents.trim().is_empty() => serde_json::from_str(&contents)?,
+                Ok(_) => vec![],
+                Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+                Err(error) => {
+                    return Err(error);
+                }
+            };
+
+            records.push(record.clone());
+            export_runs(path, &records)
+        }
+    }
+}
+
+/// Overwrites the export file with the given runs
+pub fn export_runs(path: &Path, records: &[RunRecord]) -> io::Result<()> {
+    match ExportFormat::from_path(path) {
+        ExportFormat::Csv => {
+            let mut contents = String::from(CSV_HEADER);
+
+            for record in records {
+                contents.push('\n');
+                contents.push_str(&record.to_csv());
+            }
+            contents.push('\n');
+
+            fs::write(path, contents)
+        }
+        ExportFormat::Json => fs::write(path, serde_json::to_string_pretty(records)?),
+    }
+}
+
+/// Writes the whole scoreboard to a file without starting the game,
+/// usage: `sandforge --export-scores [path]`, path defaults to `score_export` from the config,
+/// the scoreboard of the profile that was played last is exported
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let dir = selected_profile_dir();
+
+    let Some(path) = args
+        .next()
+        .map(PathBuf::from)
+        .or_else(|| load_profile_config(&dir).score_export.clone()) else {
+        eprintln!("usage: sandforge --export-scores <path.csv|path.json>");
+        std::process::exit(1);
+    };
+
+    let scoreboard = load_scoreboard(&dir);
+
+    match export_runs(&path, &scoreboard.runs) {
+        Ok(()) => println!("exported {} runs to {}", scoreboard.runs.len(), path.display()),
+        Err(error) => {
+            eprintln!("{}: failed to export scoreboard: {}", path.display(), error);
+            std::process::exit(1);
+        }
+    }
+}
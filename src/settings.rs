@@ -1,11 +1,23 @@
+use std::{ fs, io, path::{ Path, PathBuf } };
+
 use bevy::{
     audio::Volume,
     prelude::*,
+    transform::TransformSystem,
+    utils::HashMap,
     window::{ PresentMode, PrimaryWindow, WindowMode },
 };
 use bevy_persistent::{ Persistent, StorageFormat };
+use leafwing_input_manager::{
+    axislike::{ DualAxis, SingleAxis, VirtualAxis },
+    buttonlike::MouseWheelDirection,
+    input_map::InputMap,
+    user_input::InputKind,
+};
 use serde::{ Deserialize, Serialize };
 
+use crate::{ actors::player::PlayerActions, daily::DailyRecord, localization::DEFAULT_LANGUAGE, score_export::RunRecord };
+
 #[derive(Debug, Resource, Serialize, Deserialize, Clone)]
 pub struct Config {
     #[serde(default)]
@@ -20,73 +32,769 @@ pub struct Config {
     #[serde(default)]
     pub volume: i32,
 
+    /// Share of the master volume for every audio bus, in percent
+    #[serde(default = "default_bus_volume")]
+    pub music_volume: i32,
+    #[serde(default = "default_bus_volume")]
+    pub sfx_volume: i32,
+    #[serde(default = "default_bus_volume")]
+    pub ambience_volume: i32,
+    #[serde(default = "default_bus_volume")]
+    pub ui_volume: i32,
+
     #[serde(default)]
     pub spatial: bool,
+
+    #[serde(default = "default_controls")]
+    pub controls: Vec<Control>,
+
+    /// Preferred zoom of the camera, multiplier of the default projection scale
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+
+    #[serde(default = "default_zoom_range")]
+    pub zoom_range: [f32; 2],
+
+    /// Share of the window resolution the world is rendered at before it is upscaled,
+    /// the interface is always drawn at the full resolution
+    #[serde(default = "default_render_scale")]
+    pub render_scale: f32,
+
+    /// How far and smoothly light spreads, lower tiers take less time on the GPU
+    #[serde(default)]
+    pub lighting_quality: LightingQuality,
+
+    /// Scanlines and curvature of an old monitor over the world, strength is in percent
+    #[serde(default)]
+    pub crt_filter: bool,
+    #[serde(default = "default_filter_intensity")]
+    pub crt_intensity: i32,
+
+    /// Retro palette the world is limited to, strength is the share of the palette color in percent
+    #[serde(default)]
+    pub retro_palette: RetroPalette,
+    #[serde(default = "default_filter_intensity")]
+    pub retro_palette_intensity: i32,
+
+    /// Largest distance in pixels between terrain colliders and the outline of the pixels,
+    /// higher values produce fewer vertices and smoother slopes
+    #[serde(default = "default_collider_tolerance")]
+    pub collider_tolerance: f32,
+
+    /// File every finished run is appended to, format is picked by the extension (`.csv` or `.json`)
+    #[serde(default)]
+    pub score_export: Option<PathBuf>,
+
+    /// Tones down damage flashes and the glow of fire and emissive materials
+    #[serde(default)]
+    pub reduced_flashes: bool,
+
+    /// Outlines enemies and projectiles with a bright color
+    #[serde(default)]
+    pub high_contrast: bool,
+
+    /// Alternative colors for materials that are hard to tell apart, applied when registries are loaded
+    #[serde(default)]
+    pub palette: ColorPalette,
+
+    /// Floating numbers above actors showing the damage of every hit
+    #[serde(default = "default_damage_numbers")]
+    pub damage_numbers: bool,
+
+    /// Enemies leave blood and bones behind instead of only a puff of mist
+    #[serde(default = "default_gore")]
+    pub gore: bool,
+
+    /// Nudges enemy counts and damage within the bounds of the level depending on how recent levels went
+    #[serde(default)]
+    pub adaptive_difficulty: bool,
+
+    /// Id of the language file the interface strings are taken from
+    #[serde(default = "default_language")]
+    pub language: String,
+
+    /// Spring of the hook rope, higher stiffness pulls harder towards the rest length
+    /// and higher damping takes more energy out of the swing
+    #[serde(default = "default_hook_stiffness")]
+    pub hook_stiffness: f32,
+    #[serde(default = "default_hook_damping")]
+    pub hook_damping: f32,
+}
+
+/// Presets of the light propagation, custom values can only be set in the config file
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum LightingQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Custom(LightingPreset),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct LightingPreset {
+    /// Blur passes over the lighting texture, light travels further with every one
+    pub passes: u32,
+    /// Multiplier of the distance between the samples of a pass
+    pub radius: f32,
+    /// Exponent applied to the light after every pass, values above 1 make it fade out faster
+    pub falloff: f32,
+}
+
+impl LightingQuality {
+    pub fn preset(&self) -> LightingPreset {
+        match self {
+            // fewer passes with wider steps reach about as far, but with visible banding
+            LightingQuality::Low => LightingPreset { passes: 3, radius: 2.0, falloff: 1.1 },
+            LightingQuality::Medium => LightingPreset { passes: 5, radius: 1.5, falloff: 1.05 },
+            LightingQuality::High => LightingPreset { passes: 8, radius: 1.0, falloff: 1.0 },
+            LightingQuality::Custom(preset) => *preset,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPalette {
+    #[default]
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+/// Palettes of old consoles and home computers the image can be limited to
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RetroPalette {
+    #[default]
+    Off,
+    GameBoy,
+    Pico8,
+    Cga,
+}
+
+impl RetroPalette {
+    pub fn colors(&self) -> &'static [[u8; 3]] {
+        match self {
+            RetroPalette::Off => &[],
+            RetroPalette::GameBoy => &[[0x0f, 0x38, 0x0f], [0x30, 0x62, 0x30], [0x8b, 0xac, 0x0f], [0x9b, 0xbc, 0x0f]],
+            RetroPalette::Pico8 =>
+                &[
+                    [0x00, 0x00, 0x00],
+                    [0x1d, 0x2b, 0x53],
+                    [0x7e, 0x25, 0x53],
+                    [0x00, 0x87, 0x51],
+                    [0xab, 0x52, 0x36],
+                    [0x5f, 0x57, 0x4f],
+                    [0xc2, 0xc3, 0xc7],
+                    [0xff, 0xf1, 0xe8],
+                    [0xff, 0x00, 0x4d],
+                    [0xff, 0xa3, 0x00],
+                    [0xff, 0xec, 0x27],
+                    [0x00, 0xe4, 0x36],
+                    [0x29, 0xad, 0xff],
+                    [0x83, 0x76, 0x9c],
+                    [0xff, 0x77, 0xa8],
+                    [0xff, 0xcc, 0xaa],
+                ],
+            // high intensity variant of the cyan and magenta palette
+            RetroPalette::Cga => &[[0x00, 0x00, 0x00], [0x55, 0xff, 0xff], [0xff, 0x55, 0xff], [0xff, 0xff, 0xff]],
+        }
+    }
 }
 
 fn default_volume() -> i32 {
     50
 }
 
+fn default_bus_volume() -> i32 {
+    100
+}
+
+fn default_zoom() -> f32 {
+    1.0
+}
+
+fn default_zoom_range() -> [f32; 2] {
+    [0.5, 2.0]
+}
+
+fn default_render_scale() -> f32 {
+    1.0
+}
+
+fn default_language() -> String {
+    DEFAULT_LANGUAGE.to_string()
+}
+
+fn default_filter_intensity() -> i32 {
+    50
+}
+
+fn default_collider_tolerance() -> f32 {
+    1.0
+}
+
+fn default_damage_numbers() -> bool {
+    true
+}
+
+fn default_gore() -> bool {
+    true
+}
+
+fn default_hook_stiffness() -> f32 {
+    0.25
+}
+
+fn default_hook_damping() -> f32 {
+    0.05
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Wheel(MouseWheelDirection),
+    Axis(KeyCode, KeyCode),
+}
+
+impl Binding {
+    pub fn describe(&self) -> String {
+        let key_name = |key: &KeyCode| {
+            let name = format!("{:?}", key);
+            name.strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"))
+                .unwrap_or(&name)
+                .to_string()
+        };
+
+        match self {
+            Binding::Key(key) => key_name(key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Wheel(direction) => format!("Wheel {:?}", direction),
+            Binding::Axis(negative, positive) =>
+                format!("{} / {}", key_name(negative), key_name(positive)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Control {
+    pub action: PlayerActions,
+    pub binding: Binding,
+}
+
+/// Lists every player action, bindings missing from the config fall back to these
+pub fn default_controls() -> Vec<Control> {
+    [
+        (PlayerActions::Run, Binding::Axis(KeyCode::KeyA, KeyCode::KeyD)),
+        (PlayerActions::Jump, Binding::Key(KeyCode::Space)),
+        (PlayerActions::Swim, Binding::Key(KeyCode::KeyW)),
+        (PlayerActions::Attack, Binding::Key(KeyCode::KeyF)),
+        (PlayerActions::Crouch, Binding::Key(KeyCode::KeyS)),
+        (PlayerActions::Dash, Binding::Key(KeyCode::KeyQ)),
+        (PlayerActions::Roll, Binding::Key(KeyCode::ShiftLeft)),
+        (PlayerActions::Hook, Binding::Mouse(MouseButton::Right)),
+        (PlayerActions::Reel, Binding::Axis(KeyCode::KeyZ, KeyCode::KeyX)),
+        (PlayerActions::Interaction, Binding::Key(KeyCode::KeyE)),
+        (PlayerActions::DropObject, Binding::Key(KeyCode::KeyB)),
+        (PlayerActions::Shoot, Binding::Key(KeyCode::KeyR)),
+        (PlayerActions::Throw, Binding::Key(KeyCode::KeyT)),
+        (PlayerActions::Summon, Binding::Key(KeyCode::KeyC)),
+        (PlayerActions::Collect, Binding::Key(KeyCode::KeyG)),
+        (PlayerActions::Torch, Binding::Key(KeyCode::KeyV)),
+        (PlayerActions::Storage, Binding::Key(KeyCode::Tab)),
+        (PlayerActions::SkipTutorial, Binding::Key(KeyCode::Enter)),
+        (PlayerActions::SelectMaterialNext, Binding::Wheel(MouseWheelDirection::Up)),
+        (PlayerActions::SelectMaterialPrevious, Binding::Wheel(MouseWheelDirection::Down)),
+        (PlayerActions::ZoomIn, Binding::Key(KeyCode::Equal)),
+        (PlayerActions::ZoomOut, Binding::Key(KeyCode::Minus)),
+    ]
+        .into_iter()
+        .map(|(action, binding)| Control { action, binding })
+        .collect()
+}
+
+impl Config {
+    pub fn binding(&self, action: PlayerActions) -> Binding {
+        self.controls
+            .iter()
+            .chain(default_controls().iter())
+            .find(|control| control.action == action)
+            .map(|control| control.binding)
+            .unwrap()
+    }
+
+    pub fn set_binding(&mut self, action: PlayerActions, binding: Binding) {
+        match self.controls.iter_mut().find(|control| control.action == action) {
+            Some(control) => {
+                control.binding = binding;
+            }
+            None => self.controls.push(Control { action, binding }),
+        }
+    }
+
+    /// Keyboard and mouse bindings along with the gamepad layout, used when a single player is present
+    pub fn input_map(&self) -> InputMap<PlayerActions> {
+        let mut input_map = self.keyboard_input_map();
+        input_map.merge(&Self::gamepad_input_map());
+
+        input_map
+    }
+
+    pub fn keyboard_input_map(&self) -> InputMap<PlayerActions> {
+        let mut input_map = InputMap::default();
+
+        for Control { action, .. } in default_controls() {
+            match self.binding(action) {
+                Binding::Key(key) => input_map.insert(action, key),
+                Binding::Mouse(button) => input_map.insert(action, button),
+                Binding::Wheel(direction) => input_map.insert(action, direction),
+                Binding::Axis(negative, positive) =>
+                    input_map.insert(action, VirtualAxis {
+                        negative: InputKind::PhysicalKey(negative),
+                        positive: InputKind::PhysicalKey(positive),
+                    }),
+            };
+        }
+
+        input_map
+    }
+
+    // gamepad layout is fixed, only keyboard and mouse bindings are configurable
+    pub fn gamepad_input_map() -> InputMap<PlayerActions> {
+        let mut input_map = InputMap::default();
+
+        input_map
+            .insert(PlayerActions::Run, SingleAxis::symmetric(GamepadAxisType::LeftStickX, 0.1))
+            .insert(PlayerActions::Aim, DualAxis::right_stick())
+            .insert(PlayerActions::Jump, GamepadButtonType::South)
+            .insert(PlayerActions::Swim, GamepadButtonType::DPadUp)
+            .insert(PlayerActions::Attack, GamepadButtonType::West)
+            .insert(PlayerActions::Crouch, GamepadButtonType::East)
+            .insert(PlayerActions::Interaction, GamepadButtonType::North)
+            // crouching turns interacting into dropping the first stored object and summoning into
+            // opening the storage, the chords take precedence over the single buttons
+            .insert_chord(PlayerActions::DropObject, [
+                GamepadButtonType::East,
+                GamepadButtonType::North,
+            ])
+            .insert_chord(PlayerActions::Storage, [GamepadButtonType::East, GamepadButtonType::Select])
+            .insert(PlayerActions::Dash, GamepadButtonType::LeftTrigger)
+            .insert(PlayerActions::Roll, GamepadButtonType::LeftThumb)
+            .insert(PlayerActions::Collect, GamepadButtonType::RightTrigger)
+            .insert(PlayerActions::Hook, GamepadButtonType::LeftTrigger2)
+            // pushing the stick up reels the rope in
+            .insert(
+                PlayerActions::Reel,
+                SingleAxis::symmetric(GamepadAxisType::LeftStickY, 0.1).inverted()
+            )
+            .insert(PlayerActions::Shoot, GamepadButtonType::RightTrigger2)
+            .insert(PlayerActions::Throw, GamepadButtonType::DPadDown)
+            .insert(PlayerActions::Summon, GamepadButtonType::Select)
+            .insert(PlayerActions::Torch, GamepadButtonType::RightThumb)
+            // clicking both sticks at once takes precedence over rolling and torches
+            .insert_chord(PlayerActions::SkipTutorial, [
+                GamepadButtonType::LeftThumb,
+                GamepadButtonType::RightThumb,
+            ])
+            .insert(PlayerActions::SelectMaterialNext, GamepadButtonType::DPadRight)
+            .insert(PlayerActions::SelectMaterialPrevious, GamepadButtonType::DPadLeft);
+
+        input_map
+    }
+}
+
 #[derive(Debug, Resource, Serialize, Deserialize, Clone)]
 pub struct Scoreboard {
     pub scores: Vec<(i32, i32)>,
+
+    #[serde(default)]
+    pub runs: Vec<RunRecord>,
+
+    /// Kept apart from the other scores, every entry of the same day was played on the same levels
+    #[serde(default)]
+    pub daily: Vec<DailyRecord>,
+}
+
+// entries kept in each list of the scoreboard, the lowest scores are dropped past it
+pub const MAX_SCOREBOARD_ENTRIES: usize = 100;
+
+impl Scoreboard {
+    /// Drops the lowest scores of every list until it fits [`MAX_SCOREBOARD_ENTRIES`], the rest keep their order
+    pub fn prune(&mut self) {
+        prune_lowest(&mut self.scores, |(_, score)| *score);
+        prune_lowest(&mut self.runs, |record| record.score);
+        prune_lowest(&mut self.daily, |record| record.score);
+    }
+}
+
+fn prune_lowest<T>(entries: &mut Vec<T>, score: impl Fn(&T) -> i32) {
+    while entries.len() > MAX_SCOREBOARD_ENTRIES {
+        // the oldest of equal scores goes first
+        let lowest = entries
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| score(entry))
+            .map(|(index, _)| index)
+            .unwrap();
+
+        entries.remove(lowest);
+    }
+}
+
+/// Totals across every run, unlocks are granted once these reach their requirements
+#[derive(Debug, Resource, Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub kills: u32,
+
+    #[serde(default)]
+    pub levels_cleared: u32,
+
+    #[serde(default)]
+    pub materials_collected: f32,
+
+    #[serde(default)]
+    pub best_score: i32,
+
+    /// Totals below are added up when a run ends
+    #[serde(default)]
+    pub runs: u32,
+
+    /// Seconds spent in levels
+    #[serde(default)]
+    pub play_time: f32,
+
+    #[serde(default)]
+    pub damage_dealt: f32,
+
+    #[serde(default)]
+    pub damage_taken: f32,
+
+    #[serde(default)]
+    pub pixels_destroyed: u32,
+
+    #[serde(default)]
+    pub kills_by_type: HashMap<String, u32>,
+
+    /// New runs start on the first level instead of the tutorial
+    #[serde(default)]
+    pub skip_tutorial: bool,
+}
+
+pub const DEFAULT_PROFILE_NAME: &str = "Player";
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap().join("sandforge")
+}
+
+/// Shared config, new profiles start with a copy of it
+pub fn load_config() -> Persistent<Config> {
+    Persistent::<Config>
+        ::builder()
+        .name("Main config")
+        .format(StorageFormat::Toml)
+        .path(config_dir().join("config.toml"))
+        .default(Config {
+            vsync: PresentMode::AutoVsync,
+            mode: WindowMode::Windowed,
+            resolution: [1280, 720],
+            volume: default_volume(),
+            music_volume: default_bus_volume(),
+            sfx_volume: default_bus_volume(),
+            ambience_volume: default_bus_volume(),
+            ui_volume: default_bus_volume(),
+            spatial: false,
+            controls: default_controls(),
+            zoom: default_zoom(),
+            zoom_range: default_zoom_range(),
+            render_scale: default_render_scale(),
+            lighting_quality: LightingQuality::default(),
+            crt_filter: false,
+            crt_intensity: default_filter_intensity(),
+            retro_palette: RetroPalette::Off,
+            retro_palette_intensity: default_filter_intensity(),
+            collider_tolerance: default_collider_tolerance(),
+            score_export: None,
+            reduced_flashes: false,
+            high_contrast: false,
+            palette: ColorPalette::Default,
+            damage_numbers: default_damage_numbers(),
+            gore: default_gore(),
+            adaptive_difficulty: false,
+            language: default_language(),
+            hook_stiffness: default_hook_stiffness(),
+            hook_damping: default_hook_damping(),
+        })
+        .build()
+        .expect("failed to initialize config")
+}
+
+/// Settings of a profile, overriding the shared config once they are changed
+pub fn load_profile_config(dir: &Path) -> Persistent<Config> {
+    Persistent::<Config>
+        ::builder()
+        .name("Profile config")
+        .format(StorageFormat::Toml)
+        .path(dir.join("config.toml"))
+        .default(load_config().get().clone())
+        .build()
+        .expect("failed to initialize config")
+}
+
+pub fn load_scoreboard(dir: &Path) -> Persistent<Scoreboard> {
+    Persistent::<Scoreboard>
+        ::builder()
+        .name("Scoreboard")
+        .format(StorageFormat::Toml)
+        .path(dir.join("scoreboard.toml"))
+        .default(Scoreboard {
+            scores: vec![],
+            runs: vec![],
+            daily: vec![],
+        })
+        .build()
+        .expect("failed to initialize scores")
+}
+
+pub fn load_profile(dir: &Path) -> Persistent<Profile> {
+    Persistent::<Profile>
+        ::builder()
+        .name("Profile")
+        .format(StorageFormat::Toml)
+        .path(dir.join("profile.toml"))
+        .default(Profile::default())
+        .build()
+        .expect("failed to initialize profile")
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileSlot {
+    /// Name of the directory the files of the profile are kept in
+    pub id: String,
+    pub name: String,
+}
+
+impl ProfileSlot {
+    pub fn dir(&self) -> PathBuf {
+        config_dir().join("profiles").join(&self.id)
+    }
+}
+
+/// Every profile along with the one that was played last
+#[derive(Debug, Resource, Serialize, Deserialize, Clone, Default)]
+pub struct ProfileIndex {
+    #[serde(default)]
+    pub slots: Vec<ProfileSlot>,
+
+    #[serde(default)]
+    pub selected: usize,
+}
+
+impl ProfileIndex {
+    pub fn selected(&self) -> &ProfileSlot {
+        &self.slots[self.selected.min(self.slots.len() - 1)]
+    }
+
+    /// Adds a profile with an unused id and returns its index
+    pub fn create(&mut self, name: String) -> usize {
+        let id = (1..)
+            .map(|number| format!("profile_{}", number))
+            .find(|id| self.slots.iter().all(|slot| slot.id != *id))
+            .unwrap();
+
+        self.slots.push(ProfileSlot { id, name });
+        self.slots.len() - 1
+    }
+
+    /// Removes the profile along with its files, the last profile is never removed and the profile
+    /// is kept when its files could not be removed, returns whether another profile is selected now
+    pub fn delete(&mut self, index: usize) -> io::Result<bool> {
+        if self.slots.len() <= 1 || index >= self.slots.len() {
+            return Ok(false);
+        }
+
+        if let Err(error) = fs::remove_dir_all(self.slots[index].dir()) {
+            if error.kind() != io::ErrorKind::NotFound {
+                return Err(error);
+            }
+        }
+
+        self.slots.remove(index);
+
+        let changed = self.selected == index;
+
+        if self.selected >= index && self.selected > 0 {
+            self.selected -= 1;
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Loads the profile index, the first profile takes over the files kept before there were profiles
+pub fn load_profile_index() -> Persistent<ProfileIndex> {
+    let mut index = Persistent::<ProfileIndex>
+        ::builder()
+        .name("Profiles")
+        .format(StorageFormat::Toml)
+        .path(config_dir().join("profiles.toml"))
+        .default(ProfileIndex::default())
+        .build()
+        .expect("failed to initialize profiles");
+
+    if index.slots.is_empty() {
+        index.create(DEFAULT_PROFILE_NAME.to_string());
+
+        let dir = index.selected().dir();
+        fs::create_dir_all(&dir).expect("failed to create profile directory");
+
+        for file in ["profile.toml", "scoreboard.toml"] {
+            let legacy = config_dir().join(file);
+
+            if legacy.exists() {
+                fs::copy(&legacy, dir.join(file)).expect("failed to move old profile");
+            }
+        }
+
+        index.persist().expect("failed to update profiles");
+    }
+
+    index
+}
+
+/// Directory of the profile that was played last, for tools started without the game
+pub fn selected_profile_dir() -> PathBuf {
+    load_profile_index().selected().dir()
+}
+
+/// Swaps the resources of the current profile for the ones of the selected profile
+pub fn switch_profile(commands: &mut Commands, index: &ProfileIndex) {
+    let dir = index.selected().dir();
+
+    commands.insert_resource(load_profile_config(&dir));
+    commands.insert_resource(load_scoreboard(&dir));
+    commands.insert_resource(load_profile(&dir));
 }
 
 pub struct SettingsPlugin;
 
 impl Plugin for SettingsPlugin {
     fn build(&self, app: &mut App) {
-        let config_dir = dirs::config_dir().unwrap().join("sandforge");
-
-        app.insert_resource(
-            Persistent::<Config>
-                ::builder()
-                .name("Main config")
-                .format(StorageFormat::Toml)
-                .path(config_dir.join("config.toml"))
-                .default(Config {
-                    vsync: PresentMode::AutoVsync,
-                    mode: WindowMode::Windowed,
-                    resolution: [1280, 720],
-                    volume: default_volume(),
-                    spatial: false,
-                })
-                .build()
-                .expect("failed to initialize config")
-        )
-            .insert_resource(
-                Persistent::<Scoreboard>
-                    ::builder()
-                    .name("Scoreboard")
-                    .format(StorageFormat::Toml)
-                    .path(config_dir.join("scoreboard.toml"))
-                    .default(Scoreboard {
-                        scores: vec![],
-                    })
-                    .build()
-                    .expect("failed to initialize scores")
-            );
+        let index = load_profile_index();
+        let dir = index.selected().dir();
+
+        app.insert_resource(load_profile_config(&dir))
+            .insert_resource(load_scoreboard(&dir))
+            .insert_resource(load_profile(&dir))
+            .insert_resource(index)
+            .add_systems(PostUpdate, apply_bus_volume.before(TransformSystem::TransformPropagate));
     }
 }
 
-pub fn process_config(
-    mut audio_sink_q: Query<&mut AudioSink>,
-    mut global_volume: ResMut<GlobalVolume>,
-    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
-    config: Res<Persistent<Config>>,
+/// Category every played sound belongs to, each one has its own volume slider
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+    Ambience,
+    Ui,
+}
+
+/// Buses shown in the settings with the key of their name in the language files
+pub const AUDIO_BUSES: [(AudioBus, &str); 4] = [
+    (AudioBus::Music, "settings.music"),
+    (AudioBus::Sfx, "settings.effects"),
+    (AudioBus::Ambience, "settings.ambience"),
+    (AudioBus::Ui, "settings.interface"),
+];
+
+/// Volume the sound was spawned with, before the volume of its bus was applied
+#[derive(Component)]
+pub struct BaseVolume(pub f32);
+
+impl Config {
+    pub fn master_volume(&self) -> f32 {
+        ((self.volume as f32) / 100.0).clamp(0.0, 1.0)
+    }
+
+    pub fn bus_volume(&self, bus: AudioBus) -> i32 {
+        match bus {
+            AudioBus::Music => self.music_volume,
+            AudioBus::Sfx => self.sfx_volume,
+            AudioBus::Ambience => self.ambience_volume,
+            AudioBus::Ui => self.ui_volume,
+        }
+    }
+
+    pub fn set_bus_volume(&mut self, bus: AudioBus, value: i32) {
+        let volume = match bus {
+            AudioBus::Music => &mut self.music_volume,
+            AudioBus::Sfx => &mut self.sfx_volume,
+            AudioBus::Ambience => &mut self.ambience_volume,
+            AudioBus::Ui => &mut self.ui_volume,
+        };
+
+        *volume = value.clamp(0, 100);
+    }
+
+    /// Share of the master volume the bus is played at
+    pub fn bus_scale(&self, bus: AudioBus) -> f32 {
+        ((self.bus_volume(bus) as f32) / 100.0).clamp(0.0, 1.0)
+    }
+
+    /// Final volume of a sound on the bus, for systems that set the volume of a sink directly
+    pub fn volume_of(&self, bus: AudioBus, volume: f32) -> f32 {
+        volume * self.master_volume() * self.bus_scale(bus)
+    }
+}
+
+/// Scales sounds right before they start playing, the master volume is applied by bevy on top
+fn apply_bus_volume(
+    mut commands: Commands,
+    mut audio_q: Query<(Entity, &AudioBus, &mut PlaybackSettings), Added<PlaybackSettings>>,
+    config: Res<Persistent<Config>>
 ) {
-    let mut window = window_q.single_mut();
+    for (entity, bus, mut settings) in audio_q.iter_mut() {
+        let volume = settings.volume.get();
 
-    window.resolution.set(config.resolution[0] as f32, config.resolution[1] as f32);
-    window.resolution.set_scale_factor_override(Some(config.resolution[0] as f32 / 1280.0));
-    window.mode = config.mode.clone();
-    window.present_mode = config.vsync.clone();
+        commands.entity(entity).insert(BaseVolume(volume));
+        settings.volume = Volume::new(volume * config.bus_scale(*bus));
+    }
+}
+
+/// Applies the volume settings to the sounds that are already playing
+pub fn update_audio_volumes(
+    config: &Config,
+    global_volume: &mut GlobalVolume,
+    audio_sink_q: &Query<(&AudioSink, Option<&AudioBus>, Option<&BaseVolume>)>
+) {
+    global_volume.volume = Volume::new(config.master_volume());
 
-    let volume = ((config.volume as f32) / 100.0).clamp(0.0, 100.0);
-    global_volume.volume = Volume::new(volume);
+    for (audio_sink, bus, base) in audio_sink_q.iter() {
+        let base = base.map_or(1.0, |base| base.0);
 
-    for audio_sink in audio_sink_q.iter_mut() {
-        audio_sink.set_volume(volume);
+        audio_sink.set_volume(match bus {
+            Some(bus) => config.volume_of(*bus, base),
+            None => base * config.master_volume(),
+        });
     }
+}
+
+pub fn apply_window_config(config: &Config, window: &mut Window) {
+    window.resolution.set(config.resolution[0] as f32, config.resolution[1] as f32);
+    window.resolution.set_scale_factor_override(Some((config.resolution[0] as f32) / 1280.0));
+    window.mode = config.mode;
+    window.present_mode = config.vsync;
+}
+
+pub fn process_config(
+    audio_sink_q: Query<(&AudioSink, Option<&AudioBus>, Option<&BaseVolume>)>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    config: Res<Persistent<Config>>,
+) {
+    apply_window_config(&config, &mut window_q.single_mut());
+    update_audio_volumes(&config, &mut global_volume, &audio_sink_q);
 }
\ No newline at end of file
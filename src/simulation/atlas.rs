@@ -0,0 +1,318 @@
+use std::sync::{ Arc, Mutex };
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::{ RenderAssetUsages, RenderAssets },
+        render_resource::{
+            BufferInitDescriptor,
+            BufferUsages,
+            CommandEncoderDescriptor,
+            Extent3d,
+            ImageCopyBuffer,
+            ImageCopyTexture,
+            ImageDataLayout,
+            Origin3d,
+            TextureAspect,
+            TextureDimension,
+            TextureFormat,
+        },
+        renderer::{ RenderDevice, RenderQueue },
+        texture::BevyDefault,
+        Render,
+        RenderApp,
+        RenderSet,
+    },
+};
+use bevy_math::{ URect, UVec2 };
+
+use crate::constants::CHUNK_SIZE;
+
+// chunks along each side of a page, so a page holds 256 chunks
+const PAGE_CHUNKS: u32 = 16;
+const PAGE_SIZE: u32 = PAGE_CHUNKS * (CHUNK_SIZE as u32);
+
+pub struct ChunkAtlasPlugin;
+
+impl Plugin for ChunkAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        let uploads = AtlasUploads::default();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(uploads.clone())
+                .add_systems(Render, write_atlas_uploads.in_set(RenderSet::PrepareResources));
+        }
+
+        app.insert_resource(uploads)
+            .init_resource::<ChunkAtlas>()
+            .add_systems(Last, flush_atlas_uploads);
+    }
+}
+
+/// Place of a chunk in the atlas, `offset` is its bottom left corner on the page in pixels
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSlot {
+    pub page: usize,
+    pub offset: UVec2,
+}
+
+impl AtlasSlot {
+    /// Area of the page the chunk sprites draw
+    pub fn rect(&self) -> Rect {
+        Rect::from_corners(self.offset.as_vec2(), (self.offset + (CHUNK_SIZE as u32)).as_vec2())
+    }
+}
+
+pub struct AtlasPage {
+    pub terrain: Handle<Image>,
+    pub lighting: Handle<Image>,
+    /// Depth and water style of liquid pixels, read by the water shader
+    pub liquid: Handle<Image>,
+    /// Heat and smoke rising from hot pixels, read by the heat haze shader
+    pub heat: Handle<Image>,
+}
+
+/// Shared textures of every loaded chunk, every layer of a chunk is kept on the
+/// pages at the same slot, pixels are never touched in the main world and only receive
+/// the sub rects queued with [`ChunkAtlas::write`]
+#[derive(Resource, Default)]
+pub struct ChunkAtlas {
+    pages: Vec<AtlasPage>,
+    free: Vec<AtlasSlot>,
+    batch: AtlasBatch,
+}
+
+impl ChunkAtlas {
+    pub fn page(&self, index: usize) -> &AtlasPage {
+        &self.pages[index]
+    }
+
+    /// Takes a free slot, adding a page once all of them are used,
+    /// leftovers of the previous chunk in the slot are cleared
+    pub fn allocate(&mut self, images: &mut Assets<Image>) -> AtlasSlot {
+        if self.free.is_empty() {
+            self.add_page(images);
+        }
+
+        let slot = self.free.pop().unwrap();
+        let rect = URect::from_corners(UVec2::ZERO, UVec2::splat(CHUNK_SIZE as u32));
+        let empty = vec![0; (CHUNK_SIZE.pow(2) * 4) as usize];
+
+        self.write(slot, AtlasLayer::Terrain, rect, &empty);
+        self.write(slot, AtlasLayer::Lighting, rect, &empty);
+        self.write(slot, AtlasLayer::Liquid, rect, &empty);
+        self.write(slot, AtlasLayer::Heat, rect, &empty);
+
+        slot
+    }
+
+    /// Frees every slot, pages are kept for the next level
+    pub fn clear(&mut self) {
+        self.free = (0..self.pages.len()).flat_map(page_slots).collect();
+        self.batch = AtlasBatch::default();
+    }
+
+    /// Queues the rect of a chunk texture with `source` holding the whole chunk,
+    /// only the rows of the rect are copied so the upload stays tightly packed
+    pub fn write(&mut self, slot: AtlasSlot, layer: AtlasLayer, rect: URect, source: &[u8]) {
+        let page = &self.pages[slot.page];
+        let texture = match layer {
+            AtlasLayer::Terrain => page.terrain.id(),
+            AtlasLayer::Lighting => page.lighting.id(),
+            AtlasLayer::Liquid => page.liquid.id(),
+            AtlasLayer::Heat => page.heat.id(),
+        };
+
+        let offset = self.batch.data.len();
+
+        for y in rect.min.y..rect.max.y {
+            let row = (y * (CHUNK_SIZE as u32)) as usize;
+            self.batch.data.extend_from_slice(
+                &source[(row + rect.min.x as usize) * 4..(row + rect.max.x as usize) * 4]
+            );
+        }
+
+        self.batch.writes.push(AtlasWrite {
+            texture,
+            origin: slot.offset + rect.min,
+            size: rect.size(),
+            offset,
+        });
+    }
+
+    fn add_page(&mut self, images: &mut Assets<Image>) {
+        let mut new_image = || {
+            images.add(
+                Image::new(
+                    Extent3d {
+                        width: PAGE_SIZE,
+                        height: PAGE_SIZE,
+                        ..Default::default()
+                    },
+                    TextureDimension::D2,
+                    vec![0; (PAGE_SIZE.pow(2) * 4) as usize],
+                    TextureFormat::bevy_default(),
+                    RenderAssetUsages::RENDER_WORLD
+                )
+            )
+        };
+
+        self.pages.push(AtlasPage {
+            terrain: new_image(),
+            lighting: new_image(),
+            liquid: new_image(),
+            heat: new_image(),
+        });
+
+        // popped from the back, so the first slot of the page is handed out first
+        self.free.extend(page_slots(self.pages.len() - 1).rev());
+    }
+}
+
+fn page_slots(page: usize) -> impl DoubleEndedIterator<Item = AtlasSlot> {
+    (0..PAGE_CHUNKS.pow(2)).map(move |index| AtlasSlot {
+        page,
+        offset: UVec2::new(index % PAGE_CHUNKS, index / PAGE_CHUNKS) * (CHUNK_SIZE as u32),
+    })
+}
+
+#[derive(Clone, Copy)]
+pub enum AtlasLayer {
+    Terrain,
+    Lighting,
+    Liquid,
+    Heat,
+}
+
+struct AtlasWrite {
+    texture: AssetId<Image>,
+    origin: UVec2,
+    size: UVec2,
+    offset: usize,
+}
+
+impl AtlasWrite {
+    fn len(&self) -> usize {
+        (self.size.x * self.size.y * 4) as usize
+    }
+}
+
+/// Sub rects with their pixels packed one after another into a single buffer
+#[derive(Default)]
+struct AtlasBatch {
+    writes: Vec<AtlasWrite>,
+    data: Vec<u8>,
+}
+
+impl AtlasBatch {
+    fn append(&mut self, mut other: AtlasBatch) {
+        let offset = self.data.len();
+
+        self.writes.extend(
+            other.writes.drain(..).map(|write| AtlasWrite { offset: write.offset + offset, ..write })
+        );
+        self.data.append(&mut other.data);
+    }
+}
+
+/// Batches waiting for the render world, shared between both worlds
+#[derive(Resource, Clone, Default)]
+struct AtlasUploads(Arc<Mutex<AtlasBatch>>);
+
+/// Hands the writes of the frame over to the render world at once
+fn flush_atlas_uploads(mut atlas: ResMut<ChunkAtlas>, uploads: Res<AtlasUploads>) {
+    if atlas.batch.writes.is_empty() {
+        return;
+    }
+
+    let batch = std::mem::take(&mut atlas.batch);
+    uploads.0.lock().unwrap().append(batch);
+}
+
+/// Copies every pending rect into the pages from one staging buffer, rows are padded
+/// there to the alignment buffer copies need
+fn write_atlas_uploads(
+    uploads: Res<AtlasUploads>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>
+) {
+    let mut uploads = uploads.0.lock().unwrap();
+
+    if uploads.writes.is_empty() {
+        return;
+    }
+
+    let AtlasBatch { writes, data } = std::mem::take(&mut *uploads);
+
+    let mut staging = vec![];
+    let mut copies = vec![];
+
+    for write in writes {
+        let bytes = &data[write.offset..write.offset + write.len()];
+
+        let Some(image) = gpu_images.get(write.texture) else {
+            // page was added this frame and is not prepared yet
+            let offset = uploads.data.len();
+            uploads.data.extend_from_slice(bytes);
+            uploads.writes.push(AtlasWrite { offset, ..write });
+            continue;
+        };
+
+        let row_size = (write.size.x * 4) as usize;
+        let padded_row_size = RenderDevice::align_copy_bytes_per_row(row_size);
+        let offset = staging.len();
+
+        for row in bytes.chunks_exact(row_size) {
+            staging.extend_from_slice(row);
+            staging.resize(staging.len() + padded_row_size - row_size, 0);
+        }
+
+        copies.push((&image.texture, write, offset, padded_row_size));
+    }
+
+    if copies.is_empty() {
+        return;
+    }
+
+    let buffer = render_device.create_buffer_with_data(
+        &(BufferInitDescriptor {
+            label: Some("chunk_atlas_staging_buffer"),
+            contents: &staging,
+            usage: BufferUsages::COPY_SRC,
+        })
+    );
+
+    let mut encoder = render_device.create_command_encoder(
+        &(CommandEncoderDescriptor {
+            label: Some("chunk_atlas_upload_encoder"),
+        })
+    );
+
+    for (texture, write, offset, padded_row_size) in copies {
+        encoder.copy_buffer_to_texture(
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: offset as u64,
+                    bytes_per_row: Some(padded_row_size as u32),
+                    rows_per_image: None,
+                },
+            },
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d { x: write.origin.x, y: write.origin.y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: write.size.x,
+                height: write.size.y,
+                depth_or_array_layers: 1,
+            }
+        );
+    }
+
+    render_queue.submit([encoder.finish()]);
+}
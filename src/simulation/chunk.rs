@@ -14,11 +14,22 @@ use bevy_math::{ ivec2, IVec2, URect, UVec2, Vec2 };
 use bevy_rapier2d::prelude::*;
 use crate::constants::{ CHUNK_CELLS, CHUNK_SIZE };
 
+// liquid deeper than this in pixels is drawn with the whole deep color of its water style,
+// the water shader decodes depth with the same value
+const MAX_LIQUID_DEPTH: u32 = 32;
+// heat of burning and hot pixels, it drops by the decay for every pixel of air it rises through
+const FIRE_HEAT: u8 = 255;
+const HOT_HEAT: u8 = 192;
+const HEAT_DECAY: u8 = 8;
+// smoke only comes from fire and rises higher than the heat
+const SMOKE_DECAY: u8 = 4;
+
 use super::{
+    atlas::{ AtlasLayer, AtlasSlot, ChunkAtlas },
     chunk_groups::ChunkGroup,
     dirty_rect::{ RenderMessage, UpdateMessage },
     materials::PhysicsType,
-    colliders::douglas_peucker,
+    colliders::simplify_ring,
     pixel::{ Pixel, WALL },
 };
 
@@ -52,12 +63,17 @@ pub enum ChunkState {
     Sleeping,
 }
 
+/// Terrain, lighting, liquids and heat are kept as pixels on the CPU and drawn from the slot of the chunk
+/// in the [`ChunkAtlas`], the background is rarely changed and has its own image
 #[derive(Clone)]
 pub struct ChunkData {
     pub pixels: Vec<Pixel>,
-    pub texture: Handle<Image>,
+    pub terrain: Vec<u8>,
+    pub lighting: Vec<u8>,
+    pub liquid: Vec<u8>,
+    pub heat: Vec<u8>,
     pub background: Handle<Image>,
-    pub lighting: Handle<Image>,
+    pub slot: Option<AtlasSlot>,
     pub state: ChunkState,
 }
 
@@ -65,9 +81,12 @@ impl Default for ChunkData {
     fn default() -> Self {
         Self {
             pixels: vec![Pixel::default(); CHUNK_CELLS as usize],
-            texture: Handle::default(),
+            terrain: vec![0; (CHUNK_CELLS * 4) as usize],
+            lighting: vec![0; (CHUNK_CELLS * 4) as usize],
+            liquid: vec![0; (CHUNK_CELLS * 4) as usize],
+            heat: vec![0; (CHUNK_CELLS * 4) as usize],
             background: Handle::default(),
-            lighting: Handle::default(),
+            slot: None,
             state: ChunkState::Initialized,
         }
     }
@@ -88,15 +107,18 @@ impl ChunkData {
         )
     }
 
-    pub fn update_textures(&self, images: &mut Assets<Image>, lighting: [f32; 3]) {
+    pub fn update_textures(&mut self, images: &Assets<Image>, atlas: &mut ChunkAtlas, lighting: [f32; 3]) {
         self.update_textures_part(
             images,
+            atlas,
             lighting,
             URect::from_corners(UVec2::ZERO, UVec2::splat(CHUNK_SIZE as u32))
         );
     }
 
-    pub fn build_colliders(&self) -> Result<Vec<Collider>, String> {
+    /// Outlines every static region of the chunk, including the caves inside of them,
+    /// with a single polyline, `tolerance` is the simplification distance in pixels
+    pub fn build_colliders(&self, tolerance: f32) -> Result<Collider, String> {
         let values = self.pixels
             .iter()
             .map(|pixel| {
@@ -110,66 +132,56 @@ impl ChunkData {
             false
         );
 
-        contour_generator
+        let contours = contour_generator
             .contours(&values, &[1.0])
-            .map(|contours| {
-                contours[0]
-                    .geometry()
-                    .0.iter()
-                    .map(|polygon| {
-                        let points = polygon
-                            .interiors()
-                            .iter()
-                            .chain(std::iter::once(polygon.exterior()))
-                            .map(|line| {
-                                line.0
-                                    .iter()
-                                    .map(
-                                        |point|
-                                            Vec2::new(
-                                                (point.x as f32) + 0.5,
-                                                (point.y as f32) + 0.5
-                                            ) / (CHUNK_SIZE as f32)
-                                    )
-                                    .collect::<Vec<Vec2>>()
-                            })
-                            .map(|line| {
-                                douglas_peucker(&line, 0.25 / (CHUNK_SIZE.pow(2) as f32))
-                            })
-                            .filter(|points| points.len() > 2)
-                            .collect::<Vec<Vec<Vec2>>>();
-
-                        points
-                    })
-                    .filter(|polygon| !polygon.is_empty())
-                    .flat_map(|boundaries| {
-                        boundaries
-                            .iter()
-                            .map(|boundary| {
-                                let vertices = boundary
-                                    .iter()
-                                    .map(|point| Vec2::new(point[0], point[1]))
-                                    .collect();
-
-                                Collider::polyline(vertices, None)
-                            })
-                            .collect::<Vec<Collider>>()
+            .map_err(|_| "no contours were found".to_string())?;
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+
+        for polygon in contours[0].geometry().0.iter() {
+            for line in std::iter::once(polygon.exterior()).chain(polygon.interiors().iter()) {
+                let ring = line.0
+                    .iter()
+                    .map(|point| {
+                        Vec2::new((point.x as f32) + 0.5, (point.y as f32) + 0.5) /
+                            (CHUNK_SIZE as f32)
                     })
-                    .collect::<Vec<Collider>>()
-            })
-            .map_err(|_| "no contours were found".to_string())
+                    .collect::<Vec<Vec2>>();
+
+                let ring = simplify_ring(&ring, tolerance / (CHUNK_SIZE as f32));
+
+                if ring.len() < 3 {
+                    continue;
+                }
+
+                let offset = vertices.len() as u32;
+                let count = ring.len() as u32;
+
+                vertices.extend(ring);
+                indices.extend((0..count).map(|index| [offset + index, offset + ((index + 1) % count)]));
+            }
+        }
+
+        if vertices.is_empty() {
+            return Err("no contours were found".to_string());
+        }
+
+        Ok(Collider::polyline(vertices, Some(indices)))
     }
 
-    pub fn update_textures_part(&self, images: &mut Assets<Image>, lighting_color: [f32; 3], rect: URect) {
-        let Some(mut terrain) = images.remove(self.texture.clone()) else {
-            panic!();
-        };
-        let Some(mut lighting) = images.remove(self.lighting.clone()) else {
-            panic!();
-        };
-        let Some(background) = images.remove(self.background.clone()) else {
+    /// Redraws the rect of the terrain, lighting, liquids and heat, then queues it for the atlas
+    pub fn update_textures_part(
+        &mut self,
+        images: &Assets<Image>,
+        atlas: &mut ChunkAtlas,
+        lighting_color: [f32; 3],
+        rect: URect
+    ) {
+        let Some(background) = images.get(&self.background) else {
             panic!();
         };
+        let (terrain, lighting) = (&mut self.terrain, &mut self.lighting);
 
         let fire_colors: [[u8; 4]; 5] = [
             [0xa9, 0x43, 0x1e, 0xff],
@@ -187,15 +199,15 @@ impl ChunkData {
                 let texture_range = index * 4..(index + 1) * 4;
 
                 if pixel.on_fire {
-                    terrain.data[texture_range.clone()].copy_from_slice(
+                    terrain[texture_range.clone()].copy_from_slice(
                         &fire_colors[fastrand::i32(0..fire_colors.len() as i32) as usize]
                     );
                 } else {
                     let color = pixel.get_color();
-                    terrain.data[texture_range.clone()].copy_from_slice(&color);
+                    terrain[texture_range.clone()].copy_from_slice(&color);
                 }
 
-                let terrain_opactiy = terrain.data[index * 4 + 3];
+                let terrain_opactiy = terrain[index * 4 + 3];
                 let background_opactiy = background.data[index * 4 + 3];
 
                 let lighting_value = if terrain_opactiy == 255 {
@@ -206,7 +218,7 @@ impl ChunkData {
                 };
 
                 if let Some(color) = pixel.material.lighting {
-                    lighting.data[texture_range.clone()].copy_from_slice(
+                    lighting[texture_range.clone()].copy_from_slice(
                         &[
                             u8::max(color[0], (lighting_color[0] * 255.0 * lighting_value) as u8),
                             u8::max(color[1], (lighting_color[1] * 255.0 * lighting_value) as u8),
@@ -216,7 +228,7 @@ impl ChunkData {
                     );
                 } else if pixel.on_fire {
                     let color = fire_colors[fastrand::i32(0..fire_colors.len() as i32) as usize];
-                    lighting.data[texture_range.clone()].copy_from_slice(
+                    lighting[texture_range.clone()].copy_from_slice(
                         &[
                             u8::max(color[0], (lighting_color[0] * 255.0 * lighting_value) as u8),
                             u8::max(color[1], (lighting_color[1] * 255.0 * lighting_value) as u8),
@@ -225,7 +237,7 @@ impl ChunkData {
                         ]
                     );
                 } else {
-                    lighting.data[texture_range.clone()].copy_from_slice(
+                    lighting[texture_range.clone()].copy_from_slice(
                         &[
                             (lighting_color[0] * 255.0 * lighting_value) as u8,
                             (lighting_color[1] * 255.0 * lighting_value) as u8,
@@ -237,9 +249,89 @@ impl ChunkData {
             }
         }
 
-        images.insert(self.texture.clone(), terrain);
-        images.insert(self.lighting.clone(), lighting);
-        images.insert(self.background.clone(), background);
+        // depth of a liquid pixel depends on every pixel above it,
+        // so the columns of the rect are redrawn down to the bottom
+        let liquid_rect = URect::new(rect.min.x, 0, rect.max.x, rect.max.y);
+        let liquid = &mut self.liquid;
+
+        for x in liquid_rect.min.x..liquid_rect.max.x {
+            // chunk above is not known here, liquid reaching the top is taken as deep
+            let mut depth = MAX_LIQUID_DEPTH;
+
+            for y in (0..CHUNK_SIZE as u32).rev() {
+                let index = (y * (CHUNK_SIZE as u32) + x) as usize;
+                let pixel = &self.pixels[index];
+
+                let style = match pixel.physics_type {
+                    PhysicsType::Liquid(..) => pixel.material.water.as_ref(),
+                    _ => None,
+                };
+
+                let value = match style {
+                    Some(style) => {
+                        let value = [
+                            ((depth.min(MAX_LIQUID_DEPTH) * 255) / MAX_LIQUID_DEPTH) as u8,
+                            style.index * 16,
+                            0,
+                            255,
+                        ];
+                        depth += 1;
+                        value
+                    }
+                    None => {
+                        depth = 0;
+                        [0; 4]
+                    }
+                };
+
+                if y < liquid_rect.max.y {
+                    liquid[index * 4..(index + 1) * 4].copy_from_slice(&value);
+                }
+            }
+        }
+
+        // heat rises from the pixels below, so the columns of the rect are redrawn up to the top
+        let heat_rect = URect::new(rect.min.x, rect.min.y, rect.max.x, CHUNK_SIZE as u32);
+        let heat_map = &mut self.heat;
+
+        for x in heat_rect.min.x..heat_rect.max.x {
+            // chunk below is not known here, heat starts cold at the bottom
+            let (mut heat, mut smoke) = (0u8, 0u8);
+
+            for y in 0..CHUNK_SIZE as u32 {
+                let index = (y * (CHUNK_SIZE as u32) + x) as usize;
+                let pixel = &self.pixels[index];
+
+                let passable = matches!(pixel.physics_type, PhysicsType::Air | PhysicsType::Gas(..));
+
+                if pixel.on_fire {
+                    heat = FIRE_HEAT;
+                    smoke = FIRE_HEAT;
+                } else if pixel.material.tags.contains("hot") {
+                    heat = heat.max(HOT_HEAT);
+                } else if passable {
+                    heat = heat.saturating_sub(HEAT_DECAY);
+                    smoke = smoke.saturating_sub(SMOKE_DECAY);
+                } else {
+                    heat = 0;
+                    smoke = 0;
+                }
+
+                // only the air above hot pixels shimmers
+                let value = if passable && (heat > 0 || smoke > 0) { [heat, smoke, 0, 255] } else { [0; 4] };
+
+                if y >= heat_rect.min.y {
+                    heat_map[index * 4..(index + 1) * 4].copy_from_slice(&value);
+                }
+            }
+        }
+
+        if let Some(slot) = self.slot {
+            atlas.write(slot, AtlasLayer::Terrain, rect, &self.terrain);
+            atlas.write(slot, AtlasLayer::Lighting, rect, &self.lighting);
+            atlas.write(slot, AtlasLayer::Liquid, liquid_rect, &self.liquid);
+            atlas.write(slot, AtlasLayer::Heat, heat_rect, &self.heat);
+        }
     }
 }
 
@@ -1,3 +1,5 @@
+use std::time::{ Duration, Instant };
+
 use bevy::{ prelude::*, tasks::ComputeTaskPool, utils::HashMap };
 use bevy_math::{ ivec2, IVec2, Rect, UVec2, Vec3Swizzles };
 use itertools::{ Either, Itertools };
@@ -6,6 +8,8 @@ use crate::{
     camera::TrackingCamera,
     constants::CHUNK_SIZE,
     generation::chunk::GenerationEvent,
+    inspector::ChunkInspector,
+    profiler::{ Stage, StageTimings },
     registries::Registries,
 };
 
@@ -21,11 +25,14 @@ use super::{
         UpdateMessage,
     },
     materials::{
+        update_emitter,
         update_fire,
         update_gas,
+        update_growth,
         update_liquid,
         update_powder,
         update_reactions,
+        update_sink,
         Material,
         PhysicsType,
     },
@@ -185,8 +192,18 @@ pub fn chunks_update(
     mut chunk_manager: ResMut<ChunkManager>,
     mut dirty_rects_resource: ResMut<DirtyRects>,
     mut collider_ev: EventWriter<ChunkColliderEvent>,
+    mut timings: ResMut<StageTimings>,
+    mut inspector: ResMut<ChunkInspector>,
     registries: Res<Registries>
 ) {
+    let _span = info_span!("chunk_update").entered();
+    let start = Instant::now();
+    timings.update_rects = dirty_rects_resource.current.len();
+
+    let selected = inspector.selected;
+    let selected_rect = selected.and_then(|position| dirty_rects_resource.current.get(&position).copied());
+    let mut selected_duration: Option<Duration> = None;
+
     let DirtyRects {
         current: dirty_rects,
         new: new_dirty_rects,
@@ -259,7 +276,7 @@ pub fn chunks_update(
             });
 
         for group in groups_by_x {
-            ComputeTaskPool::get().scope(|scope| {
+            let durations = ComputeTaskPool::get().scope(|scope| {
                 group
                     .into_iter()
                     .filter_map(|position| {
@@ -277,6 +294,9 @@ pub fn chunks_update(
                     })
                     .for_each(|(position, dirty_rect, mut chunk_group)| {
                         scope.spawn(async move {
+                            let _span = info_span!("update_chunk").entered();
+                            let start = Instant::now();
+
                             let api = ChunkApi {
                                 cell_position: ivec2(0, 0),
                                 chunk_position: position,
@@ -288,9 +308,14 @@ pub fn chunks_update(
                             };
 
                             update_chunk(api, dirty_rect, materials);
+
+                            // only the chunk under the inspector is of interest
+                            (Some(position) == selected).then(|| start.elapsed())
                         })
                     })
             });
+
+            selected_duration = selected_duration.or(durations.into_iter().flatten().next());
         }
 
         update_send.close();
@@ -318,6 +343,9 @@ pub fn chunks_update(
     dirty_rects_resource.current.clear();
     dirty_rects_resource.collider.clear();
     dirty_rects_resource.swap();
+
+    inspector.record_pass(selected_rect, selected_duration);
+    timings.record(Stage::ChunkUpdate, start);
 }
 
 fn update_chunk(mut api: ChunkApi, dirty_rect: URect, materials: &HashMap<String, Material>) {
@@ -353,7 +381,13 @@ fn update_chunk(mut api: ChunkApi, dirty_rect: URect, materials: &HashMap<String
                 continue;
             }
 
+            if update_growth(&mut api, materials) {
+                continue;
+            }
+
             update_reactions(&mut api, materials);
+            update_emitter(&mut api, materials);
+            update_sink(&mut api);
 
             api.mark_updated();
         }
@@ -1,7 +1,12 @@
+use std::time::Instant;
+
 use bevy::{prelude::*, utils::HashSet};
 use bevy_math::{ IVec2, Vec2 };
+use bevy_persistent::Persistent;
 use bevy_rapier2d::geometry::{ Collider, CollisionGroups, Group };
 
+use crate::{ profiler::{ Stage, StageTimings }, settings::Config };
+
 use super::{ chunk::Chunk, chunk_manager::ChunkManager };
 
 pub const TERRAIN_MASK: u32 = 1 << 0;
@@ -11,48 +16,72 @@ pub const ENEMY_MASK: u32 = 1 << 4;
 pub const HITBOX_MASK: u32 = 1 << 5;
 pub const OBJECT_MASK: u32 = 1 << 6;
 
-pub fn douglas_peucker(vertices: &[Vec2], epsilon: f32) -> Vec<Vec2> {
-    let mut d_squared_max = 0.0;
-    let mut farthest_point_index = 0;
-    let end = vertices.len() - 1;
-    if end < 3 {
+/// Simplifies an open line, so no removed vertex is further than `tolerance` from the result,
+/// both endpoints are always kept
+pub fn douglas_peucker(vertices: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if vertices.len() < 3 {
         return vertices.to_vec();
     }
-    let line = (vertices[0], vertices[end - 1]);
-    for (i, _) in vertices
+
+    let first = vertices[0];
+    let last = vertices[vertices.len() - 1];
+
+    let (farthest_index, distance) = vertices[1..vertices.len() - 1]
         .iter()
         .enumerate()
-        .take(end - 1)
-        .skip(1) {
-        let d_squared = perpendicular_squared_distance(vertices[i], line);
-        if d_squared > d_squared_max {
-            farthest_point_index = i;
-            d_squared_max = d_squared;
-        }
+        .map(|(index, vertex)| (index + 1, segment_distance(*vertex, (first, last))))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+
+    if distance > tolerance {
+        let mut simplified = douglas_peucker(&vertices[..=farthest_index], tolerance);
+        simplified.pop();
+        simplified.extend(douglas_peucker(&vertices[farthest_index..], tolerance));
+        simplified
+    } else {
+        vec![first, last]
     }
+}
 
-    if d_squared_max > epsilon {
-        let rec_results1 = douglas_peucker(&vertices[0..farthest_point_index], epsilon);
-        let rec_results2 = douglas_peucker(&vertices[farthest_point_index..end + 1], epsilon);
+/// Simplifies a closed ring, the result does not repeat the first vertex at the end
+pub fn simplify_ring(ring: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    let ring = match ring {
+        [first, .., last] if first == last => &ring[..ring.len() - 1],
+        _ => ring,
+    };
 
-        [rec_results1, rec_results2[1..rec_results2.len()].to_vec()].concat()
-    } else {
-        vec![vertices[0], vertices[end]]
+    if ring.len() < 4 {
+        return ring.to_vec();
     }
+
+    // ring is cut in two open lines at the vertex farthest from the first one
+    let split = (1..ring.len())
+        .max_by(|a, b| {
+            ring[*a].distance_squared(ring[0]).total_cmp(&ring[*b].distance_squared(ring[0]))
+        })
+        .unwrap();
+
+    let mut simplified = douglas_peucker(&ring[..=split], tolerance);
+    simplified.pop();
+
+    let second_half = [&ring[split..], &ring[..1]].concat();
+    simplified.extend(douglas_peucker(&second_half, tolerance));
+    simplified.pop();
+
+    simplified
 }
 
-fn perpendicular_squared_distance(point: Vec2, line: (Vec2, Vec2)) -> f32 {
-    let x_diff = line.1.x - line.0.x;
-    let y_diff = line.1.y - line.0.y;
-    let numerator = (
-        y_diff * point.x -
-        x_diff * point.y +
-        line.1.x * line.0.y -
-        line.1.y * line.0.x
-    ).abs();
-    let numerator_squared = numerator * numerator;
-    let denominator_squared = y_diff * y_diff + x_diff * x_diff;
-    numerator_squared / denominator_squared
+fn segment_distance(point: Vec2, segment: (Vec2, Vec2)) -> f32 {
+    let direction = segment.1 - segment.0;
+    let length_squared = direction.length_squared();
+
+    if length_squared == 0.0 {
+        return point.distance(segment.0);
+    }
+
+    let t = ((point - segment.0).dot(direction) / length_squared).clamp(0.0, 1.0);
+
+    point.distance(segment.0 + direction * t)
 }
 
 #[derive(Event, Deref, DerefMut)]
@@ -61,12 +90,17 @@ pub struct ChunkColliderEvent(pub IVec2);
 pub fn process_chunk_collider_events(
     mut commands: Commands,
     chunk_manager: Res<ChunkManager>,
+    config: Res<Persistent<Config>>,
     mut chunk_ev: EventReader<ChunkColliderEvent>,
+    mut timings: ResMut<StageTimings>,
     mut chunk_set: ParamSet<
         (Query<&Children, With<Chunk>>, Query<Entity, (With<Parent>, With<Collider>)>)
     >
 ) {
+    let _span = info_span!("collider_events").entered();
+    let start = Instant::now();
     let set: HashSet<IVec2> = chunk_ev.read().map(|ev| ev.0).collect();
+    timings.collider_rects = set.len();
 
     for chunk_position in set {
         if let Some((entity, chunk)) = chunk_manager.chunks.get(&chunk_position) {
@@ -82,23 +116,23 @@ pub fn process_chunk_collider_events(
                 }
             }
 
-            if let Ok(colliders) = chunk.build_colliders() {
+            if let Ok(collider) = chunk.build_colliders(config.collider_tolerance) {
                 commands.entity(*entity).with_children(|parent| {
-                    for collider in colliders {
-                        parent.spawn((
-                            collider,
-                            TransformBundle {
-                                local: Transform::IDENTITY,
-                                ..Default::default()
-                            },
-                            CollisionGroups::new(
-                                Group::from_bits_truncate(TERRAIN_MASK),
-                                Group::from_bits_truncate(OBJECT_MASK)
-                            ),
-                        ));
-                    }
+                    parent.spawn((
+                        collider,
+                        TransformBundle {
+                            local: Transform::IDENTITY,
+                            ..Default::default()
+                        },
+                        CollisionGroups::new(
+                            Group::from_bits_truncate(TERRAIN_MASK),
+                            Group::from_bits_truncate(OBJECT_MASK)
+                        ),
+                    ));
                 });
             }
         }
     }
+
+    timings.record(Stage::Colliders, start);
 }
@@ -0,0 +1,238 @@
+use std::time::{ Duration, Instant };
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{ Extent3d, TextureDimension, TextureFormat },
+        texture::BevyDefault,
+        view::RenderLayers,
+    },
+    time::common_conditions::on_timer,
+    utils::{ HashMap, HashSet },
+};
+use bevy_persistent::Persistent;
+
+use crate::{
+    camera::LIGHTING_RENDER_LAYER,
+    constants::CHUNK_SIZE,
+    profiler::{ Stage, StageTimings },
+    settings::Config,
+    state::GameState,
+};
+
+use super::{
+    chunk::ChunkState,
+    chunk_manager::ChunkManager,
+    dirty_rect::DirtyRects,
+    materials::Emission,
+    render_dirty_rect_updates,
+};
+
+const GLOW_TEXTURE_SIZE: u32 = 64;
+const EMISSION_INTERVAL: Duration = Duration::from_millis(200);
+// pixels burning in fire glow as if they were made of an emissive material
+const FIRE_EMISSION: Emission = Emission { color: [0xea, 0xaa, 0x00], intensity: 0.8 };
+// chunks with less total intensity do not get a light
+const MIN_INTENSITY: f32 = 4.0;
+// radius of the light in pixels grows with the square root of total intensity
+const RADIUS_PER_INTENSITY: f32 = 1.5;
+const MAX_RADIUS: f32 = 160.0;
+// total intensity at which the light is at its brightest
+const FULL_INTENSITY: f32 = 256.0;
+const MAX_ALPHA: f32 = 0.75;
+// flickering fire is much dimmer with reduced flashes
+const REDUCED_MAX_ALPHA: f32 = 0.3;
+
+pub struct EmissionPlugin;
+
+impl Plugin for EmissionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkEmissions>()
+            .add_systems(Startup, setup_glow_texture)
+            .add_systems(OnEnter(GameState::LevelInitialization), |mut emissions: ResMut<ChunkEmissions>| {
+                emissions.lights.clear();
+                emissions.scanned.clear();
+                emissions.dirty.clear();
+            })
+            .add_systems(
+                PostUpdate,
+                (
+                    collect_dirty_emissions.before(render_dirty_rect_updates),
+                    update_chunk_lights.run_if(on_timer(EMISSION_INTERVAL)),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Light sprites of chunks with emissive pixels,
+/// one per chunk so thousands of glowing pixels cost a single draw
+#[derive(Resource, Default)]
+pub struct ChunkEmissions {
+    pub lights: HashMap<IVec2, Entity>,
+    /// Chunks that were looked through at least once since they were generated
+    pub scanned: HashSet<IVec2>,
+    /// Chunks that were redrawn since the last update of the lights
+    pub dirty: HashSet<IVec2>,
+}
+
+#[derive(Component)]
+pub struct ChunkLight;
+
+#[derive(Resource)]
+pub struct GlowTexture(pub Handle<Image>);
+
+fn setup_glow_texture(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let center = (GLOW_TEXTURE_SIZE as f32) / 2.0;
+
+    let data = (0..GLOW_TEXTURE_SIZE.pow(2))
+        .flat_map(|index| {
+            let position = Vec2::new(
+                ((index % GLOW_TEXTURE_SIZE) as f32) + 0.5,
+                ((index / GLOW_TEXTURE_SIZE) as f32) + 0.5
+            );
+            let falloff = (1.0 - position.distance(Vec2::splat(center)) / center).max(0.0);
+
+            [255, 255, 255, (falloff.powi(2) * 255.0) as u8]
+        })
+        .collect::<Vec<u8>>();
+
+    let texture = Image::new(
+        Extent3d {
+            width: GLOW_TEXTURE_SIZE,
+            height: GLOW_TEXTURE_SIZE,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::RENDER_WORLD
+    );
+
+    commands.insert_resource(GlowTexture(images.add(texture)));
+}
+
+fn collect_dirty_emissions(
+    dirty_rects: Res<DirtyRects>,
+    chunk_manager: Res<ChunkManager>,
+    mut emissions: ResMut<ChunkEmissions>
+) {
+    let ChunkEmissions { scanned, dirty, .. } = &mut *emissions;
+
+    dirty.extend(dirty_rects.render.keys().copied());
+
+    // freshly generated chunks are drawn whole without going through dirty rects,
+    // they are only scanned once populated since pixels are still placed before that
+    dirty.extend(
+        chunk_manager.chunks
+            .iter()
+            .filter(|(position, (_, chunk))| {
+                chunk.state >= ChunkState::Active && !scanned.contains(*position)
+            })
+            .map(|(position, _)| *position)
+    );
+}
+
+fn update_chunk_lights(
+    mut commands: Commands,
+    mut emissions: ResMut<ChunkEmissions>,
+    mut light_q: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<ChunkLight>>,
+    chunk_manager: Res<ChunkManager>,
+    glow: Res<GlowTexture>,
+    config: Res<Persistent<Config>>,
+    mut timings: ResMut<StageTimings>
+) {
+    let _span = info_span!("chunk_lights").entered();
+    let start = Instant::now();
+    let max_alpha = if config.reduced_flashes { REDUCED_MAX_ALPHA } else { MAX_ALPHA };
+
+    let ChunkEmissions { lights, scanned, dirty } = &mut *emissions;
+
+    for chunk_position in dirty.drain() {
+        let Some((chunk_entity, chunk)) = chunk_manager.chunks.get(&chunk_position) else {
+            lights.remove(&chunk_position);
+            scanned.remove(&chunk_position);
+            continue;
+        };
+
+        if chunk.state >= ChunkState::Active {
+            scanned.insert(chunk_position);
+        }
+
+        let mut intensity = 0.0;
+        let mut color = Vec3::ZERO;
+        let mut center = Vec2::ZERO;
+
+        for (index, pixel) in chunk.pixels.iter().enumerate() {
+            let emission = if pixel.on_fire {
+                Some(FIRE_EMISSION)
+            } else {
+                pixel.material.emission
+            };
+
+            let Some(emission) = emission else {
+                continue;
+            };
+
+            let position = Vec2::new(
+                ((index as i32) % CHUNK_SIZE) as f32,
+                ((index as i32) / CHUNK_SIZE) as f32
+            );
+
+            intensity += emission.intensity;
+            color += Vec3::from_array(emission.color.map(|channel| channel as f32)) * emission.intensity;
+            center += (position + 0.5) * emission.intensity;
+        }
+
+        let light = lights
+            .get(&chunk_position)
+            .and_then(|entity| light_q.get_mut(*entity).ok());
+
+        if intensity < MIN_INTENSITY {
+            if let Some((_, _, mut visibility)) = light {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        }
+
+        let color = color / intensity / 255.0;
+        let center = center / intensity / (CHUNK_SIZE as f32);
+        let radius = (intensity.sqrt() * RADIUS_PER_INTENSITY).min(MAX_RADIUS);
+        let alpha = (intensity / FULL_INTENSITY).min(max_alpha);
+
+        let sprite = Sprite {
+            color: Color::rgba(color.x, color.y, color.z, alpha),
+            custom_size: Some(Vec2::splat((radius * 2.0) / (CHUNK_SIZE as f32))),
+            ..Default::default()
+        };
+        // light sits right above the chunk's own lighting texture
+        let transform = Transform::from_translation(center.extend(0.5));
+
+        if let Some((mut light_sprite, mut light_transform, mut visibility)) = light {
+            *light_sprite = sprite;
+            *light_transform = transform;
+            *visibility = Visibility::Inherited;
+        } else {
+            let entity = commands
+                .spawn((
+                    Name::new("Chunk light"),
+                    ChunkLight,
+                    SpriteBundle {
+                        texture: glow.0.clone(),
+                        sprite,
+                        transform,
+                        ..Default::default()
+                    },
+                    RenderLayers::layer(LIGHTING_RENDER_LAYER),
+                ))
+                .id();
+
+            commands.entity(*chunk_entity).add_child(entity);
+            lights.insert(chunk_position, entity);
+        }
+    }
+
+    timings.record(Stage::Lighting, start);
+}
@@ -17,20 +17,56 @@ pub struct Material {
     #[serde(default)]
     pub durability: Option<f32>,
 
+    /// Tier of the mining tool needed to carve the material with attacks, 0 is carved by anything
+    #[serde(default)]
+    pub hardness: u32,
+
+    /// Share of its speed an actor loses for every pixel of the powder it pushes out of the way
+    #[serde(default = "default_displacement_cost")]
+    pub displacement_cost: f32,
+
+    /// Share of the storage of the players the material can fill, the excess spills out
+    #[serde(default = "default_storage")]
+    pub storage: f32,
+
     #[serde(default)]
     pub lighting: Option<[u8; 4]>,
 
+    #[serde(default)]
+    pub emission: Option<Emission>,
+
+    /// Look of the surface and depths of a liquid, drawn over its pixels
+    #[serde(default)]
+    pub water: Option<WaterStyle>,
+
     #[serde(default)]
     pub fire: Option<Fire>,
 
+    #[serde(default)]
+    pub growth: Option<Growth>,
+
+    #[serde(default)]
+    pub emitter: Option<Emitter>,
+
+    #[serde(default)]
+    pub sink: Option<Sink>,
+
     #[serde(default)]
     pub reactions: Option<HashMap<String, Reaction>>,
 
     #[serde(default)]
     pub contact: Option<ContactEffect>,
 
+    /// Effect of projectiles shot out of the material where they land
+    #[serde(default)]
+    pub impact: Option<Impact>,
+
     #[serde(default)]
     pub tags: HashSet<String>,
+
+    /// Asset path of the sound played when walking on or hitting the material
+    #[serde(default)]
+    pub sound: Option<String>,
 }
 
 #[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -45,6 +81,54 @@ pub enum ContactEffect {
     Transistion(f32, String),
 }
 
+/// Radius is in pixels, projectiles hitting an actor apply the effect to it as well
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum Impact {
+    /// Puts out burning pixels and actors
+    Extinguish(f32),
+    /// Sets flammable pixels and actors on fire
+    Ignite(f32),
+    /// Eats away terrain pixels that can be destroyed
+    Dissolve(f32),
+}
+
+/// Light cast by the material around itself, summed up over every pixel of a chunk
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct Emission {
+    pub color: [u8; 3],
+    pub intensity: f32,
+}
+
+// styles the water shader has room for, materials past it are drawn flat
+pub const MAX_WATER_STYLES: usize = 16;
+
+/// Waves, glints and darkening with depth of a liquid, only changes how its pixels are drawn
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct WaterStyle {
+    /// Color the liquid turns into with depth, alpha is how much of it is mixed in at the bottom
+    pub deep_color: [u8; 4],
+    /// Largest sideways shift of the pixels under the surface, in pixels
+    #[serde(default = "default_wave_amplitude")]
+    pub wave_amplitude: f32,
+    /// Waves along one pixel of height, in radians
+    #[serde(default = "default_wave_frequency")]
+    pub wave_frequency: f32,
+    /// Brightness of the glints running along the surface
+    #[serde(default)]
+    pub shimmer: f32,
+    /// Place of the style among the ones sent to the water shader, given out once materials are loaded
+    #[serde(skip)]
+    pub index: u8,
+}
+
+fn default_wave_amplitude() -> f32 {
+    1.0
+}
+
+fn default_wave_frequency() -> f32 {
+    0.4
+}
+
 #[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Fire {
     pub probability: f32,
@@ -55,6 +139,36 @@ pub struct Fire {
     pub try_to_ignite: bool,
 }
 
+/// Plant that spreads along surfaces, drinking a wet pixel next to it for every pixel it grows
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Growth {
+    /// Chance to grow on every update while there is water around
+    pub rate: f32,
+    /// Pixels a plant grows into, counting the one it started from
+    pub max_size: u32,
+    /// Material of the grown pixels, so seeds can sprout into vines
+    #[serde(default)]
+    pub into: Option<String>,
+}
+
+/// Spawns pixels of a material next to itself, below first, for waterfalls and lava drips
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Emitter {
+    pub material_id: String,
+    /// Chance to spawn a pixel on every update while there is room for it
+    pub rate: f32,
+}
+
+/// Drains pixels of the listed materials that touch it
+#[derive(Reflect, Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Sink {
+    /// Any powder, liquid or gas is drained when empty
+    #[serde(default)]
+    pub material_ids: Vec<String>,
+    /// Chance to drain a pixel on every update while one is touching it
+    pub rate: f32,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct Reaction {
     pub probability: f32,
@@ -121,6 +235,21 @@ impl Default for PhysicsType {
     }
 }
 
+fn default_displacement_cost() -> f32 {
+    0.02
+}
+
+fn default_storage() -> f32 {
+    1.0
+}
+
+impl Material {
+    /// Most of the material that can be carried with the storage
+    pub fn capacity(&self, max_storage: f32) -> f32 {
+        max_storage * self.storage
+    }
+}
+
 impl Default for Material {
     fn default() -> Self {
         Self {
@@ -131,10 +260,20 @@ impl Default for Material {
             color_offset: 0,
             reactions: None,
             lighting: None,
+            emission: None,
+            water: None,
             fire: None,
+            growth: None,
+            emitter: None,
+            sink: None,
             contact: None,
+            impact: None,
             durability: None,
+            hardness: 0,
+            displacement_cost: default_displacement_cost(),
+            storage: default_storage(),
             tags: HashSet::new(),
+            sound: None,
         }
     }
 }
@@ -216,6 +355,145 @@ pub fn update_fire(api: &mut ChunkApi) -> bool {
     false
 }
 
+/// Plants wither next to fire and otherwise grow into an empty pixel along a surface,
+/// returns whether the pixel withered
+pub fn update_growth(api: &mut ChunkApi, materials: &HashMap<String, Material>) -> bool {
+    let mut pixel = api.get(0, 0);
+
+    let (Some(growth), Some(remaining)) = (pixel.material.growth.clone(), pixel.growth) else {
+        return false;
+    };
+
+    if
+        EIGHT_DIRECTIONS.iter().any(|direction| {
+            let neighbour = api.get(direction.x, direction.y);
+
+            neighbour.on_fire || neighbour.material.tags.contains("hot")
+        })
+    {
+        if pixel.physics_type == PhysicsType::Static {
+            api.collider_changed(0, 0);
+        }
+
+        api.update(Pixel::default());
+        return true;
+    }
+
+    if remaining == 0 {
+        return false;
+    }
+
+    let Some(water) = FOUR_DIRECTIONS.into_iter().find(|direction| {
+        api.get(direction.x, direction.y).material.tags.contains("wet")
+    }) else {
+        return false;
+    };
+
+    // stays awake only while it has something to drink, flowing water wakes it up again
+    api.keep_alive(0, 0);
+
+    if fastrand::f32() >= growth.rate {
+        return false;
+    }
+
+    // other plant pixels are not a surface, otherwise vines would grow straight into the air
+    let targets = FOUR_DIRECTIONS.into_iter()
+        .filter(|direction| {
+            api.get_physics_type(direction.x, direction.y) == PhysicsType::Air &&
+                EIGHT_DIRECTIONS.iter().any(|offset| {
+                    let neighbour = *direction + *offset;
+
+                    neighbour != IVec2::ZERO &&
+                        api.get_physics_type(neighbour.x, neighbour.y) == PhysicsType::Static &&
+                        api.get(neighbour.x, neighbour.y).material.growth.is_none()
+                })
+        })
+        .collect::<Vec<IVec2>>();
+
+    if targets.is_empty() {
+        return false;
+    }
+
+    let target = targets[fastrand::usize(..targets.len())];
+
+    let material = growth.into
+        .as_ref()
+        .and_then(|id| materials.get(id))
+        .unwrap_or(&pixel.material);
+    let mut grown = Pixel::from(material).with_clock(api.clock);
+    grown.growth = Some(remaining - 1);
+
+    if grown.physics_type == PhysicsType::Static {
+        api.collider_changed(target.x, target.y);
+    }
+
+    api.set(target.x, target.y, grown);
+    api.set(water.x, water.y, Pixel::default());
+
+    // only the tip keeps growing
+    pixel.growth = Some(0);
+    api.update(pixel);
+
+    false
+}
+
+/// Emitters only stay awake while there is room next to them, so once the pool under
+/// a waterfall is full they sleep until the liquid flows away and wakes them up
+pub fn update_emitter(api: &mut ChunkApi, materials: &HashMap<String, Material>) {
+    let pixel = api.get(0, 0);
+
+    let Some(emitter) = &pixel.material.emitter else {
+        return;
+    };
+
+    let Some(material) = materials.get(&emitter.material_id) else {
+        return;
+    };
+
+    let side = api.rand_dir();
+    let Some(target) = [IVec2::NEG_Y, IVec2::new(side, 0), IVec2::new(-side, 0), IVec2::Y]
+        .into_iter()
+        .find(|direction| api.get_physics_type(direction.x, direction.y) == PhysicsType::Air) else {
+        return;
+    };
+
+    api.keep_alive(0, 0);
+
+    if fastrand::f32() < emitter.rate {
+        api.set(target.x, target.y, Pixel::from(material).with_clock(api.clock));
+    }
+}
+
+/// Sinks stay awake while a matching pixel is touching them, same as emitters
+pub fn update_sink(api: &mut ChunkApi) {
+    let pixel = api.get(0, 0);
+
+    let Some(sink) = &pixel.material.sink else {
+        return;
+    };
+
+    let Some(source) = FOUR_DIRECTIONS.into_iter().find(|direction| {
+        let neighbour = api.get(direction.x, direction.y);
+
+        if sink.material_ids.is_empty() {
+            matches!(
+                neighbour.physics_type,
+                PhysicsType::Powder | PhysicsType::Liquid(..) | PhysicsType::Gas(..)
+            )
+        } else {
+            sink.material_ids.contains(&neighbour.material.id)
+        }
+    }) else {
+        return;
+    };
+
+    api.keep_alive(0, 0);
+
+    if fastrand::f32() < sink.rate {
+        api.set(source.x, source.y, Pixel::default());
+    }
+}
+
 pub fn update_reactions(api: &mut ChunkApi, materials: &HashMap<String, Material>) {
     let pixel = api.get(0, 0);
 
@@ -1,6 +1,6 @@
-use std::time::Duration;
+use std::time::{ Duration, Instant };
 
-use bevy::{ prelude::*, time::common_conditions::on_timer, transform::TransformSystem };
+use bevy::{ prelude::*, transform::TransformSystem };
 use bevy_rapier2d::{
     plugin::{ systems::sync_removals, NoUserData, PhysicsSet, RapierPhysicsPlugin },
     render::{ DebugRenderContext, DebugRenderMode, RapierDebugRenderPlugin },
@@ -8,10 +8,12 @@ use bevy_rapier2d::{
 
 use crate::{
     generation::{ GenerationPlugin, LevelData },
+    profiler::{ Stage, StageTimings },
     state::GameState,
 };
 
 use self::{
+    atlas::{ ChunkAtlas, ChunkAtlasPlugin },
     chunk_manager::{
         chunk_set_parent,
         chunks_update,
@@ -22,41 +24,67 @@ use self::{
     },
     colliders::{ process_chunk_collider_events, ChunkColliderEvent },
     dirty_rect::{ dirty_rects_gizmos, DirtyRects },
+    emission::EmissionPlugin,
     object::{
         fill_objects,
         object_collision_damage,
         // process_explosive,
+        process_explosions,
         process_projectiles,
         unfill_objects,
+        ExplosionEvent,
         Object,
     },
+    speed::{
+        apply_physics_speed,
+        control_simulation_speed,
+        pause_physics_after_step,
+        run_simulation_ticks,
+        simulation_pass_due,
+        SimulationSpeed,
+        SimulationTick,
+    },
     particle::{
+        clear_particle_pool,
         particle_modify_velocity,
         particle_set_parent,
         particle_setup,
         particles_update,
+        recycle_particles,
         ParticleParent,
+        ParticlePool,
     },
+    particle_render::ParticleRenderPlugin,
 };
 
+pub mod atlas;
 pub mod chunk;
 pub mod chunk_groups;
 pub mod chunk_manager;
 pub mod dirty_rect;
+pub mod emission;
 pub mod materials;
 pub mod colliders;
 pub mod object;
 pub mod particle;
+pub mod particle_render;
 pub mod pixel;
+pub mod speed;
+
+/// Interval between simulation passes over the chunks
+pub const SIMULATION_STEP: Duration = Duration::from_millis(10);
 
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ChunkManager>()
-            .add_plugins(GenerationPlugin)
+            .init_resource::<ParticlePool>()
+            .init_resource::<SimulationSpeed>()
+            .add_plugins((GenerationPlugin, EmissionPlugin, ChunkAtlasPlugin, ParticleRenderPlugin))
             .add_event::<ChunkColliderEvent>()
-            .add_systems(OnExit(GameState::GameOver), reset_world)
+            .add_event::<ExplosionEvent>()
+            .add_systems(OnExit(GameState::GameOver), (reset_world, clear_particle_pool))
             .add_systems(Startup, (manager_setup, particle_setup))
             .add_systems(PreUpdate, update_loaded_chunks.run_if(in_state(GameState::Game)))
             .add_systems(
@@ -65,12 +93,18 @@ impl Plugin for SimulationPlugin {
                     in_state(GameState::Game).or_else(in_state(GameState::Splash))
                 )
             )
+            .add_systems(SimulationTick, (
+                (
+                    recycle_particles,
+                    particle_set_parent,
+                    particle_modify_velocity,
+                    particles_update,
+                ).chain(),
+                chunks_update.run_if(simulation_pass_due),
+            ).chain())
             .add_systems(
                 Update,
-                (
-                    (particle_set_parent, particle_modify_velocity, particles_update).chain(),
-                    chunks_update.chain().run_if(on_timer(Duration::from_millis(10))),
-                )
+                (control_simulation_speed, apply_physics_speed, run_simulation_ticks)
                     .chain()
                     .run_if(in_state(GameState::Game))
             )
@@ -87,13 +121,19 @@ impl Plugin for SimulationPlugin {
                     (
                         object_collision_damage,
                         // process_explosive,
-                        process_projectiles,
+                        (process_projectiles, process_explosions).chain(),
                     ).after(PhysicsSet::Writeback),
                     fill_objects,
                 )
                     .chain()
                     .run_if(in_state(GameState::Game))
             )
+            .add_systems(
+                FixedUpdate,
+                pause_physics_after_step
+                    .after(PhysicsSet::StepSimulation)
+                    .run_if(in_state(GameState::Game))
+            )
             .insert_resource(Msaa::Off)
             .init_resource::<DirtyRects>();
 
@@ -156,6 +196,7 @@ pub fn reset_world(
     mut commands: Commands,
     particles_instances: Query<Entity, With<ParticleParent>>,
     mut chunk_manager: ResMut<ChunkManager>,
+    mut atlas: ResMut<ChunkAtlas>,
     chunks: Query<Entity, With<Terrain>>,
     objects: Query<Entity, With<Object>>
 ) {
@@ -167,19 +208,28 @@ pub fn reset_world(
     }
 
     chunk_manager.chunks.clear();
+    atlas.clear();
 }
 
 pub fn render_dirty_rect_updates(
     mut dirty_rects_resource: ResMut<DirtyRects>,
-    mut images: ResMut<Assets<Image>>,
+    mut atlas: ResMut<ChunkAtlas>,
+    images: Res<Assets<Image>>,
+    mut timings: ResMut<StageTimings>,
     level: Res<LevelData>,
-    chunk_manager: Res<ChunkManager>
+    mut chunk_manager: ResMut<ChunkManager>
 ) {
+    let _span = info_span!("render_dirty_rects").entered();
+    let start = Instant::now();
+    timings.render_rects = dirty_rects_resource.render.len();
+
     dirty_rects_resource.render.iter_mut().for_each(|(position, rect)| {
-        if let Some(chunk) = chunk_manager.get_chunk_data(position) {
-            chunk.update_textures_part(&mut images, level.0.lighting, *rect);
+        if let Some(chunk) = chunk_manager.get_chunk_data_mut(position) {
+            chunk.update_textures_part(&images, &mut atlas, level.0.lighting, *rect);
         }
     });
 
     dirty_rects_resource.render.clear();
+
+    timings.record(Stage::TextureUpload, start);
 }
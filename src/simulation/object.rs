@@ -1,33 +1,50 @@
-use std::{ f32::consts::{ FRAC_PI_2, PI }, mem, time::{ SystemTime, UNIX_EPOCH } };
+use std::{ f32::consts::{ FRAC_PI_2, PI, TAU }, mem };
 
 use bevy::{
     prelude::*,
     utils::{dbg, HashMap},
     window::PrimaryWindow,
 };
-use bevy_egui::{ egui::Id, EguiContexts };
+use bevy_egui::EguiContexts;
 use bevy_math::ivec2;
 use bevy_rapier2d::prelude::*;
 use itertools::Itertools;
 
 use crate::{
-    actors::{ enemy::Enemy, health::DamageEvent },
-    camera::TrackingCamera,
+    actors::{
+        actor::Actor,
+        behavior::NoiseEvent,
+        effects::Burning,
+        enemy::Enemy,
+        health::{ DamageCause, DamageEvent, DamageType },
+        player::Player,
+    },
+    camera::{ TrackingCamera, WorldRenderTarget },
     constants::{ CHUNK_SIZE, PARTICLE_Z },
-    gui::{ Cell, Inventory },
+    curses::FragileTerrain,
+    gui::Inventory,
+    score_export::RunStats,
+    stains::{ StainEvent, StainKind },
 };
 
 use super::{
-    chunk::ChunkState,
+    chunk::{ Chunk, ChunkState },
     chunk_groups:: ChunkGroupCustom ,
     chunk_manager::ChunkManager,
-    colliders::{ douglas_peucker, ACTOR_MASK, OBJECT_MASK },
+    colliders::{ simplify_ring, ACTOR_MASK, OBJECT_MASK },
     dirty_rect:: DirtyRects ,
-    materials::PhysicsType,
-    particle::{ Particle, ParticleBundle },
+    materials::{ Impact, PhysicsType },
+    particle::{ Particle, ParticleBundle, Particles },
     pixel::Pixel,
 };
 
+// simplification distance of object outlines in pixels, objects are small so it is kept tight
+const COLLIDER_TOLERANCE: f32 = 0.5;
+// explosions are heard by enemies this many times further away than they reach
+const EXPLOSION_NOISE_SCALE: f32 = 8.0;
+// scorch marks reach this far relative to the radius of the explosion
+const SCORCH_SCALE: f32 = 1.5;
+
 #[derive(Bundle)]
 pub struct ObjectBundle {
     pub object: Object,
@@ -73,17 +90,64 @@ pub struct Object {
     pub pixel_count: usize,
 }
 
+/// Loose object the player can take into the inventory, objects with a purpose of their own are left out
+#[derive(Component, Clone, Copy, Default)]
+pub struct Storable;
+
+// multiplier of the damage of a projectile sent back by a player
+const DEFLECT_DAMAGE_SCALE: f32 = 2.0;
+
+/// Side a projectile fights for, only projectiles of enemies can be deflected
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectileOwner {
+    #[default]
+    Neutral,
+    Player,
+    Enemy,
+}
+
 #[derive(Component, Clone)]
 pub struct Projectile {
     pub damage: f32,
+    pub owner: ProjectileOwner,
     pub launched_by: Option<Entity>,
     pub left_source: bool,
     pub timer: Timer,
     pub collided_with: Vec<Entity>,
     pub explosion_on_contact: Option<ExplosionParameters>,
-    pub insert_on_contact: bool,
+    pub terrain_interaction: TerrainInteraction,
+    pub impact: Option<Impact>,
+}
+
+/// What happens to a projectile once it hits the terrain, projectiles that hit an actor
+/// are inserted or removed right away
+#[derive(Default, Clone, Debug)]
+pub enum TerrainInteraction {
+    /// Projectile disappears
+    #[default]
+    Vanish,
+    /// Pixels of the projectile are placed into the world where it stopped
+    Insert,
+    /// Reflects off the surface keeping part of the velocity, pixels are inserted once bounces run out
+    Bounce {
+        restitution: f32,
+        bounces: u32,
+        /// Set after a bounce until the projectile is out of the surface again
+        leaving: bool,
+    },
+    /// Destroys terrain pixels on its way, pixels are inserted once the limit is reached
+    Pierce {
+        pixels: u32,
+    },
+    /// Stays embedded in the terrain as a fixed object
+    Stick,
+    /// Bursts into particles of its pixels thrown away from the surface with the given speed in pixels
+    Splash {
+        speed: f32,
+    },
 }
 
+/// Radius is in pixels, damage is dealt both to enemies and to durability of terrain pixels
 #[derive(Component, Clone)]
 pub struct ExplosionParameters {
     pub radius: f32,
@@ -91,15 +155,24 @@ pub struct ExplosionParameters {
     pub force: f32,
 }
 
+/// Position is in chunk units
+#[derive(Event, Clone)]
+pub struct ExplosionEvent {
+    pub position: Vec2,
+    pub parameters: ExplosionParameters,
+}
+
 impl Projectile {
     pub fn new(penetration_threshold_secs: f32, damage: f32) -> Self {
         Self {
             damage,
+            owner: ProjectileOwner::default(),
             launched_by: None,
             timer: Timer::from_seconds(penetration_threshold_secs, TimerMode::Once),
             collided_with: vec![],
             explosion_on_contact: None,
-            insert_on_contact: false,
+            terrain_interaction: TerrainInteraction::default(),
+            impact: None,
             left_source: false,
         }
     }
@@ -109,6 +182,27 @@ impl Projectile {
         self
     }
 
+    pub fn with_owner(mut self, owner: ProjectileOwner) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// Hands a projectile of an enemy over to the player that hit it back, it can hurt
+    /// the enemy that launched it and hits harder, returns false for any other projectile
+    pub fn deflect(&mut self, player: Entity) -> bool {
+        if self.owner != ProjectileOwner::Enemy {
+            return false;
+        }
+
+        self.owner = ProjectileOwner::Player;
+        self.launched_by = Some(player);
+        self.left_source = true;
+        self.collided_with.clear();
+        self.damage *= DEFLECT_DAMAGE_SCALE;
+
+        true
+    }
+
     pub fn with_explosion(mut self, radius: f32, damage: f32, force: f32) -> Self {
         self.explosion_on_contact = Some(ExplosionParameters {
             radius,
@@ -119,7 +213,32 @@ impl Projectile {
     }
 
     pub fn insert_on_contact(mut self) -> Self {
-        self.insert_on_contact = true;
+        self.terrain_interaction = TerrainInteraction::Insert;
+        self
+    }
+
+    pub fn with_bounce(mut self, restitution: f32, bounces: u32) -> Self {
+        self.terrain_interaction = TerrainInteraction::Bounce { restitution, bounces, leaving: false };
+        self
+    }
+
+    pub fn with_pierce(mut self, pixels: u32) -> Self {
+        self.terrain_interaction = TerrainInteraction::Pierce { pixels };
+        self
+    }
+
+    pub fn stick_on_contact(mut self) -> Self {
+        self.terrain_interaction = TerrainInteraction::Stick;
+        self
+    }
+
+    pub fn with_splash(mut self, speed: f32) -> Self {
+        self.terrain_interaction = TerrainInteraction::Splash { speed };
+        self
+    }
+
+    pub fn with_impact(mut self, impact: Option<Impact>) -> Self {
+        self.impact = impact;
         self
     }
 }
@@ -140,6 +259,7 @@ impl Object {
         })
     }
 
+    /// Triangulates every separate part of the object along with the holes inside of it
     pub fn create_collider(&self) -> Result<Collider, String> {
         let values = self.pixels
             .iter()
@@ -152,75 +272,75 @@ impl Object {
             false
         );
 
-        contour_generator
+        let contours = contour_generator
             .contours(&values, &[1.0])
-            .map_err(|_| "no contours were found".to_string())
-            .and_then(|contours| {
-                contours[0]
-                    .geometry()
-                    .0.first()
-                    .ok_or("no contours were found".to_string())
-                    .map(|polygon| {
-                        std::iter
-                            ::once(polygon.exterior())
-                            .chain(polygon.interiors().iter())
-                            .map(|line| {
-                                line.0
-                                    .iter()
-                                    .map(|point| {
-                                        Vec2::new(
-                                            ((point.x as f32) - (self.size.x as f32) / 2.0) /
-                                                (CHUNK_SIZE as f32),
-                                            ((point.y as f32) - (self.size.y as f32) / 2.0) /
-                                                (CHUNK_SIZE as f32)
-                                        )
-                                    })
-                                    .collect::<Vec<Vec2>>()
-                            })
-                            .map(|line| {
-                                douglas_peucker(&line, 0.25 / (CHUNK_SIZE.pow(2) as f32))
-                            })
-                            .filter(|points| points.len() > 2)
-                            .map(|line| {
-                                line.into_iter()
-                                    .map(|point| vec![point.x, point.y])
-                                    .collect_vec()
-                            })
-                            .collect::<Vec<Vec<Vec<f32>>>>()
+            .map_err(|_| "no contours were found".to_string())?;
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+
+        for polygon in contours[0].geometry().0.iter() {
+            let to_local = |line: &[contour::Pt]| {
+                let ring = line
+                    .iter()
+                    .map(|point| {
+                        Vec2::new(
+                            ((point.x as f32) - (self.size.x as f32) / 2.0) / (CHUNK_SIZE as f32),
+                            ((point.y as f32) - (self.size.y as f32) / 2.0) / (CHUNK_SIZE as f32)
+                        )
                     })
-                    .and_then(|boundaries| {
-                        if boundaries.is_empty() {
-                            return Err("empty boundary was received".to_string());
-                        }
+                    .collect::<Vec<Vec2>>();
 
-                        let (vertices, holes, dimensions) = earcutr::flatten(&boundaries);
+                simplify_ring(&ring, COLLIDER_TOLERANCE / (CHUNK_SIZE as f32))
+                    .into_iter()
+                    .map(|point| vec![point.x, point.y])
+                    .collect_vec()
+            };
 
-                        let Ok(triangles) = earcutr::earcut(&vertices, &holes, dimensions) else {
-                            return Err("error occured during triangulation".to_string());
-                        };
+            let exterior = to_local(&polygon.exterior().0);
 
-                        if triangles.is_empty() {
-                            return Err("no triangles were constructed".to_owned());
-                        }
+            // parts thinner than the tolerance collapse and are left without a collider
+            if exterior.len() < 3 {
+                continue;
+            }
 
-                        let mut indices = vec![];
-                        let mut converted_vertices = vec![];
+            let boundaries = std::iter
+                ::once(exterior)
+                .chain(
+                    polygon
+                        .interiors()
+                        .iter()
+                        .map(|line| to_local(&line.0))
+                        .filter(|points| points.len() > 2)
+                )
+                .collect::<Vec<Vec<Vec<f32>>>>();
 
-                        for vertices in vertices.chunks_exact(2) {
-                            converted_vertices.push(Vec2::new(vertices[0], vertices[1]));
-                        }
+            let (flat_vertices, holes, dimensions) = earcutr::flatten(&boundaries);
 
-                        for triangle in triangles.chunks_exact(3) {
-                            indices.push([
-                                triangle[0] as u32,
-                                triangle[1] as u32,
-                                triangle[2] as u32,
-                            ]);
-                        }
+            let Ok(triangles) = earcutr::earcut(&flat_vertices, &holes, dimensions) else {
+                return Err("error occured during triangulation".to_string());
+            };
 
-                        Ok(Collider::trimesh(converted_vertices, indices))
-                    })
-            })
+            let offset = vertices.len() as u32;
+
+            for vertex in flat_vertices.chunks_exact(2) {
+                vertices.push(Vec2::new(vertex[0], vertex[1]));
+            }
+
+            for triangle in triangles.chunks_exact(3) {
+                indices.push([
+                    offset + (triangle[0] as u32),
+                    offset + (triangle[1] as u32),
+                    offset + (triangle[2] as u32),
+                ]);
+            }
+        }
+
+        if indices.is_empty() {
+            return Err("no triangles were constructed".to_owned());
+        }
+
+        Ok(Collider::trimesh(vertices, indices))
     }
 
     pub fn iterate_over_pixels(
@@ -311,19 +431,26 @@ impl Object {
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn process_projectiles(
     mut commands: Commands,
+    mut particles: Particles,
     rapier_context: Res<RapierContext>,
     mut damage_ev: EventWriter<DamageEvent>,
+    mut explosion_ev: EventWriter<ExplosionEvent>,
     mut dirty_rects_resource: ResMut<DirtyRects>,
     mut chunk_manager: ResMut<ChunkManager>,
-    mut projectile_q: Query<(Entity, &Transform, &mut Object, &mut Projectile, &Velocity)>,
-    actor_q: Query<&Transform, (With<Enemy>, Without<Projectile>)>,
+    mut projectile_q: Query<(Entity, &Transform, &mut Object, &mut Projectile, &mut Velocity)>,
     sensor_q: Query<Entity, With<Sensor>>,
     rigidbody_q: Query<Entity, (With<RigidBody>, Without<Sensor>)>,
-    time: Res<Time>
+    terrain_q: Query<(), With<Chunk>>,
+    actor_q: Query<(), With<Actor>>,
+    player_q: Query<(), With<Player>>,
+    enemy_q: Query<(), With<Enemy>>,
+    time: Res<Time>,
+    mut run_stats: ResMut<RunStats>
 ) {
-    for (entity, transform, mut object, mut parameters, velocity) in projectile_q.iter_mut() {
+    for (entity, transform, mut object, mut parameters, mut velocity) in projectile_q.iter_mut() {
         if object.placed {
             continue;
         }
@@ -355,6 +482,17 @@ pub fn process_projectiles(
                     return None;
                 }
 
+                // projectiles fly through the side that launched them
+                let friendly = match parameters.owner {
+                    ProjectileOwner::Player => player_q.contains(rb_entity),
+                    ProjectileOwner::Enemy => enemy_q.contains(rb_entity),
+                    ProjectileOwner::Neutral => false,
+                };
+
+                if friendly {
+                    return None;
+                }
+
                 (!sensor_q.contains(collider_entity) && rigidbody_q.contains(rb_entity)).then_some(
                     rb_entity
                 )
@@ -364,7 +502,7 @@ pub fn process_projectiles(
         for actor_entity in collided_with.iter() {
             if parameters.launched_by.map_or(false, |entity| {
                 *actor_entity == entity
-            }) {
+            }) || terrain_q.contains(*actor_entity) {
                 continue;
             }
 
@@ -375,7 +513,84 @@ pub fn process_projectiles(
                 knockback: velocity.linvel / 2.0,
                 ignore_iframes: false,
                 play_sound: true,
+                cause: DamageCause::Object,
+                damage_type: DamageType::Projectile,
             });
+
+            if actor_q.contains(*actor_entity) {
+                match parameters.impact {
+                    Some(Impact::Ignite(_)) => {
+                        commands.entity(*actor_entity).try_insert(Burning::default());
+                    }
+                    Some(Impact::Extinguish(_)) => {
+                        commands.entity(*actor_entity).remove::<Burning>();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(terrain) = collided_with.iter().find(|entity| terrain_q.contains(**entity)) {
+            let stopped = match &mut parameters.terrain_interaction {
+                TerrainInteraction::Bounce { restitution, bounces, leaving } if *bounces > 0 => {
+                    let normal = terrain_normal(&object, transform, &chunk_manager);
+                    let along_normal = velocity.linvel.dot(normal);
+
+                    if along_normal < 0.0 {
+                        velocity.linvel = (velocity.linvel - 2.0 * along_normal * normal) * *restitution;
+                        *bounces -= 1;
+                        *leaving = true;
+                        false
+                    } else {
+                        // only the surface it just bounced off is let go, without a bounce or
+                        // a direction to the surface it would pass through the terrain
+                        !*leaving || normal == Vec2::ZERO
+                    }
+                }
+                TerrainInteraction::Pierce { pixels } if *pixels > 0 => {
+                    let clock = chunk_manager.clock();
+                    let mut blocked = false;
+
+                    for (position, object_pixel) in object.iterate_over_pixels(transform) {
+                        if object_pixel.is_none() {
+                            continue;
+                        }
+
+                        let Ok(world_pixel) = chunk_manager.get_mut(position) else {
+                            continue;
+                        };
+
+                        if !matches!(world_pixel.physics_type, PhysicsType::Static | PhysicsType::Powder) {
+                            continue;
+                        }
+
+                        // indestructible pixels and those left once the pierce runs out stop it
+                        if world_pixel.durability.is_none() || *pixels == 0 {
+                            blocked = true;
+                            continue;
+                        }
+
+                        *world_pixel = Pixel::default().with_clock(clock);
+                        *pixels -= 1;
+                        run_stats.pixels_destroyed += 1;
+
+                        dirty_rects_resource.request_update(position);
+                        dirty_rects_resource.request_render(position);
+                        dirty_rects_resource.collider.insert(
+                            position.div_euclid(IVec2::splat(CHUNK_SIZE))
+                        );
+                    }
+
+                    blocked
+                }
+                _ => true,
+            };
+
+            if stopped {
+                parameters.collided_with.push(*terrain);
+            }
+        } else if let TerrainInteraction::Bounce { leaving, .. } = &mut parameters.terrain_interaction {
+            *leaving = false;
         }
 
         if parameters.collided_with.is_empty() {
@@ -385,135 +600,329 @@ pub fn process_projectiles(
         parameters.timer.tick(time.delta());
 
         if parameters.timer.finished() {
+            if let Some(impact) = parameters.impact.as_ref() {
+                apply_impact(
+                    impact,
+                    (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2(),
+                    &mut chunk_manager,
+                    &mut dirty_rects_resource,
+                    &mut run_stats
+                );
+            }
+
             let (chunk_group_position, mut chunk_group) = object.create_chunk_group(
                 transform,
                 &mut chunk_manager
             );
 
             if let Some(explosion) = parameters.explosion_on_contact.as_ref() {
-                let global_position = (transform.translation.xy() * (CHUNK_SIZE as f32)).as_ivec2();
-                let local_position = global_position - chunk_group_position * CHUNK_SIZE;
+                explosion_ev.send(ExplosionEvent {
+                    position: transform.translation.xy(),
+                    parameters: explosion.clone(),
+                });
+            }
+
+            let stopped_by_actor = parameters.collided_with
+                .iter()
+                .any(|hit_entity| !terrain_q.contains(*hit_entity));
 
-                for x in -explosion.radius as i32..=explosion.radius as i32 {
-                    for y in -explosion.radius as i32..=explosion.radius as i32 {
-                        let offset = ivec2(x, y);
+            match &parameters.terrain_interaction {
+                TerrainInteraction::Vanish => {}
+                TerrainInteraction::Stick if !stopped_by_actor => {
+                    velocity.linvel = Vec2::ZERO;
+                    velocity.angvel = 0.0;
 
-                        if (offset.length_squared() as f32) > explosion.radius.powi(2) {
+                    commands
+                        .entity(entity)
+                        .remove::<(Projectile, Sensor)>()
+                        .insert(RigidBody::Fixed);
+                    continue;
+                }
+                TerrainInteraction::Splash { speed } => {
+                    let mut normal = terrain_normal(&object, transform, &chunk_manager);
+
+                    if normal == Vec2::ZERO {
+                        normal = -velocity.linvel.normalize_or_zero();
+                    }
+
+                    for (position, object_pixel) in object.iterate_over_pixels(transform) {
+                        let Some(pixel) = object_pixel.take() else {
+                            continue;
+                        };
+
+                        let direction = (
+                            normal + Vec2::from_angle(fastrand::f32() * TAU)
+                        ).normalize_or_zero();
+
+                        particles.spawn(ParticleBundle {
+                            sprite: SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::rgba_u8(
+                                        pixel.color[0],
+                                        pixel.color[1],
+                                        pixel.color[2],
+                                        pixel.color[3]
+                                    ),
+                                    custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                                    ..Default::default()
+                                },
+                                transform: Transform::from_translation(
+                                    (position.as_vec2() / (CHUNK_SIZE as f32)).extend(PARTICLE_Z)
+                                ),
+                                ..Default::default()
+                            },
+                            velocity: Velocity::linear(
+                                (direction * *speed * (fastrand::f32() / 2.0 + 0.5)) /
+                                    (CHUNK_SIZE as f32)
+                            ),
+                            particle: Particle::new(pixel),
+                            ..Default::default()
+                        });
+                    }
+                }
+                _ => {
+                    for (position, object_pixel) in object.iterate_over_pixels(transform) {
+                        if object_pixel.is_none() {
                             continue;
                         }
 
-                        let Some(pixel) = chunk_group.get_mut(local_position + offset) else {
+                        let Some(world_pixel) = chunk_group.get_mut(
+                            position - chunk_group_position * CHUNK_SIZE
+                        ) else {
                             continue;
                         };
 
-                        if let Some(durability) = &mut pixel.durability {
-                            *durability -= explosion.damage;
-                            if *durability <= 0.0 {
-                                *pixel = Pixel::default().with_clock(chunk_manager.clock());
+                        {
+                            match world_pixel.physics_type {
+                                PhysicsType::Powder | PhysicsType::Liquid(_) | PhysicsType::Gas(..) => {
+                                    let pixel = std::mem::take(world_pixel);
+
+                                    particles.spawn(ParticleBundle {
+                                        sprite: SpriteBundle {
+                                            sprite: Sprite {
+                                                color: Color::rgba_u8(
+                                                    pixel.color[0],
+                                                    pixel.color[1],
+                                                    pixel.color[2],
+                                                    pixel.color[3]
+                                                ),
+                                                custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                                                ..Default::default()
+                                            },
+                                            transform: Transform::from_translation(
+                                                (position.as_vec2() / (CHUNK_SIZE as f32)).extend(
+                                                    PARTICLE_Z
+                                                )
+                                            ),
+                                            ..Default::default()
+                                        },
+                                        velocity: Velocity::linear(
+                                            Vec2::new(
+                                                fastrand::f32() - 0.5,
+                                                fastrand::f32() / 2.0 + 0.5
+                                            ) / (CHUNK_SIZE as f32)
+                                        ),
+                                        particle: Particle::new(pixel),
+                                        ..Default::default()
+                                    });
+                                }
+                                PhysicsType::Static | PhysicsType::Rigidbody { .. } => {
+                                    continue;
+                                }
+                                _ => {}
                             }
-                        }
 
-                        dirty_rects_resource.request_update(global_position + offset);
-                        dirty_rects_resource.request_render(global_position + offset);
+                            *world_pixel = object_pixel.clone().unwrap();
+
+                            dirty_rects_resource.request_update(position);
+                            dirty_rects_resource.request_render(position);
+                        }
                     }
                 }
+            }
 
-                rapier_context.intersections_with_shape(
-                    transform.translation.xy(),
-                    0.0,
-                    &Collider::ball(explosion.radius / (CHUNK_SIZE as f32)),
-                    QueryFilter::only_dynamic().groups(
-                        CollisionGroups::new(Group::all(), Group::from_bits_retain(ACTOR_MASK))
-                    ),
-                    |entity| {
-                        let rb = rapier_context.collider_parent(entity).unwrap_or(entity);
-                        let Ok(actor_transform) = actor_q.get(rb) else {
-                            return true;
-                        };
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
 
-                        damage_ev.send(DamageEvent {
-                            target: entity,
-                            value: explosion.damage,
-                            knockback: explosion.force *
-                            (
-                                actor_transform.translation.xy() - transform.translation.xy()
-                            ).normalize(),
-                            ignore_iframes: false,
-                            play_sound: true,
-                        });
+/// Hands the pixels around the landing spot over to the fire subsystem, or dissolves them
+fn apply_impact(
+    impact: &Impact,
+    center: IVec2,
+    chunk_manager: &mut ChunkManager,
+    dirty_rects: &mut DirtyRects,
+    run_stats: &mut RunStats
+) {
+    let radius = match impact {
+        Impact::Extinguish(radius) | Impact::Ignite(radius) | Impact::Dissolve(radius) => *radius,
+    };
+    let clock = chunk_manager.clock();
 
-                        true
-                    }
-                );
+    for x in -radius as i32..=radius as i32 {
+        for y in -radius as i32..=radius as i32 {
+            let offset = ivec2(x, y);
+
+            if (offset.length_squared() as f32) > radius.powi(2) {
+                continue;
             }
 
-            if parameters.insert_on_contact {
-                for (position, object_pixel) in object.iterate_over_pixels(transform) {
-                    if object_pixel.is_none() {
+            let position = center + offset;
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            match impact {
+                Impact::Extinguish(_) => {
+                    let Some(fire_parameters) = pixel.fire_parameters.as_mut() else {
                         continue;
-                    }
+                    };
 
-                    let Some(world_pixel) = chunk_group.get_mut(
-                        position - chunk_group_position * CHUNK_SIZE
-                    ) else {
+                    fire_parameters.try_to_ignite = false;
+                    pixel.on_fire = false;
+                }
+                Impact::Ignite(_) => {
+                    let Some(fire_parameters) = pixel.fire_parameters.as_mut() else {
                         continue;
                     };
 
+                    fire_parameters.try_to_ignite = true;
+                }
+                Impact::Dissolve(_) => {
+                    if
+                        pixel.durability.is_none() ||
+                        !matches!(pixel.physics_type, PhysicsType::Static | PhysicsType::Powder)
                     {
-                        match world_pixel.physics_type {
-                            PhysicsType::Powder | PhysicsType::Liquid(_) | PhysicsType::Gas(..) => {
-                                let pixel = std::mem::take(world_pixel);
-
-                                commands.spawn(ParticleBundle {
-                                    sprite: SpriteBundle {
-                                        sprite: Sprite {
-                                            color: Color::rgba_u8(
-                                                pixel.color[0],
-                                                pixel.color[1],
-                                                pixel.color[2],
-                                                pixel.color[3]
-                                            ),
-                                            custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
-                                            ..Default::default()
-                                        },
-                                        transform: Transform::from_translation(
-                                            (position.as_vec2() / (CHUNK_SIZE as f32)).extend(
-                                                PARTICLE_Z
-                                            )
-                                        ),
-                                        ..Default::default()
-                                    },
-                                    velocity: Velocity::linear(
-                                        Vec2::new(
-                                            fastrand::f32() - 0.5,
-                                            fastrand::f32() / 2.0 + 0.5
-                                        ) / (CHUNK_SIZE as f32)
-                                    ),
-                                    particle: Particle::new(pixel),
-                                    ..Default::default()
-                                });
-                            }
-                            PhysicsType::Static | PhysicsType::Rigidbody { .. } => {
-                                continue;
-                            }
-                            _ => {}
-                        }
+                        continue;
+                    }
 
-                        *world_pixel = object_pixel.clone().unwrap();
+                    *pixel = Pixel::default().with_clock(clock);
+                    run_stats.pixels_destroyed += 1;
 
-                        dirty_rects_resource.request_update(position);
-                        dirty_rects_resource.request_render(position);
-                    }
+                    dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
                 }
             }
 
-            commands.entity(entity).despawn_recursive();
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+        }
+    }
+}
+
+/// Breaks terrain pixels in the radius of every explosion and pushes enemies caught in it away,
+/// enemies further out hear it and come to check
+#[allow(clippy::too_many_arguments)]
+pub fn process_explosions(
+    mut explosion_ev: EventReader<ExplosionEvent>,
+    mut damage_ev: EventWriter<DamageEvent>,
+    mut noise_ev: EventWriter<NoiseEvent>,
+    mut stain_ev: EventWriter<StainEvent>,
+    mut dirty_rects_resource: ResMut<DirtyRects>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    actor_q: Query<&Transform, With<Enemy>>,
+    rapier_context: Res<RapierContext>,
+    mut run_stats: ResMut<RunStats>,
+    fragile_terrain: Option<Res<FragileTerrain>>
+) {
+    let terrain_scale = fragile_terrain.map_or(1.0, |fragile| fragile.damage_scale);
+
+    for ExplosionEvent { position, parameters: explosion } in explosion_ev.read() {
+        let global_position = (*position * (CHUNK_SIZE as f32)).as_ivec2();
+        let clock = chunk_manager.clock();
+
+        noise_ev.send(NoiseEvent {
+            position: *position * (CHUNK_SIZE as f32),
+            radius: explosion.radius * EXPLOSION_NOISE_SCALE,
+        });
+
+        // pixels that survive the blast are scorched, a bit past its edge as well
+        stain_ev.send(StainEvent {
+            position: *position * (CHUNK_SIZE as f32),
+            radius: explosion.radius * SCORCH_SCALE,
+            kind: StainKind::Scorch,
+        });
+
+        for x in -explosion.radius as i32..=explosion.radius as i32 {
+            for y in -explosion.radius as i32..=explosion.radius as i32 {
+                let offset = ivec2(x, y);
+
+                if (offset.length_squared() as f32) > explosion.radius.powi(2) {
+                    continue;
+                }
+
+                let Ok(pixel) = chunk_manager.get_mut(global_position + offset) else {
+                    continue;
+                };
+
+                if let Some(durability) = &mut pixel.durability {
+                    *durability -= explosion.damage * terrain_scale;
+                    if *durability <= 0.0 {
+                        *pixel = Pixel::default().with_clock(clock);
+                        run_stats.pixels_destroyed += 1;
+
+                        dirty_rects_resource.collider.insert(
+                            (global_position + offset).div_euclid(IVec2::splat(CHUNK_SIZE))
+                        );
+                    }
+                }
+
+                dirty_rects_resource.request_update(global_position + offset);
+                dirty_rects_resource.request_render(global_position + offset);
+            }
         }
+
+        rapier_context.intersections_with_shape(
+            *position,
+            0.0,
+            &Collider::ball(explosion.radius / (CHUNK_SIZE as f32)),
+            QueryFilter::only_dynamic().groups(
+                CollisionGroups::new(Group::all(), Group::from_bits_retain(ACTOR_MASK))
+            ),
+            |entity| {
+                let rb = rapier_context.collider_parent(entity).unwrap_or(entity);
+                let Ok(actor_transform) = actor_q.get(rb) else {
+                    return true;
+                };
+
+                damage_ev.send(DamageEvent {
+                    target: rb,
+                    value: explosion.damage,
+                    knockback: explosion.force *
+                    (actor_transform.translation.xy() - *position).normalize_or_zero(),
+                    ignore_iframes: false,
+                    play_sound: true,
+                    cause: DamageCause::Explosion,
+                    damage_type: DamageType::Physical,
+                });
+
+                true
+            }
+        );
     }
 }
 
+/// Direction away from the terrain pixels around the projectile, zero if there are none
+fn terrain_normal(object: &Object, transform: &Transform, chunk_manager: &ChunkManager) -> Vec2 {
+    let center = (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2();
+    let radius = object.size.max_element() / 2 + 1;
+
+    (-radius..=radius)
+        .cartesian_product(-radius..=radius)
+        .map(|(x, y)| ivec2(x, y))
+        .filter(|offset| offset.length_squared() <= radius.pow(2))
+        .filter(|offset| {
+            chunk_manager
+                .get(center + *offset)
+                .is_ok_and(|pixel| {
+                    matches!(pixel.physics_type, PhysicsType::Static | PhysicsType::Powder)
+                })
+        })
+        .fold(Vec2::ZERO, |normal, offset| normal - offset.as_vec2())
+        .normalize_or_zero()
+}
+
 pub fn fill_objects(
-    mut commands: Commands,
+    mut particles: Particles,
     mut dirty_rects_resource: ResMut<DirtyRects>,
     mut chunk_manager: ResMut<ChunkManager>,
     mut object_q: Query<
@@ -546,7 +955,7 @@ pub fn fill_objects(
                 PhysicsType::Powder | PhysicsType::Liquid(_) | PhysicsType::Gas(..) => {
                     let pixel = std::mem::take(world_pixel);
 
-                    commands.spawn(ParticleBundle {
+                    particles.spawn(ParticleBundle {
                         sprite: SpriteBundle {
                             sprite: Sprite {
                                 color: Color::rgba_u8(
@@ -672,6 +1081,8 @@ pub fn object_collision_damage(
                     knockback: velocity.linvel / 2.0,
                     ignore_iframes: false,
                     play_sound: true,
+                    cause: DamageCause::Object,
+                    damage_type: DamageType::Physical,
                 });
 
                 velocity.linvel *= 0.8;
@@ -680,6 +1091,38 @@ pub fn object_collision_damage(
     }
 }
 
+/// Takes the pixels of the object back from the world and despawns it, the object is left as it was
+/// before it was placed, e.g. to be stored in the inventory
+pub fn remove_object(
+    commands: &mut Commands,
+    chunk_manager: &mut ChunkManager,
+    dirty_rects: &mut DirtyRects,
+    entity: Entity,
+    transform: &Transform,
+    object: &mut Object
+) {
+    if object.placed {
+        let (chunk_group_position, mut chunk_group) = object.create_chunk_group(transform, chunk_manager);
+
+        for (position, object_pixel) in object.iterate_over_pixels(transform) {
+            let Some(pixel) = chunk_group.get_mut(position - chunk_group_position * CHUNK_SIZE) else {
+                continue;
+            };
+
+            if pixel.physics_type == PhysicsType::Rigidbody(entity) {
+                *object_pixel = Some(mem::take(pixel).reset_physics());
+
+                dirty_rects.request_update(position);
+                dirty_rects.request_render(position);
+            }
+        }
+
+        object.placed = false;
+    }
+
+    commands.entity(entity).despawn();
+}
+
 pub fn get_object_by_click(
     mut commands: Commands,
     mut chunk_manager: ResMut<ChunkManager>,
@@ -689,6 +1132,7 @@ pub fn get_object_by_click(
     rapier_context: Res<RapierContext>,
     window_q: Query<(Entity, &Window), With<PrimaryWindow>>,
     camera_q: Query<(&Camera, &GlobalTransform), With<TrackingCamera>>,
+    world_target: Res<WorldRenderTarget>,
     mut object_q: Query<(&Transform, &mut Object)>,
     mut egui_context: EguiContexts
 ) {
@@ -706,7 +1150,7 @@ pub fn get_object_by_click(
     {
         if let Some(position) = window.cursor_position() {
             let point = camera
-                .viewport_to_world(camera_global_transform, position)
+                .viewport_to_world(camera_global_transform, world_target.viewport_position(window, position))
                 .map(|ray| ray.origin.truncate())
                 .unwrap();
 
@@ -715,36 +1159,16 @@ pub fn get_object_by_click(
                     return true;
                 };
 
-                if let Some(result) = inventory.cells.iter_mut().find(|cell| cell.is_none()) {
-                    result.replace(Cell {
-                        id: Id::new(
-                            SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
-                        ),
-                        texture: None,
-                        object: object.clone(),
-                    });
-                    commands.entity(entity).despawn();
-                } else {
-                    return false;
-                }
-
-                let (chunk_group_position, mut chunk_group) = object.create_chunk_group(
-                    transform,
-                    &mut chunk_manager
-                );
-
-                for (position, _) in object.iterate_over_pixels(transform) {
-                    if
-                        let Some(pixel) = chunk_group.get_mut(
-                            position - chunk_group_position * CHUNK_SIZE
-                        )
-                    {
-                        if matches!(pixel.physics_type, PhysicsType::Rigidbody { .. }) {
-                            *pixel = Pixel::default();
-
-                            dirty_rects_resource.request_render(position);
-                        }
-                    }
+                if !inventory.is_full() {
+                    remove_object(
+                        &mut commands,
+                        &mut chunk_manager,
+                        &mut dirty_rects_resource,
+                        entity,
+                        transform,
+                        &mut object
+                    );
+                    inventory.store(object.clone());
                 }
 
                 false
@@ -1,7 +1,13 @@
 use std::mem;
 
 use async_channel::Sender;
-use bevy::{ prelude::*, render::view::RenderLayers, tasks::ComputeTaskPool, utils::HashMap };
+use bevy::{
+    ecs::system::{ EntityCommands, SystemParam },
+    prelude::*,
+    render::view::RenderLayers,
+    tasks::ComputeTaskPool,
+    utils::HashMap,
+};
 use bevy_math::ivec2;
 use bevy_rapier2d::dynamics::Velocity;
 use serde::{ Deserialize, Serialize };
@@ -22,7 +28,9 @@ use super::{
     pixel::Pixel,
 };
 
-#[derive(Bundle)]
+/// Particle to spawn with [`Particles::spawn`], only the color, size and transform of the sprite
+/// are kept since particles are drawn by the instanced
+/// [`ParticleRenderPlugin`](super::particle_render::ParticleRenderPlugin)
 pub struct ParticleBundle {
     pub sprite: SpriteBundle,
     pub velocity: Velocity,
@@ -45,13 +53,95 @@ impl Default for ParticleBundle {
     }
 }
 
+/// Components a particle entity is spawned with, it has no texture so the sprite renderer skips it
+#[derive(Bundle)]
+struct ParticleEntityBundle {
+    sprite: Sprite,
+    spatial: SpatialBundle,
+    velocity: Velocity,
+    movement: ParticleMovement,
+    state: ParticleObjectState,
+    particle: Particle,
+    render_layers: RenderLayers,
+}
+
+impl From<ParticleBundle> for ParticleEntityBundle {
+    fn from(bundle: ParticleBundle) -> Self {
+        Self {
+            sprite: bundle.sprite.sprite,
+            spatial: SpatialBundle {
+                visibility: bundle.sprite.visibility,
+                transform: bundle.sprite.transform,
+                ..Default::default()
+            },
+            velocity: bundle.velocity,
+            movement: bundle.movement,
+            state: bundle.state,
+            particle: bundle.particle,
+            render_layers: bundle.render_layers,
+        }
+    }
+}
+
 #[derive(Component, Default)]
 pub struct ParticleParent;
 
+// released particles past this many pooled ones are despawned instead
+const MAX_POOLED_PARTICLES: usize = 4096;
+
+/// Inactive particles kept hidden to be reused by the next spawns instead of being despawned
+#[derive(Resource, Default)]
+pub struct ParticlePool {
+    /// Particles that can be reused
+    free: Vec<Entity>,
+    /// Particles finished this frame, their deactivation is only applied with the commands
+    released: Vec<Entity>,
+}
+
+/// Spawns particles in place of pooled ones when there are any
+#[derive(SystemParam)]
+pub struct Particles<'w, 's> {
+    commands: Commands<'w, 's>,
+    pool: ResMut<'w, ParticlePool>,
+}
+
+impl<'w, 's> Particles<'w, 's> {
+    pub fn spawn(&mut self, bundle: ParticleBundle) -> EntityCommands<'_> {
+        let bundle = ParticleEntityBundle::from(bundle);
+
+        match self.pool.free.pop() {
+            Some(entity) => {
+                let mut entity_commands = self.commands.entity(entity);
+                entity_commands.insert(bundle);
+                entity_commands
+            }
+            None => self.commands.spawn(bundle),
+        }
+    }
+
+    /// Hides the particle and strips everything added on top of the bundle,
+    /// it is reused starting from the next frame unless the pool is already full
+    pub fn release(&mut self, entity: Entity) {
+        if self.pool.free.len() + self.pool.released.len() >= MAX_POOLED_PARTICLES {
+            self.commands.entity(entity).despawn_recursive();
+            return;
+        }
+
+        self.commands
+            .entity(entity)
+            .retain::<(ParticleEntityBundle, Parent)>()
+            .insert((Particle::default(), Visibility::Hidden));
+
+        self.pool.released.push(entity);
+    }
+}
+
 #[derive(Component, Reflect, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub enum ParticleMovement {
     Fall,
     Follow(Entity),
+    /// Speeds up towards the target from any distance, used by the vacuum
+    Pull(Entity),
 }
 
 #[derive(Component, Reflect, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
@@ -218,6 +308,17 @@ pub fn particle_setup(mut commands: Commands) {
     commands.spawn((Name::new("Particles"), SpatialBundle::INHERITED_IDENTITY, ParticleParent));
 }
 
+pub fn recycle_particles(mut pool: ResMut<ParticlePool>) {
+    let ParticlePool { free, released } = &mut *pool;
+    free.append(released);
+}
+
+/// Pooled particles are despawned along with the rest of the world
+pub fn clear_particle_pool(mut pool: ResMut<ParticlePool>) {
+    pool.free.clear();
+    pool.released.clear();
+}
+
 pub fn particle_set_parent(
     mut commands: Commands,
     particle_q: Query<Entity, Added<Particle>>,
@@ -230,6 +331,10 @@ pub fn particle_set_parent(
     }
 }
 
+// pixels per frame pulled particles gain every frame and the most they can reach
+const PULL_ACCELERATION: f32 = 0.25;
+const PULL_MAX_SPEED: f32 = 3.0;
+
 pub fn particle_modify_velocity(
     mut particle_q: Query<
         (&Particle, &Transform, &mut Velocity, &mut ParticleMovement),
@@ -270,12 +375,27 @@ pub fn particle_modify_velocity(
                     time.delta_seconds() *
                     25.0 * (fastrand::f32() * 0.05 + 0.95);
             }
+            ParticleMovement::Pull(target_entity) => {
+                let Ok(target_transform) = transform_q.get(target_entity) else {
+                    *movement = ParticleMovement::Fall;
+                    continue;
+                };
+
+                let direction = (
+                    target_transform.translation().xy() - transform.translation.xy()
+                ).normalize_or_zero();
+
+                velocity.linvel = (
+                    velocity.linvel +
+                    (direction * PULL_ACCELERATION * time.delta_seconds() * 25.0) / (CHUNK_SIZE as f32)
+                ).clamp_length_max(PULL_MAX_SPEED / (CHUNK_SIZE as f32));
+            }
         }
     }
 }
 
 pub fn particles_update(
-    mut commands: Commands,
+    mut particles_pool: Particles,
     mut chunk_manager: ResMut<ChunkManager>,
     mut dirty_rects_resource: ResMut<DirtyRects>,
     mut particles: Query<
@@ -288,11 +408,8 @@ pub fn particles_update(
             &mut ParticleMovement,
         )
     >,
-    transform_q: Query<&GlobalTransform, Without<Particle>>,
-    particles_instances: Query<Entity, With<ParticleParent>>
+    transform_q: Query<&GlobalTransform, Without<Particle>>
 ) {
-    let particles_instances = particles_instances.single();
-
     let DirtyRects { new: new_dirty_rects, render: render_rects, .. } = &mut *dirty_rects_resource;
 
     let (update_send, update_recv) = async_channel::unbounded::<UpdateMessage>();
@@ -326,8 +443,7 @@ pub fn particles_update(
 
         scope.spawn(async move {
             while let Ok(entity) = particle_recv.recv().await {
-                commands.entity(particles_instances).remove_children(&[entity]);
-                commands.entity(entity).despawn();
+                particles_pool.release(entity);
             }
         });
 
@@ -480,7 +596,8 @@ pub fn particles_update(
                                                         }
                                                     }
                                                 }
-                                                ParticleMovement::Follow(target_entity) => {
+                                                | ParticleMovement::Follow(target_entity)
+                                                | ParticleMovement::Pull(target_entity) => {
                                                     let Ok(target_transform) =
                                                         transform_q.get(target_entity) else {
                                                         *movement = ParticleMovement::Fall;
@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use bevy::{
+    core_pipeline::core_2d::Transparent2d,
+    ecs::system::{ lifetimeless::SRes, SystemParamItem },
+    prelude::*,
+    render::{
+        render_phase::{
+            AddRenderCommand,
+            DrawFunctions,
+            PhaseItem,
+            RenderCommand,
+            RenderCommandResult,
+            RenderPhase,
+            SetItemPipeline,
+            TrackedRenderPass,
+        },
+        render_resource::{
+            BindGroupLayout,
+            BlendState,
+            BufferUsages,
+            BufferVec,
+            ColorTargetState,
+            ColorWrites,
+            FragmentState,
+            MultisampleState,
+            PipelineCache,
+            PrimitiveState,
+            RenderPipelineDescriptor,
+            SpecializedRenderPipeline,
+            SpecializedRenderPipelines,
+            TextureFormat,
+            VertexBufferLayout,
+            VertexFormat,
+            VertexState,
+            VertexStepMode,
+        },
+        renderer::{ RenderDevice, RenderQueue },
+        texture::BevyDefault,
+        view::{ ExtractedView, ViewTarget, VisibleEntities },
+        Extract,
+        ExtractSchedule,
+        Render,
+        RenderApp,
+        RenderSet,
+    },
+    sprite::{ Mesh2dPipeline, SetMesh2dViewBindGroup },
+    utils::{ FloatOrd, HashMap },
+};
+
+use crate::constants::PARTICLE_Z;
+
+use super::particle::Particle;
+
+/// Draws every visible particle of a view with a single instanced draw call
+/// instead of going through the sprite renderer
+pub struct ParticleRenderPlugin;
+
+impl Plugin for ParticleRenderPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedParticles>()
+                .init_resource::<ParticleInstances>()
+                .init_resource::<SpecializedRenderPipelines<ParticlePipeline>>()
+                .add_render_command::<Transparent2d, DrawParticles>()
+                .add_systems(ExtractSchedule, extract_particles)
+                .add_systems(
+                    Render,
+                    (
+                        queue_particles.in_set(RenderSet::Queue),
+                        write_particle_instances.in_set(RenderSet::PrepareResources),
+                    )
+                );
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<ParticlePipeline>();
+        }
+    }
+}
+
+/// Center and size of the quad of a particle in world space followed by its linear color,
+/// read by the vertex shader once per instance
+type ParticleInstance = [Vec4; 2];
+
+#[derive(Resource, Default)]
+struct ExtractedParticles(HashMap<Entity, ParticleInstance>);
+
+/// Instances of every view, each view draws its own range of the buffer
+#[derive(Resource)]
+struct ParticleInstances {
+    buffer: BufferVec<ParticleInstance>,
+    ranges: HashMap<Entity, Range<u32>>,
+}
+
+impl Default for ParticleInstances {
+    fn default() -> Self {
+        Self {
+            buffer: BufferVec::new(BufferUsages::VERTEX),
+            ranges: HashMap::default(),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn extract_particles(
+    mut extracted: ResMut<ExtractedParticles>,
+    particle_q: Extract<Query<(Entity, &ViewVisibility, &Sprite, &GlobalTransform), With<Particle>>>
+) {
+    extracted.0.clear();
+
+    for (entity, visibility, sprite, transform) in particle_q.iter() {
+        if !visibility.get() {
+            continue;
+        }
+
+        let (scale, _, translation) = transform.to_scale_rotation_translation();
+
+        let size = sprite.custom_size.unwrap_or(Vec2::ONE) * scale.xy();
+
+        extracted.0.insert(entity, [
+            Vec4::new(translation.x, translation.y, size.x, size.y),
+            Vec4::from_array(sprite.color.as_linear_rgba_f32()),
+        ]);
+    }
+}
+
+#[derive(Resource)]
+struct ParticlePipeline {
+    view_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for ParticlePipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            view_layout: world.resource::<Mesh2dPipeline>().view_layout.clone(),
+            shader: world.resource::<AssetServer>().load("shaders/particle.wgsl"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ParticlePipelineKey {
+    hdr: bool,
+    samples: u32,
+}
+
+impl SpecializedRenderPipeline for ParticlePipeline {
+    type Key = ParticlePipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("particle_pipeline".into()),
+            layout: vec![self.view_layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![
+                    VertexBufferLayout::from_vertex_formats(VertexStepMode::Instance, [
+                        VertexFormat::Float32x4,
+                        VertexFormat::Float32x4,
+                    ])
+                ],
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: if key.hdr {
+                            ViewTarget::TEXTURE_FORMAT_HDR
+                        } else {
+                            TextureFormat::bevy_default()
+                        },
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_particles(
+    pipeline: Res<ParticlePipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<ParticlePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    msaa: Res<Msaa>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    extracted: Res<ExtractedParticles>,
+    mut instances: ResMut<ParticleInstances>,
+    mut view_q: Query<(Entity, &ExtractedView, &VisibleEntities, &mut RenderPhase<Transparent2d>)>
+) {
+    let ParticleInstances { buffer, ranges } = &mut *instances;
+    buffer.clear();
+    ranges.clear();
+
+    let draw_function = draw_functions.read().id::<DrawParticles>();
+
+    for (view, extracted_view, visible_entities, mut phase) in view_q.iter_mut() {
+        let start = buffer.len() as u32;
+
+        for entity in visible_entities.entities.iter() {
+            if let Some(instance) = extracted.0.get(entity) {
+                buffer.push(*instance);
+            }
+        }
+
+        let end = buffer.len() as u32;
+
+        if start == end {
+            continue;
+        }
+
+        ranges.insert(view, start..end);
+
+        let key = ParticlePipelineKey {
+            hdr: extracted_view.hdr,
+            samples: msaa.samples(),
+        };
+
+        phase.add(Transparent2d {
+            sort_key: FloatOrd(PARTICLE_Z),
+            entity: view,
+            pipeline: pipelines.specialize(&pipeline_cache, &pipeline, key),
+            draw_function,
+            batch_range: 0..1,
+            dynamic_offset: None,
+        });
+    }
+}
+
+fn write_particle_instances(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut instances: ResMut<ParticleInstances>
+) {
+    instances.buffer.write_buffer(&render_device, &render_queue);
+}
+
+type DrawParticles = (SetItemPipeline, SetMesh2dViewBindGroup<0>, DrawParticleInstances);
+
+/// Draws the quad once per particle of the view's range of the instance buffer
+struct DrawParticleInstances;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawParticleInstances {
+    type Param = SRes<ParticleInstances>;
+    type ViewQuery = Entity;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        view: Entity,
+        _entity: Option<()>,
+        instances: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>
+    ) -> RenderCommandResult {
+        let instances = instances.into_inner();
+        let (Some(buffer), Some(range)) = (instances.buffer.buffer(), instances.ranges.get(&view)) else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, buffer.slice(..));
+        pass.draw(0..6, range.clone());
+
+        RenderCommandResult::Success
+    }
+}
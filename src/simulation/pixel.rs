@@ -9,7 +9,11 @@ pub struct Pixel {
 
     pub durability: Option<f32>,
     pub color: [u8; 4],
+    /// Offset added to the color when rendered, left by footprints, blood and scorch marks
+    pub stain: [i8; 3],
     pub fire_parameters: Option<Fire>,
+    /// Pixels a plant is yet to grow into, the tip hands what is left over to the pixel it grows
+    pub growth: Option<u32>,
 
     pub updated_at: u8,
     pub on_fire: bool,
@@ -21,9 +25,11 @@ impl Default for Pixel {
             physics_type: PhysicsType::Air,
             material: Material::default(),
             fire_parameters: None,
+            growth: None,
             on_fire: false,
             updated_at: 0,
             color: [0; 4],
+            stain: [0; 3],
             durability: None,
         }
     }
@@ -53,6 +59,7 @@ impl From<Material> for Pixel {
             durability: val.durability.clone(),
             physics_type: val.physics_type.clone(),
             fire_parameters: val.fire.clone(),
+            growth: val.growth.as_ref().map(|growth| growth.max_size.saturating_sub(1)),
             material: val,
 
             ..Default::default()
@@ -75,6 +82,7 @@ impl From<&Material> for Pixel {
             color: color_offseted,
             physics_type: val.physics_type.clone(),
             fire_parameters: val.fire.clone(),
+            growth: val.growth.as_ref().map(|growth| growth.max_size.saturating_sub(1)),
             material: val.clone(),
 
             ..Default::default()
@@ -84,13 +92,32 @@ impl From<&Material> for Pixel {
 
 impl Pixel {
     pub fn get_color(&self) -> [u8; 4] {
+        let mut color = self.color;
+
+        for (channel, offset) in color.iter_mut().zip(self.stain) {
+            *channel = channel.saturating_add_signed(offset);
+        }
+
         match self.physics_type {
             PhysicsType::Liquid { .. } =>
-                self.color.map(|channel| channel.saturating_add(fastrand::u8(0..10))),
+                color.map(|channel| channel.saturating_add(fastrand::u8(0..10))),
             PhysicsType::Gas(..) =>
-                self.color.map(|channel| channel.saturating_add(fastrand::u8(0..50))),
-            _ => self.color,
+                color.map(|channel| channel.saturating_add(fastrand::u8(0..50))),
+            _ => color,
+        }
+    }
+
+    /// Moves the stain towards the given one by the share of the difference,
+    /// returns whether it changed so only those pixels are redrawn
+    pub fn apply_stain(&mut self, stain: [i8; 3], strength: f32) -> bool {
+        let previous = self.stain;
+
+        for (current, target) in self.stain.iter_mut().zip(stain) {
+            let difference = ((target as f32) - (*current as f32)) * strength.clamp(0.0, 1.0);
+            *current = ((*current as f32) + difference).round() as i8;
         }
+
+        self.stain != previous
     }
 
     pub fn with_clock(self, updated_at: u8) -> Self {
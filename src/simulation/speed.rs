@@ -0,0 +1,145 @@
+use bevy::{ ecs::schedule::ScheduleLabel, prelude::* };
+use bevy_rapier2d::plugin::{ RapierConfiguration, TimestepMode };
+
+use crate::notifications::Notification;
+
+use super::SIMULATION_STEP;
+
+const SLOW_MOTION: f32 = 0.5;
+const FAST_FORWARD: f32 = 4.0;
+// max_dt rapier uses by default, scaled along with the time
+const PHYSICS_MAX_DT: f32 = 1.0 / 60.0;
+
+/// Particle updates followed by a pass over the chunks if one is due,
+/// run as many times per frame as the simulation speed asks for
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimulationTick;
+
+/// Rate of the falling sand simulation and physics relative to the frame rate
+#[derive(Resource)]
+pub struct SimulationSpeed {
+    pub paused: bool,
+    pub scale: f32,
+    /// Runs a single tick with a pass over the chunks while paused
+    pub step: bool,
+    /// Scaled frames that are yet to be simulated
+    accumulator: f32,
+    timer: Timer,
+    pass_due: bool,
+    /// Physics is let through for a single fixed update after a step
+    physics_step: bool,
+}
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            scale: 1.0,
+            step: false,
+            accumulator: 0.0,
+            timer: Timer::new(SIMULATION_STEP, TimerMode::Repeating),
+            pass_due: false,
+            physics_step: false,
+        }
+    }
+}
+
+impl SimulationSpeed {
+    fn label(&self) -> String {
+        if self.paused {
+            String::from("Simulation paused")
+        } else {
+            format!("Simulation speed {}x", self.scale)
+        }
+    }
+}
+
+/// F7 pauses, F8 steps while paused, F9 and F10 toggle slow motion and fast forward
+pub fn control_simulation_speed(
+    mut speed: ResMut<SimulationSpeed>,
+    mut notifications: EventWriter<Notification>,
+    keys: Res<ButtonInput<KeyCode>>
+) {
+    if keys.just_pressed(KeyCode::F7) {
+        speed.paused = !speed.paused;
+    } else if keys.just_pressed(KeyCode::F8) {
+        speed.step = speed.paused;
+        return;
+    } else if keys.just_pressed(KeyCode::F9) {
+        speed.scale = if speed.scale == SLOW_MOTION { 1.0 } else { SLOW_MOTION };
+    } else if keys.just_pressed(KeyCode::F10) {
+        speed.scale = if speed.scale == FAST_FORWARD { 1.0 } else { FAST_FORWARD };
+    } else {
+        return;
+    }
+
+    notifications.send(Notification::new(speed.label()));
+}
+
+/// Every tick advances the simulation by the length of the frame, so at normal speed
+/// there is one tick per frame and a pass over the chunks whenever the step has passed,
+/// a step also turns on the physics pipeline until the next fixed update has run it
+pub fn run_simulation_ticks(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+    let mut speed = world.resource_mut::<SimulationSpeed>();
+
+    let stepping = std::mem::take(&mut speed.step);
+    let ticks = if stepping {
+        speed.physics_step = true;
+        1
+    } else if speed.paused {
+        0
+    } else {
+        speed.accumulator += speed.scale;
+        let ticks = speed.accumulator.floor();
+        speed.accumulator -= ticks;
+        ticks as u32
+    };
+
+    if stepping {
+        world.resource_mut::<RapierConfiguration>().physics_pipeline_active = true;
+    }
+
+    for _ in 0..ticks {
+        let mut speed = world.resource_mut::<SimulationSpeed>();
+        speed.timer.tick(delta);
+        speed.pass_due = stepping || speed.timer.just_finished();
+
+        world.run_schedule(SimulationTick);
+    }
+}
+
+pub fn simulation_pass_due(speed: Res<SimulationSpeed>) -> bool {
+    speed.pass_due
+}
+
+/// Rapier steps with the fixed timestep scaled by the speed, split into substeps when
+/// sped up so bodies stay as stable as at normal speed
+pub fn apply_physics_speed(
+    speed: Res<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>
+) {
+    let active = !speed.paused || speed.physics_step;
+    let timestep_mode = TimestepMode::Variable {
+        max_dt: PHYSICS_MAX_DT * speed.scale.max(1.0),
+        time_scale: speed.scale,
+        substeps: speed.scale.ceil().max(1.0) as usize,
+    };
+
+    if rapier_config.physics_pipeline_active != active || rapier_config.timestep_mode != timestep_mode {
+        rapier_config.physics_pipeline_active = active;
+        rapier_config.timestep_mode = timestep_mode;
+    }
+}
+
+/// A physics step that was let through while paused only lasts a single fixed update,
+/// which has to have stepped rapier before the pipeline is paused again
+pub fn pause_physics_after_step(
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>
+) {
+    if speed.physics_step && rapier_config.physics_pipeline_active {
+        speed.physics_step = false;
+        rapier_config.physics_pipeline_active = !speed.paused;
+    }
+}
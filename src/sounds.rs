@@ -0,0 +1,224 @@
+use bevy::{ audio::{ PlaybackMode, Volume }, prelude::*, utils::HashMap };
+use bevy_math::ivec2;
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    actors::{ actor::{ Actor, ActorFlags }, player::Player },
+    assets::AudioAssetCollection,
+    constants::CHUNK_SIZE,
+    settings::AudioBus,
+    simulation::{ chunk_manager::ChunkManager, materials::{ Material, PhysicsType }, object::Object },
+    state::GameState,
+};
+
+// pixels walked between two footsteps
+const STEP_DISTANCE: f32 = 10.0;
+const STEP_VOLUME: f32 = 0.25;
+// falling speed in pixels per frame that is heard on landing, and the one that is heard at full volume
+const LANDING_SPEED: f32 = 2.5;
+const LANDING_FULL_SPEED: f32 = 8.0;
+// change of speed in pixels per second that is heard as an impact, and the one that is heard at full volume
+const IMPACT_SPEED: f32 = 60.0;
+const IMPACT_FULL_SPEED: f32 = 600.0;
+// limits how many surface sounds are played at the same time
+const MAX_SOUNDS: usize = 8;
+
+pub struct SoundsPlugin;
+
+impl Plugin for SoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (add_footsteps, add_impact_trackers, play_footsteps, play_impacts).run_if(
+                in_state(GameState::Game)
+            )
+        );
+    }
+}
+
+/// Marks sounds spawned by this module so they can be counted
+#[derive(Component)]
+pub struct SurfaceSFX;
+
+#[derive(Component, Default)]
+pub struct Footsteps {
+    /// Pixels walked since the last step was played
+    pub distance: f32,
+    pub grounded: bool,
+    /// Vertical speed of the last frame in the air
+    pub fall_speed: f32,
+}
+
+#[derive(Component)]
+pub struct ImpactTracker {
+    pub material: Option<Material>,
+    pub last_velocity: Vec2,
+}
+
+/// Sound configured for the material, falls back to the collect sounds for powders and liquids
+pub fn surface_sound(
+    material: &Material,
+    audio_assets: &AudioAssetCollection
+) -> Option<Handle<AudioSource>> {
+    if let Some(path) = &material.sound {
+        return audio_assets.surface.get(path).cloned();
+    }
+
+    match material.physics_type {
+        PhysicsType::Powder => fastrand::choice(audio_assets.powder.values()).cloned(),
+        PhysicsType::Liquid(_) => fastrand::choice(audio_assets.liquid.values()).cloned(),
+        _ => None,
+    }
+}
+
+fn spawn_surface_sound(
+    commands: &mut Commands,
+    source: Handle<AudioSource>,
+    translation: Vec3,
+    volume: f32
+) {
+    commands.spawn((
+        SurfaceSFX,
+        AudioBus::Sfx,
+        TransformBundle::from_transform(Transform::from_translation(translation)),
+        AudioBundle {
+            source,
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                spatial: true,
+                volume: Volume::new(volume),
+                speed: fastrand::f32() * 0.4 + 0.8,
+                ..Default::default()
+            },
+        },
+    ));
+}
+
+fn add_footsteps(mut commands: Commands, player_q: Query<Entity, Added<Player>>) {
+    for entity in player_q.iter() {
+        commands.entity(entity).insert(Footsteps::default());
+    }
+}
+
+/// Objects are sounded by the material most of their pixels are made of
+fn add_impact_trackers(
+    mut commands: Commands,
+    object_q: Query<(Entity, &Object, &Velocity), Without<ImpactTracker>>
+) {
+    for (entity, object, velocity) in object_q.iter() {
+        let mut counts: HashMap<&str, (usize, &Material)> = HashMap::new();
+
+        for pixel in object.pixels.iter().flatten() {
+            counts.entry(&pixel.material.id).or_insert((0, &pixel.material)).0 += 1;
+        }
+
+        commands.entity(entity).insert(ImpactTracker {
+            material: counts
+                .into_values()
+                .max_by_key(|(count, _)| *count)
+                .map(|(_, material)| material.clone()),
+            last_velocity: velocity.linvel,
+        });
+    }
+}
+
+/// Shallow liquid is heard over the ground below it
+fn footstep_surface<'a>(actor: &Actor, chunk_manager: &'a ChunkManager) -> Option<&'a Material> {
+    let position = actor.position.round().as_ivec2();
+    let center = ivec2(position.x + (actor.size.x as i32) / 2, position.y);
+
+    if let Ok(pixel) = chunk_manager.get(center) {
+        if matches!(pixel.physics_type, PhysicsType::Liquid(_)) {
+            return Some(&pixel.material);
+        }
+    }
+
+    (0..actor.size.x as i32)
+        .map(|x| ivec2(position.x + x, position.y - 1))
+        .filter_map(|position| chunk_manager.get(position).ok())
+        .find(|pixel| !pixel.is_empty())
+        .map(|pixel| &pixel.material)
+}
+
+fn play_footsteps(
+    mut commands: Commands,
+    mut player_q: Query<(&Transform, &Actor, &Velocity, &mut Footsteps)>,
+    sound_q: Query<(), With<SurfaceSFX>>,
+    chunk_manager: Res<ChunkManager>,
+    audio_assets: Res<AudioAssetCollection>,
+    time: Res<Time>
+) {
+    let mut playing = sound_q.iter().len();
+
+    for (transform, actor, velocity, mut footsteps) in player_q.iter_mut() {
+        let grounded = actor.flags.contains(ActorFlags::GROUNDED);
+        let wading =
+            actor.flags.contains(ActorFlags::SUBMERGED) && !actor.flags.contains(ActorFlags::SWIMMING);
+
+        let mut volume = None;
+
+        if grounded && !footsteps.grounded && -footsteps.fall_speed > LANDING_SPEED {
+            volume = Some((-footsteps.fall_speed / LANDING_FULL_SPEED).min(1.0));
+            footsteps.distance = 0.0;
+        } else if grounded || wading {
+            footsteps.distance += velocity.linvel.x.abs() * time.delta_seconds() * 60.0;
+
+            if footsteps.distance >= STEP_DISTANCE {
+                volume = Some(STEP_VOLUME);
+                footsteps.distance = 0.0;
+            }
+        }
+
+        footsteps.grounded = grounded;
+        footsteps.fall_speed = if grounded { 0.0 } else { velocity.linvel.y };
+
+        let Some(volume) = volume else {
+            continue;
+        };
+
+        if playing >= MAX_SOUNDS {
+            continue;
+        }
+
+        let Some(source) = footstep_surface(actor, &chunk_manager).and_then(|material| {
+            surface_sound(material, &audio_assets)
+        }) else {
+            continue;
+        };
+
+        spawn_surface_sound(&mut commands, source, transform.translation, volume);
+        playing += 1;
+    }
+}
+
+fn play_impacts(
+    mut commands: Commands,
+    mut object_q: Query<(&Transform, &Velocity, &mut ImpactTracker)>,
+    sound_q: Query<(), With<SurfaceSFX>>,
+    audio_assets: Res<AudioAssetCollection>
+) {
+    let mut playing = sound_q.iter().len();
+
+    for (transform, velocity, mut tracker) in object_q.iter_mut() {
+        let change = (tracker.last_velocity - velocity.linvel).length() * (CHUNK_SIZE as f32);
+        tracker.last_velocity = velocity.linvel;
+
+        if change < IMPACT_SPEED || playing >= MAX_SOUNDS {
+            continue;
+        }
+
+        let Some(source) = tracker.material
+            .as_ref()
+            .and_then(|material| surface_sound(material, &audio_assets)) else {
+            continue;
+        };
+
+        spawn_surface_sound(
+            &mut commands,
+            source,
+            transform.translation,
+            (change / IMPACT_FULL_SPEED).min(1.0)
+        );
+        playing += 1;
+    }
+}
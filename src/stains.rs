@@ -0,0 +1,144 @@
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_math::ivec2;
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    actors::actor::{ Actor, ActorFlags },
+    simulation::{ chunk_manager::ChunkManager, dirty_rect::DirtyRects, materials::PhysicsType },
+    state::GameState,
+};
+
+// distance in pixels walked between two footprints
+const FOOTPRINT_STRIDE: i32 = 4;
+// actors moving slower than this in pixels per second do not leave footprints
+const FOOTPRINT_SPEED: f32 = 8.0;
+
+pub struct StainsPlugin;
+
+impl Plugin for StainsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<StainEvent>().add_systems(
+            Update,
+            (leave_footprints, process_stains).chain().run_if(in_state(GameState::Game))
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StainKind {
+    /// Only marks materials tagged as soft
+    Footprint,
+    Blood,
+    Scorch,
+}
+
+impl StainKind {
+    /// Color offset the stained pixels approach
+    fn offset(self) -> [i8; 3] {
+        match self {
+            StainKind::Footprint => [-25, -25, -20],
+            StainKind::Blood => [30, -50, -50],
+            StainKind::Scorch => [-70, -70, -70],
+        }
+    }
+
+    /// Share of the offset applied in the center of the stain, it fades out towards the edge
+    fn strength(self) -> f32 {
+        match self {
+            StainKind::Footprint => 1.0,
+            StainKind::Blood => 0.8,
+            StainKind::Scorch => 0.9,
+        }
+    }
+
+    /// Chance of every pixel in the radius to be stained, so splatters are not solid circles
+    fn coverage(self) -> f32 {
+        match self {
+            StainKind::Footprint => 1.0,
+            StainKind::Blood => 0.6,
+            StainKind::Scorch => 0.85,
+        }
+    }
+}
+
+/// Tints terrain pixels around the position without touching their material,
+/// position and radius are in pixels
+#[derive(Event, Clone)]
+pub struct StainEvent {
+    pub position: Vec2,
+    pub radius: f32,
+    pub kind: StainKind,
+}
+
+/// Grounded actors leave a mark under their feet every few pixels they walk
+fn leave_footprints(
+    actor_q: Query<(Entity, &Actor, &Velocity)>,
+    mut stain_ev: EventWriter<StainEvent>,
+    mut last_footprints: Local<HashMap<Entity, i32>>
+) {
+    last_footprints.retain(|entity, _| actor_q.contains(*entity));
+
+    for (entity, actor, velocity) in actor_q.iter() {
+        if !actor.flags.contains(ActorFlags::GROUNDED) || velocity.linvel.x.abs() < FOOTPRINT_SPEED {
+            continue;
+        }
+
+        let foot = ivec2(
+            (actor.position.x + actor.size.x / 2.0).round() as i32,
+            actor.position.y.round() as i32 - 1
+        );
+
+        if last_footprints.get(&entity).is_some_and(|last| (foot.x - last).abs() < FOOTPRINT_STRIDE) {
+            continue;
+        }
+
+        last_footprints.insert(entity, foot.x);
+
+        stain_ev.send(StainEvent {
+            position: foot.as_vec2(),
+            radius: 1.0,
+            kind: StainKind::Footprint,
+        });
+    }
+}
+
+/// Only solid and powder pixels are stained and only the changed ones are redrawn,
+/// the simulation itself is never woken up by stains
+fn process_stains(
+    mut stain_ev: EventReader<StainEvent>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>
+) {
+    for StainEvent { position, radius, kind } in stain_ev.read() {
+        let center = position.round().as_ivec2();
+        let extent = radius.ceil() as i32;
+
+        for x in -extent..=extent {
+            for y in -extent..=extent {
+                let distance = ivec2(x, y).as_vec2().length();
+
+                if distance > *radius || fastrand::f32() > kind.coverage() {
+                    continue;
+                }
+
+                let Ok(pixel) = chunk_manager.get_mut(center + ivec2(x, y)) else {
+                    continue;
+                };
+
+                if !matches!(pixel.physics_type, PhysicsType::Static | PhysicsType::Powder) {
+                    continue;
+                }
+
+                if *kind == StainKind::Footprint && !pixel.material.tags.contains("soft") {
+                    continue;
+                }
+
+                let strength = kind.strength() * (1.0 - distance / (radius + 1.0));
+
+                if pixel.apply_stain(kind.offset(), strength) {
+                    dirty_rects.request_render(center + ivec2(x, y));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use bevy::prelude::*;
+use bevy_math::vec2;
+use bevy_persistent::Persistent;
+use bevy_rapier2d::dynamics::Velocity;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::player::{
+        InventoryParameters,
+        Player,
+        PlayerActions,
+        PlayerMaterials,
+        PlayerSelectedMaterial,
+    },
+    constants::{ CHUNK_SIZE, PARTICLE_Z },
+    despawn_component,
+    localization::Locale,
+    registries::Registries,
+    settings::Config,
+    simulation::{ particle::{ Particle, ParticleBundle, Particles }, pixel::Pixel },
+    state::GameState,
+};
+
+// amounts of materials are counted in units of this many pixels
+const PIXELS_PER_UNIT: f32 = 16.0;
+// larger overflows spill over several frames
+const SPILL_PER_FRAME: u32 = 16;
+// speed in pixels per frame spilled pixels burst out of the player with
+const SPILL_SPEED: f32 = 1.5;
+const PANEL_BACKGROUND: Color = Color::rgba(0.1, 0.1, 0.1, 0.85);
+const SELECTED_COLOR: Color = Color::rgb(0.949, 0.945, 0.639);
+const SWATCH_SIZE: f32 = 12.0;
+
+/// Panel listing every carried material, collapsed to its title until the storage action is used
+pub struct StoragePlugin;
+
+impl Plugin for StoragePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StoragePanel>()
+            .add_systems(OnEnter(GameState::Game), setup_storage_panel)
+            .add_systems(OnExit(GameState::Game), despawn_component::<UiStoragePanel>)
+            .add_systems(
+                Update,
+                (spill_overflowing_materials, toggle_storage_panel, update_storage_panel)
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Expanded state of the panel, kept between levels
+#[derive(Resource, Default)]
+pub struct StoragePanel {
+    pub expanded: bool,
+}
+
+#[derive(Component)]
+pub struct UiStoragePanel;
+
+#[derive(Component)]
+pub struct UiStorageTitle;
+
+#[derive(Component)]
+pub struct UiStorageRows;
+
+/// Materials over their capacity spill out of a player as falling pixels
+fn spill_overflowing_materials(
+    mut particles: Particles,
+    player_q: Query<(&Transform, &InventoryParameters), With<Player>>,
+    mut player_materials: ResMut<PlayerMaterials>,
+    registries: Res<Registries>
+) {
+    // inventory is shared, so the roomiest storage among players counts
+    let Some(max_storage) = player_q
+        .iter()
+        .map(|(_, inventory)| inventory.max_storage)
+        .reduce(f32::max) else {
+        return;
+    };
+
+    let Some((transform, _)) = player_q.iter().next() else {
+        return;
+    };
+
+    let overflowing = player_materials
+        .iter()
+        .filter_map(|(id, amount)| {
+            let material = registries.materials.get(id)?;
+            let excess = ((amount - material.capacity(max_storage)) * PIXELS_PER_UNIT).floor();
+
+            (excess >= 1.0).then(|| (material, (excess as u32).min(SPILL_PER_FRAME)))
+        })
+        .collect_vec();
+
+    for (material, count) in overflowing {
+        if let Some(amount) = player_materials.get_mut(&material.id) {
+            *amount -= (count as f32) / PIXELS_PER_UNIT;
+        }
+
+        for _ in 0..count {
+            let pixel = Pixel::from(material);
+            let velocity = vec2(fastrand::f32() - 0.5, fastrand::f32() * 0.5 + 0.5) * SPILL_SPEED;
+
+            particles.spawn(ParticleBundle {
+                sprite: SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba_u8(
+                            pixel.color[0],
+                            pixel.color[1],
+                            pixel.color[2],
+                            pixel.color[3]
+                        ),
+                        custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(
+                        transform.translation.xy().extend(PARTICLE_Z)
+                    ),
+                    ..Default::default()
+                },
+                velocity: Velocity::linear(velocity / (CHUNK_SIZE as f32)),
+                particle: Particle::new(pixel),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+fn setup_storage_panel(mut commands: Commands, locale: Res<Locale>, panel: Res<StoragePanel>) {
+    commands
+        .spawn((
+            Name::new("Storage panel"),
+            UiStoragePanel,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: PANEL_BACKGROUND.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                UiStorageTitle,
+                TextBundle::from_section(locale.get("storage.title"), TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                }),
+            ));
+
+            parent.spawn((
+                UiStorageRows,
+                NodeBundle {
+                    style: Style {
+                        display: if panel.expanded { Display::Flex } else { Display::None },
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn toggle_storage_panel(
+    player_q: Query<&ActionState<PlayerActions>, With<Player>>,
+    mut panel: ResMut<StoragePanel>,
+    mut rows_q: Query<&mut Style, With<UiStorageRows>>
+) {
+    if !player_q.iter().any(|action_state| action_state.just_pressed(&PlayerActions::Storage)) {
+        return;
+    }
+
+    panel.expanded = !panel.expanded;
+
+    for mut style in rows_q.iter_mut() {
+        style.display = if panel.expanded { Display::Flex } else { Display::None };
+    }
+}
+
+/// Rows are rebuilt whenever the carried materials change, the selected one is highlighted
+#[allow(clippy::too_many_arguments)]
+fn update_storage_panel(
+    mut commands: Commands,
+    player_q: Query<&InventoryParameters, With<Player>>,
+    player_materials: Res<PlayerMaterials>,
+    selected_material: Res<PlayerSelectedMaterial>,
+    panel: Res<StoragePanel>,
+    mut title_q: Query<&mut Text, With<UiStorageTitle>>,
+    rows_q: Query<Entity, With<UiStorageRows>>,
+    registries: Res<Registries>,
+    locale: Res<Locale>,
+    config: Res<Persistent<Config>>
+) {
+    let (Ok(mut title), Ok(rows)) = (title_q.get_single_mut(), rows_q.get_single()) else {
+        return;
+    };
+
+    if
+        !player_materials.is_changed() &&
+        !selected_material.is_changed() &&
+        !panel.is_changed() &&
+        !title.is_added()
+    {
+        return;
+    }
+
+    let Some(max_storage) = player_q
+        .iter()
+        .map(|inventory| inventory.max_storage)
+        .reduce(f32::max) else {
+        return;
+    };
+
+    let carried = player_materials
+        .iter()
+        .filter(|(_, amount)| **amount >= 1.0 / PIXELS_PER_UNIT)
+        .filter_map(|(id, amount)| registries.materials.get(id).map(|material| (material, *amount)))
+        .collect_vec();
+
+    title.sections[0].value = if panel.expanded {
+        locale.get("storage.title").to_string()
+    } else {
+        let binding = config.binding(PlayerActions::Storage).describe();
+        locale.format("storage.collapsed", &[&carried.len(), &binding])
+    };
+
+    commands.entity(rows).despawn_descendants();
+
+    if !panel.expanded {
+        return;
+    }
+
+    commands.entity(rows).with_children(|parent| {
+        for (material, amount) in carried {
+            let text_color = if material.id == selected_material.0 { SELECTED_COLOR } else { Color::WHITE };
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(SWATCH_SIZE),
+                            height: Val::Px(SWATCH_SIZE),
+                            ..default()
+                        },
+                        background_color: Color::rgba_u8(
+                            material.color[0],
+                            material.color[1],
+                            material.color[2],
+                            material.color[3]
+                        ).into(),
+                        ..default()
+                    });
+
+                    parent.spawn(
+                        TextBundle::from_section(
+                            format!(
+                                "{} {:.0}/{:.0}",
+                                material.ui_name,
+                                amount,
+                                material.capacity(max_storage)
+                            ),
+                            TextStyle {
+                                font_size: 16.0,
+                                color: text_color,
+                                ..default()
+                            }
+                        )
+                    );
+                });
+        }
+    });
+}
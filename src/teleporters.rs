@@ -0,0 +1,300 @@
+use bevy::{ audio::{ PlaybackMode, Volume }, prelude::*, render::view::RenderLayers, sprite::Anchor };
+use bevy_math::{ ivec2, vec2 };
+use bevy_rapier2d::prelude::*;
+use itertools::Itertools;
+use leafwing_input_manager::action_state::ActionState;
+
+use crate::{
+    actors::{ actor::Actor, player::{ CoopPlayer, Player, PlayerActions } },
+    assets::AudioAssetCollection,
+    bucket::{ interact_with_buckets, CarriedBy },
+    camera::{ TrackingCamera, ACTOR_RENDER_LAYER },
+    constants::{ CHUNK_SIZE, DECORATION_Z, PARTICLE_Z },
+    despawn_component,
+    object_inventory::store_objects,
+    registries::Registries,
+    settings::AudioBus,
+    simulation::{
+        chunk_manager::ChunkManager,
+        object::{ fill_objects, unfill_objects, Object },
+        particle::{ Particle, ParticleBundle, Particles },
+        pixel::Pixel,
+    },
+    state::GameState,
+    traps::find_surface,
+    vehicle::{ PilotedBy, Piloting },
+};
+
+// sizes are in pixels
+const TELEPORTER_SIZE: Vec2 = Vec2::new(10.0, 16.0);
+const TELEPORTER_COLOR: Color = Color::rgba(0.45, 0.85, 1.0, 0.6);
+// objects and particles this close to the middle of a teleporter are taken along
+const TRANSFER_RADIUS: f32 = 24.0;
+const TELEPORT_COOLDOWN: f32 = 1.5;
+const EFFECT_MATERIAL: &str = "shimmer";
+const EFFECT_PARTICLES: u32 = 24;
+
+pub struct TeleportersPlugin;
+
+impl Plugin for TeleportersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TeleporterRegistry>()
+            .init_resource::<PendingTransfers>()
+            .add_systems(OnEnter(GameState::LevelInitialization), (
+                despawn_component::<Teleporter>,
+                |mut registry: ResMut<TeleporterRegistry>, mut transfers: ResMut<PendingTransfers>| {
+                    registry.pairs.clear();
+                    transfers.0.clear();
+                },
+            ))
+            .add_systems(OnExit(GameState::GameOver), despawn_component::<Teleporter>)
+            .add_systems(
+                Update,
+                (
+                    tick_teleport_cooldowns,
+                    use_teleporters.after(interact_with_buckets).before(store_objects),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                FixedUpdate,
+                transfer_nearby
+                    .after(unfill_objects)
+                    .before(PhysicsSet::SyncBackend)
+                    .before(fill_objects)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Teleporters of the current level, pairs of the bottom centers of the twins in pixels
+#[derive(Resource, Default)]
+pub struct TeleporterRegistry {
+    pub pairs: Vec<[IVec2; 2]>,
+}
+
+impl TeleporterRegistry {
+    /// Whether a teleporter at the position would be far enough from every other one
+    /// to not take their objects along
+    pub fn is_clear(&self, position: IVec2) -> bool {
+        self.pairs
+            .iter()
+            .flatten()
+            .all(|other| other.as_vec2().distance(position.as_vec2()) >= TRANSFER_RADIUS * 2.0)
+    }
+}
+
+/// Pad that moves a player using it to its twin, positions are the bottom centers in pixels
+#[derive(Component)]
+pub struct Teleporter {
+    pub position: IVec2,
+    pub twin: IVec2,
+}
+
+/// Teleporters do nothing for the player until it runs out, so pressing the interaction
+/// a few times in a row does not bounce the player back and forth
+#[derive(Component, Deref, DerefMut)]
+pub struct TeleportCooldown(pub Timer);
+
+/// Surroundings of used teleporters that are moved on the next physics step,
+/// objects can not be moved while their pixels are in the world
+#[derive(Resource, Default)]
+struct PendingTransfers(Vec<Transfer>);
+
+/// Values are in pixels
+struct Transfer {
+    from: Vec2,
+    offset: Vec2,
+}
+
+/// Bottom center of a teleporter standing on the floor under the pixel position,
+/// none if there is no room for it there
+pub fn find_teleporter_floor(chunk_manager: &ChunkManager, position: IVec2) -> Option<IVec2> {
+    if !chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) {
+        return None;
+    }
+
+    let floor = find_surface(chunk_manager, position, -1)?;
+    let size = TELEPORTER_SIZE.as_ivec2();
+    let min = ivec2(position.x - size.x / 2, floor + 1);
+
+    let on_floor = (0..size.x).all(|x| {
+        chunk_manager.get(min + ivec2(x, -1)).is_ok_and(|pixel| !pixel.is_empty())
+    });
+    let has_room = (0..size.x)
+        .cartesian_product(0..size.y)
+        .all(|(x, y)| chunk_manager.get(min + ivec2(x, y)).is_ok_and(|pixel| pixel.is_empty()));
+
+    (on_floor && has_room).then_some(ivec2(position.x, floor + 1))
+}
+
+/// Spawns both teleporters of the pair and adds it to the registry
+pub fn spawn_teleporters(commands: &mut Commands, registry: &mut TeleporterRegistry, pair: [IVec2; 2]) {
+    for (position, twin) in [(pair[0], pair[1]), (pair[1], pair[0])] {
+        commands.spawn((
+            Name::new("Teleporter"),
+            Teleporter { position, twin },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: TELEPORTER_COLOR,
+                    custom_size: Some(TELEPORTER_SIZE / (CHUNK_SIZE as f32)),
+                    anchor: Anchor::BottomCenter,
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(
+                    (position.as_vec2() / (CHUNK_SIZE as f32)).extend(DECORATION_Z)
+                ),
+                ..Default::default()
+            },
+            RenderLayers::layer(ACTOR_RENDER_LAYER),
+        ));
+    }
+
+    registry.pairs.push(pair);
+}
+
+fn tick_teleport_cooldowns(
+    mut commands: Commands,
+    mut cooldown_q: Query<(Entity, &mut TeleportCooldown)>,
+    time: Res<Time>
+) {
+    for (entity, mut cooldown) in cooldown_q.iter_mut() {
+        if cooldown.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<TeleportCooldown>();
+        }
+    }
+}
+
+/// Interaction on a teleporter moves the player to its twin, along with everything lying around
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn use_teleporters(
+    mut commands: Commands,
+    mut player_q: Query<
+        (Entity, &mut Actor, &mut Velocity, &mut ActionState<PlayerActions>, Has<CoopPlayer>),
+        (With<Player>, Without<TeleportCooldown>, Without<Piloting>)
+    >,
+    teleporter_q: Query<&Teleporter>,
+    mut camera_q: Query<&mut TrackingCamera>,
+    mut transfers: ResMut<PendingTransfers>,
+    mut particles: Particles,
+    audio_assets: Res<AudioAssetCollection>,
+    registries: Res<Registries>
+) {
+    for (entity, mut actor, mut velocity, mut action_state, is_coop) in player_q.iter_mut() {
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        let feet = actor.position + vec2(actor.size.x / 2.0, 0.0);
+
+        let Some(teleporter) = teleporter_q.iter().find(|teleporter| {
+            let offset = feet - teleporter.position.as_vec2();
+
+            offset.x.abs() < TELEPORTER_SIZE.x / 2.0 && offset.y > -2.0 && offset.y < TELEPORTER_SIZE.y
+        }) else {
+            continue;
+        };
+
+        action_state.consume(&PlayerActions::Interaction);
+
+        let offset = (teleporter.twin - teleporter.position).as_vec2();
+
+        actor.position += offset;
+        velocity.linvel = Vec2::ZERO;
+
+        commands
+            .entity(entity)
+            .insert(TeleportCooldown(Timer::from_seconds(TELEPORT_COOLDOWN, TimerMode::Once)));
+
+        // camera jumps along instead of gliding over the whole level
+        if !is_coop {
+            if let Ok(mut camera) = camera_q.get_single_mut() {
+                camera.set_position((actor.position + actor.size / 2.0) / (CHUNK_SIZE as f32));
+            }
+        }
+
+        transfers.0.push(Transfer {
+            from: teleporter.position.as_vec2() + vec2(0.0, TELEPORTER_SIZE.y / 2.0),
+            offset,
+        });
+
+        // sparks are only spawned at the twin, the ones at the teleporter would be taken along
+        if let Some(material) = registries.materials.get(EFFECT_MATERIAL) {
+            for _ in 0..EFFECT_PARTICLES {
+                let pixel = Pixel::from(material);
+                let offset = vec2(
+                    (fastrand::f32() - 0.5) * TELEPORTER_SIZE.x,
+                    fastrand::f32() * TELEPORTER_SIZE.y
+                );
+
+                particles.spawn(ParticleBundle {
+                    sprite: SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgba_u8(
+                                pixel.color[0],
+                                pixel.color[1],
+                                pixel.color[2],
+                                pixel.color[3]
+                            ),
+                            custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(
+                            ((teleporter.twin.as_vec2() + offset) / (CHUNK_SIZE as f32)).extend(PARTICLE_Z)
+                        ),
+                        ..Default::default()
+                    },
+                    velocity: Velocity::linear(
+                        vec2(fastrand::f32() - 0.5, fastrand::f32() + 0.5) / (CHUNK_SIZE as f32)
+                    ),
+                    particle: Particle::visual(pixel),
+                    ..Default::default()
+                });
+            }
+        }
+
+        commands.spawn((
+            TransformBundle::from_transform(
+                Transform::from_translation((teleporter.twin.as_vec2() / (CHUNK_SIZE as f32)).extend(0.0))
+            ),
+            AudioBus::Sfx,
+            AudioBundle {
+                source: audio_assets.perk.clone(),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    spatial: true,
+                    volume: Volume::new(0.5),
+                    ..Default::default()
+                },
+            },
+        ));
+    }
+}
+
+/// Moves loose objects and particles from around used teleporters to their twins,
+/// held buckets and driven vehicles follow their players on their own
+#[allow(clippy::type_complexity)]
+fn transfer_nearby(
+    mut transfers: ResMut<PendingTransfers>,
+    mut object_q: Query<&mut Transform, (With<Object>, Without<CarriedBy>, Without<PilotedBy>)>,
+    mut particle_q: Query<(&mut Transform, &Particle), Without<Object>>
+) {
+    for transfer in transfers.0.drain(..) {
+        let from = transfer.from / (CHUNK_SIZE as f32);
+        let offset = (transfer.offset / (CHUNK_SIZE as f32)).extend(0.0);
+        let radius = TRANSFER_RADIUS / (CHUNK_SIZE as f32);
+
+        for mut transform in object_q.iter_mut() {
+            if transform.translation.xy().distance(from) < radius {
+                transform.translation += offset;
+            }
+        }
+
+        for (mut transform, particle) in particle_q.iter_mut() {
+            if particle.active && transform.translation.xy().distance(from) < radius {
+                transform.translation += offset;
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    fs::{ self, File },
+    io::{ BufReader, BufWriter },
+    path::{ Path, PathBuf },
+    sync::{ Arc, Mutex },
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::{ RenderAssetUsages, RenderAssets },
+        render_resource::{
+            BufferDescriptor,
+            BufferUsages,
+            CommandEncoderDescriptor,
+            Extent3d,
+            ImageCopyBuffer,
+            ImageDataLayout,
+            Maintain,
+            MapMode,
+            TextureDimension,
+            TextureFormat,
+        },
+        renderer::{ RenderDevice, RenderQueue },
+        Render,
+        RenderApp,
+        RenderSet,
+    },
+    tasks::AsyncComputeTaskPool,
+    utils::HashMap,
+};
+use bevy_persistent::Persistent;
+use itertools::Itertools;
+
+use crate::{
+    camera::WorldRenderTarget,
+    score_export::{ record_run, RunStats },
+    settings::{ selected_profile_dir, Scoreboard },
+    state::GameState,
+};
+
+// height of thumbnails follows the aspect of the view
+const THUMBNAIL_WIDTH: u32 = 192;
+
+/// Small snapshot of the world at the end of every recorded run, kept as a png
+/// next to the scoreboard of the profile and named after the timestamp of the run
+pub struct ThumbnailsPlugin;
+
+impl Plugin for ThumbnailsPlugin {
+    fn build(&self, app: &mut App) {
+        let requests = ThumbnailRequests::default();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(requests.clone())
+                .add_systems(Render, read_back_thumbnails.in_set(RenderSet::Cleanup));
+        }
+
+        app.insert_resource(requests)
+            .init_resource::<Thumbnails>()
+            .add_systems(OnEnter(GameState::GameOver), request_thumbnail.after(record_run));
+    }
+}
+
+/// Loaded thumbnails by their path, missing ones are remembered as well
+#[derive(Resource, Default)]
+pub struct Thumbnails(HashMap<PathBuf, Option<Handle<Image>>>);
+
+impl Thumbnails {
+    /// Thumbnail of the run with the timestamp, read from the disk the first time it is asked for
+    pub fn get(&mut self, images: &mut Assets<Image>, timestamp: u64) -> Option<Handle<Image>> {
+        let path = thumbnail_path(timestamp);
+
+        self.0
+            .entry(path)
+            .or_insert_with_key(|path| load_thumbnail(path).map(|image| images.add(image)))
+            .clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Render targets waiting to be copied, shared between both worlds
+#[derive(Resource, Clone, Default)]
+struct ThumbnailRequests(Arc<Mutex<Vec<ThumbnailRequest>>>);
+
+struct ThumbnailRequest {
+    texture: Handle<Image>,
+    path: PathBuf,
+}
+
+fn thumbnail_dir() -> PathBuf {
+    selected_profile_dir().join("thumbnails")
+}
+
+pub fn thumbnail_path(timestamp: u64) -> PathBuf {
+    thumbnail_dir().join(format!("{}.png", timestamp))
+}
+
+/// Queues a copy of the world for the run that was just recorded,
+/// thumbnails of runs that dropped off the scoreboard are removed
+fn request_thumbnail(
+    stats: Res<RunStats>,
+    world_target: Res<WorldRenderTarget>,
+    requests: Res<ThumbnailRequests>,
+    scoreboard: Res<Persistent<Scoreboard>>
+) {
+    let Some(timestamp) = stats.recorded_at else {
+        return;
+    };
+
+    prune_thumbnails(&thumbnail_dir(), &scoreboard);
+
+    requests.0.lock().unwrap().push(ThumbnailRequest {
+        texture: world_target.texture.clone(),
+        path: thumbnail_path(timestamp),
+    });
+}
+
+fn prune_thumbnails(dir: &Path, scoreboard: &Scoreboard) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let timestamp = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok());
+
+        let recorded = timestamp.is_some_and(|timestamp| {
+            scoreboard.runs.iter().any(|record| record.timestamp == timestamp)
+        });
+
+        if !recorded {
+            if let Err(error) = fs::remove_file(&path) {
+                warn!("{}: failed to remove thumbnail: {}", path.display(), error);
+            }
+        }
+    }
+}
+
+/// Copies requested targets into buffers once the frame is rendered, the buffers are mapped
+/// and written to the disk off the render thread while the device is polled every frame
+fn read_back_thumbnails(
+    requests: Res<ThumbnailRequests>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>
+) {
+    render_device.poll(Maintain::Poll);
+
+    let requests = std::mem::take(&mut *requests.0.lock().unwrap());
+
+    for request in requests {
+        let Some(image) = gpu_images.get(&request.texture) else {
+            continue;
+        };
+
+        let size = image.size.as_uvec2();
+        let row_size = (size.x * 4) as usize;
+        let padded_row_size = RenderDevice::align_copy_bytes_per_row(row_size);
+
+        let buffer = render_device.create_buffer(
+            &(BufferDescriptor {
+                label: Some("thumbnail_readback_buffer"),
+                size: (padded_row_size * (size.y as usize)) as u64,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        );
+
+        let mut encoder = render_device.create_command_encoder(
+            &(CommandEncoderDescriptor {
+                label: Some("thumbnail_readback_encoder"),
+            })
+        );
+
+        encoder.copy_texture_to_buffer(
+            image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_size as u32),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            }
+        );
+
+        render_queue.submit([encoder.finish()]);
+
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let slice = buffer.slice(..);
+                let (sender, receiver) = async_channel::bounded(1);
+
+                slice.map_async(MapMode::Read, move |result| {
+                    let _ = sender.try_send(result);
+                });
+
+                if !matches!(receiver.recv().await, Ok(Ok(()))) {
+                    error!("{}: failed to read the view back", request.path.display());
+                    return;
+                }
+
+                let pixels = slice
+                    .get_mapped_range()
+                    .chunks_exact(padded_row_size)
+                    .flat_map(|row| row[..row_size].iter().copied())
+                    .collect_vec();
+
+                buffer.unmap();
+
+                if let Err(error) = write_thumbnail(&request.path, &pixels, size) {
+                    error!("{}: failed to save thumbnail: {}", request.path.display(), error);
+                }
+            })
+            .detach();
+    }
+}
+
+/// Averages blocks of the view down to the thumbnail size and saves it as a png
+fn write_thumbnail(path: &Path, pixels: &[u8], size: UVec2) -> Result<(), String> {
+    let width = THUMBNAIL_WIDTH.min(size.x).max(1);
+    let height = ((size.y * width) / size.x).max(1);
+
+    let mut thumbnail = Vec::with_capacity((width * height * 4) as usize);
+
+    for (y, x) in (0..height).cartesian_product(0..width) {
+        let min = UVec2::new((x * size.x) / width, (y * size.y) / height);
+        let max = UVec2::new(((x + 1) * size.x) / width, ((y + 1) * size.y) / height).max(min + 1);
+
+        let mut sum = [0u32; 3];
+        for (source_y, source_x) in (min.y..max.y).cartesian_product(min.x..max.x) {
+            let index = ((source_y * size.x + source_x) * 4) as usize;
+            for (sum, value) in sum.iter_mut().zip(&pixels[index..index + 3]) {
+                *sum += *value as u32;
+            }
+        }
+
+        let size = max - min;
+        let count = size.x * size.y;
+        thumbnail.extend(sum.map(|channel| (channel / count) as u8));
+        thumbnail.push(255);
+    }
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+    }
+
+    let file = File::create(path).map_err(|error| error.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&thumbnail).map_err(|error| error.to_string())?;
+    writer.finish().map_err(|error| error.to_string())
+}
+
+fn load_thumbnail(path: &Path) -> Option<Image> {
+    let file = File::open(path).ok()?;
+    let mut reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).ok()?;
+
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+
+    data.truncate(info.buffer_size());
+
+    Some(
+        Image::new(
+            Extent3d {
+                width: info.width,
+                height: info.height,
+                ..Default::default()
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD
+        )
+    )
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    path::Path,
+    sync::{ atomic::{ AtomicBool, AtomicU64, Ordering }, Mutex, MutexGuard, PoisonError },
+    time::{ Duration, Instant, SystemTime, UNIX_EPOCH },
+};
+
+use bevy::{
+    log::{ tracing_subscriber::{ layer::{ Context, SubscriberExt }, Layer }, BoxedSubscriber },
+    prelude::*,
+    utils::{ tracing::{ span, Subscriber }, HashMap },
+};
+use serde::Serialize;
+
+use crate::{ notifications::{ Notification, NotificationKind }, settings::config_dir };
+
+// spans that ended longer ago than this are dropped from the recording
+const TRACE_WINDOW: Duration = Duration::from_secs(10);
+
+// spans are only recorded between two presses of F6, the recorder is not locked otherwise
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+pub struct TracePlugin;
+
+impl Plugin for TracePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, dump_trace);
+    }
+}
+
+/// Passed to the log plugin, keeps the spans of the last seconds around for [`dump_trace`] while recording
+pub fn record_spans(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    Box::new(subscriber.with(TraceLayer))
+}
+
+struct OpenSpan {
+    name: &'static str,
+    entered: Option<Instant>,
+}
+
+struct TraceSpan {
+    name: &'static str,
+    thread: u64,
+    start: Instant,
+    duration: Duration,
+}
+
+struct Recorder {
+    origin: Instant,
+    open: HashMap<span::Id, OpenSpan>,
+    spans: VecDeque<TraceSpan>,
+}
+
+fn lock_recorder() -> MutexGuard<'static, Option<Recorder>> {
+    RECORDER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn with_recorder(f: impl FnOnce(&mut Recorder)) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut recorder = lock_recorder();
+
+    f(
+        recorder.get_or_insert_with(|| Recorder {
+            origin: Instant::now(),
+            open: HashMap::default(),
+            spans: VecDeque::new(),
+        })
+    );
+}
+
+struct TraceLayer;
+
+impl<S: Subscriber> Layer<S> for TraceLayer {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, _ctx: Context<'_, S>) {
+        with_recorder(|recorder| {
+            recorder.open.insert(id.clone(), OpenSpan {
+                name: attrs.metadata().name(),
+                entered: None,
+            });
+        });
+    }
+
+    fn on_enter(&self, id: &span::Id, _ctx: Context<'_, S>) {
+        with_recorder(|recorder| {
+            if let Some(span) = recorder.open.get_mut(id) {
+                span.entered = Some(Instant::now());
+            }
+        });
+    }
+
+    fn on_exit(&self, id: &span::Id, _ctx: Context<'_, S>) {
+        let now = Instant::now();
+
+        with_recorder(|recorder| {
+            let Some(span) = recorder.open.get_mut(id) else {
+                return;
+            };
+            let Some(start) = span.entered.take() else {
+                return;
+            };
+
+            recorder.spans.push_back(TraceSpan {
+                name: span.name,
+                thread: THREAD.with(|thread| *thread),
+                start,
+                duration: now - start,
+            });
+
+            while
+                recorder.spans
+                    .front()
+                    .is_some_and(|span| now - (span.start + span.duration) > TRACE_WINDOW)
+            {
+                recorder.spans.pop_front();
+            }
+        });
+    }
+
+    fn on_close(&self, id: span::Id, _ctx: Context<'_, S>) {
+        with_recorder(|recorder| {
+            recorder.open.remove(&id);
+        });
+    }
+}
+
+/// Complete event of the Chrome trace format, times are in microseconds
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChromeTrace {
+    trace_events: Vec<TraceEvent>,
+}
+
+/// Writes the recorded spans as a trace `chrome://tracing` and Perfetto can open,
+/// returns the amount of spans in it
+fn write_trace(path: &Path, recorder: &Recorder) -> Result<usize, String> {
+    let trace = ChromeTrace {
+        trace_events: recorder.spans
+            .iter()
+            .map(|span| TraceEvent {
+                name: span.name,
+                ph: "X",
+                ts: (span.start - recorder.origin).as_secs_f64() * 1e6,
+                dur: span.duration.as_secs_f64() * 1e6,
+                pid: 1,
+                tid: span.thread,
+            })
+            .collect(),
+    };
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+    }
+
+    let contents = serde_json::to_string(&trace).map_err(|error| error.to_string())?;
+    fs::write(path, contents).map_err(|error| error.to_string())?;
+
+    Ok(trace.trace_events.len())
+}
+
+/// Starts recording spans on F6, the next press saves the spans of the last seconds
+/// to the config directory and stops the recording
+pub fn dump_trace(keys: Res<ButtonInput<KeyCode>>, mut notifications: EventWriter<Notification>) {
+    if !keys.just_pressed(KeyCode::F6) {
+        return;
+    }
+
+    if !RECORDING.load(Ordering::Relaxed) {
+        *lock_recorder() = None;
+        RECORDING.store(true, Ordering::Relaxed);
+        notifications.send(Notification::new("Recording trace, press F6 again to save it"));
+        return;
+    }
+
+    RECORDING.store(false, Ordering::Relaxed);
+    let Some(recorder) = lock_recorder().take() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let path = config_dir().join("traces").join(format!("trace-{}.json", timestamp));
+
+    notifications.send(match write_trace(&path, &recorder) {
+        Ok(count) =>
+            Notification::new(
+                format!("Trace of {} spans saved to {}", count, path.display())
+            ).with_kind(NotificationKind::Success),
+        Err(error) =>
+            Notification::new(
+                format!("Failed to save trace to {}: {}", path.display(), error)
+            ).with_kind(NotificationKind::Error),
+    });
+}
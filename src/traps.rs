@@ -0,0 +1,271 @@
+use bevy::{ prelude::*, render::view::RenderLayers, sprite::Anchor };
+use bevy_math::ivec2;
+use itertools::Itertools;
+
+use crate::{
+    actors::{ actor::Actor, effects::Death, health::{ DamageCause, DamageEvent, DamageType, Health }, player::Player },
+    camera::ACTOR_RENDER_LAYER,
+    constants::{ CHUNK_SIZE, DECORATION_Z },
+    despawn_component,
+    generation::level::TrapKind,
+    registries::Registries,
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        materials::PhysicsType,
+        pixel::Pixel,
+    },
+    state::GameState,
+};
+
+// distance in pixels searched from a random point to the floor or ceiling a trap is placed on
+const TRAP_SCAN_DISTANCE: i32 = 48;
+const SPIKE_HEIGHT: i32 = 3;
+const SPIKE_COLOR: Color = Color::rgb(0.6, 0.6, 0.65);
+const SPIKE_KNOCKBACK: Vec2 = Vec2::new(0.0, 2.0);
+// players this far below an unstable ceiling bring it down
+const COLLAPSE_DISTANCE: f32 = 64.0;
+
+pub struct TrapsPlugin;
+
+impl Plugin for TrapsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::LevelInitialization), despawn_component::<Trap>)
+            .add_systems(OnExit(GameState::GameOver), despawn_component::<Trap>)
+            .add_systems(
+                Update,
+                (spike_damage, collapse_ceilings).run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Marks every entity spawned by traps, removed along with the level
+#[derive(Component)]
+pub struct Trap;
+
+/// Area is in pixels, starting from the bottom left corner
+#[derive(Component)]
+pub struct Spikes {
+    pub damage: f32,
+    pub min: Vec2,
+    pub size: Vec2,
+}
+
+/// Rectangle of static pixels that is replaced with the material once triggered
+#[derive(Component)]
+pub struct UnstableCeiling {
+    pub min: IVec2,
+    pub size: IVec2,
+    pub material: String,
+}
+
+/// Places a trap on the floor or ceiling closest to the pixel position,
+/// returns false if there is no room for it there
+pub fn place_trap(
+    commands: &mut Commands,
+    kind: &TrapKind,
+    position: IVec2,
+    chunk_manager: &mut ChunkManager,
+    dirty_rects: &mut DirtyRects,
+    registries: &Registries
+) -> bool {
+    if !chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) {
+        return false;
+    }
+
+    match kind {
+        TrapKind::Spikes { width, damage } => {
+            let Some(floor) = find_surface(chunk_manager, position, -1) else {
+                return false;
+            };
+            let min = ivec2(position.x - width / 2, floor + 1);
+
+            if
+                !is_filled(chunk_manager, min - IVec2::Y, ivec2(*width, 1), |pixel| !pixel.is_empty()) ||
+                !is_filled(chunk_manager, min, ivec2(*width, SPIKE_HEIGHT), Pixel::is_empty)
+            {
+                return false;
+            }
+
+            let size = ivec2(*width, SPIKE_HEIGHT).as_vec2();
+
+            commands.spawn((
+                Name::new("Spikes"),
+                Trap,
+                Spikes {
+                    damage: *damage,
+                    min: min.as_vec2(),
+                    size,
+                },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: SPIKE_COLOR,
+                        custom_size: Some(size / (CHUNK_SIZE as f32)),
+                        anchor: Anchor::BottomLeft,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(
+                        (min.as_vec2() / (CHUNK_SIZE as f32)).extend(DECORATION_Z)
+                    ),
+                    ..Default::default()
+                },
+                RenderLayers::layer(ACTOR_RENDER_LAYER),
+            ));
+        }
+        TrapKind::UnstableCeiling { width, height, material_id } => {
+            let Some(ceiling) = find_surface(chunk_manager, position, 1) else {
+                return false;
+            };
+            let min = ivec2(position.x - width / 2, ceiling);
+            let size = ivec2(*width, *height);
+
+            if
+                !is_filled(chunk_manager, min - IVec2::Y, ivec2(*width, 1), Pixel::is_empty) ||
+                !is_filled(chunk_manager, min, size, |pixel| pixel.physics_type == PhysicsType::Static)
+            {
+                return false;
+            }
+
+            commands.spawn((
+                Name::new("Unstable ceiling"),
+                Trap,
+                UnstableCeiling {
+                    min,
+                    size,
+                    material: material_id.clone(),
+                },
+            ));
+        }
+        TrapKind::AcidPool { width, depth, material_id } => {
+            let Some(material) = registries.materials.get(material_id) else {
+                return false;
+            };
+            let Some(floor) = find_surface(chunk_manager, position, -1) else {
+                return false;
+            };
+            let min = ivec2(position.x - width / 2, floor - depth + 1);
+            let size = ivec2(*width, *depth);
+
+            // pit is walled on the sides and the bottom, so the liquid stays in
+            if
+                !is_filled(chunk_manager, min - IVec2::ONE, size + ivec2(2, 1), |pixel| {
+                    !pixel.is_empty()
+                }) ||
+                !is_filled(chunk_manager, ivec2(min.x, floor + 1), ivec2(*width, 1), Pixel::is_empty)
+            {
+                return false;
+            }
+
+            for (x, y) in (0..size.x).cartesian_product(0..size.y) {
+                let position = min + ivec2(x, y);
+
+                if chunk_manager.set(position, Pixel::from(material)).is_ok() {
+                    dirty_rects.request_update(position);
+                    dirty_rects.request_render(position);
+                    dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// Height of the first non-empty pixel above or below the position, depending on the direction
+pub fn find_surface(chunk_manager: &ChunkManager, position: IVec2, direction: i32) -> Option<i32> {
+    (1..=TRAP_SCAN_DISTANCE)
+        .map(|distance| position.y + distance * direction)
+        .find(|y| !chunk_manager.get(ivec2(position.x, *y)).is_ok_and(|pixel| pixel.is_empty()))
+}
+
+fn is_filled<F: Fn(&Pixel) -> bool>(
+    chunk_manager: &ChunkManager,
+    min: IVec2,
+    size: IVec2,
+    predicate: F
+) -> bool {
+    (0..size.x)
+        .cartesian_product(0..size.y)
+        .all(|(x, y)| chunk_manager.get(min + ivec2(x, y)).is_ok_and(&predicate))
+}
+
+/// Hurts every actor standing in the spikes, iframes keep the damage from repeating every frame
+#[allow(clippy::type_complexity)]
+fn spike_damage(
+    spike_q: Query<&Spikes>,
+    actor_q: Query<(Entity, &Actor), (With<Health>, Without<Death>)>,
+    mut damage_ev: EventWriter<DamageEvent>
+) {
+    for spikes in spike_q.iter() {
+        let max = spikes.min + spikes.size;
+
+        for (entity, actor) in actor_q.iter() {
+            let overlaps =
+                actor.position.cmplt(max).all() && (actor.position + actor.size).cmpgt(spikes.min).all();
+
+            if !overlaps {
+                continue;
+            }
+
+            damage_ev.send(DamageEvent {
+                target: entity,
+                value: spikes.damage,
+                knockback: SPIKE_KNOCKBACK,
+                ignore_iframes: false,
+                play_sound: true,
+                cause: DamageCause::Hazard("spikes"),
+                damage_type: DamageType::Physical,
+            });
+        }
+    }
+}
+
+/// Turns the static pixels of a ceiling into its material as soon as a player walks below it
+fn collapse_ceilings(
+    mut commands: Commands,
+    ceiling_q: Query<(Entity, &UnstableCeiling)>,
+    player_q: Query<&Actor, With<Player>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    registries: Res<Registries>
+) {
+    for (entity, ceiling) in ceiling_q.iter() {
+        let min = ceiling.min.as_vec2();
+        let max = (ceiling.min + ceiling.size).as_vec2();
+
+        let triggered = player_q.iter().any(|actor| {
+            let center = actor.position.x + actor.size.x / 2.0;
+            let top = actor.position.y + actor.size.y;
+
+            (min.x..max.x).contains(&center) && (min.y - COLLAPSE_DISTANCE..min.y).contains(&top)
+        });
+
+        if !triggered {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+
+        let Some(material) = registries.materials.get(&ceiling.material) else {
+            continue;
+        };
+
+        for (x, y) in (0..ceiling.size.x).cartesian_product(0..ceiling.size.y) {
+            let position = ceiling.min + ivec2(x, y);
+
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            if pixel.physics_type != PhysicsType::Static {
+                continue;
+            }
+
+            *pixel = Pixel::from(material);
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use bevy_persistent::Persistent;
+use leafwing_input_manager::action_state::ActionState;
+use serde::Deserialize;
+
+use crate::{
+    actors::{ actor::Actor, player::{ Player, PlayerActions } },
+    constants::CHUNK_SIZE,
+    curses::apply_curses,
+    cutscene::ActiveCutscene,
+    daily::RunMode,
+    despawn_component,
+    generation::{ level::Level, next_level, LevelCounter },
+    gui::Score,
+    localization::Locale,
+    registries::Registries,
+    remove_respurce,
+    score_export::RunStats,
+    settings::{ Config, Profile },
+    state::GameState,
+};
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Setup), (
+            remove_respurce::<TutorialProgress>,
+            start_tutorial.run_if(is_tutorial_run),
+        ).chain())
+            .add_systems(
+                OnEnter(GameState::LevelInitialization),
+                finish_tutorial
+                    .before(next_level)
+                    .before(apply_curses)
+                    .run_if(resource_exists::<TutorialProgress>.and_then(is_tutorial_run))
+            )
+            .add_systems(OnEnter(GameState::Game), spawn_tutorial_hint.run_if(resource_exists::<TutorialProgress>))
+            .add_systems(OnExit(GameState::Game), despawn_component::<UiTutorialHint>)
+            .add_systems(OnExit(GameState::GameOver), remove_respurce::<TutorialProgress>)
+            .add_systems(
+                Update,
+                (track_tutorial, update_tutorial_hint, skip_tutorial)
+                    .chain()
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<TutorialProgress>))
+            );
+    }
+}
+
+/// Enemy spawned once its hint is shown for the first time, position is in pixels
+#[derive(Deserialize, Clone)]
+pub struct TutorialEnemy {
+    pub enemy_id: String,
+    pub position: [i32; 2],
+}
+
+/// Prompt shown while a player is inside its area, until the action is used while it is shown
+#[derive(Deserialize, Clone)]
+pub struct TutorialHint {
+    /// Key of the prompt in the language files, `{}` is replaced by the binding of the action
+    pub text: String,
+    /// Corners of the area in pixels
+    pub min: [i32; 2],
+    pub max: [i32; 2],
+    pub action: PlayerActions,
+    /// Hint is only shown once the action of another hint was used
+    #[serde(default)]
+    pub after: Option<PlayerActions>,
+    #[serde(default)]
+    pub enemy: Option<TutorialEnemy>,
+}
+
+impl TutorialHint {
+    fn area(&self) -> Rect {
+        Rect::new(
+            self.min[0] as f32,
+            self.min[1] as f32,
+            self.max[0] as f32,
+            self.max[1] as f32
+        )
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Tutorial {
+    pub level: Level,
+    /// Earlier hints are preferred when areas overlap
+    pub hints: Vec<TutorialHint>,
+}
+
+pub fn load_tutorial() -> Tutorial {
+    ron::de::from_str::<Tutorial>(&std::fs::read_to_string("tutorial.ron").unwrap()).unwrap()
+}
+
+/// Present while the tutorial level is played, the exit stays closed until every hint is done
+#[derive(Resource, Default)]
+pub struct TutorialProgress {
+    pub done: Vec<PlayerActions>,
+    /// Index of the hint that is shown
+    pub current: Option<usize>,
+    pub spawned: Vec<usize>,
+    pub finished: bool,
+}
+
+#[derive(Component)]
+struct UiTutorialHint;
+
+pub fn is_tutorial_run(run_mode: Res<RunMode>) -> bool {
+    *run_mode == RunMode::Tutorial
+}
+
+fn start_tutorial(mut commands: Commands) {
+    commands.insert_resource(TutorialProgress::default());
+}
+
+/// The run goes on from the first level once the tutorial is left, without anything gained in it
+fn finish_tutorial(
+    mut commands: Commands,
+    mut run_mode: ResMut<RunMode>,
+    mut counter: ResMut<LevelCounter>,
+    mut profile: ResMut<Persistent<Profile>>
+) {
+    // the tutorial level itself is initialized first
+    if counter.0 == 0 {
+        return;
+    }
+
+    commands.remove_resource::<TutorialProgress>();
+    commands.insert_resource(RunStats::default());
+    commands.insert_resource(Score::default());
+
+    *run_mode = RunMode::Normal;
+    counter.0 = 0;
+
+    if !profile.skip_tutorial {
+        profile.skip_tutorial = true;
+        profile.persist().expect("failed to update profile");
+    }
+}
+
+fn spawn_tutorial_hint(mut commands: Commands, locale: Res<Locale>, config: Res<Persistent<Config>>) {
+    let skip_binding = config.binding(PlayerActions::SkipTutorial).describe();
+
+    commands
+        .spawn((
+            UiTutorialHint,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(96.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section("", TextStyle {
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                }).with_text_justify(JustifyText::Center)
+            );
+            parent.spawn(
+                TextBundle::from_section(locale.format("tutorial.skip", &[&skip_binding]), TextStyle {
+                    font_size: 16.0,
+                    color: Color::GRAY,
+                    ..Default::default()
+                })
+            );
+        });
+}
+
+/// Picks the hint for the area the players are in and marks its action as done once it is used
+fn track_tutorial(
+    mut commands: Commands,
+    mut progress: ResMut<TutorialProgress>,
+    player_q: Query<(&Actor, &ActionState<PlayerActions>), With<Player>>,
+    registries: Res<Registries>
+) {
+    let hints = &registries.tutorial.hints;
+
+    if let Some(index) = progress.current {
+        let action = hints[index].action;
+
+        if player_q.iter().any(|(_, action_state)| action_state.pressed(&action)) {
+            progress.done.push(action);
+        }
+    }
+
+    let current = hints.iter().position(|hint| {
+        !progress.done.contains(&hint.action) &&
+            hint.after.is_none_or(|after| progress.done.contains(&after)) &&
+            player_q
+                .iter()
+                .any(|(actor, _)| hint.area().contains(actor.position + actor.size / 2.0))
+    });
+
+    if let Some(index) = current.filter(|index| !progress.spawned.contains(index)) {
+        progress.spawned.push(index);
+
+        if let Some(enemy) = &hints[index].enemy {
+            match registries.enemies.get(&enemy.enemy_id) {
+                Some(enemy_fn) => {
+                    let position = Vec2::new(enemy.position[0] as f32, enemy.position[1] as f32);
+                    let (enemy, enemy_hitbox) = enemy_fn(position / (CHUNK_SIZE as f32));
+
+                    commands.spawn(enemy).with_children(|parent| {
+                        parent.spawn(enemy_hitbox);
+                    });
+                }
+                None => warn!("unknown tutorial enemy: {}", enemy.enemy_id),
+            }
+        }
+    }
+
+    progress.current = current;
+    progress.finished = hints.iter().all(|hint| progress.done.contains(&hint.action));
+}
+
+fn update_tutorial_hint(
+    hint_q: Query<&Children, With<UiTutorialHint>>,
+    mut text_q: Query<&mut Text>,
+    progress: Res<TutorialProgress>,
+    registries: Res<Registries>,
+    locale: Res<Locale>,
+    config: Res<Persistent<Config>>
+) {
+    let Ok(children) = hint_q.get_single() else {
+        return;
+    };
+
+    let Ok(mut text) = text_q.get_mut(children[0]) else {
+        return;
+    };
+
+    let value = match progress.current {
+        Some(index) => {
+            let hint = &registries.tutorial.hints[index];
+            locale.format(&hint.text, &[&config.binding(hint.action).describe()])
+        }
+        None if progress.finished => locale.get("tutorial.exit").to_string(),
+        None => String::new(),
+    };
+
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
+}
+
+/// Leaves the tutorial for the first level and does not start it with new runs anymore,
+/// presses while a cutscene plays only skip the cutscene
+fn skip_tutorial(
+    mut game_state: ResMut<NextState<GameState>>,
+    player_q: Query<&ActionState<PlayerActions>, With<Player>>,
+    cutscene: Option<Res<ActiveCutscene>>
+) {
+    if cutscene.is_some() {
+        return;
+    }
+
+    if player_q.iter().any(|action_state| action_state.just_pressed(&PlayerActions::SkipTutorial)) {
+        game_state.set(GameState::LevelInitialization);
+    }
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::*;
+use bevy_math::{ ivec2, vec2 };
+use bevy_rapier2d::prelude::*;
+use itertools::Itertools;
+use leafwing_input_manager::{ action_state::ActionState, plugin::InputManagerSystem };
+
+use crate::{
+    actors::{ actor::Actor, player::{ Player, PlayerActions } },
+    bucket::Carrying,
+    constants::CHUNK_SIZE,
+    despawn_component,
+    interactables::interact,
+    registries::Registries,
+    score_export::RunStats,
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        materials::PhysicsType,
+        object::{ fill_objects, unfill_objects, Object, ObjectBundle },
+        pixel::Pixel,
+    },
+    state::GameState,
+};
+
+const VEHICLE_SIZE: IVec2 = IVec2::new(16, 12);
+const HULL_MATERIAL: &str = "granite";
+const CABIN_MATERIAL: &str = "stone";
+const HULL_THICKNESS: i32 = 2;
+const ENTER_DISTANCE: f32 = 24.0;
+// velocities are in world units per second like the ones of every other object
+const DRIVE_SPEED: f32 = 1.0;
+const DRIVE_ACCELERATION: f32 = 6.0;
+const THRUST: f32 = 3.0;
+const MAX_RISE_SPEED: f32 = 0.75;
+// pixels drilled out per second, the drill reaches that many pixels past the hull
+const DRILL_RATE: f32 = 96.0;
+const DRILL_REACH: i32 = 3;
+const SPAWN_PLACEMENT_ATTEMPTS: u32 = 64;
+
+pub struct VehiclePlugin;
+
+impl Plugin for VehiclePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, spawn_vehicle)
+            .add_systems(OnEnter(GameState::LevelInitialization), (
+                despawn_component::<Vehicle>,
+                |mut commands: Commands, mut player_q: Query<(Entity, &mut Actor, &Piloting)>| {
+                    for (entity, mut actor, piloting) in player_q.iter_mut() {
+                        leave_vehicle(&mut commands, entity, piloting, &mut actor, None);
+                    }
+                },
+            ))
+            .add_systems(Update, enter_vehicles.before(interact).run_if(in_state(GameState::Game)))
+            .add_systems(
+                PreUpdate,
+                block_pilot_input
+                    .after(InputManagerSystem::Update)
+                    .run_if(in_state(GameState::Game))
+            )
+            .add_systems(
+                FixedUpdate,
+                (drive_vehicles, drill, carry_pilots)
+                    .chain()
+                    .after(unfill_objects)
+                    .before(PhysicsSet::SyncBackend)
+                    .before(fill_objects)
+                    .run_if(in_state(GameState::Game))
+            );
+    }
+}
+
+/// Drill pod the player climbs into, it is driven with the run and jump actions
+/// and grinds through soft materials in the direction it is driven in
+#[derive(Component, Default)]
+pub struct Vehicle {
+    /// Drilled pixels that are yet to be removed
+    pub progress: f32,
+}
+
+/// Vehicle the player sits in, the player is hidden and protected from contact damage until getting out
+#[derive(Component)]
+pub struct Piloting {
+    pub vehicle: Entity,
+    /// Collision groups of the player before getting in, restored once the player gets out
+    collision_groups: CollisionGroups,
+}
+
+/// Player driving the vehicle
+#[derive(Component)]
+pub struct PilotedBy(pub Entity);
+
+fn is_hull(position: IVec2) -> bool {
+    position.x < HULL_THICKNESS ||
+        position.y < HULL_THICKNESS ||
+        position.x >= VEHICLE_SIZE.x - HULL_THICKNESS ||
+        position.y >= VEHICLE_SIZE.y - HULL_THICKNESS
+}
+
+/// Powders and static pixels tagged as soft
+fn is_drillable(pixel: &Pixel) -> bool {
+    match pixel.physics_type {
+        PhysicsType::Powder => true,
+        PhysicsType::Static => pixel.material.tags.contains("soft"),
+        _ => false,
+    }
+}
+
+fn spawn_vehicle(
+    mut commands: Commands,
+    chunk_manager: Res<ChunkManager>,
+    registries: Res<Registries>
+) {
+    let hull = &registries.materials[HULL_MATERIAL];
+    let cabin = &registries.materials[CABIN_MATERIAL];
+
+    let pixels = (0..VEHICLE_SIZE.y)
+        .cartesian_product(0..VEHICLE_SIZE.x)
+        .map(|(y, x)| Some(Pixel::from(if is_hull(ivec2(x, y)) { hull } else { cabin })))
+        .collect_vec();
+
+    let Ok(object) = Object::from_pixels(pixels, VEHICLE_SIZE) else {
+        return;
+    };
+    let Ok(collider) = object.create_collider() else {
+        return;
+    };
+
+    // vehicle is parked on the other side of the spawn than the bucket
+    let Some(position) = (0..SPAWN_PLACEMENT_ATTEMPTS)
+        .map(|attempt| {
+            ivec2(-16 - VEHICLE_SIZE.x - (attempt as i32) % 8 * 4, 4 + (attempt as i32) / 8 * 4)
+        })
+        .find(|position| {
+            (0..VEHICLE_SIZE.x)
+                .cartesian_product(0..VEHICLE_SIZE.y)
+                .all(|(x, y)| {
+                    chunk_manager
+                        .get(*position + ivec2(x, y))
+                        .is_ok_and(|pixel| pixel.is_empty())
+                })
+        }) else {
+        return;
+    };
+
+    commands.spawn((
+        Name::new("Vehicle"),
+        Vehicle::default(),
+        LockedAxes::ROTATION_LOCKED,
+        ObjectBundle {
+            object,
+            collider,
+            transform: TransformBundle {
+                local: Transform::from_translation(
+                    ((position.as_vec2() + VEHICLE_SIZE.as_vec2() / 2.0) / (CHUNK_SIZE as f32)).extend(0.0)
+                ),
+                ..Default::default()
+            },
+            mass_properties: ColliderMassProperties::Density(2.0),
+            ..Default::default()
+        },
+    ));
+}
+
+/// Puts the player on top of the vehicle at the position in pixels, or leaves the player where it is without one
+fn leave_vehicle(
+    commands: &mut Commands,
+    player: Entity,
+    piloting: &Piloting,
+    actor: &mut Actor,
+    vehicle_position: Option<Vec2>
+) {
+    if let Some(position) = vehicle_position {
+        actor.position = vec2(
+            position.x - actor.size.x / 2.0,
+            position.y + (VEHICLE_SIZE.y as f32) / 2.0 + 1.0
+        );
+    }
+
+    commands
+        .entity(player)
+        .remove::<Piloting>()
+        .insert((piloting.collision_groups, Visibility::Inherited));
+
+    // vehicle might be gone already, e.g. broken apart
+    if let Some(mut vehicle) = commands.get_entity(piloting.vehicle) {
+        vehicle.remove::<PilotedBy>();
+    }
+}
+
+/// Interaction gets into the closest free vehicle or out of the driven one, before anything else takes the press
+#[allow(clippy::type_complexity)]
+fn enter_vehicles(
+    mut commands: Commands,
+    mut player_q: Query<
+        (
+            Entity,
+            &mut Actor,
+            &mut Velocity,
+            &CollisionGroups,
+            &mut ActionState<PlayerActions>,
+            Option<&Piloting>,
+        ),
+        (With<Player>, Without<Carrying>)
+    >,
+    vehicle_q: Query<(Entity, &Transform, Has<PilotedBy>), With<Vehicle>>
+) {
+    for (entity, mut actor, mut velocity, collision_groups, mut action_state, piloting) in player_q.iter_mut() {
+        if !action_state.just_pressed(&PlayerActions::Interaction) {
+            continue;
+        }
+
+        if let Some(piloting) = piloting {
+            action_state.consume(&PlayerActions::Interaction);
+
+            let vehicle_position = vehicle_q
+                .get(piloting.vehicle)
+                .ok()
+                .map(|(_, transform, _)| transform.translation.xy() * (CHUNK_SIZE as f32));
+
+            leave_vehicle(&mut commands, entity, piloting, &mut actor, vehicle_position);
+            continue;
+        }
+
+        let player_position = actor.position + actor.size / 2.0;
+
+        let Some((vehicle, _)) = vehicle_q
+            .iter()
+            .filter(|(_, _, piloted)| !piloted)
+            .map(|(vehicle, transform, _)| {
+                (vehicle, (transform.translation.xy() * (CHUNK_SIZE as f32)).distance(player_position))
+            })
+            .filter(|(_, distance)| *distance < ENTER_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b)) else {
+            continue;
+        };
+
+        action_state.consume(&PlayerActions::Interaction);
+
+        velocity.linvel = Vec2::ZERO;
+        actor.carried = Vec2::ZERO;
+
+        commands.entity(entity).insert((
+            Piloting {
+                vehicle,
+                collision_groups: *collision_groups,
+            },
+            CollisionGroups::new(collision_groups.memberships, Group::NONE),
+            Visibility::Hidden,
+        ));
+        commands.entity(vehicle).insert(PilotedBy(entity));
+    }
+}
+
+/// Pilots can not attack or move on their own, only getting out is left unblocked,
+/// values of the actions are still updated and used to drive
+fn block_pilot_input(mut player_q: Query<&mut ActionState<PlayerActions>, With<Piloting>>) {
+    for mut action_state in player_q.iter_mut() {
+        for action in action_state.keys() {
+            if action != PlayerActions::Interaction {
+                action_state.consume(&action);
+            }
+        }
+    }
+}
+
+fn drive_vehicles(
+    mut commands: Commands,
+    mut vehicle_q: Query<(Entity, &mut Velocity, &mut Sleeping, &PilotedBy), With<Vehicle>>,
+    player_q: Query<&ActionState<PlayerActions>, With<Player>>,
+    time: Res<Time>
+) {
+    for (entity, mut velocity, mut sleeping, piloted_by) in vehicle_q.iter_mut() {
+        let Ok(action_state) = player_q.get(piloted_by.0) else {
+            commands.entity(entity).remove::<PilotedBy>();
+            continue;
+        };
+
+        sleeping.sleeping = false;
+
+        let target = action_state.clamped_value(&PlayerActions::Run) * DRIVE_SPEED;
+        velocity.linvel.x +=
+            (target - velocity.linvel.x) * (DRIVE_ACCELERATION * time.delta_seconds()).min(1.0);
+
+        if action_state.value(&PlayerActions::Jump) > 0.0 {
+            velocity.linvel.y = (velocity.linvel.y + THRUST * time.delta_seconds()).min(
+                MAX_RISE_SPEED
+            );
+        }
+    }
+}
+
+/// Grinds through drillable pixels next to the side of the hull the vehicle is driven towards,
+/// crouching drills downwards
+fn drill(
+    mut vehicle_q: Query<(&mut Vehicle, &Transform, &PilotedBy)>,
+    player_q: Query<&ActionState<PlayerActions>, With<Player>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    mut run_stats: ResMut<RunStats>,
+    time: Res<Time>
+) {
+    for (mut vehicle, transform, piloted_by) in vehicle_q.iter_mut() {
+        let Ok(action_state) = player_q.get(piloted_by.0) else {
+            continue;
+        };
+
+        let direction = ivec2(
+            action_state.clamped_value(&PlayerActions::Run).round() as i32,
+            if action_state.value(&PlayerActions::Crouch) > 0.0 {
+                -1
+            } else if action_state.value(&PlayerActions::Jump) > 0.0 {
+                1
+            } else {
+                0
+            }
+        );
+
+        let center = (transform.translation.xy() * (CHUNK_SIZE as f32)).round().as_ivec2();
+        let half_size = VEHICLE_SIZE / 2;
+        let min = -half_size + direction.min(IVec2::ZERO) * DRILL_REACH;
+        let max = half_size + direction.max(IVec2::ZERO) * DRILL_REACH;
+
+        let mut pixels = (min.x..max.x)
+            .cartesian_product(min.y..max.y)
+            .map(|(x, y)| ivec2(x, y))
+            .filter(|offset| {
+                offset.x < -half_size.x ||
+                    offset.y < -half_size.y ||
+                    offset.x >= half_size.x ||
+                    offset.y >= half_size.y
+            })
+            .map(|offset| center + offset)
+            .filter(|position| chunk_manager.get(*position).is_ok_and(is_drillable))
+            .collect_vec();
+
+        // progress is not saved up while there is nothing to drill
+        if pixels.is_empty() {
+            vehicle.progress = 0.0;
+            continue;
+        }
+
+        vehicle.progress += DRILL_RATE * time.delta_seconds();
+
+        pixels.sort_by_key(|position| (*position - center).length_squared());
+
+        let clock = chunk_manager.clock();
+
+        for position in pixels.into_iter().take(vehicle.progress.floor() as usize) {
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            *pixel = Pixel::default().with_clock(clock);
+            vehicle.progress -= 1.0;
+            run_stats.pixels_destroyed += 1;
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+    }
+}
+
+/// Keeps pilots in the middle of their vehicles
+fn carry_pilots(
+    mut commands: Commands,
+    mut player_q: Query<(Entity, &mut Actor, &mut Velocity, &Piloting), With<Player>>,
+    vehicle_q: Query<&Transform, With<Vehicle>>
+) {
+    for (entity, mut actor, mut velocity, piloting) in player_q.iter_mut() {
+        let Ok(transform) = vehicle_q.get(piloting.vehicle) else {
+            leave_vehicle(&mut commands, entity, piloting, &mut actor, None);
+            continue;
+        };
+
+        actor.position = transform.translation.xy() * (CHUNK_SIZE as f32) - actor.size / 2.0;
+        actor.carried = Vec2::ZERO;
+        velocity.linvel = Vec2::ZERO;
+    }
+}
@@ -0,0 +1,227 @@
+use std::sync::{ atomic::{ AtomicBool, Ordering }, Arc };
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{ Indices, PrimitiveTopology },
+        render_asset::RenderAssetUsages,
+        render_resource::{ CachedPipelineState, PipelineCache, PipelineCacheError },
+        view::RenderLayers,
+        Render,
+        RenderApp,
+        RenderSet,
+    },
+    sprite::MaterialMesh2dBundle,
+};
+
+use crate::{
+    actors::player::{ ATLAS_COLUMNS, ATLAS_ROWS },
+    assets::SpriteAssetCollection,
+    camera::{ TrackingCamera, ACTOR_RENDER_LAYER, LIGHTING_RENDER_LAYER, PARTICLE_RENDER_LAYER },
+    despawn_component,
+    postprocessing::light_propagate::LightPropagationSettings,
+    simulation::{ emission::GlowTexture, particle::Particle },
+    state::GameState,
+};
+
+// frames the effects have to be drawn for before the level may start
+const WARMUP_FRAMES: u32 = 3;
+// level starts anyway if some pipeline never finishes compiling
+const WARMUP_TIMEOUT: f32 = 5.0;
+// effects are drawn far below a pixel, pipelines get specialized without anything showing up
+const WARMUP_SCALE: f32 = 1e-4;
+
+pub struct WarmupPlugin;
+
+impl Plugin for WarmupPlugin {
+    fn build(&self, app: &mut App) {
+        let pipelines_ready = PipelinesReady::default();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(pipelines_ready.clone())
+                .add_systems(Render, check_pipelines.in_set(RenderSet::Cleanup));
+        } else {
+            // nothing to compile without a renderer
+            pipelines_ready.0.store(true, Ordering::Relaxed);
+        }
+
+        app.insert_resource(pipelines_ready)
+            .init_resource::<Warmup>()
+            .add_systems(OnExit(GameState::LoadingAssets), |mut commands: Commands| {
+                commands.init_resource::<AtlasLayouts>();
+            })
+            .add_systems(OnEnter(GameState::Setup), spawn_warmup_effects)
+            .add_systems(
+                Update,
+                tick_warmup.run_if(
+                    in_state(GameState::LevelInitialization).or_else(in_state(GameState::Splash))
+                )
+            )
+            .add_systems(OnEnter(GameState::Game), despawn_component::<WarmupEffect>);
+    }
+}
+
+/// Atlas layouts shared by every sprite sheet of the same kind instead of being created on spawn
+#[derive(Resource)]
+pub struct AtlasLayouts {
+    pub player: Handle<TextureAtlasLayout>,
+    pub attack: Handle<TextureAtlasLayout>,
+    pub portal: Handle<TextureAtlasLayout>,
+    pub loading: Handle<TextureAtlasLayout>,
+}
+
+impl FromWorld for AtlasLayouts {
+    fn from_world(world: &mut World) -> Self {
+        let mut layouts = world.resource_mut::<Assets<TextureAtlasLayout>>();
+        let mut grid = |columns: usize, rows: usize| {
+            layouts.add(TextureAtlasLayout::from_grid(Vec2::new(48.0, 48.0), columns, rows, None, None))
+        };
+
+        Self {
+            player: grid(ATLAS_COLUMNS, ATLAS_ROWS),
+            attack: grid(5, 1),
+            portal: grid(8, 3),
+            loading: grid(6, 1),
+        }
+    }
+}
+
+/// Set by the render world while no pipeline is waiting to be compiled
+#[derive(Resource, Clone, Default)]
+pub struct PipelinesReady(Arc<AtomicBool>);
+
+#[derive(Resource, Default)]
+pub struct Warmup {
+    pub frames: u32,
+    pub elapsed: f32,
+}
+
+/// Hidden copy of an effect that is otherwise first drawn in the middle of gameplay
+#[derive(Component)]
+pub struct WarmupEffect;
+
+/// Level is held on the splash screen until pipelines of the warm-up effects are compiled
+pub fn warmup_finished(warmup: Res<Warmup>, pipelines_ready: Res<PipelinesReady>) -> bool {
+    if warmup.frames < WARMUP_FRAMES {
+        return false;
+    }
+
+    pipelines_ready.0.load(Ordering::Relaxed) || warmup.elapsed >= WARMUP_TIMEOUT
+}
+
+fn check_pipelines(pipeline_cache: Res<PipelineCache>, pipelines_ready: Res<PipelinesReady>) {
+    let pending = pipeline_cache.pipelines().any(|pipeline| {
+        matches!(
+            pipeline.state,
+            CachedPipelineState::Queued |
+                CachedPipelineState::Creating(_) |
+                CachedPipelineState::Err(PipelineCacheError::ShaderNotLoaded(_))
+        )
+    });
+
+    pipelines_ready.0.store(!pending, Ordering::Relaxed);
+}
+
+fn tick_warmup(mut warmup: ResMut<Warmup>, time: Res<Time>) {
+    warmup.frames += 1;
+    warmup.elapsed += time.delta_seconds();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_warmup_effects(
+    mut commands: Commands,
+    mut warmup: ResMut<Warmup>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    camera_q: Query<Entity, With<TrackingCamera>>,
+    lighting_camera_q: Query<Entity, With<LightPropagationSettings>>,
+    layouts: Res<AtlasLayouts>,
+    sprites: Res<SpriteAssetCollection>,
+    glow: Res<GlowTexture>
+) {
+    *warmup = Warmup::default();
+
+    let (Ok(camera), Ok(lighting_camera)) = (camera_q.get_single(), lighting_camera_q.get_single()) else {
+        return;
+    };
+
+    // same vertex layout as the rope, otherwise a different pipeline gets specialized
+    let quad = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]))
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [-1.0, -1.0, 0.0], [1.0, -1.0, 0.0]]
+        )
+        .with_inserted_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]]
+        );
+
+    // half way through the view range of the camera they are attached to,
+    // cameras are stacked on top of each other so effects have to be attached to each of them
+    let transform = Transform {
+        translation: Vec3::new(0.0, 0.0, -500.0),
+        scale: Vec3::splat(WARMUP_SCALE),
+        ..Default::default()
+    };
+
+    commands.entity(camera).with_children(|parent| {
+        parent.spawn((
+            Name::new("Warm-up sprite sheet"),
+            WarmupEffect,
+            SpriteSheetBundle {
+                texture: sprites.attack.clone(),
+                atlas: TextureAtlas {
+                    layout: layouts.attack.clone(),
+                    index: 0,
+                },
+                transform,
+                ..Default::default()
+            },
+            RenderLayers::layer(ACTOR_RENDER_LAYER),
+        ));
+
+        parent.spawn((
+            Name::new("Warm-up rope"),
+            WarmupEffect,
+            MaterialMesh2dBundle {
+                mesh: meshes.add(quad).into(),
+                material: materials.add(ColorMaterial {
+                    texture: Some(sprites.rope.clone()),
+                    ..Default::default()
+                }),
+                transform,
+                ..Default::default()
+            },
+            RenderLayers::layer(ACTOR_RENDER_LAYER),
+        ));
+
+        // untextured sprite with a particle is drawn by the instanced particle pipeline
+        parent.spawn((
+            Name::new("Warm-up particle"),
+            WarmupEffect,
+            Sprite {
+                color: Color::WHITE,
+                custom_size: Some(Vec2::ONE),
+                ..Default::default()
+            },
+            SpatialBundle::from_transform(transform),
+            Particle::default(),
+            RenderLayers::layer(PARTICLE_RENDER_LAYER),
+        ));
+    });
+
+    commands.entity(lighting_camera).with_children(|parent| {
+        parent.spawn((
+            Name::new("Warm-up light"),
+            WarmupEffect,
+            SpriteBundle {
+                texture: glow.0.clone(),
+                transform,
+                ..Default::default()
+            },
+            RenderLayers::layer(LIGHTING_RENDER_LAYER),
+        ));
+    });
+}
@@ -0,0 +1,210 @@
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_math::{ ivec2, vec2 };
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    constants::{ CHUNK_SIZE, PARTICLE_Z },
+    generation::{ level::WeatherPattern, LevelData },
+    registries::Registries,
+    simulation::{
+        chunk::ChunkState,
+        chunk_manager::ChunkManager,
+        particle::{ Particle, ParticleBundle, Particles },
+        pixel::Pixel,
+        speed::run_simulation_ticks,
+    },
+    state::GameState,
+};
+
+// pixels fall from a few rows below the top edge, so they do not leave the loaded area right away
+const SPAWN_DEPTH: i32 = 4;
+// share of the difference between current and wind velocity that is applied per second
+const WIND_RESPONSE: f32 = 2.0;
+// initial speed of the falling pixels in pixels per frame
+const FALL_VELOCITY: f32 = 0.5;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, setup_weather)
+            .add_systems(
+                Update,
+                (advance_weather, spawn_weather, apply_wind, despawn_stray_weather)
+                    .chain()
+                    .before(run_simulation_ticks)
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<WeatherState>))
+            );
+    }
+}
+
+/// Current weather of the level, absent on levels without any
+#[derive(Resource)]
+pub struct WeatherState {
+    pub patterns: Vec<WeatherPattern>,
+    pub pattern: usize,
+    /// Seconds since the current pattern has started
+    pub elapsed: f32,
+    /// From 0 for clear sky to 1 for the pattern at its strongest
+    pub intensity: f32,
+    /// Fraction of a pixel left over from previous frames
+    pub accumulated: f32,
+}
+
+/// Falling pixel that was spawned by the weather and is carried by wind
+#[derive(Component)]
+pub struct WeatherParticle;
+
+impl WeatherPattern {
+    fn period(&self) -> f32 {
+        self.ramp * 2.0 + self.duration + self.pause
+    }
+
+    /// Intensity ramps up, stays at full strength for the duration, ramps down and pauses
+    fn intensity(&self, elapsed: f32) -> f32 {
+        let ramp = self.ramp.max(f32::EPSILON);
+
+        if elapsed < self.ramp {
+            elapsed / ramp
+        } else if elapsed < self.ramp + self.duration {
+            1.0
+        } else {
+            (1.0 - (elapsed - self.ramp - self.duration) / ramp).max(0.0)
+        }
+    }
+}
+
+fn setup_weather(mut commands: Commands, level_data: Res<LevelData>) {
+    if level_data.0.weather.is_empty() {
+        commands.remove_resource::<WeatherState>();
+        return;
+    }
+
+    commands.insert_resource(WeatherState {
+        patterns: level_data.0.weather.clone(),
+        pattern: 0,
+        elapsed: 0.0,
+        intensity: 0.0,
+        accumulated: 0.0,
+    });
+}
+
+fn advance_weather(mut state: ResMut<WeatherState>, time: Res<Time>) {
+    state.elapsed += time.delta_seconds();
+
+    let period = state.patterns[state.pattern].period();
+
+    if state.elapsed >= period {
+        state.elapsed -= period;
+        state.pattern = (state.pattern + 1) % state.patterns.len();
+    }
+
+    state.intensity = state.patterns[state.pattern].intensity(state.elapsed);
+}
+
+fn spawn_weather(
+    mut particles: Particles,
+    mut state: ResMut<WeatherState>,
+    chunk_manager: Res<ChunkManager>,
+    registries: Res<Registries>,
+    time: Res<Time>
+) {
+    let pattern = &state.patterns[state.pattern];
+
+    let Some(material) = registries.materials.get(&pattern.material_id) else {
+        return;
+    };
+
+    // highest loaded chunk of every column
+    let mut columns: HashMap<i32, i32> = HashMap::new();
+    for (position, (_, chunk)) in chunk_manager.chunks.iter() {
+        if chunk.state != ChunkState::Active && chunk.state != ChunkState::Sleeping {
+            continue;
+        }
+
+        let top = columns.entry(position.x).or_insert(position.y);
+        *top = (*top).max(position.y);
+    }
+
+    if columns.is_empty() {
+        return;
+    }
+
+    let wind = pattern.wind;
+    let accumulated =
+        state.accumulated +
+        pattern.rate * state.intensity * (columns.len() as f32) * time.delta_seconds();
+    let count = accumulated.floor();
+    state.accumulated = accumulated - count;
+
+    let columns = columns.into_iter().collect::<Vec<_>>();
+
+    for _ in 0..count as u32 {
+        let (x, y) = columns[fastrand::usize(..columns.len())];
+        let position = ivec2(
+            x * CHUNK_SIZE + fastrand::i32(0..CHUNK_SIZE),
+            (y + 1) * CHUNK_SIZE - 1 - fastrand::i32(0..SPAWN_DEPTH)
+        );
+
+        // only open sky produces weather, pixels are never spawned inside of the terrain
+        if !chunk_manager.get(position).is_ok_and(|pixel| pixel.is_empty()) {
+            continue;
+        }
+
+        let pixel = Pixel::from(material);
+
+        particles
+            .spawn(ParticleBundle {
+                sprite: SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba_u8(
+                            pixel.color[0],
+                            pixel.color[1],
+                            pixel.color[2],
+                            pixel.color[3]
+                        ),
+                        custom_size: Some(Vec2::ONE / (CHUNK_SIZE as f32)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(
+                        (position.as_vec2() / (CHUNK_SIZE as f32)).extend(PARTICLE_Z)
+                    ),
+                    ..Default::default()
+                },
+                velocity: Velocity::linear(vec2(wind, -FALL_VELOCITY) / (CHUNK_SIZE as f32)),
+                particle: Particle::new(pixel),
+                ..Default::default()
+            })
+            .insert(WeatherParticle);
+    }
+}
+
+fn apply_wind(
+    state: Res<WeatherState>,
+    mut particle_q: Query<&mut Velocity, With<WeatherParticle>>,
+    time: Res<Time>
+) {
+    let wind = state.patterns[state.pattern].wind / (CHUNK_SIZE as f32);
+    let response = (WIND_RESPONSE * time.delta_seconds()).min(1.0);
+
+    for mut velocity in particle_q.iter_mut() {
+        velocity.linvel.x += (wind - velocity.linvel.x) * response;
+    }
+}
+
+/// Particles are only simulated inside of loaded chunks, wind can carry them outside,
+/// released ones are deactivated right away so the simulation does not release them again
+fn despawn_stray_weather(
+    mut particles: Particles,
+    mut particle_q: Query<(Entity, &Transform, &mut Particle), With<WeatherParticle>>,
+    chunk_manager: Res<ChunkManager>
+) {
+    for (entity, transform, mut particle) in particle_q.iter_mut() {
+        let position = (transform.translation.xy() * (CHUNK_SIZE as f32)).floor().as_ivec2();
+
+        if particle.active && chunk_manager.get(position).is_err() {
+            particle.active = false;
+            particles.release(entity);
+        }
+    }
+}
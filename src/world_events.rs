@@ -0,0 +1,298 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use bevy_math::{ ivec2, vec2 };
+use itertools::Itertools;
+
+use crate::{
+    actors::{ actor::Actor, player::Player },
+    camera::CameraShake,
+    constants::CHUNK_SIZE,
+    escalation::spawn_warning,
+    generation::{ level::{ WorldEventKind, WorldEvents }, LevelData },
+    localization::Locale,
+    simulation::{
+        chunk_manager::ChunkManager,
+        dirty_rect::DirtyRects,
+        materials::PhysicsType,
+        pixel::Pixel,
+        speed::run_simulation_ticks,
+    },
+    state::GameState,
+    traps::find_surface,
+};
+
+// distance in pixels from a player that terrain crumbles and crevices open within
+const EVENT_RANGE: i32 = 160;
+const PATCH_RADIUS: Range<i32> = 3..8;
+// crevices never open right under the players
+const CREVICE_CLEARANCE: i32 = 24;
+const CREVICE_DEPTH: Range<i32> = 24..56;
+const CREVICE_WIDTH: i32 = 4;
+// random points tried for every crevice before giving up
+const CREVICE_ATTEMPTS: u32 = 8;
+// seconds the shake of an earthquake takes to build up and to calm down
+const EARTHQUAKE_RAMP: f32 = 1.0;
+// shake in pixels and seconds of the rumble of a cave-in
+const CAVE_IN_SHAKE: f32 = 2.0;
+const CAVE_IN_DURATION: f32 = 0.8;
+
+pub struct WorldEventsPlugin;
+
+impl Plugin for WorldEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnTransition { from: GameState::Splash, to: GameState::Game }, setup_world_events)
+            .add_systems(
+                Update,
+                (advance_world_events, crumble_terrain)
+                    .chain()
+                    .before(run_simulation_ticks)
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<WorldEventDirector>))
+            );
+    }
+}
+
+/// Schedules the world events of the level and runs the active one, absent on levels without any
+#[derive(Resource)]
+pub struct WorldEventDirector {
+    pub events: WorldEvents,
+    /// Counts down to the next event while none is active
+    pub timer: Timer,
+    pub active: Option<ActiveWorldEvent>,
+}
+
+pub struct ActiveWorldEvent {
+    pub kind: WorldEventKind,
+    /// Seconds since the event has started
+    pub elapsed: f32,
+    /// Fraction of a patch left over from previous frames
+    pub accumulated: f32,
+}
+
+impl WorldEventKind {
+    fn duration(&self) -> f32 {
+        match self {
+            WorldEventKind::Earthquake { duration, .. } => *duration,
+            WorldEventKind::CaveIn { .. } => CAVE_IN_DURATION,
+        }
+    }
+
+    /// Shake of the camera in pixels, earthquakes build up and calm down while cave-ins only rumble
+    fn shake(&self, elapsed: f32) -> f32 {
+        match self {
+            WorldEventKind::Earthquake { duration, strength, .. } => {
+                let ramp = (elapsed.min(duration - elapsed) / EARTHQUAKE_RAMP).clamp(0.0, 1.0);
+                strength * ramp
+            }
+            WorldEventKind::CaveIn { .. } => CAVE_IN_SHAKE * (1.0 - elapsed / CAVE_IN_DURATION).max(0.0),
+        }
+    }
+}
+
+fn next_event_timer(events: &WorldEvents) -> Timer {
+    let (min, max) = events.interval;
+
+    Timer::from_seconds(min + fastrand::f32() * (max - min).max(0.0), TimerMode::Once)
+}
+
+fn setup_world_events(mut commands: Commands, mut shake: ResMut<CameraShake>, level_data: Res<LevelData>) {
+    shake.0 = 0.0;
+
+    let Some(events) = level_data.0.events.as_ref().filter(|events| !events.kinds.is_empty()) else {
+        commands.remove_resource::<WorldEventDirector>();
+        return;
+    };
+
+    commands.insert_resource(WorldEventDirector {
+        timer: next_event_timer(events),
+        events: events.clone(),
+        active: None,
+    });
+}
+
+/// Starts a random event once the timer runs out, one-off effects are applied right away
+#[allow(clippy::too_many_arguments)]
+fn advance_world_events(
+    mut commands: Commands,
+    mut director: ResMut<WorldEventDirector>,
+    mut shake: ResMut<CameraShake>,
+    player_q: Query<&Actor, With<Player>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    locale: Res<Locale>,
+    time: Res<Time>
+) {
+    let director = &mut *director;
+
+    if director.active.is_none() {
+        if !director.timer.tick(time.delta()).finished() {
+            return;
+        }
+
+        let kind = director.events.kinds[fastrand::usize(..director.events.kinds.len())];
+        let players = player_q.iter().collect_vec();
+
+        match kind {
+            WorldEventKind::Earthquake { crevices, .. } => {
+                spawn_warning(&mut commands, locale.get("events.earthquake"));
+
+                for _ in 0..crevices {
+                    if let Some(player) = fastrand::choice(&players) {
+                        open_crevice(&mut chunk_manager, &mut dirty_rects, player);
+                    }
+                }
+            }
+            WorldEventKind::CaveIn { radius } => {
+                spawn_warning(&mut commands, locale.get("events.cave_in"));
+
+                // players standing under open sky are skipped
+                let ceilings = players
+                    .iter()
+                    .filter_map(|actor| {
+                        let head = (actor.position + actor.size * vec2(0.5, 1.0)).as_ivec2();
+                        find_surface(&chunk_manager, head, 1).map(|ceiling| ivec2(head.x, ceiling))
+                    })
+                    .collect_vec();
+
+                if let Some(ceiling) = fastrand::choice(ceilings) {
+                    crumble(&mut chunk_manager, &mut dirty_rects, ceiling + ivec2(0, radius / 2), radius);
+                }
+            }
+        }
+
+        director.active = Some(ActiveWorldEvent {
+            kind,
+            elapsed: 0.0,
+            accumulated: 0.0,
+        });
+    }
+
+    let Some(event) = director.active.as_mut() else {
+        return;
+    };
+
+    event.elapsed += time.delta_seconds();
+    shake.0 = event.kind.shake(event.elapsed);
+
+    if event.elapsed >= event.kind.duration() {
+        shake.0 = 0.0;
+        director.active = None;
+        director.timer = next_event_timer(&director.events);
+    }
+}
+
+/// Crumbles random patches of terrain around the players while an earthquake lasts
+fn crumble_terrain(
+    mut director: ResMut<WorldEventDirector>,
+    player_q: Query<&Actor, With<Player>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut dirty_rects: ResMut<DirtyRects>,
+    time: Res<Time>
+) {
+    let Some(event) = director.active.as_mut() else {
+        return;
+    };
+
+    let WorldEventKind::Earthquake { patches, .. } = event.kind else {
+        return;
+    };
+
+    let accumulated = event.accumulated + patches * time.delta_seconds();
+    let count = accumulated.floor();
+    event.accumulated = accumulated - count;
+
+    let players = player_q.iter().collect_vec();
+
+    for _ in 0..count as u32 {
+        let Some(player) = fastrand::choice(&players) else {
+            return;
+        };
+
+        let center =
+            player.position.as_ivec2() +
+            ivec2(fastrand::i32(-EVENT_RANGE..=EVENT_RANGE), fastrand::i32(-EVENT_RANGE..=EVENT_RANGE));
+
+        // patches start inside of the terrain, so the air around the players stays as it is
+        if !chunk_manager.get(center).is_ok_and(is_crumbly) {
+            continue;
+        }
+
+        crumble(&mut chunk_manager, &mut dirty_rects, center, fastrand::i32(PATCH_RADIUS));
+    }
+}
+
+/// Static pixels that can be dug out, indestructible ones have no durability
+fn is_crumbly(pixel: &Pixel) -> bool {
+    pixel.physics_type == PhysicsType::Static && pixel.durability.is_some()
+}
+
+/// Turns the terrain within the radius into powder that falls down
+fn crumble(chunk_manager: &mut ChunkManager, dirty_rects: &mut DirtyRects, center: IVec2, radius: i32) {
+    for (x, y) in (-radius..=radius).cartesian_product(-radius..=radius) {
+        if x * x + y * y > radius * radius {
+            continue;
+        }
+
+        let position = center + ivec2(x, y);
+
+        let Ok(pixel) = chunk_manager.get_mut(position) else {
+            continue;
+        };
+
+        if !is_crumbly(pixel) {
+            continue;
+        }
+
+        pixel.physics_type = PhysicsType::Powder;
+
+        dirty_rects.request_update(position);
+        dirty_rects.request_render(position);
+        dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+    }
+}
+
+/// Carves a jagged crack into the floor near the player, narrowing towards the bottom
+fn open_crevice(chunk_manager: &mut ChunkManager, dirty_rects: &mut DirtyRects, player: &Actor) {
+    let center = (player.position + player.size / 2.0).as_ivec2();
+
+    let floor = (0..CREVICE_ATTEMPTS).find_map(|_| {
+        let offset = fastrand::i32(CREVICE_CLEARANCE..=EVENT_RANGE) * (if fastrand::bool() { 1 } else { -1 });
+        let start = ivec2(center.x + offset, center.y);
+
+        if !chunk_manager.get(start).is_ok_and(|pixel| pixel.is_empty()) {
+            return None;
+        }
+
+        find_surface(chunk_manager, start, -1).map(|floor| ivec2(start.x, floor))
+    });
+
+    let Some(floor) = floor else {
+        return;
+    };
+
+    let depth = fastrand::i32(CREVICE_DEPTH);
+    let clock = chunk_manager.clock();
+    let mut x = floor.x;
+
+    for step in 0..depth {
+        x += fastrand::i32(-1..=1);
+
+        let width = (CREVICE_WIDTH * (depth - step)) / depth + 1;
+
+        for position in (x - width / 2..x - width / 2 + width).map(|x| ivec2(x, floor.y - step)) {
+            let Ok(pixel) = chunk_manager.get_mut(position) else {
+                continue;
+            };
+
+            if !is_crumbly(pixel) && pixel.physics_type != PhysicsType::Powder {
+                continue;
+            }
+
+            *pixel = Pixel::default().with_clock(clock);
+
+            dirty_rects.request_update(position);
+            dirty_rects.request_render(position);
+            dirty_rects.collider.insert(position.div_euclid(IVec2::splat(CHUNK_SIZE)));
+        }
+    }
+}
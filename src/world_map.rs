@@ -0,0 +1,290 @@
+use bevy::{
+    input::mouse::{ MouseMotion, MouseScrollUnit, MouseWheel },
+    prelude::*,
+    window::PrimaryWindow,
+};
+use leafwing_input_manager::plugin::ToggleActions;
+
+use crate::{
+    actors::player::{ Player, PlayerActions },
+    despawn_component,
+    generation::Exit,
+    minimap::{ ExploredChunks, Minimap, PointOfInterestKind, PointsOfInterest, MINIMAP_CHUNK_SIZE },
+    state::GameState,
+};
+
+const MIN_ZOOM: f32 = 1.0;
+const MAX_ZOOM: f32 = 8.0;
+const KEYBOARD_PAN_SPEED: f32 = 8.0;
+
+pub struct WorldMapPlugin;
+
+impl Plugin for WorldMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldMap>()
+            .add_systems(Update, toggle_world_map.run_if(in_state(GameState::Game)))
+            .add_systems(
+                Update,
+                (pan_world_map, update_world_map_layout)
+                    .chain()
+                    .run_if(in_state(GameState::Game).and_then(resource_exists::<WorldMapOpen>))
+            )
+            .add_systems(OnExit(GameState::Game), (
+                despawn_component::<UiWorldMap>,
+                close_world_map,
+            ));
+    }
+}
+
+/// Camera of the world map in chunk coordinates, zoom is amount of screen pixels per minimap pixel
+#[derive(Resource)]
+pub struct WorldMap {
+    pub center: Vec2,
+    pub zoom: f32,
+}
+
+impl Default for WorldMap {
+    fn default() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            zoom: 2.0,
+        }
+    }
+}
+
+/// Present while the map is shown, game is paused for the duration
+#[derive(Resource)]
+pub struct WorldMapOpen;
+
+#[derive(Component)]
+pub struct UiWorldMap;
+
+#[derive(Component)]
+pub struct UiWorldMapTexture;
+
+#[allow(clippy::too_many_arguments)]
+fn toggle_world_map(
+    mut commands: Commands,
+    mut world_map: ResMut<WorldMap>,
+    mut time: ResMut<Time<Virtual>>,
+    mut toggle_actions: ResMut<ToggleActions<PlayerActions>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    open: Option<Res<WorldMapOpen>>,
+    map_q: Query<Entity, With<UiWorldMap>>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    exit_q: Query<&GlobalTransform, With<Exit>>,
+    explored: Res<ExploredChunks>,
+    points_of_interest: Res<PointsOfInterest>,
+    minimap: Option<Res<Minimap>>
+) {
+    if !keys.just_pressed(KeyCode::KeyM) {
+        return;
+    }
+
+    if open.is_some() {
+        for entity in map_q.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        commands.remove_resource::<WorldMapOpen>();
+        set_paused(&mut time, &mut toggle_actions, false);
+        return;
+    }
+
+    let Some(minimap) = minimap else {
+        return;
+    };
+
+    if let Some(transform) = player_q.iter().next() {
+        world_map.center = transform.translation().xy();
+    }
+
+    commands.insert_resource(WorldMapOpen);
+    set_paused(&mut time, &mut toggle_actions, true);
+
+    // game is paused while map is open, so markers are placed only once
+    let mut markers = vec![];
+
+    for transform in player_q.iter() {
+        markers.push((transform.translation().xy(), Color::WHITE, 10.0));
+    }
+
+    if let Ok(transform) = exit_q.get_single() {
+        markers.push((transform.translation().xy(), Color::rgb_u8(0xb0, 0x6c, 0xff), 10.0));
+    }
+
+    for point in points_of_interest.iter() {
+        if !explored.contains(&point.position.floor().as_ivec2()) {
+            continue;
+        }
+
+        let color = match point.kind {
+            PointOfInterestKind::Shrine => Color::rgb_u8(0xff, 0xd7, 0x40),
+            PointOfInterestKind::Arena => Color::rgb_u8(0xe0, 0x4a, 0x3a),
+            PointOfInterestKind::Teleporter => Color::rgb_u8(0x73, 0xd9, 0xff),
+        };
+
+        markers.push((point.position, color, 8.0));
+    }
+
+    commands
+        .spawn((
+            Name::new("World map"),
+            UiWorldMap,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    UiWorldMapTexture,
+                    ImageBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        image: minimap.texture.clone().into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    for (position, color, size) in markers {
+                        let normalized = minimap.normalize(position);
+
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Percent(normalized.x * 100.0),
+                                top: Val::Percent(normalized.y * 100.0),
+                                width: Val::Px(size),
+                                height: Val::Px(size),
+                                margin: UiRect::all(Val::Px(-size / 2.0)),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        });
+                    }
+                });
+
+            parent.spawn(
+                TextBundle::from_section(
+                    "[M] close   [LMB] drag   [wheel] zoom",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    }
+                ).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..default()
+                })
+            );
+        });
+}
+
+fn pan_world_map(
+    mut world_map: ResMut<WorldMap>,
+    mut motion_evr: EventReader<MouseMotion>,
+    mut wheel_evr: EventReader<MouseWheel>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    minimap: Res<Minimap>
+) {
+    let zoom = world_map.zoom;
+    let chunk_pixels = zoom * (MINIMAP_CHUNK_SIZE as f32);
+
+    if buttons.pressed(MouseButton::Left) {
+        for event in motion_evr.read() {
+            world_map.center += Vec2::new(-event.delta.x, event.delta.y) / chunk_pixels;
+        }
+    } else {
+        motion_evr.clear();
+    }
+
+    let mut direction = Vec2::ZERO;
+    for (key, offset) in [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+        (KeyCode::ArrowUp, Vec2::Y),
+    ] {
+        if keys.pressed(key) {
+            direction += offset;
+        }
+    }
+
+    world_map.center += (direction * KEYBOARD_PAN_SPEED * time.delta_seconds()) / zoom;
+
+    for event in wheel_evr.read() {
+        let steps = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 32.0,
+        };
+
+        world_map.zoom = (world_map.zoom * (1.0 + steps * 0.1)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    world_map.center = world_map.center.clamp(
+        minimap.bounds.min.as_vec2(),
+        minimap.bounds.max.as_vec2()
+    );
+}
+
+fn update_world_map_layout(
+    mut texture_q: Query<&mut Style, With<UiWorldMapTexture>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    world_map: Res<WorldMap>,
+    minimap: Res<Minimap>
+) {
+    let (Ok(mut style), Ok(window)) = (texture_q.get_single_mut(), window_q.get_single()) else {
+        return;
+    };
+
+    let size = minimap.bounds.size().as_vec2() * (MINIMAP_CHUNK_SIZE as f32) * world_map.zoom;
+    let center = minimap.normalize(world_map.center) * size;
+
+    style.width = Val::Px(size.x);
+    style.height = Val::Px(size.y);
+    style.left = Val::Px(window.width() / 2.0 - center.x);
+    style.top = Val::Px(window.height() / 2.0 - center.y);
+}
+
+fn close_world_map(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    mut toggle_actions: ResMut<ToggleActions<PlayerActions>>,
+    open: Option<Res<WorldMapOpen>>
+) {
+    if open.is_some() {
+        commands.remove_resource::<WorldMapOpen>();
+        set_paused(&mut time, &mut toggle_actions, false);
+    }
+}
+
+fn set_paused(
+    time: &mut Time<Virtual>,
+    toggle_actions: &mut ToggleActions<PlayerActions>,
+    paused: bool
+) {
+    if paused {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+
+    toggle_actions.enabled = !paused;
+}